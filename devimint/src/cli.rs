@@ -1,16 +1,21 @@
-use std::ffi;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::str::FromStr as _;
 use std::time::Duration;
+use std::{ffi, iter};
 
 use anyhow::{anyhow, ensure, Context, Result};
 use clap::{Parser, Subcommand};
+use fedimint_core::invite_code::InviteCode;
 use fedimint_core::task::TaskGroup;
 use fedimint_core::util::write_overwrite_async;
+use fedimint_core::PeerId;
 use fedimint_logging::LOG_DEVIMINT;
 use rand::distributions::Alphanumeric;
 use rand::Rng as _;
+use serde::Serialize;
 use tokio::fs;
 use tokio::net::TcpStream;
 use tokio::time::Instant;
@@ -114,7 +119,158 @@ pub enum Cmd {
 #[derive(Subcommand)]
 pub enum RpcCmd {
     Wait,
-    Env,
+    Env {
+        /// Print the endpoints, invite codes, credentials and data dirs as a
+        /// JSON document for non-Rust test harnesses instead of shell exports
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Connection details of a running devimint environment, printed by `devimint
+/// env --json`
+#[derive(Debug, Serialize)]
+struct EnvDocument {
+    federation: FederationEnv,
+    bitcoind: BitcoindEnv,
+    lnd: LndEnv,
+    cln: ClnEnv,
+    gateways: GatewaysEnv,
+    esplora_url: Option<String>,
+    electrs_url: Option<String>,
+    faucet_url: Option<String>,
+    dirs: DirsEnv,
+    /// All variables of the env file, for anything not covered above
+    vars: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FederationEnv {
+    /// Only known once the federation is set up
+    invite_code: Option<String>,
+    federation_id: Option<String>,
+    /// API endpoint of every guardian by peer id
+    guardians: BTreeMap<PeerId, String>,
+    api_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BitcoindEnv {
+    rpc_url: Option<String>,
+    data_dir: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct LndEnv {
+    rpc_addr: Option<String>,
+    tls_cert: Option<String>,
+    macaroon: Option<String>,
+    data_dir: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ClnEnv {
+    rpc_socket: Option<String>,
+    data_dir: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct GatewaysEnv {
+    lnd_api: Option<String>,
+    ldk_api: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DirsEnv {
+    test_dir: Option<String>,
+    logs_dir: Option<String>,
+    client_dir: Option<String>,
+    ldk_dir: Option<String>,
+}
+
+impl EnvDocument {
+    /// Collects the document from the exports of the env file and the invite
+    /// codes written to the client dir
+    async fn read(env: &str) -> Result<Self> {
+        let vars = env
+            .lines()
+            .filter_map(|line| line.strip_prefix("export ")?.split_once('='))
+            .map(|(var, value)| (var.to_owned(), value.trim_matches('"').to_owned()))
+            .collect::<BTreeMap<_, _>>();
+        let var = |name: &str| vars.get(name).cloned();
+        let local_url =
+            |scheme: &str, port: &str| var(port).map(|port| format!("{scheme}://127.0.0.1:{port}"));
+
+        let mut invite_codes = vec![];
+        if let Some(client_dir) = var("FM_CLIENT_DIR") {
+            let client_dir = PathBuf::from(client_dir);
+            let fed_size = var("FM_FED_SIZE")
+                .and_then(|size| size.parse::<usize>().ok())
+                .unwrap_or_default();
+            for file in iter::once("invite-code".to_owned())
+                .chain((0..fed_size).map(|peer| format!("invite-code-{peer}")))
+            {
+                let path = client_dir.join(file);
+                if fs::try_exists(&path).await.unwrap_or(false) {
+                    let invite_code = fs::read_to_string(&path).await?;
+                    invite_codes.push(
+                        InviteCode::from_str(invite_code.trim()).with_context(|| {
+                            format!("Invalid invite code in {}", path.display())
+                        })?,
+                    );
+                }
+            }
+        }
+        let guardians = invite_codes
+            .iter()
+            .flat_map(InviteCode::peers)
+            .map(|(peer, url)| (peer, url.to_string()))
+            .collect();
+
+        Ok(Self {
+            federation: FederationEnv {
+                invite_code: invite_codes
+                    .first()
+                    .map(ToString::to_string)
+                    .or_else(|| var(FM_INVITE_CODE_ENV)),
+                federation_id: invite_codes
+                    .first()
+                    .map(|invite_code| invite_code.federation_id().to_string()),
+                guardians,
+                api_secret: var("FM_API_SECRET"),
+            },
+            bitcoind: BitcoindEnv {
+                rpc_url: var("FM_BITCOIN_RPC_URL"),
+                data_dir: var("FM_BTC_DIR"),
+            },
+            lnd: LndEnv {
+                rpc_addr: var("FM_LND_RPC_ADDR"),
+                tls_cert: var("FM_LND_TLS_CERT"),
+                macaroon: var("FM_LND_MACAROON"),
+                data_dir: var("FM_LND_DIR"),
+            },
+            cln: ClnEnv {
+                rpc_socket: var("FM_CLN_SOCKET"),
+                data_dir: var("FM_CLN_DIR"),
+            },
+            gateways: GatewaysEnv {
+                lnd_api: local_url("http", "FM_PORT_GW_LND"),
+                ldk_api: local_url("http", "FM_PORT_GW_LDK"),
+                password: var("FM_GATEWAY_PASSWORD"),
+            },
+            esplora_url: local_url("http", "FM_PORT_ESPLORA"),
+            electrs_url: local_url("tcp", "FM_PORT_ELECTRS"),
+            faucet_url: local_url("http", "FM_PORT_FAUCET"),
+            dirs: DirsEnv {
+                test_dir: var("FM_TEST_DIR"),
+                logs_dir: var("FM_LOGS_DIR"),
+                client_dir: var("FM_CLIENT_DIR"),
+                ldk_dir: var("FM_LDK_DIR"),
+            },
+            vars,
+        })
+    }
 }
 
 pub async fn setup(arg: CommonArgs) -> Result<(ProcessManager, TaskGroup)> {
@@ -374,7 +530,7 @@ pub async fn exec_user_command(exec: Vec<ffi::OsString>) -> Result<(), anyhow::E
 pub async fn rpc_command(rpc: RpcCmd, common: CommonArgs) -> Result<()> {
     fedimint_logging::TracingSetup::default().init()?;
     match rpc {
-        RpcCmd::Env => {
+        RpcCmd::Env { json } => {
             let env_file = common.test_dir().join("env");
             poll("env file", || async {
                 if fs::try_exists(&env_file)
@@ -389,7 +545,12 @@ pub async fn rpc_command(rpc: RpcCmd, common: CommonArgs) -> Result<()> {
             })
             .await?;
             let env = fs::read_to_string(&env_file).await?;
-            print!("{env}");
+            if json {
+                let document = EnvDocument::read(&env).await?;
+                println!("{}", serde_json::to_string_pretty(&document)?);
+            } else {
+                print!("{env}");
+            }
             Ok(())
         }
         RpcCmd::Wait => {
@@ -29,6 +29,7 @@
 use tonic_lnd::Client as LndClient;
 use tracing::{debug, error, info, trace, warn};
 
+use crate::mining::MiningController;
 use crate::util::{poll, poll_with_timeout, ProcessHandle, ProcessManager};
 use crate::vars::utf8;
 use crate::version_constants::{VERSION_0_4_0_ALPHA, VERSION_0_5_0_ALPHA};
@@ -91,7 +92,7 @@ pub async fn new(processmgr: &ProcessManager, skip_setup: bool) -> Result<Self>
         })
     }
 
-    fn new_bitcoin_rpc(
+    pub(crate) fn new_bitcoin_rpc(
         url: &str,
         auth: bitcoincore_rpc::Auth,
     ) -> anyhow::Result<bitcoincore_rpc::Client> {
@@ -173,6 +174,13 @@ pub async fn poll_ready(&self) -> anyhow::Result<()> {
         .await
     }
 
+    /// Controller for mining blocks on demand or on a schedule
+    pub async fn mining_controller(&self) -> anyhow::Result<MiningController> {
+        Ok(MiningController::new(
+            self.wallet_client().await?.client.clone(),
+        ))
+    }
+
     /// Client that can has wallet initialized, can generate internal addresses
     /// and send funds
     pub async fn wallet_client(&self) -> anyhow::Result<&Self> {
@@ -77,6 +77,7 @@ pub fn attach_default_module_init_params(
                 finality_delay,
                 client_default_bitcoin_rpc: default_esplora_server(network),
                 fee_consensus: fedimint_wallet_client::config::FeeConsensus::default(),
+                peg_in_confirmation_tiers: vec![],
             },
         },
     );
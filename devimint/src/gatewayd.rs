@@ -9,7 +9,7 @@
 use fedimint_core::envs::{is_env_var_set, FM_DEVIMINT_DISABLE_MODULE_LNV2_ENV};
 use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::util::{backoff_util, retry};
-use fedimint_core::{Amount, BitcoinAmountOrAll};
+use fedimint_core::{Amount, BitcoinAmountOrAll, Sats};
 use fedimint_ln_server::common::lightning_invoice::Bolt11Invoice;
 use fedimint_lnv2_common::gateway_api::PaymentFee;
 use fedimint_testing::gateway::LightningNodeType;
@@ -478,9 +478,9 @@ pub async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>> {
                     remote_pubkey: remote_pubkey
                         .parse()
                         .expect("Lightning node returned invalid remote channel pubkey"),
-                    channel_size_sats,
-                    outbound_liquidity_sats,
-                    inbound_liquidity_sats,
+                    channel_size_sats: Sats(channel_size_sats),
+                    outbound_liquidity_sats: Sats(outbound_liquidity_sats),
+                    inbound_liquidity_sats: Sats(inbound_liquidity_sats),
                     short_channel_id,
                 })
             })
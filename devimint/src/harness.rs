@@ -0,0 +1,118 @@
+//! Integration test harness for crates outside of this repository
+//!
+//! Module authors can test their modules against a real dev federation
+//! without writing the setup themselves. Add a test target with
+//! `harness = false` and `devimint` as a dev-dependency, then declare the
+//! tests with [`crate::test!`]:
+//!
+//! ```ignore
+//! devimint::test! {
+//!     async fn reissues_notes(fed, gw, client) {
+//!         let notes = devimint::cmd!(client, "spend", "1000msat").out_json().await?;
+//!         // ...
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! All tests of a target share one federation and LND gateway, but each gets
+//! its own client funded with [`HARNESS_CLIENT_FUNDING_SATS`]. Like every
+//! other devimint test, the target has to run in the devimint environment,
+//! e.g. `devimint dev-fed --exec cargo test --test <name>`.
+
+use fedimint_logging::LOG_DEVIMINT;
+use futures::future::BoxFuture;
+use tracing::{error, info};
+
+use crate::federation::{Client, Federation};
+use crate::{run_devfed_test, Gatewayd};
+
+/// Re-exported so that tests don't need to depend on `anyhow` themselves
+pub use anyhow::Result;
+
+/// Amount each test's client is pegged-in before the test starts
+pub const HARNESS_CLIENT_FUNDING_SATS: u64 = 100_000;
+
+/// A test declared with [`crate::test!`]
+pub struct HarnessTest {
+    pub name: &'static str,
+    pub run: fn(Federation, Gatewayd, Client) -> BoxFuture<'static, Result<()>>,
+}
+
+/// Starts a dev federation and runs `tests` one after another, skipping those
+/// whose name doesn't contain the first non-flag command line argument
+pub fn run_harness_tests(tests: &[HarnessTest]) -> Result<()> {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let tests = tests
+        .iter()
+        .filter(|test| filter.as_ref().map_or(true, |f| test.name.contains(f)))
+        .collect::<Vec<_>>();
+
+    if tests.is_empty() {
+        return Ok(());
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run_devfed_test(|dev_fed, _process_mgr| async move {
+            let fed = dev_fed.fed().await?.clone();
+            let gw = dev_fed.gw_lnd_registered().await?.clone();
+
+            let mut failed = vec![];
+            for test in tests {
+                info!(target: LOG_DEVIMINT, name = test.name, "Running harness test");
+
+                let client = fed.new_joined_client(test.name).await?;
+                fed.pegin_client(HARNESS_CLIENT_FUNDING_SATS, &client)
+                    .await?;
+                client.use_gateway(&gw).await?;
+
+                match (test.run)(fed.clone(), gw.clone(), client).await {
+                    Ok(()) => info!(target: LOG_DEVIMINT, name = test.name, "Harness test passed"),
+                    Err(e) => {
+                        error!(target: LOG_DEVIMINT, name = test.name, ?e, "Harness test failed");
+                        failed.push(test.name);
+                    }
+                }
+            }
+
+            anyhow::ensure!(failed.is_empty(), "Failed harness tests: {failed:?}");
+
+            Ok(())
+        }))
+}
+
+/// Declares integration tests run against a dev federation, see
+/// [`crate::harness`]
+///
+/// Each test is an `async fn` taking the names its
+/// [`crate::federation::Federation`], LND [`crate::Gatewayd`] and funded
+/// [`crate::federation::Client`] are bound to, returning
+/// [`crate::harness::Result`]. The macro generates the target's `main`.
+#[macro_export]
+macro_rules! test {
+    ($(
+        $(#[$attr:meta])*
+        async fn $name:ident($fed:ident, $gw:ident, $client:ident) $body:block
+    )+) => {
+        $(
+            $(#[$attr])*
+            #[allow(unused_variables)]
+            async fn $name(
+                $fed: $crate::federation::Federation,
+                $gw: $crate::Gatewayd,
+                $client: $crate::federation::Client,
+            ) -> $crate::harness::Result<()> $body
+        )+
+
+        fn main() -> $crate::harness::Result<()> {
+            $crate::harness::run_harness_tests(&[$(
+                $crate::harness::HarnessTest {
+                    name: stringify!($name),
+                    run: |fed, gw, client| ::std::boxed::Box::pin($name(fed, gw, client)),
+                },
+            )+])
+        }
+    };
+}
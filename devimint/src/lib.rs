@@ -35,6 +35,8 @@
 pub mod external;
 pub mod federation;
 pub mod gatewayd;
+pub mod harness;
+pub mod mining;
 pub mod tests;
 pub mod util;
 pub mod vars;
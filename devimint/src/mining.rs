@@ -0,0 +1,134 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bitcoincore_rpc::RpcApi;
+use fedimint_core::time::duration_since_epoch;
+use fedimint_logging::LOG_DEVIMINT;
+use tokio::task::{spawn_blocking, JoinHandle};
+use tracing::{debug, warn};
+
+use crate::envs::FM_BITCOIN_RPC_URL_ENV;
+use crate::external::Bitcoind;
+
+/// Mines regtest blocks on demand or on a schedule, optionally with the chain's
+/// clock warped into the future, so tests that depend on confirmations or
+/// timeouts don't need to shell out to `bitcoin-cli`.
+#[derive(Clone)]
+pub struct MiningController {
+    client: Arc<bitcoincore_rpc::Client>,
+    /// How far the clock of bitcoind is set ahead of the real time
+    time_warp: Arc<Mutex<Duration>>,
+}
+
+impl MiningController {
+    pub fn new(client: Arc<bitcoincore_rpc::Client>) -> Self {
+        Self {
+            client,
+            time_warp: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    /// Connects to the bitcoind of an already running devimint environment,
+    /// e.g. from a separate test process
+    pub fn from_env() -> Result<Self> {
+        let url = std::env::var(FM_BITCOIN_RPC_URL_ENV)
+            .with_context(|| format!("{FM_BITCOIN_RPC_URL_ENV} not set"))?
+            .parse()?;
+        let (host, auth) = fedimint_bitcoind::bitcoincore::from_url_to_url_auth(&url)?;
+        let client =
+            Bitcoind::new_bitcoin_rpc(&host, auth).context("Failed to connect to bitcoind")?;
+        Ok(Self::new(Arc::new(client)))
+    }
+
+    /// Mines `blocks` blocks and returns the new block count
+    pub async fn mine(&self, blocks: u64) -> Result<u64> {
+        let time_warp = *self.time_warp.lock().expect("poisoned");
+        let client = self.client.clone();
+        let block_count = spawn_blocking(move || -> Result<u64> {
+            if time_warp != Duration::ZERO {
+                // Mock time doesn't advance on its own, so keep it moving along
+                // with the real time
+                let mock_time = (duration_since_epoch() + time_warp).as_secs();
+                client.call::<()>("setmocktime", &[mock_time.into()])?;
+            }
+            let address = client
+                .get_new_address(None, None)?
+                .require_network(bitcoin::Network::Regtest)
+                .expect("Devimint always runs in regtest");
+            client.generate_to_address(blocks, &address)?;
+            Ok(client.get_block_count()? + 1)
+        })
+        .await??;
+        debug!(target: LOG_DEVIMINT, blocks, block_count, "Mined blocks");
+        Ok(block_count)
+    }
+
+    /// Sends `amount` from the wallet of bitcoind to `address`, e.g. to fund a
+    /// peg-in, without waiting for a confirmation
+    pub async fn send_to_address(
+        &self,
+        address: &bitcoin::Address,
+        amount: bitcoin::Amount,
+    ) -> Result<bitcoin::Txid> {
+        let client = self.client.clone();
+        let address = address.clone();
+        let txid = spawn_blocking(move || {
+            client.send_to_address(&address, amount, None, None, None, None, None, None)
+        })
+        .await??;
+        debug!(target: LOG_DEVIMINT, %amount, %txid, "Sent funds from bitcoind");
+        Ok(txid)
+    }
+
+    /// Returns a new address of the wallet of bitcoind, e.g. as destination of
+    /// a peg-out
+    pub async fn new_address(&self) -> Result<bitcoin::Address> {
+        let client = self.client.clone();
+        let address = spawn_blocking(move || client.get_new_address(None, None)).await??;
+        Ok(address
+            .require_network(bitcoin::Network::Regtest)
+            .expect("Devimint always runs in regtest"))
+    }
+
+    /// Moves the clock of bitcoind `by` into the future and mines a block, so
+    /// the chain's median time past moves along with it
+    pub async fn warp_time(&self, by: Duration) -> Result<u64> {
+        *self.time_warp.lock().expect("poisoned") += by;
+        debug!(target: LOG_DEVIMINT, ?by, "Warping bitcoind time");
+        self.mine(1).await
+    }
+
+    /// Mines `blocks` blocks every `interval` until the returned handle is
+    /// dropped or stopped
+    pub fn auto_mine_every(&self, interval: Duration, blocks: u64) -> AutoMiner {
+        let controller = self.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                fedimint_core::task::sleep(interval).await;
+                if let Err(e) = controller.mine(blocks).await {
+                    warn!(target: LOG_DEVIMINT, err = %e, "Scheduled mining failed");
+                }
+            }
+        });
+        AutoMiner { handle }
+    }
+}
+
+/// Background task mining blocks on a schedule, see
+/// [`MiningController::auto_mine_every`]
+pub struct AutoMiner {
+    handle: JoinHandle<()>,
+}
+
+impl AutoMiner {
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for AutoMiner {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -1096,7 +1096,11 @@ pub async fn finish_hold_invoice_payment(
     Ok(())
 }
 
-pub async fn cli_load_test_tool_test(dev_fed: DevFed) -> Result<()> {
+pub async fn cli_load_test_tool_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+    gateway_chaos_after: Option<Duration>,
+) -> Result<()> {
     log_binary_versions().await?;
     let data_dir = env::var(FM_DATA_DIR_ENV)?;
     let load_test_temp = PathBuf::from(data_dir).join("load-test-temp");
@@ -1111,6 +1115,70 @@ pub async fn cli_load_test_tool_test(dev_fed: DevFed) -> Result<()> {
         .await?;
     run_standard_load_test(&load_test_temp, &invite_code).await?;
     run_ln_circular_load_test(&load_test_temp, &invite_code).await?;
+    if let Some(gateway_chaos_after) = gateway_chaos_after {
+        run_gateway_chaos_load_test(
+            &load_test_temp,
+            &invite_code,
+            &dev_fed.gw_lnd,
+            process_mgr,
+            gateway_chaos_after,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Runs a load test paying invoices through the LND gateway, killing and
+/// restarting the gateway `kill_after` into the run, and reports how the
+/// payments ended
+pub async fn run_gateway_chaos_load_test(
+    load_test_temp: &Path,
+    invite_code: &str,
+    gw_lnd: &Gatewayd,
+    process_mgr: &ProcessManager,
+    kill_after: Duration,
+) -> anyhow::Result<()> {
+    let ln = gw_lnd.ln.clone().context("Gateway has no lightning node")?;
+    let load_test = async {
+        cmd!(
+            LoadTestTool,
+            "--archive-dir",
+            load_test_temp.display(),
+            "--users",
+            "4",
+            "load-test",
+            "--notes-per-user",
+            "10",
+            "--generate-invoice-with",
+            "cln-lightning-cli",
+            "--invoices-per-user",
+            "10",
+            "--ln-payment-sleep-secs",
+            "1",
+            "--invite-code",
+            invite_code
+        )
+        .out_string_and_success()
+        .await
+    };
+    let chaos = async {
+        fedimint_core::task::sleep(kill_after).await;
+        info!("Killing the LND gateway in the middle of the load test");
+        gw_lnd.process.terminate().await?;
+        let restarted = Gatewayd::new(process_mgr, ln).await?;
+        info!("Restarted the LND gateway");
+        anyhow::Ok(restarted)
+    };
+    // Keep the restarted gateway running until the end of the test
+    let ((output, success), _restarted) = try_join!(load_test, chaos)?;
+    println!("{output}");
+    if !success {
+        info!("Some load test users failed while the gateway was down");
+    }
+    anyhow::ensure!(
+        output.contains("LN payment outcomes"),
+        "missing LN payment outcomes"
+    );
     Ok(())
 }
 
@@ -2247,7 +2315,13 @@ pub enum TestCmd {
     CliTests,
     /// `devfed` then calls binary `fedimint-load-test-tool`. See
     /// `LoadTestArgs`.
-    LoadTestToolTest,
+    LoadTestToolTest {
+        /// Also run a LN payment load test during which the LND gateway is
+        /// killed and restarted after this many seconds, reporting how many
+        /// payments succeeded or were refunded
+        #[arg(long)]
+        gateway_chaos_after_secs: Option<u64>,
+    },
     /// `devfed` then pegin CLN & LND nodes and gateways. Kill the LN nodes,
     /// restart them, rejjoin fedimint and test payments still work
     LightningReconnectTest,
@@ -2329,10 +2403,17 @@ pub async fn handle_command(cmd: TestCmd, common_args: CommonArgs) -> Result<()>
             let dev_fed = dev_fed(&process_mgr).await?;
             cli_tests(dev_fed).await?;
         }
-        TestCmd::LoadTestToolTest => {
+        TestCmd::LoadTestToolTest {
+            gateway_chaos_after_secs,
+        } => {
             let (process_mgr, _) = setup(common_args).await?;
             let dev_fed = dev_fed(&process_mgr).await?;
-            cli_load_test_tool_test(dev_fed).await?;
+            cli_load_test_tool_test(
+                dev_fed,
+                &process_mgr,
+                gateway_chaos_after_secs.map(Duration::from_secs),
+            )
+            .await?;
         }
         TestCmd::LightningReconnectTest => {
             let (process_mgr, _) = setup(common_args).await?;
@@ -262,6 +262,22 @@ pub async fn out_string(&mut self) -> Result<String> {
         Ok(output.trim().to_owned())
     }
 
+    /// Run the command and get its output as string, along with whether it
+    /// exited successfully, for commands that are expected to fail sometimes.
+    pub async fn out_string_and_success(&mut self) -> Result<(String, bool)> {
+        debug!(target: LOG_DEVIMINT, "> {}", self.command_debug());
+        let output = self
+            .cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("command: {}", self.command_debug()))?
+            .wait_with_output()
+            .await?;
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok((stdout.trim().to_owned(), output.status.success()))
+    }
+
     /// Returns the json error if the command has a non-zero exit code.
     pub async fn expect_err_json(&mut self) -> Result<serde_json::Value> {
         let output = self
@@ -0,0 +1,50 @@
+use serde_json::Value;
+
+use super::JsonRpcResult;
+
+/// Unwraps a response the guardian compressed, see
+/// [`fedimint_core::net::api_compression`]. Other responses are returned as
+/// they are.
+#[cfg(not(target_family = "wasm"))]
+pub fn decompress_response(response: Value) -> JsonRpcResult<Value> {
+    use std::io::Read as _;
+
+    use base64::Engine as _;
+    use fedimint_core::net::api_compression::CompressedResponse;
+    use serde::Deserialize as _;
+
+    use super::JsonRpcClientError;
+
+    /// Bounds the memory a malicious guardian can make the client allocate
+    /// with a small, highly compressed response
+    const MAX_DECOMPRESSED_RESPONSE_LEN: u64 = 128 * 1024 * 1024;
+
+    let Ok(compressed) = CompressedResponse::deserialize(&response) else {
+        return Ok(response);
+    };
+
+    let invalid =
+        |err: String| JsonRpcClientError::Custom(format!("Invalid compressed response: {err}"));
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(compressed.zstd_base64)
+        .map_err(|e| invalid(e.to_string()))?;
+    let mut decompressed = vec![];
+    zstd::stream::read::Decoder::new(compressed.as_slice())
+        .map_err(|e| invalid(e.to_string()))?
+        .take(MAX_DECOMPRESSED_RESPONSE_LEN + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| invalid(e.to_string()))?;
+    if MAX_DECOMPRESSED_RESPONSE_LEN < decompressed.len() as u64 {
+        return Err(invalid(format!(
+            "longer than {MAX_DECOMPRESSED_RESPONSE_LEN} bytes"
+        )));
+    }
+    serde_json::from_slice(&decompressed).map_err(|e| invalid(e.to_string()))
+}
+
+/// Browsers can't set the headers of a WebSocket handshake, so compressed
+/// responses are never negotiated
+#[cfg(target_family = "wasm")]
+pub fn decompress_response(response: Value) -> JsonRpcResult<Value> {
+    Ok(response)
+}
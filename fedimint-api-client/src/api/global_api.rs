@@ -14,23 +14,28 @@
 use fedimint_core::core::backup::SignedBackupRequest;
 use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::endpoint_constants::{
-    ADD_CONFIG_GEN_PEER_ENDPOINT, API_ANNOUNCEMENTS_ENDPOINT, AUDIT_ENDPOINT, AUTH_ENDPOINT,
-    AWAIT_SESSION_OUTCOME_ENDPOINT, AWAIT_TRANSACTION_ENDPOINT, BACKUP_ENDPOINT,
-    CONFIG_GEN_PEERS_ENDPOINT, CONSENSUS_CONFIG_GEN_PARAMS_ENDPOINT,
-    DEFAULT_CONFIG_GEN_PARAMS_ENDPOINT, FEDIMINTD_VERSION_ENDPOINT,
-    GUARDIAN_CONFIG_BACKUP_ENDPOINT, RECOVER_ENDPOINT, RESTART_FEDERATION_SETUP_ENDPOINT,
-    RUN_DKG_ENDPOINT, SERVER_CONFIG_CONSENSUS_HASH_ENDPOINT, SESSION_COUNT_ENDPOINT,
-    SESSION_STATUS_ENDPOINT, SET_CONFIG_GEN_CONNECTIONS_ENDPOINT, SET_CONFIG_GEN_PARAMS_ENDPOINT,
-    SET_PASSWORD_ENDPOINT, SHUTDOWN_ENDPOINT, SIGN_API_ANNOUNCEMENT_ENDPOINT,
-    START_CONSENSUS_ENDPOINT, STATUS_ENDPOINT, SUBMIT_API_ANNOUNCEMENT_ENDPOINT,
-    SUBMIT_TRANSACTION_ENDPOINT, VERIFIED_CONFIGS_ENDPOINT, VERIFY_CONFIG_HASH_ENDPOINT,
+    ADD_ADMIN_KEY_ENDPOINT, ADD_CONFIG_GEN_PEER_ENDPOINT, ADMIN_KEYS_ENDPOINT,
+    API_ANNOUNCEMENTS_ENDPOINT, AUDIT_ENDPOINT, AUTH_ENDPOINT, AWAIT_SESSION_OUTCOME_ENDPOINT,
+    AWAIT_TRANSACTION_ENDPOINT, BACKUP_ENDPOINT, CONFIG_GEN_PEERS_ENDPOINT,
+    CONSENSUS_CONFIG_GEN_PARAMS_ENDPOINT, DEFAULT_CONFIG_GEN_PARAMS_ENDPOINT,
+    FEDIMINTD_VERSION_ENDPOINT, FEE_REVENUE_ENDPOINT, GUARDIAN_CONFIG_BACKUP_ENDPOINT,
+    PEER_CONNECTIVITY_ENDPOINT, RECOVER_ENDPOINT, REMOVE_ADMIN_KEY_ENDPOINT,
+    RESTART_FEDERATION_SETUP_ENDPOINT, RUN_DKG_ENDPOINT, SERVER_CONFIG_CONSENSUS_HASH_ENDPOINT,
+    SESSION_COUNT_ENDPOINT, SESSION_STATUS_ENDPOINT, SET_CONFIG_GEN_CONNECTIONS_ENDPOINT,
+    SET_CONFIG_GEN_PARAMS_ENDPOINT, SET_PASSWORD_ENDPOINT, SHUTDOWN_ENDPOINT,
+    SIGN_API_ANNOUNCEMENT_ENDPOINT, START_CONSENSUS_ENDPOINT, STATUS_ENDPOINT,
+    SUBMIT_API_ANNOUNCEMENT_ENDPOINT, SUBMIT_TRANSACTION_ENDPOINT, VERIFIED_CONFIGS_ENDPOINT,
+    VERIFY_CONFIG_HASH_ENDPOINT,
 };
+use fedimint_core::epoch::FeeRevenue;
 use fedimint_core::module::audit::AuditSummary;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::{ApiAuth, ApiRequestErased, SerdeModuleEncoding};
+use fedimint_core::net::admin_auth::AddAdminKeyRequest;
 use fedimint_core::net::api_announcement::{
     SignedApiAnnouncement, SignedApiAnnouncementSubmission,
 };
+use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::session_outcome::{AcceptedItem, SessionOutcome, SessionStatus};
 use fedimint_core::task::{MaybeSend, MaybeSync};
 use fedimint_core::transaction::{SerdeTransaction, Transaction, TransactionSubmissionOutcome};
@@ -44,11 +49,18 @@
 use tracing::debug;
 
 use super::{
-    DynModuleApi, FederationApiExt, FederationError, FederationResult, GuardianConfigBackup,
-    IGlobalFederationApi, IRawFederationApi, PeerResult, StatusResponse,
+    CacheUsage, DynModuleApi, FederationApiExt, FederationError, FederationResult,
+    GuardianConfigBackup, IGlobalFederationApi, IRawFederationApi, PeerConnectivityStatus,
+    PeerResult, SessionCacheUsage, StatusResponse,
 };
 use crate::query::FilterMapThreshold;
 
+/// Number of sessions [`GlobalFederationApiWithCache`] caches by default
+pub const DEFAULT_SESSION_CACHE_CAPACITY: NonZeroUsize = match NonZeroUsize::new(512) {
+    Some(capacity) => capacity,
+    None => panic!("is non-zero"),
+};
+
 /// Convenience extension trait used for wrapping [`IRawFederationApi`] in
 /// a [`GlobalFederationApiWithCache`]
 pub trait GlobalFederationApiWithCacheExt
@@ -56,6 +68,9 @@ pub trait GlobalFederationApiWithCacheExt
     Self: Sized,
 {
     fn with_cache(self) -> GlobalFederationApiWithCache<Self>;
+
+    /// Like [`Self::with_cache`], but caching up to `capacity` sessions
+    fn with_cache_capacity(self, capacity: NonZeroUsize) -> GlobalFederationApiWithCache<Self>;
 }
 
 impl<T> GlobalFederationApiWithCacheExt for T
@@ -65,6 +80,10 @@ impl<T> GlobalFederationApiWithCacheExt for T
     fn with_cache(self) -> GlobalFederationApiWithCache<T> {
         GlobalFederationApiWithCache::new(self)
     }
+
+    fn with_cache_capacity(self, capacity: NonZeroUsize) -> GlobalFederationApiWithCache<T> {
+        GlobalFederationApiWithCache::with_capacity(self, capacity)
+    }
 }
 
 /// [`IGlobalFederationApi`] wrapping some `T: IRawFederationApi` and adding
@@ -98,14 +117,15 @@ pub struct GlobalFederationApiWithCache<T> {
 
 impl<T> GlobalFederationApiWithCache<T> {
     pub fn new(inner: T) -> GlobalFederationApiWithCache<T> {
+        Self::with_capacity(inner, DEFAULT_SESSION_CACHE_CAPACITY)
+    }
+
+    /// Creates the API with caches that hold up to `capacity` sessions each
+    pub fn with_capacity(inner: T, capacity: NonZeroUsize) -> GlobalFederationApiWithCache<T> {
         Self {
             inner,
-            await_session_lru: Arc::new(tokio::sync::Mutex::new(lru::LruCache::new(
-                NonZeroUsize::new(512).expect("is non-zero"),
-            ))),
-            get_session_status_lru: Arc::new(tokio::sync::Mutex::new(lru::LruCache::new(
-                NonZeroUsize::new(512).expect("is non-zero"),
-            ))),
+            await_session_lru: Arc::new(tokio::sync::Mutex::new(lru::LruCache::new(capacity))),
+            get_session_status_lru: Arc::new(tokio::sync::Mutex::new(lru::LruCache::new(capacity))),
         }
     }
 }
@@ -171,6 +191,10 @@ async fn request_raw(
     ) -> result::Result<Value, JsonRpcClientError> {
         self.inner.request_raw(peer_id, method, params).await
     }
+
+    async fn set_peer_url(&self, peer_id: PeerId, url: SafeUrl) {
+        self.inner.set_peer_url(peer_id, url).await;
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -256,6 +280,20 @@ async fn session_count(&self) -> FederationResult<u64> {
         .await
     }
 
+    async fn session_cache_usage(&self) -> SessionCacheUsage {
+        fn usage<K: std::hash::Hash + Eq, V>(lru: &lru::LruCache<K, V>) -> CacheUsage {
+            CacheUsage {
+                entries: lru.len(),
+                capacity: lru.cap().get(),
+            }
+        }
+
+        SessionCacheUsage {
+            session_outcomes: usage(&*self.await_session_lru.lock().await),
+            session_statuses: usage(&*self.get_session_status_lru.lock().await),
+        }
+    }
+
     async fn await_transaction(&self, txid: TransactionId) -> TransactionId {
         self.request_current_consensus_retry(
             AWAIT_TRANSACTION_ENDPOINT.to_owned(),
@@ -488,6 +526,46 @@ async fn shutdown(&self, session: Option<u64>, auth: ApiAuth) -> FederationResul
             .await
     }
 
+    async fn add_admin_key(
+        &self,
+        request: AddAdminKeyRequest,
+        auth: ApiAuth,
+    ) -> FederationResult<()> {
+        self.request_admin(ADD_ADMIN_KEY_ENDPOINT, ApiRequestErased::new(request), auth)
+            .await
+    }
+
+    async fn remove_admin_key(&self, public_key: PublicKey, auth: ApiAuth) -> FederationResult<()> {
+        self.request_admin(
+            REMOVE_ADMIN_KEY_ENDPOINT,
+            ApiRequestErased::new(public_key),
+            auth,
+        )
+        .await
+    }
+
+    async fn peer_connectivity(
+        &self,
+        auth: ApiAuth,
+    ) -> FederationResult<BTreeMap<PeerId, PeerConnectivityStatus>> {
+        self.request_admin(
+            PEER_CONNECTIVITY_ENDPOINT,
+            ApiRequestErased::default(),
+            auth,
+        )
+        .await
+    }
+
+    async fn fee_revenue(&self) -> FederationResult<FeeRevenue> {
+        self.request_current_consensus(FEE_REVENUE_ENDPOINT.to_owned(), ApiRequestErased::default())
+            .await
+    }
+
+    async fn admin_keys(&self, auth: ApiAuth) -> FederationResult<BTreeMap<PublicKey, String>> {
+        self.request_admin(ADMIN_KEYS_ENDPOINT, ApiRequestErased::default(), auth)
+            .await
+    }
+
     async fn fedimintd_version(&self, peer_id: PeerId) -> PeerResult<String> {
         self.request_single_peer(
             FEDIMINTD_VERSION_ENDPOINT.to_owned(),
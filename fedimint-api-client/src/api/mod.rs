@@ -19,11 +19,17 @@
 use fedimint_core::core::backup::SignedBackupRequest;
 use fedimint_core::core::{Decoder, DynOutputOutcome, ModuleInstanceId, OutputOutcome};
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::epoch::FeeRevenue;
 use fedimint_core::invite_code::InviteCode;
+use fedimint_core::metrics::Histogram;
 use fedimint_core::module::audit::AuditSummary;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::{ApiAuth, ApiRequestErased, ApiVersion, SerdeModuleEncoding};
+use fedimint_core::net::admin_auth::AddAdminKeyRequest;
 use fedimint_core::net::api_announcement::SignedApiAnnouncement;
+#[cfg(not(target_family = "wasm"))]
+use fedimint_core::net::api_compression::{ACCEPT_ENCODING_HEADER, ZSTD_ENCODING};
+use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::session_outcome::{SessionOutcome, SessionStatus};
 use fedimint_core::task::{MaybeSend, MaybeSync};
 use fedimint_core::transaction::{Transaction, TransactionSubmissionOutcome};
@@ -55,13 +61,17 @@
 use tokio_rustls::{rustls::ClientConfig as TlsClientConfig, TlsConnector};
 use tracing::{debug, instrument, trace, warn};
 
+use crate::api::compression::decompress_response;
 use crate::query::{QueryStep, QueryStrategy, ThresholdConsensus};
+mod compression;
 mod error;
 mod global_api;
 pub mod net;
 mod peer;
 
-pub use global_api::{GlobalFederationApiWithCache, GlobalFederationApiWithCacheExt};
+pub use global_api::{
+    GlobalFederationApiWithCache, GlobalFederationApiWithCacheExt, DEFAULT_SESSION_CACHE_CAPACITY,
+};
 use peer::FederationPeer;
 
 pub type PeerResult<T> = Result<T, PeerError>;
@@ -71,6 +81,12 @@
 
 pub type OutputOutcomeResult<O> = result::Result<O, OutputOutcomeError>;
 
+static API_REQUEST_DURATION: Histogram = Histogram::new(
+    "client_api_request_duration_seconds",
+    "Duration of the API requests made to a single peer",
+    &["method", "result"],
+);
+
 /// Set of api versions for each component (core + modules)
 ///
 /// E.g. result of federated common api versions discovery.
@@ -107,6 +123,11 @@ async fn request_raw(
         method: &str,
         params: &[Value],
     ) -> result::Result<Value, JsonRpcClientError>;
+
+    /// Switch to a new API URL of `peer_id`, e.g. after the guardian
+    /// announced that it moved. Does nothing for implementations that don't
+    /// connect to the peers themselves.
+    async fn set_peer_url(&self, _peer_id: PeerId, _url: SafeUrl) {}
 }
 
 /// An extension trait allowing to making federation-wide API call on top
@@ -485,6 +506,9 @@ async fn get_session_status(
 
     async fn session_count(&self) -> FederationResult<u64>;
 
+    /// How full the caches of session outcomes and statuses are
+    async fn session_cache_usage(&self) -> SessionCacheUsage;
+
     async fn await_transaction(&self, txid: TransactionId) -> TransactionId;
 
     /// Fetches the server consensus hash if enough peers agree on it
@@ -609,6 +633,29 @@ async fn sign_api_announcement(
 
     async fn shutdown(&self, session: Option<u64>, auth: ApiAuth) -> FederationResult<()>;
 
+    /// Authorize an admin key to sign requests to admin endpoints of our peer
+    /// instead of using the guardian password
+    async fn add_admin_key(
+        &self,
+        request: AddAdminKeyRequest,
+        auth: ApiAuth,
+    ) -> FederationResult<()>;
+
+    /// Revoke a previously authorized admin key
+    async fn remove_admin_key(&self, public_key: PublicKey, auth: ApiAuth) -> FederationResult<()>;
+
+    /// Our peer's view of the connections to all other guardians
+    async fn peer_connectivity(
+        &self,
+        auth: ApiAuth,
+    ) -> FederationResult<BTreeMap<PeerId, PeerConnectivityStatus>>;
+
+    /// Cumulative transaction fee revenue of the federation
+    async fn fee_revenue(&self) -> FederationResult<FeeRevenue>;
+
+    /// List the admin keys authorized by our peer together with their labels
+    async fn admin_keys(&self, auth: ApiAuth) -> FederationResult<BTreeMap<PublicKey, String>>;
+
     /// Returns the fedimintd version a peer is running
     async fn fedimintd_version(&self, peer_id: PeerId) -> PeerResult<String>;
 }
@@ -686,7 +733,19 @@ async fn request_raw(
             None => method.to_string(),
             Some(id) => format!("module_{id}_{method}"),
         };
-        peer.request(&method, params).await
+        let start = fedimint_core::time::now();
+        let result = peer.request(&method, params).await;
+        API_REQUEST_DURATION.observe_duration(
+            &[&method, if result.is_ok() { "ok" } else { "error" }],
+            start.elapsed().unwrap_or_default(),
+        );
+        result
+    }
+
+    async fn set_peer_url(&self, peer_id: PeerId, url: SafeUrl) {
+        if let Some(peer) = self.peers.iter().find(|m| m.peer_id == peer_id) {
+            peer.set_url(url).await;
+        }
     }
 }
 
@@ -730,23 +789,12 @@ async fn connect(
         #[cfg(target_family = "wasm")]
         let client = WsClientBuilder::default().max_concurrent_requests(u16::MAX as usize);
 
-        if let Some(api_secret) = api_secret {
-            #[cfg(not(target_family = "wasm"))]
-            {
-                // on native platforms, jsonrpsee-client ignores `user:pass@...` in the Url,
-                // but we can set up the headers manually
-                let mut headers = HeaderMap::new();
-
-                let auth = base64::engine::general_purpose::STANDARD
-                    .encode(format!("fedimint:{api_secret}"));
-
-                headers.insert(
-                    "Authorization",
-                    HeaderValue::from_str(&format!("Basic {auth}")).expect("Can't fail"),
-                );
+        #[cfg(not(target_family = "wasm"))]
+        {
+            client = client.set_headers(connection_headers(api_secret.as_deref()));
+        }
 
-                client = client.set_headers(headers);
-            }
+        if let Some(api_secret) = api_secret {
             #[cfg(target_family = "wasm")]
             {
                 // on wasm, url will be handled by the browser, which should take care of
@@ -844,24 +892,9 @@ async fn connect_with_tor(
             None
         };
 
-        let mut ws_client_builder =
-            WsClientBuilder::default().max_concurrent_requests(u16::MAX as usize);
-
-        if let Some(api_secret) = api_secret {
-            // on native platforms, jsonrpsee-client ignores `user:pass@...` in the Url,
-            // but we can set up the headers manually
-            let mut headers = HeaderMap::new();
-
-            let auth =
-                base64::engine::general_purpose::STANDARD.encode(format!("fedimint:{api_secret}"));
-
-            headers.insert(
-                "Authorization",
-                HeaderValue::from_str(&format!("Basic {auth}")).expect("Can't fail"),
-            );
-
-            ws_client_builder = ws_client_builder.set_headers(headers);
-        }
+        let ws_client_builder = WsClientBuilder::default()
+            .max_concurrent_requests(u16::MAX as usize)
+            .set_headers(connection_headers(api_secret.as_deref()));
 
         match tls_connector {
             None => {
@@ -896,6 +929,32 @@ fn is_connected(&self) -> bool {
     }
 }
 
+/// Headers of the WebSocket handshake, authenticating with `api_secret` and
+/// asking for compressed responses
+#[cfg(not(target_family = "wasm"))]
+fn connection_headers(api_secret: Option<&str>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Some(api_secret) = api_secret {
+        // on native platforms, jsonrpsee-client ignores `user:pass@...` in the Url,
+        // but we can set up the headers manually
+        let auth =
+            base64::engine::general_purpose::STANDARD.encode(format!("fedimint:{api_secret}"));
+
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Basic {auth}")).expect("Can't fail"),
+        );
+    }
+
+    headers.insert(
+        ACCEPT_ENCODING_HEADER,
+        HeaderValue::from_static(ZSTD_ENCODING),
+    );
+
+    headers
+}
+
 impl WsFederationApi<WsClient> {
     /// Creates a new API client
     pub fn new(
@@ -991,7 +1050,8 @@ pub async fn request(&self, method: &str, params: &[Value]) -> JsonRpcResult<Val
             let rclient = self.client.read().await;
             match rclient.client.get_try().await {
                 Ok(client) if client.is_connected() => {
-                    return client.request::<_, _>(method, params).await;
+                    let response = client.request::<Value, _>(method, params).await?;
+                    return decompress_response(response);
                 }
                 Err(e) => {
                     if RETRIES <= attempts {
@@ -1018,12 +1078,7 @@ pub async fn request(&self, method: &str, params: &[Value]) -> JsonRpcResult<Val
                     trace!(target: LOG_CLIENT_NET_API, "Some other request reconnected client, retrying");
                 }
                 _ => {
-                    wclient.reconnect(
-                        self.connector,
-                        self.peer_id,
-                        self.url.clone(),
-                        self.api_secret.clone(),
-                    );
+                    wclient.reconnect(self.connector, self.peer_id, self.api_secret.clone());
                 }
             }
         }
@@ -1062,6 +1117,40 @@ pub enum PeerConnectionStatus {
     Connected,
 }
 
+/// Fill level of a bounded cache, evicting the least recently used entries once
+/// `entries` reaches `capacity`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+/// See [`IGlobalFederationApi::session_cache_usage`]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionCacheUsage {
+    pub session_outcomes: CacheUsage,
+    pub session_statuses: CacheUsage,
+}
+
+/// A guardian's view of its connection to one of its peers, to diagnose which
+/// guardian slows down consensus
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerConnectivityStatus {
+    pub connection_status: PeerConnectionStatus,
+    /// Seconds since the unix epoch when we last received a message from the
+    /// peer
+    pub last_contact: Option<u64>,
+    /// Index of the last session the peer contributed a consensus item to
+    pub last_contribution: Option<u64>,
+    /// Number of sessions since the last contribution of the peer, or since
+    /// the start of the federation if it never contributed
+    pub session_lag: u64,
+    /// Number of failed connection attempts and dropped connections since we
+    /// started
+    pub connection_errors: u64,
+    pub last_error: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct StatusResponse {
     pub server: ServerStatus,
@@ -15,7 +15,6 @@
 // TODO(tvolk131): Merge this with `FederationPeerClient`.
 #[derive(Debug)]
 pub struct FederationPeer<C> {
-    pub url: SafeUrl,
     pub peer_id: PeerId,
     pub api_secret: Option<String>,
     pub client: RwLock<FederationPeerClient<C>>,
@@ -35,18 +34,36 @@ pub fn new(
         let client = RwLock::new(FederationPeerClient::new(
             connector,
             peer_id,
-            url.clone(),
+            url,
             api_secret.clone(),
         ));
 
         Self {
-            url,
             peer_id,
             api_secret,
             client,
             connector,
         }
     }
+
+    /// Switches to a new API URL of the peer, e.g. after it announced that it
+    /// moved. Requests in flight finish on the old connection first.
+    pub async fn set_url(&self, url: SafeUrl) {
+        let mut client = self.client.write().await;
+
+        if client.url == url {
+            return;
+        }
+
+        debug!(
+            target: LOG_CLIENT_NET_API,
+            peer_id = %self.peer_id,
+            %url,
+            "Switching to new peer API URL");
+
+        client.url = url;
+        client.reconnect(self.connector, self.peer_id, self.api_secret.clone());
+    }
 }
 
 /// The client in [`FederationPeer`], that takes care of reconnecting by
@@ -54,6 +71,7 @@ pub fn new(
 #[derive(Debug)]
 pub struct FederationPeerClient<C> {
     pub client: JitTryAnyhow<C>,
+    pub url: SafeUrl,
     connection_state: Arc<tokio::sync::Mutex<FederationPeerClientConnectionState>>,
 }
 
@@ -75,10 +93,11 @@ fn new(
             client: Self::new_jit_client(
                 connector,
                 peer_id,
-                url,
+                url.clone(),
                 api_secret,
                 connection_state.clone(),
             ),
+            url,
             connection_state,
         }
     }
@@ -122,17 +141,11 @@ fn new_jit_client(
         })
     }
 
-    pub fn reconnect(
-        &mut self,
-        connector: Connector,
-        peer_id: PeerId,
-        url: SafeUrl,
-        api_secret: Option<String>,
-    ) {
+    pub fn reconnect(&mut self, connector: Connector, peer_id: PeerId, api_secret: Option<String>) {
         self.client = Self::new_jit_client(
             connector,
             peer_id,
-            url,
+            self.url.clone(),
             api_secret,
             self.connection_state.clone(),
         );
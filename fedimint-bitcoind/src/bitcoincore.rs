@@ -76,6 +76,20 @@ async fn get_fee_rate(&self, confirmation_target: u16) -> anyhow::Result<Option<
         }))
     }
 
+    async fn get_mempool_tx_fee_rate(&self, txid: &Txid) -> anyhow::Result<Option<Feerate>> {
+        use bitcoincore_rpc::jsonrpc::Error::Rpc;
+        use bitcoincore_rpc::Error::JsonRpc;
+        match block_in_place(|| self.client.get_mempool_entry(txid)) {
+            Ok(entry) => Ok(Some(Feerate {
+                sats_per_kvb: entry.fees.base.to_sat() * 1000 / entry.vsize.max(1),
+            })),
+            // Bitcoin core's RPC will return error code -5 if a transaction is not in the
+            // mempool, e.g. because it was already mined
+            Err(JsonRpc(Rpc(e))) if e.code == -5 => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     async fn submit_transaction(&self, transaction: Transaction) {
         use bitcoincore_rpc::jsonrpc::Error::Rpc;
         use bitcoincore_rpc::Error::JsonRpc;
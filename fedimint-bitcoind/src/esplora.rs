@@ -5,7 +5,7 @@
 use fedimint_core::envs::BitcoinRpcConfig;
 use fedimint_core::txoproof::TxOutProof;
 use fedimint_core::util::SafeUrl;
-use fedimint_core::{apply, async_trait_maybe_send, Feerate};
+use fedimint_core::{apply, async_trait_maybe_send, weight_to_vbytes, Feerate};
 use tracing::info;
 
 use crate::{DynBitcoindRpc, IBitcoindRpc, IBitcoindRpcFactory};
@@ -90,6 +90,20 @@ async fn get_fee_rate(&self, confirmation_target: u16) -> anyhow::Result<Option<
         }))
     }
 
+    async fn get_mempool_tx_fee_rate(&self, txid: &Txid) -> anyhow::Result<Option<Feerate>> {
+        let Some(tx) = self.client.get_tx_info(txid).await? else {
+            return Ok(None);
+        };
+
+        if tx.status.confirmed {
+            return Ok(None);
+        }
+
+        Ok(Some(Feerate {
+            sats_per_kvb: tx.fee * 1000 / weight_to_vbytes(tx.weight).max(1),
+        }))
+    }
+
     async fn submit_transaction(&self, transaction: Transaction) {
         let _ = self.client.broadcast(&transaction).await.map_err(|error| {
             // `esplora-client` v0.6.0 only surfaces HTTP error codes, which prevents us
@@ -125,6 +125,10 @@ pub trait IBitcoindRpc: Debug {
     /// estimation this function returns `None`.
     async fn get_fee_rate(&self, confirmation_target: u16) -> Result<Option<Feerate>>;
 
+    /// Returns the fee rate an unconfirmed transaction pays, or `None` if the
+    /// transaction is not in the mempool (anymore)
+    async fn get_mempool_tx_fee_rate(&self, txid: &Txid) -> Result<Option<Feerate>>;
+
     /// Submits a transaction to the Bitcoin network
     ///
     /// This operation does not return anything as it never OK to consider its
@@ -0,0 +1,230 @@
+//! Printable recovery kit, see [`BackupKit`]
+
+use std::fmt::Write as _;
+use std::io::{BufRead, Write as _};
+use std::time::SystemTime;
+
+use anyhow::{ensure, Context};
+use fedimint_aead::{decrypt, encrypt, get_encryption_key, random_salt};
+use fedimint_bip39::Mnemonic;
+use fedimint_core::config::FederationId;
+use fedimint_core::invite_code::InviteCode;
+
+use crate::client::time_to_iso8601;
+use crate::envs::FM_BACKUP_KIT_PASSPHRASE_ENV;
+
+/// Width of the wrapped lines of long values, short enough to fit a printed
+/// page in a large font
+const LINE_WIDTH: usize = 48;
+
+/// Everything needed to recover a client on paper: how to reach the
+/// federation and the client seed, encrypted with a passphrase so a found or
+/// stolen print-out isn't enough to take the funds.
+pub struct BackupKit {
+    pub federation_name: Option<String>,
+    pub federation_id: FederationId,
+    pub invite_code: InviteCode,
+    /// Salt the encryption key was derived from the passphrase with
+    pub salt: String,
+    /// Hex encoded seed entropy, encrypted with the passphrase
+    pub encrypted_seed: String,
+}
+
+impl BackupKit {
+    pub fn new(
+        federation_name: Option<String>,
+        federation_id: FederationId,
+        invite_code: InviteCode,
+        entropy: Vec<u8>,
+        passphrase: &str,
+    ) -> anyhow::Result<Self> {
+        let salt = random_salt();
+        let key = get_encryption_key(passphrase, &salt)?;
+        Ok(BackupKit {
+            federation_name,
+            federation_id,
+            invite_code,
+            salt,
+            encrypted_seed: hex::encode(encrypt(entropy, &key)?),
+        })
+    }
+
+    /// Renders the kit as plain text for printing, with long values wrapped
+    /// into numbered lines that are easy to type back in
+    pub fn render(&self) -> String {
+        let mut kit = String::new();
+        let mut line = |s: &str| {
+            kit.push_str(s);
+            kit.push('\n');
+        };
+
+        line("FEDIMINT RECOVERY KIT");
+        line("=====================");
+        line("");
+        line(&format!(
+            "Federation:    {}",
+            self.federation_name.as_deref().unwrap_or("(unnamed)")
+        ));
+        line(&format!("Federation ID: {}", self.federation_id));
+        line(&format!(
+            "Created:       {}",
+            time_to_iso8601(&SystemTime::now())
+        ));
+        line("");
+        line("Keep this page somewhere safe. The seed below can only be");
+        line("decrypted with the passphrase chosen when creating the kit,");
+        line("store the passphrase separately.");
+        line("");
+        line("1. FEDERATION INVITE");
+        line("");
+        line(&wrap(&self.invite_code.to_string()));
+        line("");
+        line("2. DERIVATION");
+        line("");
+        line("Seed:        BIP-39 mnemonic of 12 words");
+        line("Root secret: Fedimint BIP-39 root secret, as used by");
+        line("             `fedimint-cli restore`");
+        line("");
+        line("3. ENCRYPTED SEED");
+        line("");
+        line("Encryption:  ChaCha20-Poly1305 with a key derived from the");
+        line("             passphrase and the salt by Argon2");
+        line(&format!("Salt:        {}", self.salt));
+        line("");
+        line(&wrap(&self.encrypted_seed));
+        line("");
+        line("RECOVERY");
+        line("");
+        line("Type in the values without the line numbers and spaces.");
+        line("Decrypt the seed with the passphrase:");
+        line("");
+        line("  fedimint-cli decrypt-backup-kit-seed --salt <salt> \\");
+        line("    --encrypted-seed <encrypted seed>");
+        line("");
+        line("which asks for the passphrase,");
+        line("");
+        line("then restore the client with the printed mnemonic:");
+        line("");
+        line("  fedimint-cli restore --mnemonic <mnemonic> \\");
+        line("    --invite-code <invite>");
+        kit
+    }
+}
+
+/// Reads the passphrase of a [`BackupKit`] from the
+/// [`FM_BACKUP_KIT_PASSPHRASE_ENV`] env variable or, if it isn't set, from a
+/// line on stdin. It's never taken from the command line, where it would end
+/// up in the shell history and be visible to other users in the process list.
+pub fn read_passphrase() -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(FM_BACKUP_KIT_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    eprint!("Backup kit passphrase: ");
+    std::io::stderr().flush()?;
+    let mut passphrase = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut passphrase)
+        .context("Could not read the passphrase")?;
+    let passphrase = passphrase.trim_end_matches(['\r', '\n']).to_owned();
+    ensure!(!passphrase.is_empty(), "Passphrase must not be empty");
+    Ok(passphrase)
+}
+
+/// Decrypts the seed of a [`BackupKit`], tolerating the whitespace and line
+/// breaks of a value typed in from a print-out
+pub fn decrypt_seed(
+    salt: &str,
+    encrypted_seed: &str,
+    passphrase: &str,
+) -> anyhow::Result<Mnemonic> {
+    let encrypted_seed = encrypted_seed
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let mut encrypted_seed =
+        hex::decode(encrypted_seed).context("Encrypted seed is not valid hex")?;
+    let key = get_encryption_key(passphrase, salt.trim())?;
+    let entropy =
+        decrypt(&mut encrypted_seed, &key).context("Wrong passphrase or mistyped seed")?;
+    Ok(Mnemonic::from_entropy(entropy)?)
+}
+
+/// Splits `value` into numbered lines of [`LINE_WIDTH`] characters in groups
+/// of four
+fn wrap(value: &str) -> String {
+    let chars = value.chars().collect::<Vec<_>>();
+    let mut wrapped = String::new();
+    for (i, line) in chars.chunks(LINE_WIDTH).enumerate() {
+        let groups = line
+            .chunks(4)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if i > 0 {
+            wrapped.push('\n');
+        }
+        write!(wrapped, "{:>3}  {groups}", i + 1).expect("Writing to a string can't fail");
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::config::FederationId;
+    use fedimint_core::invite_code::InviteCode;
+    use fedimint_core::PeerId;
+
+    use super::{decrypt_seed, BackupKit};
+
+    /// Reads back the value wrapped into numbered lines after the line
+    /// starting with `label`, like a user typing it in from a print-out
+    fn read_wrapped(kit: &str, label: &str) -> String {
+        kit.lines()
+            .skip_while(|line| !line.starts_with(label))
+            .skip(2)
+            .take_while(|line| !line.is_empty())
+            .map(|line| &line[5..])
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn rendered_kit_decrypts_to_the_seed() {
+        let entropy = vec![7; 16];
+        let kit = BackupKit::new(
+            Some("Test federation".to_owned()),
+            FederationId::dummy(),
+            InviteCode::new(
+                "ws://test.invalid:8174".parse().expect("valid url"),
+                PeerId::from(0),
+                FederationId::dummy(),
+                None,
+            ),
+            entropy.clone(),
+            "correct horse battery staple",
+        )
+        .expect("encrypting can't fail");
+        let rendered = kit.render();
+
+        assert_eq!(
+            read_wrapped(&rendered, "1. FEDERATION INVITE")
+                .split_whitespace()
+                .collect::<String>(),
+            kit.invite_code.to_string()
+        );
+
+        let salt = rendered
+            .lines()
+            .find_map(|line| line.strip_prefix("Salt:"))
+            .expect("kit contains the salt");
+        let encrypted_seed = read_wrapped(&rendered, "Salt:");
+
+        let mnemonic = decrypt_seed(salt, &encrypted_seed, "correct horse battery staple")
+            .expect("right passphrase decrypts the seed");
+        assert_eq!(mnemonic.to_entropy(), entropy);
+
+        assert!(decrypt_seed(salt, &encrypted_seed, "wrong passphrase").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::ffi;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -9,6 +10,7 @@
 use clap::Subcommand;
 use fedimint_bip39::Mnemonic;
 use fedimint_client::backup::Metadata;
+use fedimint_client::module::ClientModule as _;
 use fedimint_client::ClientHandleArc;
 use fedimint_core::config::{ClientModuleConfig, FederationId};
 use fedimint_core::core::{ModuleInstanceId, ModuleKind, OperationId};
@@ -16,12 +18,14 @@
 use fedimint_core::{Amount, BitcoinAmountOrAll, TieredCounts, TieredMulti};
 use fedimint_ln_client::cli::LnInvoiceResponse;
 use fedimint_ln_client::{
-    LightningClientModule, LnReceiveState, OutgoingLightningPayment, PayType,
+    LightningClientModule, LnReceiveState, OutgoingLightningPayment, PayType, PaymentFeeLimit,
 };
 use fedimint_logging::LOG_CLIENT;
 use fedimint_mint_client::{
     MintClientModule, OOBNotes, SelectNotesWithAtleastAmount, SelectNotesWithExactAmount,
 };
+use fedimint_wallet_client::bip21::Bip21Uri;
+use fedimint_wallet_client::bip322::AddressOwnershipProof;
 use fedimint_wallet_client::{WalletClientModule, WithdrawState};
 use futures::StreamExt;
 use itertools::Itertools;
@@ -32,6 +36,7 @@
 use time::OffsetDateTime;
 use tracing::{debug, info, warn};
 
+use crate::backup_kit::{self, BackupKit};
 use crate::metadata_from_clap_cli;
 
 #[derive(Debug, Clone)]
@@ -124,7 +129,7 @@ pub enum ClientCmd {
     LnPay {
         /// Lightning invoice or lnurl
         payment_info: String,
-        /// Amount to pay, used for lnurl
+        /// Amount to pay, used for lnurl and zero-amount invoices
         #[clap(long)]
         amount: Option<Amount>,
         /// Invoice comment/description, used on lnurl
@@ -137,9 +142,22 @@ pub enum ClientCmd {
         gateway_id: Option<secp256k1::PublicKey>,
         #[clap(long, default_value = "false")]
         force_internal: bool,
+        /// Abort if the gateway and routing fees exceed this amount
+        #[clap(long)]
+        max_fee: Option<Amount>,
+        /// Abort if the gateway and routing fees exceed this many parts per
+        /// million of the amount
+        #[clap(long)]
+        max_fee_ppm: Option<u64>,
     },
     /// Wait for a lightning payment to complete
     AwaitLnPay { operation_id: OperationId },
+    /// Try to cancel a pending operation and wait for its final state
+    ///
+    /// Only out-of-band e-cash spends whose notes weren't claimed yet are
+    /// supported. Lightning payments are funded right away and refunded if the
+    /// gateway fails to pay.
+    Cancel { operation_id: OperationId },
     /// List registered gateways
     ListGateways {
         /// Don't fetch the registered gateways from the federation
@@ -152,10 +170,19 @@ pub enum ClientCmd {
     AwaitDeposit { operation_id: OperationId },
     /// Withdraw funds from the federation
     Withdraw {
+        /// Required unless the address is a BIP-21 URI requesting an amount
         #[clap(long)]
-        amount: BitcoinAmountOrAll,
+        amount: Option<BitcoinAmountOrAll>,
+        /// Bitcoin address or BIP-21 `bitcoin:` URI
         #[clap(long)]
-        address: bitcoin::Address<NetworkUnchecked>,
+        address: String,
+        /// Message of a BIP-322 proof that the recipient controls the address,
+        /// the withdrawal is only made if the proof is valid
+        #[clap(long, requires = "ownership_proof_signature")]
+        ownership_proof_message: Option<String>,
+        /// Base64 encoded BIP-322 signature of `--ownership-proof-message`
+        #[clap(long, requires = "ownership_proof_message")]
+        ownership_proof_signature: Option<String>,
     },
     /// Upload the (encrypted) snapshot of mint notes to federation
     Backup {
@@ -178,6 +205,14 @@ pub enum ClientCmd {
     },
     /// Print the secret key of the client
     PrintSecret,
+    /// Write a printable recovery kit with the federation invite and the
+    /// client seed encrypted with a passphrase, which is read from the
+    /// `FM_BACKUP_KIT_PASSPHRASE` env variable or stdin
+    BackupKit {
+        /// Text file to write the kit to, must not exist yet
+        #[clap(long)]
+        out_file: PathBuf,
+    },
     ListOperations {
         #[clap(long, default_value = "10")]
         limit: usize,
@@ -398,6 +433,8 @@ pub async fn handle_command(
             lnurl_comment,
             gateway_id,
             force_internal,
+            max_fee,
+            max_fee_ppm,
         } => {
             warn!(
                 target: LOG_CLIENT,
@@ -417,7 +454,16 @@ pub async fn handle_command(
                 contract_id,
                 fee,
             } = lightning_module
-                .pay_bolt11_invoice(ln_gateway, bolt11, ())
+                .pay_bolt11_invoice_with_fee_limit(
+                    ln_gateway,
+                    bolt11,
+                    amount,
+                    PaymentFeeLimit {
+                        max_fee,
+                        max_fee_ppm,
+                    },
+                    (),
+                )
                 .await?;
             let operation_id = payment_type.operation_id();
             info!(
@@ -465,6 +511,7 @@ pub async fn handle_command(
                 .await?
                 .context("expected a response")?)
         }
+        ClientCmd::Cancel { operation_id } => cancel_operation(&client, operation_id).await,
         ClientCmd::ListGateways { no_update } => {
             let lightning_module = client.get_first_module::<LightningClientModule>()?;
             if !no_update {
@@ -518,6 +565,39 @@ pub async fn handle_command(
                 "secret": mnemonic,
             }))
         }
+        ClientCmd::BackupKit { out_file } => {
+            let passphrase = backup_kit::read_passphrase()?;
+            let entropy = client.get_decoded_client_secret::<Vec<u8>>().await?;
+            let peer = *client
+                .get_peer_urls()
+                .await
+                .keys()
+                .next()
+                .context("Federation has no guardians")?;
+            let invite_code = client
+                .invite_code(peer)
+                .await
+                .context("Could not create an invite code")?;
+            let kit = BackupKit::new(
+                client.get_meta("federation_name"),
+                client.federation_id(),
+                invite_code,
+                entropy,
+                &passphrase,
+            )?;
+
+            std::fs::File::options()
+                .write(true)
+                .create_new(true)
+                .open(&out_file)
+                .and_then(|mut file| std::io::Write::write_all(&mut file, kit.render().as_bytes()))
+                .with_context(|| format!("Could not write {}", out_file.display()))?;
+
+            Ok(json!({
+                "federation_id": kit.federation_id,
+                "out_file": out_file,
+            }))
+        }
         ClientCmd::ListOperations { limit } => {
             #[derive(Serialize)]
             #[serde(rename_all = "snake_case")]
@@ -552,15 +632,41 @@ struct OperationOutput {
                 "operations": operations,
             }))
         }
-        ClientCmd::Withdraw { amount, address } => {
+        ClientCmd::Withdraw {
+            amount,
+            address,
+            ownership_proof_message,
+            ownership_proof_signature,
+        } => {
             let wallet_module = client.get_first_module::<WalletClientModule>()?;
-            let address = address.require_network(wallet_module.get_network())?;
+            let uri = if Bip21Uri::is_uri(&address) {
+                address.parse::<Bip21Uri>()?
+            } else {
+                Bip21Uri::from(address.parse::<bitcoin::Address<NetworkUnchecked>>()?)
+            };
+            let address = uri
+                .address
+                .clone()
+                .require_network(wallet_module.get_network())?;
+            let amount = match (amount, uri.amount) {
+                (Some(amount), None) => amount,
+                (None, Some(requested)) => BitcoinAmountOrAll::Amount(requested),
+                (Some(BitcoinAmountOrAll::Amount(amount)), Some(requested))
+                    if amount == requested =>
+                {
+                    BitcoinAmountOrAll::Amount(amount)
+                }
+                (Some(amount), Some(requested)) => {
+                    bail!("Amount {amount} conflicts with the {requested} requested by the URI")
+                }
+                (None, None) => bail!("No amount given and the URI doesn't request one"),
+            };
             let (amount, fees) = match amount {
                 // If the amount is "all", then we need to subtract the fees from
                 // the amount we are withdrawing
                 BitcoinAmountOrAll::All => {
                     let balance =
-                        bitcoin::Amount::from_sat(client.get_balance().await.msats / 1000);
+                        bitcoin::Amount::from_sat(client.get_balance().await.sats_round_down());
                     let fees = wallet_module.get_withdraw_fees(&address, balance).await?;
                     let amount = balance.checked_sub(fees.amount());
                     if amount.is_none() {
@@ -580,7 +686,12 @@ struct OperationOutput {
                 "Attempting withdraw with fees: {fees:?}"
             );
 
-            let operation_id = wallet_module.withdraw(&address, amount, fees, ()).await?;
+            let ownership_proof = ownership_proof_message
+                .zip(ownership_proof_signature)
+                .map(|(message, signature)| AddressOwnershipProof { message, signature });
+            let operation_id = wallet_module
+                .withdraw_to_uri_with_ownership_proof(&uri, amount, fees, ownership_proof, ())
+                .await?;
 
             let mut updates = wallet_module
                 .subscribe_withdraw_updates(operation_id)
@@ -655,18 +766,64 @@ struct OperationOutput {
     }
 }
 
+async fn cancel_operation(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+) -> anyhow::Result<serde_json::Value> {
+    let operation = client
+        .operation_log()
+        .get_operation(operation_id)
+        .await
+        .context("Operation not found")?;
+
+    if operation.outcome::<serde_json::Value>().is_some() {
+        bail!("Operation already completed");
+    }
+
+    let operation_kind = operation.operation_module_kind();
+    ensure_cancelable(operation_kind)?;
+
+    let mint = client.get_first_module::<MintClientModule>()?;
+    let mut updates = mint
+        .subscribe_spend_notes(operation_id)
+        .await?
+        .into_stream();
+
+    mint.try_cancel_spend_notes(operation_id).await;
+
+    let mut last_state = None;
+    while let Some(state) = updates.next().await {
+        debug!(target: LOG_CLIENT, ?state, "Canceling OOB spend");
+        last_state = Some(state);
+    }
+    let state = last_state.context("Spend operation ended without a state")?;
+
+    Ok(json!({
+        "operation_id": operation_id,
+        "operation_kind": operation_kind,
+        "state": state,
+    }))
+}
+
+/// Fails unless operations of `operation_kind` can be canceled by
+/// [`cancel_operation`]
+fn ensure_cancelable(operation_kind: &str) -> anyhow::Result<()> {
+    if operation_kind == LightningClientModule::kind().as_str() {
+        bail!(
+            "Lightning payments can't be canceled, they are refunded if the gateway fails to pay"
+        );
+    }
+    if operation_kind != MintClientModule::kind().as_str() {
+        bail!("Canceling {operation_kind} operations is not supported");
+    }
+    Ok(())
+}
+
 async fn get_note_summary(client: &ClientHandleArc) -> anyhow::Result<serde_json::Value> {
     let mint_client = client.get_first_module::<MintClientModule>()?;
     let wallet_client = client.get_first_module::<WalletClientModule>()?;
     let summary = mint_client
-        .get_note_counts_by_denomination(
-            &mut client
-                .db()
-                .begin_transaction_nc()
-                .await
-                .to_ref_with_prefix_module_id(1)
-                .0,
-        )
+        .get_note_counts_by_denomination(&mut mint_client.db.begin_transaction_nc().await)
         .await;
     Ok(serde_json::to_value(InfoResponse {
         federation_id: client.federation_id(),
@@ -706,3 +863,20 @@ pub(crate) fn time_to_iso8601(time: &SystemTime) -> String {
     .format(&iso8601::Iso8601::<ISO8601_CONFIG>)
     .expect("Couldn't format OffsetDateTime as ISO8601")
 }
+
+#[cfg(test)]
+mod tests {
+    use fedimint_client::module::ClientModule;
+    use fedimint_ln_client::LightningClientModule;
+    use fedimint_mint_client::MintClientModule;
+    use fedimint_wallet_client::WalletClientModule;
+
+    use super::ensure_cancelable;
+
+    #[test]
+    fn only_ecash_spends_can_be_canceled() {
+        assert!(ensure_cancelable(MintClientModule::kind().as_str()).is_ok());
+        assert!(ensure_cancelable(LightningClientModule::kind().as_str()).is_err());
+        assert!(ensure_cancelable(WalletClientModule::kind().as_str()).is_err());
+    }
+}
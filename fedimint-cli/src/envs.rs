@@ -11,6 +11,9 @@
 // Env variable to use Tor connector, instead of default Tcp/ClearNet.
 pub const FM_USE_TOR_ENV: &str = "FM_USE_TOR";
 
+// Env variable to set the passphrase encrypting the seed of a backup kit
+pub const FM_BACKUP_KIT_PASSPHRASE_ENV: &str = "FM_BACKUP_KIT_PASSPHRASE";
+
 // Api authentication secret
 pub const FM_API_SECRET_ENV: &str = "FM_API_SECRET";
 
@@ -8,6 +8,7 @@
 #![allow(clippy::return_self_not_must_use)]
 #![allow(clippy::too_many_lines)]
 
+mod backup_kit;
 mod client;
 mod db_locked;
 pub mod envs;
@@ -42,18 +43,24 @@
 use fedimint_client::{AdminCreds, Client, ClientBuilder, ClientHandleArc};
 use fedimint_core::admin_client::{ConfigGenConnectionsRequest, ConfigGenParamsRequest};
 use fedimint_core::config::{
-    FederationId, FederationIdPrefix, ServerModuleConfigGenParamsRegistry,
+    ClientConfig, FederationId, FederationIdPrefix, JsonClientConfig,
+    ServerModuleConfigGenParamsRegistry,
 };
 use fedimint_core::core::{ModuleInstanceId, OperationId};
 use fedimint_core::db::{Database, DatabaseValue};
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::module::{ApiAuth, ApiRequestErased};
-use fedimint_core::util::{backoff_util, handle_version_hash_command, retry, SafeUrl};
-use fedimint_core::{fedimint_build_code_version_env, runtime, Amount, PeerId, TieredMulti};
+use fedimint_core::net::admin_auth::AddAdminKeyRequest;
+use fedimint_core::secp256k1::{Keypair, PublicKey, SecretKey, SECP256K1};
+use fedimint_core::util::{backoff_util, confirm, handle_version_hash_command, retry, SafeUrl};
+use fedimint_core::{
+    fedimint_build_code_version_env, runtime, Amount, PeerId, TieredMulti, AMOUNT_FORMAT_HELP,
+};
 use fedimint_eventlog::EventLogId;
 use fedimint_ln_client::LightningClientInit;
 use fedimint_logging::{TracingSetup, LOG_CLIENT};
 use fedimint_meta_client::{MetaClientInit, MetaModuleMetaSourceWithFallback};
+use fedimint_mint_client::config::MintClientConfig;
 use fedimint_mint_client::{MintClientInit, MintClientModule, OOBNotes, SpendableNote};
 use fedimint_wallet_client::api::WalletFederationApi;
 use fedimint_wallet_client::{WalletClientInit, WalletClientModule};
@@ -97,6 +104,8 @@ enum CliOutput {
 
     JoinFederation {
         joined: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        preview: Option<Value>,
     },
 
     DecodeTransaction {
@@ -199,7 +208,7 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 }
 
 #[derive(Parser, Clone)]
-#[command(version)]
+#[command(version, after_help = AMOUNT_FORMAT_HELP)]
 struct Opts {
     /// The working directory of the client containing the config and db
     #[arg(long = "data-dir", env = FM_CLIENT_DIR_ENV)]
@@ -329,18 +338,35 @@ enum Command {
     #[clap(subcommand)]
     Dev(DevCmd),
 
+    /// Inspect ecash notes without importing them
+    #[clap(subcommand)]
+    Note(NoteCmd),
+
     /// Config enabling client to establish websocket connection to federation
-    InviteCode {
-        peer: PeerId,
-    },
+    InviteCode { peer: PeerId },
 
     /// Join a federation using its InviteCode
     JoinFederation {
         invite_code: String,
+        /// Show the guardians, modules and metadata of the federation and only
+        /// join it once confirmed
+        #[clap(long)]
+        preview: bool,
     },
 
-    Completion {
-        shell: clap_complete::Shell,
+    /// Print a shell completion script, e.g. for bash, zsh or fish
+    Completion { shell: clap_complete::Shell },
+
+    /// Decrypt the seed of a recovery kit written by `backup-kit` and print
+    /// its mnemonic, to restore the client with. The passphrase is read from
+    /// the `FM_BACKUP_KIT_PASSPHRASE` env variable or stdin.
+    DecryptBackupKitSeed {
+        /// Salt printed on the kit
+        #[clap(long)]
+        salt: String,
+        /// Encrypted seed printed on the kit, whitespace is ignored
+        #[clap(long)]
+        encrypted_seed: String,
     },
 }
 
@@ -353,6 +379,9 @@ enum AdminCmd {
     /// Show an audit across all modules
     Audit,
 
+    /// Show how this guardian sees its connections to the other guardians
+    PeerConnectivity,
+
     /// Download guardian config to back it up
     GuardianConfigBackup,
 
@@ -372,6 +401,22 @@ enum AdminCmd {
         /// Session index to stop after
         session_idx: u64,
     },
+    /// Authorize a key to call admin endpoints of this guardian, e.g. for
+    /// operator tooling that shouldn't know the guardian password
+    AddAdminKey {
+        public_key: PublicKey,
+        /// Describes who holds the key
+        #[clap(long)]
+        label: String,
+    },
+    /// Revoke a previously authorized admin key
+    RemoveAdminKey {
+        public_key: PublicKey,
+    },
+    /// List the authorized admin keys
+    AdminKeys,
+    /// Show the transaction fees collected by the federation
+    FeeRevenue,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -425,6 +470,19 @@ enum DkgAdminCmd {
     StartConsensus,
 }
 
+#[derive(Debug, Clone, Subcommand)]
+enum NoteCmd {
+    /// Print denominations, total amount and federation of ecash notes. If
+    /// `--config` is given the note signatures are verified too.
+    Decode {
+        notes: OOBNotes,
+        /// Client config JSON of the issuing federation (as output by
+        /// `fedimint-cli config`) used to verify the note signatures
+        #[clap(long)]
+        config: Option<PathBuf>,
+    },
+}
+
 #[derive(Debug, Clone, Subcommand)]
 enum DecodeType {
     /// Decode an invite code string into a JSON representation
@@ -485,10 +543,21 @@ enum DevCmd {
         /// called. Only use together with --peer-id.
         #[clap(long, requires = "peer_id")]
         password: Option<String>,
+        /// Admin key authorized by the guardian to sign the request with,
+        /// instead of using the guardian password
+        #[clap(long, requires = "peer_id", conflicts_with = "password")]
+        admin_key: Option<SecretKey>,
     },
 
     ApiAnnouncements,
 
+    /// Manually set the API URL of a guardian, overriding the config and API
+    /// announcements. Omit the URL to remove a previously set override.
+    SetPeerUrlOverride {
+        peer_id: PeerId,
+        url: Option<SafeUrl>,
+    },
+
     /// Advance the note_idx
     AdvanceNoteIdx {
         #[clap(long, default_value = "1")]
@@ -676,6 +745,16 @@ async fn client_join(
             .await
             .map_err_cli()?;
 
+        self.client_join_with_config(cli, invite_code, client_config)
+            .await
+    }
+
+    async fn client_join_with_config(
+        &mut self,
+        cli: &Opts,
+        invite_code: InviteCode,
+        client_config: ClientConfig,
+    ) -> CliResult<ClientHandleArc> {
         let client_builder = self.make_client_builder(cli).await?;
 
         let mnemonic = load_or_generate_mnemonic(client_builder.db_no_decoders()).await?;
@@ -790,17 +869,56 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
 
                 Ok(CliOutput::InviteCode { invite_code })
             }
-            Command::JoinFederation { invite_code } => {
-                {
+            Command::JoinFederation {
+                invite_code,
+                preview,
+            } => {
+                let preview = {
                     let invite_code: InviteCode = InviteCode::from_str(&invite_code)
                         .map_err_cli_msg("invalid invite code")?;
 
-                    // Build client and store config in DB
-                    let _client = self.client_join(&cli, invite_code).await?;
-                }
+                    if preview {
+                        let client_config = cli
+                            .connector()
+                            .download_from_invite_code(&invite_code)
+                            .await
+                            .map_err_cli()?;
+                        let decoders = self
+                            .module_inits
+                            .available_decoders(client_config.modules.iter().map(
+                                |(module_instance_id, module_config)| {
+                                    (*module_instance_id, &module_config.kind)
+                                },
+                            ))
+                            .map_err_cli()?;
+                        let client_config = client_config.redecode_raw(&decoders).map_err_cli()?;
+
+                        let preview = join_preview(&client_config);
+                        eprintln!(
+                            "{}",
+                            serde_json::to_string_pretty(&preview).expect("Can be serialized")
+                        );
+                        if !confirm("Join this federation?").map_err_cli()? {
+                            return Err(CliError {
+                                error: "Joining the federation was aborted".to_owned(),
+                            });
+                        }
+
+                        // Build client and store config in DB
+                        let _client = self
+                            .client_join_with_config(&cli, invite_code, client_config)
+                            .await?;
+                        Some(preview)
+                    } else {
+                        // Build client and store config in DB
+                        let _client = self.client_join(&cli, invite_code).await?;
+                        None
+                    }
+                };
 
                 Ok(CliOutput::JoinFederation {
                     joined: invite_code,
+                    preview,
                 })
             }
             Command::VersionHash => Ok(CliOutput::VersionHash {
@@ -843,6 +961,17 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
                     serde_json::to_value(audit).map_err_cli_msg("invalid response")?,
                 ))
             }
+            Command::Admin(AdminCmd::PeerConnectivity) => {
+                let client = self.client_open(&cli).await?;
+
+                let peer_connectivity = cli
+                    .admin_client(&client.get_peer_urls().await, client.api_secret())?
+                    .peer_connectivity(cli.auth()?)
+                    .await?;
+                Ok(CliOutput::Raw(
+                    serde_json::to_value(peer_connectivity).map_err_cli_msg("invalid response")?,
+                ))
+            }
             Command::Admin(AdminCmd::Status) => {
                 let client = self.client_open(&cli).await?;
 
@@ -898,6 +1027,14 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
                     serde_json::to_value(announcement).map_err_cli_msg("invalid response")?,
                 ))
             }
+            Command::Admin(AdminCmd::FeeRevenue) => {
+                let client = self.client_open(&cli).await?;
+
+                let fee_revenue = client.api().fee_revenue().await?;
+                Ok(CliOutput::Raw(
+                    serde_json::to_value(fee_revenue).map_err_cli_msg("invalid response")?,
+                ))
+            }
             Command::Admin(AdminCmd::Shutdown { session_idx }) => {
                 let client = self.client_open(&cli).await?;
 
@@ -907,11 +1044,42 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
 
                 Ok(CliOutput::Raw(json!(null)))
             }
+            Command::Admin(AdminCmd::AddAdminKey { public_key, label }) => {
+                let client = self.client_open(&cli).await?;
+
+                cli.admin_client(&client.get_peer_urls().await, client.api_secret())?
+                    .add_admin_key(AddAdminKeyRequest { public_key, label }, cli.auth()?)
+                    .await?;
+
+                Ok(CliOutput::Raw(json!(null)))
+            }
+            Command::Admin(AdminCmd::RemoveAdminKey { public_key }) => {
+                let client = self.client_open(&cli).await?;
+
+                cli.admin_client(&client.get_peer_urls().await, client.api_secret())?
+                    .remove_admin_key(public_key, cli.auth()?)
+                    .await?;
+
+                Ok(CliOutput::Raw(json!(null)))
+            }
+            Command::Admin(AdminCmd::AdminKeys) => {
+                let client = self.client_open(&cli).await?;
+
+                let admin_keys = cli
+                    .admin_client(&client.get_peer_urls().await, client.api_secret())?
+                    .admin_keys(cli.auth()?)
+                    .await?;
+
+                Ok(CliOutput::Raw(
+                    serde_json::to_value(admin_keys).map_err_cli_msg("invalid response")?,
+                ))
+            }
             Command::Dev(DevCmd::Api {
                 method,
                 params,
                 peer_id,
                 password: auth,
+                admin_key,
             }) => {
                 //Parse params to JSON.
                 //If fails, convert to JSON string.
@@ -929,6 +1097,17 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
                     params = params.with_auth(ApiAuth(auth));
                 }
                 let client = self.client_open(&cli).await?;
+                if let Some(admin_key) = admin_key {
+                    let admin_key = Keypair::from_secret_key(SECP256K1, &admin_key);
+                    let peer_id = peer_id.ok_or_cli_msg("--admin-key requires --peer-id")?;
+                    params = params.with_admin_signature(
+                        SECP256K1,
+                        &admin_key,
+                        client.federation_id(),
+                        peer_id.into(),
+                        &method,
+                    );
+                }
 
                 let ws_api: Arc<_> = WsFederationApi::new(
                     &cli.connector(),
@@ -971,6 +1150,17 @@ async fn handle_command(&mut self, cli: Opts) -> CliOutputResult {
                     serde_json::to_value(announcements).expect("Can be encoded"),
                 ))
             }
+            Command::Dev(DevCmd::SetPeerUrlOverride { peer_id, url }) => {
+                let client = self.client_open(&cli).await?;
+                client
+                    .set_peer_url_override(peer_id, url)
+                    .await
+                    .map_err_cli()?;
+                Ok(CliOutput::Raw(
+                    serde_json::to_value(client.get_peer_url_overrides().await)
+                        .expect("Can be encoded"),
+                ))
+            }
             Command::Dev(DevCmd::WaitBlockCount { count: target }) => retry(
                 "wait_block_count",
                 backoff_util::custom_backoff(
@@ -1198,6 +1388,20 @@ struct ReactorLogState {
                     serde_json::to_value(events).expect("Can be encoded"),
                 ))
             }
+            Command::Note(NoteCmd::Decode { notes, config }) => {
+                let config = match config {
+                    Some(path) => {
+                        let config = fs::read_to_string(&path)
+                            .map_err_cli_msg("failed to read client config")?;
+                        Some(
+                            serde_json::from_str::<JsonClientConfig>(&config)
+                                .map_err_cli_msg("invalid client config")?,
+                        )
+                    }
+                    None => None,
+                };
+                Ok(CliOutput::Raw(inspect_notes(&notes, config.as_ref())?))
+            }
             Command::Completion { shell } => {
                 clap_complete::generate(
                     shell,
@@ -1208,6 +1412,17 @@ struct ReactorLogState {
                 // HACK: prints true to stdout which is fine for shells
                 Ok(CliOutput::Raw(serde_json::Value::Bool(true)))
             }
+            Command::DecryptBackupKitSeed {
+                salt,
+                encrypted_seed,
+            } => {
+                let passphrase = backup_kit::read_passphrase().map_err_cli()?;
+                let mnemonic =
+                    backup_kit::decrypt_seed(&salt, &encrypted_seed, &passphrase).map_err_cli()?;
+                Ok(CliOutput::Raw(json!({
+                    "mnemonic": mnemonic,
+                })))
+            }
         }
     }
 
@@ -1312,6 +1527,75 @@ fn metadata_from_clap_cli(metadata: Vec<String>) -> Result<BTreeMap<String, Stri
     Ok(metadata)
 }
 
+/// Summarize `notes` without importing them, verifying their signatures if
+/// the issuing federation's client `config` is given
+fn inspect_notes(notes: &OOBNotes, config: Option<&JsonClientConfig>) -> CliResult<Value> {
+    let federation_id_prefix = notes.federation_id_prefix();
+    let mut output = json!({
+        "federation_id_prefix": federation_id_prefix.to_string(),
+        "federation_id": notes.federation_invite().map(|invite| invite.federation_id()),
+        "total_amount_msat": notes.total_amount(),
+        "note_count": notes.notes().count_items(),
+        "notes_per_denomination": notes.notes().summary(),
+    });
+
+    if let Some(config) = config {
+        let federation_id = config.global.calculate_federation_id();
+        if federation_id.to_prefix() != federation_id_prefix {
+            return Err(CliError {
+                error: format!("Notes were not issued by federation {federation_id}"),
+            });
+        }
+
+        let mint_config = config
+            .modules
+            .values()
+            .find(|module| module.is_kind(&fedimint_mint_client::KIND))
+            .ok_or_cli_msg("client config contains no mint module")?;
+        let mint_config: MintClientConfig = serde_json::from_value(mint_config.value().clone())
+            .map_err_cli_msg("invalid mint module config")?;
+
+        let invalid_notes = notes
+            .notes()
+            .iter_items()
+            .filter(|(amount, note)| {
+                mint_config
+                    .tbs_pks
+                    .get(*amount)
+                    .map_or(true, |pk| !note.verify(*pk))
+            })
+            .map(|(amount, note)| json!({ "amount_msat": amount, "nonce": note.nonce() }))
+            .collect::<Vec<_>>();
+
+        output["federation_id"] = json!(federation_id);
+        output["signatures_valid"] = json!(invalid_notes.is_empty());
+        output["invalid_notes"] = json!(invalid_notes);
+    }
+
+    Ok(output)
+}
+
+/// Summary of a federation's client config to review before joining it
+fn join_preview(client_config: &ClientConfig) -> Value {
+    let JsonClientConfig { global, modules } = client_config.to_json();
+    json!({
+        "federation_id": client_config.calculate_federation_id(),
+        "federation_name": global.federation_name(),
+        "guardians": global
+            .api_endpoints
+            .iter()
+            .map(|(peer_id, peer_url)| json!({
+                "peer_id": peer_id,
+                "name": peer_url.name,
+                "url": peer_url.url,
+            }))
+            .collect::<Vec<_>>(),
+        // Module configs contain the fees the federation charges
+        "modules": modules,
+        "meta": global.meta,
+    })
+}
+
 #[test]
 fn metadata_from_clap_cli_test() {
     for (args, expected) in [
@@ -234,6 +234,11 @@ async fn set_tx_savepoint(&mut self) -> Result<()> {
         self.num_savepoint_operations = self.num_pending_operations;
         Ok(())
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        // Only a single savepoint is kept, which stays valid until replaced
+        Ok(())
+    }
 }
 
 // In-memory database transaction should only be used for test code and never
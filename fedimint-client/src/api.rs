@@ -6,6 +6,7 @@
 use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{Database, DatabaseTransaction};
 use fedimint_core::task::{MaybeSend, MaybeSync};
+use fedimint_core::util::SafeUrl;
 use fedimint_core::{apply, async_trait_maybe_send, PeerId};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -165,4 +166,8 @@ async fn request_raw(
 
         res
     }
+
+    async fn set_peer_url(&self, peer_id: PeerId, url: SafeUrl) {
+        self.inner.set_peer_url(peer_id, url).await;
+    }
 }
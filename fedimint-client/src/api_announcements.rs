@@ -13,6 +13,7 @@
 use fedimint_core::{impl_db_lookup, impl_db_record, PeerId};
 use fedimint_logging::LOG_CLIENT;
 use futures::future::join_all;
+use futures::StreamExt;
 use tracing::{info, warn};
 
 use crate::db::DbKeyPrefix;
@@ -35,6 +36,23 @@
     query_prefix = ApiAnnouncementPrefix
 );
 
+/// API URL of a guardian set manually by the user, takes precedence over both
+/// the config and any announcements. Useful if a guardian moved to a new
+/// endpoint and the client can't reach any peer to learn about it.
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ApiUrlOverrideKey(pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ApiUrlOverridePrefix;
+
+impl_db_record!(
+    key = ApiUrlOverrideKey,
+    value = SafeUrl,
+    db_prefix = DbKeyPrefix::ApiUrlOverride,
+    notify_on_modify = false,
+);
+impl_db_lookup!(key = ApiUrlOverrideKey, query_prefix = ApiUrlOverridePrefix);
+
 /// Fetches API URL announcements from guardians, validates them and updates the
 /// DB if any new more upt to date ones are found.
 pub async fn run_api_announcement_sync(client_inner: Arc<Client>) {
@@ -108,16 +126,23 @@ pub async fn run_api_announcement_sync(client_inner: Arc<Client>) {
             }
         }
 
+        // Switch our connections over to announced URLs right away, so guardians can
+        // move without users having to restart or rejoin
+        for (peer_id, api_url) in get_api_urls(&client_inner.db, &client_inner.config().await).await
+        {
+            client_inner.api.set_peer_url(peer_id, api_url).await;
+        }
+
         // Check once an hour if there are new announcements
         sleep(Duration::from_secs(3600)).await;
     }
 }
 
 /// Returns a list of all peers and their respective API URLs taking into
-/// account announcements overwriting the URLs contained in the original
-/// configuration.
+/// account announcements and manual overrides overwriting the URLs contained
+/// in the original configuration.
 pub async fn get_api_urls(db: &Database, cfg: &ClientConfig) -> BTreeMap<PeerId, SafeUrl> {
-    override_api_urls(
+    let mut api_urls = override_api_urls(
         db,
         cfg.global
             .api_endpoints
@@ -126,5 +151,102 @@ pub async fn get_api_urls(db: &Database, cfg: &ClientConfig) -> BTreeMap<PeerId,
         &ApiAnnouncementPrefix,
         |key| key.0,
     )
-    .await
+    .await;
+
+    let overrides = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&ApiUrlOverridePrefix)
+        .await
+        .map(|(key, url)| (key.0, url))
+        .collect::<Vec<_>>()
+        .await;
+
+    for (peer_id, url) in overrides {
+        // Only override peers that are actually part of the federation
+        if let Some(api_url) = api_urls.get_mut(&peer_id) {
+            *api_url = url;
+        }
+    }
+
+    api_urls
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    use fedimint_core::config::{ClientConfig, GlobalClientConfig, PeerUrl};
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+    use fedimint_core::module::registry::ModuleDecoderRegistry;
+    use fedimint_core::module::CoreConsensusVersion;
+    use fedimint_core::net::api_announcement::ApiAnnouncement;
+    use fedimint_core::secp256k1::rand::thread_rng;
+    use fedimint_core::secp256k1::{Keypair, SECP256K1};
+    use fedimint_core::util::SafeUrl;
+    use fedimint_core::PeerId;
+
+    use super::{get_api_urls, ApiAnnouncementKey, ApiUrlOverrideKey};
+
+    fn url(s: &str) -> SafeUrl {
+        SafeUrl::from_str(s).expect("valid url")
+    }
+
+    #[tokio::test]
+    async fn announced_and_overridden_urls_replace_config_urls() {
+        let config = ClientConfig {
+            global: GlobalClientConfig {
+                api_endpoints: (0..3)
+                    .map(|peer| {
+                        (
+                            PeerId::from(peer),
+                            PeerUrl {
+                                url: url(&format!("wss://config-{peer}.example")),
+                                name: format!("peer-{peer}"),
+                            },
+                        )
+                    })
+                    .collect(),
+                broadcast_public_keys: None,
+                consensus_version: CoreConsensusVersion::new(0, 0),
+                meta: BTreeMap::new(),
+            },
+            modules: BTreeMap::new(),
+        };
+        let guardian_key = Keypair::new(SECP256K1, &mut thread_rng());
+        let db = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+
+        let mut dbtx = db.begin_transaction().await;
+        for peer in [1, 2] {
+            dbtx.insert_entry(
+                &ApiAnnouncementKey(PeerId::from(peer)),
+                &ApiAnnouncement::new(url(&format!("wss://announced-{peer}.example")), 1)
+                    .sign(SECP256K1, &guardian_key),
+            )
+            .await;
+        }
+        dbtx.insert_entry(
+            &ApiUrlOverrideKey(PeerId::from(2)),
+            &url("wss://override-2.example"),
+        )
+        .await;
+        // Overrides for peers that aren't part of the federation are ignored
+        dbtx.insert_entry(
+            &ApiUrlOverrideKey(PeerId::from(3)),
+            &url("wss://override-3.example"),
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        assert_eq!(
+            get_api_urls(&db, &config).await,
+            BTreeMap::from([
+                (PeerId::from(0), url("wss://config-0.example")),
+                (PeerId::from(1), url("wss://announced-1.example")),
+                (PeerId::from(2), url("wss://override-2.example")),
+            ])
+        );
+    }
 }
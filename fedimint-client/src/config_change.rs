@@ -0,0 +1,221 @@
+//! Detection of changes to the federation's client config
+//!
+//! The client config is downloaded once when joining and then used as is,
+//! but guardians may change parts of it later, e.g. a module's fee schedule or
+//! keys. Operations in flight keep validating the federation's responses
+//! against the stored config, so they would silently stall or fail.
+//!
+//! The client therefore periodically fetches the current config from the
+//! federation and records any difference as a pending
+//! [`FederationConfigChange`], publishing [`ClientEvent::ConfigChanged`].
+//!
+//! This only detects and reports changes, it doesn't migrate running
+//! operations:
+//!
+//! * Modules are constructed from the config once, so a change accepted with
+//!   [`crate::Client::apply_config_change`] only takes effect once the client
+//!   is restarted. Until then running state machines keep validating against
+//!   the config the client was started with and are not re-validated.
+//! * Update streams only report a change if their module opts in with
+//!   [`crate::module::ClientContext::await_config_change`], which currently
+//!   only the mint's reissue stream does. Other embedders have to subscribe to
+//!   [`ClientEvent::ConfigChanged`] or poll
+//!   [`crate::Client::pending_config_change`].
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use fedimint_api_client::api::{FederationApiExt, FederationResult};
+use fedimint_core::config::ClientConfig;
+use fedimint_core::core::{ModuleInstanceId, MODULE_INSTANCE_ID_GLOBAL};
+use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::endpoint_constants::CLIENT_CONFIG_ENDPOINT;
+use fedimint_core::module::ApiRequestErased;
+use fedimint_core::runtime::sleep;
+use fedimint_core::{impl_db_record, time};
+use fedimint_logging::LOG_CLIENT;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::db::DbKeyPrefix;
+use crate::event_bus::ClientEvent;
+use crate::Client;
+
+/// How often the federation is asked for its current client config
+pub const CONFIG_CHANGE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A change of the federation's client config that wasn't applied yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct FederationConfigChange {
+    pub detected_at: SystemTime,
+    /// Config currently returned by the federation
+    pub new_config: ClientConfig,
+    /// Module instances whose config was changed, added or removed
+    pub changed_modules: BTreeSet<ModuleInstanceId>,
+    /// Whether the consensus version or the guardians' keys changed
+    pub global_changed: bool,
+}
+
+impl FederationConfigChange {
+    /// Returns true if the change can affect operations of `module_instance_id`
+    pub fn affects_module(&self, module_instance_id: ModuleInstanceId) -> bool {
+        self.global_changed || self.changed_modules.contains(&module_instance_id)
+    }
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct PendingConfigChangeKey;
+
+impl_db_record!(
+    key = PendingConfigChangeKey,
+    value = FederationConfigChange,
+    db_prefix = DbKeyPrefix::PendingConfigChange,
+    notify_on_modify = true,
+);
+
+/// Compares the stored config `old` to the federation's `new` one, returning
+/// the changed module instances and whether the global config changed, or
+/// `None` if nothing relevant to operations changed. API endpoints and meta
+/// fields are kept up to date by other means and ignored.
+pub fn diff_configs(
+    old: &ClientConfig,
+    new: &ClientConfig,
+) -> Option<(BTreeSet<ModuleInstanceId>, bool)> {
+    let changed_modules = old
+        .modules
+        .keys()
+        .chain(new.modules.keys())
+        .filter(|module_instance_id| {
+            old.modules.get(module_instance_id) != new.modules.get(module_instance_id)
+        })
+        .copied()
+        .collect::<BTreeSet<_>>();
+
+    // Configs downloaded before 0.4 lack the guardian keys, gaining them isn't a
+    // change
+    let keys_changed = match (
+        &old.global.broadcast_public_keys,
+        &new.global.broadcast_public_keys,
+    ) {
+        (Some(old_keys), Some(new_keys)) => old_keys != new_keys,
+        _ => false,
+    };
+    let global_changed =
+        keys_changed || old.global.consensus_version != new.global.consensus_version;
+
+    (global_changed || !changed_modules.is_empty()).then_some((changed_modules, global_changed))
+}
+
+/// Periodically compares the federation's client config to the stored one,
+/// recording changes as a pending [`FederationConfigChange`]
+pub async fn run_config_change_detection(client: Arc<Client>) {
+    loop {
+        match fetch_current_config(&client).await {
+            Ok(current_config) => record_config_change(&client, current_config).await,
+            Err(e) => debug!(target: LOG_CLIENT, %e, "Failed to fetch current client config"),
+        }
+
+        sleep(CONFIG_CHANGE_CHECK_INTERVAL).await;
+    }
+}
+
+async fn fetch_current_config(client: &Client) -> anyhow::Result<ClientConfig> {
+    let config: FederationResult<ClientConfig> = client
+        .api
+        .request_current_consensus(
+            CLIENT_CONFIG_ENDPOINT.to_owned(),
+            ApiRequestErased::default(),
+        )
+        .await;
+
+    Ok(config?.redecode_raw(client.decoders())?)
+}
+
+async fn record_config_change(client: &Client, current_config: ClientConfig) {
+    let Some((changed_modules, global_changed)) =
+        diff_configs(&client.config().await, &current_config)
+    else {
+        return;
+    };
+
+    let mut dbtx = client.db().begin_transaction().await;
+
+    if dbtx
+        .get_value(&PendingConfigChangeKey)
+        .await
+        .is_some_and(|pending| pending.new_config == current_config)
+    {
+        return;
+    }
+
+    warn!(
+        target: LOG_CLIENT,
+        ?changed_modules,
+        %global_changed,
+        "Federation changed its client config"
+    );
+
+    dbtx.insert_entry(
+        &PendingConfigChangeKey,
+        &FederationConfigChange {
+            detected_at: time::now(),
+            new_config: current_config,
+            changed_modules: changed_modules.clone(),
+            global_changed,
+        },
+    )
+    .await;
+    client.publish_client_event_dbtx(
+        &mut dbtx,
+        MODULE_INSTANCE_ID_GLOBAL,
+        ClientEvent::ConfigChanged {
+            changed_modules,
+            global_changed,
+        },
+    );
+
+    if let Err(e) = dbtx.commit_tx_result().await {
+        info!(target: LOG_CLIENT, %e, "Failed to record client config change, will retry");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fedimint_core::config::{ClientConfig, GlobalClientConfig};
+    use fedimint_core::module::CoreConsensusVersion;
+
+    use super::diff_configs;
+
+    fn config(consensus_version: CoreConsensusVersion) -> ClientConfig {
+        ClientConfig {
+            global: GlobalClientConfig {
+                api_endpoints: BTreeMap::new(),
+                broadcast_public_keys: None,
+                consensus_version,
+                meta: BTreeMap::new(),
+            },
+            modules: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn detects_global_changes_only() {
+        let old = config(CoreConsensusVersion::new(2, 0));
+
+        let mut new = old.clone();
+        new.global
+            .meta
+            .insert("federation_name".to_owned(), "renamed".to_owned());
+        assert_eq!(diff_configs(&old, &new), None);
+
+        let new = config(CoreConsensusVersion::new(2, 1));
+        let (changed_modules, global_changed) =
+            diff_configs(&old, &new).expect("consensus version changed");
+        assert!(changed_modules.is_empty());
+        assert!(global_changed);
+    }
+}
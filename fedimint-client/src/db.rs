@@ -4,7 +4,7 @@
 
 use fedimint_api_client::api::ApiVersionSet;
 use fedimint_core::config::{ClientConfig, ClientConfigV0, FederationId, GlobalClientConfig};
-use fedimint_core::core::{ModuleInstanceId, OperationId};
+use fedimint_core::core::{ModuleInstanceId, ModuleKind, OperationId};
 use fedimint_core::db::{
     apply_migrations, create_database_version, get_current_database_version, CoreMigrationFn,
     Database, DatabaseTransaction, DatabaseValue, DatabaseVersion, DatabaseVersionKey,
@@ -22,6 +22,7 @@
 use tracing::{debug, info, trace, warn};
 
 use crate::backup::{ClientBackup, Metadata};
+use crate::health::{ModuleConfigHashKey, ModuleConfigHashKeyPrefix};
 use crate::module::recovery::RecoveryProgress;
 use crate::oplog::OperationLogEntry;
 use crate::sm::executor::{
@@ -49,6 +50,26 @@ pub enum DbKeyPrefix {
     ApiSecret = 0x36,
     PeerLastApiVersionsSummaryCache = 0x37,
     ApiUrlAnnouncement = 0x38,
+    ApiUrlOverride = 0x39,
+    SubWalletLabel = 0x3a,
+    /// Isolated databases of sub-wallets, see [`crate::sub_wallet`]
+    SubWallet = 0x3b,
+    ModuleInstanceKind = 0x3c,
+    /// Push notification token, see [`crate::push`]
+    PushTokenRegistration = 0x3d,
+    /// Client config change detected but not applied yet, see
+    /// [`crate::config_change`]
+    PendingConfigChange = 0x3e,
+    /// Module config hashes recorded by the integrity check, see
+    /// [`crate::health`]
+    ModuleConfigHash = 0x3f,
+    /// Federation responses recorded for operations, see [`crate::replay`]
+    RecordedApiCall = 0x40,
+    /// Operations that exhausted their retry budget, see [`crate::retry`]
+    FailedOperation = 0x41,
+    /// Progress of pruning the recorded federation responses, see
+    /// [`crate::replay`]
+    RecordingPruneCursor = 0x42,
     EventLog = fedimint_eventlog::DB_KEY_PREFIX_EVENT_LOG,
     UnorderedEventLog = fedimint_eventlog::DB_KEY_PREFIX_UNORDERED_EVENT_LOG,
 
@@ -194,6 +215,252 @@ pub struct ClientConfigKeyV0 {
     query_prefix = ClientConfigKeyPrefixV0
 );
 
+/// Kind of the module each instance id belonged to when the client first
+/// stored data for it, used to detect configs that reassign the instance ids
+/// the module databases are keyed by
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ModuleInstanceKindKey(pub ModuleInstanceId);
+
+#[derive(Debug, Encodable)]
+pub struct ModuleInstanceKindKeyPrefix;
+
+impl_db_record!(
+    key = ModuleInstanceKindKey,
+    value = ModuleKind,
+    db_prefix = DbKeyPrefix::ModuleInstanceKind
+);
+
+impl_db_lookup!(
+    key = ModuleInstanceKindKey,
+    query_prefix = ModuleInstanceKindKeyPrefix
+);
+
+/// Records the kind of every module instance of `config` and moves the data of
+/// module instances the config assigned a different id to than before.
+///
+/// Module data is stored under the instance id, so a config that reorders the
+/// modules would otherwise make a module read the data of another one. Must
+/// run before the module database migrations, which also look up module data
+/// by instance id.
+pub async fn remap_module_instance_ids(db: &Database, config: &ClientConfig) -> anyhow::Result<()> {
+    let mut dbtx = db.begin_transaction().await;
+
+    let known_kinds = dbtx
+        .find_by_prefix(&ModuleInstanceKindKeyPrefix)
+        .await
+        .map(|(key, kind)| (key.0, kind))
+        .collect::<BTreeMap<_, _>>()
+        .await;
+
+    let remap = module_instance_id_remap(&known_kinds, config)?;
+    if !remap.is_empty() {
+        info!(
+            target: LOG_CLIENT_DB,
+            ?remap,
+            "Federation reassigned module instance ids, moving module data"
+        );
+        move_module_data(&mut dbtx.to_ref_nc(), &remap).await;
+
+        for old_id in remap.keys() {
+            dbtx.remove_entry(&ModuleInstanceKindKey(*old_id)).await;
+        }
+    }
+
+    for (module_instance_id, module_config) in &config.modules {
+        dbtx.insert_entry(
+            &ModuleInstanceKindKey(*module_instance_id),
+            module_config.kind(),
+        )
+        .await;
+    }
+
+    dbtx.commit_tx_result().await
+}
+
+/// Maps the old instance id of every module that `config` assigns a new one
+/// to, based on the kinds the client recorded for the old ids.
+///
+/// Modules are only told apart by their kind, so if a kind has several
+/// instances we can't tell which old instance is which new one and fail.
+fn module_instance_id_remap(
+    known_kinds: &BTreeMap<ModuleInstanceId, ModuleKind>,
+    config: &ClientConfig,
+) -> anyhow::Result<BTreeMap<ModuleInstanceId, ModuleInstanceId>> {
+    let ids_by_kind = |ids: &mut dyn Iterator<Item = (ModuleInstanceId, &ModuleKind)>| {
+        let mut by_kind = BTreeMap::<ModuleKind, Vec<ModuleInstanceId>>::new();
+        for (id, kind) in ids {
+            by_kind.entry(kind.clone()).or_default().push(id);
+        }
+        by_kind
+    };
+    let old_ids = ids_by_kind(&mut known_kinds.iter().map(|(id, kind)| (*id, kind)));
+    let new_ids = ids_by_kind(
+        &mut config
+            .modules
+            .iter()
+            .map(|(id, module_config)| (*id, module_config.kind())),
+    );
+
+    let mut remap = BTreeMap::new();
+    for (kind, new) in &new_ids {
+        match old_ids.get(kind) {
+            None => {}
+            Some(old) if old == new => {}
+            Some(old) if old.len() == 1 && new.len() == 1 => {
+                remap.insert(old[0], new[0]);
+            }
+            Some(old) => anyhow::bail!(
+                "The federation reassigned the module instance ids {old:?} of kind {kind} to {new:?}, can't tell which module is which"
+            ),
+        }
+    }
+
+    // Data under an id the config now uses for another kind must move away, or
+    // the new module would read it
+    for (module_instance_id, module_config) in &config.modules {
+        match known_kinds.get(module_instance_id) {
+            Some(kind)
+                if kind != module_config.kind() && !remap.contains_key(module_instance_id) =>
+            {
+                anyhow::bail!(
+                    "Module instance {module_instance_id} was of kind {kind} but the config assigns it to {}, and the module of kind {kind} was removed",
+                    module_config.kind()
+                );
+            }
+            _ => {}
+        }
+    }
+
+    Ok(remap)
+}
+
+/// Moves the database partition, state machines and per-instance records of
+/// every module from its old to its new instance id
+async fn move_module_data(
+    dbtx: &mut DatabaseTransaction<'_>,
+    remap: &BTreeMap<ModuleInstanceId, ModuleInstanceId>,
+) {
+    fn module_prefix(module_instance_id: ModuleInstanceId) -> Vec<u8> {
+        let mut prefix = vec![MODULE_GLOBAL_PREFIX];
+        prefix.append(&mut module_instance_id.consensus_encode_to_vec());
+        prefix
+    }
+
+    fn remap_state(state: &[u8], old_id: ModuleInstanceId, new_id: ModuleInstanceId) -> Vec<u8> {
+        let mut new_state = new_id.consensus_encode_to_vec();
+        new_state.extend_from_slice(&state[old_id.consensus_encode_to_vec().len()..]);
+        new_state
+    }
+
+    // Read everything before writing anything, so modules that swap their ids
+    // don't overwrite each other
+    let mut partitions = vec![];
+    for (old_id, new_id) in remap {
+        let old_prefix = module_prefix(*old_id);
+        let entries = dbtx
+            .raw_find_by_prefix(&old_prefix)
+            .await
+            .expect("DB error")
+            .collect::<Vec<_>>()
+            .await;
+        dbtx.raw_remove_by_prefix(&old_prefix)
+            .await
+            .expect("DB error");
+        partitions.push((old_prefix.len(), module_prefix(*new_id), entries));
+    }
+    for (old_prefix_len, new_prefix, entries) in partitions {
+        for (key, value) in entries {
+            let mut new_key = new_prefix.clone();
+            new_key.extend_from_slice(&key[old_prefix_len..]);
+            dbtx.raw_insert_bytes(&new_key, &value)
+                .await
+                .expect("DB error");
+        }
+    }
+
+    let active_states = dbtx
+        .find_by_prefix(&ActiveStateKeyPrefixBytes)
+        .await
+        .filter(|(key, _)| std::future::ready(remap.contains_key(&key.module_instance_id)))
+        .collect::<Vec<_>>()
+        .await;
+    for (key, _) in &active_states {
+        dbtx.remove_entry(key).await;
+    }
+    for (key, meta) in active_states {
+        let new_id = remap[&key.module_instance_id];
+        let state = remap_state(&key.state, key.module_instance_id, new_id);
+        dbtx.insert_entry(
+            &ActiveStateKeyBytes {
+                operation_id: key.operation_id,
+                module_instance_id: new_id,
+                state,
+            },
+            &meta,
+        )
+        .await;
+    }
+
+    let inactive_states = dbtx
+        .find_by_prefix(&InactiveStateKeyPrefixBytes)
+        .await
+        .filter(|(key, _)| std::future::ready(remap.contains_key(&key.module_instance_id)))
+        .collect::<Vec<_>>()
+        .await;
+    for (key, _) in &inactive_states {
+        dbtx.remove_entry(key).await;
+    }
+    for (key, meta) in inactive_states {
+        let new_id = remap[&key.module_instance_id];
+        let state = remap_state(&key.state, key.module_instance_id, new_id);
+        dbtx.insert_entry(
+            &InactiveStateKeyBytes {
+                operation_id: key.operation_id,
+                module_instance_id: new_id,
+                state,
+            },
+            &meta,
+        )
+        .await;
+    }
+
+    // Shares its prefix with `ClientInitStateKey`, so we can't query by prefix
+    let mut recoveries = vec![];
+    for (old_id, new_id) in remap {
+        if let Some(recovery) = dbtx
+            .remove_entry(&ClientModuleRecovery {
+                module_instance_id: *old_id,
+            })
+            .await
+        {
+            recoveries.push((*new_id, recovery));
+        }
+    }
+    for (new_id, recovery) in recoveries {
+        dbtx.insert_entry(
+            &ClientModuleRecovery {
+                module_instance_id: new_id,
+            },
+            &recovery,
+        )
+        .await;
+    }
+
+    let config_hashes = dbtx
+        .find_by_prefix(&ModuleConfigHashKeyPrefix)
+        .await
+        .filter(|(key, _)| std::future::ready(remap.contains_key(&key.0)))
+        .collect::<Vec<_>>()
+        .await;
+    for (key, _) in &config_hashes {
+        dbtx.remove_entry(key).await;
+    }
+    for (key, hash) in config_hashes {
+        dbtx.insert_entry(&ModuleConfigHashKey(remap[&key.0]), &hash)
+            .await;
+    }
+}
+
 #[derive(Debug, Encodable, Decodable, Serialize)]
 pub struct ApiSecretKey;
 
@@ -774,3 +1041,141 @@ pub fn migrate_state(
 
     Ok(Some((new_active_states, new_inactive_states)))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fedimint_core::config::{ClientConfig, ClientModuleConfig, GlobalClientConfig};
+    use fedimint_core::core::{ModuleInstanceId, ModuleKind, OperationId};
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{
+        Database, IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped, IRawDatabaseExt,
+    };
+    use fedimint_core::encoding::{DynRawFallback, Encodable};
+    use fedimint_core::module::{CoreConsensusVersion, ModuleConsensusVersion};
+    use futures::StreamExt;
+
+    use super::{remap_module_instance_ids, ModuleInstanceKindKey};
+    use crate::sm::executor::{ActiveStateKeyBytes, ActiveStateKeyPrefixBytes};
+    use crate::sm::ActiveStateMeta;
+
+    fn config(modules: &[(ModuleInstanceId, &'static str)]) -> ClientConfig {
+        ClientConfig {
+            global: GlobalClientConfig {
+                api_endpoints: BTreeMap::new(),
+                broadcast_public_keys: None,
+                consensus_version: CoreConsensusVersion::new(2, 0),
+                meta: BTreeMap::new(),
+            },
+            modules: modules
+                .iter()
+                .map(|(module_instance_id, kind)| {
+                    (
+                        *module_instance_id,
+                        ClientModuleConfig {
+                            kind: ModuleKind::from_static_str(kind),
+                            version: ModuleConsensusVersion::new(0, 0),
+                            config: DynRawFallback::Raw {
+                                module_instance_id: *module_instance_id,
+                                raw: vec![],
+                            },
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    async fn write_module_data(db: &Database, module_instance_id: ModuleInstanceId, data: &[u8]) {
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.to_ref_nc()
+            .with_prefix_module_id(module_instance_id)
+            .0
+            .raw_insert_bytes(b"data", data)
+            .await
+            .unwrap();
+
+        let mut state = module_instance_id.consensus_encode_to_vec();
+        state.extend_from_slice(data);
+        dbtx.insert_entry(
+            &ActiveStateKeyBytes {
+                operation_id: OperationId::new_random(),
+                module_instance_id,
+                state,
+            },
+            &ActiveStateMeta {
+                created_at: fedimint_core::time::now(),
+            },
+        )
+        .await;
+        dbtx.commit_tx().await;
+    }
+
+    async fn read_module_data(db: &Database, module_instance_id: ModuleInstanceId) -> Vec<u8> {
+        db.begin_transaction_nc()
+            .await
+            .with_prefix_module_id(module_instance_id)
+            .0
+            .raw_get_bytes(b"data")
+            .await
+            .unwrap()
+            .unwrap_or_default()
+    }
+
+    /// Module instance ids and the state they carry of all active states
+    async fn active_states(db: &Database) -> BTreeMap<ModuleInstanceId, Vec<u8>> {
+        db.begin_transaction_nc()
+            .await
+            .find_by_prefix(&ActiveStateKeyPrefixBytes)
+            .await
+            .map(|(key, _meta)| {
+                let id = key.module_instance_id.consensus_encode_to_vec();
+                assert_eq!(&key.state[..id.len()], id.as_slice());
+                (key.module_instance_id, key.state[id.len()..].to_vec())
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn module_data_survives_remapping() {
+        let db = MemDatabase::new().into_database();
+
+        remap_module_instance_ids(&db, &config(&[(0, "mint"), (1, "wallet")]))
+            .await
+            .unwrap();
+        write_module_data(&db, 0, b"mint").await;
+        write_module_data(&db, 1, b"wallet").await;
+
+        // The ids of both modules overlap with the old ids of the other one
+        remap_module_instance_ids(&db, &config(&[(0, "wallet"), (1, "mint")]))
+            .await
+            .unwrap();
+        assert_eq!(read_module_data(&db, 0).await, b"wallet");
+        assert_eq!(read_module_data(&db, 1).await, b"mint");
+        assert_eq!(
+            active_states(&db).await,
+            BTreeMap::from([(0, b"wallet".to_vec()), (1, b"mint".to_vec())])
+        );
+
+        // Moving to a previously unused id leaves nothing behind
+        remap_module_instance_ids(&db, &config(&[(0, "wallet"), (7, "mint")]))
+            .await
+            .unwrap();
+        assert_eq!(read_module_data(&db, 0).await, b"wallet");
+        assert_eq!(read_module_data(&db, 1).await, b"");
+        assert_eq!(read_module_data(&db, 7).await, b"mint");
+        assert_eq!(
+            active_states(&db).await,
+            BTreeMap::from([(0, b"wallet".to_vec()), (7, b"mint".to_vec())])
+        );
+
+        let mut dbtx = db.begin_transaction_nc().await;
+        assert_eq!(dbtx.get_value(&ModuleInstanceKindKey(1)).await, None);
+        assert_eq!(
+            dbtx.get_value(&ModuleInstanceKindKey(7)).await,
+            Some(ModuleKind::from_static_str("mint"))
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Typed bus of app-level client events
+//!
+//! The operation update streams of the individual modules are the right tool
+//! to follow a single operation the app started. Reactions that don't care
+//! about a particular operation, like playing a sound when money comes in or
+//! showing a notification when a payment finished, would have to subscribe to
+//! every operation of every module instead. Modules therefore also publish
+//! a [`ClientEvent`] for such occurrences, which embedders receive from a
+//! single subscription via [`crate::Client::subscribe_client_events`].
+//!
+//! Events are only published once the database transaction that caused them
+//! committed, but they are not persisted: a subscriber that lags behind or
+//! isn't running misses them. Use the event log if every event has to be
+//! seen.
+
+use std::collections::BTreeSet;
+
+use fedimint_core::core::{ModuleInstanceId, OperationId};
+use fedimint_core::secp256k1::PublicKey;
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Number of events buffered for subscribers by default before the slowest
+/// one starts missing events, see [`crate::memory::MemoryBudget`]
+pub const CLIENT_EVENT_BUS_CAPACITY: usize = 1024;
+
+/// An app-level event published by a client module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientEventEnvelope {
+    /// Module instance that published the event
+    pub module_instance_id: ModuleInstanceId,
+    pub event: ClientEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClientEvent {
+    /// E-cash notes received out of band are being reissued into the wallet
+    NoteReceived {
+        operation_id: OperationId,
+        amount: Amount,
+    },
+    /// An incoming payment was received and claimed
+    PaymentReceived { operation_id: OperationId },
+    /// An outgoing payment reached its recipient
+    PaymentSucceeded { operation_id: OperationId },
+    /// An outgoing payment failed, the funds are or will be refunded
+    PaymentFailed {
+        operation_id: OperationId,
+        error: String,
+    },
+    /// An on-chain deposit got enough confirmations and is being claimed
+    DepositConfirmed {
+        operation_id: OperationId,
+        txid: bitcoin::Txid,
+        amount: Amount,
+    },
+    /// A gateway was announced, changed its announcement or disappeared from
+    /// the federation
+    GatewayChanged { gateway_id: PublicKey },
+    /// The gateway selected as active gateway expired or disappeared from the
+    /// federation, so a new one has to be selected
+    ActiveGatewayUnavailable { gateway_id: PublicKey },
+    /// The federation changed its client config, see
+    /// [`crate::Client::pending_config_change`]
+    ConfigChanged {
+        changed_modules: BTreeSet<ModuleInstanceId>,
+        global_changed: bool,
+    },
+}
@@ -0,0 +1,272 @@
+//! Integrity self-check of the client's local state
+//!
+//! Every time a client is built it runs a fast consistency check of its
+//! database, so wallets can detect corrupted or tampered local state before
+//! showing balances derived from it. The result is available through
+//! [`crate::Client::health`], the client keeps working either way.
+//!
+//! To keep startup fast independent of the size of the database, large tables
+//! are only checked for a random sample of their entries, so repeated starts
+//! eventually cover all of them. The check covers:
+//! * the module configs, whose hashes are recorded the first time they are
+//!   seen and may only change by applying a [`crate::config_change`]
+//! * a sample of the chronological operation log index, whose entries have to
+//!   reference existing operations
+//! * module specific state, see
+//!   [`crate::module::ClientModule::check_integrity`], e.g. a sample of the
+//!   e-cash notes' signatures
+
+use std::time::Duration;
+
+use bitcoin::hashes::sha256;
+use fedimint_core::config::ClientConfig;
+use fedimint_core::core::{ModuleInstanceId, OperationId};
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::time::now;
+use fedimint_core::{impl_db_lookup, impl_db_record};
+use fedimint_logging::LOG_CLIENT;
+use futures::StreamExt;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::db::{
+    ChronologicalOperationLogKey, ChronologicalOperationLogKeyPrefix, DbKeyPrefix, OperationLogKey,
+};
+use crate::module::ClientModuleRegistry;
+
+/// Number of entries of the chronological operation log index checked by
+/// [`check_operation_log`]
+const OPERATION_LOG_SAMPLE_SIZE: usize = 32;
+
+/// Result of the integrity check run when the client was built
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientHealth {
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl ClientHealth {
+    /// Returns true if no inconsistencies were found
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Error)]
+pub enum IntegrityIssue {
+    #[error("Config of module {module_instance_id} changed without a federation config change")]
+    ModuleConfigMismatch {
+        module_instance_id: ModuleInstanceId,
+    },
+    #[error(
+        "Chronological operation log references unknown operation {}",
+        .operation_id.fmt_full()
+    )]
+    DanglingOperationIndex { operation_id: OperationId },
+    #[error("Module {module_instance_id}: {message}")]
+    Module {
+        module_instance_id: ModuleInstanceId,
+        message: String,
+    },
+}
+
+/// Hash of a module's config at the time it was first used, see
+/// [`check_module_config_hashes`]
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ModuleConfigHashKey(pub ModuleInstanceId);
+
+#[derive(Debug, Encodable)]
+pub struct ModuleConfigHashKeyPrefix;
+
+impl_db_record!(
+    key = ModuleConfigHashKey,
+    value = sha256::Hash,
+    db_prefix = DbKeyPrefix::ModuleConfigHash
+);
+
+impl_db_lookup!(
+    key = ModuleConfigHashKey,
+    query_prefix = ModuleConfigHashKeyPrefix
+);
+
+/// Runs all integrity checks, see the [module docs](self)
+pub async fn check_integrity(
+    db: &Database,
+    config: &ClientConfig,
+    modules: &ClientModuleRegistry,
+) -> ClientHealth {
+    let mut issues = check_module_config_hashes(db, config).await;
+    issues.extend(check_operation_log(db).await);
+
+    let mut dbtx = db.begin_transaction_nc().await;
+    for (module_instance_id, _, module) in modules.iter_modules() {
+        issues.extend(
+            module
+                .check_integrity(module_instance_id, &mut dbtx)
+                .await
+                .into_iter()
+                .map(|message| IntegrityIssue::Module {
+                    module_instance_id,
+                    message,
+                }),
+        );
+    }
+
+    for issue in &issues {
+        warn!(target: LOG_CLIENT, %issue, "Client integrity check failed");
+    }
+    debug!(target: LOG_CLIENT, issues = issues.len(), "Client integrity check done");
+
+    ClientHealth { issues }
+}
+
+/// Compares the hash of every module config to the recorded one, recording it
+/// if the module is seen for the first time
+async fn check_module_config_hashes(db: &Database, config: &ClientConfig) -> Vec<IntegrityIssue> {
+    let mut dbtx = db.begin_transaction().await;
+    let mut issues = vec![];
+
+    for (module_instance_id, module_config) in &config.modules {
+        let hash = module_config.consensus_hash_sha256();
+        let key = ModuleConfigHashKey(*module_instance_id);
+
+        match dbtx.get_value(&key).await {
+            Some(recorded) if recorded != hash => {
+                issues.push(IntegrityIssue::ModuleConfigMismatch {
+                    module_instance_id: *module_instance_id,
+                });
+            }
+            Some(_) => {}
+            None => {
+                dbtx.insert_new_entry(&key, &hash).await;
+            }
+        }
+    }
+
+    if let Err(err) = dbtx.commit_tx_result().await {
+        warn!(target: LOG_CLIENT, %err, "Failed to record module config hashes");
+    }
+
+    issues
+}
+
+/// Checks that a sample of the chronological operation log index references
+/// existing operations. The sample starts at a random point in time between
+/// the oldest operation and now, wrapping around to the oldest operation if
+/// there are not enough newer ones.
+async fn check_operation_log(db: &Database) -> Vec<IntegrityIssue> {
+    let mut dbtx = db.begin_transaction_nc().await;
+
+    let Some((oldest, ())) = dbtx
+        .find_by_prefix(&ChronologicalOperationLogKeyPrefix)
+        .await
+        .next()
+        .await
+    else {
+        return vec![];
+    };
+
+    let now = now();
+    let window = now.duration_since(oldest.creation_time).unwrap_or_default();
+    let start = ChronologicalOperationLogKey {
+        creation_time: oldest.creation_time + window.mul_f64(rand::thread_rng().gen()),
+        operation_id: OperationId([0; 32]),
+    };
+    // Operations from the future are not expected, but the clock may have been
+    // adjusted since they were created
+    let end = ChronologicalOperationLogKey {
+        creation_time: now + Duration::from_secs(60 * 60 * 24),
+        operation_id: OperationId([0; 32]),
+    };
+
+    let mut sample = dbtx
+        .find_by_range(start..end)
+        .await
+        .take(OPERATION_LOG_SAMPLE_SIZE)
+        .map(|(key, ())| key.operation_id)
+        .collect::<Vec<_>>()
+        .await;
+    let remaining = OPERATION_LOG_SAMPLE_SIZE - sample.len();
+    sample.extend(
+        dbtx.find_by_range(oldest..start)
+            .await
+            .take(remaining)
+            .map(|(key, ())| key.operation_id)
+            .collect::<Vec<_>>()
+            .await,
+    );
+
+    let mut issues = vec![];
+    for operation_id in sample {
+        if dbtx
+            .get_value(&OperationLogKey { operation_id })
+            .await
+            .is_none()
+        {
+            issues.push(IntegrityIssue::DanglingOperationIndex { operation_id });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fedimint_core::config::{ClientConfig, GlobalClientConfig};
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{IDatabaseTransactionOpsCoreTyped, IRawDatabaseExt};
+    use fedimint_core::module::registry::ModuleRegistry;
+    use fedimint_core::module::CoreConsensusVersion;
+
+    use super::{check_integrity, IntegrityIssue};
+    use crate::db::OperationLogKey;
+    use crate::oplog::OperationLog;
+
+    fn empty_config() -> ClientConfig {
+        ClientConfig {
+            global: GlobalClientConfig {
+                api_endpoints: BTreeMap::new(),
+                broadcast_public_keys: None,
+                consensus_version: CoreConsensusVersion::new(0, 0),
+                meta: BTreeMap::new(),
+            },
+            modules: BTreeMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_inconsistent_operation_log() {
+        let db = MemDatabase::new().into_database();
+        let op_log = OperationLog::new(db.clone());
+        let config = empty_config();
+        let modules = ModuleRegistry::default();
+
+        let op_id = OperationId([0x42; 32]);
+        let mut dbtx = db.begin_transaction().await;
+        op_log
+            .add_operation_log_entry(&mut dbtx.to_ref_nc(), op_id, "foo", "bar")
+            .await;
+        dbtx.commit_tx().await;
+
+        assert!(check_integrity(&db, &config, &modules).await.is_healthy());
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.remove_entry(&OperationLogKey {
+            operation_id: op_id,
+        })
+        .await;
+        dbtx.commit_tx().await;
+
+        assert_eq!(
+            check_integrity(&db, &config, &modules).await.issues,
+            vec![IntegrityIssue::DanglingOperationIndex {
+                operation_id: op_id
+            }]
+        );
+    }
+}
@@ -85,6 +85,7 @@
 use std::future::pending;
 use std::ops::{self, Range};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 
@@ -95,14 +96,14 @@
 use bitcoin::secp256k1;
 use db::{
     apply_migrations_client, apply_migrations_core_client, get_core_client_database_migrations,
-    ApiSecretKey, CachedApiVersionSet, CachedApiVersionSetKey, ClientConfigKey, ClientInitStateKey,
-    ClientModuleRecovery, ClientPreRootSecretHashKey, EncodedClientSecretKey, InitMode,
-    PeerLastApiVersionsSummary, PeerLastApiVersionsSummaryKey,
+    remap_module_instance_ids, ApiSecretKey, CachedApiVersionSet, CachedApiVersionSetKey,
+    ClientConfigKey, ClientInitStateKey, ClientModuleRecovery, ClientPreRootSecretHashKey,
+    EncodedClientSecretKey, InitMode, PeerLastApiVersionsSummary, PeerLastApiVersionsSummaryKey,
 };
 use fedimint_api_client::api::net::Connector;
 use fedimint_api_client::api::{
-    ApiVersionSet, DynGlobalApi, DynModuleApi, FederationApiExt, GlobalFederationApiWithCacheExt,
-    IGlobalFederationApi, WsFederationApi,
+    ApiVersionSet, CacheUsage, DynGlobalApi, DynModuleApi, FederationApiExt,
+    GlobalFederationApiWithCacheExt, IGlobalFederationApi, WsFederationApi,
 };
 use fedimint_core::config::{
     ClientConfig, FederationId, GlobalClientConfig, JsonClientConfig, ModuleInitRegistry,
@@ -124,7 +125,9 @@
     SupportedCoreApiVersions, SupportedModuleApiVersions,
 };
 use fedimint_core::net::api_announcement::SignedApiAnnouncement;
-use fedimint_core::task::{Elapsed, MaybeSend, MaybeSync, TaskGroup};
+use fedimint_core::task::{
+    Cancellable, CancellationToken, Elapsed, MaybeSend, MaybeSync, TaskGroup,
+};
 use fedimint_core::transaction::Transaction;
 use fedimint_core::util::{backoff_util, retry, BoxStream, NextOrPending, SafeUrl};
 use fedimint_core::{
@@ -158,15 +161,40 @@
     ClientInputBundle, ClientInputSM, ClientOutput, ClientOutputSM, TxSubmissionStatesSM,
 };
 
-use crate::api_announcements::{get_api_urls, run_api_announcement_sync, ApiAnnouncementPrefix};
+use crate::api_announcements::{
+    get_api_urls, run_api_announcement_sync, ApiAnnouncementPrefix, ApiUrlOverrideKey,
+    ApiUrlOverridePrefix,
+};
 use crate::api_version_discovery::discover_common_api_versions_set;
 use crate::backup::Metadata;
+use crate::config_change::{
+    run_config_change_detection, FederationConfigChange, PendingConfigChangeKey,
+};
 use crate::db::{ClientMetadataKey, ClientModuleRecoveryState, InitState, OperationLogKey};
+use crate::event_bus::{ClientEvent, ClientEventEnvelope};
+use crate::health::{check_integrity, ClientHealth, ModuleConfigHashKeyPrefix};
+use crate::lifecycle::{MaintenanceBudget, MaintenanceReport};
+use crate::memory::{MemoryBudget, MemoryUsage};
+use crate::module::extension::{ClientModuleExtension, ClientModuleExtensionRegistry};
 use crate::module::init::{
     ClientModuleInit, ClientModuleInitRegistry, DynClientModuleInit, IClientModuleInit,
 };
 use crate::module::{ClientModule, ClientModuleRegistry, IClientModule, StateGenerator};
-use crate::oplog::OperationLog;
+#[cfg(feature = "network-sim")]
+use crate::network_sim::{NetworkConditions, SimulatedNetworkApi};
+use crate::oplog::{OperationLog, OperationLogEntry};
+use crate::push::{
+    PushRegistration, PushRelayClient, PushTokenRegistration, PushTokenRegistrationKey,
+    PushUnregistration, PushWatch,
+};
+use crate::replay::{
+    get_operation_recording, prune_operation_recordings, OperationRecording,
+    RecordingFederationApi, ReplayFederationApi, RECORDED_OPERATIONS_LIMIT,
+};
+use crate::retry::{
+    get_failed_operations, prune_failed_operations, record_failed_operation,
+    remove_failed_operation, FailedOperation, RetryPolicy,
+};
 use crate::sm::executor::{
     ActiveOperationStateKeyPrefix, ContextGen, InactiveOperationStateKeyPrefix,
 };
@@ -180,14 +208,34 @@
 
 /// Client backup
 pub mod backup;
+/// Detection of changes to the federation's client config
+pub mod config_change;
 /// Database keys used by the client
 pub mod db;
 /// Environment variables
 pub mod envs;
+/// Typed bus of app-level events published by the client modules
+pub mod event_bus;
+/// Integrity self-check of the local state run on startup
+pub mod health;
+/// Pausing the client while a mobile app is in the background
+pub mod lifecycle;
+/// Bounds on the memory used by caches and buffers
+pub mod memory;
 /// Module client interface definitions
 pub mod module;
+/// Simulated network conditions between the client and the federation, for
+/// tests
+#[cfg(feature = "network-sim")]
+pub mod network_sim;
 /// Operation log subsystem of the client
 pub mod oplog;
+/// Push notification token registration with a notification relay
+pub mod push;
+/// Recording and replaying the federation responses consumed by operations
+pub mod replay;
+/// Retry budgets and dead-letter queue of state machine actions
+pub mod retry;
 /// Secret handling & derivation
 pub mod secret;
 /// Client state machine interfaces and executor implementation
@@ -200,6 +248,8 @@
 pub mod api_announcements;
 /// Management of meta fields
 pub mod meta;
+/// Isolated child clients derived from the same root secret
+pub mod sub_wallet;
 
 #[derive(Serialize, Deserialize)]
 pub struct TxCreatedEvent {
@@ -342,7 +392,25 @@ async fn log_event_json(
         transient: bool,
     );
 
+    /// Publishes `event` on the client event bus once `dbtx` committed, see
+    /// [`event_bus`]
+    fn publish_client_event(
+        &self,
+        dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+        event: ClientEvent,
+    );
+
     async fn transaction_update_stream(&self) -> BoxStream<TxSubmissionStatesSM>;
+
+    /// Retry budget of external actions, see [`retry`]
+    fn retry_policy(&self) -> RetryPolicy;
+
+    /// Adds `action` of the current operation to the dead-letter queue, see
+    /// [`retry`]
+    async fn dead_letter(&self, action: &str, attempts: u64, last_error: String);
+
+    /// Removes `action` of the current operation from the dead-letter queue
+    async fn remove_dead_letter(&self, action: &str);
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -398,9 +466,29 @@ async fn log_event_json(
         unimplemented!("fake implementation, only for tests");
     }
 
+    fn publish_client_event(
+        &self,
+        _dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+        _event: ClientEvent,
+    ) {
+        unimplemented!("fake implementation, only for tests");
+    }
+
     async fn transaction_update_stream(&self) -> BoxStream<TxSubmissionStatesSM> {
         unimplemented!("fake implementation, only for tests");
     }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        unimplemented!("fake implementation, only for tests");
+    }
+
+    async fn dead_letter(&self, _action: &str, _attempts: u64, _last_error: String) {
+        unimplemented!("fake implementation, only for tests");
+    }
+
+    async fn remove_dead_letter(&self, _action: &str) {
+        unimplemented!("fake implementation, only for tests");
+    }
 }
 
 dyn_newtype_define! {
@@ -625,6 +713,37 @@ async fn log_event_json(
             )
             .await;
     }
+
+    fn publish_client_event(
+        &self,
+        dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+        event: ClientEvent,
+    ) {
+        self.client
+            .publish_client_event_dbtx(dbtx.global_tx(), self.module_instance_id, event);
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        self.client.retry_policy
+    }
+
+    async fn dead_letter(&self, action: &str, attempts: u64, last_error: String) {
+        record_failed_operation(
+            &self.client.db,
+            FailedOperation {
+                operation_id: self.operation,
+                action: action.to_owned(),
+                attempts,
+                last_error,
+                failed_at: fedimint_core::time::now(),
+            },
+        )
+        .await;
+    }
+
+    async fn remove_dead_letter(&self, action: &str) {
+        remove_failed_operation(&self.client.db, self.operation, action).await;
+    }
 }
 
 fn states_add_instance(
@@ -893,6 +1012,10 @@ pub struct Client {
 
     task_group: TaskGroup,
 
+    /// Tokens used to abort awaiting the results of an operation, see
+    /// [`Client::cancel_operation_awaits`]
+    operation_cancellations: std::sync::Mutex<BTreeMap<OperationId, CancellationToken>>,
+
     /// Updates about client recovery progress
     client_recovery_progress_receiver:
         watch::Receiver<BTreeMap<ModuleInstanceId, RecoveryProgress>>,
@@ -903,6 +1026,20 @@ pub struct Client {
     /// Receiver for events fired every time (ordered) log event is added.
     log_event_added_rx: watch::Receiver<()>,
     log_event_added_transient_tx: broadcast::Sender<EventLogEntry>,
+    /// App-level events published by the modules, see [`event_bus`]
+    client_event_tx: broadcast::Sender<ClientEventEnvelope>,
+    memory_budget: MemoryBudget,
+    /// Result of the integrity check run when the client was built
+    health: ClientHealth,
+    /// Whether the federation responses consumed by operations are recorded,
+    /// see [`replay`]
+    record_operations: bool,
+    /// Retry budget of the state machines' external actions, see [`retry`]
+    retry_policy: RetryPolicy,
+    module_extensions: ClientModuleExtensionRegistry,
+    /// Whether the app embedding the client is in the background, see
+    /// [`lifecycle`]
+    in_background: AtomicBool,
 }
 
 impl Client {
@@ -1015,6 +1152,74 @@ pub fn start_executor(self: &Arc<Self>) {
         self.executor.start_executor(self.context_gen());
     }
 
+    /// To be called when the embedding app moves to the background. Pauses
+    /// the state machines until [`Self::resume_foreground`] is called, see
+    /// [`lifecycle`].
+    pub fn enter_background(&self) {
+        if !self.in_background.swap(true, Ordering::SeqCst) {
+            debug!(target: LOG_CLIENT, "Entering background, pausing executor");
+        }
+        self.executor.pause();
+    }
+
+    /// To be called when the embedding app returns to the foreground, resumes
+    /// the state machines paused by [`Self::enter_background`]
+    pub fn resume_foreground(&self) {
+        if self.in_background.swap(false, Ordering::SeqCst) {
+            debug!(target: LOG_CLIENT, "Resuming foreground, resuming executor");
+        }
+        self.executor.resume();
+    }
+
+    pub fn is_in_background(&self) -> bool {
+        self.in_background.load(Ordering::SeqCst)
+    }
+
+    /// Drives the pending state machines forward within a background
+    /// execution window granted by the OS, until no active state machines are
+    /// left or `budget` runs out, then pauses them again if the client is
+    /// still in the background.
+    ///
+    /// Work interrupted by the end of the budget is not lost, state machines
+    /// pick it up again in the next maintenance or on
+    /// [`Self::resume_foreground`].
+    pub async fn run_maintenance(&self, budget: MaintenanceBudget) -> MaintenanceReport {
+        let mut transitions_rx = self.executor.subscribe_completed_transitions();
+        let transitions_before = *transitions_rx.borrow_and_update();
+        let started = fedimint_core::time::now();
+
+        self.executor.resume();
+        let budget_exhausted = runtime::timeout(budget.max_duration, async {
+            loop {
+                let transitions = *transitions_rx.borrow_and_update() - transitions_before;
+                if budget.max_transitions.is_some_and(|max| max <= transitions) {
+                    return true;
+                }
+                if self.executor.get_active_states().await.is_empty() {
+                    return false;
+                }
+                if transitions_rx.changed().await.is_err() {
+                    return false;
+                }
+            }
+        })
+        .await
+        .unwrap_or(true);
+        // Only pause if the app didn't return to the foreground meanwhile
+        if self.is_in_background() {
+            self.executor.pause();
+        }
+
+        let report = MaintenanceReport {
+            transitions: *transitions_rx.borrow() - transitions_before,
+            active_state_machines: self.executor.get_active_states().await.len(),
+            budget_exhausted,
+            elapsed: started.elapsed().unwrap_or_default(),
+        };
+        debug!(target: LOG_CLIENT, ?report, "Background maintenance done");
+        report
+    }
+
     pub fn federation_id(&self) -> FederationId {
         self.federation_id
     }
@@ -1038,6 +1243,50 @@ pub async fn config(&self) -> ClientConfig {
         self.config.read().await.clone()
     }
 
+    /// Result of the integrity self-check run when the client was built, see
+    /// [`health`]. Wallets should warn the user before showing balances if it
+    /// reports any issues.
+    pub fn health(&self) -> &ClientHealth {
+        &self.health
+    }
+
+    /// Change of the federation's client config that was detected but not
+    /// applied yet, see [`config_change`]
+    pub async fn pending_config_change(&self) -> Option<FederationConfigChange> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .get_value(&PendingConfigChangeKey)
+            .await
+    }
+
+    /// Stores the pending config change as the client config. Modules only
+    /// pick up the new config once the client is restarted, running state
+    /// machines aren't re-validated against it, see [`config_change`].
+    pub async fn apply_config_change(&self) -> anyhow::Result<()> {
+        let mut dbtx = self.db.begin_transaction().await;
+
+        let change = dbtx
+            .remove_entry(&PendingConfigChangeKey)
+            .await
+            .context("No pending config change")?;
+        dbtx.insert_entry(&ClientConfigKey, &change.new_config)
+            .await;
+        // The module config hashes are recorded again on the next start
+        dbtx.remove_by_prefix(&ModuleConfigHashKeyPrefix).await;
+        dbtx.commit_tx_result().await?;
+
+        info!(
+            target: LOG_CLIENT,
+            changed_modules = ?change.changed_modules,
+            global_changed = %change.global_changed,
+            "Applied client config change, restart the client to use it"
+        );
+        *(self.config.write().await) = change.new_config;
+
+        Ok(())
+    }
+
     pub fn api_secret(&self) -> &Option<String> {
         &self.api_secret
     }
@@ -1141,6 +1390,33 @@ pub fn operation_log(&self) -> &OperationLog {
         &self.operation_log
     }
 
+    /// Federation responses consumed by the operation, empty unless the client
+    /// was built with [`ClientBuilder::with_operation_recording`], see
+    /// [`replay`]
+    pub async fn operation_recording(&self, operation_id: OperationId) -> OperationRecording {
+        get_operation_recording(&self.db, operation_id).await
+    }
+
+    /// Actions of active operations that exhausted their retry budget and are
+    /// only retried rarely from now on, see [`retry`]
+    pub async fn failed_operations(&self) -> Vec<FailedOperation> {
+        self.prune_failed_operations().await;
+        get_failed_operations(&self.db).await
+    }
+
+    /// Drops the dead-letter entries of operations that finished
+    async fn prune_failed_operations(&self) {
+        let active_operations = self.get_active_operations().await;
+        let pruned = prune_failed_operations(&self.db, &active_operations).await;
+        if pruned != 0 {
+            debug!(
+                target: LOG_CLIENT,
+                %pruned,
+                "Pruned dead-letter entries of finished operations"
+            );
+        }
+    }
+
     /// Get the meta manager to read meta fields.
     pub fn meta_service(&self) -> &Arc<MetaService> {
         &self.meta_service
@@ -1361,13 +1637,62 @@ pub async fn has_active_states(&self, operation_id: OperationId) -> bool {
 
     /// Waits for an output from the primary module to reach its final
     /// state.
+    ///
+    /// Returns an error if awaiting got aborted using
+    /// [`Client::cancel_operation_awaits`].
     pub async fn await_primary_module_output(
         &self,
         operation_id: OperationId,
         out_point: OutPoint,
     ) -> anyhow::Result<()> {
-        self.primary_module()
-            .await_primary_module_output(operation_id, out_point)
+        self.await_cancellable(
+            operation_id,
+            self.primary_module()
+                .await_primary_module_output(operation_id, out_point),
+        )
+        .await?
+    }
+
+    /// Returns the token that aborts awaiting results of `operation_id`
+    pub fn operation_cancellation_token(&self, operation_id: OperationId) -> CancellationToken {
+        let mut tokens = self.operation_cancellations.lock().expect("poisoned");
+
+        // Forget the tokens of operations nobody awaits anymore, otherwise we'd keep one
+        // for every operation ever awaited
+        tokens.retain(|_, token| !token.is_unused());
+
+        tokens.entry(operation_id).or_default().clone()
+    }
+
+    /// Abort everyone currently awaiting results of `operation_id`, e.g.
+    /// because the user navigated away from the screen showing its progress.
+    ///
+    /// This only affects futures and update streams returned by the client,
+    /// the operation's state machines keep running in the background. Update
+    /// streams end and cancellable futures return [`Cancelled`]. Subscribing
+    /// again after this call works as usual.
+    ///
+    /// [`Cancelled`]: fedimint_core::task::Cancelled
+    pub fn cancel_operation_awaits(&self, operation_id: OperationId) {
+        if let Some(token) = self
+            .operation_cancellations
+            .lock()
+            .expect("poisoned")
+            .remove(&operation_id)
+        {
+            token.cancel();
+        }
+    }
+
+    /// Run `fut` until it completes or awaiting `operation_id` gets aborted
+    /// with [`Client::cancel_operation_awaits`]
+    pub async fn await_cancellable<F: Future>(
+        &self,
+        operation_id: OperationId,
+        fut: F,
+    ) -> Cancellable<F::Output> {
+        self.operation_cancellation_token(operation_id)
+            .run_until_cancelled(fut)
             .await
     }
 
@@ -1400,6 +1725,70 @@ pub fn get_module_client_dyn(
             .ok_or(anyhow!("Unknown module instance {}", instance_id))
     }
 
+    /// Returns the implementation of the dyn interface `I` registered with
+    /// [`ClientBuilder::with_module_interface`] for the kind of the module
+    /// instance
+    pub fn module_interface<I>(&self, instance_id: ModuleInstanceId) -> Option<Arc<I>>
+    where
+        I: ?Sized + 'static,
+    {
+        let (kind, _) = self.modules.get_with_kind(instance_id)?;
+        self.module_extensions.get_interface::<I>(kind)
+    }
+
+    /// Summary of an operation provided by the [`ClientModuleExtension`] of
+    /// the module instance that created it, if any
+    pub async fn operation_summary(
+        &self,
+        operation_id: OperationId,
+        operation: &OperationLogEntry,
+    ) -> Option<serde_json::Value> {
+        let kind = ModuleKind::clone_from_str(operation.operation_module_kind());
+        let extension = self.module_extensions.get(&kind)?;
+        let instance_id = self.operation_module_instance(operation_id, &kind).await?;
+        extension.operation_summary(self.modules.get(instance_id)?, operation)
+    }
+
+    /// Returns the module instance of `kind` that created the operation, as
+    /// determined by the state machines of the operation
+    async fn operation_module_instance(
+        &self,
+        operation_id: OperationId,
+        kind: &ModuleKind,
+    ) -> Option<ModuleInstanceId> {
+        let (active_states, inactive_states) =
+            self.executor.get_operation_states(operation_id).await;
+
+        active_states
+            .iter()
+            .map(|(state, _)| state)
+            .chain(inactive_states.iter().map(|(state, _)| state))
+            .map(DynState::module_instance_id)
+            .find(|instance_id| {
+                self.modules
+                    .get_with_kind(*instance_id)
+                    .is_some_and(|(instance_kind, _)| instance_kind == kind)
+            })
+    }
+
+    /// Funds held by every module with a [`ClientModuleExtension`] that are
+    /// not part of the spendable balance returned by [`Self::get_balance`]
+    pub async fn balance_contributions(&self) -> BTreeMap<ModuleInstanceId, Amount> {
+        let mut dbtx = self.db().begin_transaction_nc().await;
+        let mut contributions = BTreeMap::new();
+        for (instance_id, kind, module) in self.modules.iter_modules() {
+            if let Some(extension) = self.module_extensions.get(kind) {
+                contributions.insert(
+                    instance_id,
+                    extension
+                        .balance_contribution(module, instance_id, &mut dbtx)
+                        .await,
+                );
+            }
+        }
+        contributions
+    }
+
     pub fn db(&self) -> &Database {
         &self.db
     }
@@ -2022,6 +2411,144 @@ pub async fn get_peer_urls(&self) -> BTreeMap<PeerId, SafeUrl> {
         get_api_urls(&self.db, &self.config().await).await
     }
 
+    /// Registers the push token with the notification relay, so watches
+    /// added with [`Client::add_push_watch`] wake the wallet through it.
+    ///
+    /// Replaces a previously registered token.
+    pub async fn register_push_token(
+        &self,
+        relay_url: SafeUrl,
+        token: String,
+    ) -> anyhow::Result<()> {
+        let registration = PushTokenRegistration { relay_url, token };
+
+        PushRelayClient::new(registration.relay_url.clone())
+            .register(&PushRegistration {
+                token: registration.token.clone(),
+                federation_id: self.federation_id(),
+                api_endpoints: self.get_peer_urls().await,
+                watches: vec![],
+            })
+            .await?;
+
+        let mut dbtx = self.db.begin_transaction().await;
+        let previous = dbtx
+            .insert_entry(&PushTokenRegistrationKey, &registration)
+            .await;
+        dbtx.commit_tx_result().await?;
+
+        if let Some(previous) = previous.filter(|previous| *previous != registration) {
+            if let Err(err) = self.unregister_push_token_at(&previous).await {
+                warn!(target: LOG_CLIENT, %err, "Failed to unregister previous push token");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stops push notifications for this federation
+    pub async fn unregister_push_token(&self) -> anyhow::Result<()> {
+        let Some(registration) = self
+            .db
+            .begin_transaction_nc()
+            .await
+            .get_value(&PushTokenRegistrationKey)
+            .await
+        else {
+            return Ok(());
+        };
+
+        self.unregister_push_token_at(&registration).await?;
+
+        // Only drop the registration if it wasn't replaced while we talked to
+        // the relay
+        let mut dbtx = self.db.begin_transaction().await;
+        if dbtx.get_value(&PushTokenRegistrationKey).await.as_ref() == Some(&registration) {
+            dbtx.remove_entry(&PushTokenRegistrationKey).await;
+        }
+        dbtx.commit_tx_result().await
+    }
+
+    async fn unregister_push_token_at(
+        &self,
+        registration: &PushTokenRegistration,
+    ) -> anyhow::Result<()> {
+        PushRelayClient::new(registration.relay_url.clone())
+            .unregister(&PushUnregistration {
+                token: registration.token.clone(),
+                federation_id: self.federation_id(),
+            })
+            .await
+    }
+
+    /// Asks the notification relay to wake the wallet once the watched API
+    /// call returns, does nothing if no push token is registered
+    pub async fn add_push_watch(&self, watch: PushWatch) -> anyhow::Result<()> {
+        let Some(registration) = self
+            .db
+            .begin_transaction_nc()
+            .await
+            .get_value(&PushTokenRegistrationKey)
+            .await
+        else {
+            return Ok(());
+        };
+
+        PushRelayClient::new(registration.relay_url)
+            .register(&PushRegistration {
+                token: registration.token,
+                federation_id: self.federation_id(),
+                api_endpoints: self.get_peer_urls().await,
+                watches: vec![watch],
+            })
+            .await
+    }
+
+    /// Returns the API URLs manually set using
+    /// [`Client::set_peer_url_override`].
+    pub async fn get_peer_url_overrides(&self) -> BTreeMap<PeerId, SafeUrl> {
+        self.db()
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&ApiUrlOverridePrefix)
+            .await
+            .map(|(override_key, url)| (override_key.0, url))
+            .collect()
+            .await
+    }
+
+    /// Manually set the API URL used to reach `peer`, taking precedence over
+    /// the config and API announcements. Passing `None` removes the override.
+    ///
+    /// The new URL is persisted and used right away.
+    pub async fn set_peer_url_override(
+        &self,
+        peer: PeerId,
+        url: Option<SafeUrl>,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            self.config().await.global.api_endpoints.contains_key(&peer),
+            "Peer {peer} is not part of the federation"
+        );
+
+        let mut dbtx = self.db().begin_transaction().await;
+        match url {
+            Some(url) => {
+                dbtx.insert_entry(&ApiUrlOverrideKey(peer), &url).await;
+            }
+            None => {
+                dbtx.remove_entry(&ApiUrlOverrideKey(peer)).await;
+            }
+        }
+        dbtx.commit_tx_result().await?;
+
+        if let Some(api_url) = self.get_peer_urls().await.remove(&peer) {
+            self.api.set_peer_url(peer, api_url).await;
+        }
+
+        Ok(())
+    }
+
     /// Create an invite code with the api endpoint of the given peer which can
     /// be used to download this client config
     pub async fn invite_code(&self, peer: PeerId) -> Option<InviteCode> {
@@ -2193,6 +2720,54 @@ pub async fn log_event_raw_dbtx<Cap>(
         .await;
     }
 
+    /// Publishes `event` on the client event bus once `dbtx` committed, see
+    /// [`event_bus`]
+    pub fn publish_client_event_dbtx<Cap>(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_, Cap>,
+        module_instance_id: ModuleInstanceId,
+        event: ClientEvent,
+    ) where
+        Cap: Send,
+    {
+        let client_event_tx = self.client_event_tx.clone();
+        dbtx.on_commit(move || {
+            // Having no subscribers is not an error
+            let _ = client_event_tx.send(ClientEventEnvelope {
+                module_instance_id,
+                event,
+            });
+        });
+    }
+
+    /// Subscribe to the app-level events published by all modules, see
+    /// [`event_bus`]
+    pub fn subscribe_client_events(&self) -> broadcast::Receiver<ClientEventEnvelope> {
+        self.client_event_tx.subscribe()
+    }
+
+    /// Reports how full the caches and buffers bounded by the client's
+    /// [`MemoryBudget`] are
+    pub async fn memory_usage(&self) -> MemoryUsage {
+        let notifier = self.executor.notifier();
+        MemoryUsage {
+            session_cache: self.api.session_cache_usage().await,
+            notifications: CacheUsage {
+                entries: notifier.buffered_notifications(),
+                capacity: self.memory_budget.notifier_capacity.get(),
+            },
+            missed_notifications: notifier.missed_notifications(),
+            client_events: CacheUsage {
+                entries: self.client_event_tx.len(),
+                capacity: self.memory_budget.client_event_capacity.get(),
+            },
+            log_events: CacheUsage {
+                entries: self.log_event_added_transient_tx.len(),
+                capacity: self.memory_budget.log_event_capacity.get(),
+            },
+        }
+    }
+
     pub async fn handle_events<F, R, K>(&self, pos_key: &K, call_fn: F) -> anyhow::Result<()>
     where
         K: DatabaseKey + DatabaseRecord + MaybeSend + MaybeSync,
@@ -2298,14 +2873,22 @@ pub struct ClientBuilder {
     meta_service: Arc<MetaService>,
     connector: Connector,
     stopped: bool,
-    log_event_added_transient_tx: broadcast::Sender<EventLogEntry>,
+    /// Shared with the client the builder was created from, if any, otherwise
+    /// created with the capacity of the memory budget when building
+    log_event_added_transient_tx: Option<broadcast::Sender<EventLogEntry>>,
+    client_event_tx: Option<broadcast::Sender<ClientEventEnvelope>>,
+    memory_budget: MemoryBudget,
+    record_operations: bool,
+    retry_policy: RetryPolicy,
+    replay: Option<OperationRecording>,
+    #[cfg(feature = "network-sim")]
+    network_conditions: Option<NetworkConditions>,
+    module_extensions: ClientModuleExtensionRegistry,
 }
 
 impl ClientBuilder {
     fn new(db: Database) -> Self {
         let meta_service = MetaService::new(LegacyMetaSource::default());
-        let (log_event_added_transient_tx, _log_event_added_transient_rx) =
-            broadcast::channel(1024);
         ClientBuilder {
             module_inits: ModuleInitRegistry::new(),
             primary_module_instance: None,
@@ -2315,7 +2898,15 @@ fn new(db: Database) -> Self {
             db_no_decoders: db,
             stopped: false,
             meta_service,
-            log_event_added_transient_tx,
+            log_event_added_transient_tx: None,
+            client_event_tx: None,
+            memory_budget: MemoryBudget::default(),
+            record_operations: false,
+            retry_policy: RetryPolicy::default(),
+            replay: None,
+            #[cfg(feature = "network-sim")]
+            network_conditions: None,
+            module_extensions: ClientModuleExtensionRegistry::default(),
         }
     }
 
@@ -2330,7 +2921,15 @@ fn from_existing(client: &Client) -> Self {
             // non unique
             meta_service: client.meta_service.clone(),
             connector: client.connector,
-            log_event_added_transient_tx: client.log_event_added_transient_tx.clone(),
+            log_event_added_transient_tx: Some(client.log_event_added_transient_tx.clone()),
+            client_event_tx: Some(client.client_event_tx.clone()),
+            memory_budget: client.memory_budget,
+            record_operations: client.record_operations,
+            retry_policy: client.retry_policy,
+            replay: None,
+            #[cfg(feature = "network-sim")]
+            network_conditions: None,
+            module_extensions: client.module_extensions.clone(),
         }
     }
 
@@ -2344,6 +2943,21 @@ pub fn with_module<M: ClientModuleInit>(&mut self, module_init: M) {
         self.module_inits.attach(module_init);
     }
 
+    /// Register an extension of a client module, which the client dispatches
+    /// to for modules of its kind, see [`module::extension`]
+    pub fn with_module_extension<E: ClientModuleExtension>(&mut self, extension: E) {
+        self.module_extensions.attach(extension);
+    }
+
+    /// Register `interface` as the implementation of the dyn interface `I`
+    /// for modules of `kind`, see [`Client::module_interface`]
+    pub fn with_module_interface<I>(&mut self, kind: ModuleKind, interface: Arc<I>)
+    where
+        I: ?Sized + MaybeSend + MaybeSync + 'static,
+    {
+        self.module_extensions.attach_interface(kind, interface);
+    }
+
     pub fn stopped(&mut self) {
         self.stopped = true;
     }
@@ -2405,6 +3019,13 @@ pub fn with_meta_service(&mut self, meta_service: Arc<MetaService>) {
         self.meta_service = meta_service;
     }
 
+    /// Bounds the memory used by the client's caches and buffers, see
+    /// [`memory`]. Event channels shared with the client this builder was
+    /// created from keep their capacity, so their subscribers keep receiving.
+    pub fn with_memory_budget(&mut self, memory_budget: MemoryBudget) {
+        self.memory_budget = memory_budget;
+    }
+
     async fn migrate_database(&self, db: &Database) -> anyhow::Result<()> {
         // Only apply the client database migrations if the database has been
         // initialized.
@@ -2455,6 +3076,31 @@ pub fn with_tor_connector(&mut self) {
         self.with_connector(Connector::tor());
     }
 
+    /// Record the federation responses consumed by every operation so they
+    /// can be replayed for debugging, see [`replay`]
+    pub fn with_operation_recording(&mut self) {
+        self.record_operations = true;
+    }
+
+    /// Bounds how often state machines retry external actions before their
+    /// operation is dead-lettered, see [`retry`]
+    pub fn with_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Answer all federation requests from `recording` instead of contacting
+    /// the federation, see [`replay`]
+    pub fn with_replay(&mut self, recording: OperationRecording) {
+        self.replay = Some(recording);
+    }
+
+    /// Send all federation requests through a simulated network with the
+    /// given `conditions`. Only meant for tests, see [`network_sim`]
+    #[cfg(feature = "network-sim")]
+    pub fn with_network_conditions(&mut self, conditions: NetworkConditions) {
+        self.network_conditions = Some(conditions);
+    }
+
     async fn init(
         self,
         pre_root_secret: DerivableSecret,
@@ -2672,7 +3318,7 @@ pub async fn open(self, pre_root_secret: DerivableSecret) -> anyhow::Result<Clie
         let api_secret = Client::get_api_secret_from_db(&self.db_no_decoders).await;
         let stopped = self.stopped;
 
-        let log_event_added_transient_tx = self.log_event_added_transient_tx.clone();
+        let log_event_added_transient_tx = self.log_event_added_transient_tx();
         let client = self
             .build_stopped(
                 pre_root_secret,
@@ -2695,7 +3341,7 @@ async fn build(
         api_secret: Option<String>,
         stopped: bool,
     ) -> anyhow::Result<ClientHandle> {
-        let log_event_added_transient_tx = self.log_event_added_transient_tx.clone();
+        let log_event_added_transient_tx = self.log_event_added_transient_tx();
         let client = self
             .build_stopped(
                 pre_root_secret,
@@ -2711,6 +3357,18 @@ async fn build(
         Ok(client)
     }
 
+    fn client_event_tx(&self) -> broadcast::Sender<ClientEventEnvelope> {
+        self.client_event_tx
+            .clone()
+            .unwrap_or_else(|| broadcast::channel(self.memory_budget.client_event_capacity.get()).0)
+    }
+
+    fn log_event_added_transient_tx(&self) -> broadcast::Sender<EventLogEntry> {
+        self.log_event_added_transient_tx
+            .clone()
+            .unwrap_or_else(|| broadcast::channel(self.memory_budget.log_event_capacity.get()).0)
+    }
+
     // TODO: remove config argument
     /// Build a [`Client`] but do not start the executor
     async fn build_stopped(
@@ -2729,27 +3387,50 @@ async fn build_stopped(
         let db = self.db_no_decoders.with_decoders(decoders.clone());
         let connector = self.connector;
         let peer_urls = get_api_urls(&db, &config).await;
-        let api = if let Some(admin_creds) = self.admin_creds.as_ref() {
-            WsFederationApi::new_admin(
-                admin_creds.peer_id,
-                peer_urls
-                    .into_iter()
-                    .find_map(|(peer, api_url)| (admin_creds.peer_id == peer).then_some(api_url))
-                    .context("Admin creds should match a peer")?,
-                &api_secret,
-                &connector,
-            )
-            .with_client_ext(db.clone(), log_ordering_wakeup_tx.clone())
-            .with_cache()
-            .into()
-        } else {
-            WsFederationApi::from_endpoints(peer_urls, &api_secret, &connector)
+        let api: DynGlobalApi = if let Some(recording) = self.replay.clone() {
+            ReplayFederationApi::new(peer_urls.keys().copied().collect(), recording)
                 .with_client_ext(db.clone(), log_ordering_wakeup_tx.clone())
-                .with_cache()
+                .with_cache_capacity(self.memory_budget.session_cache)
+                .into()
+        } else {
+            let ws_api = if let Some(admin_creds) = self.admin_creds.as_ref() {
+                WsFederationApi::new_admin(
+                    admin_creds.peer_id,
+                    peer_urls
+                        .into_iter()
+                        .find_map(|(peer, api_url)| {
+                            (admin_creds.peer_id == peer).then_some(api_url)
+                        })
+                        .context("Admin creds should match a peer")?,
+                    &api_secret,
+                    &connector,
+                )
+            } else {
+                WsFederationApi::from_endpoints(peer_urls, &api_secret, &connector)
+            };
+
+            let api = ws_api.with_client_ext(db.clone(), log_ordering_wakeup_tx.clone());
+            #[cfg(feature = "network-sim")]
+            let api = SimulatedNetworkApi::new(
+                Arc::new(api),
+                self.network_conditions.clone().unwrap_or_default(),
+            );
+            api.with_cache_capacity(self.memory_budget.session_cache)
+                .into()
+        };
+        let api: DynGlobalApi = if self.record_operations {
+            RecordingFederationApi::new(api, db.clone())
+                .with_cache_capacity(self.memory_budget.session_cache)
                 .into()
+        } else {
+            api
         };
         let task_group = TaskGroup::new();
 
+        // Move module data to the instance ids of the current config first, as the
+        // migrations look up module data by instance id
+        remap_module_instance_ids(&db, &config).await?;
+
         // Migrate the database before interacting with it in case any on-disk data
         // structures have changed.
         self.migrate_database(&db).await?;
@@ -2770,7 +3451,8 @@ async fn build_stopped(
             })
             .ok_or(anyhow!("No primary module set or found"))?;
 
-        let notifier = Notifier::new(db.clone());
+        let notifier =
+            Notifier::with_capacity(db.clone(), self.memory_budget.notifier_capacity.get());
 
         let common_api_versions = Client::load_and_refresh_common_api_version_static(
             &config,
@@ -2993,6 +3675,12 @@ async fn build_stopped(
             executor_builder.build(db.clone(), notifier, task_group.clone())
         };
 
+        let health = check_integrity(&db, &config, &modules).await;
+
+        if self.record_operations {
+            prune_operation_recordings(&db, RECORDED_OPERATIONS_LIMIT).await;
+        }
+
         let recovery_receiver_init_val = module_recovery_progress_receivers
             .iter()
             .map(|(module_instance_id, rx)| (*module_instance_id, *rx.borrow()))
@@ -3013,6 +3701,8 @@ async fn build_stopped(
             log_ordering_wakeup_tx,
             log_event_added_rx,
             log_event_added_transient_tx: log_event_added_transient_tx.clone(),
+            client_event_tx: self.client_event_tx(),
+            memory_budget: self.memory_budget,
             executor,
             api,
             secp_ctx: Secp256k1::new(),
@@ -3022,6 +3712,12 @@ async fn build_stopped(
             client_recovery_progress_receiver,
             meta_service: self.meta_service,
             connector,
+            operation_cancellations: std::sync::Mutex::default(),
+            health,
+            record_operations: self.record_operations,
+            retry_policy: self.retry_policy,
+            module_extensions: self.module_extensions,
+            in_background: AtomicBool::new(false),
         });
         client_inner
             .task_group
@@ -3040,6 +3736,11 @@ async fn build_stopped(
             run_api_announcement_sync(client_inner.clone()),
         );
 
+        client_inner.task_group.spawn_cancellable(
+            "config-change-detection",
+            run_config_change_detection(client_inner.clone()),
+        );
+
         client_inner.task_group.spawn_cancellable(
             "event log ordering task",
             run_event_log_ordering_task(
@@ -3055,6 +3756,8 @@ async fn build_stopped(
             module.start().await;
         }
 
+        client_arc.prune_failed_operations().await;
+
         final_client.set(client_arc.downgrade());
 
         if !module_recoveries.is_empty() {
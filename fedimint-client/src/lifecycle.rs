@@ -0,0 +1,49 @@
+//! Running the client under the background execution limits of mobile apps
+//!
+//! iOS and Android suspend apps shortly after they leave the foreground and
+//! only grant them short windows of background execution later on. Apps can
+//! call [`crate::Client::enter_background`] when that happens, which pauses the
+//! state machine executor: operation update streams go quiet and state
+//! machines created in the meantime are only queued. Inside a granted
+//! background window [`crate::Client::run_maintenance`] drives the pending
+//! work forward for at most a [`MaintenanceBudget`] before pausing again, and
+//! [`crate::Client::resume_foreground`] resumes normal operation.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Bounds on the work done by a single [`crate::Client::run_maintenance`] call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceBudget {
+    /// Wall time the state machines are run for, should stay below the window
+    /// granted by the OS
+    pub max_duration: Duration,
+    /// Number of state transitions after which the maintenance stops early,
+    /// `None` for no limit
+    pub max_transitions: Option<u64>,
+}
+
+impl Default for MaintenanceBudget {
+    /// Fits into the ~30 seconds iOS grants a background app refresh task
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_secs(25),
+            max_transitions: None,
+        }
+    }
+}
+
+/// What a [`crate::Client::run_maintenance`] call got done
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    /// State transitions completed during the maintenance
+    pub transitions: u64,
+    /// State machines still active afterwards, e.g. waiting for a payment or
+    /// for the federation to reach consensus
+    pub active_state_machines: usize,
+    /// Whether the maintenance stopped because the budget ran out rather than
+    /// because no active state machines were left
+    pub budget_exhausted: bool,
+    pub elapsed: Duration,
+}
@@ -0,0 +1,135 @@
+//! Bounds on the memory the client keeps outside of its database
+//!
+//! Apart from the database, the client's memory use is dominated by caches of
+//! session outcomes fetched from the federation and by the buffers of its
+//! broadcast channels. All of them are bounded: caches evict their least
+//! recently used entries and subscribers that lag behind a full buffer miss
+//! messages. The defaults favor throughput; embedders on memory constrained
+//! devices can lower them with [`crate::ClientBuilder::with_memory_budget`]
+//! and watch the result with [`crate::Client::memory_usage`].
+
+use std::num::NonZeroUsize;
+
+use fedimint_api_client::api::{CacheUsage, SessionCacheUsage, DEFAULT_SESSION_CACHE_CAPACITY};
+use serde::{Deserialize, Serialize};
+
+use crate::event_bus::CLIENT_EVENT_BUS_CAPACITY;
+use crate::sm::NOTIFIER_CAPACITY;
+
+/// Number of log events buffered for subscribers by default
+pub const LOG_EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryBudget {
+    /// Sessions kept in each of the federation API's session caches. Session
+    /// outcomes contain all transactions of a session, so this dominates the
+    /// memory use during recovery.
+    pub session_cache: NonZeroUsize,
+    /// State transitions buffered for subscribers of operation updates
+    pub notifier_capacity: NonZeroUsize,
+    /// Events buffered for subscribers of the client event bus
+    pub client_event_capacity: NonZeroUsize,
+    /// Events buffered for subscribers of the event log
+    pub log_event_capacity: NonZeroUsize,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self {
+            session_cache: DEFAULT_SESSION_CACHE_CAPACITY,
+            notifier_capacity: NonZeroUsize::new(NOTIFIER_CAPACITY).expect("is non-zero"),
+            client_event_capacity: NonZeroUsize::new(CLIENT_EVENT_BUS_CAPACITY)
+                .expect("is non-zero"),
+            log_event_capacity: NonZeroUsize::new(LOG_EVENT_CAPACITY).expect("is non-zero"),
+        }
+    }
+}
+
+impl MemoryBudget {
+    /// Budget keeping the client within a few tens of MB on low-end mobile
+    /// devices, at the cost of re-fetching sessions during recovery and update
+    /// streams of busy clients ending early
+    pub fn low_memory() -> Self {
+        Self {
+            session_cache: NonZeroUsize::new(16).expect("is non-zero"),
+            notifier_capacity: NonZeroUsize::new(256).expect("is non-zero"),
+            client_event_capacity: NonZeroUsize::new(64).expect("is non-zero"),
+            log_event_capacity: NonZeroUsize::new(64).expect("is non-zero"),
+        }
+    }
+}
+
+/// Snapshot of how full the client's caches and buffers are, see
+/// [`crate::Client::memory_usage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    pub session_cache: SessionCacheUsage,
+    pub notifications: CacheUsage,
+    /// State transitions subscribers missed so far because the notification
+    /// buffer was full
+    pub missed_notifications: u64,
+    pub client_events: CacheUsage,
+    pub log_events: CacheUsage,
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::event_bus::{ClientEvent, ClientEventEnvelope};
+    use crate::ClientBuilder;
+
+    #[test]
+    fn low_memory_budget_is_below_default() {
+        let default = MemoryBudget::default();
+        let low_memory = MemoryBudget::low_memory();
+
+        assert!(low_memory.session_cache < default.session_cache);
+        assert!(low_memory.notifier_capacity < default.notifier_capacity);
+        assert!(low_memory.client_event_capacity < default.client_event_capacity);
+        assert!(low_memory.log_event_capacity < default.log_event_capacity);
+    }
+
+    #[test]
+    fn memory_budget_rejects_zero_capacities() {
+        let budget = serde_json::to_value(MemoryBudget::low_memory()).expect("Can be serialized");
+        assert_eq!(
+            serde_json::from_value::<MemoryBudget>(budget.clone()).expect("Valid budget"),
+            MemoryBudget::low_memory()
+        );
+
+        for capacity in [
+            "session_cache",
+            "notifier_capacity",
+            "client_event_capacity",
+            "log_event_capacity",
+        ] {
+            let mut budget = budget.clone();
+            budget[capacity] = 0.into();
+            assert!(serde_json::from_value::<MemoryBudget>(budget).is_err());
+        }
+    }
+
+    #[test]
+    fn memory_budget_keeps_event_channels_shared_with_existing_client() {
+        let (client_event_tx, mut client_events) = broadcast::channel(CLIENT_EVENT_BUS_CAPACITY);
+        let mut builder = ClientBuilder::new(MemDatabase::new().into());
+        builder.client_event_tx = Some(client_event_tx);
+        builder.with_memory_budget(MemoryBudget::low_memory());
+
+        let event = ClientEventEnvelope {
+            module_instance_id: 0,
+            event: ClientEvent::PaymentReceived {
+                operation_id: OperationId::new_random(),
+            },
+        };
+        builder
+            .client_event_tx()
+            .send(event.clone())
+            .expect("Subscriber of the existing client is still listening");
+        assert_eq!(client_events.try_recv().expect("Event was sent"), event);
+    }
+}
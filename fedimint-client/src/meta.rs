@@ -6,11 +6,13 @@
 use anyhow::{bail, Context as _};
 use async_stream::stream;
 use fedimint_api_client::api::DynGlobalApi;
-use fedimint_core::config::ClientConfig;
+use fedimint_core::config::{
+    ClientConfig, META_FEDERATION_ICON_URL_KEY, META_FEDERATION_NAME_KEY, META_WELCOME_MESSAGE_KEY,
+};
 use fedimint_core::db::{Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped};
 use fedimint_core::task::waiter::Waiter;
 use fedimint_core::task::{MaybeSend, MaybeSync};
-use fedimint_core::util::{backoff_util, retry};
+use fedimint_core::util::{backoff_util, retry, SafeUrl};
 use fedimint_core::{apply, async_trait_maybe_send};
 use fedimint_logging::LOG_CLIENT;
 use serde::de::DeserializeOwned;
@@ -348,3 +350,50 @@ pub fn parse_meta_value_static<V: DeserializeOwned + 'static>(
         res
     }
 }
+
+/// Human-friendly branding of a federation that wallets can display instead of
+/// the raw federation id.
+///
+/// Initially set by the guardians during federation setup, later updates
+/// agreed on by the guardians (e.g. via the meta module) take precedence.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FederationMeta {
+    /// See [`META_FEDERATION_NAME_KEY`]
+    pub name: Option<String>,
+    /// See [`META_FEDERATION_ICON_URL_KEY`]
+    pub icon_url: Option<SafeUrl>,
+    /// See [`META_WELCOME_MESSAGE_KEY`]
+    pub welcome_message: Option<String>,
+}
+
+impl Client {
+    /// Returns the name, icon and welcome message of the federation, if set.
+    ///
+    /// Like [`MetaService::get_field`] this may wait for the meta fields to be
+    /// fetched for the first time.
+    pub async fn federation_meta(&self) -> FederationMeta {
+        let icon_url = self
+            .meta_field::<String>(META_FEDERATION_ICON_URL_KEY)
+            .await
+            .and_then(|icon_url| match icon_url.parse() {
+                Ok(icon_url) => Some(icon_url),
+                Err(e) => {
+                    warn!(target: LOG_CLIENT, %icon_url, err = %e, "Invalid federation icon url");
+                    None
+                }
+            });
+
+        FederationMeta {
+            name: self.meta_field(META_FEDERATION_NAME_KEY).await,
+            icon_url,
+            welcome_message: self.meta_field(META_WELCOME_MESSAGE_KEY).await,
+        }
+    }
+
+    async fn meta_field<V: DeserializeOwned + 'static>(&self, field: &str) -> Option<V> {
+        self.meta_service()
+            .get_field::<V>(self.db(), field)
+            .await
+            .and_then(|meta_value| meta_value.value)
+    }
+}
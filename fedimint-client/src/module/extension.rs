@@ -0,0 +1,196 @@
+//! Extensions of client modules registered by downstream crates
+//!
+//! Code that only knows a module by its kind, like a wallet UI listing all
+//! operations, can't call into the typed client module of a custom module.
+//! Crates providing such a module can register a [`ClientModuleExtension`] for
+//! it with [`crate::ClientBuilder::with_module_extension`], which the client
+//! dispatches to dynamically to summarize operations and account for funds not
+//! part of the spendable balance. Arbitrary other dyn interfaces can be
+//! registered per kind with [`crate::ClientBuilder::with_module_interface`] and
+//! looked up with [`crate::Client::module_interface`].
+
+use std::any::{Any, TypeId};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use fedimint_core::core::{ModuleInstanceId, ModuleKind};
+use fedimint_core::db::DatabaseTransaction;
+use fedimint_core::task::{MaybeSend, MaybeSync};
+use fedimint_core::{
+    apply, async_trait_maybe_send, dyn_newtype_define, maybe_add_send_sync, Amount,
+};
+use serde_json::Value;
+
+use super::{ClientModule, DynClientModule};
+use crate::oplog::OperationLogEntry;
+
+/// Extension of the client module [`Self::Module`], see the
+/// [module docs](self)
+#[apply(async_trait_maybe_send!)]
+pub trait ClientModuleExtension: Debug + MaybeSend + MaybeSync + 'static {
+    type Module: ClientModule;
+
+    /// Summary of an operation of the module to show in operation listings,
+    /// `None` if the operation should be listed without one
+    fn operation_summary(
+        &self,
+        _module: &Self::Module,
+        _operation: &OperationLogEntry,
+    ) -> Option<Value> {
+        None
+    }
+
+    /// Funds the module holds for the user that are not part of the
+    /// spendable balance, e.g. because they are still pending or locked
+    async fn balance_contribution(
+        &self,
+        _module: &Self::Module,
+        _dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Amount {
+        Amount::ZERO
+    }
+}
+
+/// Type-erased version of [`ClientModuleExtension`]
+#[apply(async_trait_maybe_send!)]
+pub trait IClientModuleExtension: Debug + MaybeSend + MaybeSync {
+    fn operation_summary(
+        &self,
+        module: &DynClientModule,
+        operation: &OperationLogEntry,
+    ) -> Option<Value>;
+
+    async fn balance_contribution(
+        &self,
+        module: &DynClientModule,
+        module_instance: ModuleInstanceId,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Amount;
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<T> IClientModuleExtension for T
+where
+    T: ClientModuleExtension,
+{
+    fn operation_summary(
+        &self,
+        module: &DynClientModule,
+        operation: &OperationLogEntry,
+    ) -> Option<Value> {
+        <T as ClientModuleExtension>::operation_summary(self, downcast::<T>(module)?, operation)
+    }
+
+    async fn balance_contribution(
+        &self,
+        module: &DynClientModule,
+        module_instance: ModuleInstanceId,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Amount {
+        let Some(module) = downcast::<T>(module) else {
+            return Amount::ZERO;
+        };
+
+        <T as ClientModuleExtension>::balance_contribution(
+            self,
+            module,
+            &mut dbtx.to_ref_with_prefix_module_id(module_instance).0,
+        )
+        .await
+    }
+}
+
+/// Extensions are registered by kind, so a module of a different type could
+/// still be registered under the same kind, which we ignore
+fn downcast<T: ClientModuleExtension>(module: &DynClientModule) -> Option<&T::Module> {
+    module.as_any().downcast_ref::<T::Module>()
+}
+
+dyn_newtype_define!(
+    #[derive(Clone)]
+    pub DynClientModuleExtension(Arc<IClientModuleExtension>)
+);
+
+/// Extensions and dyn interfaces registered for module kinds, see the
+/// [module docs](self)
+#[derive(Debug, Clone, Default)]
+pub struct ClientModuleExtensionRegistry {
+    extensions: BTreeMap<ModuleKind, DynClientModuleExtension>,
+    interfaces: BTreeMap<(ModuleKind, TypeId), Arc<maybe_add_send_sync!(dyn Any)>>,
+}
+
+impl ClientModuleExtensionRegistry {
+    /// Registers `extension` for the kind of its module, replacing any
+    /// extension registered for the kind before
+    pub fn attach<E: ClientModuleExtension>(&mut self, extension: E) {
+        self.extensions.insert(
+            <E::Module as ClientModule>::kind(),
+            DynClientModuleExtension::from(extension),
+        );
+    }
+
+    /// Registers `interface` as the implementation of `I` for modules of
+    /// `kind`, replacing any implementation registered for the kind before
+    pub fn attach_interface<I>(&mut self, kind: ModuleKind, interface: Arc<I>)
+    where
+        I: ?Sized + MaybeSend + MaybeSync + 'static,
+    {
+        self.interfaces
+            .insert((kind, TypeId::of::<I>()), Arc::new(interface));
+    }
+
+    pub fn get(&self, kind: &ModuleKind) -> Option<&DynClientModuleExtension> {
+        self.extensions.get(kind)
+    }
+
+    pub fn get_interface<I>(&self, kind: &ModuleKind) -> Option<Arc<I>>
+    where
+        I: ?Sized + 'static,
+    {
+        self.interfaces
+            .get(&(kind.clone(), TypeId::of::<I>()))
+            .and_then(|interface| interface.downcast_ref::<Arc<I>>())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use fedimint_core::core::ModuleKind;
+
+    use super::ClientModuleExtensionRegistry;
+
+    trait Greeter: Send + Sync {
+        fn greet(&self) -> String;
+    }
+
+    struct English;
+
+    impl Greeter for English {
+        fn greet(&self) -> String {
+            "hello".to_owned()
+        }
+    }
+
+    #[test]
+    fn interfaces_are_looked_up_by_kind_and_type() {
+        let kind = ModuleKind::from_static_str("custom");
+        let other_kind = ModuleKind::from_static_str("other");
+
+        let mut registry = ClientModuleExtensionRegistry::default();
+        registry.attach_interface::<dyn Greeter>(kind.clone(), Arc::new(English));
+
+        assert_eq!(
+            registry
+                .get_interface::<dyn Greeter>(&kind)
+                .expect("registered")
+                .greet(),
+            "hello"
+        );
+        assert!(registry.get_interface::<dyn Greeter>(&other_kind).is_none());
+        assert!(registry.get_interface::<English>(&kind).is_none());
+    }
+}
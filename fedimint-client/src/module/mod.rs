@@ -18,20 +18,23 @@
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::module::registry::{ModuleDecoderRegistry, ModuleRegistry};
 use fedimint_core::module::{CommonModuleInit, ModuleCommon, ModuleInit};
-use fedimint_core::task::{MaybeSend, MaybeSync};
+use fedimint_core::task::{Cancellable, MaybeSend, MaybeSync};
 use fedimint_core::util::BoxStream;
 use fedimint_core::{
     apply, async_trait_maybe_send, dyn_newtype_define, maybe_add_send_sync, Amount, OutPoint,
     TransactionId,
 };
 use fedimint_eventlog::Event;
-use futures::Stream;
+use futures::{Future, Stream, StreamExt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use self::init::ClientModuleInit;
+use crate::config_change::{FederationConfigChange, PendingConfigChangeKey};
+use crate::event_bus::ClientEvent;
 use crate::module::recovery::{DynModuleBackup, ModuleBackup};
 use crate::oplog::{OperationLogEntry, UpdateStreamOrOutcome};
+use crate::push::PushWatch;
 use crate::sm::{self, ActiveStateMeta, Context, DynContext, DynState, State};
 use crate::transaction::{ClientInputBundle, ClientOutputBundle, TransactionBuilder};
 use crate::{
@@ -39,6 +42,7 @@
     InstancelessDynClientInputBundle, TransactionUpdates,
 };
 
+pub mod extension;
 pub mod init;
 pub mod recovery;
 
@@ -149,6 +153,25 @@ pub fn self_ref<'s>(&'s self) -> ClientContextSelfRef<'s, M> {
         }
     }
 
+    /// Asks the notification relay to wake the wallet once the module API
+    /// `method` returns for `params`, see [`crate::push`]
+    pub async fn add_push_watch(
+        &self,
+        label: String,
+        method: &str,
+        params: impl Serialize,
+    ) -> anyhow::Result<()> {
+        self.client
+            .get()
+            .add_push_watch(PushWatch {
+                label,
+                module_instance_id: Some(self.module_instance_id),
+                method: method.to_owned(),
+                params: serde_json::to_value(params)?,
+            })
+            .await
+    }
+
     /// Get a reference to a global Api handle
     pub fn global_api(&self) -> DynGlobalApi {
         self.client.get().api_clone()
@@ -348,6 +371,20 @@ pub async fn get_config(&self) -> ClientConfig {
         self.client.get().config().await
     }
 
+    /// Waits until a change of the federation's client config affecting this
+    /// module is detected, so update streams can tell the user that pending
+    /// operations may need the change applied, see [`crate::config_change`]
+    pub async fn await_config_change(&self) -> FederationConfigChange {
+        self.client
+            .get()
+            .db()
+            .wait_key_check(&PendingConfigChangeKey, |change| {
+                change.filter(|change| change.affects_module(self.module_instance_id))
+            })
+            .await
+            .0
+    }
+
     /// Returns an invite code for the federation that points to an arbitrary
     /// guardian server for fetching the config
     pub async fn get_invite_code(&self) -> InviteCode {
@@ -466,7 +503,26 @@ pub fn outcome_or_updates<U, S>(
         U: Clone + Serialize + DeserializeOwned + Debug + MaybeSend + MaybeSync + 'static,
         S: Stream<Item = U> + MaybeSend + 'static,
     {
-        operation.outcome_or_updates(&self.global_db(), operation_id, stream_gen)
+        let cancelled = self
+            .client
+            .get()
+            .operation_cancellation_token(operation_id)
+            .cancelled();
+        operation.outcome_or_updates(&self.global_db(), operation_id, || {
+            stream_gen().take_until(cancelled)
+        })
+    }
+
+    /// Run `fut` until it completes or awaiting `operation_id` gets aborted
+    /// with [`crate::Client::cancel_operation_awaits`]
+    pub async fn await_cancellable<F: Future>(
+        &self,
+        operation_id: OperationId,
+        fut: F,
+    ) -> Cancellable<F::Output> {
+        // Don't hold on to the client while awaiting, see `FinalClient::get`
+        let token = self.client.get().operation_cancellation_token(operation_id);
+        token.run_until_cancelled(fut).await
     }
 
     pub async fn claim_inputs<I, S>(
@@ -547,6 +603,22 @@ pub async fn log_event<E, Cap>(&self, dbtx: &mut DatabaseTransaction<'_, Cap>, e
             )
             .await;
     }
+
+    /// Publishes `event` on the client event bus once `dbtx` committed, see
+    /// [`crate::event_bus`]
+    pub fn publish_client_event<Cap>(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_, Cap>,
+        event: ClientEvent,
+    ) where
+        Cap: Send,
+    {
+        self.client.get().publish_client_event_dbtx(
+            &mut dbtx.global_dbtx(self.global_dbtx_access_token),
+            self.module_instance_id,
+            event,
+        );
+    }
 }
 
 /// Fedimint module client
@@ -769,6 +841,16 @@ async fn subscribe_balance_changes(&self) -> BoxStream<'static, ()> {
     async fn leave(&self, _dbtx: &mut DatabaseTransaction<'_>) -> anyhow::Result<()> {
         bail!("Unable to determine if safe to leave the federation: Not implemented")
     }
+
+    /// Checks the module's local state for inconsistencies, returning a
+    /// description of every one found
+    ///
+    /// Called every time the client is built, see [`crate::health`], so
+    /// implementations have to be fast, e.g. by only checking a sample of
+    /// large datasets.
+    async fn check_integrity(&self, _dbtx: &mut DatabaseTransaction<'_>) -> Vec<String> {
+        vec![]
+    }
 }
 
 /// Type-erased version of [`ClientModule`]
@@ -824,6 +906,12 @@ async fn get_balance(
     ) -> Amount;
 
     async fn subscribe_balance_changes(&self) -> BoxStream<'static, ()>;
+
+    async fn check_integrity(
+        &self,
+        module_instance: ModuleInstanceId,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Vec<String>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -949,6 +1037,18 @@ async fn get_balance(
     async fn subscribe_balance_changes(&self) -> BoxStream<'static, ()> {
         <T as ClientModule>::subscribe_balance_changes(self).await
     }
+
+    async fn check_integrity(
+        &self,
+        module_instance: ModuleInstanceId,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Vec<String> {
+        <T as ClientModule>::check_integrity(
+            self,
+            &mut dbtx.to_ref_with_prefix_module_id(module_instance).0,
+        )
+        .await
+    }
 }
 
 dyn_newtype_define!(
@@ -0,0 +1,261 @@
+//! Simulated network conditions between the client and the federation, for
+//! tests
+//!
+//! State machines are supposed to survive slow guardians, lost messages and
+//! connections dropping while they await an outcome, but such conditions are
+//! rare on the loopback interface tests run on and reproducing them used to
+//! require `tc`/`netem` setups. A client built with
+//! [`crate::ClientBuilder::with_network_conditions`] sends all its federation
+//! requests through a [`SimulatedNetworkApi`] that injects latency, loses
+//! requests and responses and drops pending requests according to
+//! [`NetworkConditions`].
+//!
+//! Only available with the `network-sim` feature, which is meant to be
+//! enabled by test crates only. With the feature enabled, clients built
+//! without network conditions simulate a perfect network.
+
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::result;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use fedimint_api_client::api::{
+    DynModuleApi, IModuleFederationApi, IRawFederationApi, JsonRpcClientError,
+};
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::task::{sleep, MaybeSend, MaybeSync};
+use fedimint_core::util::SafeUrl;
+use fedimint_core::{apply, async_trait_maybe_send, PeerId};
+use fedimint_logging::LOG_CLIENT_NET_API;
+use futures::future::{select, Either};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+use tracing::trace;
+
+/// Network conditions simulated by [`SimulatedNetworkApi`], the default
+/// simulates a perfect network
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkConditions {
+    /// Delay added to every request, half of it before the request is sent
+    /// and half after the response arrived
+    pub latency: Duration,
+    /// Upper bound of an additional random delay added to every request
+    pub jitter: Duration,
+    /// Probability of a request getting lost before it reaches the peer
+    pub request_loss: f64,
+    /// Probability of a response getting lost after the peer processed the
+    /// request
+    pub response_loss: f64,
+    /// Probability of the connection dropping while a request is pending, the
+    /// request fails after a random time up to [`Self::max_disconnect_delay`]
+    /// unless the peer responded before
+    pub disconnect: f64,
+    /// Upper bound of how long a request is pending before its connection
+    /// drops, see [`Self::disconnect`]
+    pub max_disconnect_delay: Duration,
+    /// Seed of the random decisions, to make a simulation reproducible
+    pub seed: Option<u64>,
+}
+
+impl NetworkConditions {
+    /// Returns conditions with a constant `latency`
+    pub fn with_latency(latency: Duration) -> Self {
+        Self {
+            latency,
+            ..Self::default()
+        }
+    }
+
+    /// Returns conditions losing a fraction `loss` of the requests and
+    /// responses each
+    pub fn lossy(loss: f64) -> Self {
+        Self {
+            request_loss: loss,
+            response_loss: loss,
+            ..Self::default()
+        }
+    }
+}
+
+/// What happens to a single request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestFate {
+    Delivered,
+    RequestLost,
+    ResponseLost,
+    Disconnected { after: Duration },
+}
+
+/// A wrapper over [`IRawFederationApi`] simulating [`NetworkConditions`], see
+/// the [module docs](self)
+#[derive(Debug)]
+pub struct SimulatedNetworkApi<I> {
+    inner: I,
+    conditions: Arc<NetworkConditions>,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl<I> SimulatedNetworkApi<I> {
+    pub fn new(inner: I, conditions: NetworkConditions) -> Self {
+        let rng = match conditions.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        Self {
+            inner,
+            conditions: Arc::new(conditions),
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    /// Randomly decides the delay and fate of a request
+    fn roll(&self) -> (Duration, RequestFate) {
+        let conditions = &self.conditions;
+        let mut rng = self.rng.lock().expect("poisoned");
+
+        let jitter = if conditions.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            rng.gen_range(Duration::ZERO..=conditions.jitter)
+        };
+
+        let fate = if rng.gen_bool(conditions.request_loss) {
+            RequestFate::RequestLost
+        } else if rng.gen_bool(conditions.response_loss) {
+            RequestFate::ResponseLost
+        } else if rng.gen_bool(conditions.disconnect) {
+            RequestFate::Disconnected {
+                after: rng.gen_range(Duration::ZERO..=conditions.max_disconnect_delay),
+            }
+        } else {
+            RequestFate::Delivered
+        };
+
+        (conditions.latency + jitter, fate)
+    }
+}
+
+fn simulated_error(reason: &str) -> JsonRpcClientError {
+    JsonRpcClientError::Transport(anyhow::format_err!("Simulated network failure: {reason}").into())
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<I> IRawFederationApi for SimulatedNetworkApi<I>
+where
+    I: Deref + Debug + MaybeSend + MaybeSync + 'static,
+    I::Target: IRawFederationApi,
+{
+    fn all_peers(&self) -> &BTreeSet<PeerId> {
+        self.inner.all_peers()
+    }
+
+    fn self_peer(&self) -> Option<PeerId> {
+        self.inner.self_peer()
+    }
+
+    fn with_module(&self, id: ModuleInstanceId) -> DynModuleApi {
+        SimulatedNetworkApi {
+            inner: self.inner.with_module(id),
+            conditions: self.conditions.clone(),
+            rng: self.rng.clone(),
+        }
+        .into()
+    }
+
+    async fn request_raw(
+        &self,
+        peer_id: PeerId,
+        method: &str,
+        params: &[Value],
+    ) -> result::Result<Value, JsonRpcClientError> {
+        let (delay, fate) = self.roll();
+        trace!(target: LOG_CLIENT_NET_API, %peer_id, %method, ?delay, ?fate, "Simulating request");
+
+        sleep(delay / 2).await;
+
+        let res = match fate {
+            RequestFate::Delivered => self.inner.request_raw(peer_id, method, params).await,
+            RequestFate::RequestLost => Err(simulated_error("request lost")),
+            RequestFate::ResponseLost => {
+                // The peer still processes the request, we just never learn the outcome
+                let _ = self.inner.request_raw(peer_id, method, params).await;
+                Err(simulated_error("response lost"))
+            }
+            RequestFate::Disconnected { after } => {
+                match select(
+                    self.inner.request_raw(peer_id, method, params),
+                    Box::pin(sleep(after)),
+                )
+                .await
+                {
+                    Either::Left((res, _)) => res,
+                    Either::Right(((), _)) => Err(simulated_error("connection dropped")),
+                }
+            }
+        };
+
+        sleep(delay - delay / 2).await;
+
+        res
+    }
+
+    async fn set_peer_url(&self, peer_id: PeerId, url: SafeUrl) {
+        self.inner.set_peer_url(peer_id, url).await;
+    }
+}
+
+impl<I> IModuleFederationApi for SimulatedNetworkApi<I>
+where
+    I: Deref + Debug + MaybeSend + MaybeSync + 'static,
+    I::Target: IRawFederationApi,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{NetworkConditions, RequestFate, SimulatedNetworkApi};
+
+    #[test]
+    fn rolls_are_reproducible_and_respect_conditions() {
+        let conditions = NetworkConditions {
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_millis(50),
+            request_loss: 0.2,
+            response_loss: 0.2,
+            disconnect: 0.2,
+            max_disconnect_delay: Duration::from_secs(1),
+            seed: Some(42),
+        };
+
+        let rolls = |conditions: NetworkConditions| {
+            let api = SimulatedNetworkApi::new((), conditions);
+            (0..100).map(|_| api.roll()).collect::<Vec<_>>()
+        };
+
+        let first = rolls(conditions.clone());
+        assert_eq!(first, rolls(conditions.clone()));
+
+        for (delay, fate) in &first {
+            assert!(Duration::from_millis(100) <= *delay && *delay <= Duration::from_millis(150));
+            if let RequestFate::Disconnected { after } = fate {
+                assert!(*after <= Duration::from_secs(1));
+            }
+        }
+        assert!(first
+            .iter()
+            .any(|(_, fate)| *fate == RequestFate::Delivered));
+        assert!(first
+            .iter()
+            .any(|(_, fate)| *fate != RequestFate::Delivered));
+
+        assert!(rolls(NetworkConditions::default())
+            .iter()
+            .all(|(delay, fate)| delay.is_zero() && *fate == RequestFate::Delivered));
+    }
+}
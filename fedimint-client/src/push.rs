@@ -0,0 +1,248 @@
+//! Push notifications for events the wallet is waiting for
+//!
+//! Mobile wallets can't keep a connection to the federation open in the
+//! background. Instead they register an opaque push token (e.g. from APNs or
+//! FCM) with a notification relay and tell the relay which federation API
+//! calls to watch. The relay keeps calling each watched endpoint, which blocks
+//! until the event happened (e.g. `await_account` for the contract of an
+//! incoming lightning payment), and wakes the wallet by sending a push
+//! notification carrying the watch's label to the token once it returns.
+//!
+//! # Relay protocol
+//!
+//! The relay is an HTTP service accepting JSON requests:
+//!
+//! * `POST {relay}/v1/register` with a [`PushRegistration`] registers the
+//!   token and adds the watches to the ones already registered for the token
+//!   and federation. Registering a token again without watches is a no-op.
+//! * `POST {relay}/v1/unregister` with a [`PushUnregistration`] drops the
+//!   token and all its watches for the federation.
+//!
+//! Any `2xx` response status means success. The relay sends one notification
+//! per watch and forgets the watch afterwards. A notification only tells the
+//! wallet to wake up, the wallet still learns the outcome from the federation
+//! itself, so the relay can't steal funds or forge payments.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _};
+use fedimint_core::config::FederationId;
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::util::SafeUrl;
+use fedimint_core::{impl_db_record, runtime, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::db::DbKeyPrefix;
+
+pub const PUSH_RELAY_REGISTER_PATH: &str = "v1/register";
+pub const PUSH_RELAY_UNREGISTER_PATH: &str = "v1/unregister";
+
+/// How long we wait for the relay to answer a request
+pub const PUSH_RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Request registering a push token and watches with the relay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushRegistration {
+    /// Opaque token the relay sends notifications to
+    pub token: String,
+    pub federation_id: FederationId,
+    /// API endpoints of the guardians the relay should call
+    pub api_endpoints: BTreeMap<PeerId, SafeUrl>,
+    pub watches: Vec<PushWatch>,
+}
+
+/// Request dropping a push token and all its watches for a federation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushUnregistration {
+    pub token: String,
+    pub federation_id: FederationId,
+}
+
+/// Federation API call that returns once the event the wallet waits for
+/// happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushWatch {
+    /// Opaque to the relay and sent in the notification, so the wallet knows
+    /// which operation to look at
+    pub label: String,
+    /// Module the endpoint belongs to, `None` for core endpoints
+    pub module_instance_id: Option<ModuleInstanceId>,
+    pub method: String,
+    /// Parameters of the call, as sent in the `params` of the API request
+    pub params: serde_json::Value,
+}
+
+/// Relay and token the client is registered with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct PushTokenRegistration {
+    pub relay_url: SafeUrl,
+    pub token: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct PushTokenRegistrationKey;
+
+impl_db_record!(
+    key = PushTokenRegistrationKey,
+    value = PushTokenRegistration,
+    db_prefix = DbKeyPrefix::PushTokenRegistration,
+);
+
+/// HTTP client for the relay protocol described in the [module
+/// docs](crate::push)
+#[derive(Debug, Clone)]
+pub struct PushRelayClient {
+    relay_url: SafeUrl,
+    http: reqwest::Client,
+}
+
+impl PushRelayClient {
+    pub fn new(relay_url: SafeUrl) -> Self {
+        Self {
+            relay_url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn register(&self, registration: &PushRegistration) -> anyhow::Result<()> {
+        self.post(PUSH_RELAY_REGISTER_PATH, registration).await
+    }
+
+    pub async fn unregister(&self, unregistration: &PushUnregistration) -> anyhow::Result<()> {
+        self.post(PUSH_RELAY_UNREGISTER_PATH, unregistration).await
+    }
+
+    async fn post(&self, path: &str, body: &impl Serialize) -> anyhow::Result<()> {
+        let url = self
+            .relay_url
+            .join(path)
+            .context("Invalid push relay url")?;
+
+        let response = runtime::timeout(
+            PUSH_RELAY_TIMEOUT,
+            self.http.post(url.to_unsafe()).json(body).send(),
+        )
+        .await
+        .context("Push relay timed out")?
+        .context("Push relay could not be reached")?;
+
+        if !response.status().is_success() {
+            bail!("Push relay returned status {}", response.status());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use std::sync::mpsc;
+
+    use fedimint_core::config::FederationId;
+    use fedimint_core::util::SafeUrl;
+    use fedimint_core::PeerId;
+
+    use super::{PushRegistration, PushRelayClient, PushUnregistration, PushWatch};
+
+    /// Serves a single HTTP request with `status` and returns the relay url
+    /// and a receiver for the request line and body
+    fn relay_responding_with(status: &str) -> (SafeUrl, mpsc::Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let relay_url = SafeUrl::from_str(&format!(
+            "http://{}/",
+            listener
+                .local_addr()
+                .expect("Bound listener has an address")
+        ))
+        .expect("Valid url");
+        let status = status.to_owned();
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Failed to accept");
+            let mut reader = BufReader::new(stream.try_clone().expect("Failed to clone"));
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).expect("Failed to read");
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).expect("Failed to read");
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().expect("Valid content length");
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).expect("Failed to read");
+
+            write!(stream, "HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n")
+                .expect("Failed to write");
+            sender
+                .send((
+                    request_line.trim().to_owned(),
+                    String::from_utf8(body).expect("Body is JSON"),
+                ))
+                .expect("Receiver is alive");
+        });
+
+        (relay_url, receiver)
+    }
+
+    #[tokio::test]
+    async fn registration_is_posted_to_the_relay() {
+        let (relay_url, requests) = relay_responding_with("200 OK");
+        let registration = PushRegistration {
+            token: "token".to_owned(),
+            federation_id: FederationId::dummy(),
+            api_endpoints: [(
+                PeerId::from(0),
+                SafeUrl::from_str("wss://guardian.example.com").expect("Valid url"),
+            )]
+            .into(),
+            watches: vec![PushWatch {
+                label: "receive".to_owned(),
+                module_instance_id: Some(1),
+                method: "await_account".to_owned(),
+                params: serde_json::json!({ "contract_id": "00" }),
+            }],
+        };
+
+        PushRelayClient::new(relay_url)
+            .register(&registration)
+            .await
+            .expect("Relay accepted the registration");
+
+        let (request_line, body) = requests.recv().expect("Relay received a request");
+        assert_eq!(request_line, "POST /v1/register HTTP/1.1");
+        assert_eq!(
+            serde_json::from_str::<PushRegistration>(&body).expect("Valid registration"),
+            registration
+        );
+    }
+
+    #[tokio::test]
+    async fn relay_errors_are_returned() {
+        let (relay_url, requests) = relay_responding_with("500 Internal Server Error");
+
+        let result = PushRelayClient::new(relay_url)
+            .unregister(&PushUnregistration {
+                token: "token".to_owned(),
+                federation_id: FederationId::dummy(),
+            })
+            .await;
+
+        assert!(result.is_err());
+        let (request_line, _) = requests.recv().expect("Relay received a request");
+        assert_eq!(request_line, "POST /v1/unregister HTTP/1.1");
+    }
+}
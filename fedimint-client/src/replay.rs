@@ -0,0 +1,499 @@
+//! Recording and replaying the federation responses consumed by operations
+//!
+//! Some client bugs only show up for a particular sequence of federation
+//! responses, e.g. a guardian answering late or with an error, which makes
+//! them hard to reproduce once observed in production. A client built with
+//! [`crate::ClientBuilder::with_operation_recording`] records every request
+//! the state machines of an operation make, along with the response. Only the
+//! recordings of the [`RECORDED_OPERATIONS_LIMIT`] most recent operations are
+//! kept. The recording of an operation is exported with
+//! [`crate::Client::operation_recording`] and can be fed to a client built
+//! with [`crate::ClientBuilder::with_replay`], which answers all requests from
+//! the recording instead of contacting the federation. Starting from a copy of
+//! the original client database, the state machines of the operation then
+//! consume exactly the same responses, offline.
+
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::future::Future;
+use std::ops::Deref;
+use std::result;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use fedimint_api_client::api::{
+    DynModuleApi, IModuleFederationApi, IRawFederationApi, JsonRpcClientError,
+};
+use fedimint_core::core::{ModuleInstanceId, OperationId};
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::task::{MaybeSend, MaybeSync};
+use fedimint_core::time::now;
+use fedimint_core::util::SafeUrl;
+use fedimint_core::{apply, async_trait_maybe_send, impl_db_lookup, impl_db_record, PeerId};
+use fedimint_logging::LOG_CLIENT;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::db::{ChronologicalOperationLogKey, ChronologicalOperationLogKeyPrefix, DbKeyPrefix};
+
+/// Number of most recent operations whose recordings are kept, see
+/// [`prune_operation_recordings`]
+pub const RECORDED_OPERATIONS_LIMIT: usize = 100;
+
+tokio::task_local! {
+    /// Operation on whose behalf the current task makes federation requests
+    static CURRENT_OPERATION: OperationId;
+}
+
+/// Runs `future` on behalf of `operation_id`, so [`RecordingFederationApi`]
+/// records the federation requests it makes for the operation
+pub(crate) async fn on_behalf_of<F: Future>(operation_id: OperationId, future: F) -> F::Output {
+    CURRENT_OPERATION.scope(operation_id, future).await
+}
+
+/// A federation request made by one of an operation's state machines and the
+/// response it received
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct RecordedApiCall {
+    /// Set if the request was made through a module's API
+    pub module_instance_id: Option<ModuleInstanceId>,
+    pub peer_id: PeerId,
+    pub method: String,
+    /// JSON encoded request parameters
+    pub params: String,
+    /// JSON encoded response or the error returned by the request
+    pub response: Result<String, String>,
+}
+
+/// All federation responses an operation consumed, in the order they were
+/// received
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationRecording {
+    pub operation_id: OperationId,
+    pub calls: Vec<RecordedApiCall>,
+}
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct RecordedApiCallKey {
+    pub operation_id: OperationId,
+    pub index: u64,
+}
+
+#[derive(Debug, Encodable)]
+pub struct RecordedApiCallPrefix {
+    pub operation_id: OperationId,
+}
+
+impl_db_record!(
+    key = RecordedApiCallKey,
+    value = RecordedApiCall,
+    db_prefix = DbKeyPrefix::RecordedApiCall,
+);
+
+impl_db_lookup!(
+    key = RecordedApiCallKey,
+    query_prefix = RecordedApiCallPrefix
+);
+
+/// Most recent operation whose recording was removed by
+/// [`prune_operation_recordings`], so the next pruning can start from there
+#[derive(Debug, Encodable, Decodable)]
+pub struct RecordingPruneCursorKey;
+
+impl_db_record!(
+    key = RecordingPruneCursorKey,
+    value = ChronologicalOperationLogKey,
+    db_prefix = DbKeyPrefix::RecordingPruneCursor,
+);
+
+/// Removes the recordings of all but the `keep` most recent operations. Only
+/// the operations created since the last pruning are visited.
+pub async fn prune_operation_recordings(db: &Database, keep: usize) {
+    let mut dbtx = db.begin_transaction().await;
+
+    let operations = match dbtx.get_value(&RecordingPruneCursorKey).await {
+        Some(cursor) => {
+            // Operations from the future are not expected, but the clock may have been
+            // adjusted since they were created
+            let end = ChronologicalOperationLogKey {
+                creation_time: now() + Duration::from_secs(60 * 60 * 24),
+                operation_id: OperationId([0; 32]),
+            };
+            dbtx.find_by_range(cursor..end)
+                .await
+                .map(|(key, ())| key)
+                .collect::<Vec<_>>()
+                .await
+        }
+        None => {
+            dbtx.find_by_prefix(&ChronologicalOperationLogKeyPrefix)
+                .await
+                .map(|(key, ())| key)
+                .collect::<Vec<_>>()
+                .await
+        }
+    };
+
+    let Some(prune) = operations
+        .len()
+        .checked_sub(keep)
+        .filter(|prune| *prune > 0)
+    else {
+        return;
+    };
+
+    for key in &operations[..prune] {
+        dbtx.remove_by_prefix(&RecordedApiCallPrefix {
+            operation_id: key.operation_id,
+        })
+        .await;
+    }
+    dbtx.insert_entry(&RecordingPruneCursorKey, &operations[prune - 1])
+        .await;
+
+    if let Err(err) = dbtx.commit_tx_result().await {
+        warn!(target: LOG_CLIENT, %err, "Failed to prune operation recordings");
+    }
+}
+
+/// Reads the recording of an operation, the recording is empty if the
+/// operation made no requests or was not recorded
+pub async fn get_operation_recording(
+    db: &Database,
+    operation_id: OperationId,
+) -> OperationRecording {
+    let mut calls = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&RecordedApiCallPrefix { operation_id })
+        .await
+        .map(|(key, call)| (key.index, call))
+        .collect::<Vec<_>>()
+        .await;
+    calls.sort_by_key(|(index, _)| *index);
+
+    OperationRecording {
+        operation_id,
+        calls: calls.into_iter().map(|(_, call)| call).collect(),
+    }
+}
+
+/// A wrapper over [`IRawFederationApi`] recording all requests made on
+/// behalf of an operation, see the [module docs](self)
+///
+/// The client wraps its API once, the operation a request is made for is
+/// set by the state machine executor using [`on_behalf_of`]. Requests made
+/// outside of an operation are not recorded.
+#[derive(Debug)]
+pub struct RecordingFederationApi<I> {
+    inner: I,
+    module_instance_id: Option<ModuleInstanceId>,
+    db: Database,
+}
+
+impl<I> RecordingFederationApi<I> {
+    pub fn new(inner: I, db: Database) -> Self {
+        Self {
+            inner,
+            module_instance_id: None,
+            db,
+        }
+    }
+
+    async fn record(&self, operation_id: OperationId, call: RecordedApiCall) {
+        let result = self
+            .db
+            .autocommit(
+                |dbtx, _| {
+                    let call = call.clone();
+                    Box::pin(async move {
+                        let index = dbtx
+                            .find_by_prefix_sorted_descending(&RecordedApiCallPrefix {
+                                operation_id,
+                            })
+                            .await
+                            .next()
+                            .await
+                            .map_or(0, |(key, _)| key.index + 1);
+
+                        dbtx.insert_new_entry(
+                            &RecordedApiCallKey {
+                                operation_id,
+                                index,
+                            },
+                            &call,
+                        )
+                        .await;
+
+                        Result::<(), ()>::Ok(())
+                    })
+                },
+                None,
+            )
+            .await;
+
+        if result.is_err() {
+            warn!(target: LOG_CLIENT, operation_id = %operation_id.fmt_short(), "Failed to record api call");
+        }
+    }
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<I> IRawFederationApi for RecordingFederationApi<I>
+where
+    I: Deref + Debug + MaybeSend + MaybeSync + 'static,
+    I::Target: IRawFederationApi,
+{
+    fn all_peers(&self) -> &BTreeSet<PeerId> {
+        self.inner.all_peers()
+    }
+
+    fn self_peer(&self) -> Option<PeerId> {
+        self.inner.self_peer()
+    }
+
+    fn with_module(&self, id: ModuleInstanceId) -> DynModuleApi {
+        RecordingFederationApi {
+            inner: self.inner.with_module(id),
+            module_instance_id: Some(id),
+            db: self.db.clone(),
+        }
+        .into()
+    }
+
+    async fn request_raw(
+        &self,
+        peer_id: PeerId,
+        method: &str,
+        params: &[Value],
+    ) -> result::Result<Value, JsonRpcClientError> {
+        let res = self.inner.request_raw(peer_id, method, params).await;
+
+        let Ok(operation_id) = CURRENT_OPERATION.try_with(|operation_id| *operation_id) else {
+            return res;
+        };
+
+        self.record(
+            operation_id,
+            RecordedApiCall {
+                module_instance_id: self.module_instance_id,
+                peer_id,
+                method: method.to_string(),
+                params: serde_json::to_string(params).expect("JSON serialization should not fail"),
+                response: match &res {
+                    Ok(value) => {
+                        Ok(serde_json::to_string(value)
+                            .expect("JSON serialization should not fail"))
+                    }
+                    Err(e) => Err(e.to_string()),
+                },
+            },
+        )
+        .await;
+
+        res
+    }
+
+    async fn set_peer_url(&self, peer_id: PeerId, url: SafeUrl) {
+        self.inner.set_peer_url(peer_id, url).await;
+    }
+}
+
+impl<I> IModuleFederationApi for RecordingFederationApi<I>
+where
+    I: Deref + Debug + MaybeSend + MaybeSync + 'static,
+    I::Target: IRawFederationApi,
+{
+}
+
+/// An [`IRawFederationApi`] answering requests from a recording instead of
+/// contacting the federation, see the [module docs](self)
+///
+/// Every recorded response is returned once, to the first request matching
+/// the recorded module, peer, method and parameters. Requests without a
+/// matching response fail as if the peer was unreachable.
+#[derive(Debug, Clone)]
+pub struct ReplayFederationApi {
+    peers: BTreeSet<PeerId>,
+    module_instance_id: Option<ModuleInstanceId>,
+    remaining_calls: Arc<Mutex<Vec<RecordedApiCall>>>,
+}
+
+impl ReplayFederationApi {
+    pub fn new(peers: BTreeSet<PeerId>, recording: OperationRecording) -> Self {
+        Self {
+            peers,
+            module_instance_id: None,
+            remaining_calls: Arc::new(Mutex::new(recording.calls)),
+        }
+    }
+}
+
+#[apply(async_trait_maybe_send!)]
+impl IRawFederationApi for ReplayFederationApi {
+    fn all_peers(&self) -> &BTreeSet<PeerId> {
+        &self.peers
+    }
+
+    fn self_peer(&self) -> Option<PeerId> {
+        None
+    }
+
+    fn with_module(&self, id: ModuleInstanceId) -> DynModuleApi {
+        ReplayFederationApi {
+            module_instance_id: Some(id),
+            ..self.clone()
+        }
+        .into()
+    }
+
+    async fn request_raw(
+        &self,
+        peer_id: PeerId,
+        method: &str,
+        params: &[Value],
+    ) -> result::Result<Value, JsonRpcClientError> {
+        let params = serde_json::to_string(params).expect("JSON serialization should not fail");
+
+        let call = {
+            let mut remaining_calls = self.remaining_calls.lock().expect("poisoned");
+            remaining_calls
+                .iter()
+                .position(|call| {
+                    call.module_instance_id == self.module_instance_id
+                        && call.peer_id == peer_id
+                        && call.method == method
+                        && call.params == params
+                })
+                .map(|position| remaining_calls.remove(position))
+        };
+
+        match call.map(|call| call.response) {
+            Some(Ok(response)) => serde_json::from_str(&response)
+                .map_err(|e| JsonRpcClientError::Custom(format!("Invalid recorded response: {e}"))),
+            Some(Err(error)) => Err(JsonRpcClientError::Custom(error)),
+            None => Err(JsonRpcClientError::Custom(format!(
+                "No recorded response for {method} to peer {peer_id}"
+            ))),
+        }
+    }
+}
+
+impl IModuleFederationApi for ReplayFederationApi {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use fedimint_api_client::api::IRawFederationApi;
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{IDatabaseTransactionOpsCoreTyped, IRawDatabaseExt};
+    use fedimint_core::PeerId;
+    use serde_json::json;
+
+    use super::{
+        get_operation_recording, prune_operation_recordings, OperationRecording, RecordedApiCall,
+        RecordedApiCallKey, ReplayFederationApi,
+    };
+    use crate::oplog::OperationLog;
+
+    fn call(method: &str, params: &str, response: Result<&str, &str>) -> RecordedApiCall {
+        RecordedApiCall {
+            module_instance_id: None,
+            peer_id: PeerId::from(0),
+            method: method.to_string(),
+            params: params.to_string(),
+            response: response
+                .map(ToString::to_string)
+                .map_err(ToString::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_responses_in_order() {
+        let api = ReplayFederationApi::new(
+            BTreeSet::from([PeerId::from(0)]),
+            OperationRecording {
+                operation_id: OperationId([0; 32]),
+                calls: vec![
+                    call("status", "[1]", Err("timeout")),
+                    call("status", "[2]", Ok("\"other\"")),
+                    call("status", "[1]", Ok("\"done\"")),
+                ],
+            },
+        );
+
+        let peer = PeerId::from(0);
+        assert!(api.request_raw(peer, "status", &[json!(1)]).await.is_err());
+        assert_eq!(
+            api.request_raw(peer, "status", &[json!(1)]).await.unwrap(),
+            json!("done")
+        );
+        assert!(api.request_raw(peer, "status", &[json!(1)]).await.is_err());
+        assert_eq!(
+            api.request_raw(peer, "status", &[json!(2)]).await.unwrap(),
+            json!("other")
+        );
+        assert!(api
+            .with_module(0)
+            .request_raw(peer, "status", &[json!(2)])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn prunes_recordings_of_old_operations() {
+        let db = MemDatabase::new().into_database();
+        let op_log = OperationLog::new(db.clone());
+
+        let record_operation = |operation_id| {
+            let db = db.clone();
+            let op_log = op_log.clone();
+            async move {
+                let mut dbtx = db.begin_transaction().await;
+                op_log
+                    .add_operation_log_entry(&mut dbtx.to_ref_nc(), operation_id, "foo", "bar")
+                    .await;
+                dbtx.insert_new_entry(
+                    &RecordedApiCallKey {
+                        operation_id,
+                        index: 0,
+                    },
+                    &call("status", "[]", Ok("null")),
+                )
+                .await;
+                dbtx.commit_tx().await;
+                // Make sure the operations are created at different times
+                fedimint_core::task::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        };
+        let operations = (0..4).map(|i| OperationId([i; 32])).collect::<Vec<_>>();
+        let is_recorded = |operation_id| {
+            let db = db.clone();
+            async move {
+                !get_operation_recording(&db, operation_id)
+                    .await
+                    .calls
+                    .is_empty()
+            }
+        };
+
+        for operation_id in &operations[..3] {
+            record_operation(*operation_id).await;
+        }
+        prune_operation_recordings(&db, 2).await;
+        assert!(!is_recorded(operations[0]).await);
+        assert!(is_recorded(operations[1]).await);
+        assert!(is_recorded(operations[2]).await);
+
+        record_operation(operations[3]).await;
+        prune_operation_recordings(&db, 2).await;
+        assert!(!is_recorded(operations[1]).await);
+        assert!(is_recorded(operations[2]).await);
+        assert!(is_recorded(operations[3]).await);
+    }
+}
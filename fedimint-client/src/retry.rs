@@ -0,0 +1,308 @@
+//! Retry budgets of the external actions state machines perform
+//!
+//! State machines can't fail a transition, so calls to the federation or to a
+//! gateway are retried until they succeed. If the other side is broken for
+//! good, such an operation used to retry forever, with nothing but log lines
+//! to show for it. State machines retrying through
+//! [`crate::DynGlobalClientContext::retry`] get a budget of attempts set by the
+//! client's [`RetryPolicy`]. Once it's exhausted the action lands in a
+//! dead-letter queue, queryable with [`crate::Client::failed_operations`], and
+//! is only retried every [`RetryPolicy::dead_letter_interval`] from then on.
+//! With the default policy that means an action is retried with its own
+//! backoff for 100 attempts, then once an hour for as long as its operation is
+//! active. Giving up entirely could strand funds, so the action leaves the
+//! queue again if a later attempt succeeds.
+//!
+//! The budget counts attempts since the state machine was last started, so
+//! restarting the client gives dead-lettered actions a fresh budget. Entries of
+//! operations whose state machines finished are pruned when the client starts
+//! and whenever the queue is queried, see [`prune_failed_operations`].
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use fedimint_core::core::OperationId;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::util::backoff_util::Backoff;
+use fedimint_core::{impl_db_lookup, impl_db_record, runtime};
+use fedimint_logging::LOG_CLIENT;
+use futures::{Future, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::db::DbKeyPrefix;
+use crate::DynGlobalClientContext;
+
+/// How often the external actions of state machines are attempted before the
+/// operation is dead-lettered, see the [module docs](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Attempts after which an action is dead-lettered, actions whose backoff
+    /// gives up earlier are dead-lettered then
+    pub max_attempts: u64,
+    /// Delay between the attempts of a dead-lettered action
+    pub dead_letter_interval: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Dead-letters actions after 100 attempts and retries them hourly from
+    /// then on
+    fn default() -> Self {
+        Self {
+            max_attempts: 100,
+            dead_letter_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// An external action of an operation that exhausted its retry budget
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct FailedOperation {
+    pub operation_id: OperationId,
+    /// Name of the action, e.g. `gateway-send-payment`
+    pub action: String,
+    /// Attempts made since the state machine was last started
+    pub attempts: u64,
+    pub last_error: String,
+    /// When the action was dead-lettered
+    pub failed_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct FailedOperationKey {
+    pub operation_id: OperationId,
+    pub action: String,
+}
+
+#[derive(Debug, Encodable)]
+pub struct FailedOperationKeyPrefix;
+
+impl_db_record!(
+    key = FailedOperationKey,
+    value = FailedOperation,
+    db_prefix = DbKeyPrefix::FailedOperation,
+);
+
+impl_db_lookup!(
+    key = FailedOperationKey,
+    query_prefix = FailedOperationKeyPrefix
+);
+
+/// Adds `failure` to the dead-letter queue, keeping the time the action was
+/// first dead-lettered if it already was
+pub async fn record_failed_operation(db: &Database, failure: FailedOperation) {
+    let mut dbtx = db.begin_transaction().await;
+    let key = FailedOperationKey {
+        operation_id: failure.operation_id,
+        action: failure.action.clone(),
+    };
+    let failed_at = dbtx
+        .get_value(&key)
+        .await
+        .map_or(failure.failed_at, |previous| previous.failed_at);
+    dbtx.insert_entry(
+        &key,
+        &FailedOperation {
+            failed_at,
+            ..failure
+        },
+    )
+    .await;
+    dbtx.commit_tx().await;
+}
+
+/// Removes `action` of `operation_id` from the dead-letter queue
+pub async fn remove_failed_operation(db: &Database, operation_id: OperationId, action: &str) {
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.remove_entry(&FailedOperationKey {
+        operation_id,
+        action: action.to_owned(),
+    })
+    .await;
+    dbtx.commit_tx().await;
+}
+
+/// Removes the dead-lettered actions of operations that are not in
+/// `active_operations` anymore, their state machines finished so they won't be
+/// retried again. Returns how many entries were removed.
+pub async fn prune_failed_operations(
+    db: &Database,
+    active_operations: &HashSet<OperationId>,
+) -> usize {
+    let mut dbtx = db.begin_transaction().await;
+    let stale_keys = dbtx
+        .find_by_prefix(&FailedOperationKeyPrefix)
+        .await
+        .map(|(key, _)| key)
+        .filter(|key| std::future::ready(!active_operations.contains(&key.operation_id)))
+        .collect::<Vec<_>>()
+        .await;
+    for key in &stale_keys {
+        dbtx.remove_entry(key).await;
+    }
+    dbtx.commit_tx().await;
+    stale_keys.len()
+}
+
+/// All dead-lettered actions, including those of operations that finished
+/// since the queue was last pruned
+pub async fn get_failed_operations(db: &Database) -> Vec<FailedOperation> {
+    db.begin_transaction_nc()
+        .await
+        .find_by_prefix(&FailedOperationKeyPrefix)
+        .await
+        .map(|(_, failure)| failure)
+        .collect()
+        .await
+}
+
+impl DynGlobalClientContext {
+    /// Runs `op_fn` until it succeeds, waiting between the attempts as
+    /// determined by `strategy` until the [`RetryPolicy`] of the client is
+    /// exhausted, see the [module docs](self)
+    pub async fn retry<F, Fut, T>(&self, action: &str, mut strategy: impl Backoff, op_fn: F) -> T
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let policy = self.retry_policy();
+        let mut attempts: u64 = 0;
+        let mut dead_lettered = false;
+        loop {
+            attempts += 1;
+            let error = match op_fn().await {
+                Ok(result) => {
+                    if dead_lettered {
+                        info!(
+                            target: LOG_CLIENT,
+                            %attempts,
+                            "{action} succeeded after being dead-lettered",
+                        );
+                        self.remove_dead_letter(action).await;
+                    }
+                    return result;
+                }
+                Err(error) => error,
+            };
+
+            let interval = match strategy.next() {
+                Some(interval) if attempts < policy.max_attempts => {
+                    debug!(
+                        target: LOG_CLIENT,
+                        %error,
+                        %attempts,
+                        interval = interval.as_secs(),
+                        "{action} failed, retrying",
+                    );
+                    interval
+                }
+                _ => {
+                    if !dead_lettered {
+                        warn!(
+                            target: LOG_CLIENT,
+                            ?error,
+                            %attempts,
+                            "{action} exhausted its retry budget, dead-lettering the operation",
+                        );
+                        dead_lettered = true;
+                    }
+                    self.dead_letter(action, attempts, format!("{error:#}"))
+                        .await;
+                    policy.dead_letter_interval
+                }
+            };
+            runtime::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::{Duration, SystemTime};
+
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+
+    use super::{
+        get_failed_operations, prune_failed_operations, record_failed_operation,
+        remove_failed_operation, FailedOperation,
+    };
+
+    fn failure(action: &str, attempts: u64, failed_at: SystemTime) -> FailedOperation {
+        failure_of(OperationId([0; 32]), action, attempts, failed_at)
+    }
+
+    fn failure_of(
+        operation_id: OperationId,
+        action: &str,
+        attempts: u64,
+        failed_at: SystemTime,
+    ) -> FailedOperation {
+        FailedOperation {
+            operation_id,
+            action: action.to_owned(),
+            attempts,
+            last_error: "unreachable".to_owned(),
+            failed_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn dead_letter_queue_keeps_first_failure_time() {
+        let db = MemDatabase::new().into_database();
+        let first = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let later = SystemTime::UNIX_EPOCH + Duration::from_secs(200);
+
+        record_failed_operation(&db, failure("gateway-send-payment", 100, first)).await;
+        record_failed_operation(&db, failure("gateway-send-payment", 101, later)).await;
+        record_failed_operation(&db, failure("tx-submit", 100, later)).await;
+
+        let mut failed = get_failed_operations(&db).await;
+        failed.sort_by_key(|failure| failure.action.clone());
+        assert_eq!(
+            failed,
+            vec![
+                failure("gateway-send-payment", 101, first),
+                failure("tx-submit", 100, later),
+            ]
+        );
+
+        remove_failed_operation(&db, OperationId([0; 32]), "gateway-send-payment").await;
+        assert_eq!(
+            get_failed_operations(&db).await,
+            vec![failure("tx-submit", 100, later)]
+        );
+    }
+
+    #[tokio::test]
+    async fn pruning_removes_failures_of_finished_operations() {
+        let db = MemDatabase::new().into_database();
+        let failed_at = SystemTime::UNIX_EPOCH;
+        let active = OperationId([1; 32]);
+        let finished = OperationId([2; 32]);
+
+        record_failed_operation(&db, failure_of(active, "tx-submit", 100, failed_at)).await;
+        record_failed_operation(&db, failure_of(finished, "tx-submit", 100, failed_at)).await;
+        record_failed_operation(
+            &db,
+            failure_of(finished, "gateway-send-payment", 100, failed_at),
+        )
+        .await;
+
+        assert_eq!(
+            prune_failed_operations(&db, &HashSet::from([active])).await,
+            2
+        );
+        assert_eq!(
+            get_failed_operations(&db).await,
+            vec![failure_of(active, "tx-submit", 100, failed_at)]
+        );
+
+        assert_eq!(
+            prune_failed_operations(&db, &HashSet::from([active])).await,
+            0
+        );
+    }
+}
@@ -14,10 +14,12 @@
 // Derived from federation-root-secret
 const TYPE_MODULE: ChildId = ChildId(0);
 const TYPE_BACKUP: ChildId = ChildId(1);
+const TYPE_SUB_WALLET: ChildId = ChildId(2);
 
 pub trait DeriveableSecretClientExt {
     fn derive_module_secret(&self, module_instance_id: ModuleInstanceId) -> DerivableSecret;
     fn derive_backup_secret(&self) -> DerivableSecret;
+    fn derive_sub_wallet_secret(&self, index: u64) -> DerivableSecret;
     fn derive_pre_root_secret_hash(&self) -> [u8; 8];
 }
 
@@ -33,6 +35,11 @@ fn derive_backup_secret(&self) -> DerivableSecret {
         self.child_key(TYPE_BACKUP)
     }
 
+    fn derive_sub_wallet_secret(&self, index: u64) -> DerivableSecret {
+        assert_eq!(self.level(), 0);
+        self.child_key(TYPE_SUB_WALLET).child_key(ChildId(index))
+    }
+
     fn derive_pre_root_secret_hash(&self) -> [u8; 8] {
         // Note: this hash is derived from a pre-root-secret: one passed from the
         // outside, before the federation ID is used to derive the
@@ -15,6 +15,7 @@
 use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
 use fedimint_core::fmt_utils::AbbreviateJson;
 use fedimint_core::maybe_add_send_sync;
+use fedimint_core::metrics::Counter;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::task::TaskGroup;
 use fedimint_core::util::BoxFuture;
@@ -22,10 +23,11 @@
 use futures::future::{self, select_all};
 use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::select;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tracing::{debug, error, info, trace, warn, Instrument};
 
 use super::state::StateTransitionFunction;
+use crate::replay::on_behalf_of;
 use crate::sm::notifier::Notifier;
 use crate::sm::state::{DynContext, DynState};
 use crate::sm::{ClientSMDatabaseTransaction, State, StateTransition};
@@ -34,6 +36,12 @@
 /// After how many attempts a DB transaction is aborted with an error
 const MAX_DB_ATTEMPTS: Option<usize> = Some(100);
 
+static STATE_TRANSITIONS: Counter = Counter::new(
+    "client_sm_transitions_total",
+    "State transitions completed by the executor",
+    &["module_instance", "terminal"],
+);
+
 pub type ContextGen =
     Arc<maybe_add_send_sync!(dyn Fn(ModuleInstanceId, OperationId) -> DynGlobalClientContext)>;
 
@@ -65,8 +73,17 @@ struct ExecutorInner {
     notifier: Notifier,
     /// Any time executor should notice state machine update (e.g. because it
     /// was created), it's must be sent through this channel for it to notice.
+    ///
+    /// Unbounded on purpose: the executor loop sends into it itself, so a
+    /// bounded channel could deadlock, and dropping an update would stall the
+    /// state machine until restart. It only grows while transitions outpace
+    /// the executor, which is bounded by the number of active state machines.
     sm_update_tx: mpsc::UnboundedSender<DynState>,
     client_task_group: TaskGroup,
+    /// Whether the executor loop is paused, see [`Executor::pause`]
+    paused: watch::Sender<bool>,
+    /// Number of state transitions completed since the executor was built
+    completed_transitions: watch::Sender<u64>,
 }
 
 enum ExecutorState {
@@ -411,6 +428,37 @@ pub fn stop_executor(&self) -> Option<()> {
     pub fn notifier(&self) -> &Notifier {
         &self.inner.notifier
     }
+
+    /// Stops driving state machines forward until [`Self::resume`] is called,
+    /// without tearing down the background task.
+    ///
+    /// Trigger futures and transitions that are in flight are not polled while
+    /// paused and continue where they left off once resumed. State machines
+    /// added in the meantime are queued and started on resume. Returns `false`
+    /// if the executor was already paused.
+    pub fn pause(&self) -> bool {
+        self.inner
+            .paused
+            .send_if_modified(|paused| !mem::replace(paused, true))
+    }
+
+    /// Resumes an executor paused by [`Self::pause`], returns `false` if it
+    /// wasn't paused
+    pub fn resume(&self) -> bool {
+        self.inner
+            .paused
+            .send_if_modified(|paused| mem::replace(paused, false))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.inner.paused.borrow()
+    }
+
+    /// Subscribes to the number of state transitions completed since the
+    /// executor was built
+    pub fn subscribe_completed_transitions(&self) -> watch::Receiver<u64> {
+        self.inner.completed_transitions.subscribe()
+    }
 }
 
 impl Drop for ExecutorInner {
@@ -470,7 +518,7 @@ async fn get_transition_for(
                         transition,
                     } = transition;
                     TransitionForActiveState {
-                        outcome: trigger.await,
+                        outcome: on_behalf_of(state.operation_id(), trigger).await,
                         state,
                         transition_fn: transition,
                         meta,
@@ -553,9 +601,19 @@ enum ExecutorLoopEvent {
         // just so we can get back to `futures.next()` ASAP.
         let mut futures: FuturesUnordered<BoxFuture<'_, ExecutorLoopEvent>> =
             FuturesUnordered::new();
+        let mut paused_rx = self.paused.subscribe();
 
         loop {
+            if *paused_rx.borrow_and_update() {
+                debug!(target: LOG_CLIENT_REACTOR, pending = futures.len(), "Executor paused");
+                // Can't fail: `self` holds the sender
+                let _ = paused_rx.wait_for(|paused| !paused).await;
+                debug!(target: LOG_CLIENT_REACTOR, pending = futures.len(), "Executor resumed");
+            }
+
             let event = tokio::select! {
+                _ = paused_rx.changed() => continue,
+
                 new = sm_update_rx.recv() => {
                     if let Some(new) = new {
                         ExecutorLoopEvent::New {
@@ -717,6 +775,10 @@ enum ExecutorLoopEvent {
                                     ?outcome,
                                     "State transition complete",
                                 );
+                                STATE_TRANSITIONS.inc(&[
+                                    &state.module_instance_id().to_string(),
+                                    if outcome.is_active() { "false" } else { "true" },
+                                ]);
 
                                 match &outcome {
                                     ActiveOrInactiveState::Active { dyn_state, meta: _ } => {
@@ -752,6 +814,7 @@ enum ExecutorLoopEvent {
                         currently_running_sms.remove(&state),
                         "State must have been recorded"
                     );
+                    self.completed_transitions.send_modify(|n| *n += 1);
                     debug!(
                         target: LOG_CLIENT_REACTOR,
                         operation_id = %state.operation_id().fmt_short(),
@@ -888,6 +951,8 @@ pub fn build(self, db: Database, notifier: Notifier, client_task_group: TaskGrou
             notifier,
             sm_update_tx,
             client_task_group,
+            paused: watch::channel(false).0,
+            completed_transitions: watch::channel(0).0,
         });
 
         debug!(
@@ -1452,4 +1517,45 @@ async fn test_executor() {
             "State was written to DB and waits for broadcast"
         );
     }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_executor_pause() {
+        const MOCK_INSTANCE: ModuleInstanceId = 42;
+
+        let (executor, sender, _db) = get_executor();
+        let mut transitions = executor.subscribe_completed_transitions();
+        executor
+            .add_state_machines(vec![DynState::from_typed(
+                MOCK_INSTANCE,
+                MockStateMachine::Start,
+            )])
+            .await
+            .unwrap();
+        runtime::sleep(Duration::from_secs(1)).await;
+
+        assert!(executor.pause());
+        assert!(!executor.pause(), "Pausing twice is a no-op");
+        sender.send(0).unwrap();
+        runtime::sleep(Duration::from_secs(1)).await;
+        assert!(
+            executor
+                .contains_active_state(MOCK_INSTANCE, MockStateMachine::Start)
+                .await,
+            "Paused executor doesn't transition"
+        );
+        assert_eq!(*transitions.borrow_and_update(), 0);
+
+        assert!(executor.resume());
+        runtime::timeout(Duration::from_secs(2), transitions.wait_for(|n| *n == 1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            executor
+                .contains_inactive_state(MOCK_INSTANCE, MockStateMachine::Final)
+                .await,
+            "Resumed executor picks up the triggered transition"
+        );
+    }
 }
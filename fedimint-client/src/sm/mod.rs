@@ -13,5 +13,5 @@
     ActiveStateKeyBytes, ActiveStateKeyPrefix, ActiveStateMeta, Executor, ExecutorBuilder,
     InactiveStateKeyBytes, InactiveStateKeyPrefix, InactiveStateMeta,
 };
-pub use notifier::{ModuleNotifier, Notifier, NotifierSender};
+pub use notifier::{ModuleNotifier, Notifier, NotifierSender, NOTIFIER_CAPACITY};
 pub use state::{Context, DynContext, DynState, IState, OperationState, State, StateTransition};
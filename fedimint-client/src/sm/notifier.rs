@@ -1,9 +1,10 @@
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use fedimint_core::core::{ModuleInstanceId, OperationId};
 use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
-use fedimint_core::util::broadcaststream::BroadcastStream;
+use fedimint_core::util::broadcaststream::{BroadcastStream, BroadcastStreamRecvError};
 use fedimint_core::util::BoxStream;
 use fedimint_logging::LOG_CLIENT;
 use futures::StreamExt;
@@ -15,6 +16,11 @@
 };
 use crate::sm::{ActiveStateMeta, DynState, InactiveStateMeta, State};
 
+/// Number of state transitions buffered for subscribers by default. A
+/// subscriber that falls further behind misses transitions and its stream
+/// ends, see [`Notifier::missed_notifications`].
+pub const NOTIFIER_CAPACITY: usize = 10_000;
+
 /// State transition notifier owned by the modularized client used to inform
 /// modules of state transitions.
 ///
@@ -27,17 +33,40 @@ pub struct Notifier {
     broadcast: tokio::sync::broadcast::Sender<DynState>,
     /// Database used to load all states that happened before subscribing
     db: Database,
+    /// Number of state transitions subscribers missed because they lagged
+    /// behind by more than the capacity of `broadcast`
+    missed: Arc<AtomicU64>,
 }
 
 impl Notifier {
     pub fn new(db: Database) -> Self {
-        let (sender, _receiver) = tokio::sync::broadcast::channel(10_000);
+        Self::with_capacity(db, NOTIFIER_CAPACITY)
+    }
+
+    /// Creates a notifier buffering up to `capacity` state transitions for
+    /// subscribers
+    pub fn with_capacity(db: Database, capacity: usize) -> Self {
+        let (sender, _receiver) = tokio::sync::broadcast::channel(capacity);
         Self {
             broadcast: sender,
             db,
+            missed: Arc::default(),
         }
     }
 
+    /// Number of state transitions subscribers missed so far because they
+    /// couldn't keep up. Anything but zero means the client is overloaded and
+    /// some update streams ended early.
+    pub fn missed_notifications(&self) -> u64 {
+        self.missed.load(Ordering::Relaxed)
+    }
+
+    /// Number of state transitions currently buffered for the slowest
+    /// subscriber
+    pub fn buffered_notifications(&self) -> usize {
+        self.broadcast.len()
+    }
+
     /// Notify all subscribers of a state transition
     pub fn notify(&self, state: DynState) {
         let queue_len = self.broadcast.len();
@@ -60,6 +89,7 @@ pub fn module_notifier<S>(&self, module_instance: ModuleInstanceId) -> ModuleNot
             broadcast: self.broadcast.clone(),
             module_instance,
             db: self.db.clone(),
+            missed: self.missed.clone(),
             _pd: PhantomData,
         }
     }
@@ -97,6 +127,8 @@ pub struct ModuleNotifier<S> {
     /// Database used to load all states that happened before subscribing, see
     /// [`Notifier`]
     db: Database,
+    /// See [`Notifier::missed_notifications`]
+    missed: Arc<AtomicU64>,
     /// `S` limits the type of state that can be subscribed to the one
     /// associated with the module instance
     _pd: PhantomData<S>,
@@ -204,10 +236,13 @@ pub async fn subscribe(&self, operation_id: OperationId) -> BoxStream<'static, S
     /// Subscribe to all state transitions belonging to the module instance.
     pub fn subscribe_all_operations(&self) -> BoxStream<'static, S> {
         let module_instance_id = self.module_instance;
+        let missed = self.missed.clone();
         Box::pin(
             BroadcastStream::new(self.broadcast.subscribe())
-                .take_while(|res| {
+                .take_while(move |res| {
                     let cont = if let Err(err) = res {
+                        let BroadcastStreamRecvError::Lagged(n) = err;
+                        missed.fetch_add(*n, Ordering::Relaxed);
                         error!(target: LOG_CLIENT, ?err, "ModuleNotifier stream stopped on error");
                         false
                     } else {
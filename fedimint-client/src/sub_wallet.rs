@@ -0,0 +1,208 @@
+//! Sub-wallets are isolated child clients of the same federation that are
+//! derived from the root secret of their parent client. Each sub-wallet is
+//! identified by a user-chosen label that is mapped to an account index the
+//! first time it is used, and gets its own notes, operation log and event log
+//! stored under a separate prefix of the parent's database.
+//!
+//! Since sub-wallet secrets are derived deterministically from the parent's
+//! secret, funds in a sub-wallet can be recovered from the same seed as long as
+//! the account index is known.
+
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use fedimint_core::db::{
+    AutocommitError, Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped,
+};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleRegistry;
+use fedimint_core::{impl_db_lookup, impl_db_record};
+use futures::StreamExt;
+
+use crate::db::DbKeyPrefix;
+use crate::secret::DeriveableSecretClientExt;
+use crate::{Client, ClientHandle};
+
+/// Maps the label of a sub-wallet to its account index
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct SubWalletLabelKey(pub String);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct SubWalletLabelPrefix;
+
+impl_db_record!(
+    key = SubWalletLabelKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::SubWalletLabel,
+    notify_on_modify = false,
+);
+impl_db_lookup!(key = SubWalletLabelKey, query_prefix = SubWalletLabelPrefix);
+
+/// Returns the isolated database of the sub-wallet with the given account
+/// index, nested in the database of its parent client
+pub fn sub_wallet_db(parent_db: &Database, index: u64) -> Database {
+    let mut prefix = vec![DbKeyPrefix::SubWallet as u8];
+    // Fixed width encoding, so no index' prefix is a prefix of another one
+    prefix.extend_from_slice(&index.to_be_bytes());
+    parent_db
+        .with_decoders(ModuleRegistry::default())
+        .with_prefix(prefix)
+}
+
+impl Client {
+    /// Opens the sub-wallet with the given label, creating it on first use.
+    ///
+    /// The returned client shares the federation config, modules and
+    /// connector of this client, but has its own secret and database, so its
+    /// balance and operations are fully separated from both this client and
+    /// any other sub-wallet.
+    pub async fn open_sub_wallet(&self, label: &str) -> anyhow::Result<ClientHandle> {
+        if label.is_empty() {
+            bail!("Sub-wallet label must not be empty");
+        }
+
+        let index = self.sub_wallet_index(label).await?;
+        let db = sub_wallet_db(&self.db, index);
+        let pre_root_secret = self.root_secret.derive_sub_wallet_secret(index);
+
+        let mut builder = Client::builder(db.clone()).await?;
+        builder.with_module_inits(self.module_inits.clone());
+        builder.with_primary_module_instance_id(self.primary_module_instance);
+        builder.with_meta_service(self.meta_service.clone());
+        builder.with_connector(self.connector);
+
+        if Client::is_initialized(&db).await {
+            builder.open(pre_root_secret).await
+        } else {
+            builder
+                .join(
+                    pre_root_secret,
+                    self.config().await,
+                    self.api_secret.clone(),
+                )
+                .await
+        }
+    }
+
+    /// Lists the labels of all sub-wallets created so far together with
+    /// their account indexes
+    pub async fn list_sub_wallets(&self) -> BTreeMap<String, u64> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&SubWalletLabelPrefix)
+            .await
+            .map(|(SubWalletLabelKey(label), index)| (label, index))
+            .collect()
+            .await
+    }
+
+    /// Looks up the account index of a sub-wallet, assigning the next free one
+    /// if the label wasn't used before
+    async fn sub_wallet_index(&self, label: &str) -> anyhow::Result<u64> {
+        self.db
+            .autocommit(
+                |dbtx, _| {
+                    Box::pin(async move {
+                        Ok::<_, anyhow::Error>(get_or_assign_index(dbtx, label).await)
+                    })
+                },
+                Some(100),
+            )
+            .await
+            .map_err(|e| match e {
+                AutocommitError::ClosureError { error, .. } => error,
+                AutocommitError::CommitFailed { last_error, .. } => last_error,
+            })
+    }
+}
+
+async fn get_or_assign_index(dbtx: &mut DatabaseTransaction<'_>, label: &str) -> u64 {
+    let key = SubWalletLabelKey(label.to_owned());
+    if let Some(index) = dbtx.get_value(&key).await {
+        return index;
+    }
+
+    let next_index = dbtx
+        .find_by_prefix(&SubWalletLabelPrefix)
+        .await
+        .map(|(_, index)| index + 1)
+        .fold(0, |max, index| async move { max.max(index) })
+        .await;
+    dbtx.insert_new_entry(&key, &next_index).await;
+    next_index
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{IDatabaseTransactionOpsCoreTyped, IRawDatabaseExt};
+
+    use super::{get_or_assign_index, sub_wallet_db, SubWalletLabelKey};
+
+    #[tokio::test]
+    async fn sub_wallet_indexes_are_stable_and_unique() {
+        let db = MemDatabase::new().into_database();
+
+        let mut dbtx = db.begin_transaction().await;
+        assert_eq!(
+            get_or_assign_index(&mut dbtx.to_ref_nc(), "savings").await,
+            0
+        );
+        assert_eq!(
+            get_or_assign_index(&mut dbtx.to_ref_nc(), "spending").await,
+            1
+        );
+        assert_eq!(
+            get_or_assign_index(&mut dbtx.to_ref_nc(), "savings").await,
+            0
+        );
+        dbtx.commit_tx().await;
+
+        let mut dbtx = db.begin_transaction().await;
+        assert_eq!(
+            get_or_assign_index(&mut dbtx.to_ref_nc(), "spending").await,
+            1
+        );
+        assert_eq!(
+            get_or_assign_index(&mut dbtx.to_ref_nc(), "travel").await,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn sub_wallet_databases_are_isolated() {
+        let db = MemDatabase::new().into_database();
+        let savings = sub_wallet_db(&db, 0);
+        let spending = sub_wallet_db(&db, 1);
+
+        let mut dbtx = savings.begin_transaction().await;
+        dbtx.insert_entry(&SubWalletLabelKey("nested".into()), &7)
+            .await;
+        dbtx.commit_tx().await;
+
+        assert_eq!(
+            savings
+                .begin_transaction_nc()
+                .await
+                .get_value(&SubWalletLabelKey("nested".into()))
+                .await,
+            Some(7)
+        );
+        assert_eq!(
+            spending
+                .begin_transaction_nc()
+                .await
+                .get_value(&SubWalletLabelKey("nested".into()))
+                .await,
+            None
+        );
+        assert_eq!(
+            db.begin_transaction_nc()
+                .await
+                .get_value(&SubWalletLabelKey("nested".into()))
+                .await,
+            None
+        );
+    }
+}
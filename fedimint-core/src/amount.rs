@@ -10,6 +10,12 @@
 
 pub const SATS_PER_BITCOIN: u64 = 100_000_000;
 
+/// Explains the amount format accepted by [`Amount::from_str`] in CLI help
+/// texts
+pub const AMOUNT_FORMAT_HELP: &str = "Amounts can be given with a denomination like `21sat`, \
+     `0.001btc` or `2100msat`. Amounts without a denomination are millisatoshis unless \
+     noted otherwise.";
+
 /// Shorthand for [`Amount::from_msats`]
 pub fn msats(msats: u64) -> Amount {
     Amount::from_msats(msats)
@@ -54,12 +60,41 @@ pub const fn from_bitcoins(bitcoins: u64) -> Self {
     /// with denomination, use [`FromStr`].
     pub fn from_str_in(s: &str, denom: Denomination) -> Result<Self, ParseAmountError> {
         if denom == Denomination::MilliSatoshi {
-            return Ok(Self::from_msats(s.parse()?));
+            if s.contains('.') {
+                return Err(ParseAmountError::FractionalMsats);
+            }
+            let amount = Self::from_msats(s.parse()?);
+            if amount > Self::from(bitcoin::Amount::MAX_MONEY) {
+                return Err(ParseAmountError::ExceedsMaxMoney);
+            }
+            return Ok(amount);
         }
         let btc_amt = bitcoin::amount::Amount::from_str_in(s, denom)?;
+        if btc_amt > bitcoin::Amount::MAX_MONEY {
+            return Err(ParseAmountError::ExceedsMaxMoney);
+        }
         Ok(Self::from(btc_amt))
     }
 
+    /// Parse a decimal string with an optional denomination suffix like
+    /// `21sat`, `0.001btc` or `2100msat`, interpreting numbers without
+    /// denomination in `default_denom`.
+    ///
+    /// [`FromStr`] defaults to millisatoshis, CLI arguments that historically
+    /// took e.g. satoshis can use this to stay backwards compatible.
+    pub fn from_str_with_default_denom(
+        s: &str,
+        default_denom: Denomination,
+    ) -> Result<Self, ParseAmountError> {
+        let s = s.trim();
+        if let Some(i) = s.find(char::is_alphabetic) {
+            let (amt, denom) = s.split_at(i);
+            Self::from_str_in(amt.trim(), denom.trim().parse()?)
+        } else {
+            Self::from_str_in(s, default_denom)
+        }
+    }
+
     pub fn saturating_sub(self, other: Self) -> Self {
         Self {
             msats: self.msats.saturating_sub(other.msats),
@@ -199,13 +234,7 @@ impl FromStr for Amount {
     type Err = ParseAmountError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(i) = s.find(char::is_alphabetic) {
-            let (amt, denom) = s.split_at(i);
-            Self::from_str_in(amt.trim(), denom.trim().parse()?)
-        } else {
-            // default to millisatoshi
-            Self::from_str_in(s.trim(), Denomination::MilliSatoshi)
-        }
+        Self::from_str_with_default_denom(s, Denomination::MilliSatoshi)
     }
 }
 
@@ -226,6 +255,225 @@ fn try_from(value: Amount) -> anyhow::Result<Self> {
     }
 }
 
+/// An amount of millisatoshis, for APIs that spell out the unit of their
+/// amounts, e.g. to tell them apart from [`Sats`] in the same struct.
+///
+/// Converts losslessly from and into an [`Amount`] and from [`Sats`], while
+/// converting into [`Sats`] fails if there is a millisatoshi remainder.
+#[derive(
+    Clone,
+    Copy,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Deserialize,
+    Serialize,
+    Encodable,
+    Decodable,
+)]
+#[serde(transparent)]
+pub struct Msats(pub u64);
+
+impl Msats {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn to_amount(self) -> Amount {
+        Amount::from_msats(self.0)
+    }
+
+    /// Drops any millisatoshi remainder, for amounts that are shown or spent
+    /// in whole satoshis
+    pub const fn to_sats_round_down(self) -> Sats {
+        Sats(self.0 / 1000)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
+impl std::fmt::Display for Msats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} msat", self.0)
+    }
+}
+
+impl std::fmt::Debug for Msats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}msat", self.0)
+    }
+}
+
+impl std::ops::Add for Msats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl std::iter::Sum for Msats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|amt| amt.0).sum::<u64>())
+    }
+}
+
+impl FromStr for Msats {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Amount::from_str(s).map(Self::from)
+    }
+}
+
+impl From<Msats> for Amount {
+    fn from(msats: Msats) -> Self {
+        msats.to_amount()
+    }
+}
+
+impl From<Amount> for Msats {
+    fn from(amount: Amount) -> Self {
+        Self(amount.msats)
+    }
+}
+
+impl From<Sats> for Msats {
+    fn from(sats: Sats) -> Self {
+        sats.to_msats().into()
+    }
+}
+
+/// An amount of whole satoshis, for values that can't be more precise, like
+/// on-chain balances or channel sizes.
+///
+/// Converts losslessly into an [`Amount`], while converting an [`Amount`] into
+/// satoshis fails if it has a millisatoshi remainder. Use
+/// [`Amount::sats_round_down`] to round explicitly instead.
+#[derive(
+    Clone,
+    Copy,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Deserialize,
+    Serialize,
+    Encodable,
+    Decodable,
+)]
+#[serde(transparent)]
+pub struct Sats(pub u64);
+
+impl Sats {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn to_msats(self) -> Amount {
+        Amount::from_sats(self.0)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
+impl std::fmt::Display for Sats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} sat", self.0)
+    }
+}
+
+impl std::fmt::Debug for Sats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}sat", self.0)
+    }
+}
+
+impl std::ops::Add for Sats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl std::iter::Sum for Sats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|amt| amt.0).sum::<u64>())
+    }
+}
+
+/// Parses the same formats as [`Amount`], but numbers without a denomination
+/// are satoshis
+impl FromStr for Sats {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(Amount::from_str_with_default_denom(
+            s,
+            Denomination::Satoshi,
+        )?)
+    }
+}
+
+impl From<Sats> for Amount {
+    fn from(sats: Sats) -> Self {
+        sats.to_msats()
+    }
+}
+
+impl TryFrom<Amount> for Sats {
+    type Error = ParseAmountError;
+
+    fn try_from(amount: Amount) -> Result<Self, Self::Error> {
+        if amount.msats % 1000 != 0 {
+            return Err(ParseAmountError::FractionalSats);
+        }
+        Ok(Self(amount.msats / 1000))
+    }
+}
+
+impl TryFrom<Msats> for Sats {
+    type Error = ParseAmountError;
+
+    fn try_from(msats: Msats) -> Result<Self, Self::Error> {
+        Self::try_from(msats.to_amount())
+    }
+}
+
+impl From<bitcoin::Amount> for Sats {
+    fn from(amount: bitcoin::Amount) -> Self {
+        Self(amount.to_sat())
+    }
+}
+
+impl From<Sats> for bitcoin::Amount {
+    fn from(sats: Sats) -> Self {
+        Self::from_sat(sats.0)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParseAmountError {
     #[error("Error parsing string as integer: {0}")]
@@ -234,6 +482,12 @@ pub enum ParseAmountError {
     WrongBitcoinAmount(#[from] bitcoin::amount::ParseAmountError),
     #[error("Error parsing string as a bitcoin denomination: {0}")]
     WrongBitcoinDenomination(#[from] bitcoin_units::amount::ParseDenominationError),
+    #[error("Millisatoshi amounts can't have a fractional part")]
+    FractionalMsats,
+    #[error("Amount is more precise than satoshis")]
+    FractionalSats,
+    #[error("Amount exceeds the total supply of 21 million bitcoin")]
+    ExceedsMaxMoney,
 }
 
 #[cfg(test)]
@@ -286,5 +540,75 @@ fn test_amount_parsing() {
             Amount::from_sats(12_345_600_000),
             Amount::from_str("123.456btc").unwrap()
         );
+        assert_eq!(
+            Amount::from_sats(100_000),
+            Amount::from_str("0.001btc").unwrap()
+        );
+        assert_eq!(
+            Amount::from_msats(2100),
+            Amount::from_str(" 2100msat ").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_amount_parsing_errors() {
+        assert!(matches!(
+            Amount::from_str("1.5msat"),
+            Err(ParseAmountError::FractionalMsats)
+        ));
+        assert!(matches!(
+            Amount::from_str("0.0001sat"),
+            Err(ParseAmountError::WrongBitcoinAmount(_))
+        ));
+        assert!(matches!(
+            Amount::from_str("21000001btc"),
+            Err(ParseAmountError::ExceedsMaxMoney)
+        ));
+        assert!(matches!(
+            Amount::from_str("2100000000000000001"),
+            Err(ParseAmountError::ExceedsMaxMoney)
+        ));
+        assert!(matches!(
+            Amount::from_str("21 bananas"),
+            Err(ParseAmountError::WrongBitcoinDenomination(_))
+        ));
+        assert!(Amount::from_str("-21sat").is_err());
+    }
+
+    #[test]
+    fn test_msats_and_sats_conversions() {
+        assert_eq!(Sats(21).to_msats(), Amount::from_msats(21_000));
+        assert_eq!(Sats::try_from(Amount::from_sats(21)).unwrap(), Sats(21));
+        assert!(matches!(
+            Sats::try_from(Amount::from_msats(21_001)),
+            Err(ParseAmountError::FractionalSats)
+        ));
+
+        assert_eq!(Msats::from(Sats(21)), Msats(21_000));
+        assert_eq!(Sats::try_from(Msats(21_000)).unwrap(), Sats(21));
+        assert!(Sats::try_from(Msats(21_001)).is_err());
+        assert_eq!(Msats(21_999).to_sats_round_down(), Sats(21));
+        assert_eq!(Amount::from(Msats(21_001)), Amount::from_msats(21_001));
+        assert_eq!(Msats::from_str("21sat").unwrap(), Msats(21_000));
+
+        assert_eq!(Sats(21), Sats::from_str("21").unwrap());
+        assert_eq!(Sats(21), Sats::from_str("21000msat").unwrap());
+        assert_eq!(Sats(100_000), Sats::from_str("0.001btc").unwrap());
+        assert!(matches!(
+            Sats::from_str("21001msat"),
+            Err(ParseAmountError::FractionalSats)
+        ));
+    }
+
+    #[test]
+    fn test_amount_parsing_default_denom() {
+        assert_eq!(
+            Amount::from_sats(21),
+            Amount::from_str_with_default_denom("21", Denomination::Satoshi).unwrap()
+        );
+        assert_eq!(
+            Amount::from_msats(21),
+            Amount::from_str_with_default_denom("21msat", Denomination::Satoshi).unwrap()
+        );
     }
 }
@@ -9,7 +9,7 @@
 use bitcoin::hashes::sha256::{Hash as Sha256, HashEngine};
 use bitcoin::hashes::{hex, sha256, Hash as BitcoinHash};
 use bls12_381::Scalar;
-use fedimint_core::core::{ModuleInstanceId, ModuleKind};
+use fedimint_core::core::{ModuleInstanceId, ModuleKind, MODULE_INSTANCE_ID_GLOBAL};
 use fedimint_core::encoding::{DynRawFallback, Encodable};
 use fedimint_core::module::registry::ModuleRegistry;
 use fedimint_core::task::Cancelled;
@@ -505,6 +505,32 @@ pub fn to_typed<P: ModuleInitParams>(&self) -> anyhow::Result<P> {
         ))
     }
 
+    /// Instance id derived from the module kind, the consensus params and the
+    /// `discriminator`, so it does not depend on the order the modules are
+    /// attached in.
+    ///
+    /// The `discriminator` tells apart instances of the same kind that share
+    /// their consensus params. The local params differ between guardians and
+    /// are not part of it.
+    pub fn stable_instance_id(&self, kind: &ModuleKind, discriminator: u64) -> ModuleInstanceId {
+        let mut engine = HashEngine::default();
+        kind.consensus_encode(&mut engine)
+            .expect("Hashing never fails");
+        discriminator
+            .consensus_encode(&mut engine)
+            .expect("Hashing never fails");
+        serde_json::to_string(&self.consensus)
+            .expect("JSON value is serializable")
+            .consensus_encode(&mut engine)
+            .expect("Hashing never fails");
+        let hash = Sha256::from_engine(engine);
+
+        match ModuleInstanceId::from_be_bytes([hash[0], hash[1]]) {
+            MODULE_INSTANCE_ID_GLOBAL => ModuleInstanceId::from_be_bytes([hash[2], hash[3]]),
+            id => id,
+        }
+    }
+
     fn parse<P: DeserializeOwned>(name: &str, json: serde_json::Value) -> anyhow::Result<P> {
         serde_json::from_value(json).with_context(|| format!("Schema mismatch for {name} argument"))
     }
@@ -636,6 +662,33 @@ pub fn attach_config_gen_params<T: ModuleInitParams>(
         self.append_module(kind, params);
         self
     }
+
+    /// Attaches the params under an id derived from the kind, consensus
+    /// params and `discriminator` (see
+    /// [`ConfigGenModuleParams::stable_instance_id`]) instead of the next free
+    /// one, so adding, removing or reordering other modules does not change
+    /// the id of this one when the config is regenerated.
+    ///
+    /// Fails if the id is already taken, picking another one would make the
+    /// id depend on the attachment order again. One of the colliding modules
+    /// has to be attached with a different `discriminator` instead.
+    pub fn attach_config_gen_params_stable<T: ModuleInitParams>(
+        &mut self,
+        kind: ModuleKind,
+        discriminator: u64,
+        gen: T,
+    ) -> anyhow::Result<&mut Self> {
+        let params = ConfigGenModuleParams::from_typed(gen)
+            .with_context(|| format!("Invalid config gen params for {kind}"))?;
+        let id = params.stable_instance_id(&kind, discriminator);
+        if let Some((other_kind, _)) = self.get_with_kind(id) {
+            bail!(
+                "Stable module instance id {id} of {kind} is already taken by {other_kind}, attach one of them with another discriminator"
+            );
+        }
+        self.register_module(id, kind, params);
+        Ok(self)
+    }
 }
 
 impl ServerModuleInitRegistry {
@@ -1029,6 +1082,14 @@ fn deserialize2<'d, D: Deserializer<'d>>(d: D) -> Result<Self, D::Error> {
 /// of the config
 pub const META_FEDERATION_NAME_KEY: &str = "federation_name";
 
+/// Key under which a URL of the federation's icon can be sent to client in the
+/// `meta` part of the config
+pub const META_FEDERATION_ICON_URL_KEY: &str = "federation_icon_url";
+
+/// Key under which a message for new users can be sent to client in the `meta`
+/// part of the config
+pub const META_WELCOME_MESSAGE_KEY: &str = "welcome_message";
+
 pub fn load_from_file<T: DeserializeOwned>(path: &Path) -> Result<T, anyhow::Error> {
     let file = std::fs::File::open(path)?;
     Ok(serde_json::from_reader(file)?)
@@ -1067,9 +1128,103 @@ mod tests {
     use std::collections::BTreeMap;
 
     use fedimint_core::config::{ClientConfig, GlobalClientConfig};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
 
+    use super::{ConfigGenModuleParams, ModuleInitParams};
+    use crate::core::ModuleKind;
+    use crate::module::registry::ModuleRegistry;
     use crate::module::CoreConsensusVersion;
 
+    #[derive(Serialize, Deserialize)]
+    struct TestParams {
+        local: serde_json::Value,
+        consensus: serde_json::Value,
+    }
+
+    impl ModuleInitParams for TestParams {
+        type Local = serde_json::Value;
+        type Consensus = serde_json::Value;
+
+        fn from_parts(local: Self::Local, consensus: Self::Consensus) -> Self {
+            Self { local, consensus }
+        }
+
+        fn to_parts(self) -> (Self::Local, Self::Consensus) {
+            (self.local, self.consensus)
+        }
+    }
+
+    #[test]
+    fn test_stable_instance_id() {
+        let mint = ConfigGenModuleParams::new(json!({"peer": 0}), json!({"amounts": [1, 2]}));
+        let mint_other_peer =
+            ConfigGenModuleParams::new(json!({"peer": 1}), json!({"amounts": [1, 2]}));
+        let mint_other_params =
+            ConfigGenModuleParams::new(json!({"peer": 0}), json!({"amounts": [1, 2, 4]}));
+        let kind = ModuleKind::from_static_str("mint");
+
+        assert_eq!(
+            mint.stable_instance_id(&kind, 0),
+            mint_other_peer.stable_instance_id(&kind, 0)
+        );
+        assert_ne!(
+            mint.stable_instance_id(&kind, 0),
+            mint_other_params.stable_instance_id(&kind, 0)
+        );
+        assert_ne!(
+            mint.stable_instance_id(&kind, 0),
+            mint.stable_instance_id(&ModuleKind::from_static_str("wallet"), 0)
+        );
+        assert_ne!(
+            mint.stable_instance_id(&kind, 0),
+            mint.stable_instance_id(&kind, 1)
+        );
+
+        let mut registry = ModuleRegistry::<ConfigGenModuleParams>::default();
+        registry
+            .attach_config_gen_params_stable(
+                kind.clone(),
+                0,
+                mint_other_params.to_typed::<TestParams>().unwrap(),
+            )
+            .unwrap();
+        registry
+            .attach_config_gen_params_stable(
+                kind.clone(),
+                0,
+                mint.to_typed::<TestParams>().unwrap(),
+            )
+            .unwrap();
+        assert!(registry.get(mint.stable_instance_id(&kind, 0)).is_some());
+        assert!(registry
+            .get(mint_other_params.stable_instance_id(&kind, 0))
+            .is_some());
+    }
+
+    #[test]
+    fn test_stable_instance_id_collision() {
+        let mint = ConfigGenModuleParams::new(json!({"peer": 0}), json!({"amounts": [1, 2]}));
+        let params = || mint.to_typed::<TestParams>().unwrap();
+        let kind = ModuleKind::from_static_str("mint");
+
+        let mut registry = ModuleRegistry::<ConfigGenModuleParams>::default();
+        registry
+            .attach_config_gen_params_stable(kind.clone(), 0, params())
+            .unwrap();
+        let err = registry
+            .attach_config_gen_params_stable(kind.clone(), 0, params())
+            .unwrap_err();
+        assert!(err.to_string().contains("is already taken"));
+        assert_eq!(registry.iter_modules().count(), 1);
+
+        // A second instance with the same params needs its own discriminator
+        registry
+            .attach_config_gen_params_stable(kind.clone(), 1, params())
+            .unwrap();
+        assert!(registry.get(mint.stable_instance_id(&kind, 1)).is_some());
+    }
+
     #[test]
     fn test_dcode_meta() {
         let config = ClientConfig {
@@ -93,6 +93,15 @@ async fn output_status(
         module_instance_id: ModuleInstanceId,
     ) -> Option<DynOutputOutcome>;
 
+    /// Recreates the outcome of an output accepted in a previous session from
+    /// our own keys after syncing from a state snapshot
+    async fn rebuild_output_outcome(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        output: &DynOutput,
+        out_point: OutPoint,
+    ) -> anyhow::Result<()>;
+
     /// Queries the database and returns all assets and liabilities of the
     /// module.
     ///
@@ -247,6 +256,26 @@ async fn output_status(
             .map(|v| DynOutputOutcome::from_typed(module_instance_id, v))
     }
 
+    /// Recreates the outcome of an output accepted in a previous session from
+    /// our own keys after syncing from a state snapshot
+    async fn rebuild_output_outcome(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        output: &DynOutput,
+        out_point: OutPoint,
+    ) -> anyhow::Result<()> {
+        <Self as ServerModule>::rebuild_output_outcome(
+            self,
+            dbtx,
+            output
+                .as_any()
+                .downcast_ref::<<<Self as ServerModule>::Common as ModuleCommon>::Output>()
+                .expect("incorrect output type passed to module plugin"),
+            out_point,
+        )
+        .await
+    }
+
     /// Queries the database and returns all assets and liabilities of the
     /// module.
     ///
@@ -2,7 +2,7 @@
 use std::ops::Range;
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use futures::{stream, StreamExt};
 use hex::ToHex;
 use imbl::OrdMap;
@@ -47,21 +47,21 @@ pub struct MemTransaction<'a> {
     operations: Vec<DatabaseOperation>,
     tx_data: OrdMap<Vec<u8>, Vec<u8>>,
     db: &'a MemDatabase,
-    savepoint: OrdMap<Vec<u8>, Vec<u8>>,
+    /// The data and number of pending operations at each savepoint, the most
+    /// recent one last
+    savepoints: Vec<(OrdMap<Vec<u8>, Vec<u8>>, usize)>,
     num_pending_operations: usize,
-    num_savepoint_operations: usize,
 }
 
 impl<'a> fmt::Debug for MemTransaction<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_fmt(format_args!(
-            "MemTransaction {{ db={:?}, operations_len={}, tx_data_len={}, savepoint_len={}, num_pending_ops={}, num_savepoint_ops={} }}",
+            "MemTransaction {{ db={:?}, operations_len={}, tx_data_len={}, savepoints={}, num_pending_ops={} }}",
             self.db,
             self.operations.len(),
             self.tx_data.len(),
-            self.savepoint.len(),
+            self.savepoints.len(),
             self.num_pending_operations,
-            self.num_savepoint_operations,
         ))
     }
 }
@@ -93,11 +93,10 @@ async fn begin_transaction<'a>(&'a self) -> MemTransaction<'a> {
         let db_copy = self.data.read().await.clone();
         let mut memtx = MemTransaction {
             operations: Vec::new(),
-            tx_data: db_copy.clone(),
+            tx_data: db_copy,
             db: self,
-            savepoint: db_copy,
+            savepoints: vec![],
             num_pending_operations: 0,
-            num_savepoint_operations: 0,
         };
 
         memtx.set_tx_savepoint().await.expect("can't fail");
@@ -196,20 +195,31 @@ async fn raw_find_by_range(&mut self, range: Range<&[u8]>) -> Result<PrefixStrea
 #[apply(async_trait_maybe_send!)]
 impl<'a> IDatabaseTransactionOps for MemTransaction<'a> {
     async fn rollback_tx_to_savepoint(&mut self) -> Result<()> {
-        self.tx_data = self.savepoint.clone();
+        let (tx_data, num_savepoint_operations) = self
+            .savepoints
+            .pop()
+            .context("No savepoint to roll back to")?;
+
+        self.tx_data = tx_data;
 
         // Remove any pending operations beyond the savepoint
-        let removed_ops = self.num_pending_operations - self.num_savepoint_operations;
+        let removed_ops = self.num_pending_operations - num_savepoint_operations;
         for _i in 0..removed_ops {
             self.operations.pop();
         }
+        self.num_pending_operations = num_savepoint_operations;
 
         Ok(())
     }
 
     async fn set_tx_savepoint(&mut self) -> Result<()> {
-        self.savepoint = self.tx_data.clone();
-        self.num_savepoint_operations = self.num_pending_operations;
+        self.savepoints
+            .push((self.tx_data.clone(), self.num_pending_operations));
+        Ok(())
+    }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        self.savepoints.pop().context("No savepoint to release")?;
         Ok(())
     }
 }
@@ -788,6 +788,11 @@ async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<
         self.inner.raw_insert_bytes(&key, value).await
     }
 
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let key = self.get_full_key(key);
+        self.inner.raw_put_bytes(&key, value).await
+    }
+
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let key = self.get_full_key(key);
         self.inner.raw_get_bytes(&key).await
@@ -846,6 +851,10 @@ async fn rollback_tx_to_savepoint(&mut self) -> Result<()> {
     async fn set_tx_savepoint(&mut self) -> Result<()> {
         self.set_tx_savepoint().await
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        self.inner.release_tx_savepoint().await
+    }
 }
 
 /// Core raw a operations database transactions supports
@@ -856,6 +865,13 @@ pub trait IDatabaseTransactionOpsCore: MaybeSend {
     /// Insert entry
     async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>>;
 
+    /// Insert entry without reading the previous value, for callers that
+    /// already know the key is not present. Backends where reading the
+    /// previous value is expensive should override this.
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.raw_insert_bytes(key, value).await.map(|_| ())
+    }
+
     /// Get key value
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>>;
 
@@ -890,6 +906,10 @@ async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<
         (**self).raw_insert_bytes(key, value).await
     }
 
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        (**self).raw_put_bytes(key, value).await
+    }
+
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         (**self).raw_get_bytes(key).await
     }
@@ -929,6 +949,10 @@ async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<
         (**self).raw_insert_bytes(key, value).await
     }
 
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        (**self).raw_put_bytes(key, value).await
+    }
+
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         (**self).raw_get_bytes(key).await
     }
@@ -976,7 +1000,17 @@ pub trait IDatabaseTransactionOps: IDatabaseTransactionOpsCore + MaybeSend {
     /// a transaction.
     async fn set_tx_savepoint(&mut self) -> Result<()>;
 
+    /// Roll back the writes applied since the most recent savepoint and
+    /// discard that savepoint, exposing the one set before it.
     async fn rollback_tx_to_savepoint(&mut self) -> Result<()>;
+
+    /// Discard the most recent savepoint while keeping the writes applied
+    /// since it was created.
+    ///
+    /// Some implementations can't discard savepoints and keep them until the
+    /// transaction ends, so a new savepoint has to be set before rolling back
+    /// again.
+    async fn release_tx_savepoint(&mut self) -> Result<()>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -991,6 +1025,10 @@ async fn set_tx_savepoint(&mut self) -> Result<()> {
     async fn rollback_tx_to_savepoint(&mut self) -> Result<()> {
         (**self).rollback_tx_to_savepoint().await
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        (**self).release_tx_savepoint().await
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -1005,6 +1043,10 @@ async fn set_tx_savepoint(&mut self) -> Result<()> {
     async fn rollback_tx_to_savepoint(&mut self) -> Result<()> {
         (**self).rollback_tx_to_savepoint().await
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        (**self).release_tx_savepoint().await
+    }
 }
 
 /// Like [`IDatabaseTransactionOpsCore`], but typed
@@ -1028,6 +1070,14 @@ async fn insert_new_entry<K>(&mut self, key: &K, value: &K::Value)
         K: DatabaseKey + DatabaseRecord + MaybeSend + MaybeSync,
         K::Value: MaybeSend + MaybeSync;
 
+    /// Inserts an entry the caller knows not to exist yet, without reading
+    /// the database to check for a previous value. Unlike
+    /// [`Self::insert_new_entry`] an existing entry is silently overwritten.
+    async fn put_new_entry<K>(&mut self, key: &K, value: &K::Value)
+    where
+        K: DatabaseKey + DatabaseRecord + MaybeSend + MaybeSync,
+        K::Value: MaybeSend + MaybeSync;
+
     async fn find_by_range<K>(
         &mut self,
         key_range: Range<K>,
@@ -1130,6 +1180,16 @@ async fn insert_new_entry<K>(&mut self, key: &K, value: &K::Value)
         }
     }
 
+    async fn put_new_entry<K>(&mut self, key: &K, value: &K::Value)
+    where
+        K: DatabaseKey + DatabaseRecord + MaybeSend + MaybeSync,
+        K::Value: MaybeSend + MaybeSync,
+    {
+        self.raw_put_bytes(&key.to_bytes(), &value.to_bytes())
+            .await
+            .expect("Unrecoverable error occurred while inserting entry into the database");
+    }
+
     async fn find_by_range<K>(
         &mut self,
         key_range: Range<K>,
@@ -1362,6 +1422,15 @@ async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<
             .await
     }
 
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.add_notification_key(key)?;
+        self.raw
+            .as_mut()
+            .context("Cannot insert into already consumed transaction")?
+            .raw_put_bytes(key, value)
+            .await
+    }
+
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         self.raw
             .as_mut()
@@ -1434,6 +1503,15 @@ async fn set_tx_savepoint(&mut self) -> Result<()> {
             .await?;
         Ok(())
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        self.raw
+            .as_mut()
+            .context("Cannot release a tx savepoint on an already consumed transaction")?
+            .release_tx_savepoint()
+            .await?;
+        Ok(())
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -1786,6 +1864,17 @@ pub fn on_commit(&mut self, f: maybe_add_send!(impl FnOnce() + 'static)) {
         self.on_commit_hooks.push(Box::new(f));
     }
 
+    /// Number of hooks registered with [`Self::on_commit`] so far
+    pub fn on_commit_hooks_len(&self) -> usize {
+        self.on_commit_hooks.len()
+    }
+
+    /// Drop all hooks registered after the first `len` ones, used to discard
+    /// the hooks of writes that were rolled back to a savepoint
+    pub fn truncate_on_commit_hooks(&mut self, len: usize) {
+        self.on_commit_hooks.truncate(len);
+    }
+
     pub fn global_dbtx<'a>(
         &'a mut self,
         access_token: GlobalDBTxAccessToken,
@@ -1878,6 +1967,11 @@ async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<
         self.tx.raw_insert_bytes(key, value).await
     }
 
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.commit_tracker.has_writes = true;
+        self.tx.raw_put_bytes(key, value).await
+    }
+
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         self.tx.raw_get_bytes(key).await
     }
@@ -1917,6 +2011,10 @@ async fn set_tx_savepoint(&mut self) -> Result<()> {
     async fn rollback_tx_to_savepoint(&mut self) -> Result<()> {
         self.tx.rollback_tx_to_savepoint().await
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        self.tx.release_tx_savepoint().await
+    }
 }
 
 impl<T> DatabaseKeyPrefix for T
@@ -3314,6 +3412,10 @@ async fn rollback_tx_to_savepoint(&mut self) -> anyhow::Result<()> {
             async fn set_tx_savepoint(&mut self) -> anyhow::Result<()> {
                 unimplemented!()
             }
+
+            async fn release_tx_savepoint(&mut self) -> anyhow::Result<()> {
+                unimplemented!()
+            }
         }
 
         #[async_trait]
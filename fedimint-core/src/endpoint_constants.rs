@@ -13,6 +13,8 @@
 pub const AWAIT_SIGNED_SESSION_OUTCOME_ENDPOINT: &str = "await_signed_session_outcome";
 pub const SESSION_STATUS_ENDPOINT: &str = "session_status";
 pub const SHUTDOWN_ENDPOINT: &str = "shutdown";
+pub const STATE_SNAPSHOT_INFO_ENDPOINT: &str = "state_snapshot_info";
+pub const STATE_SNAPSHOT_CHUNK_ENDPOINT: &str = "state_snapshot_chunk";
 pub const CONFIG_GEN_PEERS_ENDPOINT: &str = "config_gen_peers";
 pub const CONSENSUS_CONFIG_GEN_PARAMS_ENDPOINT: &str = "consensus_config_gen_params";
 pub const DEFAULT_CONFIG_GEN_PARAMS_ENDPOINT: &str = "default_config_gen_params";
@@ -35,3 +37,8 @@
 pub const SUBMIT_API_ANNOUNCEMENT_ENDPOINT: &str = "submit_api_announcement";
 pub const SIGN_API_ANNOUNCEMENT_ENDPOINT: &str = "sign_api_announcement";
 pub const FEDIMINTD_VERSION_ENDPOINT: &str = "fedimintd_version";
+pub const ADD_ADMIN_KEY_ENDPOINT: &str = "add_admin_key";
+pub const REMOVE_ADMIN_KEY_ENDPOINT: &str = "remove_admin_key";
+pub const ADMIN_KEYS_ENDPOINT: &str = "admin_keys";
+pub const PEER_CONNECTIVITY_ENDPOINT: &str = "peer_connectivity";
+pub const FEE_REVENUE_ENDPOINT: &str = "fee_revenue";
@@ -1,7 +1,9 @@
 use fedimint_core::core::DynModuleConsensusItem as ModuleConsensusItem;
 use fedimint_core::encoding::{Decodable, Encodable};
+use serde::{Deserialize, Serialize};
 
 use crate::transaction::Transaction;
+use crate::Amount;
 
 /// All the items that may be produced during a consensus epoch
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Encodable, Decodable)]
@@ -15,3 +17,18 @@ pub enum ConsensusItem {
     #[encodable_default]
     Default { variant: u64, bytes: Vec<u8> },
 }
+
+/// Cumulative transaction fee revenue of the federation
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct FeeRevenue {
+    /// Fees and overpayments of all accepted transactions
+    pub collected: Amount,
+}
+
+impl Default for FeeRevenue {
+    fn default() -> Self {
+        Self {
+            collected: Amount::ZERO,
+        }
+    }
+}
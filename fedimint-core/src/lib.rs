@@ -90,6 +90,8 @@
 /// Common macros
 #[macro_use]
 pub mod macros;
+/// Metrics facade shared by the client, the server and the modules
+pub mod metrics;
 /// Extenable module sysystem
 pub mod module;
 /// Peer networking
@@ -0,0 +1,339 @@
+//! Instrumentation surface shared by the client, the server and the modules
+//!
+//! Metrics are declared as [`Counter`], [`Gauge`] and [`Histogram`] statics
+//! and recorded into, without depending on prometheus, so the same code works
+//! in wasm too. The records are forwarded to the [`IMetrics`] backend installed
+//! with [`set_metrics_backend`], by default [`NoopMetrics`]. `fedimintd`
+//! installs `fedimint_metrics::PrometheusMetrics` to export them via
+//! prometheus, apps embedding the client can install their own.
+//!
+//! ```
+//! use fedimint_core::metrics::Counter;
+//!
+//! static REISSUED_NOTES: Counter = Counter::new(
+//!     "client_mint_reissued_notes_total",
+//!     "Notes reissued by the client",
+//!     &["module"],
+//! );
+//!
+//! REISSUED_NOTES.inc_by(&["mint"], 3);
+//! ```
+
+use std::fmt::Debug;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use anyhow::bail;
+
+/// Buckets of histograms that don't set their own, in seconds
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Buckets for histograms of amounts, in sats
+pub const AMOUNTS_BUCKETS_SATS: &[f64] = &[
+    0.0,
+    0.1,
+    1.0,
+    10.0,
+    100.0,
+    1000.0,
+    10000.0,
+    100_000.0,
+    1_000_000.0,
+    10_000_000.0,
+    100_000_000.0,
+];
+
+/// A monotonically increasing count, optionally split by labels
+#[derive(Debug)]
+pub struct Counter {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub label_names: &'static [&'static str],
+}
+
+impl Counter {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+        }
+    }
+
+    pub fn inc(&self, label_values: &[&str]) {
+        self.inc_by(label_values, 1);
+    }
+
+    pub fn inc_by(&self, label_values: &[&str], by: u64) {
+        backend().inc_counter(self, label_values, by);
+    }
+
+    /// Exports the count of `label_values` as zero until it's first
+    /// incremented, for counters that are incremented rarely
+    pub fn init(&self, label_values: &[&str]) {
+        self.inc_by(label_values, 0);
+    }
+}
+
+/// A value that can go up and down, optionally split by labels
+#[derive(Debug)]
+pub struct Gauge {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub label_names: &'static [&'static str],
+}
+
+impl Gauge {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+        }
+    }
+
+    pub fn set(&self, label_values: &[&str], value: i64) {
+        backend().set_gauge(self, label_values, value);
+    }
+
+    pub fn inc(&self, label_values: &[&str]) {
+        self.add(label_values, 1);
+    }
+
+    pub fn dec(&self, label_values: &[&str]) {
+        self.add(label_values, -1);
+    }
+
+    pub fn add(&self, label_values: &[&str], delta: i64) {
+        backend().add_gauge(self, label_values, delta);
+    }
+}
+
+/// A distribution of observed values, optionally split by labels
+#[derive(Debug)]
+pub struct Histogram {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub label_names: &'static [&'static str],
+    pub buckets: &'static [f64],
+}
+
+impl Histogram {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+            buckets: DEFAULT_BUCKETS,
+        }
+    }
+
+    pub const fn with_buckets(self, buckets: &'static [f64]) -> Self {
+        Self { buckets, ..self }
+    }
+
+    pub fn observe(&self, label_values: &[&str], value: f64) {
+        backend().observe_histogram(self, label_values, value);
+    }
+
+    pub fn observe_duration(&self, label_values: &[&str], duration: Duration) {
+        self.observe(label_values, duration.as_secs_f64());
+    }
+
+    /// Observes the time until the returned timer is dropped or
+    /// [`HistogramTimer::observe_duration`] is called
+    pub fn start_timer(&'static self, label_values: &[&str]) -> HistogramTimer {
+        HistogramTimer {
+            histogram: self,
+            label_values: label_values.iter().map(ToString::to_string).collect(),
+            start: crate::time::now(),
+            observed: false,
+        }
+    }
+
+    /// Exports the distribution of `label_values` as empty until the first
+    /// value is observed, for histograms that are observed rarely
+    pub fn init(&self, label_values: &[&str]) {
+        backend().init_histogram(self, label_values);
+    }
+}
+
+/// Measures a duration for a [`Histogram`], see [`Histogram::start_timer`]
+#[derive(Debug)]
+pub struct HistogramTimer {
+    histogram: &'static Histogram,
+    label_values: Vec<String>,
+    start: SystemTime,
+    observed: bool,
+}
+
+impl HistogramTimer {
+    /// Observes the time since the timer was started
+    pub fn observe_duration(mut self) {
+        self.observe();
+    }
+
+    fn observe(&mut self) {
+        if self.observed {
+            return;
+        }
+        self.observed = true;
+        let label_values = self
+            .label_values
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        self.histogram.observe_duration(
+            &label_values,
+            crate::time::now()
+                .duration_since(self.start)
+                .unwrap_or_default(),
+        );
+    }
+}
+
+impl Drop for HistogramTimer {
+    fn drop(&mut self) {
+        self.observe();
+    }
+}
+
+/// Where the records of [`Counter`]s, [`Gauge`]s and [`Histogram`]s end up,
+/// see the [module docs](self)
+pub trait IMetrics: Debug + Send + Sync + 'static {
+    fn inc_counter(&self, counter: &Counter, label_values: &[&str], by: u64);
+
+    fn set_gauge(&self, gauge: &Gauge, label_values: &[&str], value: i64);
+
+    fn add_gauge(&self, gauge: &Gauge, label_values: &[&str], delta: i64);
+
+    fn observe_histogram(&self, histogram: &Histogram, label_values: &[&str], value: f64);
+
+    /// Creates the series of `label_values` without observing a value
+    fn init_histogram(&self, histogram: &Histogram, label_values: &[&str]);
+}
+
+/// Backend discarding all records, used until another one is installed
+#[derive(Debug, Default)]
+pub struct NoopMetrics;
+
+impl IMetrics for NoopMetrics {
+    fn inc_counter(&self, _counter: &Counter, _label_values: &[&str], _by: u64) {}
+
+    fn set_gauge(&self, _gauge: &Gauge, _label_values: &[&str], _value: i64) {}
+
+    fn add_gauge(&self, _gauge: &Gauge, _label_values: &[&str], _delta: i64) {}
+
+    fn observe_histogram(&self, _histogram: &Histogram, _label_values: &[&str], _value: f64) {}
+
+    fn init_histogram(&self, _histogram: &Histogram, _label_values: &[&str]) {}
+}
+
+static BACKEND: OnceLock<Box<dyn IMetrics>> = OnceLock::new();
+
+/// Installs the backend all metrics are recorded into for the rest of the
+/// process' life, fails if one was installed already
+pub fn set_metrics_backend(metrics: impl IMetrics) -> anyhow::Result<()> {
+    if BACKEND.set(Box::new(metrics)).is_err() {
+        bail!("A metrics backend was already installed");
+    }
+    Ok(())
+}
+
+fn backend() -> &'static dyn IMetrics {
+    BACKEND.get().map_or(&NoopMetrics, AsRef::as_ref)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    static RECORDS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    static COUNTER: Counter = Counter::new("test_total", "Test counter", &["label"]);
+    static GAUGE: Gauge = Gauge::new("test_gauge", "Test gauge", &[]);
+    static HISTOGRAM: Histogram = Histogram::new("test_seconds", "Test histogram", &["label"]);
+
+    #[derive(Debug)]
+    struct RecordingMetrics;
+
+    fn record(record: String) {
+        RECORDS.lock().expect("Locking can't fail").push(record);
+    }
+
+    impl IMetrics for RecordingMetrics {
+        fn inc_counter(&self, counter: &Counter, label_values: &[&str], by: u64) {
+            record(format!("{} {label_values:?} +{by}", counter.name));
+        }
+
+        fn set_gauge(&self, gauge: &Gauge, label_values: &[&str], value: i64) {
+            record(format!("{} {label_values:?} ={value}", gauge.name));
+        }
+
+        fn add_gauge(&self, gauge: &Gauge, label_values: &[&str], delta: i64) {
+            record(format!("{} {label_values:?} {delta:+}", gauge.name));
+        }
+
+        fn observe_histogram(&self, histogram: &Histogram, label_values: &[&str], value: f64) {
+            record(format!(
+                "{} {label_values:?} observe {value}",
+                histogram.name
+            ));
+        }
+
+        fn init_histogram(&self, histogram: &Histogram, label_values: &[&str]) {
+            record(format!("{} {label_values:?} init", histogram.name));
+        }
+    }
+
+    #[test]
+    fn records_reach_the_installed_backend() {
+        // Discarded, there is no backend yet
+        COUNTER.inc(&["before"]);
+
+        set_metrics_backend(RecordingMetrics).expect("No backend was installed yet");
+        assert!(set_metrics_backend(NoopMetrics).is_err());
+
+        COUNTER.inc_by(&["a"], 3);
+        COUNTER.init(&["b"]);
+        GAUGE.set(&[], 5);
+        GAUGE.dec(&[]);
+        HISTOGRAM.observe(&["a"], 0.5);
+        HISTOGRAM.init(&["b"]);
+        HISTOGRAM.start_timer(&["c"]).observe_duration();
+        drop(HISTOGRAM.start_timer(&["d"]));
+
+        let records = RECORDS.lock().expect("Locking can't fail").clone();
+        assert_eq!(
+            records[..6],
+            [
+                "test_total [\"a\"] +3",
+                "test_total [\"b\"] +0",
+                "test_gauge [] =5",
+                "test_gauge [] -1",
+                "test_seconds [\"a\"] observe 0.5",
+                "test_seconds [\"b\"] init",
+            ]
+        );
+        // Timers observe exactly once, whether stopped explicitly or dropped
+        assert_eq!(records.len(), 8);
+        assert!(records[6].starts_with("test_seconds [\"c\"] observe "));
+        assert!(records[7].starts_with("test_seconds [\"d\"] observe "));
+    }
+}
@@ -32,8 +32,8 @@
 mod version;
 pub use self::version::*;
 use crate::config::{
-    ClientModuleConfig, ConfigGenModuleParams, DkgPeerMsg, ModuleInitParams, ServerModuleConfig,
-    ServerModuleConsensusConfig,
+    ClientModuleConfig, ConfigGenModuleParams, DkgPeerMsg, FederationId, ModuleInitParams,
+    ServerModuleConfig, ServerModuleConsensusConfig,
 };
 use crate::core::{
     ClientConfig, Decoder, DecoderBuilder, Input, InputError, ModuleConsensusItem,
@@ -46,6 +46,7 @@
 use crate::encoding::{Decodable, DecodeError, Encodable};
 use crate::fmt_utils::AbbreviateHexBytes;
 use crate::module::audit::Audit;
+use crate::net::admin_auth::AdminRequestSignature;
 use crate::net::peers::MuxPeerConnections;
 use crate::server::DynServerModule;
 use crate::task::{MaybeSend, TaskGroup};
@@ -83,6 +84,9 @@ impl TransactionItemAmount {
 pub struct ApiRequest<T> {
     /// Hashed user password if the API requires authentication
     pub auth: Option<ApiAuth>,
+    /// Signature of a guardian admin key, alternative to `auth`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_signature: Option<AdminRequestSignature>,
     /// Parameters required by the API
     pub params: T,
 }
@@ -93,6 +97,7 @@ impl Default for ApiRequestErased {
     fn default() -> Self {
         Self {
             auth: None,
+            admin_signature: None,
             params: JsonValue::Null,
         }
     }
@@ -102,6 +107,7 @@ impl ApiRequestErased {
     pub fn new<T: Serialize>(params: T) -> Self {
         Self {
             auth: None,
+            admin_signature: None,
             params: serde_json::to_value(params)
                 .expect("parameter serialization error - this should not happen"),
         }
@@ -114,7 +120,25 @@ pub fn to_json(&self) -> JsonValue {
     pub fn with_auth(self, auth: ApiAuth) -> Self {
         Self {
             auth: Some(auth),
-            params: self.params,
+            ..self
+        }
+    }
+
+    /// Authenticates the request to `method` of guardian `peer_id` with a
+    /// guardian admin key instead of the guardian password
+    pub fn with_admin_signature<C: secp256k1::Signing>(
+        self,
+        ctx: &secp256k1::Secp256k1<C>,
+        key: &secp256k1::Keypair,
+        federation_id: FederationId,
+        peer_id: PeerId,
+        method: &str,
+    ) -> Self {
+        let admin_signature =
+            AdminRequestSignature::sign(ctx, key, federation_id, peer_id, method, &self.params);
+        Self {
+            admin_signature: Some(admin_signature),
+            ..self
         }
     }
 
@@ -123,6 +147,7 @@ pub fn to_typed<T: serde::de::DeserializeOwned>(
     ) -> Result<ApiRequest<T>, serde_json::Error> {
         Ok(ApiRequest {
             auth: self.auth,
+            admin_signature: self.admin_signature,
             params: serde_json::from_value::<T>(self.params)?,
         })
     }
@@ -528,6 +553,9 @@ fn get_client_config(
     /// database before the module is initialized. The migrations map is
     /// indexed on the from version.
     fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn>;
+
+    /// Prefixes of the keys in the module's database holding consensus state
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>>;
 }
 
 dyn_newtype_define!(
@@ -672,6 +700,20 @@ fn get_client_config(
     fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn> {
         BTreeMap::new()
     }
+
+    /// Prefixes of the keys in the module's database holding consensus state,
+    /// i.e. data that is the same for all guardians after processing the same
+    /// sessions. Only these are included in the state snapshots lagging
+    /// guardians sync from, so data only relevant to this guardian, like its
+    /// own proposals, what it was told via the API or outcomes containing its
+    /// signature shares, must not be listed. The latter are recreated by
+    /// [`ServerModule::rebuild_output_outcome`] after syncing.
+    ///
+    /// Returns `None` by default, which disables state snapshots for the whole
+    /// federation since the module's state can't be synced.
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -750,6 +792,10 @@ fn get_client_config(
     fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn> {
         <Self as ServerModuleInit>::get_database_migrations(self)
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        <Self as ServerModuleInit>::consensus_db_prefixes(self)
+    }
 }
 
 /// Module associated types required by both client and server
@@ -882,6 +928,23 @@ async fn output_status(
         out_point: OutPoint,
     ) -> Option<<Self::Common as ModuleCommon>::OutputOutcome>;
 
+    /// Recreates the outcome of an output accepted in a previous session from
+    /// our own keys. Outcomes may contain data only this guardian can
+    /// produce, like its signature shares, so they are excluded from state
+    /// snapshots and rebuilt after syncing from one.
+    ///
+    /// The default does nothing, which is only correct if the module's
+    /// outcomes are the same for all guardians and under its
+    /// [`ServerModuleInit::consensus_db_prefixes`].
+    async fn rebuild_output_outcome(
+        &self,
+        _dbtx: &mut DatabaseTransaction<'_>,
+        _output: &<Self::Common as ModuleCommon>::Output,
+        _out_point: OutPoint,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     /// Queries the database and returns all assets and liabilities of the
     /// module.
     ///
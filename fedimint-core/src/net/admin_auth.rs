@@ -0,0 +1,165 @@
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::Message;
+use fedimint_core::encoding::{Decodable, Encodable};
+use jsonrpsee_core::JsonValue;
+use serde::{Deserialize, Serialize};
+
+use crate::config::FederationId;
+use crate::time::duration_since_epoch;
+use crate::PeerId;
+
+const ADMIN_REQUEST_MESSAGE_TAG: &[u8] = b"fedimint-admin-request";
+
+/// Signature of a guardian's admin key over an API request, allowing operator
+/// tooling to call admin endpoints without knowing the guardian password.
+///
+/// The signature commits to the federation and guardian the request is meant
+/// for, the called method, the request parameters, the time of signing and a
+/// random nonce, so a captured request can neither be used against another
+/// guardian or for a different endpoint nor replayed against the same one.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct AdminRequestSignature {
+    /// Federation the request was signed for
+    pub federation_id: FederationId,
+    /// Guardian the request was signed for
+    pub peer_id: PeerId,
+    /// API method the request was signed for
+    pub method: String,
+    /// Admin key authorized by the guardian
+    pub public_key: secp256k1::PublicKey,
+    /// Seconds since the unix epoch at the time of signing
+    pub timestamp: u64,
+    /// Random value to distinguish otherwise identical requests
+    pub nonce: u64,
+    pub signature: secp256k1::schnorr::Signature,
+}
+
+/// Authorizes an admin key to call admin endpoints of the guardian
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct AddAdminKeyRequest {
+    pub public_key: secp256k1::PublicKey,
+    /// Describes who holds the key, e.g. the name of the tool using it
+    pub label: String,
+}
+
+#[derive(Encodable, Decodable)]
+struct AdminRequestMessage {
+    federation_id: FederationId,
+    peer_id: PeerId,
+    method: String,
+    params: String,
+    timestamp: u64,
+    nonce: u64,
+}
+
+impl AdminRequestMessage {
+    fn tagged_hash(&self) -> sha256::Hash {
+        let mut msg = ADMIN_REQUEST_MESSAGE_TAG.to_vec();
+        msg.append(&mut self.consensus_encode_to_vec());
+        sha256::Hash::hash(&msg)
+    }
+}
+
+impl AdminRequestSignature {
+    /// Signs a request to `method` of guardian `peer_id` of federation
+    /// `federation_id` with the given parameters at the current time
+    pub fn sign<C: secp256k1::Signing>(
+        ctx: &secp256k1::Secp256k1<C>,
+        key: &secp256k1::Keypair,
+        federation_id: FederationId,
+        peer_id: PeerId,
+        method: &str,
+        params: &JsonValue,
+    ) -> Self {
+        let timestamp = duration_since_epoch().as_secs();
+        let nonce = rand::random();
+        let msg = AdminRequestMessage {
+            federation_id,
+            peer_id,
+            method: method.to_owned(),
+            params: params.to_string(),
+            timestamp,
+            nonce,
+        };
+        let signature = ctx.sign_schnorr(&Message::from_digest(*msg.tagged_hash().as_ref()), key);
+
+        Self {
+            federation_id,
+            peer_id,
+            method: method.to_owned(),
+            public_key: key.public_key(),
+            timestamp,
+            nonce,
+            signature,
+        }
+    }
+
+    /// Returns true if the signature was made by `public_key` over the given
+    /// parameters. Freshness and replay protection are the responsibility of
+    /// the caller.
+    pub fn verify<C: secp256k1::Verification>(
+        &self,
+        ctx: &secp256k1::Secp256k1<C>,
+        params: &JsonValue,
+    ) -> bool {
+        let msg = AdminRequestMessage {
+            federation_id: self.federation_id,
+            peer_id: self.peer_id,
+            method: self.method.clone(),
+            params: params.to_string(),
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+        };
+        ctx.verify_schnorr(
+            &self.signature,
+            &Message::from_digest(*msg.tagged_hash().as_ref()),
+            &self.public_key.x_only_public_key().0,
+        )
+        .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::{sha256, Hash};
+    use secp256k1::{Keypair, SECP256K1};
+    use serde_json::json;
+
+    use super::AdminRequestSignature;
+    use crate::config::FederationId;
+    use crate::PeerId;
+
+    #[test]
+    fn admin_request_signature_commits_to_request() {
+        let key = Keypair::new(SECP256K1, &mut rand::thread_rng());
+        let federation_id = FederationId(sha256::Hash::hash(b"federation"));
+        let params = json!({ "session": 42 });
+        let signature = AdminRequestSignature::sign(
+            SECP256K1,
+            &key,
+            federation_id,
+            PeerId::from(0),
+            "shutdown",
+            &params,
+        );
+
+        assert!(signature.verify(SECP256K1, &params));
+        assert!(!signature.verify(SECP256K1, &json!({ "session": 43 })));
+
+        let mut other_method = signature.clone();
+        other_method.method = "audit".to_owned();
+        assert!(!other_method.verify(SECP256K1, &params));
+
+        let mut other_nonce = signature.clone();
+        other_nonce.nonce += 1;
+        assert!(!other_nonce.verify(SECP256K1, &params));
+
+        let mut other_peer = signature.clone();
+        other_peer.peer_id = PeerId::from(1);
+        assert!(!other_peer.verify(SECP256K1, &params));
+
+        let mut other_federation = signature;
+        other_federation.federation_id = FederationId(sha256::Hash::hash(b"other federation"));
+        assert!(!other_federation.verify(SECP256K1, &params));
+    }
+}
@@ -0,0 +1,32 @@
+//! Compression of large API responses, negotiated per connection
+//!
+//! Session outcomes and other consensus items are returned as hex encoded
+//! blobs, which makes syncing the history of a busy federation transfer a lot
+//! of data. Clients able to decompress responses send
+//! [`ACCEPT_ENCODING_HEADER`] when opening the connection, after which the
+//! guardian replaces every response longer than
+//! [`MIN_COMPRESSED_RESPONSE_LEN`] with a [`CompressedResponse`]. Clients that
+//! don't send the header, e.g. browsers which can't set headers on a
+//! WebSocket, keep receiving plain responses.
+
+use serde::{Deserialize, Serialize};
+
+/// Header of the WebSocket handshake listing the response encodings a client
+/// accepts, comma separated
+pub const ACCEPT_ENCODING_HEADER: &str = "fedimint-accept-encoding";
+
+/// Responses compressed with zstd, see [`CompressedResponse`]
+pub const ZSTD_ENCODING: &str = "zstd";
+
+/// Serialized length from which responses are compressed, below it the saved
+/// bytes don't make up for the CPU time
+pub const MIN_COMPRESSED_RESPONSE_LEN: usize = 8 * 1024;
+
+/// A JSON response that was serialized, compressed with zstd and base64
+/// encoded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompressedResponse {
+    #[serde(rename = "fedimint_zstd")]
+    pub zstd_base64: String,
+}
@@ -1,4 +1,6 @@
+pub mod admin_auth;
 pub mod api_announcement;
+pub mod api_compression;
 pub mod peers;
 
 pub const STANDARD_FEDIMINT_P2P_PORT: u16 = 8173;
@@ -413,10 +413,97 @@ pub async fn sleep_in_test(comment: impl AsRef<str>, duration: Duration) {
 /// program shutdown).
 pub type Cancellable<T> = std::result::Result<T, Cancelled>;
 
+/// A cloneable handle used to signal cancellation to any number of waiters.
+///
+/// Unlike [`TaskHandle`] it is not tied to a [`TaskGroup`], so it can be used
+/// to abort awaiting some result (e.g. when the user navigates away) without
+/// stopping the background work producing it.
+#[derive(Clone, Debug)]
+pub struct CancellationToken {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Cancel all current and future waiters of this token
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Returns `true` if there are neither other clones of this token nor
+    /// anyone waiting for it to get cancelled
+    pub fn is_unused(&self) -> bool {
+        Arc::strong_count(&self.tx) == 1 && self.tx.receiver_count() == 0
+    }
+
+    /// Returns a future that resolves once the token got cancelled
+    pub fn cancelled(&self) -> impl Future<Output = ()> + MaybeSend + 'static {
+        let mut rx = self.tx.subscribe();
+        async move {
+            let _ = rx.wait_for(|cancelled| *cancelled).await;
+        }
+    }
+
+    /// Run the future or return [`Cancelled`] if the token gets cancelled
+    /// first
+    pub async fn run_until_cancelled<F: Future>(&self, fut: F) -> Cancellable<F::Output> {
+        match future::select(pin!(self.cancelled()), pin!(fut)).await {
+            Either::Left(((), _)) => Err(Cancelled),
+            Either::Right((value, _)) => Ok(value),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test_log::test(tokio::test)]
+    async fn cancellation_token_aborts_waiters() {
+        let token = CancellationToken::new();
+        let waiter = token.clone();
+        let handle = spawn("cancellation waiter", async move {
+            waiter
+                .run_until_cancelled(std::future::pending::<()>())
+                .await
+        });
+        sleep(Duration::from_millis(10)).await;
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert!(handle.await.expect("task not to panic").is_err());
+        assert!(token.run_until_cancelled(async { 42 }).await.is_err());
+    }
+
+    #[test]
+    fn cancellation_token_is_unused_without_clones_or_waiters() {
+        let token = CancellationToken::new();
+        assert!(token.is_unused());
+
+        let clone = token.clone();
+        assert!(!token.is_unused());
+        drop(clone);
+
+        let waiter = token.cancelled();
+        assert!(!token.is_unused());
+        drop(waiter);
+        assert!(token.is_unused());
+    }
+
     #[test_log::test(tokio::test)]
     async fn shutdown_task_group_after() -> anyhow::Result<()> {
         let tg = TaskGroup::new();
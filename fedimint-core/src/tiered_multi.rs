@@ -120,6 +120,90 @@ pub fn push(&mut self, amt: Amount, val: T) {
         self.0.entry(amt).or_default().push(val);
     }
 
+    /// Returns all items of `self` and `other` combined
+    ///
+    /// Within each tier the items of `self` come first, followed by the items
+    /// of `other`, both in their original order.
+    pub fn union(mut self, other: Self) -> Self {
+        for (amt, notes) in other.0 {
+            self.0.entry(amt).or_default().extend(notes);
+        }
+        self.assert_invariants();
+        self
+    }
+
+    /// Returns the items of `self` that are not contained in `other`
+    ///
+    /// Every item in `other` removes at most one equal item of the same tier
+    /// from `self`, the first one in order of elements in the Vec.
+    pub fn difference(mut self, other: &Self) -> Self
+    where
+        T: PartialEq,
+    {
+        for (amt, notes) in other.iter() {
+            let Entry::Occupied(mut entry) = self.0.entry(amt) else {
+                continue;
+            };
+
+            for note in notes {
+                if let Some(idx) = entry.get().iter().position(|n| n == note) {
+                    entry.get_mut().remove(idx);
+                }
+            }
+
+            if entry.get().is_empty() {
+                entry.remove_entry();
+            }
+        }
+        self.assert_invariants();
+        self
+    }
+
+    /// Selects items with a total amount of *exactly* `amount`, returns `None`
+    /// if that is not possible
+    ///
+    /// See [`TieredMulti::split`] for how the items are chosen.
+    pub fn select_exact(&self, amount: Amount) -> Option<Self>
+    where
+        T: Clone,
+    {
+        self.clone().split(amount).map(|(selected, _)| selected)
+    }
+
+    /// Splits `self` into items with a total amount of *exactly* `amount` and
+    /// the remaining items, returns `None` if that is not possible
+    ///
+    /// Items are chosen greedily from the highest tier to the lowest, taking as
+    /// many items of a tier as fit into the remaining amount. Within a tier the
+    /// items are taken in order of elements in the Vec. For denominations that
+    /// are powers of a common base this always finds a selection if one exists.
+    pub fn split(self, amount: Amount) -> Option<(Self, Self)> {
+        let mut remaining = amount;
+        let mut selected = Self::default();
+        let mut rest = Self::default();
+
+        for (amt, mut notes) in self.0.into_iter().rev() {
+            let take = ((remaining.msats / amt.msats) as usize).min(notes.len());
+            remaining -= amt * take as u64;
+
+            let left = notes.split_off(take);
+            if !notes.is_empty() {
+                selected.0.insert(amt, notes);
+            }
+            if !left.is_empty() {
+                rest.0.insert(amt, left);
+            }
+        }
+
+        if remaining != Amount::ZERO {
+            return None;
+        }
+
+        selected.assert_invariants();
+        rest.assert_invariants();
+        Some((selected, rest))
+    }
+
     fn assert_invariants(&self) {
         // Just for compactness and determinism, we don't want entries with 0 items
         #[cfg(debug_assertions)]
@@ -276,4 +360,47 @@ fn summary_works() {
         assert_eq!(summary.count_items(), notes.count_items());
         assert_eq!(summary.count_tiers(), notes.count_tiers());
     }
+
+    fn notes(items: &[(u64, u8)]) -> TieredMulti<u8> {
+        items
+            .iter()
+            .map(|(sats, id)| (Amount::from_sats(*sats), *id))
+            .collect()
+    }
+
+    #[test]
+    fn union_and_difference_work() {
+        let a = notes(&[(1, 0), (2, 1), (2, 2)]);
+        let b = notes(&[(2, 3), (4, 4)]);
+
+        let union = a.clone().union(b.clone());
+        assert_eq!(union, notes(&[(1, 0), (2, 1), (2, 2), (2, 3), (4, 4)]));
+        assert_eq!(union.total_amount(), a.total_amount() + b.total_amount());
+
+        assert_eq!(union.clone().difference(&b), a);
+        assert_eq!(union.difference(&a), b);
+        assert_eq!(
+            a.difference(&notes(&[(2, 2), (8, 5)])),
+            notes(&[(1, 0), (2, 1)])
+        );
+    }
+
+    #[test]
+    fn split_works() {
+        let all = notes(&[(1, 0), (1, 1), (2, 2), (4, 3), (4, 4), (8, 5)]);
+
+        let (selected, rest) = all.clone().split(Amount::from_sats(7)).unwrap();
+        assert_eq!(selected, notes(&[(1, 0), (2, 2), (4, 3)]));
+        assert_eq!(rest, notes(&[(1, 1), (4, 4), (8, 5)]));
+        assert_eq!(selected.union(rest), all);
+
+        assert_eq!(
+            all.select_exact(Amount::from_sats(17)),
+            Some(notes(&[(1, 0), (4, 3), (4, 4), (8, 5)]))
+        );
+        assert_eq!(all.select_exact(Amount::ZERO), Some(TieredMulti::default()));
+        assert_eq!(all.select_exact(all.total_amount()), Some(all.clone()));
+        assert_eq!(all.select_exact(Amount::from_sats(21)), None);
+        assert_eq!(all.select_exact(Amount::from_msats(1500)), None);
+    }
 }
@@ -141,8 +141,8 @@ pub fn port_or_known_default(&self) -> Option<u16> {
             return Some(port);
         }
         match self.0.scheme() {
-            // p2p port scheme
-            "fedimint" => Some(STANDARD_FEDIMINT_P2P_PORT),
+            // p2p port schemes
+            "fedimint" | "fedimint+quic" => Some(STANDARD_FEDIMINT_P2P_PORT),
             _ => self.0.port_or_known_default(),
         }
     }
@@ -408,6 +408,17 @@ pub fn handle_version_hash_command(version_hash: &str) {
     }
 }
 
+/// For CLIs, asks the user a yes/no question on the terminal, defaulting to
+/// no.
+#[cfg(not(target_family = "wasm"))]
+pub fn confirm(question: &str) -> io::Result<bool> {
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
 /// Run the supplied closure `op_fn` until it succeeds. Frequency and number of
 /// retries is determined by the specified strategy.
 ///
@@ -19,6 +19,7 @@
 use fedimint_server::config::io::read_server_config;
 use fedimint_server::config::ServerConfig;
 use fedimint_server::consensus::db as ConsensusRange;
+use fedimint_server::net::api::admin_auth::AdminKeyPrefix;
 use fedimint_server::net::api::announcement::ApiAnnouncementPrefix;
 use futures::StreamExt;
 use ln_gateway::Gateway;
@@ -325,6 +326,47 @@ async fn write_serialized_consensus_range(
                     "API Announcements"
                 );
             }
+            ConsensusRange::DbKeyPrefix::AdminKeys => {
+                push_db_pair_items_no_serde!(
+                    dbtx,
+                    AdminKeyPrefix,
+                    AdminKey,
+                    String,
+                    consensus,
+                    "Admin Keys"
+                );
+            }
+            ConsensusRange::DbKeyPrefix::SessionFees => {
+                push_db_pair_items!(
+                    dbtx,
+                    ConsensusRange::SessionFeesPrefix,
+                    ConsensusRange::SessionFeesKey,
+                    fedimint_core::Amount,
+                    consensus,
+                    "Session Fees"
+                );
+            }
+            ConsensusRange::DbKeyPrefix::FeeRevenue => {
+                push_db_pair_items!(
+                    dbtx,
+                    ConsensusRange::FeeRevenuePrefix,
+                    ConsensusRange::FeeRevenueKey,
+                    fedimint_core::epoch::FeeRevenue,
+                    consensus,
+                    "Fee Revenue"
+                );
+            }
+            ConsensusRange::DbKeyPrefix::OutputOutcomeRebuild => {
+                if let Some(rebuilt_sessions) = dbtx
+                    .get_value(&ConsensusRange::OutputOutcomeRebuildKey)
+                    .await
+                {
+                    consensus.insert(
+                        "Output Outcome Rebuild".to_string(),
+                        Box::new(rebuilt_sessions),
+                    );
+                }
+            }
         }
     }
     async fn write_serialized_client_operation_log(
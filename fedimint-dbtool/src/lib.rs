@@ -142,7 +142,7 @@ pub fn with_client_module_init<T>(mut self, gen: T) -> Self
 
     pub fn with_default_modules_inits(self) -> Self {
         self.with_server_module_init(WalletInit)
-            .with_server_module_init(MintInit)
+            .with_server_module_init(MintInit::default())
             .with_server_module_init(LightningInit)
             .with_server_module_init(fedimint_lnv2_server::LightningInit)
             .with_server_module_init(MetaInit)
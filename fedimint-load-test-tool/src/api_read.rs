@@ -0,0 +1,332 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use fedimint_api_client::api::net::Connector;
+use fedimint_api_client::api::{DynGlobalApi, DynModuleApi, IRawFederationApi};
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::endpoint_constants::{
+    AWAIT_TRANSACTION_ENDPOINT, CLIENT_CONFIG_ENDPOINT, SESSION_COUNT_ENDPOINT,
+    SESSION_STATUS_ENDPOINT,
+};
+use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::invite_code::InviteCode;
+use fedimint_core::module::ApiRequestErased;
+use fedimint_core::util::BoxFuture;
+use fedimint_core::{PeerId, TransactionId};
+use fedimint_wallet_client::endpoint_constants::WALLET_SUMMARY_ENDPOINT;
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::Rng;
+use tracing::{info, warn};
+
+use crate::common::build_client;
+use crate::metrics_channel::MetricSender;
+use crate::seed::with_rng;
+use crate::think_time::ThinkTime;
+use crate::MetricEvent;
+
+/// A read-only call to the guardian API, which doesn't require funds and
+/// doesn't create transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiReadCall {
+    /// Fetch the client config, like every client joining the federation
+    Config,
+    /// Look up the status of a recently accepted transaction
+    TxStatus,
+    /// Fetch the summary of the federation's on-chain balance
+    Balance,
+    /// Fetch the number of sessions, like every client syncing
+    SessionCount,
+    /// Fetch the outcome of a random past session
+    Session,
+}
+
+impl ApiReadCall {
+    const ALL: [ApiReadCall; 5] = [
+        ApiReadCall::Config,
+        ApiReadCall::TxStatus,
+        ApiReadCall::Balance,
+        ApiReadCall::SessionCount,
+        ApiReadCall::Session,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ApiReadCall::Config => "config",
+            ApiReadCall::TxStatus => "tx-status",
+            ApiReadCall::Balance => "balance",
+            ApiReadCall::SessionCount => "session-count",
+            ApiReadCall::Session => "session",
+        }
+    }
+
+    fn metric_name(self) -> String {
+        format!("api_read_{}", self.name().replace('-', "_"))
+    }
+}
+
+impl fmt::Display for ApiReadCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ApiReadCall {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|call| call.name() == s)
+            .with_context(|| {
+                format!(
+                    "Unknown read call {s}, expected one of: {}",
+                    Self::ALL.map(ApiReadCall::name).join(", ")
+                )
+            })
+    }
+}
+
+/// Relative frequencies of the [`ApiReadCall`]s users issue, parsed from a
+/// comma separated list of `<call>:<weight>`, e.g.
+/// `config:1,tx-status:4,balance:2,session-count:4,session:1`
+#[derive(Debug, Clone)]
+pub struct ApiReadMix {
+    weights: Vec<(ApiReadCall, u32)>,
+}
+
+impl ApiReadMix {
+    fn without(&self, excluded: ApiReadCall) -> anyhow::Result<Self> {
+        let weights = self
+            .weights
+            .iter()
+            .copied()
+            .filter(|(call, _)| *call != excluded)
+            .collect::<Vec<_>>();
+        if weights.is_empty() {
+            bail!("No read calls left in the mix without {excluded}");
+        }
+        Ok(Self { weights })
+    }
+
+    fn contains(&self, call: ApiReadCall) -> bool {
+        self.weights.iter().any(|(c, _)| *c == call)
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> ApiReadCall {
+        let index = WeightedIndex::new(self.weights.iter().map(|(_, weight)| *weight))
+            .expect("Weights were validated when parsing");
+        self.weights[index.sample(rng)].0
+    }
+}
+
+impl fmt::Display for ApiReadMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mix = self
+            .weights
+            .iter()
+            .map(|(call, weight)| format!("{call}:{weight}"))
+            .collect::<Vec<_>>();
+        f.write_str(&mix.join(","))
+    }
+}
+
+impl FromStr for ApiReadMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut weights = Vec::<(ApiReadCall, u32)>::new();
+        for entry in s.split(',') {
+            let (call, weight) = entry
+                .split_once(':')
+                .with_context(|| format!("Invalid mix entry {entry}, expected <call>:<weight>"))?;
+            let call = ApiReadCall::from_str(call)?;
+            let weight = weight
+                .parse::<u32>()
+                .with_context(|| format!("Invalid weight of {call}: {weight}"))?;
+            if weights.iter().any(|(c, _)| *c == call) {
+                bail!("{call} is given more than once");
+            }
+            if weight > 0 {
+                weights.push((call, weight));
+            }
+        }
+        if weights.is_empty() {
+            bail!("At least one read call needs a positive weight");
+        }
+        Ok(Self { weights })
+    }
+}
+
+/// What the read calls are issued against, fetched once before the users
+/// start so they don't have to issue any extra calls
+struct ApiReadTargets {
+    wallet_instance_id: ModuleInstanceId,
+    session_count: u64,
+    txids: Vec<TransactionId>,
+}
+
+impl ApiReadTargets {
+    async fn fetch(invite_code: &InviteCode, tx_status_sessions: u64) -> anyhow::Result<Self> {
+        let (client, _) = build_client(Some(invite_code.clone()), None).await?;
+        let wallet_instance_id = client
+            .get_first_instance(&fedimint_wallet_client::KIND)
+            .context("The federation has no wallet module")?;
+        let session_count = client.api().session_count().await?;
+
+        let mut txids = vec![];
+        for session in session_count.saturating_sub(tx_status_sessions)..session_count {
+            let outcome = client.api().await_block(session, client.decoders()).await?;
+            txids.extend(
+                outcome
+                    .items
+                    .into_iter()
+                    .filter_map(|item| match item.item {
+                        ConsensusItem::Transaction(tx) => Some(tx.tx_hash()),
+                        _ => None,
+                    }),
+            );
+        }
+        info!(
+            "Found {} transactions in the last {tx_status_sessions} of {session_count} sessions",
+            txids.len()
+        );
+
+        Ok(Self {
+            wallet_instance_id,
+            session_count,
+            txids,
+        })
+    }
+}
+
+/// Spawns `users` users that each issue read calls sampled from `mix` over
+/// their own connections for `duration`, without creating any transactions.
+///
+/// Every call goes to a random guardian and bypasses the client side caches,
+/// so the measured latencies are those of the guardian API layer. Failed calls
+/// are reported as separate `_error` metrics instead of failing the user.
+pub async fn run_api_read_load_test(
+    invite_code: InviteCode,
+    users: u16,
+    duration: Duration,
+    mix: ApiReadMix,
+    tx_status_sessions: u64,
+    think_time: ThinkTime,
+    event_sender: &MetricSender,
+) -> anyhow::Result<Vec<BoxFuture<'static, anyhow::Result<()>>>> {
+    let targets = ApiReadTargets::fetch(&invite_code, tx_status_sessions).await?;
+
+    let mut mix = mix;
+    if targets.txids.is_empty() && mix.contains(ApiReadCall::TxStatus) {
+        warn!("No recent transactions to look up, removing tx-status from the mix");
+        mix = mix.without(ApiReadCall::TxStatus)?;
+    }
+    if targets.session_count == 0 && mix.contains(ApiReadCall::Session) {
+        warn!("No past sessions to fetch, removing session from the mix");
+        mix = mix.without(ApiReadCall::Session)?;
+    }
+    info!("Running {users} users issuing read calls with the mix {mix} for {duration:?}");
+
+    let targets = Arc::new(targets);
+    Ok((0..users)
+        .map(|_| {
+            let api = DynGlobalApi::from_invite_code(&Connector::default(), &invite_code);
+            let wallet_api = api.with_module(targets.wallet_instance_id);
+            let targets = targets.clone();
+            let mix = mix.clone();
+            let event_sender = event_sender.clone();
+            let f: BoxFuture<_> = Box::pin(async move {
+                let initial_time = fedimint_core::time::now();
+                while initial_time.elapsed()? < duration {
+                    let (call, peer_id, params) = with_rng(|rng| {
+                        let call = mix.sample(rng);
+                        let peer_id = *api
+                            .all_peers()
+                            .iter()
+                            .choose(rng)
+                            .expect("Federation has peers");
+                        (call, peer_id, call_params(call, &targets, rng))
+                    });
+
+                    let m = fedimint_core::time::now();
+                    let result = issue_call(&api, &wallet_api, call, peer_id, params).await;
+                    let name = match result {
+                        Ok(()) => call.metric_name(),
+                        Err(e) => {
+                            warn!("Read call {call} to peer {peer_id} failed: {e}");
+                            format!("{}_error", call.metric_name())
+                        }
+                    };
+                    event_sender
+                        .send(MetricEvent {
+                            name,
+                            duration: m.elapsed()?,
+                        })
+                        .await?;
+
+                    think_time.sleep().await;
+                }
+                Ok(())
+            });
+            f
+        })
+        .collect())
+}
+
+fn call_params(
+    call: ApiReadCall,
+    targets: &ApiReadTargets,
+    rng: &mut impl Rng,
+) -> ApiRequestErased {
+    match call {
+        ApiReadCall::Config | ApiReadCall::Balance | ApiReadCall::SessionCount => {
+            ApiRequestErased::default()
+        }
+        ApiReadCall::TxStatus => ApiRequestErased::new(
+            targets
+                .txids
+                .choose(rng)
+                .expect("tx-status is only in the mix if there are transactions"),
+        ),
+        ApiReadCall::Session => ApiRequestErased::new(rng.gen_range(0..targets.session_count)),
+    }
+}
+
+async fn issue_call(
+    api: &DynGlobalApi,
+    wallet_api: &DynModuleApi,
+    call: ApiReadCall,
+    peer_id: PeerId,
+    params: ApiRequestErased,
+) -> anyhow::Result<()> {
+    let params = [params.to_json()];
+    match call {
+        ApiReadCall::Config => {
+            api.request_raw(peer_id, CLIENT_CONFIG_ENDPOINT, &params)
+                .await
+        }
+        ApiReadCall::TxStatus => {
+            api.request_raw(peer_id, AWAIT_TRANSACTION_ENDPOINT, &params)
+                .await
+        }
+        ApiReadCall::Balance => {
+            wallet_api
+                .request_raw(peer_id, WALLET_SUMMARY_ENDPOINT, &params)
+                .await
+        }
+        ApiReadCall::SessionCount => {
+            api.request_raw(peer_id, SESSION_COUNT_ENDPOINT, &params)
+                .await
+        }
+        ApiReadCall::Session => {
+            api.request_raw(peer_id, SESSION_STATUS_ENDPOINT, &params)
+                .await
+        }
+    }?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use fedimint_client::backup::Metadata;
+use fedimint_client::{Client, ClientHandleArc};
+use fedimint_core::invite_code::InviteCode;
+use fedimint_core::Amount;
+use fedimint_mint_client::OOBNotes;
+use tracing::info;
+
+use crate::common::{build_client, reissue_notes, remint_denomination, restore_client};
+use crate::metrics_channel::MetricSender;
+use crate::think_time::ThinkTime;
+use crate::tx_size::spend_denomination;
+use crate::{spend_and_reissue_notes, MetricEvent};
+
+/// Backs up a client holding `count` notes of `denomination` to the
+/// federation, makes `ops` transactions, wipes its database and restores it
+/// from the federation, for every note count of `note_counts` and every number
+/// of operations of `history`.
+///
+/// Every case runs on a new in-memory client, funded by `client` and paying
+/// the funds back to it once restored. The operations after the backup are
+/// single note reissues that keep the number of notes the same, the restore
+/// has to replay them from the federation's history. Records
+/// `backup_<count>_notes`, `restore_download_<count>_notes` for downloading
+/// the backup and `restore_<count>_notes_<ops>_ops` for the whole restore.
+#[allow(clippy::too_many_arguments)]
+pub async fn do_backup_restore_user_task(
+    prefix: String,
+    client: ClientHandleArc,
+    invite_code: InviteCode,
+    oob_notes: Vec<OOBNotes>,
+    note_counts: Vec<u16>,
+    history: Vec<u16>,
+    denomination: Amount,
+    think_time: ThinkTime,
+    event_sender: MetricSender,
+) -> anyhow::Result<()> {
+    for oob_notes in oob_notes {
+        reissue_notes(&client, oob_notes, &event_sender).await?;
+    }
+    let mut cases = note_counts
+        .iter()
+        .flat_map(|&count| history.iter().map(move |&ops| (count, ops)))
+        .peekable();
+    while let Some((count, ops)) = cases.next() {
+        backup_and_restore(
+            &client,
+            &invite_code,
+            count,
+            ops,
+            denomination,
+            &event_sender,
+        )
+        .await?;
+        info!(
+            "{prefix} Restored a client with {count} notes and {ops} operations since its backup"
+        );
+        if cases.peek().is_some() {
+            think_time.sleep().await;
+        }
+    }
+    Ok(())
+}
+
+async fn backup_and_restore(
+    client: &ClientHandleArc,
+    invite_code: &InviteCode,
+    count: u16,
+    ops: u16,
+    denomination: Amount,
+    event_sender: &MetricSender,
+) -> anyhow::Result<()> {
+    remint_denomination(client, denomination, count).await?;
+    let oob_notes = spend_denomination(client, denomination, count).await?;
+    let (backed_up, _) = build_client(Some(invite_code.clone()), None).await?;
+    reissue_notes(&backed_up, oob_notes, event_sender).await?;
+    // Reissuing splits the amount into the client's own denominations
+    remint_denomination(&backed_up, denomination, count).await?;
+
+    let m = fedimint_core::time::now();
+    backed_up.backup_to_federation(Metadata::empty()).await?;
+    send_metric(event_sender, format!("backup_{count}_notes"), m.elapsed()?).await?;
+    for _ in 0..ops {
+        remint_denomination(&backed_up, denomination, 1).await?;
+    }
+
+    let balance = backed_up.get_balance().await;
+    let config = backed_up.config().await;
+    let api_secret = backed_up.api_secret().clone();
+    let client_secret = Client::load_decodable_client_secret::<[u8; 64]>(backed_up.db()).await?;
+    // Stops the client before its state is restored elsewhere
+    drop(backed_up);
+
+    let m = fedimint_core::time::now();
+    let (restored, download) = restore_client(config, api_secret, client_secret).await?;
+    let restore = m.elapsed()?;
+    send_metric(
+        event_sender,
+        format!("restore_download_{count}_notes"),
+        download,
+    )
+    .await?;
+    send_metric(
+        event_sender,
+        format!("restore_{count}_notes_{ops}_ops"),
+        restore,
+    )
+    .await?;
+
+    let restored_balance = restored.get_balance().await;
+    if restored_balance != balance {
+        bail!("Restored a balance of {restored_balance} instead of {balance}");
+    }
+    spend_and_reissue_notes(&restored, client, balance, event_sender).await
+}
+
+async fn send_metric(
+    event_sender: &MetricSender,
+    name: String,
+    duration: Duration,
+) -> anyhow::Result<()> {
+    event_sender.send(MetricEvent { name, duration }).await
+}
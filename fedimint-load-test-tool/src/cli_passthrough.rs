@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use devimint::cmd;
+use devimint::util::get_fedimint_cli_path;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::invite_code::InviteCode;
+use fedimint_core::Amount;
+use fedimint_mint_client::{MintClientModule, OOBNotes, ReissueExternalNotesState};
+use futures::StreamExt;
+use tracing::info;
+
+use crate::common::do_spend_notes;
+use crate::metrics_channel::MetricSender;
+use crate::think_time::ThinkTime;
+use crate::MetricEvent;
+
+/// Prefix of the metrics of operations done through the client library
+pub const LIB_METRIC_PREFIX: &str = "lib_";
+/// Prefix of the metrics of the same operations done through `fedimint-cli`
+pub const CLI_METRIC_PREFIX: &str = "cli_";
+
+/// A user that does everything by spawning a `fedimint-cli` process on its own
+/// data dir, like a script driving the CLI would
+pub struct CliUser {
+    data_dir: PathBuf,
+}
+
+impl CliUser {
+    /// Opens the CLI client in `data_dir`, joining the federation with
+    /// `invite_code` if it doesn't exist yet
+    pub async fn open(data_dir: PathBuf, invite_code: &Option<InviteCode>) -> anyhow::Result<Self> {
+        let user = Self { data_dir };
+        if !user.data_dir.join("client.db").exists() {
+            let invite_code = invite_code.clone().context(
+                "Running on this data dir for the first time, an invite code is required",
+            )?;
+            tokio::fs::create_dir_all(&user.data_dir).await?;
+            cmd!(
+                get_fedimint_cli_path(),
+                "--data-dir",
+                user.data_dir.display(),
+                "join-federation",
+                invite_code
+            )
+            .run()
+            .await?;
+        }
+        Ok(user)
+    }
+
+    pub async fn spend(&self, amount: Amount) -> anyhow::Result<OOBNotes> {
+        cmd!(
+            get_fedimint_cli_path(),
+            "--data-dir",
+            self.data_dir.display(),
+            "spend",
+            amount.msats.to_string(),
+            "--allow-overpay"
+        )
+        .out_json()
+        .await?["notes"]
+            .as_str()
+            .map(OOBNotes::from_str)
+            .transpose()?
+            .context("missing notes output")
+    }
+
+    /// Reissues `oob_notes`, waiting until they are spendable
+    pub async fn reissue(&self, oob_notes: &OOBNotes) -> anyhow::Result<()> {
+        cmd!(
+            get_fedimint_cli_path(),
+            "--data-dir",
+            self.data_dir.display(),
+            "reissue",
+            oob_notes
+        )
+        .run()
+        .await
+    }
+}
+
+/// Spends and reissues `amount` `iterations` times, alternating between the
+/// client library and `fedimint-cli` so both paths run under the same load.
+/// Each path gets its own funds, `lib_notes` and `cli_notes` respectively.
+#[allow(clippy::too_many_arguments)]
+pub async fn do_cli_passthrough_user_task(
+    prefix: String,
+    client: ClientHandleArc,
+    cli_user: CliUser,
+    lib_notes: Vec<OOBNotes>,
+    cli_notes: Vec<OOBNotes>,
+    iterations: u16,
+    amount: Amount,
+    think_time: ThinkTime,
+    event_sender: MetricSender,
+) -> anyhow::Result<()> {
+    for oob_notes in lib_notes {
+        lib_reissue(&client, oob_notes).await?;
+    }
+    for oob_notes in cli_notes {
+        cli_user.reissue(&oob_notes).await?;
+    }
+    info!("{prefix} Funded library and CLI clients");
+
+    for i in 0..iterations {
+        let m = fedimint_core::time::now();
+        let (_, oob_notes) = do_spend_notes(&client, amount).await?;
+        send_metric(&event_sender, LIB_METRIC_PREFIX, "spend_notes", m).await?;
+        let m = fedimint_core::time::now();
+        lib_reissue(&client, oob_notes).await?;
+        send_metric(&event_sender, LIB_METRIC_PREFIX, "reissue_notes", m).await?;
+
+        let m = fedimint_core::time::now();
+        let oob_notes = cli_user.spend(amount).await?;
+        send_metric(&event_sender, CLI_METRIC_PREFIX, "spend_notes", m).await?;
+        let m = fedimint_core::time::now();
+        cli_user.reissue(&oob_notes).await?;
+        send_metric(&event_sender, CLI_METRIC_PREFIX, "reissue_notes", m).await?;
+
+        if i + 1 < iterations {
+            think_time.sleep().await;
+        }
+    }
+    Ok(())
+}
+
+async fn lib_reissue(client: &ClientHandleArc, oob_notes: OOBNotes) -> anyhow::Result<()> {
+    let mint = client.get_first_module::<MintClientModule>()?;
+    let operation_id = mint.reissue_external_notes(oob_notes, ()).await?;
+    let mut updates = mint
+        .subscribe_reissue_external_notes(operation_id)
+        .await?
+        .into_stream();
+    while let Some(update) = updates.next().await {
+        if let ReissueExternalNotesState::Failed(e) = update {
+            bail!("Reissue failed: {e}")
+        }
+    }
+    Ok(())
+}
+
+async fn send_metric(
+    event_sender: &MetricSender,
+    prefix: &str,
+    name: &str,
+    started: std::time::SystemTime,
+) -> anyhow::Result<()> {
+    event_sender
+        .send(MetricEvent {
+            name: format!("{prefix}{name}"),
+            duration: started.elapsed()?,
+        })
+        .await
+}
+
+/// Prints how much slower every operation measured on both paths was through
+/// `fedimint-cli`, given the average duration of every metric
+pub fn print_cli_overhead(averages: &BTreeMap<String, Duration>) {
+    for (name, cli_avg) in averages {
+        let Some(operation) = name.strip_prefix(CLI_METRIC_PREFIX) else {
+            continue;
+        };
+        let Some(lib_avg) = averages.get(&format!("{LIB_METRIC_PREFIX}{operation}")) else {
+            continue;
+        };
+        let overhead_ms = (cli_avg.as_secs_f64() - lib_avg.as_secs_f64()) * 1000.0;
+        let ratio = cli_avg.as_secs_f64() / lib_avg.as_secs_f64();
+        println!("CLI overhead of {operation}: {overhead_ms:.0}ms on average ({ratio:.2}x)");
+    }
+}
@@ -1,5 +1,7 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -8,7 +10,8 @@
 use devimint::util::{ClnLightningCli, FedimintCli, LnCli};
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
 use fedimint_client::transaction::TransactionBuilder;
-use fedimint_client::{Client, ClientHandleArc};
+use fedimint_client::{Client, ClientBuilder, ClientHandleArc};
+use fedimint_core::config::ClientConfig;
 use fedimint_core::core::{IntoDynInstance, OperationId};
 use fedimint_core::db::Database;
 use fedimint_core::invite_code::InviteCode;
@@ -20,17 +23,48 @@
 };
 use fedimint_ln_common::LightningGateway;
 use fedimint_mint_client::{
-    MintClientInit, MintClientModule, MintCommonInit, OOBNotes, SelectNotesWithAtleastAmount,
+    MintClientInit, MintClientModule, MintCommonInit, OOBNotes, ReissueExternalNotesState,
+    SelectNotesWithAtleastAmount,
 };
 use fedimint_wallet_client::WalletClientInit;
 use futures::StreamExt;
 use lightning_invoice::Bolt11Invoice;
-use tokio::sync::mpsc;
-use tracing::{info, warn};
+use rand::Rng;
+use tracing::{debug, info, warn};
 
+use crate::metrics_channel::MetricSender;
+use crate::seed::with_rng;
 use crate::MetricEvent;
 
+/// Set by `--remote`, see [`set_remote`]
+static REMOTE: AtomicBool = AtomicBool::new(false);
+
+/// Marks the federation under test as remote, so the fedimint-cli of a local
+/// devimint environment, which belongs to another federation if any, is never
+/// used to get invite codes or notes
+pub fn set_remote(remote: bool) {
+    REMOTE.store(remote, Ordering::Relaxed);
+}
+
+/// Set by `--reuse-db`, see [`set_reuse_db`]
+static REUSE_DB: AtomicBool = AtomicBool::new(false);
+
+/// Makes the run fund the users from the notes prepared in the client
+/// databases of `--reuse-db` only, instead of topping them up with notes from
+/// fedimint-cli
+pub fn set_reuse_db(reuse_db: bool) {
+    REUSE_DB.store(reuse_db, Ordering::Relaxed);
+}
+
+fn ensure_local_federation(action: &str) -> anyhow::Result<()> {
+    if REMOTE.load(Ordering::Relaxed) {
+        bail!("Not {action} through fedimint-cli, the federation is remote");
+    }
+    Ok(())
+}
+
 pub async fn get_invite_code_cli(peer: PeerId) -> anyhow::Result<InviteCode> {
+    ensure_local_federation("getting an invite code")?;
     cmd!(FedimintCli, "invite-code", peer).out_json().await?["invite_code"]
         .as_str()
         .map(InviteCode::from_str)
@@ -39,6 +73,7 @@ pub async fn get_invite_code_cli(peer: PeerId) -> anyhow::Result<InviteCode> {
 }
 
 pub async fn get_notes_cli(amount: &Amount) -> anyhow::Result<OOBNotes> {
+    ensure_local_federation("getting notes")?;
     cmd!(FedimintCli, "spend", amount.msats.to_string())
         .out_json()
         .await?["notes"]
@@ -49,6 +84,11 @@ pub async fn get_notes_cli(amount: &Amount) -> anyhow::Result<OOBNotes> {
 }
 
 pub async fn try_get_notes_cli(amount: &Amount, tries: usize) -> anyhow::Result<OOBNotes> {
+    // Not worth retrying
+    ensure_local_federation("getting notes")?;
+    if REUSE_DB.load(Ordering::Relaxed) {
+        bail!("Not getting notes through fedimint-cli, only the notes prepared in the --reuse-db are used");
+    }
     for _ in 0..tries {
         match get_notes_cli(amount).await {
             Ok(oob_notes) => return Ok(oob_notes),
@@ -64,7 +104,7 @@ pub async fn try_get_notes_cli(amount: &Amount, tries: usize) -> anyhow::Result<
 pub async fn reissue_notes(
     client: &ClientHandleArc,
     oob_notes: OOBNotes,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
 ) -> anyhow::Result<()> {
     let m = fedimint_core::time::now();
     let mint = &client.get_first_module::<MintClientModule>()?;
@@ -74,14 +114,28 @@ pub async fn reissue_notes(
         .await?
         .into_stream();
     while let Some(update) = updates.next().await {
-        if let fedimint_mint_client::ReissueExternalNotesState::Failed(e) = update {
-            bail!("Reissue failed: {e}")
+        match update {
+            ReissueExternalNotesState::Issuing => {
+                event_sender
+                    .send(MetricEvent {
+                        name: "reissue_notes_accepted".into(),
+                        duration: m.elapsed()?,
+                    })
+                    .await?;
+            }
+            ReissueExternalNotesState::NotesStored { stored, total } => {
+                debug!("Stored {stored} of {total} reissued notes");
+            }
+            ReissueExternalNotesState::Failed(e) => bail!("Reissue failed: {e}"),
+            _ => {}
         }
     }
-    event_sender.send(MetricEvent {
-        name: "reissue_notes".into(),
-        duration: m.elapsed()?,
-    })?;
+    event_sender
+        .send(MetricEvent {
+            name: "reissue_notes".into(),
+            duration: m.elapsed()?,
+        })
+        .await?;
     Ok(())
 }
 
@@ -149,11 +203,7 @@ pub async fn build_client(
     } else {
         fedimint_core::db::mem_impl::MemDatabase::new().into()
     };
-    let mut client_builder = Client::builder(db).await?;
-    client_builder.with_module(MintClientInit);
-    client_builder.with_module(LightningClientInit::default());
-    client_builder.with_module(WalletClientInit::default());
-    client_builder.with_primary_module_kind(fedimint_mint_client::KIND);
+    let client_builder = client_builder(db).await?;
     let client_secret =
         Client::load_or_generate_client_secret(client_builder.db_no_decoders()).await?;
     let root_secret = PlainRootSecretStrategy::to_root_secret(&client_secret);
@@ -173,6 +223,41 @@ pub async fn build_client(
     Ok((Arc::new(client), invite_code))
 }
 
+/// Restores the client with `client_secret` into a fresh in-memory database,
+/// from its latest backup on the federation and the history since. Returns
+/// the client once all modules recovered and how long downloading the backup
+/// took.
+pub async fn restore_client(
+    config: ClientConfig,
+    api_secret: Option<String>,
+    client_secret: [u8; 64],
+) -> anyhow::Result<(ClientHandleArc, Duration)> {
+    let client_builder =
+        client_builder(fedimint_core::db::mem_impl::MemDatabase::new().into()).await?;
+    Client::store_encodable_client_secret(client_builder.db_no_decoders(), client_secret).await?;
+    let root_secret = PlainRootSecretStrategy::to_root_secret(&client_secret);
+
+    let m = fedimint_core::time::now();
+    let backup = client_builder
+        .download_backup_from_federation(&root_secret, &config, api_secret.clone())
+        .await?;
+    let download = m.elapsed()?;
+    let client = client_builder
+        .recover(root_secret, config, api_secret, backup)
+        .await?;
+    client.wait_for_all_recoveries().await?;
+    Ok((Arc::new(client), download))
+}
+
+async fn client_builder(db: Database) -> anyhow::Result<ClientBuilder> {
+    let mut client_builder = Client::builder(db).await?;
+    client_builder.with_module(MintClientInit);
+    client_builder.with_module(LightningClientInit::default());
+    client_builder.with_module(WalletClientInit::default());
+    client_builder.with_primary_module_kind(fedimint_mint_client::KIND);
+    Ok(client_builder)
+}
+
 pub async fn lnd_create_invoice(amount: Amount) -> anyhow::Result<(Bolt11Invoice, String)> {
     let result = cmd!(LnCli, "addinvoice", "--amt_msat", amount.msats)
         .out_json()
@@ -220,15 +305,61 @@ pub async fn lnd_wait_invoice_payment(r_hash: String) -> anyhow::Result<()> {
     anyhow::bail!("Timeout waiting for invoice to settle: {r_hash}")
 }
 
+/// How the `subscribe_ln_pay` stream of a payment through the gateway ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LnPayOutcome {
+    Success,
+    Refunded,
+    Canceled,
+}
+
+pub const GATEWAY_PAY_INVOICE_SUCCESS: &str = "gateway_pay_invoice_success";
+pub const GATEWAY_PAY_INVOICE_REFUNDED: &str = "gateway_pay_invoice_refunded";
+pub const GATEWAY_PAY_INVOICE_CANCELED: &str = "gateway_pay_invoice_canceled";
+/// Payments that couldn't be started or ended in an unexpected error, e.g.
+/// because the gateway was down
+pub const GATEWAY_PAY_INVOICE_FAILED: &str = "gateway_pay_invoice_failed";
+
 pub async fn gateway_pay_invoice(
     prefix: &str,
     gateway_name: &str,
     client: &ClientHandleArc,
     invoice: Bolt11Invoice,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
     ln_gateway: Option<LightningGateway>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<LnPayOutcome> {
     let m = fedimint_core::time::now();
+    let result = do_gateway_pay_invoice(
+        prefix,
+        gateway_name,
+        client,
+        invoice,
+        event_sender,
+        ln_gateway,
+        m,
+    )
+    .await;
+    if let Err(e) = &result {
+        warn!("{prefix} Invoice payment failed: {e}");
+        event_sender
+            .send(MetricEvent {
+                name: GATEWAY_PAY_INVOICE_FAILED.into(),
+                duration: m.elapsed()?,
+            })
+            .await?;
+    }
+    result
+}
+
+async fn do_gateway_pay_invoice(
+    prefix: &str,
+    gateway_name: &str,
+    client: &ClientHandleArc,
+    invoice: Bolt11Invoice,
+    event_sender: &MetricSender,
+    ln_gateway: Option<LightningGateway>,
+    m: std::time::SystemTime,
+) -> anyhow::Result<LnPayOutcome> {
     let lightning_module = &client.get_first_module::<LightningClientModule>()?;
     let OutgoingLightningPayment {
         payment_type,
@@ -251,15 +382,19 @@ pub async fn gateway_pay_invoice(
             LnPayState::Success { preimage: _ } => {
                 let elapsed: Duration = m.elapsed()?;
                 info!("{prefix} Invoice paid in {elapsed:?}");
-                event_sender.send(MetricEvent {
-                    name: "gateway_pay_invoice_success".into(),
-                    duration: elapsed,
-                })?;
-                event_sender.send(MetricEvent {
-                    name: format!("gateway_{gateway_name}_pay_invoice_success"),
-                    duration: elapsed,
-                })?;
-                break;
+                event_sender
+                    .send(MetricEvent {
+                        name: GATEWAY_PAY_INVOICE_SUCCESS.into(),
+                        duration: elapsed,
+                    })
+                    .await?;
+                event_sender
+                    .send(MetricEvent {
+                        name: format!("gateway_{gateway_name}_pay_invoice_success"),
+                        duration: elapsed,
+                    })
+                    .await?;
+                return Ok(LnPayOutcome::Success);
             }
             LnPayState::Created
             | LnPayState::Funded { block_height: _ }
@@ -267,20 +402,24 @@ pub async fn gateway_pay_invoice(
             LnPayState::Canceled => {
                 let elapsed: Duration = m.elapsed()?;
                 warn!("{prefix} Invoice canceled in {elapsed:?}");
-                event_sender.send(MetricEvent {
-                    name: "gateway_pay_invoice_canceled".into(),
-                    duration: elapsed,
-                })?;
-                break;
+                event_sender
+                    .send(MetricEvent {
+                        name: GATEWAY_PAY_INVOICE_CANCELED.into(),
+                        duration: elapsed,
+                    })
+                    .await?;
+                return Ok(LnPayOutcome::Canceled);
             }
             LnPayState::Refunded { gateway_error } => {
                 let elapsed: Duration = m.elapsed()?;
                 warn!("{prefix} Invoice refunded due to {gateway_error} in {elapsed:?}");
-                event_sender.send(MetricEvent {
-                    name: "gateway_pay_invoice_refunded".into(),
-                    duration: elapsed,
-                })?;
-                break;
+                event_sender
+                    .send(MetricEvent {
+                        name: GATEWAY_PAY_INVOICE_REFUNDED.into(),
+                        duration: elapsed,
+                    })
+                    .await?;
+                return Ok(LnPayOutcome::Refunded);
             }
             LnPayState::WaitingForRefund { error_reason } => {
                 warn!("{prefix} Waiting for refund: {error_reason:?}");
@@ -290,13 +429,37 @@ pub async fn gateway_pay_invoice(
             }
         }
     }
-    Ok(())
+    bail!("Payment updates ended without an outcome")
+}
+
+/// Prints how the payments through the gateway ended, given the number of
+/// events of every metric, to quantify how payments recover from gateway
+/// failures
+pub fn print_ln_pay_outcomes(counts: &BTreeMap<String, usize>) {
+    let count = |name: &str| counts.get(name).copied().unwrap_or_default();
+    let (success, refunded, canceled, failed) = (
+        count(GATEWAY_PAY_INVOICE_SUCCESS),
+        count(GATEWAY_PAY_INVOICE_REFUNDED),
+        count(GATEWAY_PAY_INVOICE_CANCELED),
+        count(GATEWAY_PAY_INVOICE_FAILED),
+    );
+    let total = success + refunded + canceled + failed;
+    if total == 0 {
+        return;
+    }
+    let percent = |n: usize| n as f64 * 100.0 / total as f64;
+    println!(
+        "LN payment outcomes of {total} payments: {success} succeeded ({:.1}%), {refunded} refunded ({:.1}%), {canceled} canceled ({:.1}%), {failed} failed ({:.1}%)",
+        percent(success),
+        percent(refunded),
+        percent(canceled),
+        percent(failed),
+    );
 }
 
 pub async fn cln_create_invoice(amount: Amount) -> anyhow::Result<(Bolt11Invoice, String)> {
-    let now = fedimint_core::time::now();
-    let random_n: u128 = rand::random();
-    let label = format!("label-{now:?}-{random_n}");
+    let random_n: u128 = with_rng(|rng| rng.gen());
+    let label = format!("label-{random_n}");
     let invoice_string = cmd!(ClnLightningCli, "invoice", amount.msats, &label, &label)
         .out_json()
         .await?["bolt11"]
@@ -338,28 +501,23 @@ pub fn parse_gateway_id(s: &str) -> Result<secp256k1::PublicKey, secp256k1::Erro
 pub async fn get_note_summary(client: &ClientHandleArc) -> anyhow::Result<TieredCounts> {
     let mint_client = client.get_first_module::<MintClientModule>()?;
     let summary = mint_client
-        .get_note_counts_by_denomination(
-            &mut client
-                .db()
-                .begin_transaction_nc()
-                .await
-                .to_ref_with_prefix_module_id(1)
-                .0,
-        )
+        .get_note_counts_by_denomination(&mut mint_client.db.begin_transaction_nc().await)
         .await;
     Ok(summary)
 }
 
+/// Creates `quantity` notes of `denomination` in a single transaction, returns
+/// how long the federation took to accept it
 pub async fn remint_denomination(
     client: &ClientHandleArc,
     denomination: Amount,
     quantity: u16,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<Duration> {
     let mint_client = client.get_first_module::<MintClientModule>()?;
     let mut dbtx = client.db().begin_transaction().await;
     let mut module_transaction = dbtx.to_ref_with_prefix_module_id(mint_client.id).0;
     let mut tx = TransactionBuilder::new();
-    let operation_id = OperationId::new_random();
+    let operation_id = with_rng(|rng| OperationId(rng.gen()));
     for _ in 0..quantity {
         let outputs = mint_client
             .create_output(
@@ -375,6 +533,7 @@ pub async fn remint_denomination(
     }
     drop(module_transaction);
     let operation_meta_gen = |_| ();
+    let m = fedimint_core::time::now();
     let txid = client
         .finalize_and_submit_transaction(
             operation_id,
@@ -389,6 +548,7 @@ pub async fn remint_denomination(
         .await_tx_accepted(txid)
         .await
         .map_err(|e| anyhow!("{e}"))?;
+    let accepted = m.elapsed()?;
     dbtx.commit_tx().await;
     for i in 0..quantity {
         let out_point = OutPoint {
@@ -399,5 +559,5 @@ pub async fn remint_denomination(
             .await_output_finalized(operation_id, out_point)
             .await?;
     }
-    Ok(())
+    Ok(accepted)
 }
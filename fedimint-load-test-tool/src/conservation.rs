@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use devimint::cmd;
+use devimint::util::GatewayLndCli;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::config::FederationId;
+use fedimint_core::Amount;
+use tracing::{info, warn};
+
+/// How many times we re-read the balances before giving up, to give
+/// in-flight state machines (change, refunds) some time to settle
+const SETTLE_ATTEMPTS: usize = 30;
+
+/// Snapshot of the funds entering the test, used to verify after the run that
+/// no e-cash was created or destroyed apart from fees.
+///
+/// The invariant checked is:
+///
+/// ```text
+/// coordinator_after + sum(users_after) + gateway_after
+///     <= coordinator_before + faucet_outflow + gateway_before
+/// ```
+///
+/// and the difference between both sides must not exceed `tolerance`.
+pub struct ConservationCheck {
+    federation_id: FederationId,
+    coordinator: ClientHandleArc,
+    users: Vec<ClientHandleArc>,
+    coordinator_before: Amount,
+    gateway_before: Option<Amount>,
+    faucet_outflow: Amount,
+    tolerance: Amount,
+}
+
+impl ConservationCheck {
+    /// Record the balances before any funds enter the test. Must be called
+    /// before reissuing the initial notes or pulling notes from the faucet.
+    pub async fn start(
+        coordinator: &ClientHandleArc,
+        track_gateway: bool,
+        tolerance: Amount,
+    ) -> anyhow::Result<Self> {
+        let federation_id = coordinator.federation_id();
+        let coordinator_before = coordinator.get_balance().await;
+        let gateway_before = if track_gateway {
+            Some(gateway_ecash_balance(federation_id).await?)
+        } else {
+            None
+        };
+        info!(
+            %coordinator_before,
+            ?gateway_before,
+            "Recorded balances for conservation check"
+        );
+        Ok(Self {
+            federation_id,
+            coordinator: coordinator.clone(),
+            users: vec![],
+            coordinator_before,
+            gateway_before,
+            faucet_outflow: Amount::ZERO,
+            tolerance,
+        })
+    }
+
+    /// Account for funds that entered the test from outside (initial notes or
+    /// notes spent by `fedimint-cli`)
+    pub fn add_faucet_outflow(&mut self, amount: Amount) {
+        self.faucet_outflow += amount;
+    }
+
+    /// Register the simulated users whose balances will be summed up
+    pub fn set_users(&mut self, users: Vec<ClientHandleArc>) {
+        self.users = users;
+    }
+
+    /// Verify the invariant, retrying for a while so pending operations can
+    /// finish before we fail
+    pub async fn verify(self) -> anyhow::Result<()> {
+        let expected = self.coordinator_before
+            + self.faucet_outflow
+            + self.gateway_before.unwrap_or(Amount::ZERO);
+
+        let mut actual = Amount::ZERO;
+        for attempt in 1..=SETTLE_ATTEMPTS {
+            actual = self.current_total().await?;
+            if actual <= expected && expected.saturating_sub(actual) <= self.tolerance {
+                info!(
+                    %expected,
+                    %actual,
+                    fees = %expected.saturating_sub(actual),
+                    "Conservation of funds verified"
+                );
+                return Ok(());
+            }
+            warn!(
+                %expected,
+                %actual,
+                attempt,
+                "Funds not conserved yet, waiting for pending operations to settle"
+            );
+            fedimint_core::task::sleep(Duration::from_secs(1)).await;
+        }
+
+        if actual > expected {
+            bail!(
+                "Conservation of funds violated: {} created out of thin air (expected at most {expected}, found {actual})",
+                actual.saturating_sub(expected)
+            );
+        }
+        bail!(
+            "Conservation of funds violated: {} lost, exceeding the fee tolerance of {} (expected {expected}, found {actual})",
+            expected.saturating_sub(actual),
+            self.tolerance
+        );
+    }
+
+    async fn current_total(&self) -> anyhow::Result<Amount> {
+        let mut total = self.coordinator.get_balance().await;
+        for user in &self.users {
+            total += user.get_balance().await;
+        }
+        if self.gateway_before.is_some() {
+            total += gateway_ecash_balance(self.federation_id).await?;
+        }
+        Ok(total)
+    }
+}
+
+async fn gateway_ecash_balance(federation_id: FederationId) -> anyhow::Result<Amount> {
+    let balances = cmd!(GatewayLndCli, "get-balances").out_json().await?;
+    let federation_id = federation_id.to_string();
+    let msats = balances["ecash_balances"]
+        .as_array()
+        .context("Missing ecash_balances field")?
+        .iter()
+        .find(|info| info["federation_id"].as_str() == Some(federation_id.as_str()))
+        .context("Gateway is not connected to the federation")?["ecash_balance_msats"]
+        .as_u64()
+        .context("Missing ecash_balance_msats field")?;
+    Ok(Amount::from_msats(msats))
+}
@@ -0,0 +1,76 @@
+use fedimint_client::ClientHandleArc;
+use fedimint_core::TieredCounts;
+
+use crate::common::get_note_summary;
+
+/// Notes the users hold per denomination at the start of the run, to show
+/// after it whether the load pattern fragments the wallets into small tiers
+pub struct NoteDistribution {
+    users: Vec<ClientHandleArc>,
+    start: TieredCounts,
+}
+
+impl NoteDistribution {
+    pub async fn snapshot(users: Vec<ClientHandleArc>) -> anyhow::Result<Self> {
+        let start = sum_note_counts(&users).await?;
+        Ok(Self { users, start })
+    }
+
+    /// Prints the notes of all users per denomination at the start and the
+    /// end of the run
+    pub async fn print_report(&self) -> anyhow::Result<()> {
+        let end = sum_note_counts(&self.users).await?;
+        let mut denominations = self
+            .start
+            .iter()
+            .chain(end.iter())
+            .map(|(denomination, _)| denomination)
+            .collect::<Vec<_>>();
+        denominations.sort();
+        denominations.dedup();
+
+        println!(
+            "Note distribution of {} users, start -> end:",
+            self.users.len()
+        );
+        println!(
+            "{:>16} {:>8} {:>8} {:>8}",
+            "denomination", "start", "end", "change"
+        );
+        for denomination in denominations {
+            print_row(
+                &denomination.to_string(),
+                self.start.get(denomination),
+                end.get(denomination),
+            );
+        }
+        print_row("notes", self.start.count_items(), end.count_items());
+        print_row("tiers", self.start.count_tiers(), end.count_tiers());
+        println!(
+            "{:>16} {} -> {}",
+            "amount",
+            self.start.total_amount(),
+            end.total_amount()
+        );
+        Ok(())
+    }
+}
+
+async fn sum_note_counts(users: &[ClientHandleArc]) -> anyhow::Result<TieredCounts> {
+    let mut total = TieredCounts::default();
+    for client in users {
+        for (denomination, count) in get_note_summary(client).await?.iter() {
+            total.inc(denomination, count);
+        }
+    }
+    Ok(total)
+}
+
+fn print_row(name: &str, start: usize, end: usize) {
+    let change = if start <= end {
+        format!("+{}", end - start)
+    } else {
+        format!("-{}", start - end)
+    };
+    println!("{name:>16} {start:>8} {end:>8} {change:>8}");
+}
@@ -0,0 +1,239 @@
+use std::iter;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use fedimint_core::runtime::spawn;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::failures::count_failures;
+use crate::live_metrics::LiveMetrics;
+use crate::metrics_channel::{metrics_channel, MetricSender, MetricsChannelSaturation};
+use crate::regression::Baseline;
+use crate::{
+    compare_to_baseline, exit_on_regressions, handle_metrics_summary, run, Command,
+    CoordinatorArgs, EventMetricSummary, MetricEvent, Opts, WorkerArgs,
+};
+
+/// Metric events buffered for the connection to the coordinator, once full
+/// the `--metrics-overflow` policy of the worker applies
+const FORWARD_CAPACITY: usize = 1024;
+
+/// Sent by the coordinator to a worker that connected. All messages are sent
+/// as one line of JSON each.
+#[derive(Debug, Serialize, Deserialize)]
+enum CoordinatorMessage {
+    /// Run the load test of `args`, the command line of the tool without the
+    /// binary name
+    Run { worker: u16, args: Vec<String> },
+}
+
+/// Sent by a worker to the coordinator
+#[derive(Debug, Serialize, Deserialize)]
+enum WorkerMessage {
+    Metric(MetricEvent),
+    /// The run ended, with the error it failed with if any
+    Finished {
+        error: Option<String>,
+    },
+}
+
+/// Has `args.workers` workers run the load test of `args.worker_args` and
+/// summarizes the metrics they stream back as if they were recorded by a
+/// single run with the users of all workers
+pub(crate) async fn run_coordinator(opts: Opts, args: CoordinatorArgs) -> anyhow::Result<()> {
+    if args.workers == 0 {
+        bail!("At least one worker is required");
+    }
+    let worker_opts = parse_worker_opts(&args.worker_args).context("Invalid worker arguments")?;
+    if matches!(
+        worker_opts.command,
+        Command::Coordinator(_) | Command::Worker(_)
+    ) {
+        bail!("Workers can't run a coordinator or another worker");
+    }
+    if matches!(
+        worker_opts.command,
+        Command::ExportProfile(_) | Command::PrepareNotes(_)
+    ) {
+        bail!("Workers can only run load tests");
+    }
+    if worker_opts.baseline.is_some() || worker_opts.write_baseline.is_some() {
+        bail!("Pass --baseline and --write-baseline to the coordinator, which compares the merged metrics");
+    }
+    let baseline = opts.baseline.as_deref().map(Baseline::read).transpose()?;
+    let opts = Opts {
+        users: worker_opts.users.saturating_mul(args.workers),
+        command: worker_opts.command,
+        ..opts
+    };
+
+    let listener = TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", args.listen))?;
+    info!("Waiting for {} workers on {}", args.workers, args.listen);
+    let mut workers = Vec::with_capacity(args.workers.into());
+    for worker in 0..args.workers {
+        let (stream, address) = listener.accept().await?;
+        info!("Worker {worker} connected from {address}");
+        workers.push((worker, address, stream));
+    }
+
+    let (event_sender, event_receiver, saturation) = metrics_channel(
+        opts.metrics_channel_capacity,
+        opts.metrics_overflow,
+        opts.warmup.map(|warmup| warmup.0),
+    );
+    let summary_handle = spawn("handle metrics summary", {
+        let opts = opts.clone();
+        async { handle_metrics_summary(opts, event_receiver, saturation).await }
+    });
+    let live_metrics = match opts.metrics_listen {
+        Some(bind_address) => Some(LiveMetrics::start(bind_address).await?),
+        None => None,
+    };
+    // All workers start at the same time, once every one of them connected
+    let runs = workers.into_iter().map(|(worker, address, stream)| {
+        let args = args.worker_args.clone();
+        let event_sender = event_sender.clone();
+        async move {
+            coordinate_worker(worker, stream, args, event_sender)
+                .await
+                .with_context(|| format!("Worker {worker} at {address}"))
+        }
+    });
+    event_sender.start_warmup();
+    let results = futures::future::join_all(runs).await;
+    event_sender.log_warmup();
+    drop(event_sender);
+    let summaries = summary_handle.await??;
+    if let Some(live_metrics) = live_metrics {
+        live_metrics.stop().await?;
+    }
+    let regressions = compare_to_baseline(&opts, baseline.as_ref(), &summaries)?;
+
+    let failures = results.iter().filter(|r| r.is_err()).count();
+    for r in results {
+        if let Err(e) = r {
+            warn!("{e:#}");
+        }
+    }
+    if failures > 0 {
+        bail!("{failures} of {} workers failed", args.workers);
+    }
+    let failed_operations = count_failures(&summaries);
+    if failed_operations > 0 {
+        bail!("Finished with {failed_operations} failed operations");
+    }
+    exit_on_regressions(&opts, baseline.as_ref(), &regressions);
+    info!("Finished successfully");
+    Ok(())
+}
+
+async fn coordinate_worker(
+    worker: u16,
+    stream: TcpStream,
+    args: Vec<String>,
+    event_sender: MetricSender,
+) -> anyhow::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    send_message(&mut writer, &CoordinatorMessage::Run { worker, args }).await?;
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str(&line).context("Invalid message")? {
+            WorkerMessage::Metric(event) => event_sender.send(event).await?,
+            WorkerMessage::Finished { error: None } => return Ok(()),
+            WorkerMessage::Finished { error: Some(error) } => bail!("Run failed: {error}"),
+        }
+    }
+    bail!("Disconnected before finishing the run")
+}
+
+/// Connects to the coordinator, runs the load test it hands out and streams the
+/// metric events back to it
+pub(crate) async fn run_worker(args: WorkerArgs) -> anyhow::Result<()> {
+    let stream = TcpStream::connect(&args.coordinator)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to connect to the coordinator at {}",
+                args.coordinator
+            )
+        })?;
+    let (reader, mut writer) = stream.into_split();
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .context("Coordinator disconnected before handing out a run")?;
+    let CoordinatorMessage::Run { worker, args } =
+        serde_json::from_str(&line).context("Invalid message")?;
+    let mut opts = parse_worker_opts(&args)?;
+    // Otherwise all workers would generate the same invoice labels
+    opts.seed = opts.seed.map(|seed| seed.wrapping_add(u64::from(worker)));
+    info!("Running as worker {worker}: {}", args.join(" "));
+
+    let (messages, mut messages_rx) = mpsc::channel(FORWARD_CAPACITY);
+    let writer_handle = spawn("forward metrics to coordinator", async move {
+        while let Some(message) = messages_rx.recv().await {
+            send_message(&mut writer, &message).await?;
+        }
+        writer.shutdown().await?;
+        anyhow::Ok(())
+    });
+    let result = run(
+        opts,
+        Some(MetricsForwarder {
+            messages: messages.clone(),
+        }),
+    )
+    .await;
+    let error = result.as_ref().err().map(|e| format!("{e:#}"));
+    // Can only fail if the writer failed, which is reported below
+    let _ = messages.send(WorkerMessage::Finished { error }).await;
+    drop(messages);
+    writer_handle.await??;
+    result
+}
+
+/// Forwards the metric events of a worker's run to the coordinator instead of
+/// summarizing them
+pub(crate) struct MetricsForwarder {
+    messages: mpsc::Sender<WorkerMessage>,
+}
+
+impl MetricsForwarder {
+    pub(crate) async fn forward(
+        self,
+        mut event_receiver: mpsc::Receiver<MetricEvent>,
+        saturation: Arc<MetricsChannelSaturation>,
+    ) -> anyhow::Result<Vec<EventMetricSummary>> {
+        while let Some(event) = event_receiver.recv().await {
+            self.messages
+                .send(WorkerMessage::Metric(event))
+                .await
+                .context("Connection to the coordinator closed")?;
+        }
+        saturation.log();
+        // The coordinator summarizes the metrics of all workers
+        Ok(vec![])
+    }
+}
+
+fn parse_worker_opts(args: &[String]) -> anyhow::Result<Opts> {
+    Ok(Opts::try_parse_from(
+        iter::once("fedimint-load-test-tool".to_owned()).chain(args.iter().cloned()),
+    )?)
+}
+
+async fn send_message(writer: &mut OwnedWriteHalf, message: &impl Serialize) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(message).expect("to be serializable");
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
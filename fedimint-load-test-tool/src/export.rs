@@ -0,0 +1,124 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::EventMetricSummary;
+
+/// File format of [`MetricsExport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// A single object with an `events` and a `summary` array
+    Json,
+    /// One row per event and per summary, told apart by the `record` column
+    Csv,
+}
+
+/// Every metric event of a run, for processing the results with other tools
+/// instead of parsing the printed summary
+pub struct MetricsExport {
+    format: OutputFormat,
+    start: Instant,
+    events: Vec<ExportedEvent>,
+}
+
+#[derive(Serialize)]
+struct ExportedEvent {
+    name: String,
+    /// Milliseconds since the start of the run at which the event completed
+    completed_at_ms: f64,
+    duration_ms: f64,
+}
+
+#[derive(Serialize)]
+struct Export<'a> {
+    events: &'a [ExportedEvent],
+    summary: &'a [EventMetricSummary],
+}
+
+impl MetricsExport {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            start: Instant::now(),
+            events: vec![],
+        }
+    }
+
+    /// Records an event that completed just now
+    pub fn record(&mut self, name: &str, duration: Duration) {
+        self.events.push(ExportedEvent {
+            name: name.to_owned(),
+            completed_at_ms: millis(self.start.elapsed()),
+            duration_ms: millis(duration),
+        });
+    }
+
+    pub(crate) async fn write(
+        &self,
+        path: &Path,
+        summary: &[EventMetricSummary],
+    ) -> anyhow::Result<()> {
+        let output = match self.format {
+            OutputFormat::Json => serde_json::to_string_pretty(&Export {
+                events: &self.events,
+                summary,
+            })
+            .expect("Export is serializable"),
+            OutputFormat::Csv => self.render_csv(summary),
+        };
+        tokio::fs::write(path, output)
+            .await
+            .with_context(|| format!("Failed to write metrics to {path:?}"))
+    }
+
+    fn render_csv(&self, summary: &[EventMetricSummary]) -> String {
+        let mut csv = "record,name,completed_at_ms,duration_ms,users,n,avg_ms,median_ms,p95_ms,p99_ms,max_ms,min_ms\n".to_owned();
+        for event in &self.events {
+            writeln!(
+                csv,
+                "event,{},{},{},,,,,,,,",
+                escape_csv(&event.name),
+                event.completed_at_ms,
+                event.duration_ms
+            )
+            .expect("Writing to a string can't fail");
+        }
+        for metric in summary {
+            writeln!(
+                csv,
+                "summary,{},,,{},{},{},{},{},{},{},{}",
+                escape_csv(&metric.name),
+                metric.users,
+                metric.n,
+                metric.avg_ms,
+                metric.median_ms,
+                optional(metric.p95_ms),
+                optional(metric.p99_ms),
+                metric.max_ms,
+                metric.min_ms,
+            )
+            .expect("Writing to a string can't fail");
+        }
+        csv
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn optional(value: Option<u128>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
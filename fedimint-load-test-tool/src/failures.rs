@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+
+use tracing::warn;
+
+use crate::metrics_channel::MetricSender;
+use crate::{EventMetricSummary, MetricEvent};
+
+/// Prefix of the metric events recording a failed operation, named
+/// `failed:<category>:<operation>`
+const FAILURE_METRIC_PREFIX: &str = "failed:";
+
+/// Cause of a failed operation, guessed from its error message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureCategory {
+    Timeout,
+    InsufficientFunds,
+    GatewayError,
+    ConsensusRejection,
+    Other,
+}
+
+impl FailureCategory {
+    const ALL: [FailureCategory; 5] = [
+        FailureCategory::Timeout,
+        FailureCategory::InsufficientFunds,
+        FailureCategory::GatewayError,
+        FailureCategory::ConsensusRejection,
+        FailureCategory::Other,
+    ];
+
+    pub fn of(error: &anyhow::Error) -> Self {
+        let error = format!("{error:#}").to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|needle| error.contains(needle));
+        if mentions(&["insufficient", "not enough funds"]) {
+            FailureCategory::InsufficientFunds
+        } else if mentions(&["timeout", "timed out", "deadline has elapsed"]) {
+            FailureCategory::Timeout
+        } else if mentions(&["gateway"]) {
+            FailureCategory::GatewayError
+        } else if mentions(&["rejected"]) {
+            FailureCategory::ConsensusRejection
+        } else {
+            FailureCategory::Other
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FailureCategory::Timeout => "timeout",
+            FailureCategory::InsufficientFunds => "insufficient_funds",
+            FailureCategory::GatewayError => "gateway_error",
+            FailureCategory::ConsensusRejection => "consensus_rejection",
+            FailureCategory::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for FailureCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Runs one operation of a user, recording the time it took under the name of
+/// the `operation` if it succeeded, or as a failure of the operation with its
+/// [`FailureCategory`] otherwise.
+///
+/// A failed operation doesn't end the user's task, so a run shows the failure
+/// rate by cause instead of stopping at the first error. Only failing to
+/// record the metric is returned as an error.
+pub async fn record_outcome<T>(
+    prefix: &str,
+    operation: &str,
+    event_sender: &MetricSender,
+    op: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<Option<T>> {
+    let m = fedimint_core::time::now();
+    let result = op.await;
+    let duration = m.elapsed()?;
+    match result {
+        Ok(result) => {
+            event_sender
+                .send(MetricEvent {
+                    name: operation.to_owned(),
+                    duration,
+                })
+                .await?;
+            Ok(Some(result))
+        }
+        Err(e) => {
+            let category = FailureCategory::of(&e);
+            warn!("{prefix} {operation} failed ({category}): {e:#}");
+            event_sender
+                .send(MetricEvent {
+                    name: format!("{FAILURE_METRIC_PREFIX}{category}:{operation}"),
+                    duration,
+                })
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Category and operation of the metric events [`record_outcome`] records
+/// for failed operations, `None` for all other events
+pub fn parse_failure(name: &str) -> Option<(FailureCategory, &str)> {
+    let (category, operation) = name.strip_prefix(FAILURE_METRIC_PREFIX)?.split_once(':')?;
+    let category = FailureCategory::ALL
+        .into_iter()
+        .find(|c| c.name() == category)?;
+    Some((category, operation))
+}
+
+/// Number of operations recorded as failed by [`record_outcome`]
+pub fn count_failures(summaries: &[EventMetricSummary]) -> u64 {
+    summaries
+        .iter()
+        .filter(|summary| summary.name.starts_with(FAILURE_METRIC_PREFIX))
+        .map(|summary| summary.n)
+        .sum()
+}
+
+/// Prints how many of the attempts of every operation failed for which cause,
+/// given the number of events of every metric
+pub fn print_failure_breakdown(counts: &BTreeMap<String, usize>) {
+    let mut failures = BTreeMap::<&str, BTreeMap<FailureCategory, usize>>::new();
+    for (name, count) in counts {
+        let Some((category, operation)) = parse_failure(name) else {
+            continue;
+        };
+        *failures
+            .entry(operation)
+            .or_default()
+            .entry(category)
+            .or_default() += count;
+    }
+    if failures.is_empty() {
+        return;
+    }
+
+    println!(
+        "{:<32} {:>8} {:>8} {:>7}  {}",
+        "operation", "attempts", "failed", "rate", "by cause"
+    );
+    for (operation, categories) in failures {
+        let failed: usize = categories.values().sum();
+        let attempts = failed + counts.get(operation).copied().unwrap_or_default();
+        let rate = failed as f64 * 100.0 / attempts as f64;
+        let by_cause = categories
+            .iter()
+            .map(|(category, count)| format!("{category}={count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{operation:<32} {attempts:>8} {failed:>8} {rate:>6.1}%  {by_cause}");
+    }
+}
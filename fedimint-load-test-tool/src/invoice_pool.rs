@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use fedimint_core::runtime::spawn;
+use fedimint_core::Amount;
+use futures::{stream, StreamExt, TryStreamExt};
+use lightning_invoice::Bolt11Invoice;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{info, warn};
+
+use crate::common::{
+    cln_create_invoice, cln_wait_invoice_payment, lnd_create_invoice, lnd_wait_invoice_payment,
+};
+use crate::metrics_channel::MetricSender;
+use crate::{LnInvoiceGeneration, MetricEvent};
+
+/// How many invoices are created concurrently while filling the pool
+const REFILL_CONCURRENCY: usize = 4;
+
+/// How long to wait before retrying after failing to create an invoice
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Invoice created on one of the lightning nodes, along with what is needed to
+/// await its payment
+pub struct NodeInvoice {
+    pub invoice: Bolt11Invoice,
+    handle: NodeInvoiceHandle,
+    created_at: SystemTime,
+    creation_duration: Duration,
+}
+
+enum NodeInvoiceHandle {
+    Cln { label: String },
+    Lnd { r_hash: String },
+}
+
+impl NodeInvoice {
+    pub async fn create(generation: LnInvoiceGeneration, amount: Amount) -> anyhow::Result<Self> {
+        let m = fedimint_core::time::now();
+        let (invoice, handle) = match generation {
+            LnInvoiceGeneration::ClnLightningCli => {
+                let (invoice, label) = cln_create_invoice(amount).await?;
+                (invoice, NodeInvoiceHandle::Cln { label })
+            }
+            LnInvoiceGeneration::LnCli => {
+                let (invoice, r_hash) = lnd_create_invoice(amount).await?;
+                (invoice, NodeInvoiceHandle::Lnd { r_hash })
+            }
+        };
+
+        Ok(Self {
+            invoice,
+            handle,
+            created_at: m,
+            creation_duration: m.elapsed().unwrap_or_default(),
+        })
+    }
+
+    /// Waits until the node that created the invoice received its payment
+    pub async fn wait_payment(&self) -> anyhow::Result<()> {
+        match &self.handle {
+            NodeInvoiceHandle::Cln { label } => cln_wait_invoice_payment(label).await,
+            NodeInvoiceHandle::Lnd { r_hash } => lnd_wait_invoice_payment(r_hash.clone()).await,
+        }
+    }
+}
+
+/// Invoices created ahead of time on the lightning node receiving the
+/// payments of the load test.
+///
+/// The lightning node takes a while to create an invoice and handles the
+/// requests one after the other, so if every user created its invoice right
+/// before paying it the users would mostly wait for each other on the node and
+/// `gateway_pay_invoice` would be measured under that contention. Instead the
+/// pool is filled before the users start and refilled in the background as
+/// they take invoices out of it. Taking an invoice only blocks if the users
+/// drain the pool faster than it is refilled, how long they waited is reported
+/// as `invoice_pool_take`.
+pub struct InvoicePool {
+    receiver: Mutex<mpsc::Receiver<NodeInvoice>>,
+}
+
+impl InvoicePool {
+    /// Creates `size` invoices and starts refilling the pool whenever one is
+    /// taken out. The refill stops once the pool is dropped.
+    pub async fn start(
+        generation: LnInvoiceGeneration,
+        amount: Amount,
+        size: usize,
+    ) -> anyhow::Result<Arc<Self>> {
+        anyhow::ensure!(size > 0, "The invoice pool can't be empty");
+
+        info!("Creating {size} invoices of {amount} for the invoice pool");
+        let (sender, receiver) = mpsc::channel(size);
+        let invoices = stream::iter(0..size)
+            .map(|_| NodeInvoice::create(generation, amount))
+            .buffer_unordered(REFILL_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+        for invoice in invoices {
+            sender
+                .try_send(invoice)
+                .map_err(|_| anyhow::anyhow!("Invoice pool is full"))?;
+        }
+
+        for i in 0..REFILL_CONCURRENCY {
+            spawn(
+                &format!("invoice pool refill {i}"),
+                refill(sender.clone(), generation, amount),
+            );
+        }
+
+        Ok(Arc::new(Self {
+            receiver: Mutex::new(receiver),
+        }))
+    }
+
+    /// Takes the oldest invoice out of the pool, waiting for a new one if the
+    /// pool is empty
+    pub async fn take(&self, event_sender: &MetricSender) -> anyhow::Result<NodeInvoice> {
+        let m = fedimint_core::time::now();
+        let invoice = self
+            .receiver
+            .lock()
+            .await
+            .recv()
+            .await
+            .context("Invoice pool refill stopped")?;
+        let waited = m.elapsed()?;
+        if invoice.created_at > m {
+            warn!("Invoice pool was empty, waited {waited:?} for an invoice");
+        }
+
+        event_sender
+            .send(MetricEvent {
+                name: "invoice_pool_create".into(),
+                duration: invoice.creation_duration,
+            })
+            .await?;
+        event_sender
+            .send(MetricEvent {
+                name: "invoice_pool_take".into(),
+                duration: waited,
+            })
+            .await?;
+        Ok(invoice)
+    }
+}
+
+/// Creates a new invoice every time the pool has room for one, until the pool
+/// is dropped
+async fn refill(
+    sender: mpsc::Sender<NodeInvoice>,
+    generation: LnInvoiceGeneration,
+    amount: Amount,
+) {
+    // Only create an invoice once it's sure to fit in the pool
+    while let Ok(permit) = sender.reserve().await {
+        match NodeInvoice::create(generation, amount).await {
+            Ok(invoice) => permit.send(invoice),
+            Err(e) => {
+                warn!("Failed to create an invoice for the invoice pool: {e}");
+                drop(permit);
+                fedimint_core::task::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
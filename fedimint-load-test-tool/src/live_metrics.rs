@@ -0,0 +1,66 @@
+//! Live view of a running load test for Prometheus, so long soak tests can be
+//! watched while they run instead of only after the final summary
+
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use fedimint_core::task::TaskGroup;
+use fedimint_metrics::prometheus::{
+    register_histogram_vec_with_registry, register_int_counter_vec_with_registry,
+};
+use fedimint_metrics::{histogram_opts, opts, HistogramVec, IntCounterVec, REGISTRY};
+use tracing::info;
+
+static EVENTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec_with_registry!(
+        opts!(
+            "load_test_events_total",
+            "Metric events recorded by the load test"
+        ),
+        &["event"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+static EVENT_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec_with_registry!(
+        histogram_opts!(
+            "load_test_event_duration_seconds",
+            "Duration of the metric events recorded by the load test",
+            vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]
+        ),
+        &["event"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+/// Records a metric event in the counters and histograms labeled with its name
+pub fn record(name: &str, duration: Duration) {
+    EVENTS_TOTAL.with_label_values(&[name]).inc();
+    EVENT_DURATION_SECONDS
+        .with_label_values(&[name])
+        .observe(duration.as_secs_f64());
+}
+
+/// HTTP server publishing the [`record`]ed events on `/metrics`
+pub struct LiveMetrics {
+    task_group: TaskGroup,
+}
+
+impl LiveMetrics {
+    pub async fn start(bind_address: SocketAddr) -> anyhow::Result<Self> {
+        let task_group = TaskGroup::new();
+        drop(fedimint_metrics::run_api_server(bind_address, task_group.clone()).await?);
+        info!("Serving live metrics on http://{bind_address}/metrics");
+        Ok(Self { task_group })
+    }
+
+    pub async fn stop(self) -> anyhow::Result<()> {
+        self.task_group
+            .shutdown_join_all(Some(Duration::from_secs(10)))
+            .await
+    }
+}
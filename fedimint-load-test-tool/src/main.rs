@@ -6,18 +6,23 @@
 #![allow(clippy::too_many_lines)]
 
 use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::vec;
 
 use anyhow::{bail, Context};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use common::{
     cln_create_invoice, cln_pay_invoice, cln_wait_invoice_payment, gateway_pay_invoice,
-    get_note_summary, parse_gateway_id, reissue_notes,
+    get_note_summary, lnd_pay_invoice, parse_gateway_id, print_ln_pay_outcomes, reissue_notes,
+    LnPayOutcome,
 };
 use devimint::cmd;
+use devimint::mining::MiningController;
 use devimint::util::GatewayLndCli;
 use fedimint_client::ClientHandleArc;
 use fedimint_core::endpoint_constants::SESSION_COUNT_ENDPOINT;
@@ -25,7 +30,7 @@
 use fedimint_core::module::ApiRequestErased;
 use fedimint_core::runtime::spawn;
 use fedimint_core::util::{BoxFuture, SafeUrl};
-use fedimint_core::Amount;
+use fedimint_core::{Amount, Sats};
 use fedimint_ln_client::{LightningClientModule, LnReceiveState};
 use fedimint_ln_common::LightningGateway;
 use fedimint_mint_client::OOBNotes;
@@ -37,10 +42,58 @@
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
+use crate::api_read::{run_api_read_load_test, ApiReadMix};
+use crate::backup_restore::do_backup_restore_user_task;
+use crate::cli_passthrough::{do_cli_passthrough_user_task, print_cli_overhead, CliUser};
 use crate::common::{
-    build_client, do_spend_notes, get_invite_code_cli, remint_denomination, try_get_notes_cli,
+    build_client, do_spend_notes, get_invite_code_cli, remint_denomination, set_remote,
+    set_reuse_db, try_get_notes_cli,
 };
+use crate::conservation::ConservationCheck;
+use crate::denominations::NoteDistribution;
+use crate::distributed::{run_coordinator, run_worker, MetricsForwarder};
+use crate::export::{MetricsExport, OutputFormat};
+use crate::failures::{count_failures, print_failure_breakdown, record_outcome};
+use crate::invoice_pool::{InvoicePool, NodeInvoice};
+use crate::live_metrics::LiveMetrics;
+use crate::metrics_channel::{
+    metrics_channel, MetricSender, MetricsChannelSaturation, MetricsOverflowPolicy,
+};
+use crate::mix::{do_mixed_user_task, Scenario, ScenarioMix};
+use crate::observer::Observers;
+use crate::onchain::do_pegin_pegout_user_task;
+use crate::ramp_up::RampUp;
+use crate::regression::{Baseline, MaxRegression, Regression, REGRESSION_EXIT_CODE};
+use crate::replay::{do_replay_user_task, export_profile, ReplayClock, TrafficProfile};
+use crate::report::{nearest_rank, Report};
+use crate::soak::{print_intermediate_summary, SoakDuration};
+use crate::stale_state::StaleStateCheck;
+use crate::think_time::ThinkTime;
+use crate::tx_size::do_tx_size_user_task;
+pub mod api_read;
+pub mod backup_restore;
+pub mod cli_passthrough;
 pub mod common;
+pub mod conservation;
+pub mod denominations;
+pub mod distributed;
+pub mod export;
+pub mod failures;
+pub mod invoice_pool;
+pub mod live_metrics;
+pub mod metrics_channel;
+pub mod mix;
+pub mod observer;
+pub mod onchain;
+pub mod ramp_up;
+pub mod regression;
+pub mod replay;
+pub mod report;
+pub mod seed;
+pub mod soak;
+pub mod stale_state;
+pub mod think_time;
+pub mod tx_size;
 
 #[derive(Parser, Clone)]
 #[command(version)]
@@ -55,19 +108,158 @@ struct Opts {
     #[arg(long, help = "Output with the metrics results in JSON format")]
     metrics_json_output: Option<PathBuf>,
 
+    #[arg(
+        long,
+        help = "Write every metric event and the summary of each metric to this file, in the --output-format"
+    )]
+    output_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "json",
+        help = "Format of the --output-file"
+    )]
+    output_format: OutputFormat,
+
+    #[arg(
+        long,
+        alias = "report-html",
+        help = "Write a self-contained report with throughput, latency and error rate charts and percentile tables of the run, as Markdown if the path ends in .md and as an HTML page otherwise"
+    )]
+    report: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Serve counters and histograms of the metric events on http://<address>/metrics for Prometheus while the test runs"
+    )]
+    metrics_listen: Option<SocketAddr>,
+
     #[arg(
         long,
         help = "If given, will be used to store and retrieve past metrics for comparison purposes"
     )]
     archive_dir: Option<PathBuf>,
 
+    #[arg(
+        long,
+        help = "Take the coordinator and user clients from this directory of client databases prepared with prepare-notes instead of from the --archive-dir. The users are paid from the prepared notes only, never with notes fetched through fedimint-cli, so short benchmarks don't spend most of their time funding"
+    )]
+    reuse_db: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Mine a block every given number of seconds while the test runs, using the bitcoind of the devimint environment (FM_BITCOIN_RPC_URL). Defaults to every second for the peg-in/peg-out load test"
+    )]
+    auto_mine_every_secs: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Distribution of the pauses between successive operations of a user: fixed:<secs>, exponential:<mean secs> or pareto:<min secs>:<shape>. Overrides --ln-payment-sleep-secs"
+    )]
+    think_time: Option<ThinkTime>,
+
+    #[arg(
+        long,
+        help = "Start the users gradually instead of all at once: <secs> to spread their starts evenly over the given seconds, or <secs>:<steps> to start them in that many equally sized batches"
+    )]
+    ramp_up: Option<RampUp>,
+
+    #[arg(
+        long,
+        help = "Don't fail the run if a simulated user is left with an operation that never reached a terminal state or a note stuck in a pending state"
+    )]
+    skip_stale_state_check: bool,
+
+    #[arg(
+        long,
+        help = "Load test a remote federation, e.g. a staging deployment, with only the --invite-code and --initial-notes given. Never falls back to the fedimint-cli, bitcoind or gateway of a local devimint environment"
+    )]
+    remote: bool,
+
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of observer clients that hold no funds and only subscribe to session updates while the users run, to measure the cost of subscription fan-out on the guardians"
+    )]
+    observers: u16,
+
+    #[arg(
+        long,
+        help = "Seed all randomness of the users (think times, scenario choices, invoice labels) is derived from, so runs of the same build with the same seed generate identical workloads. Invoice labels repeat as well, so use a fresh lightning node for each run"
+    )]
+    seed: Option<u64>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "How many seconds observers wait between polls of the session count"
+    )]
+    observer_poll_secs: u64,
+
+    #[arg(
+        long,
+        default_value = "10000",
+        help = "How many metric events can be queued for the summary before the overflow policy applies"
+    )]
+    metrics_channel_capacity: NonZeroUsize,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "block",
+        help = "Whether users wait for a full metrics queue (adding latency) or drop the event (losing measurements)"
+    )]
+    metrics_overflow: MetricsOverflowPolicy,
+
+    #[arg(
+        long,
+        help = "Leave operations completed in this window after the users start out of the metrics, e.g. 60s, so cold client databases and first connections don't skew the results. The operations are still executed"
+    )]
+    warmup: Option<SoakDuration>,
+
+    #[arg(
+        long,
+        help = "Print a summary of the metrics recorded so far every given number of seconds. Defaults to every 60 seconds for load tests with a --duration"
+    )]
+    stats_interval_secs: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Compare the average, median and p95 latencies of the run against this baseline file and exit with code 2 if any of them regressed beyond --max-regression"
+    )]
+    baseline: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "20%",
+        help = "How much slower than the --baseline a latency may get, as a percentage"
+    )]
+    max_regression: MaxRegression,
+
+    #[arg(
+        long,
+        help = "Write the latencies of the run to this file, for use as a --baseline of later runs"
+    )]
+    write_baseline: Option<PathBuf>,
+
     #[clap(subcommand)]
     command: Command,
 }
 
+impl Opts {
+    /// Directory of the client databases of the run, if they are kept
+    fn db_path(&self) -> Option<PathBuf> {
+        self.reuse_db
+            .clone()
+            .or_else(|| get_db_path(&self.archive_dir))
+    }
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum LnInvoiceGeneration {
     ClnLightningCli,
+    LnCli,
 }
 
 #[derive(Subcommand, Clone)]
@@ -109,6 +301,128 @@ enum Command {
     /// we can keep making the payments in a loop
     #[command()]
     LnCircularLoadTest(LnCircularLoadTestArgs),
+    /// Run a load test where every user spends and reissues notes both through
+    /// the client library and by spawning fedimint-cli, to measure the overhead
+    /// of the CLI
+    #[command()]
+    CliPassthroughLoadTest(CliPassthroughLoadTestArgs),
+    /// Run a load test where users only issue read calls to the guardian API,
+    /// without funds or transactions, to benchmark the API layer
+    #[command()]
+    ApiReadLoadTest(ApiReadLoadTestArgs),
+    /// Run a load test where every user pegs in funds sent by the devimint
+    /// bitcoind and pegs them out again, to exercise the wallet module under
+    /// concurrency
+    #[command()]
+    PegInOutLoadTest(PegInOutLoadTestArgs),
+    /// Run a load test where users submit transactions with a given number of
+    /// note inputs or outputs, to measure how the size of transactions affects
+    /// how long the federation takes to accept them
+    #[command()]
+    TxSizeLoadTest(TxSizeLoadTestArgs),
+    /// Run a load test where users back up clients holding a given number of
+    /// notes to the federation, make a given number of transactions, wipe
+    /// their databases and restore them from the federation, to measure how
+    /// long a restore takes depending on the notes and the history to replay
+    #[command()]
+    BackupRestoreLoadTest(BackupRestoreLoadTestArgs),
+    /// Run the reissue and gateway payment load test against several
+    /// federations in parallel, to benchmark a gateway serving many
+    /// federations. Metrics are tagged with the federation id prefix, e.g.
+    /// `gateway_pay_invoice@1a2b3c4d`
+    #[command()]
+    MultiFederationLoadTest(MultiFederationLoadTestArgs),
+    /// Orchestrate a load test generated by workers on several machines, for
+    /// when a single machine saturates before the federation does. Waits for
+    /// the workers to connect, has all of them run the load test given after
+    /// `--` and merges the metrics they stream back into one summary and
+    /// report. The metric options like --report and --baseline of the
+    /// coordinator apply to the merged metrics.
+    #[command()]
+    Coordinator(CoordinatorArgs),
+    /// Connect to a coordinator, run the load test it hands out and stream the
+    /// metrics back to it
+    #[command()]
+    Worker(WorkerArgs),
+    /// Write the timings, kinds and amounts of the operations in the operation
+    /// logs of client databases to a traffic profile, to replay their temporal
+    /// pattern with `load-test --replay`. The profile has no operation ids,
+    /// notes, invoices or absolute times
+    #[command()]
+    ExportProfile(ExportProfileArgs),
+    /// Mint a pool of notes into a coordinator client database ahead of time
+    /// and join --users user clients, to pass the databases to later load
+    /// tests with --reuse-db
+    #[command()]
+    PrepareNotes(PrepareNotesArgs),
+}
+
+#[derive(Args, Clone)]
+struct CoordinatorArgs {
+    #[arg(long, help = "Address to accept the connections of the workers on")]
+    listen: SocketAddr,
+
+    #[arg(long, help = "Number of workers to wait for before starting the run")]
+    workers: u16,
+
+    #[arg(
+        last = true,
+        required = true,
+        help = "Options and command every worker runs, e.g. -- --users 50 load-test --invite-code ...; --users counts the users of each worker"
+    )]
+    worker_args: Vec<String>,
+}
+
+#[derive(Args, Clone)]
+struct WorkerArgs {
+    #[arg(long, help = "Address of the coordinator as <host>:<port>")]
+    coordinator: String,
+}
+
+#[derive(Args, Clone)]
+struct ExportProfileArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Database of a client to export the operations of, can be given several times. Every database becomes one user of the profile"
+    )]
+    client_db: Vec<PathBuf>,
+
+    #[arg(long, help = "File to write the traffic profile to")]
+    output: PathBuf,
+}
+
+#[derive(Args, Clone)]
+struct PrepareNotesArgs {
+    #[arg(
+        long,
+        help = "Federation invite code. Only required if the databases in --db don't exist yet"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        help = "Notes to fund the pool with. If none and the coordinator holds too little, will call fedimint-cli spend"
+    )]
+    initial_notes: Option<OOBNotes>,
+
+    #[arg(
+        long,
+        help = "Denomination of the notes to prepare, the --note-denomination of the load tests reusing them"
+    )]
+    amount: Amount,
+
+    #[arg(
+        long,
+        help = "How many notes to prepare, at least --users times the --notes-per-user of the load tests reusing them"
+    )]
+    count: u16,
+
+    #[arg(
+        long,
+        help = "Directory to keep the client databases in, to pass to load tests as --reuse-db"
+    )]
+    db: PathBuf,
 }
 
 #[derive(Args, Clone)]
@@ -144,6 +458,13 @@ struct LoadTestArgs {
     )]
     invoices_per_user: u16,
 
+    #[arg(
+        long,
+        default_value = "0",
+        help = "How many invoices to create ahead of time and keep available while users take them, so creating invoices doesn't slow down the payments. If 0, every invoice is created right before paying it. Only applicable if --generate-invoice-with is provided"
+    )]
+    invoice_pool_size: usize,
+
     #[arg(
         long,
         default_value = "0",
@@ -177,28 +498,341 @@ struct LoadTestArgs {
         default_value = "1000"
     )]
     invoice_amount: Amount,
+
+    #[arg(
+        long,
+        help = "After the run, verify that the users, coordinator and gateway balances add up to the funds that entered the test, within --conservation-tolerance. If a gateway is used, its balance is read with gateway-lnd"
+    )]
+    assert_conservation: bool,
+
+    #[arg(
+        long,
+        help = "Maximum amount that may be lost to fees when checking --assert-conservation",
+        default_value = "0"
+    )]
+    conservation_tolerance: Amount,
+
+    #[arg(
+        long,
+        help = "Run a soak test: instead of stopping after --invoices-per-user, users keep spending and reissuing notes and paying invoices until the given duration passed, e.g. 90 (seconds), 30m or 2h"
+    )]
+    duration: Option<SoakDuration>,
+
+    #[arg(
+        long,
+        help = "Instead of reissuing notes and then paying invoices, every user randomly interleaves scenarios with the given relative frequencies as <scenario>=<weight>,... where scenario is one of reissue, ln_pay, spend (to another user) or ln_receive (paid by the node of --generate-invoice-with), e.g. reissue=50,ln_pay=30,spend=20"
+    )]
+    mix: Option<ScenarioMix>,
+
+    #[arg(
+        long,
+        default_value = "10",
+        help = "How many scenarios of the --mix each user runs. Ignored for soak tests, which run scenarios until the --duration passed"
+    )]
+    mix_operations_per_user: u16,
+
+    #[arg(
+        long,
+        help = "Instead of reissuing notes and then paying invoices, replay the operations of a traffic profile written by export-profile at the times they were recorded, relative to the start of the run. The recorded users are spread over the --users in a round robin fashion"
+    )]
+    replay: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "How much faster than recorded to replay the --replay profile, e.g. 2 to replay an hour of traffic in 30 minutes"
+    )]
+    replay_speed: f64,
 }
 
 #[derive(Args, Clone)]
 struct LnCircularLoadTestArgs {
     #[arg(
         long,
-        help = "Federation invite code. If none given, we assume the client already has a config downloaded in DB"
+        help = "Federation invite code. If none given, we assume the client already has a config downloaded in DB"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        help = "Notes for the test. If none and no funds on archive, will call fedimint-cli spend"
+    )]
+    initial_notes: Option<OOBNotes>,
+
+    #[arg(
+        long,
+        default_value = "60",
+        help = "For how many seconds to run the test"
+    )]
+    test_duration_secs: u64,
+
+    #[arg(
+        long,
+        default_value = "0",
+        help = "How many seconds to sleep between LN payments"
+    )]
+    ln_payment_sleep_secs: u64,
+
+    #[arg(
+        long,
+        help = "How many notes to distribute to each user",
+        default_value = "1"
+    )]
+    notes_per_user: u16,
+
+    #[arg(
+        long,
+        help = "Note denomination to use for the test",
+        default_value = "1024"
+    )]
+    note_denomination: Amount,
+
+    #[arg(
+        long,
+        help = "Invoice amount when generating one",
+        default_value = "1000"
+    )]
+    invoice_amount: Amount,
+
+    #[arg(long)]
+    strategy: LnCircularStrategy,
+}
+
+#[derive(Args, Clone)]
+struct CliPassthroughLoadTestArgs {
+    #[arg(
+        long,
+        help = "Federation invite code. If none given, we assume the client already has a config downloaded in DB"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        help = "Notes for the test. If none and no funds on archive, will call fedimint-cli spend"
+    )]
+    initial_notes: Option<OOBNotes>,
+
+    #[arg(
+        long,
+        default_value = "5",
+        help = "How many times each user spends and reissues notes on each path"
+    )]
+    iterations: u16,
+
+    #[arg(
+        long,
+        help = "How many notes to distribute to each user, for each path",
+        default_value = "1"
+    )]
+    notes_per_user: u16,
+
+    #[arg(
+        long,
+        help = "Note denomination to use for the test",
+        default_value = "1024"
+    )]
+    note_denomination: Amount,
+
+    #[arg(long, help = "Amount spent in each iteration", default_value = "512")]
+    spend_amount: Amount,
+}
+
+#[derive(Args, Clone)]
+struct ApiReadLoadTestArgs {
+    #[arg(
+        long,
+        help = "Federation invite code. If none given, we try to get one through fedimint-cli"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        default_value = "60",
+        help = "For how many seconds the users keep issuing read calls"
+    )]
+    test_duration_secs: u64,
+
+    #[arg(
+        long,
+        default_value = "config:1,tx-status:1,balance:1,session-count:1,session:1",
+        help = "Relative frequencies of the read calls as <call>:<weight>,... where call is one of config, tx-status, balance, session-count or session"
+    )]
+    mix: ApiReadMix,
+
+    #[arg(
+        long,
+        default_value = "10",
+        help = "How many of the latest sessions to collect the transactions tx-status looks up from"
+    )]
+    tx_status_sessions: u64,
+}
+
+#[derive(Args, Clone)]
+struct PegInOutLoadTestArgs {
+    #[arg(
+        long,
+        help = "Federation invite code. If none given, we assume the client already has a config downloaded in DB"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "How many times each user pegs in and out"
+    )]
+    iterations: u16,
+
+    #[arg(
+        long,
+        default_value = "100000",
+        help = "Amount sent to the deposit address of each peg-in, in sats unless a denomination is given"
+    )]
+    pegin_amount: Sats,
+
+    #[arg(
+        long,
+        default_value = "50000",
+        help = "Amount withdrawn by each peg-out, in sats unless a denomination is given. Must leave enough of the peg-in for the fees"
+    )]
+    pegout_amount: Sats,
+}
+
+#[derive(Args, Clone)]
+struct TxSizeLoadTestArgs {
+    #[arg(
+        long,
+        help = "Federation invite code. If none given, we assume the client already has a config downloaded in DB"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        help = "Notes for the test. If none and no funds on archive, will call fedimint-cli spend"
+    )]
+    initial_notes: Option<OOBNotes>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "1,10,100",
+        help = "Size classes to test, the number of notes in the inputs or outputs of each transaction"
+    )]
+    sizes: Vec<u16>,
+
+    #[arg(
+        long,
+        default_value = "3",
+        help = "How many transactions of each size class and direction every user submits"
+    )]
+    iterations: u16,
+
+    #[arg(
+        long,
+        default_value = "1024",
+        help = "Denomination of the notes moved by the transactions"
+    )]
+    tx_note_denomination: Amount,
+
+    #[arg(
+        long,
+        help = "How many notes to distribute to each user",
+        default_value = "1"
+    )]
+    notes_per_user: u16,
+
+    #[arg(
+        long,
+        help = "Note denomination to use for funding the users, must cover the largest size class of --tx-note-denomination notes",
+        default_value = "262144"
+    )]
+    note_denomination: Amount,
+}
+
+#[derive(Args, Clone)]
+struct BackupRestoreLoadTestArgs {
+    #[arg(
+        long,
+        help = "Federation invite code. If none given, one is retrieved with fedimint-cli. Required to join the clients that are backed up and restored"
+    )]
+    invite_code: Option<InviteCode>,
+
+    #[arg(
+        long,
+        help = "Notes for the test. If none and no funds on archive, will call fedimint-cli spend"
+    )]
+    initial_notes: Option<OOBNotes>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "1,10,100",
+        help = "Numbers of notes the backed up clients hold"
+    )]
+    note_counts: Vec<u16>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "0,10",
+        help = "Numbers of transactions the clients make between the backup and the restore, which the restore has to replay from the federation's history"
+    )]
+    history: Vec<u16>,
+
+    #[arg(
+        long,
+        default_value = "1024",
+        help = "Denomination of the notes the backed up clients hold"
+    )]
+    restore_note_denomination: Amount,
+
+    #[arg(
+        long,
+        help = "How many notes to distribute to each user",
+        default_value = "1"
+    )]
+    notes_per_user: u16,
+
+    #[arg(
+        long,
+        help = "Note denomination to use for funding the users, must cover the largest of the --note-counts of --restore-note-denomination notes",
+        default_value = "262144"
+    )]
+    note_denomination: Amount,
+}
+
+#[derive(Args, Clone)]
+struct MultiFederationLoadTestArgs {
+    #[arg(
+        long = "invite-code",
+        required = true,
+        help = "Invite code of a federation to test, repeat for every federation. Every federation gets --users users"
+    )]
+    invite_codes: Vec<InviteCode>,
+
+    #[arg(
+        long = "initial-notes",
+        help = "Notes for the test, repeat for every federation, matched to the federations by their federation id. If none for a federation and no funds on archive, will call fedimint-cli spend, which only works for the federation fedimint-cli joined"
+    )]
+    initial_notes: Vec<OOBNotes>,
+
+    #[arg(
+        long,
+        help = "Gateway Id, must be registered with all federations. If none, retrieve one according to --generate-invoice-with"
     )]
-    invite_code: Option<InviteCode>,
+    gateway_id: Option<String>,
 
     #[arg(
         long,
-        help = "Notes for the test. If none and no funds on archive, will call fedimint-cli spend"
+        help = "The method used to generate invoices to be paid through the gateway. If none, no gateway/LN tests will be run. Note that you can't generate an invoice using the same lightning node used by the gateway (i.e self payment is forbidden)"
     )]
-    initial_notes: Option<OOBNotes>,
+    generate_invoice_with: Option<LnInvoiceGeneration>,
 
     #[arg(
         long,
-        default_value = "60",
-        help = "For how many seconds to run the test"
+        default_value = "1",
+        help = "How many invoices will be created for each user. Only applicable if --generate-invoice-with is provided"
     )]
-    test_duration_secs: u64,
+    invoices_per_user: u16,
 
     #[arg(
         long,
@@ -210,7 +844,7 @@ struct LnCircularLoadTestArgs {
     #[arg(
         long,
         help = "How many notes to distribute to each user",
-        default_value = "1"
+        default_value = "2"
     )]
     notes_per_user: u16,
 
@@ -227,9 +861,6 @@ struct LnCircularLoadTestArgs {
         default_value = "1000"
     )]
     invoice_amount: Amount,
-
-    #[arg(long)]
-    strategy: LnCircularStrategy,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -243,19 +874,24 @@ enum LnCircularStrategy {
     PartnerPingPong,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricEvent {
     name: String,
     duration: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct EventMetricSummary {
+pub struct EventMetricSummary {
     name: String,
     users: u64,
     n: u64,
     avg_ms: u128,
     median_ms: u128,
+    /// Missing in metrics archived before percentiles were recorded
+    #[serde(default)]
+    p95_ms: Option<u128>,
+    #[serde(default)]
+    p99_ms: Option<u128>,
     max_ms: u128,
     min_ms: u128,
     timestamp_seconds: u64,
@@ -265,6 +901,8 @@ struct EventMetricSummary {
 struct EventMetricComparison {
     avg_ms_gain: f64,
     median_ms_gain: f64,
+    p95_ms_gain: Option<f64>,
+    p99_ms_gain: Option<f64>,
     max_ms_gain: f64,
     min_ms_gain: f64,
     current: EventMetricSummary,
@@ -280,10 +918,13 @@ fn to_percent(gain: f64) -> String {
                 format!("-{:.2}%", (1.0 - gain) * 100.0)
             }
         }
+        let optional_percent = |gain: Option<f64>| gain.map_or("n/a".to_owned(), to_percent);
         f.write_str(&format!(
-            "avg: {}, median: {}, max: {}, min: {}",
+            "avg: {}, median: {}, p95: {}, p99: {}, max: {}, min: {}",
             to_percent(self.avg_ms_gain),
             to_percent(self.median_ms_gain),
+            optional_percent(self.p95_ms_gain),
+            optional_percent(self.p99_ms_gain),
             to_percent(self.max_ms_gain),
             to_percent(self.min_ms_gain),
         ))
@@ -294,12 +935,94 @@ fn to_percent(gain: f64) -> String {
 async fn main() -> anyhow::Result<()> {
     fedimint_logging::TracingSetup::default().init()?;
     let opts = Opts::parse();
-    let (event_sender, event_receiver) = tokio::sync::mpsc::unbounded_channel();
+    match opts.command.clone() {
+        Command::Coordinator(args) => run_coordinator(opts, args).await,
+        Command::Worker(args) => run_worker(args).await,
+        Command::ExportProfile(args) => export_profile(&args.client_db, &args.output).await,
+        _ => run(opts, None).await,
+    }
+}
+
+/// Runs the load test of `opts`, summarizing the metrics or, in a worker,
+/// forwarding them to the coordinator
+async fn run(opts: Opts, forwarder: Option<MetricsForwarder>) -> anyhow::Result<()> {
+    // Fail before the run if the baseline can't be used
+    let baseline = opts.baseline.as_deref().map(Baseline::read).transpose()?;
+    seed::seed_shared_rng(opts.seed);
+    set_remote(opts.remote);
+    if let Some(reuse_db) = &opts.reuse_db {
+        if !reuse_db.join("coordinator.db").exists() {
+            bail!("--reuse-db {reuse_db:?} has no coordinator database, prepare one with prepare-notes");
+        }
+        if matches!(opts.command, Command::MultiFederationLoadTest(_)) {
+            bail!("--reuse-db holds the clients of a single federation and can't be used for the multi-federation load test");
+        }
+    }
+    set_reuse_db(opts.reuse_db.is_some());
+    if opts.remote {
+        if opts.auto_mine_every_secs.is_some() {
+            bail!("--auto-mine-every-secs mines with the bitcoind of the devimint environment, which a remote federation doesn't use");
+        }
+        if matches!(opts.command, Command::PegInOutLoadTest(_)) {
+            bail!("The peg-in/peg-out load test funds deposits from the bitcoind of the devimint environment and can't run against a remote federation");
+        }
+        if matches!(&opts.command, Command::LoadTest(args) if args.assert_conservation && (args.gateway_id.is_some() || args.generate_invoice_with.is_some()))
+        {
+            bail!("--assert-conservation reads the gateway balance from the devimint environment, which a remote federation doesn't use");
+        }
+    }
+    if let Some(seed) = opts.seed {
+        info!("Deriving all randomness from the seed {seed}");
+    }
+    let (event_sender, event_receiver, saturation) = metrics_channel(
+        opts.metrics_channel_capacity,
+        opts.metrics_overflow,
+        opts.warmup.map(|warmup| warmup.0),
+    );
     let summary_handle = spawn("handle metrics summary", {
         let opts = opts.clone();
-        async { handle_metrics_summary(opts, event_receiver).await }
+        async move {
+            match forwarder {
+                Some(forwarder) => forwarder.forward(event_receiver, saturation).await,
+                None => handle_metrics_summary(opts, event_receiver, saturation).await,
+            }
+        }
     });
+    let live_metrics = match opts.metrics_listen {
+        Some(bind_address) => Some(LiveMetrics::start(bind_address).await?),
+        None => None,
+    };
+    // Peg-ins and peg-outs only complete once their transactions confirm
+    let auto_mine_every_secs =
+        opts.auto_mine_every_secs
+            .or(matches!(opts.command, Command::PegInOutLoadTest(_)).then_some(1));
+    let auto_miner = auto_mine_every_secs
+        .map(|secs| {
+            anyhow::Ok(MiningController::from_env()?.auto_mine_every(Duration::from_secs(secs), 1))
+        })
+        .transpose()?;
+    let mut conservation_check = None;
+    let mut stale_state_check = None;
+    let mut observers = None;
     let futures = match opts.command.clone() {
+        Command::Coordinator(_) | Command::Worker(_) => {
+            bail!("Workers can't run a coordinator or another worker")
+        }
+        Command::ExportProfile(_) => bail!("Exporting a traffic profile isn't a load test"),
+        Command::PrepareNotes(args) => {
+            let invite_code = invite_code_or_fallback(args.invite_code).await;
+            run_prepare_notes(
+                args.db,
+                opts.users,
+                invite_code,
+                args.initial_notes,
+                args.amount,
+                args.count,
+                &event_sender,
+            )
+            .await?;
+            vec![]
+        }
         Command::TestConnect {
             invite_code,
             duration_secs,
@@ -322,6 +1045,14 @@ async fn main() -> anyhow::Result<()> {
             test_download_config(&invite_code, opts.users, &event_sender.clone())
         }
         Command::LoadTest(args) => {
+            let replay = args
+                .replay
+                .as_deref()
+                .map(TrafficProfile::read)
+                .transpose()?;
+            if replay.is_some() && opts.ramp_up.is_some() {
+                bail!("--ramp-up can't be combined with --replay, which starts the operations at the times they were recorded");
+            }
             let invite_code = invite_code_or_fallback(args.invite_code).await;
 
             let gateway_id = if let Some(gateway_id) = args.gateway_id {
@@ -348,45 +1079,256 @@ async fn main() -> anyhow::Result<()> {
             if args.generate_invoice_with.is_none() && invoices.is_empty() {
                 info!("No --generate-invoice-with given no invoices on --invoices-file, not LN/gateway tests will be run");
             }
-            run_load_test(
-                opts.archive_dir,
+            let db_path = opts.db_path();
+            let (futures, check, users_clients) = run_load_test(
+                db_path.clone(),
                 opts.users,
-                invite_code,
+                invite_code.clone(),
                 args.initial_notes,
                 args.generate_invoice_with,
                 args.invoices_per_user,
-                Duration::from_secs(args.ln_payment_sleep_secs),
+                args.invoice_pool_size,
+                think_time_or_fixed(opts.think_time, args.ln_payment_sleep_secs),
                 invoices,
                 gateway_id,
                 args.notes_per_user,
                 args.note_denomination,
                 args.invoice_amount,
+                args.assert_conservation
+                    .then_some(args.conservation_tolerance),
+                args.duration,
+                args.mix,
+                args.mix_operations_per_user,
+                replay,
+                args.replay_speed,
                 event_sender.clone(),
             )
-            .await?
+            .await?;
+            conservation_check = check;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            observers = start_observers(
+                opts.observers,
+                opts.observer_poll_secs,
+                &db_path,
+                &invite_code,
+                &event_sender,
+            )
+            .await?;
+            futures
         }
         Command::LnCircularLoadTest(args) => {
             let invite_code = invite_code_or_fallback(args.invite_code).await;
-            run_ln_circular_load_test(
-                opts.archive_dir,
+            let db_path = opts.db_path();
+            let (futures, users_clients) = run_ln_circular_load_test(
+                db_path.clone(),
                 opts.users,
-                invite_code,
+                invite_code.clone(),
                 args.initial_notes,
                 Duration::from_secs(args.test_duration_secs),
-                Duration::from_secs(args.ln_payment_sleep_secs),
+                think_time_or_fixed(opts.think_time, args.ln_payment_sleep_secs),
                 args.notes_per_user,
                 args.note_denomination,
                 args.invoice_amount,
                 args.strategy,
                 event_sender.clone(),
             )
+            .await?;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            observers = start_observers(
+                opts.observers,
+                opts.observer_poll_secs,
+                &db_path,
+                &invite_code,
+                &event_sender,
+            )
+            .await?;
+            futures
+        }
+        Command::CliPassthroughLoadTest(args) => {
+            let invite_code = invite_code_or_fallback(args.invite_code).await;
+            let db_path = opts.db_path();
+            let (futures, users_clients) = run_cli_passthrough_load_test(
+                db_path.clone(),
+                opts.users,
+                invite_code.clone(),
+                args.initial_notes,
+                args.iterations,
+                think_time_or_fixed(opts.think_time, 0),
+                args.notes_per_user,
+                args.note_denomination,
+                args.spend_amount,
+                event_sender.clone(),
+            )
+            .await?;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            observers = start_observers(
+                opts.observers,
+                opts.observer_poll_secs,
+                &db_path,
+                &invite_code,
+                &event_sender,
+            )
+            .await?;
+            futures
+        }
+        Command::ApiReadLoadTest(args) => {
+            let invite_code = invite_code_or_fallback(args.invite_code)
+                .await
+                .context("An invite code is required to issue read calls")?;
+            run_api_read_load_test(
+                invite_code,
+                opts.users,
+                Duration::from_secs(args.test_duration_secs),
+                args.mix,
+                args.tx_status_sessions,
+                think_time_or_fixed(opts.think_time, 0),
+                &event_sender,
+            )
             .await?
         }
+        Command::TxSizeLoadTest(args) => {
+            let invite_code = invite_code_or_fallback(args.invite_code).await;
+            let db_path = opts.db_path();
+            let (futures, users_clients) = run_tx_size_load_test(
+                db_path.clone(),
+                opts.users,
+                invite_code.clone(),
+                args.initial_notes,
+                args.sizes,
+                args.iterations,
+                args.tx_note_denomination,
+                think_time_or_fixed(opts.think_time, 0),
+                args.notes_per_user,
+                args.note_denomination,
+                event_sender.clone(),
+            )
+            .await?;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            observers = start_observers(
+                opts.observers,
+                opts.observer_poll_secs,
+                &db_path,
+                &invite_code,
+                &event_sender,
+            )
+            .await?;
+            futures
+        }
+        Command::BackupRestoreLoadTest(args) => {
+            let invite_code = invite_code_or_fallback(args.invite_code)
+                .await
+                .context("An invite code is required to join the clients that are restored")?;
+            let db_path = opts.db_path();
+            let (futures, users_clients) = run_backup_restore_load_test(
+                db_path.clone(),
+                opts.users,
+                invite_code.clone(),
+                args.initial_notes,
+                args.note_counts,
+                args.history,
+                args.restore_note_denomination,
+                think_time_or_fixed(opts.think_time, 0),
+                args.notes_per_user,
+                args.note_denomination,
+                event_sender.clone(),
+            )
+            .await?;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            observers = start_observers(
+                opts.observers,
+                opts.observer_poll_secs,
+                &db_path,
+                &Some(invite_code),
+                &event_sender,
+            )
+            .await?;
+            futures
+        }
+        Command::MultiFederationLoadTest(args) => {
+            let gateway_id = if let Some(gateway_id) = args.gateway_id {
+                Some(gateway_id)
+            } else if let Some(generate_invoice_with) = args.generate_invoice_with {
+                Some(get_gateway_id(generate_invoice_with).await?)
+            } else {
+                None
+            };
+            let (futures, users_clients) = run_multi_federation_load_test(
+                opts.archive_dir,
+                opts.users,
+                args.invite_codes,
+                args.initial_notes,
+                args.generate_invoice_with,
+                args.invoices_per_user,
+                think_time_or_fixed(opts.think_time, args.ln_payment_sleep_secs),
+                gateway_id,
+                args.notes_per_user,
+                args.note_denomination,
+                args.invoice_amount,
+                &event_sender,
+            )
+            .await?;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            futures
+        }
+        Command::PegInOutLoadTest(args) => {
+            let invite_code = invite_code_or_fallback(args.invite_code).await;
+            let db_path = opts.db_path();
+            let (futures, users_clients) = run_pegin_pegout_load_test(
+                db_path.clone(),
+                opts.users,
+                invite_code.clone(),
+                args.iterations,
+                args.pegin_amount,
+                args.pegout_amount,
+                think_time_or_fixed(opts.think_time, 0),
+                event_sender.clone(),
+            )
+            .await?;
+            stale_state_check = Some(StaleStateCheck::new(users_clients));
+            observers = start_observers(
+                opts.observers,
+                opts.observer_poll_secs,
+                &db_path,
+                &invite_code,
+                &event_sender,
+            )
+            .await?;
+            futures
+        }
     };
 
+    let futures = seed::scope_user_tasks(opts.seed, futures);
+    let futures = match opts.ramp_up {
+        Some(ramp_up) => {
+            info!(
+                "Ramping up {} user tasks with the schedule {ramp_up}",
+                futures.len()
+            );
+            ramp_up.schedule(futures)
+        }
+        None => futures,
+    };
+    let note_distribution = match &stale_state_check {
+        Some(stale_state_check) => {
+            Some(NoteDistribution::snapshot(stale_state_check.users().to_vec()).await?)
+        }
+        None => None,
+    };
+    event_sender.start_warmup();
     let result = futures::future::join_all(futures).await;
+    if let Some(observers) = observers {
+        observers.stop().await?;
+    }
+    if let Some(auto_miner) = auto_miner {
+        auto_miner.stop();
+    }
+    event_sender.log_warmup();
     drop(event_sender);
-    summary_handle.await??;
+    let summaries = summary_handle.await??;
+    if let Some(live_metrics) = live_metrics {
+        live_metrics.stop().await?;
+    }
+    let regressions = compare_to_baseline(&opts, baseline.as_ref(), &summaries)?;
     let len_failures = result.iter().filter(|r| r.is_err()).count();
     eprintln!("{} results, {len_failures} failures", result.len());
     for r in result {
@@ -394,13 +1336,99 @@ async fn main() -> anyhow::Result<()> {
             warn!("Task failed: {:?}", e);
         }
     }
+    if let Some(note_distribution) = note_distribution {
+        note_distribution.print_report().await?;
+    }
+    if let Some(conservation_check) = conservation_check {
+        conservation_check.verify().await?;
+    }
+    if let Some(stale_state_check) = stale_state_check {
+        if opts.skip_stale_state_check {
+            info!("Skipping the stale state check");
+        } else {
+            stale_state_check.verify().await?;
+        }
+    }
     if len_failures > 0 {
         bail!("Finished with failures");
     }
+    let failed_operations = count_failures(&summaries);
+    if failed_operations > 0 {
+        bail!("Finished with {failed_operations} failed operations");
+    }
+    exit_on_regressions(&opts, baseline.as_ref(), &regressions);
     info!("Finished successfully");
     Ok(())
 }
 
+/// Writes the `--write-baseline` and returns the latencies that regressed
+/// beyond the `--baseline`
+fn compare_to_baseline(
+    opts: &Opts,
+    baseline: Option<&Baseline>,
+    summaries: &[EventMetricSummary],
+) -> anyhow::Result<Vec<Regression>> {
+    if let Some(path) = &opts.write_baseline {
+        Baseline::from_summaries(opts.users, summaries).write(path)?;
+        info!("Wrote baseline to {path:?}");
+    }
+    let regressions = match baseline {
+        Some(baseline) => baseline.compare(opts.users, summaries, opts.max_regression)?,
+        None => vec![],
+    };
+    for regression in &regressions {
+        eprintln!("Regressed beyond {}: {regression}", opts.max_regression);
+    }
+    Ok(regressions)
+}
+
+/// Exits with [`REGRESSION_EXIT_CODE`] if any latency regressed
+fn exit_on_regressions(opts: &Opts, baseline: Option<&Baseline>, regressions: &[Regression]) {
+    if !regressions.is_empty() {
+        warn!(
+            "Finished with {} latencies regressed beyond {} of the baseline",
+            regressions.len(),
+            opts.max_regression
+        );
+        std::process::exit(REGRESSION_EXIT_CODE);
+    }
+    if baseline.is_some() {
+        info!(
+            "No latency regressed beyond {} of the baseline",
+            opts.max_regression
+        );
+    }
+}
+
+async fn start_observers(
+    observers: u16,
+    observer_poll_secs: u64,
+    db_path: &Option<PathBuf>,
+    invite_code: &Option<InviteCode>,
+    event_sender: &MetricSender,
+) -> anyhow::Result<Option<Observers>> {
+    if observers == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        Observers::start(
+            observers,
+            Duration::from_secs(observer_poll_secs),
+            db_path,
+            invite_code,
+            event_sender,
+        )
+        .await?,
+    ))
+}
+
+fn think_time_or_fixed(think_time: Option<ThinkTime>, sleep_secs: u64) -> ThinkTime {
+    let think_time = think_time.unwrap_or(ThinkTime::Fixed(Duration::from_secs(sleep_secs)));
+    info!("Users will think for {think_time} between operations");
+    think_time
+}
+
 async fn invite_code_or_fallback(invite_code: Option<InviteCode>) -> Option<InviteCode> {
     if let Some(invite_code) = invite_code {
         Some(invite_code)
@@ -416,38 +1444,152 @@ async fn invite_code_or_fallback(invite_code: Option<InviteCode>) -> Option<Invi
     }
 }
 
+/// Fails if a scenario `source` runs, as told by `contains`, can't be run with
+/// the other options of the load test
+fn check_scenarios(
+    source: &str,
+    contains: impl Fn(Scenario) -> bool,
+    generate_invoice_with: Option<LnInvoiceGeneration>,
+    conservation_tolerance: Option<Amount>,
+    invoices_from_file: &[Bolt11Invoice],
+) -> anyhow::Result<()> {
+    if contains(Scenario::LnPay) && generate_invoice_with.is_none() {
+        bail!("The ln_pay scenario of {source} requires --generate-invoice-with");
+    }
+    if contains(Scenario::LnReceive) {
+        if generate_invoice_with.is_none() {
+            bail!("The ln_receive scenario of {source} requires --generate-invoice-with");
+        }
+        if conservation_tolerance.is_some() {
+            bail!("The ln_receive scenario of {source} brings in funds from outside the test, which --assert-conservation doesn't account for");
+        }
+    }
+    if !invoices_from_file.is_empty() {
+        bail!("{source} can't be combined with invoices from --invoices-file");
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn run_load_test(
-    archive_dir: Option<PathBuf>,
+    db_path: Option<PathBuf>,
     users: u16,
     invite_code: Option<InviteCode>,
     initial_notes: Option<OOBNotes>,
     generate_invoice_with: Option<LnInvoiceGeneration>,
     generated_invoices_per_user: u16,
-    ln_payment_sleep: Duration,
+    invoice_pool_size: usize,
+    think_time: ThinkTime,
     invoices_from_file: Vec<Bolt11Invoice>,
     gateway_id: Option<String>,
     notes_per_user: u16,
     note_denomination: Amount,
     invoice_amount: Amount,
-    event_sender: mpsc::UnboundedSender<MetricEvent>,
-) -> anyhow::Result<Vec<BoxFuture<'static, anyhow::Result<()>>>> {
-    let db_path = get_db_path(&archive_dir);
+    conservation_tolerance: Option<Amount>,
+    soak_duration: Option<SoakDuration>,
+    mix: Option<ScenarioMix>,
+    mix_operations_per_user: u16,
+    replay: Option<TrafficProfile>,
+    replay_speed: f64,
+    event_sender: MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Option<ConservationCheck>,
+    Vec<ClientHandleArc>,
+)> {
+    if let Some(mix) = &mix {
+        check_scenarios(
+            "--mix",
+            |scenario| mix.contains(scenario),
+            generate_invoice_with,
+            conservation_tolerance,
+            &invoices_from_file,
+        )?;
+    }
+    if let Some(replay) = &replay {
+        if mix.is_some() || soak_duration.is_some() {
+            bail!("--replay can't be combined with --mix or --duration");
+        }
+        if users == 0 {
+            bail!("--replay requires at least one user");
+        }
+        if !(replay_speed.is_finite() && replay_speed > 0.0) {
+            bail!("--replay-speed must be positive, got {replay_speed}");
+        }
+        check_scenarios(
+            "--replay",
+            |scenario| replay.contains(scenario),
+            generate_invoice_with,
+            conservation_tolerance,
+            &invoices_from_file,
+        )?;
+    }
     let (coordinator, invite_code) = get_coordinator_client(&db_path, &invite_code).await?;
     let minimum_notes = notes_per_user * users;
     let minimum_amount_required = note_denomination * u64::from(minimum_notes);
 
-    reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
-    get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
+    let mut conservation_check = if let Some(tolerance) = conservation_tolerance {
+        Some(ConservationCheck::start(&coordinator, gateway_id.is_some(), tolerance).await?)
+    } else {
+        None
+    };
+    let initial_amount = reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
+    let faucet_amount =
+        get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
+    if let Some(conservation_check) = &mut conservation_check {
+        conservation_check.add_faucet_outflow(initial_amount + faucet_amount);
+    }
     print_coordinator_notes(&coordinator).await?;
-    info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user (this may take a while if the number of users/notes is high)");
-    remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    if !holds_notes(&coordinator, note_denomination, minimum_notes).await? {
+        info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user (this may take a while if the number of users/notes is high)");
+        remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    }
     print_coordinator_notes(&coordinator).await?;
 
     let users_clients = get_users_clients(users, db_path, invite_code).await?;
+    if let Some(conservation_check) = &mut conservation_check {
+        conservation_check.set_users(users_clients.clone());
+    }
+    let users_clients_after_run = users_clients.clone();
 
     let mut users_notes =
         get_notes_for_users(users, notes_per_user, coordinator, note_denomination).await?;
+    if let Some(replay) = replay {
+        info!("Users will replay the traffic profile of {replay} at {replay_speed}x speed");
+        let funds_per_user = note_denomination * u64::from(notes_per_user);
+        if funds_per_user < replay.max_amount() {
+            warn!(
+                "Users start with {funds_per_user} each, less than the largest operation of the profile, {}",
+                replay.max_amount()
+            );
+        }
+        let all_users_clients = Arc::new(users_clients.clone());
+        let clock = ReplayClock::default();
+        info!("Starting user tasks");
+        let futures = users_clients
+            .into_iter()
+            .zip(replay.schedules(users))
+            .enumerate()
+            .map(|(u, (client, operations))| {
+                let u = u as u16;
+                let f: BoxFuture<_> = Box::pin(do_replay_user_task(
+                    format!("User {u}:"),
+                    client,
+                    all_users_clients.clone(),
+                    users_notes.remove(&u).unwrap(),
+                    operations,
+                    clock.clone(),
+                    replay_speed,
+                    generate_invoice_with,
+                    event_sender.clone(),
+                    gateway_id.clone(),
+                ));
+                f
+            })
+            .collect::<Vec<_>>();
+        return Ok((futures, conservation_check, users_clients_after_run));
+    }
+
     let mut users_invoices = HashMap::new();
     let mut user = 0;
     // Distribute invoices to users in a round robin fashion
@@ -459,6 +1601,51 @@ async fn run_load_test(
         user = (user + 1) % users;
     }
 
+    let invoice_pool = match generate_invoice_with {
+        Some(generate_invoice_with) if invoice_pool_size > 0 && generated_invoices_per_user > 0 => {
+            Some(
+                InvoicePool::start(generate_invoice_with, invoice_amount, invoice_pool_size)
+                    .await?,
+            )
+        }
+        _ => None,
+    };
+
+    let deadline = soak_duration.map(|soak_duration| {
+        info!("Users will keep running scenarios for {soak_duration}");
+        soak_duration.deadline()
+    });
+    if let Some(mix) = mix {
+        info!("Users will interleave scenarios of the mix {mix}");
+        let all_users_clients = Arc::new(users_clients.clone());
+        info!("Starting user tasks");
+        let futures = users_clients
+            .into_iter()
+            .enumerate()
+            .map(|(u, client)| {
+                let u = u as u16;
+                let f: BoxFuture<_> = Box::pin(do_mixed_user_task(
+                    format!("User {u}:"),
+                    client,
+                    all_users_clients.clone(),
+                    users_notes.remove(&u).unwrap(),
+                    mix.clone(),
+                    mix_operations_per_user,
+                    deadline,
+                    think_time,
+                    note_denomination,
+                    invoice_amount,
+                    generate_invoice_with,
+                    invoice_pool.clone(),
+                    event_sender.clone(),
+                    gateway_id.clone(),
+                ));
+                f
+            })
+            .collect::<Vec<_>>();
+        return Ok((futures, conservation_check, users_clients_after_run));
+    }
+
     info!("Starting user tasks");
     let futures = users_clients
         .into_iter()
@@ -473,10 +1660,13 @@ async fn run_load_test(
                 client,
                 oob_notes,
                 generated_invoices_per_user,
-                ln_payment_sleep,
+                think_time,
                 invoice_amount,
                 invoices,
                 generate_invoice_with,
+                invoice_pool.clone(),
+                note_denomination,
+                deadline,
                 event_sender,
                 gateway_id.clone(),
             ));
@@ -484,7 +1674,188 @@ async fn run_load_test(
         })
         .collect::<Vec<_>>();
 
-    Ok(futures)
+    Ok((futures, conservation_check, users_clients_after_run))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_backup_restore_load_test(
+    db_path: Option<PathBuf>,
+    users: u16,
+    invite_code: InviteCode,
+    initial_notes: Option<OOBNotes>,
+    note_counts: Vec<u16>,
+    history: Vec<u16>,
+    restore_note_denomination: Amount,
+    think_time: ThinkTime,
+    notes_per_user: u16,
+    note_denomination: Amount,
+    event_sender: MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Vec<ClientHandleArc>,
+)> {
+    if note_counts.contains(&0) {
+        bail!("Backed up clients need at least one note, got a note count of 0");
+    }
+    if history.is_empty() {
+        bail!("No numbers of transactions given for --history");
+    }
+    let max_count = note_counts
+        .iter()
+        .copied()
+        .max()
+        .context("No note counts given")?;
+    let required_per_user = restore_note_denomination * u64::from(max_count);
+    if note_denomination * u64::from(notes_per_user) < required_per_user {
+        bail!("Users need at least {required_per_user} to fund clients with {max_count} notes, increase --notes-per-user or --note-denomination");
+    }
+    let (coordinator, _) = get_coordinator_client(&db_path, &Some(invite_code.clone())).await?;
+    let minimum_notes = notes_per_user * users;
+    let minimum_amount_required = note_denomination * u64::from(minimum_notes);
+
+    reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
+    get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
+
+    if !holds_notes(&coordinator, note_denomination, minimum_notes).await? {
+        info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user (this may take a while if the number of users/notes is high)");
+        remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    }
+
+    print_coordinator_notes(&coordinator).await?;
+
+    let users_clients = get_users_clients(users, db_path, Some(invite_code.clone())).await?;
+    let users_clients_after_run = users_clients.clone();
+
+    let mut users_notes =
+        get_notes_for_users(users, notes_per_user, coordinator, note_denomination).await?;
+
+    info!("Starting user tasks");
+    let futures = users_clients
+        .into_iter()
+        .enumerate()
+        .map(|(u, client)| {
+            let u = u as u16;
+            let oob_notes = users_notes.remove(&u).unwrap();
+            let f: BoxFuture<_> = Box::pin(do_backup_restore_user_task(
+                format!("User {u}:"),
+                client,
+                invite_code.clone(),
+                oob_notes,
+                note_counts.clone(),
+                history.clone(),
+                restore_note_denomination,
+                think_time,
+                event_sender.clone(),
+            ));
+            f
+        })
+        .collect::<Vec<_>>();
+
+    Ok((futures, users_clients_after_run))
+}
+
+/// Funds a coordinator client in `db_path` with `count` notes of `amount` and
+/// joins `users` user clients, so load tests with `--reuse-db` can start paying
+/// the users right away
+async fn run_prepare_notes(
+    db_path: PathBuf,
+    users: u16,
+    invite_code: Option<InviteCode>,
+    initial_notes: Option<OOBNotes>,
+    amount: Amount,
+    count: u16,
+    event_sender: &MetricSender,
+) -> anyhow::Result<()> {
+    let db_path = Some(db_path);
+    let (coordinator, invite_code) = get_coordinator_client(&db_path, &invite_code).await?;
+    reissue_initial_notes(initial_notes, &coordinator, event_sender).await?;
+    get_required_notes(&coordinator, amount * u64::from(count), event_sender).await?;
+    if !holds_notes(&coordinator, amount, count).await? {
+        info!("Reminting {count} notes of denomination {amount} (this may take a while if the number of notes is high)");
+        remint_denomination(&coordinator, amount, count).await?;
+    }
+    print_coordinator_notes(&coordinator).await?;
+    info!("Joining {users} user clients");
+    get_users_clients(users, db_path, invite_code).await?;
+    Ok(())
+}
+
+/// Runs [`run_load_test`] against every federation of `invite_codes`, with
+/// the users of each federation in a separate directory of the archive and
+/// their metrics tagged with the federation, see
+/// [`MetricSender::for_federation`]
+#[allow(clippy::too_many_arguments)]
+async fn run_multi_federation_load_test(
+    archive_dir: Option<PathBuf>,
+    users: u16,
+    invite_codes: Vec<InviteCode>,
+    mut initial_notes: Vec<OOBNotes>,
+    generate_invoice_with: Option<LnInvoiceGeneration>,
+    generated_invoices_per_user: u16,
+    think_time: ThinkTime,
+    gateway_id: Option<String>,
+    notes_per_user: u16,
+    note_denomination: Amount,
+    invoice_amount: Amount,
+    event_sender: &MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Vec<ClientHandleArc>,
+)> {
+    let federations = invite_codes
+        .iter()
+        .map(|invite_code| invite_code.federation_id().to_prefix())
+        .collect::<Vec<_>>();
+    if let Some(notes) = initial_notes
+        .iter()
+        .find(|notes| !federations.contains(&notes.federation_id_prefix()))
+    {
+        bail!(
+            "Initial notes of federation {} match none of the invite codes",
+            notes.federation_id_prefix()
+        );
+    }
+
+    let mut futures = vec![];
+    let mut users_clients = vec![];
+    for invite_code in invite_codes {
+        let federation_id = invite_code.federation_id();
+        let federation_notes = initial_notes
+            .iter()
+            .position(|notes| notes.federation_id_prefix() == federation_id.to_prefix())
+            .map(|i| initial_notes.remove(i));
+        info!(
+            "Preparing {users} users of federation {federation_id}, tagging their metrics with @{}",
+            federation_id.to_prefix()
+        );
+        let federation_dir = archive_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("federation_{}", federation_id.to_prefix())));
+        let (federation_futures, _, federation_clients) = run_load_test(
+            get_db_path(&federation_dir),
+            users,
+            Some(invite_code),
+            federation_notes,
+            generate_invoice_with,
+            generated_invoices_per_user,
+            0,
+            think_time,
+            vec![],
+            gateway_id.clone(),
+            notes_per_user,
+            note_denomination,
+            invoice_amount,
+            None,
+            None,
+            None,
+            0,
+            event_sender.for_federation(federation_id),
+        )
+        .await?;
+        futures.extend(federation_futures);
+        users_clients.extend(federation_clients);
+    }
+    Ok((futures, users_clients))
 }
 
 async fn get_notes_for_users(
@@ -540,6 +1911,21 @@ async fn get_user_client(
     Ok((client, invite_code))
 }
 
+/// Whether `coordinator` holds at least `quantity` notes of `denomination`,
+/// e.g. prepared with prepare-notes, so they don't need to be reminted
+async fn holds_notes(
+    coordinator: &ClientHandleArc,
+    denomination: Amount,
+    quantity: u16,
+) -> anyhow::Result<bool> {
+    let held = get_note_summary(coordinator).await?.get(denomination);
+    if held < quantity.into() {
+        return Ok(false);
+    }
+    info!("Coordinator already holds {held} notes of denomination {denomination}, not reminting");
+    Ok(true)
+}
+
 async fn print_coordinator_notes(coordinator: &ClientHandleArc) -> anyhow::Result<()> {
     info!("Note summary:");
     let summary = get_note_summary(coordinator).await?;
@@ -552,16 +1938,18 @@ async fn print_coordinator_notes(coordinator: &ClientHandleArc) -> anyhow::Resul
 async fn get_required_notes(
     coordinator: &ClientHandleArc,
     minimum_amount_required: Amount,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
-) -> anyhow::Result<()> {
+    event_sender: &MetricSender,
+) -> anyhow::Result<Amount> {
     let current_balance = coordinator.get_balance().await;
     if current_balance < minimum_amount_required {
         let diff = minimum_amount_required.saturating_sub(current_balance);
         info!("Current balance {current_balance} on coordinator not enough, trying to get {diff} more through fedimint-cli");
         match try_get_notes_cli(&diff, 5).await {
             Ok(notes) => {
-                info!("Got {} more notes, reissuing them", notes.total_amount());
+                let amount = notes.total_amount();
+                info!("Got {amount} more notes, reissuing them");
                 reissue_notes(coordinator, notes, event_sender).await?;
+                return Ok(amount);
             }
             Err(e) => {
                 info!("Unable to get more notes: '{e}', will try to proceed without them");
@@ -570,20 +1958,21 @@ async fn get_required_notes(
     } else {
         info!("Current balance of {current_balance} already covers the minimum required of {minimum_amount_required}");
     }
-    Ok(())
+    Ok(Amount::ZERO)
 }
 
 async fn reissue_initial_notes(
     initial_notes: Option<OOBNotes>,
     coordinator: &ClientHandleArc,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
-) -> anyhow::Result<()> {
+    event_sender: &MetricSender,
+) -> anyhow::Result<Amount> {
     if let Some(notes) = initial_notes {
         let amount = notes.total_amount();
         info!("Reissuing initial notes, got {amount}");
         reissue_notes(coordinator, notes, event_sender).await?;
+        return Ok(amount);
     }
-    Ok(())
+    Ok(Amount::ZERO)
 }
 
 async fn get_coordinator_client(
@@ -639,11 +2028,14 @@ async fn do_load_test_user_task(
     client: ClientHandleArc,
     oob_notes: Vec<OOBNotes>,
     generated_invoices_per_user: u16,
-    ln_payment_sleep: Duration,
+    think_time: ThinkTime,
     invoice_amount: Amount,
     additional_invoices: Vec<Bolt11Invoice>,
     generate_invoice_with: Option<LnInvoiceGeneration>,
-    event_sender: mpsc::UnboundedSender<MetricEvent>,
+    invoice_pool: Option<Arc<InvoicePool>>,
+    note_denomination: Amount,
+    deadline: Option<Instant>,
+    event_sender: MetricSender,
     gateway_id: Option<String>,
 ) -> anyhow::Result<()> {
     let ln_gateway = get_lightning_gateway(&client, gateway_id).await;
@@ -653,37 +2045,67 @@ async fn do_load_test_user_task(
             .await
             .map_err(|e| anyhow::anyhow!("while reissuing initial {amount}: {e}"))?;
     }
-    let mut generated_invoices_per_user_iterator = (0..generated_invoices_per_user).peekable();
-    while let Some(_) = generated_invoices_per_user_iterator.next() {
-        let total_amount = get_note_summary(&client).await?.total_amount();
-        if invoice_amount > total_amount {
-            warn!("Can't pay invoice, not enough funds: {invoice_amount} > {total_amount}");
-        } else {
-            match generate_invoice_with {
-                Some(LnInvoiceGeneration::ClnLightningCli) => {
-                    let (invoice, label) = cln_create_invoice(invoice_amount).await?;
-                    gateway_pay_invoice(
+    if let Some(deadline) = deadline {
+        // Soak test: cycle through the scenarios until the deadline, the invoices on file
+        // are only paid once afterwards as they can't be paid again
+        while Instant::now() < deadline {
+            record_outcome(
+                &prefix,
+                "spend_and_reissue",
+                &event_sender,
+                spend_and_reissue_notes(&client, &client, note_denomination, &event_sender),
+            )
+            .await?;
+            if let Some(generate_invoice_with) = generate_invoice_with {
+                record_outcome(
+                    &prefix,
+                    "pay_generated_invoice",
+                    &event_sender,
+                    pay_generated_invoice(
                         &prefix,
-                        "LND",
+                        generate_invoice_with,
                         &client,
-                        invoice,
+                        invoice_amount,
+                        invoice_pool.as_deref(),
                         &event_sender,
                         ln_gateway.clone(),
-                    )
-                    .await?;
-                    cln_wait_invoice_payment(&label).await?;
-                }
-                None if additional_invoices.is_empty() => {
+                    ),
+                )
+                .await?;
+            }
+            if Instant::now() < deadline {
+                think_time.sleep().await;
+            }
+        }
+    } else {
+        let mut generated_invoices_per_user_iterator = (0..generated_invoices_per_user).peekable();
+        while let Some(_) = generated_invoices_per_user_iterator.next() {
+            let Some(generate_invoice_with) = generate_invoice_with else {
+                if additional_invoices.is_empty() {
                     debug!("No method given to generate an invoice and no invoices on file, will not test the gateway");
-                    break;
-                }
-                None => {
-                    break;
                 }
+                break;
             };
-            if generated_invoices_per_user_iterator.peek().is_some() {
+            // A failed payment is paced like a successful one
+            let paid = record_outcome(
+                &prefix,
+                "pay_generated_invoice",
+                &event_sender,
+                pay_generated_invoice(
+                    &prefix,
+                    generate_invoice_with,
+                    &client,
+                    invoice_amount,
+                    invoice_pool.as_deref(),
+                    &event_sender,
+                    ln_gateway.clone(),
+                ),
+            )
+            .await?
+            .unwrap_or(true);
+            if paid && generated_invoices_per_user_iterator.peek().is_some() {
                 // Only sleep while there are more invoices to pay
-                fedimint_core::task::sleep(ln_payment_sleep).await;
+                think_time.sleep().await;
             }
         }
     }
@@ -708,28 +2130,149 @@ async fn do_load_test_user_task(
             .await?;
             if additional_invoices.peek().is_some() {
                 // Only sleep while there are more invoices to pay
-                fedimint_core::task::sleep(ln_payment_sleep).await;
+                think_time.sleep().await;
             }
         }
     }
     Ok(())
 }
 
+/// Pays an invoice generated with `generate_invoice_with` through the gateway,
+/// returns `false` if the user didn't have enough funds left to pay it
+async fn pay_generated_invoice(
+    prefix: &str,
+    generate_invoice_with: LnInvoiceGeneration,
+    client: &ClientHandleArc,
+    invoice_amount: Amount,
+    invoice_pool: Option<&InvoicePool>,
+    event_sender: &MetricSender,
+    ln_gateway: Option<LightningGateway>,
+) -> anyhow::Result<bool> {
+    let total_amount = get_note_summary(client).await?.total_amount();
+    if invoice_amount > total_amount {
+        warn!("Can't pay invoice, not enough funds: {invoice_amount} > {total_amount}");
+        return Ok(false);
+    }
+    let invoice = match invoice_pool {
+        Some(invoice_pool) => invoice_pool.take(event_sender).await?,
+        None => NodeInvoice::create(generate_invoice_with, invoice_amount).await?,
+    };
+    let gateway_name = match generate_invoice_with {
+        LnInvoiceGeneration::ClnLightningCli => "LND",
+        LnInvoiceGeneration::LnCli => "unknown",
+    };
+    let outcome = gateway_pay_invoice(
+        prefix,
+        gateway_name,
+        client,
+        invoice.invoice.clone(),
+        event_sender,
+        ln_gateway,
+    )
+    .await?;
+    // A refunded or canceled payment never reaches the node
+    if outcome == LnPayOutcome::Success {
+        invoice.wait_payment().await?;
+    }
+    Ok(true)
+}
+
+/// Creates an invoice through the gateway and has the node of
+/// `generate_invoice_with` pay it, recording the time from starting to create
+/// the invoice until the client claimed the payment
+async fn receive_ln_payment(
+    prefix: &str,
+    generate_invoice_with: LnInvoiceGeneration,
+    client: &ClientHandleArc,
+    invoice_amount: Amount,
+    event_sender: &MetricSender,
+    ln_gateway: Option<LightningGateway>,
+) -> anyhow::Result<()> {
+    let m = fedimint_core::time::now();
+    let (operation_id, invoice) =
+        client_create_invoice(client, invoice_amount, event_sender, ln_gateway).await?;
+    match generate_invoice_with {
+        LnInvoiceGeneration::ClnLightningCli => cln_pay_invoice(invoice).await?,
+        LnInvoiceGeneration::LnCli => lnd_pay_invoice(invoice).await?,
+    }
+    let lightning_module = client.get_first_module::<LightningClientModule>()?;
+    let mut updates = lightning_module
+        .subscribe_ln_receive(operation_id)
+        .await?
+        .into_stream();
+    while let Some(update) = updates.next().await {
+        debug!(%prefix, ?update, "Invoice receive update");
+        match update {
+            LnReceiveState::Claimed => {
+                let elapsed = m.elapsed()?;
+                info!("{prefix} Invoice payment claimed in {elapsed:?}");
+                event_sender
+                    .send(MetricEvent {
+                        name: "ln_receive_claimed".into(),
+                        duration: elapsed,
+                    })
+                    .await?;
+                return Ok(());
+            }
+            LnReceiveState::Canceled { reason } => {
+                let elapsed = m.elapsed()?;
+                warn!("{prefix} Invoice payment receive was canceled: {reason}");
+                event_sender
+                    .send(MetricEvent {
+                        name: "ln_receive_canceled".into(),
+                        duration: elapsed,
+                    })
+                    .await?;
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+    bail!("Receive update stream ended before the payment was claimed")
+}
+
+/// Spends notes worth `amount`, or all remaining funds if less, and reissues
+/// them into the client of `recipient`, which may be the same client
+async fn spend_and_reissue_notes(
+    client: &ClientHandleArc,
+    recipient: &ClientHandleArc,
+    amount: Amount,
+    event_sender: &MetricSender,
+) -> anyhow::Result<()> {
+    let total_amount = get_note_summary(client).await?.total_amount();
+    let amount = amount.min(total_amount);
+    if amount == Amount::ZERO {
+        warn!("Can't spend notes, no funds left");
+        return Ok(());
+    }
+    let m = fedimint_core::time::now();
+    let (_, oob_notes) = do_spend_notes(client, amount).await?;
+    event_sender
+        .send(MetricEvent {
+            name: "spend_notes".into(),
+            duration: m.elapsed()?,
+        })
+        .await?;
+    reissue_notes(recipient, oob_notes, event_sender).await
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn run_ln_circular_load_test(
-    archive_dir: Option<PathBuf>,
+    db_path: Option<PathBuf>,
     users: u16,
     invite_code: Option<InviteCode>,
     initial_notes: Option<OOBNotes>,
     test_duration: Duration,
-    ln_payment_sleep: Duration,
+    think_time: ThinkTime,
     notes_per_user: u16,
     note_denomination: Amount,
     invoice_amount: Amount,
     strategy: LnCircularStrategy,
-    event_sender: mpsc::UnboundedSender<MetricEvent>,
-) -> anyhow::Result<Vec<BoxFuture<'static, anyhow::Result<()>>>> {
-    let db_path = get_db_path(&archive_dir);
+    event_sender: MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Vec<ClientHandleArc>,
+)> {
     let (coordinator, invite_code) = get_coordinator_client(&db_path, &invite_code).await?;
     let minimum_notes = notes_per_user * users;
     let minimum_amount_required = note_denomination * u64::from(minimum_notes);
@@ -737,12 +2280,15 @@ async fn run_ln_circular_load_test(
     reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
     get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
 
-    info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user (this may take a while if the number of users/notes is high)");
-    remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    if !holds_notes(&coordinator, note_denomination, minimum_notes).await? {
+        info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user (this may take a while if the number of users/notes is high)");
+        remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    }
 
     print_coordinator_notes(&coordinator).await?;
 
     let users_clients = get_users_clients(users, db_path, invite_code.clone()).await?;
+    let users_clients_after_run = users_clients.clone();
 
     let mut users_notes =
         get_notes_for_users(users, notes_per_user, coordinator, note_denomination).await?;
@@ -761,7 +2307,7 @@ async fn run_ln_circular_load_test(
                 invite_code.clone(),
                 oob_notes,
                 test_duration,
-                ln_payment_sleep,
+                think_time,
                 invoice_amount,
                 strategy,
                 event_sender,
@@ -770,7 +2316,205 @@ async fn run_ln_circular_load_test(
         })
         .collect::<Vec<_>>();
 
-    Ok(futures)
+    Ok((futures, users_clients_after_run))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_cli_passthrough_load_test(
+    db_path: Option<PathBuf>,
+    users: u16,
+    invite_code: Option<InviteCode>,
+    initial_notes: Option<OOBNotes>,
+    iterations: u16,
+    think_time: ThinkTime,
+    notes_per_user: u16,
+    note_denomination: Amount,
+    spend_amount: Amount,
+    event_sender: MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Vec<ClientHandleArc>,
+)> {
+    let (coordinator, invite_code) = get_coordinator_client(&db_path, &invite_code).await?;
+    // Library and CLI clients of a user are funded separately
+    let funded_clients = users * 2;
+    let minimum_notes = notes_per_user * funded_clients;
+    let minimum_amount_required = note_denomination * u64::from(minimum_notes);
+
+    reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
+    get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
+
+    if !holds_notes(&coordinator, note_denomination, minimum_notes).await? {
+        info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user and path (this may take a while if the number of users/notes is high)");
+        remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    }
+
+    print_coordinator_notes(&coordinator).await?;
+
+    let cli_data_dir = match &db_path {
+        Some(db_path) => db_path.join("cli"),
+        None => std::env::temp_dir().join(format!("fedimint-load-test-cli-{}", std::process::id())),
+    };
+    let mut cli_users = Vec::with_capacity(users.into());
+    for u in 0..users {
+        cli_users.push(CliUser::open(cli_data_dir.join(format!("user_{u}")), &invite_code).await?);
+    }
+    let users_clients = get_users_clients(users, db_path, invite_code).await?;
+    let users_clients_after_run = users_clients.clone();
+
+    let mut users_notes = get_notes_for_users(
+        funded_clients,
+        notes_per_user,
+        coordinator,
+        note_denomination,
+    )
+    .await?;
+
+    info!("Starting user tasks");
+    let futures = users_clients
+        .into_iter()
+        .zip(cli_users)
+        .enumerate()
+        .map(|(u, (client, cli_user))| {
+            let u = u as u16;
+            let lib_notes = users_notes.remove(&u).unwrap();
+            let cli_notes = users_notes.remove(&(users + u)).unwrap();
+            let event_sender = event_sender.clone();
+            let f: BoxFuture<_> = Box::pin(do_cli_passthrough_user_task(
+                format!("User {u}:"),
+                client,
+                cli_user,
+                lib_notes,
+                cli_notes,
+                iterations,
+                spend_amount,
+                think_time,
+                event_sender,
+            ));
+            f
+        })
+        .collect::<Vec<_>>();
+
+    Ok((futures, users_clients_after_run))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_tx_size_load_test(
+    db_path: Option<PathBuf>,
+    users: u16,
+    invite_code: Option<InviteCode>,
+    initial_notes: Option<OOBNotes>,
+    sizes: Vec<u16>,
+    iterations: u16,
+    tx_note_denomination: Amount,
+    think_time: ThinkTime,
+    notes_per_user: u16,
+    note_denomination: Amount,
+    event_sender: MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Vec<ClientHandleArc>,
+)> {
+    if sizes.contains(&0) {
+        bail!("Transactions need at least one note, got a size class of 0");
+    }
+    let max_size = sizes
+        .iter()
+        .copied()
+        .max()
+        .context("No size classes given")?;
+    let required_per_user = tx_note_denomination * u64::from(max_size);
+    if note_denomination * u64::from(notes_per_user) < required_per_user {
+        bail!("Users need at least {required_per_user} to submit transactions of {max_size} notes, increase --notes-per-user or --note-denomination");
+    }
+    let (coordinator, invite_code) = get_coordinator_client(&db_path, &invite_code).await?;
+    let minimum_notes = notes_per_user * users;
+    let minimum_amount_required = note_denomination * u64::from(minimum_notes);
+
+    reissue_initial_notes(initial_notes, &coordinator, &event_sender).await?;
+    get_required_notes(&coordinator, minimum_amount_required, &event_sender).await?;
+
+    if !holds_notes(&coordinator, note_denomination, minimum_notes).await? {
+        info!("Reminting {minimum_notes} notes of denomination {note_denomination} for {users} users, {notes_per_user} notes per user (this may take a while if the number of users/notes is high)");
+        remint_denomination(&coordinator, note_denomination, minimum_notes).await?;
+    }
+
+    print_coordinator_notes(&coordinator).await?;
+
+    let users_clients = get_users_clients(users, db_path, invite_code).await?;
+    let users_clients_after_run = users_clients.clone();
+
+    let mut users_notes =
+        get_notes_for_users(users, notes_per_user, coordinator, note_denomination).await?;
+
+    info!("Starting user tasks");
+    let futures = users_clients
+        .into_iter()
+        .enumerate()
+        .map(|(u, client)| {
+            let u = u as u16;
+            let oob_notes = users_notes.remove(&u).unwrap();
+            let f: BoxFuture<_> = Box::pin(do_tx_size_user_task(
+                format!("User {u}:"),
+                client,
+                oob_notes,
+                sizes.clone(),
+                iterations,
+                tx_note_denomination,
+                think_time,
+                event_sender.clone(),
+            ));
+            f
+        })
+        .collect::<Vec<_>>();
+
+    Ok((futures, users_clients_after_run))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_pegin_pegout_load_test(
+    db_path: Option<PathBuf>,
+    users: u16,
+    invite_code: Option<InviteCode>,
+    iterations: u16,
+    pegin_amount: Sats,
+    pegout_amount: Sats,
+    think_time: ThinkTime,
+    event_sender: MetricSender,
+) -> anyhow::Result<(
+    Vec<BoxFuture<'static, anyhow::Result<()>>>,
+    Vec<ClientHandleArc>,
+)> {
+    if pegout_amount >= pegin_amount {
+        bail!("The peg-out amount {pegout_amount} must be less than the peg-in amount {pegin_amount} to pay for fees");
+    }
+    if let Some(db_path) = &db_path {
+        tokio::fs::create_dir_all(db_path).await?;
+    }
+    let mining = MiningController::from_env()?;
+    let users_clients = get_users_clients(users, db_path, invite_code).await?;
+    let users_clients_after_run = users_clients.clone();
+
+    info!("Starting user tasks");
+    let futures = users_clients
+        .into_iter()
+        .enumerate()
+        .map(|(u, client)| {
+            let f: BoxFuture<_> = Box::pin(do_pegin_pegout_user_task(
+                format!("User {u}:"),
+                client,
+                mining.clone(),
+                iterations,
+                pegin_amount,
+                pegout_amount,
+                think_time,
+                event_sender.clone(),
+            ));
+            f
+        })
+        .collect::<Vec<_>>();
+
+    Ok((futures, users_clients_after_run))
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -780,10 +2524,10 @@ async fn do_ln_circular_test_user_task(
     invite_code: Option<InviteCode>,
     oob_notes: Vec<OOBNotes>,
     test_duration: Duration,
-    ln_payment_sleep: Duration,
+    think_time: ThinkTime,
     invoice_amount: Amount,
     strategy: LnCircularStrategy,
-    event_sender: mpsc::UnboundedSender<MetricEvent>,
+    event_sender: MetricSender,
 ) -> anyhow::Result<()> {
     for oob_note in oob_notes {
         let amount = oob_note.total_amount();
@@ -800,7 +2544,7 @@ async fn do_ln_circular_test_user_task(
     };
     let sleep_a_bit = || async {
         if still_ontime().await {
-            fedimint_core::task::sleep(ln_payment_sleep).await;
+            think_time.sleep().await;
         }
     };
     match strategy {
@@ -845,7 +2589,7 @@ async fn run_two_gateways_strategy(
     prefix: &str,
     invoice_generation: &LnInvoiceGeneration,
     invoice_amount: &Amount,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
     client: &ClientHandleArc,
     ln_gateway: Option<LightningGateway>,
 ) -> Result<(), anyhow::Error> {
@@ -855,10 +2599,12 @@ async fn run_two_gateways_strategy(
             let (invoice, label) = cln_create_invoice(*invoice_amount).await?;
             let elapsed = create_invoice_time.elapsed()?;
             info!("Created invoice using CLN in {elapsed:?}");
-            event_sender.send(MetricEvent {
-                name: GATEWAY_CREATE_INVOICE.into(),
-                duration: elapsed,
-            })?;
+            event_sender
+                .send(MetricEvent {
+                    name: GATEWAY_CREATE_INVOICE.into(),
+                    duration: elapsed,
+                })
+                .await?;
             gateway_pay_invoice(
                 prefix,
                 "LND",
@@ -883,6 +2629,9 @@ async fn run_two_gateways_strategy(
             )
             .await?;
         }
+        LnInvoiceGeneration::LnCli => {
+            bail!("The two gateways strategy only pays invoices created with CLN")
+        }
     };
     Ok(())
 }
@@ -891,7 +2640,7 @@ async fn do_self_payment(
     prefix: &str,
     client: &ClientHandleArc,
     invoice_amount: Amount,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
 ) -> anyhow::Result<()> {
     let (operation_id, invoice) =
         client_create_invoice(client, invoice_amount, event_sender, None).await?;
@@ -918,7 +2667,7 @@ async fn do_partner_ping_pong(
     client: &ClientHandleArc,
     partner: &ClientHandleArc,
     invoice_amount: Amount,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
 ) -> anyhow::Result<()> {
     // Ping (partner creates invoice, client pays)
     let (operation_id, invoice) =
@@ -966,15 +2715,17 @@ async fn wait_invoice_payment(
     gateway_name: &str,
     client: &ClientHandleArc,
     operation_id: fedimint_core::core::OperationId,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
     pay_invoice_time: std::time::SystemTime,
 ) -> anyhow::Result<()> {
     let elapsed = pay_invoice_time.elapsed()?;
     info!("{prefix} Invoice payment receive started using {gateway_name} in {elapsed:?}");
-    event_sender.send(MetricEvent {
-        name: format!("gateway_{gateway_name}_payment_received_started"),
-        duration: elapsed,
-    })?;
+    event_sender
+        .send(MetricEvent {
+            name: format!("gateway_{gateway_name}_payment_received_started"),
+            duration: elapsed,
+        })
+        .await?;
     let lightning_module = client.get_first_module::<LightningClientModule>()?;
     let mut updates = lightning_module
         .subscribe_ln_receive(operation_id)
@@ -986,23 +2737,29 @@ async fn wait_invoice_payment(
             LnReceiveState::Claimed => {
                 let elapsed: Duration = pay_invoice_time.elapsed()?;
                 info!("{prefix} Invoice payment received on {gateway_name} in {elapsed:?}");
-                event_sender.send(MetricEvent {
-                    name: "gateway_payment_received_success".into(),
-                    duration: elapsed,
-                })?;
-                event_sender.send(MetricEvent {
-                    name: format!("gateway_{gateway_name}_payment_received_success"),
-                    duration: elapsed,
-                })?;
+                event_sender
+                    .send(MetricEvent {
+                        name: "gateway_payment_received_success".into(),
+                        duration: elapsed,
+                    })
+                    .await?;
+                event_sender
+                    .send(MetricEvent {
+                        name: format!("gateway_{gateway_name}_payment_received_success"),
+                        duration: elapsed,
+                    })
+                    .await?;
                 break;
             }
             LnReceiveState::Canceled { reason } => {
                 let elapsed: Duration = pay_invoice_time.elapsed()?;
                 info!("{prefix} Invoice payment receive was canceled on {gateway_name}: {reason} in {elapsed:?}");
-                event_sender.send(MetricEvent {
-                    name: "gateway_payment_received_canceled".into(),
-                    duration: elapsed,
-                })?;
+                event_sender
+                    .send(MetricEvent {
+                        name: "gateway_payment_received_canceled".into(),
+                        duration: elapsed,
+                    })
+                    .await?;
                 break;
             }
             _ => {}
@@ -1014,7 +2771,7 @@ async fn wait_invoice_payment(
 async fn client_create_invoice(
     client: &ClientHandleArc,
     invoice_amount: Amount,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
     ln_gateway: Option<LightningGateway>,
 ) -> anyhow::Result<(fedimint_core::core::OperationId, Bolt11Invoice)> {
     let create_invoice_time = fedimint_core::time::now();
@@ -1031,17 +2788,19 @@ async fn client_create_invoice(
         .await?;
     let elapsed = create_invoice_time.elapsed()?;
     info!("Created invoice using gateway in {elapsed:?}");
-    event_sender.send(MetricEvent {
-        name: GATEWAY_CREATE_INVOICE.into(),
-        duration: elapsed,
-    })?;
+    event_sender
+        .send(MetricEvent {
+            name: GATEWAY_CREATE_INVOICE.into(),
+            duration: elapsed,
+        })
+        .await?;
     Ok((operation_id, invoice))
 }
 
 fn test_download_config(
     invite_code: &InviteCode,
     users: u16,
-    event_sender: &mpsc::UnboundedSender<MetricEvent>,
+    event_sender: &MetricSender,
 ) -> Vec<BoxFuture<'static, anyhow::Result<()>>> {
     (0..users)
         .map(|_| {
@@ -1052,10 +2811,12 @@ fn test_download_config(
                 let _ = fedimint_api_client::api::net::Connector::default()
                     .download_from_invite_code(&invite_code)
                     .await?;
-                event_sender.send(MetricEvent {
-                    name: "download_client_config".into(),
-                    duration: m.elapsed()?,
-                })?;
+                event_sender
+                    .send(MetricEvent {
+                        name: "download_client_config".into(),
+                        duration: m.elapsed()?,
+                    })
+                    .await?;
                 Ok(())
             });
             f
@@ -1069,7 +2830,7 @@ async fn test_connect_raw_client(
     duration: Duration,
     timeout: Duration,
     limit_endpoints: Option<usize>,
-    event_sender: mpsc::UnboundedSender<MetricEvent>,
+    event_sender: MetricSender,
 ) -> anyhow::Result<Vec<BoxFuture<'static, anyhow::Result<()>>>> {
     use jsonrpsee_core::client::ClientT;
     use jsonrpsee_ws_client::WsClientBuilder;
@@ -1115,10 +2876,12 @@ async fn test_connect_raw_client(
                     let _epoch: u64 = client
                         .request::<_, _>(SESSION_COUNT_ENDPOINT, vec![ApiRequestErased::default()])
                         .await?;
-                    event_sender.send(MetricEvent {
-                        name: SESSION_COUNT_ENDPOINT.into(),
-                        duration: m.elapsed()?,
-                    })?;
+                    event_sender
+                        .send(MetricEvent {
+                            name: SESSION_COUNT_ENDPOINT.into(),
+                            duration: m.elapsed()?,
+                        })
+                        .await?;
                     fedimint_core::task::sleep(Duration::from_secs(1)).await;
                 }
                 Ok(())
@@ -1141,8 +2904,9 @@ fn url_to_string_with_default_port(url: &SafeUrl) -> String {
 
 async fn handle_metrics_summary(
     opts: Opts,
-    mut event_receiver: mpsc::UnboundedReceiver<MetricEvent>,
-) -> anyhow::Result<()> {
+    mut event_receiver: mpsc::Receiver<MetricEvent>,
+    saturation: Arc<MetricsChannelSaturation>,
+) -> anyhow::Result<Vec<EventMetricSummary>> {
     let timestamp_seconds = fedimint_core::time::duration_since_epoch().as_secs();
     let mut metrics_json_output_files = vec![];
     let mut previous_metrics = vec![];
@@ -1215,29 +2979,78 @@ async fn handle_metrics_summary(
                 .await?,
         ));
     }
+    let mut report = opts.report.as_ref().map(|_| Report::new(opts.users));
+    let mut export = opts
+        .output_file
+        .as_ref()
+        .map(|_| MetricsExport::new(opts.output_format));
+    let stats_interval = opts
+        .stats_interval_secs
+        .or(match &opts.command {
+            Command::LoadTest(args) if args.duration.is_some() => Some(60),
+            _ => None,
+        })
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    let start = Instant::now();
+    let mut stats_ticker = stats_interval
+        .map(|interval| tokio::time::interval_at(tokio::time::Instant::now() + interval, interval));
     let mut results = BTreeMap::new();
-    while let Some(event) = event_receiver.recv().await {
+    loop {
+        let event = tokio::select! {
+            event = event_receiver.recv() => event,
+            _ = async { stats_ticker.as_mut().expect("only polled if set").tick().await }, if stats_ticker.is_some() => {
+                print_intermediate_summary(start.elapsed(), &results);
+                continue;
+            }
+        };
+        let Some(event) = event else {
+            break;
+        };
+        if let Some(report) = &mut report {
+            report.record(&event.name, event.duration);
+        }
+        if let Some(export) = &mut export {
+            export.record(&event.name, event.duration);
+        }
+        if opts.metrics_listen.is_some() {
+            live_metrics::record(&event.name, event.duration);
+        }
         let entry = results.entry(event.name).or_insert_with(Vec::new);
         entry.push(event.duration);
     }
+    saturation.log();
+    if let (Some(report), Some(path)) = (&report, &opts.report) {
+        report.write(path).await?;
+        info!("Wrote report to {path:?}");
+    }
     let mut previous_metrics = previous_metrics
         .into_iter()
         .map(|metric| (metric.name.clone(), metric))
         .collect::<HashMap<_, _>>();
+    let mut averages = BTreeMap::new();
+    let mut counts = BTreeMap::new();
+    let mut summaries = vec![];
     for (k, mut v) in results {
         v.sort();
         let n = v.len();
         let max = v.iter().last().unwrap();
         let min = v.first().unwrap();
         let median = v[n / 2];
+        let p95 = nearest_rank(&v, 95);
+        let p99 = nearest_rank(&v, 99);
         let sum: Duration = v.iter().sum();
         let avg = sum / n as u32;
+        averages.insert(k.clone(), avg);
+        counts.insert(k.clone(), n);
         let metric_summary = EventMetricSummary {
             name: k.clone(),
             users: u64::from(opts.users),
             n: n as u64,
             avg_ms: avg.as_millis(),
             median_ms: median.as_millis(),
+            p95_ms: Some(p95.as_millis()),
+            p99_ms: Some(p99.as_millis()),
             max_ms: max.as_millis(),
             min_ms: min.as_millis(),
             timestamp_seconds,
@@ -1253,6 +3066,14 @@ fn calculate_gain(current: u128, previous: u128) -> f64 {
                         metric_summary.median_ms,
                         previous_metric.median_ms,
                     ),
+                    p95_ms_gain: metric_summary
+                        .p95_ms
+                        .zip(previous_metric.p95_ms)
+                        .map(|(current, previous)| calculate_gain(current, previous)),
+                    p99_ms_gain: metric_summary
+                        .p99_ms
+                        .zip(previous_metric.p99_ms)
+                        .map(|(current, previous)| calculate_gain(current, previous)),
                     max_ms_gain: calculate_gain(metric_summary.max_ms, previous_metric.max_ms),
                     min_ms_gain: calculate_gain(metric_summary.min_ms, previous_metric.min_ms),
                     current: metric_summary.clone(),
@@ -1275,9 +3096,9 @@ fn calculate_gain(current: u128, previous: u128) -> f64 {
             None
         };
         if let Some(comparison) = comparison {
-            println!("{n} {k}: avg {avg:?}, median {median:?}, max {max:?}, min {min:?} (compared to previous: {comparison})");
+            println!("{n} {k}: avg {avg:?}, median {median:?}, p95 {p95:?}, p99 {p99:?}, max {max:?}, min {min:?} (compared to previous: {comparison})");
         } else {
-            println!("{n} {k}: avg {avg:?}, median {median:?}, max {max:?}, min {min:?}");
+            println!("{n} {k}: avg {avg:?}, median {median:?}, p95 {p95:?}, p99 {p99:?}, max {max:?}, min {min:?}");
         }
         let metric_summary_json =
             serde_json::to_string(&metric_summary).expect("to be serializable");
@@ -1287,6 +3108,14 @@ fn calculate_gain(current: u128, previous: u128) -> f64 {
                 .await
                 .expect("to write on file");
         }
+        summaries.push(metric_summary);
+    }
+    print_cli_overhead(&averages);
+    print_ln_pay_outcomes(&counts);
+    print_failure_breakdown(&counts);
+    if let (Some(export), Some(path)) = (&export, &opts.output_file) {
+        export.write(path, &summaries).await?;
+        info!("Wrote metrics to {path:?}");
     }
     for mut output in metrics_json_output_files {
         output.flush().await?;
@@ -1294,7 +3123,7 @@ fn calculate_gain(current: u128, previous: u128) -> f64 {
     if let Some(mut output) = comparison_output {
         output.flush().await?;
     }
-    Ok(())
+    Ok(summaries)
 }
 
 async fn get_gateway_id(generate_invoice_with: LnInvoiceGeneration) -> anyhow::Result<String> {
@@ -1303,6 +3132,10 @@ async fn get_gateway_id(generate_invoice_with: LnInvoiceGeneration) -> anyhow::R
             // If we are paying a lnd invoice, we use the cln node
             cmd!(GatewayLndCli, "info").out_json().await
         }
+        LnInvoiceGeneration::LnCli => {
+            // The LND gateway can't pay invoices of its own node
+            bail!("Paying LND invoices requires a gateway with another node, provide its --gateway-id")
+        }
     }?;
     let gateway_id = gateway_json["gateway_id"]
         .as_str()
@@ -0,0 +1,181 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use fedimint_core::config::{FederationId, FederationIdPrefix};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{info, warn};
+
+use crate::MetricEvent;
+
+/// What a user does when the metrics summary can't keep up and the channel is
+/// full
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum MetricsOverflowPolicy {
+    /// Wait for the summary to catch up, which adds latency to the users
+    Block,
+    /// Drop the event, which keeps the users' pace but loses measurements
+    Drop,
+}
+
+/// How often the metrics channel was full, so a summary that couldn't keep up
+/// shows in the results instead of silently distorting them
+#[derive(Debug, Default)]
+pub struct MetricsChannelSaturation {
+    dropped: AtomicU64,
+    blocked: AtomicU64,
+    blocked_micros: AtomicU64,
+}
+
+impl MetricsChannelSaturation {
+    pub fn log(&self) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        let blocked = self.blocked.load(Ordering::Relaxed);
+        let blocked_ms = self.blocked_micros.load(Ordering::Relaxed) / 1000;
+        if dropped == 0 && blocked == 0 {
+            info!("Metrics channel never saturated");
+        } else {
+            warn!(
+                dropped,
+                blocked, blocked_ms, "Metrics channel saturated, results may be distorted"
+            );
+        }
+    }
+}
+
+/// Operations completing within the `--warmup` after the users started are
+/// still executed but left out of the metrics, as cold client databases and
+/// first connections would otherwise skew the tail latencies
+#[derive(Debug)]
+struct Warmup {
+    duration: Duration,
+    /// Set once the users start, everything sent before is excluded as well
+    until: OnceLock<Instant>,
+    excluded: AtomicU64,
+}
+
+impl Warmup {
+    fn excludes_now(&self) -> bool {
+        match self.until.get() {
+            Some(until) => Instant::now() < *until,
+            None => true,
+        }
+    }
+}
+
+/// Bounded replacement of an unbounded metrics sender, applying the
+/// [`MetricsOverflowPolicy`] once the summary falls behind
+#[derive(Debug, Clone)]
+pub struct MetricSender {
+    sender: mpsc::Sender<MetricEvent>,
+    policy: MetricsOverflowPolicy,
+    saturation: Arc<MetricsChannelSaturation>,
+    federation: Option<FederationIdPrefix>,
+    warmup: Option<Arc<Warmup>>,
+}
+
+impl MetricSender {
+    /// Starts the warm-up window, to be called right before the users start
+    pub fn start_warmup(&self) {
+        if let Some(warmup) = &self.warmup {
+            if warmup.until.set(Instant::now() + warmup.duration).is_ok() {
+                info!(
+                    "Excluding operations completed in the first {}s from the metrics",
+                    warmup.duration.as_secs()
+                );
+            }
+        }
+    }
+
+    /// Logs how many events fell into the warm-up window
+    pub fn log_warmup(&self) {
+        if let Some(warmup) = &self.warmup {
+            info!(
+                excluded = warmup.excluded.load(Ordering::Relaxed),
+                "Metric events excluded during the warm-up"
+            );
+        }
+    }
+
+    /// Sender tagging the name of every event with the prefix of
+    /// `federation_id`, e.g. `reissue_notes@1a2b3c4d`, so the events of
+    /// different federations are summarized separately
+    pub fn for_federation(&self, federation_id: FederationId) -> MetricSender {
+        MetricSender {
+            federation: Some(federation_id.to_prefix()),
+            ..self.clone()
+        }
+    }
+
+    pub async fn send(&self, mut event: MetricEvent) -> anyhow::Result<()> {
+        if let Some(warmup) = &self.warmup {
+            if warmup.excludes_now() {
+                warmup.excluded.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+        if let Some(federation) = self.federation {
+            event.name = format!("{}@{federation}", event.name);
+        }
+        let event = match self.sender.try_send(event) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Full(event)) => event,
+            Err(TrySendError::Closed(_)) => return Err(anyhow!("Metrics summary stopped")),
+        };
+
+        match self.policy {
+            MetricsOverflowPolicy::Drop => {
+                self.saturation.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            MetricsOverflowPolicy::Block => {
+                let m = fedimint_core::time::now();
+                self.sender
+                    .send(event)
+                    .await
+                    .map_err(|_| anyhow!("Metrics summary stopped"))?;
+                self.saturation.blocked.fetch_add(1, Ordering::Relaxed);
+                self.saturation.blocked_micros.fetch_add(
+                    m.elapsed().unwrap_or_default().as_micros() as u64,
+                    Ordering::Relaxed,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Channel of the metric events to the summary, leaving out the events sent
+/// before the `warmup` after [`MetricSender::start_warmup`] is over
+pub fn metrics_channel(
+    capacity: NonZeroUsize,
+    policy: MetricsOverflowPolicy,
+    warmup: Option<Duration>,
+) -> (
+    MetricSender,
+    mpsc::Receiver<MetricEvent>,
+    Arc<MetricsChannelSaturation>,
+) {
+    let (sender, receiver) = mpsc::channel(capacity.get());
+    let saturation = Arc::new(MetricsChannelSaturation::default());
+    (
+        MetricSender {
+            sender,
+            policy,
+            saturation: saturation.clone(),
+            federation: None,
+            warmup: warmup.map(|duration| {
+                Arc::new(Warmup {
+                    duration,
+                    until: OnceLock::new(),
+                    excluded: AtomicU64::new(0),
+                })
+            }),
+        },
+        receiver,
+        saturation,
+    )
+}
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{bail, Context};
+use fedimint_client::ClientHandleArc;
+use fedimint_core::Amount;
+use fedimint_ln_common::LightningGateway;
+use fedimint_mint_client::OOBNotes;
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::common::reissue_notes;
+use crate::failures::record_outcome;
+use crate::invoice_pool::InvoicePool;
+use crate::metrics_channel::MetricSender;
+use crate::seed::with_rng;
+use crate::think_time::ThinkTime;
+use crate::{
+    get_lightning_gateway, pay_generated_invoice, receive_ln_payment, spend_and_reissue_notes,
+    LnInvoiceGeneration,
+};
+
+/// An operation users pick from a [`ScenarioMix`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    /// Spend notes and reissue them into the same client
+    Reissue,
+    /// Pay an invoice through the gateway
+    LnPay,
+    /// Spend notes and reissue them into the client of another user
+    Spend,
+    /// Create an invoice through the gateway and have the node of
+    /// `--generate-invoice-with` pay it
+    LnReceive,
+}
+
+impl Scenario {
+    const ALL: [Scenario; 4] = [
+        Scenario::Reissue,
+        Scenario::LnPay,
+        Scenario::Spend,
+        Scenario::LnReceive,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Scenario::Reissue => "reissue",
+            Scenario::LnPay => "ln_pay",
+            Scenario::Spend => "spend",
+            Scenario::LnReceive => "ln_receive",
+        }
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|scenario| scenario.name() == s)
+            .with_context(|| {
+                format!(
+                    "Unknown scenario {s}, expected one of: {}",
+                    Self::ALL.map(Scenario::name).join(", ")
+                )
+            })
+    }
+}
+
+/// Relative frequencies of the [`Scenario`]s every user randomly interleaves,
+/// parsed from a comma separated list of `<scenario>=<weight>`, e.g.
+/// `reissue=50,ln_pay=30,spend=20`
+#[derive(Debug, Clone)]
+pub struct ScenarioMix {
+    weights: Vec<(Scenario, u32)>,
+}
+
+impl ScenarioMix {
+    pub fn contains(&self, scenario: Scenario) -> bool {
+        self.weights.iter().any(|(s, _)| *s == scenario)
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> Scenario {
+        let index = WeightedIndex::new(self.weights.iter().map(|(_, weight)| *weight))
+            .expect("Weights were validated when parsing");
+        self.weights[index.sample(rng)].0
+    }
+}
+
+impl fmt::Display for ScenarioMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mix = self
+            .weights
+            .iter()
+            .map(|(scenario, weight)| format!("{scenario}={weight}"))
+            .collect::<Vec<_>>();
+        f.write_str(&mix.join(","))
+    }
+}
+
+impl FromStr for ScenarioMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut weights = Vec::<(Scenario, u32)>::new();
+        for entry in s.split(',') {
+            let (scenario, weight) = entry.split_once('=').with_context(|| {
+                format!("Invalid mix entry {entry}, expected <scenario>=<weight>")
+            })?;
+            let scenario = Scenario::from_str(scenario)?;
+            let weight = weight
+                .parse::<u32>()
+                .with_context(|| format!("Invalid weight of {scenario}: {weight}"))?;
+            if weights.iter().any(|(s, _)| *s == scenario) {
+                bail!("{scenario} is given more than once");
+            }
+            if weight > 0 {
+                weights.push((scenario, weight));
+            }
+        }
+        if weights.is_empty() {
+            bail!("At least one scenario needs a positive weight");
+        }
+        Ok(Self { weights })
+    }
+}
+
+/// Runs `operations` scenarios picked from `mix`, or keeps picking them until
+/// `deadline` for a soak test.
+///
+/// Besides the metrics of the operations themselves, the duration of every
+/// scenario is recorded as `mix_<scenario>`. A failed scenario is recorded
+/// with its cause instead of stopping the user.
+#[allow(clippy::too_many_arguments)]
+pub async fn do_mixed_user_task(
+    prefix: String,
+    client: ClientHandleArc,
+    users_clients: Arc<Vec<ClientHandleArc>>,
+    oob_notes: Vec<OOBNotes>,
+    mix: ScenarioMix,
+    operations: u16,
+    deadline: Option<Instant>,
+    think_time: ThinkTime,
+    note_denomination: Amount,
+    invoice_amount: Amount,
+    generate_invoice_with: Option<LnInvoiceGeneration>,
+    invoice_pool: Option<Arc<InvoicePool>>,
+    event_sender: MetricSender,
+    gateway_id: Option<String>,
+) -> anyhow::Result<()> {
+    let ln_gateway = get_lightning_gateway(&client, gateway_id).await;
+    for oob_note in oob_notes {
+        let amount = oob_note.total_amount();
+        reissue_notes(&client, oob_note, &event_sender)
+            .await
+            .map_err(|e| anyhow::anyhow!("while reissuing initial {amount}: {e}"))?;
+    }
+
+    let mut performed = BTreeMap::<Scenario, u64>::new();
+    let mut remaining = operations;
+    loop {
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => break,
+            None if remaining == 0 => break,
+            None => remaining -= 1,
+            Some(_) => {}
+        }
+
+        let scenario = with_rng(|rng| mix.sample(rng));
+        let amount = match scenario {
+            Scenario::Reissue | Scenario::Spend => note_denomination,
+            Scenario::LnPay | Scenario::LnReceive => invoice_amount,
+        };
+        let run_scenario = perform_scenario(
+            &prefix,
+            scenario,
+            &client,
+            &users_clients,
+            amount,
+            generate_invoice_with,
+            invoice_pool.as_deref(),
+            ln_gateway.clone(),
+            &event_sender,
+        );
+        let operation = format!("mix_{scenario}");
+        if record_outcome(&prefix, &operation, &event_sender, run_scenario)
+            .await?
+            .is_some()
+        {
+            *performed.entry(scenario).or_default() += 1;
+        }
+
+        let more = match deadline {
+            Some(deadline) => Instant::now() < deadline,
+            None => remaining > 0,
+        };
+        if more {
+            think_time.sleep().await;
+        }
+    }
+
+    let performed = performed
+        .iter()
+        .map(|(scenario, count)| format!("{scenario}={count}"))
+        .collect::<Vec<_>>();
+    info!("{prefix} Performed {}", performed.join(","));
+    Ok(())
+}
+
+/// Runs a single `scenario` for `client`, moving `amount` in notes or over
+/// lightning. `Spend` sends the notes to a random other user of
+/// `users_clients`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn perform_scenario(
+    prefix: &str,
+    scenario: Scenario,
+    client: &ClientHandleArc,
+    users_clients: &[ClientHandleArc],
+    amount: Amount,
+    generate_invoice_with: Option<LnInvoiceGeneration>,
+    invoice_pool: Option<&InvoicePool>,
+    ln_gateway: Option<LightningGateway>,
+    event_sender: &MetricSender,
+) -> anyhow::Result<()> {
+    match scenario {
+        Scenario::Reissue => {
+            spend_and_reissue_notes(client, client, amount, event_sender).await?;
+        }
+        Scenario::LnPay => {
+            let generate_invoice_with = generate_invoice_with
+                .context("The ln_pay scenario requires --generate-invoice-with")?;
+            pay_generated_invoice(
+                prefix,
+                generate_invoice_with,
+                client,
+                amount,
+                invoice_pool,
+                event_sender,
+                ln_gateway,
+            )
+            .await?;
+        }
+        Scenario::Spend => {
+            // Falls back to reissuing into the same client if it's the only user
+            let recipient = with_rng(|rng| {
+                users_clients
+                    .iter()
+                    .filter(|other| !Arc::ptr_eq(other, client))
+                    .choose(rng)
+                    .unwrap_or(client)
+                    .clone()
+            });
+            spend_and_reissue_notes(client, &recipient, amount, event_sender).await?;
+        }
+        Scenario::LnReceive => {
+            let generate_invoice_with = generate_invoice_with
+                .context("The ln_receive scenario requires --generate-invoice-with")?;
+            receive_ln_payment(
+                prefix,
+                generate_invoice_with,
+                client,
+                amount,
+                event_sender,
+                ln_gateway,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
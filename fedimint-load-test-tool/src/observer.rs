@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use fedimint_api_client::api::IGlobalFederationApi as _;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::invite_code::InviteCode;
+use fedimint_core::task::TaskGroup;
+use tracing::{info, warn};
+
+use crate::common::build_client;
+use crate::metrics_channel::MetricSender;
+use crate::MetricEvent;
+
+/// How long to wait before retrying after a failed request
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Clients that hold no funds and only follow the progress of the federation
+/// while the users run their workload.
+///
+/// Every observer keeps a long-polling request for the next session open and
+/// polls the session count like an idle wallet would. Comparing runs with and
+/// without observers shows how the fan-out of such subscriptions affects the
+/// guardians, separately from the cost of processing transactions.
+pub struct Observers {
+    task_group: TaskGroup,
+}
+
+impl Observers {
+    pub async fn start(
+        n: u16,
+        poll_interval: Duration,
+        db_path: &Option<PathBuf>,
+        invite_code: &Option<InviteCode>,
+        event_sender: &MetricSender,
+    ) -> anyhow::Result<Self> {
+        let task_group = TaskGroup::new();
+        for o in 0..n {
+            let observer_db = db_path
+                .as_ref()
+                .map(|db_path| db_path.join(format!("observer_{o}.db")));
+            let observer_invite_code = if observer_db.as_ref().map_or(false, |db| db.exists()) {
+                None
+            } else {
+                invite_code.clone()
+            };
+            let (client, _) = build_client(observer_invite_code, observer_db.as_ref()).await?;
+
+            task_group.spawn_cancellable(
+                format!("observer {o} await sessions"),
+                await_sessions(client.clone(), event_sender.clone()),
+            );
+            task_group.spawn_cancellable(
+                format!("observer {o} poll session count"),
+                poll_session_count(client, poll_interval, event_sender.clone()),
+            );
+        }
+        info!("Started {n} observers");
+
+        Ok(Self { task_group })
+    }
+
+    /// Stops the observers, they must be stopped before the metrics can be
+    /// summarized
+    pub async fn stop(self) -> anyhow::Result<()> {
+        self.task_group
+            .shutdown_join_all(Some(Duration::from_secs(10)))
+            .await
+    }
+}
+
+/// Awaits every new session, measuring how long the outcome of a session took
+/// to arrive once we started waiting for it
+async fn await_sessions(client: ClientHandleArc, event_sender: MetricSender) {
+    let mut next_session = loop {
+        match client.api().session_count().await {
+            Ok(session_count) => break session_count,
+            Err(e) => {
+                warn!("Observer failed to fetch the session count: {e}");
+                fedimint_core::task::sleep(RETRY_DELAY).await;
+            }
+        }
+    };
+
+    loop {
+        let m = fedimint_core::time::now();
+        match client
+            .api()
+            .await_block(next_session, client.decoders())
+            .await
+        {
+            Ok(_) => {
+                next_session += 1;
+                let _ = event_sender
+                    .send(MetricEvent {
+                        name: "observer_await_session".into(),
+                        duration: m.elapsed().unwrap_or_default(),
+                    })
+                    .await;
+            }
+            Err(e) => {
+                warn!("Observer failed to await session {next_session}: {e}");
+                fedimint_core::task::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn poll_session_count(
+    client: ClientHandleArc,
+    poll_interval: Duration,
+    event_sender: MetricSender,
+) {
+    loop {
+        let m = fedimint_core::time::now();
+        match client.api().session_count().await {
+            Ok(_) => {
+                let _ = event_sender
+                    .send(MetricEvent {
+                        name: "observer_session_count".into(),
+                        duration: m.elapsed().unwrap_or_default(),
+                    })
+                    .await;
+            }
+            Err(e) => warn!("Observer failed to fetch the session count: {e}"),
+        }
+        fedimint_core::task::sleep(poll_interval).await;
+    }
+}
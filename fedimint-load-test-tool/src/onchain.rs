@@ -0,0 +1,90 @@
+use anyhow::{bail, Context};
+use devimint::mining::MiningController;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::Sats;
+use fedimint_wallet_client::{DepositStateV2, WalletClientModule, WithdrawState};
+use futures::StreamExt;
+use tracing::info;
+
+use crate::metrics_channel::MetricSender;
+use crate::think_time::ThinkTime;
+use crate::MetricEvent;
+
+/// Pegs in `pegin_amount` and pegs out `pegout_amount` `iterations` times.
+///
+/// The deposit address is funded from the wallet of the devimint bitcoind,
+/// the peg-out goes back to a new address of it. Confirmations rely on blocks
+/// being mined in the background, see `--auto-mine-every-secs`.
+#[allow(clippy::too_many_arguments)]
+pub async fn do_pegin_pegout_user_task(
+    prefix: String,
+    client: ClientHandleArc,
+    mining: MiningController,
+    iterations: u16,
+    pegin_amount: Sats,
+    pegout_amount: Sats,
+    think_time: ThinkTime,
+    event_sender: MetricSender,
+) -> anyhow::Result<()> {
+    let wallet = client.get_first_module::<WalletClientModule>()?;
+    for i in 0..iterations {
+        let m = fedimint_core::time::now();
+        let (operation_id, address, _) = wallet.allocate_deposit_address_expert_only(()).await?;
+        let txid = mining
+            .send_to_address(&address, pegin_amount.into())
+            .await?;
+        info!("{prefix} Funded deposit address {address} in {txid}");
+        send_metric(&event_sender, "pegin_fund", m).await?;
+
+        let m = fedimint_core::time::now();
+        let mut updates = wallet.subscribe_deposit(operation_id).await?.into_stream();
+        loop {
+            match updates.next().await.context("Peg-in updates ended")? {
+                DepositStateV2::Claimed { .. } => break,
+                DepositStateV2::Failed(e) => bail!("Peg-in failed: {e}"),
+                _ => {}
+            }
+        }
+        send_metric(&event_sender, "pegin_claim", m).await?;
+
+        let m = fedimint_core::time::now();
+        let address = mining.new_address().await?;
+        let fees = wallet
+            .get_withdraw_fees(&address, pegout_amount.into())
+            .await?;
+        let operation_id = wallet
+            .withdraw(&address, pegout_amount.into(), fees, ())
+            .await?;
+        let mut updates = wallet
+            .subscribe_withdraw_updates(operation_id)
+            .await?
+            .into_stream();
+        let txid = loop {
+            match updates.next().await.context("Peg-out updates ended")? {
+                WithdrawState::Succeeded(txid) => break txid,
+                WithdrawState::Failed(e) => bail!("Peg-out failed: {e}"),
+                WithdrawState::Created => {}
+            }
+        };
+        info!("{prefix} Pegged out to {address} in {txid}");
+        send_metric(&event_sender, "pegout", m).await?;
+
+        if i + 1 < iterations {
+            think_time.sleep().await;
+        }
+    }
+    Ok(())
+}
+
+async fn send_metric(
+    event_sender: &MetricSender,
+    name: &str,
+    started: std::time::SystemTime,
+) -> anyhow::Result<()> {
+    event_sender
+        .send(MetricEvent {
+            name: name.into(),
+            duration: started.elapsed()?,
+        })
+        .await
+}
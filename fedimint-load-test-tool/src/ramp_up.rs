@@ -0,0 +1,91 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use fedimint_core::util::BoxFuture;
+
+/// Schedule by which the simulated users start.
+///
+/// Starting all users at once makes them hit the federation with the same
+/// requests at the same time, which fails in ways real traffic never would.
+/// Spreading their starts over a ramp-up period avoids that thundering herd.
+///
+/// Parsed from `<secs>` to start the users one after another, evenly spread
+/// over the period, or `<secs>:<steps>` to start them in `steps` equally sized
+/// batches. Either way the first users start right away and the last ones
+/// once the period is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RampUp {
+    duration: Duration,
+    steps: Option<usize>,
+}
+
+impl RampUp {
+    /// How long after the start of the test the `user`-th of `users` users
+    /// starts
+    pub fn delay(&self, user: usize, users: usize) -> Duration {
+        let batches = self.steps.map_or(users, |steps| steps.min(users));
+        if batches < 2 {
+            return Duration::ZERO;
+        }
+        let batch = user * batches / users;
+        self.duration.mul_f64(batch as f64 / (batches - 1) as f64)
+    }
+
+    /// Delays the start of each user task according to the schedule
+    pub fn schedule<T: 'static>(
+        &self,
+        tasks: Vec<BoxFuture<'static, T>>,
+    ) -> Vec<BoxFuture<'static, T>> {
+        let users = tasks.len();
+        tasks
+            .into_iter()
+            .enumerate()
+            .map(|(user, task)| {
+                let delay = self.delay(user, users);
+                let f: BoxFuture<_> = Box::pin(async move {
+                    if !delay.is_zero() {
+                        fedimint_core::task::sleep(delay).await;
+                    }
+                    task.await
+                });
+                f
+            })
+            .collect()
+    }
+}
+
+impl FromStr for RampUp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (secs, steps) = match s.split_once(':') {
+            Some((secs, steps)) => {
+                let steps = steps
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid number of ramp-up steps: {steps}"))?;
+                if steps < 2 {
+                    bail!("A stepped ramp-up needs at least 2 steps, got {steps}");
+                }
+                (secs, Some(steps))
+            }
+            None => (s, None),
+        };
+        let secs = secs
+            .parse::<f64>()
+            .with_context(|| format!("Invalid number of seconds: {secs}"))?;
+        let duration = Duration::try_from_secs_f64(secs)
+            .with_context(|| format!("Invalid number of seconds: {secs}"))?;
+        Ok(RampUp { duration, steps })
+    }
+}
+
+impl fmt::Display for RampUp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.steps {
+            Some(steps) => write!(f, "{}:{steps}", self.duration.as_secs_f64()),
+            None => write!(f, "{}", self.duration.as_secs_f64()),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::EventMetricSummary;
+
+/// Exit code of a run whose metrics regressed beyond `--max-regression`, so CI
+/// can tell a performance regression apart from a failed run
+pub const REGRESSION_EXIT_CODE: i32 = 2;
+
+/// Aggregate latencies of a reference run that later runs are compared
+/// against, see `--baseline`.
+///
+/// Stored as JSON like
+/// `{"users":10,"metrics":{"reissue_notes":{"avg_ms":120,"median_ms":110,"p95_ms":300}}}`.
+/// `--write-baseline` creates it from the results of a run, metrics that are
+/// too noisy to gate on can be removed from it by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    /// Latencies depend on the load, so only runs with the same number of
+    /// users are compared
+    pub users: u16,
+    pub metrics: BTreeMap<String, BaselineLatencies>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[allow(clippy::struct_field_names)]
+pub struct BaselineLatencies {
+    pub avg_ms: u128,
+    pub median_ms: u128,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p95_ms: Option<u128>,
+}
+
+impl Baseline {
+    pub fn from_summaries(users: u16, summaries: &[EventMetricSummary]) -> Self {
+        let metrics = summaries
+            .iter()
+            .map(|summary| {
+                let latencies = BaselineLatencies {
+                    avg_ms: summary.avg_ms,
+                    median_ms: summary.median_ms,
+                    p95_ms: summary.p95_ms,
+                };
+                (summary.name.clone(), latencies)
+            })
+            .collect();
+        Self { users, metrics }
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let baseline = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read baseline {path:?}"))?;
+        serde_json::from_str(&baseline).with_context(|| format!("Invalid baseline {path:?}"))
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let baseline = serde_json::to_string_pretty(self).expect("to be serializable");
+        std::fs::write(path, baseline).with_context(|| format!("Failed to write baseline {path:?}"))
+    }
+
+    /// Returns every aggregate latency of `summaries` that is more than
+    /// `max_regression` above the baseline
+    pub fn compare(
+        &self,
+        users: u16,
+        summaries: &[EventMetricSummary],
+        max_regression: MaxRegression,
+    ) -> anyhow::Result<Vec<Regression>> {
+        if users != self.users {
+            bail!(
+                "The baseline was recorded with {} users, but the run had {users}",
+                self.users
+            );
+        }
+        let mut regressions = vec![];
+        for (name, baseline) in &self.metrics {
+            let Some(summary) = summaries.iter().find(|summary| summary.name == *name) else {
+                warn!("Metric {name} of the baseline wasn't recorded in this run");
+                continue;
+            };
+            let aggregates = [
+                ("avg", Some(baseline.avg_ms), Some(summary.avg_ms)),
+                ("median", Some(baseline.median_ms), Some(summary.median_ms)),
+                ("p95", baseline.p95_ms, summary.p95_ms),
+            ];
+            for (aggregate, baseline_ms, current_ms) in aggregates {
+                let (Some(baseline_ms), Some(current_ms)) = (baseline_ms, current_ms) else {
+                    continue;
+                };
+                // Nothing to compare against relatively, and not worth gating on
+                if baseline_ms == 0 {
+                    continue;
+                }
+                let change = current_ms as f64 / baseline_ms as f64 - 1.0;
+                if max_regression.0 < change * 100.0 {
+                    regressions.push(Regression {
+                        metric: name.clone(),
+                        aggregate,
+                        baseline_ms,
+                        current_ms,
+                        change,
+                    });
+                }
+            }
+        }
+        Ok(regressions)
+    }
+}
+
+/// A latency that regressed beyond `--max-regression`
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub metric: String,
+    pub aggregate: &'static str,
+    pub baseline_ms: u128,
+    pub current_ms: u128,
+    /// Relative change, `0.2` for 20% slower than the baseline
+    pub change: f64,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}ms -> {}ms (+{:.1}%)",
+            self.metric,
+            self.aggregate,
+            self.baseline_ms,
+            self.current_ms,
+            self.change * 100.0
+        )
+    }
+}
+
+/// How much slower than the baseline a latency may get before the run fails,
+/// parsed from a percentage like `20%` or `20`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxRegression(f64);
+
+impl fmt::Display for MaxRegression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+impl FromStr for MaxRegression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let percent = s
+            .strip_suffix('%')
+            .unwrap_or(s)
+            .parse::<f64>()
+            .with_context(|| format!("Invalid percentage {s}"))?;
+        if !percent.is_finite() || percent < 0.0 {
+            bail!("Maximum regression must be a non-negative percentage, got {s}");
+        }
+        Ok(MaxRegression(percent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{Baseline, BaselineLatencies, MaxRegression};
+    use crate::EventMetricSummary;
+
+    fn summary(
+        name: &str,
+        avg_ms: u128,
+        median_ms: u128,
+        p95_ms: Option<u128>,
+    ) -> EventMetricSummary {
+        EventMetricSummary {
+            name: name.to_owned(),
+            users: 10,
+            n: 100,
+            avg_ms,
+            median_ms,
+            p95_ms,
+            p99_ms: None,
+            max_ms: 1_000,
+            min_ms: 1,
+            timestamp_seconds: 0,
+        }
+    }
+
+    fn baseline() -> Baseline {
+        Baseline {
+            users: 10,
+            metrics: BTreeMap::from([
+                (
+                    "reissue_notes".to_owned(),
+                    BaselineLatencies {
+                        avg_ms: 100,
+                        median_ms: 100,
+                        p95_ms: Some(200),
+                    },
+                ),
+                (
+                    "gateway_pay_invoice".to_owned(),
+                    BaselineLatencies {
+                        avg_ms: 0,
+                        median_ms: 100,
+                        p95_ms: None,
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn max_regression_is_parsed_from_percentages() {
+        assert_eq!("20%".parse::<MaxRegression>().unwrap(), MaxRegression(20.0));
+        assert_eq!("7.5".parse::<MaxRegression>().unwrap(), MaxRegression(7.5));
+        assert!("-1%".parse::<MaxRegression>().is_err());
+        assert!("NaN".parse::<MaxRegression>().is_err());
+        assert!("twenty".parse::<MaxRegression>().is_err());
+    }
+
+    #[test]
+    fn only_latencies_beyond_the_threshold_regress() {
+        let summaries = [
+            // avg within the threshold, p95 beyond it
+            summary("reissue_notes", 110, 90, Some(250)),
+            // avg of 0 in the baseline is never compared, the missing p95 is ignored
+            summary("gateway_pay_invoice", 50, 121, Some(1_000)),
+            summary("not_in_baseline", 1_000, 1_000, None),
+        ];
+
+        let regressions = baseline()
+            .compare(10, &summaries, MaxRegression(20.0))
+            .expect("Same number of users");
+        let regressed = regressions
+            .iter()
+            .map(|r| (r.metric.as_str(), r.aggregate))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regressed,
+            vec![("gateway_pay_invoice", "median"), ("reissue_notes", "p95")]
+        );
+        assert_eq!(regressions[1].baseline_ms, 200);
+        assert_eq!(regressions[1].current_ms, 250);
+    }
+
+    #[test]
+    fn runs_with_a_different_number_of_users_are_not_compared() {
+        assert!(baseline()
+            .compare(
+                20,
+                &[summary("reissue_notes", 100, 100, None)],
+                MaxRegression(20.0)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn baseline_is_created_from_a_run() {
+        let summaries = [summary("reissue_notes", 120, 90, Some(250))];
+        let baseline = Baseline::from_summaries(10, &summaries);
+
+        let regressions = baseline
+            .compare(10, &summaries, MaxRegression(0.0))
+            .expect("Same number of users");
+        assert!(regressions.is_empty());
+        assert_eq!(baseline.metrics["reissue_notes"].p95_ms, Some(250));
+    }
+}
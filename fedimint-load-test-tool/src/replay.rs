@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context};
+use fedimint_client::oplog::OperationLogEntry;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::Amount;
+use fedimint_ln_client::{LightningOperationMeta, LightningOperationMetaVariant};
+use fedimint_mint_client::{MintOperationMeta, MintOperationMetaVariant, OOBNotes};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::common::{build_client, reissue_notes};
+use crate::failures::record_outcome;
+use crate::metrics_channel::MetricSender;
+use crate::mix::{perform_scenario, Scenario};
+use crate::{get_lightning_gateway, LnInvoiceGeneration, MetricEvent};
+
+/// Version of the [`TrafficProfile`] format written by this build
+const PROFILE_VERSION: u16 = 1;
+
+/// How many operation log entries are read from a client database at a time
+const OPERATION_LOG_PAGE_SIZE: usize = 100;
+
+/// Maximum random shift of the offset of an exported operation
+const OFFSET_JITTER_MS: i64 = 5_000;
+
+/// Timings, kinds and amounts of the operations of a group of users, e.g.
+/// exported from the client databases of a production deployment, to replay
+/// the same temporal pattern against a test federation.
+///
+/// Nothing identifying the users or their payments is kept: operations only
+/// have an offset from the first operation of the profile and the index of
+/// their user, no operation ids, notes, invoices or absolute times. Offsets
+/// are shifted randomly by up to five seconds and amounts are rounded to two
+/// significant digits, so operations can't be matched with payments seen
+/// elsewhere by their exact timing or amount.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficProfile {
+    version: u16,
+    operations: Vec<ProfiledOperation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfiledOperation {
+    /// Milliseconds since the start of the profile
+    offset_ms: u64,
+    /// Index of the user that performed the operation
+    user: u16,
+    kind: Scenario,
+    amount_msats: Amount,
+}
+
+impl TrafficProfile {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let profile = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read traffic profile {path:?}"))?;
+        let profile: Self = serde_json::from_str(&profile)
+            .with_context(|| format!("Invalid traffic profile {path:?}"))?;
+        if profile.version != PROFILE_VERSION {
+            bail!(
+                "Traffic profile {path:?} has version {}, only version {PROFILE_VERSION} is supported",
+                profile.version
+            );
+        }
+        if profile.operations.is_empty() {
+            bail!("Traffic profile {path:?} has no operations");
+        }
+        Ok(profile)
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let profile = serde_json::to_string_pretty(self).expect("to be serializable");
+        std::fs::write(path, profile)
+            .with_context(|| format!("Failed to write traffic profile {path:?}"))
+    }
+
+    pub fn contains(&self, kind: Scenario) -> bool {
+        self.operations
+            .iter()
+            .any(|operation| operation.kind == kind)
+    }
+
+    /// Amount of the largest operation
+    pub fn max_amount(&self) -> Amount {
+        self.operations
+            .iter()
+            .map(|operation| operation.amount_msats)
+            .max()
+            .unwrap_or(Amount::ZERO)
+    }
+
+    /// Time between the first and the last operation
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(
+            self.operations
+                .iter()
+                .map(|operation| operation.offset_ms)
+                .max()
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Splits the operations among `users` simulated users, the operations of
+    /// the recorded user `u` are replayed by the simulated user `u % users`
+    pub fn schedules(&self, users: u16) -> Vec<Vec<ProfiledOperation>> {
+        let mut schedules = vec![vec![]; users.into()];
+        for operation in &self.operations {
+            schedules[usize::from(operation.user % users)].push(*operation);
+        }
+        for schedule in &mut schedules {
+            schedule.sort_by_key(|operation| operation.offset_ms);
+        }
+        schedules
+    }
+}
+
+impl fmt::Display for TrafficProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut kinds = BTreeMap::<Scenario, u64>::new();
+        for operation in &self.operations {
+            *kinds.entry(operation.kind).or_default() += 1;
+        }
+        let kinds = kinds
+            .iter()
+            .map(|(kind, count)| format!("{kind}={count}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{} operations over {}s ({})",
+            self.operations.len(),
+            self.duration().as_secs(),
+            kinds.join(",")
+        )
+    }
+}
+
+/// Reads the operation logs of the clients in `client_dbs` into a
+/// [`TrafficProfile`] and writes it to `output`. Every database is one user of
+/// the profile.
+///
+/// Reissues, out of band spends and lightning payments and receives are
+/// exported, other operations like peg-ins are skipped.
+pub async fn export_profile(client_dbs: &[PathBuf], output: &Path) -> anyhow::Result<()> {
+    let mut operations = vec![];
+    let mut skipped = 0;
+    for (user, client_db) in client_dbs.iter().enumerate() {
+        let user = u16::try_from(user).context("Too many client databases")?;
+        let (client, _) = build_client(None, Some(client_db))
+            .await
+            .with_context(|| format!("Failed to open the client database {client_db:?}"))?;
+        let mut last_seen = None;
+        loop {
+            let page = client
+                .operation_log()
+                .paginate_operations_rev(OPERATION_LOG_PAGE_SIZE, last_seen)
+                .await;
+            for (key, entry) in &page {
+                match profiled_kind(entry) {
+                    Some((kind, amount)) => {
+                        operations.push((key.creation_time, user, kind, amount));
+                    }
+                    None => skipped += 1,
+                }
+            }
+            if page.len() < OPERATION_LOG_PAGE_SIZE {
+                break;
+            }
+            last_seen = page.last().map(|(key, _)| *key);
+        }
+    }
+
+    let Some(first) = operations.iter().map(|(time, ..)| *time).min() else {
+        bail!("The client databases have no operations to export");
+    };
+    let mut rng = rand::thread_rng();
+    let mut operations = operations
+        .into_iter()
+        .map(|(time, user, kind, amount)| ProfiledOperation {
+            offset_ms: offset_ms(first, time)
+                .saturating_add_signed(rng.gen_range(-OFFSET_JITTER_MS..=OFFSET_JITTER_MS)),
+            user,
+            kind,
+            amount_msats: bucket_amount(amount),
+        })
+        .collect::<Vec<_>>();
+    operations.sort_by_key(|operation| (operation.offset_ms, operation.user));
+    let profile = TrafficProfile {
+        version: PROFILE_VERSION,
+        operations,
+    };
+    profile.write(output)?;
+    info!("Exported {profile} to {output:?}, skipped {skipped} operations that can't be replayed");
+    Ok(())
+}
+
+/// Returns the scenario replaying the operation of `entry` and its amount,
+/// if it can be replayed
+fn profiled_kind(entry: &OperationLogEntry) -> Option<(Scenario, Amount)> {
+    let meta = entry.meta::<serde_json::Value>();
+    match entry.operation_module_kind() {
+        kind if kind == fedimint_mint_client::KIND.as_str() => {
+            let meta = serde_json::from_value::<MintOperationMeta>(meta).ok()?;
+            let scenario = match meta.variant {
+                MintOperationMetaVariant::Reissuance { .. } => Scenario::Reissue,
+                MintOperationMetaVariant::SpendOOB { .. } => Scenario::Spend,
+            };
+            Some((scenario, meta.amount))
+        }
+        kind if kind == fedimint_ln_common::KIND.as_str() => {
+            let meta = serde_json::from_value::<LightningOperationMeta>(meta).ok()?;
+            let (scenario, invoice) = match meta.variant {
+                LightningOperationMetaVariant::Pay(pay) => (Scenario::LnPay, pay.invoice),
+                LightningOperationMetaVariant::Receive { invoice, .. } => {
+                    (Scenario::LnReceive, invoice)
+                }
+                LightningOperationMetaVariant::Claim { .. } => return None,
+            };
+            Some((
+                scenario,
+                Amount::from_msats(invoice.amount_milli_satoshis()?),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Rounds `amount` to two significant digits of sats, but at least one sat
+fn bucket_amount(amount: Amount) -> Amount {
+    let sats = (amount.msats / 1000).max(1);
+    let mut scale = 1;
+    while sats / scale >= 100 {
+        scale *= 10;
+    }
+    Amount::from_sats((sats + scale / 2) / scale * scale)
+}
+
+fn offset_ms(first: SystemTime, time: SystemTime) -> u64 {
+    time.duration_since(first)
+        .unwrap_or_default()
+        .as_millis()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+/// Start of a replay, shared by all users. The first user that is funded
+/// starts the replay, users funded later catch up on the operations that were
+/// due in the meantime.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayClock(Arc<OnceLock<Instant>>);
+
+impl ReplayClock {
+    fn start(&self) -> Instant {
+        *self.0.get_or_init(Instant::now)
+    }
+}
+
+/// Runs the `operations` of a user of a [`TrafficProfile`] at their offsets
+/// from the start of the replay, divided by `speed`.
+///
+/// Besides the metrics of the operations themselves, the duration of every
+/// operation is recorded as `replay_<scenario>` and how late it started as
+/// `replay_start_lag`. The operations of a user run one after another, so an
+/// operation that takes longer than the gap to the next one delays it.
+#[allow(clippy::too_many_arguments)]
+pub async fn do_replay_user_task(
+    prefix: String,
+    client: ClientHandleArc,
+    users_clients: Arc<Vec<ClientHandleArc>>,
+    oob_notes: Vec<OOBNotes>,
+    operations: Vec<ProfiledOperation>,
+    clock: ReplayClock,
+    speed: f64,
+    generate_invoice_with: Option<LnInvoiceGeneration>,
+    event_sender: MetricSender,
+    gateway_id: Option<String>,
+) -> anyhow::Result<()> {
+    let ln_gateway = get_lightning_gateway(&client, gateway_id).await;
+    for oob_note in oob_notes {
+        let amount = oob_note.total_amount();
+        reissue_notes(&client, oob_note, &event_sender)
+            .await
+            .map_err(|e| anyhow::anyhow!("while reissuing initial {amount}: {e}"))?;
+    }
+
+    let start = clock.start();
+    let mut performed = 0;
+    for operation in &operations {
+        let due = start + Duration::from_millis(operation.offset_ms).div_f64(speed);
+        tokio::time::sleep_until(due.into()).await;
+        event_sender
+            .send(MetricEvent {
+                name: "replay_start_lag".to_owned(),
+                duration: Instant::now().saturating_duration_since(due),
+            })
+            .await?;
+
+        let run_operation = perform_scenario(
+            &prefix,
+            operation.kind,
+            &client,
+            &users_clients,
+            operation.amount_msats,
+            generate_invoice_with,
+            None,
+            ln_gateway.clone(),
+            &event_sender,
+        );
+        let name = format!("replay_{}", operation.kind);
+        if record_outcome(&prefix, &name, &event_sender, run_operation)
+            .await?
+            .is_some()
+        {
+            performed += 1;
+        }
+    }
+    info!(
+        "{prefix} Replayed {performed} of {} operations",
+        operations.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_client::oplog::OperationLogEntry;
+    use fedimint_core::Amount;
+    use serde_json::json;
+
+    use super::{bucket_amount, profiled_kind, ProfiledOperation, TrafficProfile, PROFILE_VERSION};
+    use crate::mix::Scenario;
+
+    fn operation(offset_ms: u64, user: u16, kind: Scenario) -> ProfiledOperation {
+        ProfiledOperation {
+            offset_ms,
+            user,
+            kind,
+            amount_msats: Amount::from_sats(1000),
+        }
+    }
+
+    fn profile_of(operations: Vec<ProfiledOperation>) -> TrafficProfile {
+        TrafficProfile {
+            version: PROFILE_VERSION,
+            operations,
+        }
+    }
+
+    fn entry(kind: &str, meta: serde_json::Value) -> OperationLogEntry {
+        serde_json::from_value(json!({
+            "operation_module_kind": kind,
+            "meta": meta,
+            "outcome": null,
+        }))
+        .expect("valid entry")
+    }
+
+    #[test]
+    fn schedules_split_users_and_sort_by_offset() {
+        let profile = profile_of(vec![
+            operation(300, 2, Scenario::Reissue),
+            operation(100, 0, Scenario::LnPay),
+            operation(200, 1, Scenario::Spend),
+            operation(0, 2, Scenario::LnReceive),
+        ]);
+
+        assert_eq!(
+            profile.schedules(2),
+            vec![
+                vec![
+                    operation(0, 2, Scenario::LnReceive),
+                    operation(100, 0, Scenario::LnPay),
+                    operation(300, 2, Scenario::Reissue),
+                ],
+                vec![operation(200, 1, Scenario::Spend)],
+            ]
+        );
+        assert_eq!(profile.schedules(4)[3], vec![]);
+    }
+
+    #[test]
+    fn profiles_replayable_operations() {
+        let reissue = entry(
+            "mint",
+            json!({
+                "variant": { "reissuance": { "txid": null, "out_point_indices": [] } },
+                "amount": 12_345,
+                "extra_meta": null,
+            }),
+        );
+        assert_eq!(
+            profiled_kind(&reissue),
+            Some((Scenario::Reissue, Amount::from_msats(12_345)))
+        );
+
+        let claim = entry(
+            "ln",
+            json!({
+                "variant": { "claim": { "out_points": [] } },
+                "extra_meta": null,
+            }),
+        );
+        assert_eq!(profiled_kind(&claim), None);
+
+        let peg_in = entry("wallet", json!({ "variant": "deposit" }));
+        assert_eq!(profiled_kind(&peg_in), None);
+    }
+
+    #[test]
+    fn buckets_amounts() {
+        assert_eq!(bucket_amount(Amount::from_msats(1)), Amount::from_sats(1));
+        assert_eq!(bucket_amount(Amount::from_sats(99)), Amount::from_sats(99));
+        assert_eq!(
+            bucket_amount(Amount::from_sats(155)),
+            Amount::from_sats(160)
+        );
+        assert_eq!(
+            bucket_amount(Amount::from_msats(12_345_678)),
+            Amount::from_sats(12_000)
+        );
+    }
+
+    #[test]
+    fn profile_roundtrips_through_file() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("profile.json");
+        let profile = profile_of(vec![
+            operation(0, 0, Scenario::Reissue),
+            operation(1500, 1, Scenario::LnPay),
+        ]);
+
+        profile.write(&path).expect("writable");
+        assert_eq!(TrafficProfile::read(&path).expect("readable"), profile);
+
+        profile_of(vec![]).write(&path).expect("writable");
+        assert!(TrafficProfile::read(&path).is_err());
+    }
+}
@@ -0,0 +1,593 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::failures::{parse_failure, FailureCategory};
+
+/// Number of time buckets the charts are aimed to have, the bucket width is
+/// chosen to fit the duration of the run
+const TARGET_BUCKETS: u64 = 300;
+
+/// Latency percentiles listed in the report tables
+const PERCENTILES: [u64; 4] = [50, 90, 95, 99];
+
+/// Format of the `--report`, chosen by the extension of its path: Markdown
+/// for `.md` and `.markdown`, HTML otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Html,
+    Markdown,
+}
+
+impl ReportFormat {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("md" | "markdown") => ReportFormat::Markdown,
+            _ => ReportFormat::Html,
+        }
+    }
+}
+
+/// Collects the metric events of a run and renders them into a single file
+/// with throughput, latency and error rate charts and tables, so the results
+/// can be shared without any other files.
+///
+/// The HTML page draws the charts itself, the Markdown document uses mermaid
+/// charts, which e.g. GitHub renders in issues and pull requests.
+pub struct Report {
+    start: Instant,
+    users: u16,
+    /// Time since the start of the run at which each event completed and its
+    /// duration, per event name
+    events: BTreeMap<String, Vec<(Duration, Duration)>>,
+}
+
+/// Aggregates of a run that both formats render
+struct Summary<'a> {
+    run_duration: Duration,
+    bucket: Duration,
+    latencies: Vec<LatencyRow<'a>>,
+    errors: Vec<ErrorRow<'a>>,
+    chart_data: ChartData<'a>,
+}
+
+struct LatencyRow<'a> {
+    name: &'a str,
+    n: usize,
+    throughput: f64,
+    avg: Duration,
+    percentiles: Vec<Duration>,
+    min: Duration,
+    max: Duration,
+}
+
+/// Failures of an operation recorded by [`crate::failures::record_outcome`]
+struct ErrorRow<'a> {
+    operation: &'a str,
+    attempts: usize,
+    failed: usize,
+    by_cause: BTreeMap<FailureCategory, usize>,
+}
+
+#[derive(Serialize)]
+struct ChartData<'a> {
+    bucket_secs: f64,
+    series: BTreeMap<&'a str, SeriesData>,
+    /// Percentage of the attempts of each operation with failures that failed
+    /// in each bucket, `None` if nothing was attempted
+    error_rate: BTreeMap<&'a str, Vec<Option<f64>>>,
+}
+
+#[derive(Serialize, Default)]
+struct SeriesData {
+    /// Completed events per second in each bucket
+    throughput: Vec<f64>,
+    /// Median latency of the events completed in each bucket, `None` if no
+    /// event completed
+    median_ms: Vec<Option<f64>>,
+    /// 99th percentile latency of the events completed in each bucket
+    p99_ms: Vec<Option<f64>>,
+}
+
+impl Report {
+    pub fn new(users: u16) -> Self {
+        Self {
+            start: Instant::now(),
+            users,
+            events: BTreeMap::new(),
+        }
+    }
+
+    /// Records an event that completed just now
+    pub fn record(&mut self, name: &str, duration: Duration) {
+        let completed_at = self.start.elapsed();
+        self.events
+            .entry(name.to_owned())
+            .or_default()
+            .push((completed_at, duration));
+    }
+
+    pub async fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let summary = self.summarize();
+        let report = match ReportFormat::of(path) {
+            ReportFormat::Html => self.render_html(&summary),
+            ReportFormat::Markdown => self.render_markdown(&summary),
+        };
+        tokio::fs::write(path, report)
+            .await
+            .with_context(|| format!("Failed to write report to {path:?}"))
+    }
+
+    fn summarize(&self) -> Summary<'_> {
+        let run_duration = self.start.elapsed();
+        let bucket = bucket_width(run_duration);
+        let num_buckets = (run_duration.as_nanos() / bucket.as_nanos()) as usize + 1;
+
+        // Failed operations are shown as error rates, their latencies would only
+        // skew the ones of the operations that succeeded
+        let mut successes = BTreeMap::new();
+        let mut failures = BTreeMap::<&str, (Vec<Duration>, BTreeMap<_, _>)>::new();
+        for (name, events) in &self.events {
+            match parse_failure(name) {
+                Some((category, operation)) => {
+                    let (failed_at, by_cause) = failures.entry(operation).or_default();
+                    failed_at.extend(events.iter().map(|(completed_at, _)| *completed_at));
+                    *by_cause.entry(category).or_default() += events.len();
+                }
+                None => {
+                    successes.insert(name.as_str(), events.as_slice());
+                }
+            }
+        }
+
+        let latencies = successes
+            .iter()
+            .map(|(&name, events)| {
+                let mut durations = events.iter().map(|(_, d)| *d).collect::<Vec<_>>();
+                durations.sort();
+                let sum: Duration = durations.iter().sum();
+                LatencyRow {
+                    name,
+                    n: durations.len(),
+                    throughput: durations.len() as f64 / run_duration.as_secs_f64(),
+                    avg: sum / durations.len() as u32,
+                    percentiles: PERCENTILES
+                        .iter()
+                        .map(|p| nearest_rank(&durations, *p))
+                        .collect(),
+                    min: durations[0],
+                    max: durations[durations.len() - 1],
+                }
+            })
+            .collect();
+
+        let mut errors = vec![];
+        let mut error_rate = BTreeMap::new();
+        for (operation, (failed_at, by_cause)) in failures {
+            let succeeded = successes.get(operation).copied().unwrap_or_default();
+            errors.push(ErrorRow {
+                operation,
+                attempts: succeeded.len() + failed_at.len(),
+                failed: failed_at.len(),
+                by_cause,
+            });
+            error_rate.insert(
+                operation,
+                error_rate_series(succeeded, &failed_at, bucket, num_buckets),
+            );
+        }
+
+        let chart_data = ChartData {
+            bucket_secs: bucket.as_secs_f64(),
+            series: successes
+                .iter()
+                .map(|(name, events)| (*name, series(events, bucket, num_buckets)))
+                .collect(),
+            error_rate,
+        };
+
+        Summary {
+            run_duration,
+            bucket,
+            latencies,
+            errors,
+            chart_data,
+        }
+    }
+
+    fn render_html(&self, summary: &Summary<'_>) -> String {
+        // Prevent the data from closing the script element early
+        let chart_json = serde_json::to_string(&summary.chart_data)
+            .expect("Chart data is serializable")
+            .replace("</", "<\\/");
+
+        let mut table = String::new();
+        for row in &summary.latencies {
+            write!(
+                table,
+                "<tr><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td>",
+                escape_html(row.name),
+                row.n,
+                row.throughput,
+                millis(row.avg),
+            )
+            .expect("Writing to a string can't fail");
+            for percentile in &row.percentiles {
+                write!(table, "<td>{}</td>", millis(*percentile))
+                    .expect("Writing to a string can't fail");
+            }
+            writeln!(
+                table,
+                "<td>{}</td><td>{}</td></tr>",
+                millis(row.min),
+                millis(row.max),
+            )
+            .expect("Writing to a string can't fail");
+        }
+
+        let percentile_headers = PERCENTILES
+            .iter()
+            .map(|p| format!("<th>p{p} (ms)</th>"))
+            .collect::<String>();
+
+        let mut errors = String::new();
+        if !summary.errors.is_empty() {
+            errors.push_str("<h2>Errors</h2>\n<table>\n<tr><th>Operation</th><th>attempts</th><th>failed</th><th>rate</th><th>by cause</th></tr>\n");
+            for row in &summary.errors {
+                writeln!(
+                    errors,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td><td>{}</td></tr>",
+                    escape_html(row.operation),
+                    row.attempts,
+                    row.failed,
+                    row.failed as f64 * 100.0 / row.attempts as f64,
+                    by_cause(&row.by_cause),
+                )
+                .expect("Writing to a string can't fail");
+            }
+            errors.push_str("</table>\n<h2>Error rate (%)</h2>\n<canvas id=\"error_rate\" width=\"1000\" height=\"300\"></canvas>\n");
+        }
+
+        format!(
+            r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Fedimint load test report</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: right; }}
+td:first-child {{ text-align: left; }}
+canvas {{ border: 1px solid #ccc; display: block; margin-bottom: 1.5em; }}
+</style>
+</head>
+<body>
+<h1>Fedimint load test report</h1>
+<p>{users} users, run took {duration:.1} s, charts use {bucket:.1} s buckets</p>
+<h2>Latency</h2>
+<table>
+<tr><th>Event</th><th>n</th><th>Throughput (1/s)</th><th>avg (ms)</th>{percentile_headers}<th>min (ms)</th><th>max (ms)</th></tr>
+{table}</table>
+{errors}<h2>Throughput (events/s)</h2>
+<canvas id="throughput" width="1000" height="300"></canvas>
+<h2>Median latency (ms)</h2>
+<canvas id="median_ms" width="1000" height="300"></canvas>
+<h2>p99 latency (ms)</h2>
+<canvas id="p99_ms" width="1000" height="300"></canvas>
+<script>
+const data = {chart_json};
+const colors = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf"];
+
+function field(name) {{
+  return Object.fromEntries(Object.entries(data.series).map(([n, s]) => [n, s[name]]));
+}}
+
+function drawChart(id, series) {{
+  const canvas = document.getElementById(id);
+  if (canvas === null) {{
+    return;
+  }}
+  const ctx = canvas.getContext("2d");
+  const pad = {{ left: 60, right: 180, top: 10, bottom: 30 }};
+  const width = canvas.width - pad.left - pad.right;
+  const height = canvas.height - pad.top - pad.bottom;
+  const names = Object.keys(series);
+  const values = names.flatMap(name => series[name]).filter(v => v !== null);
+  const maxY = Math.max(1, ...values);
+  const buckets = Math.max(1, ...names.map(name => series[name].length));
+  const x = i => pad.left + (buckets > 1 ? i / (buckets - 1) : 0) * width;
+  const y = v => pad.top + height - (v / maxY) * height;
+
+  ctx.font = "12px sans-serif";
+  ctx.strokeStyle = "#999";
+  ctx.strokeRect(pad.left, pad.top, width, height);
+  ctx.fillStyle = "#000";
+  for (let i = 0; i <= 4; i++) {{
+    const v = maxY * i / 4;
+    ctx.fillText(v.toFixed(1), 5, y(v) + 4);
+  }}
+  ctx.fillText("0 s", pad.left, canvas.height - 10);
+  ctx.fillText((buckets * data.bucket_secs).toFixed(0) + " s", pad.left + width - 30, canvas.height - 10);
+
+  names.forEach((name, n) => {{
+    const color = colors[n % colors.length];
+    ctx.strokeStyle = color;
+    ctx.beginPath();
+    let drawing = false;
+    series[name].forEach((v, i) => {{
+      if (v === null) {{
+        drawing = false;
+      }} else if (drawing) {{
+        ctx.lineTo(x(i), y(v));
+      }} else {{
+        ctx.moveTo(x(i), y(v));
+        drawing = true;
+      }}
+    }});
+    ctx.stroke();
+    ctx.fillStyle = color;
+    ctx.fillRect(pad.left + width + 10, pad.top + n * 16, 10, 10);
+    ctx.fillStyle = "#000";
+    ctx.fillText(name, pad.left + width + 25, pad.top + n * 16 + 10);
+  }});
+}}
+
+drawChart("throughput", field("throughput"));
+drawChart("median_ms", field("median_ms"));
+drawChart("p99_ms", field("p99_ms"));
+drawChart("error_rate", data.error_rate);
+</script>
+</body>
+</html>
+"##,
+            users = self.users,
+            duration = summary.run_duration.as_secs_f64(),
+            bucket = summary.bucket.as_secs_f64(),
+        )
+    }
+
+    fn render_markdown(&self, summary: &Summary<'_>) -> String {
+        let mut report = String::new();
+        writeln!(
+            report,
+            "# Fedimint load test report\n\n{} users, run took {:.1} s, charts use {:.1} s buckets\n",
+            self.users,
+            summary.run_duration.as_secs_f64(),
+            summary.bucket.as_secs_f64(),
+        )
+        .expect("Writing to a string can't fail");
+
+        let percentile_headers = PERCENTILES
+            .iter()
+            .map(|p| format!(" p{p} (ms) |"))
+            .collect::<String>();
+        writeln!(
+            report,
+            "## Latency\n\n| Event | n | Throughput (1/s) | avg (ms) |{percentile_headers} min (ms) | max (ms) |\n|---|{}",
+            "---:|".repeat(PERCENTILES.len() + 5),
+        )
+        .expect("Writing to a string can't fail");
+        for row in &summary.latencies {
+            let percentiles = row
+                .percentiles
+                .iter()
+                .map(|p| format!(" {} |", millis(*p)))
+                .collect::<String>();
+            writeln!(
+                report,
+                "| {} | {} | {:.2} | {} |{percentiles} {} | {} |",
+                escape_markdown(row.name),
+                row.n,
+                row.throughput,
+                millis(row.avg),
+                millis(row.min),
+                millis(row.max),
+            )
+            .expect("Writing to a string can't fail");
+        }
+
+        if !summary.errors.is_empty() {
+            report.push_str(
+                "\n## Errors\n\n| Operation | attempts | failed | rate | by cause |\n|---|---:|---:|---:|---|\n",
+            );
+            for row in &summary.errors {
+                writeln!(
+                    report,
+                    "| {} | {} | {} | {:.1}% | {} |",
+                    escape_markdown(row.operation),
+                    row.attempts,
+                    row.failed,
+                    row.failed as f64 * 100.0 / row.attempts as f64,
+                    by_cause(&row.by_cause),
+                )
+                .expect("Writing to a string can't fail");
+            }
+        }
+
+        let series = &summary.chart_data.series;
+        let charts: [(&str, BTreeMap<&str, Vec<Option<f64>>>); 4] = [
+            (
+                "Throughput (events/s)",
+                series
+                    .iter()
+                    .map(|(name, s)| (*name, s.throughput.iter().copied().map(Some).collect()))
+                    .collect(),
+            ),
+            (
+                "Median latency (ms)",
+                series
+                    .iter()
+                    .map(|(name, s)| (*name, s.median_ms.clone()))
+                    .collect(),
+            ),
+            (
+                "p99 latency (ms)",
+                series
+                    .iter()
+                    .map(|(name, s)| (*name, s.p99_ms.clone()))
+                    .collect(),
+            ),
+            ("Error rate (%)", summary.chart_data.error_rate.clone()),
+        ];
+        for (title, lines) in charts {
+            if !lines.is_empty() {
+                mermaid_chart(&mut report, title, summary, &lines);
+            }
+        }
+        report
+    }
+}
+
+/// Appends a mermaid line chart of `lines` to the Markdown `report`. Mermaid
+/// charts have no legend, so the lines are listed below the chart in the
+/// order of their colors.
+fn mermaid_chart(
+    report: &mut String,
+    title: &str,
+    summary: &Summary<'_>,
+    lines: &BTreeMap<&str, Vec<Option<f64>>>,
+) {
+    let end_secs = lines.values().map(Vec::len).max().unwrap_or_default() as f64
+        * summary.bucket.as_secs_f64();
+    writeln!(
+        report,
+        "\n## {title}\n\n```mermaid\nxychart-beta\n    x-axis \"time (s)\" 0 --> {end_secs:.0}"
+    )
+    .expect("Writing to a string can't fail");
+    for values in lines.values() {
+        // Mermaid can't leave gaps, buckets without events are drawn as 0
+        let values = values
+            .iter()
+            .map(|v| format!("{:.1}", v.unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(report, "    line [{values}]").expect("Writing to a string can't fail");
+    }
+    report.push_str("```\n\n");
+    for (n, name) in lines.keys().enumerate() {
+        writeln!(report, "{}. {}", n + 1, escape_markdown(name))
+            .expect("Writing to a string can't fail");
+    }
+}
+
+/// Bucket width so a run is split into about [`TARGET_BUCKETS`] buckets, but
+/// never less than a second
+fn bucket_width(run_duration: Duration) -> Duration {
+    Duration::from_secs((run_duration.as_secs() / TARGET_BUCKETS).max(1))
+}
+
+fn series(events: &[(Duration, Duration)], bucket: Duration, num_buckets: usize) -> SeriesData {
+    let mut buckets = vec![Vec::new(); num_buckets];
+    for (completed_at, duration) in events {
+        buckets[bucket_index(*completed_at, bucket, num_buckets)].push(*duration);
+    }
+
+    let mut series = SeriesData::default();
+    for mut durations in buckets {
+        durations.sort();
+        series
+            .throughput
+            .push(durations.len() as f64 / bucket.as_secs_f64());
+        let percentile_ms =
+            |p| (!durations.is_empty()).then(|| nearest_rank(&durations, p).as_secs_f64() * 1000.0);
+        series.median_ms.push(percentile_ms(50));
+        series.p99_ms.push(percentile_ms(99));
+    }
+    series
+}
+
+fn error_rate_series(
+    succeeded: &[(Duration, Duration)],
+    failed_at: &[Duration],
+    bucket: Duration,
+    num_buckets: usize,
+) -> Vec<Option<f64>> {
+    let mut attempts = vec![(0usize, 0usize); num_buckets];
+    for (completed_at, _) in succeeded {
+        attempts[bucket_index(*completed_at, bucket, num_buckets)].0 += 1;
+    }
+    for completed_at in failed_at {
+        attempts[bucket_index(*completed_at, bucket, num_buckets)].1 += 1;
+    }
+    attempts
+        .into_iter()
+        .map(|(succeeded, failed)| {
+            (0 < succeeded + failed).then(|| failed as f64 * 100.0 / (succeeded + failed) as f64)
+        })
+        .collect()
+}
+
+fn bucket_index(completed_at: Duration, bucket: Duration, num_buckets: usize) -> usize {
+    ((completed_at.as_nanos() / bucket.as_nanos()) as usize).min(num_buckets - 1)
+}
+
+/// Nearest-rank percentile of the sorted, non-empty `durations`
+pub(crate) fn nearest_rank(durations: &[Duration], percentile: u64) -> Duration {
+    let rank = (percentile * durations.len() as u64).div_ceil(100).max(1);
+    durations[rank as usize - 1]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}", duration.as_secs_f64() * 1000.0)
+}
+
+fn by_cause(by_cause: &BTreeMap<FailureCategory, usize>) -> String {
+    by_cause
+        .iter()
+        .map(|(category, count)| format!("{category}={count}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::nearest_rank;
+    use crate::EventMetricSummary;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let durations = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(nearest_rank(&durations, 50), Duration::from_millis(50));
+        assert_eq!(nearest_rank(&durations, 95), Duration::from_millis(95));
+        assert_eq!(nearest_rank(&durations, 99), Duration::from_millis(99));
+        assert_eq!(nearest_rank(&durations, 100), Duration::from_millis(100));
+
+        // Few samples round up to the next one
+        let durations = [10, 20, 30].map(Duration::from_millis);
+        assert_eq!(nearest_rank(&durations, 50), Duration::from_millis(20));
+        assert_eq!(nearest_rank(&durations, 95), Duration::from_millis(30));
+        assert_eq!(nearest_rank(&durations, 0), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn summaries_archived_without_percentiles_can_be_read() {
+        let summary: EventMetricSummary = serde_json::from_str(
+            r#"{"name":"reissue_notes","users":1,"n":2,"avg_ms":15,"median_ms":20,
+                "max_ms":20,"min_ms":10,"timestamp_seconds":0}"#,
+        )
+        .expect("Valid summary");
+
+        assert_eq!(summary.median_ms, 20);
+        assert_eq!(summary.p95_ms, None);
+        assert_eq!(summary.p99_ms, None);
+    }
+}
@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+
+use fedimint_core::util::BoxFuture;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+tokio::task_local! {
+    /// Random number generator of the user task being polled
+    static USER_RNG: RefCell<StdRng>;
+}
+
+/// Generator for randomness drawn outside of user tasks, e.g. by the invoice
+/// pool, see [`seed_shared_rng`]
+static SHARED_RNG: OnceLock<Mutex<StdRng>> = OnceLock::new();
+
+/// Seeds the generator used outside of user tasks, must be called before
+/// anything draws from it
+pub fn seed_shared_rng(seed: Option<u64>) {
+    if SHARED_RNG.set(Mutex::new(rng("shared", seed))).is_err() {
+        panic!("Shared generator was already seeded or used");
+    }
+}
+
+/// Gives every user task its own random number generator, derived from `seed`
+/// and the index of the user.
+///
+/// The tasks run concurrently, so a single generator would hand out its values
+/// in whatever order the tasks happen to poll it. With one generator per user
+/// every user draws the same sequence of think times, scenarios and invoice
+/// labels in every run with the same seed. Without a seed the generators are
+/// seeded from the OS.
+pub fn scope_user_tasks<T: 'static>(
+    seed: Option<u64>,
+    tasks: Vec<BoxFuture<'static, T>>,
+) -> Vec<BoxFuture<'static, T>> {
+    tasks
+        .into_iter()
+        .enumerate()
+        .map(|(user, task)| {
+            let f: BoxFuture<_> = Box::pin(USER_RNG.scope(RefCell::new(rng(user, seed)), task));
+            f
+        })
+        .collect()
+}
+
+/// Calls `f` with the generator of the current user task, or the shared one
+/// outside of user tasks
+pub fn with_rng<R>(f: impl FnOnce(&mut StdRng) -> R) -> R {
+    let mut f = Some(f);
+    match USER_RNG.try_with(|rng| f.take().expect("Not called yet")(&mut rng.borrow_mut())) {
+        Ok(r) => r,
+        Err(_) => {
+            let mut rng = SHARED_RNG
+                .get_or_init(|| Mutex::new(StdRng::from_entropy()))
+                .lock()
+                .expect("Locking can't fail");
+            f.take().expect("Not called yet")(&mut rng)
+        }
+    }
+}
+
+fn rng(stream: impl Hash, seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => {
+            let mut hasher = DefaultHasher::new();
+            (seed, stream).hash(&mut hasher);
+            StdRng::seed_from_u64(hasher.finish())
+        }
+        None => StdRng::from_entropy(),
+    }
+}
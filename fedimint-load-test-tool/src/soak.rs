@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+
+use crate::report::nearest_rank;
+
+/// How long a soak test keeps the users cycling through their scenarios.
+///
+/// Endurance problems like leaks or slowly growing databases only show after
+/// hours of traffic, which is awkward to reach by tuning operation counts.
+///
+/// Parsed from a number of seconds or a sequence of numbers with a unit of
+/// `d`, `h`, `m` or `s`, e.g. `90`, `2h` or `1h30m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoakDuration(pub Duration);
+
+impl SoakDuration {
+    /// The instant at which a soak started now ends
+    pub fn deadline(&self) -> Instant {
+        Instant::now() + self.0
+    }
+}
+
+impl FromStr for SoakDuration {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Ok(secs) = s.parse::<u64>() {
+            return Ok(SoakDuration(Duration::from_secs(secs)));
+        }
+
+        let mut total = Duration::ZERO;
+        let mut rest = s;
+        while !rest.is_empty() {
+            let unit_start = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .with_context(|| format!("Missing unit after {rest} in duration {s}"))?;
+            let (number, unit) = rest.split_at(unit_start);
+            let unit_len = unit
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(unit.len());
+            let (unit, remainder) = unit.split_at(unit_len);
+            let number = number
+                .parse::<u64>()
+                .with_context(|| format!("Invalid duration: {s}"))?;
+            let unit_secs = match unit {
+                "d" => 24 * 60 * 60,
+                "h" => 60 * 60,
+                "m" => 60,
+                "s" => 1,
+                _ => bail!("Invalid duration unit {unit} in {s}, expected d, h, m or s"),
+            };
+            let secs = number
+                .checked_mul(unit_secs)
+                .with_context(|| format!("Duration too long: {s}"))?;
+            total = total
+                .checked_add(Duration::from_secs(secs))
+                .with_context(|| format!("Duration too long: {s}"))?;
+            rest = remainder;
+        }
+        if total.is_zero() {
+            bail!("Invalid duration: {s}");
+        }
+        Ok(SoakDuration(total))
+    }
+}
+
+impl fmt::Display for SoakDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if hours > 0 {
+            write!(f, "{hours}h")?;
+        }
+        if minutes > 0 {
+            write!(f, "{minutes}m")?;
+        }
+        if secs > 0 || (hours == 0 && minutes == 0) {
+            write!(f, "{secs}s")?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints a summary of the events recorded so far, so long runs can be
+/// followed without waiting for the final summary
+pub fn print_intermediate_summary(elapsed: Duration, results: &BTreeMap<String, Vec<Duration>>) {
+    println!(
+        "Intermediate summary after {}:",
+        SoakDuration(Duration::from_secs(elapsed.as_secs()))
+    );
+    for (name, durations) in results {
+        let mut durations = durations.clone();
+        durations.sort();
+        let n = durations.len();
+        let Some(max) = durations.last() else {
+            continue;
+        };
+        let sum: Duration = durations.iter().sum();
+        let avg = sum / n as u32;
+        let p95 = nearest_rank(&durations, 95);
+        let rate = n as f64 / elapsed.as_secs_f64().max(1.0);
+        println!("  {n} {name}: {rate:.2}/s, avg {avg:?}, p95 {p95:?}, max {max:?}");
+    }
+}
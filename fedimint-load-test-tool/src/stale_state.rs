@@ -0,0 +1,118 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use anyhow::bail;
+use fedimint_client::sm::IState as _;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
+use fedimint_mint_client::client_db::PendingOOBNoteKeyPrefix;
+use fedimint_mint_client::MintClientModule;
+use futures::StreamExt;
+use tracing::{info, warn};
+
+/// How many times we re-scan the clients before giving up, to give state
+/// machines that were still running when the users finished some time to
+/// settle
+const SETTLE_ATTEMPTS: usize = 30;
+
+/// Verifies after the run that the simulated users didn't leak any state:
+/// every operation reached a terminal state and no note is stuck in a pending
+/// state. Such leaks don't show up in the latency numbers, but mean a real
+/// wallet would show a wrong balance or never finish an operation.
+pub struct StaleStateCheck {
+    users: Vec<ClientHandleArc>,
+}
+
+impl StaleStateCheck {
+    pub fn new(users: Vec<ClientHandleArc>) -> Self {
+        Self { users }
+    }
+
+    pub fn users(&self) -> &[ClientHandleArc] {
+        &self.users
+    }
+
+    /// Scan all users, retrying for a while so operations that are about to
+    /// finish don't fail the run
+    pub async fn verify(self) -> anyhow::Result<()> {
+        self.log_missed_notifications();
+
+        let mut stale = vec![];
+        for attempt in 1..=SETTLE_ATTEMPTS {
+            stale = self.scan().await?;
+            if stale.is_empty() {
+                info!(users = self.users.len(), "No stale client state found");
+                return Ok(());
+            }
+            warn!(
+                stale = stale.len(),
+                attempt, "Found stale client state, waiting for it to settle"
+            );
+            fedimint_core::task::sleep(Duration::from_secs(1)).await;
+        }
+
+        let mut listing = String::new();
+        for line in &stale {
+            writeln!(listing, "  {line}")?;
+        }
+        bail!(
+            "Found {} stale client states after the run:\n{listing}",
+            stale.len()
+        );
+    }
+
+    /// Missed notifications don't leave stale state behind, but mean the
+    /// clients were too overloaded to follow their own operations
+    fn log_missed_notifications(&self) {
+        for (u, client) in self.users.iter().enumerate() {
+            let missed = client.executor().notifier().missed_notifications();
+            if missed != 0 {
+                warn!(
+                    user = u,
+                    missed, "Client missed state transition notifications"
+                );
+            }
+        }
+    }
+
+    async fn scan(&self) -> anyhow::Result<Vec<String>> {
+        let mut stale = vec![];
+        for (u, client) in self.users.iter().enumerate() {
+            for (state, meta) in client.executor().get_active_states().await {
+                let operation_id = state.operation_id();
+                let operation_kind = client
+                    .operation_log()
+                    .get_operation(operation_id)
+                    .await
+                    .map_or_else(
+                        || "unknown".to_owned(),
+                        |op| op.operation_module_kind().to_owned(),
+                    );
+                stale.push(format!(
+                    "User {u}: {operation_kind} operation {} has a non-terminal state machine (created at {:?}): {state:?}",
+                    operation_id.fmt_short(),
+                    meta.created_at
+                ));
+            }
+
+            let mint = client.get_first_module::<MintClientModule>()?;
+            let pending_notes = mint
+                .db
+                .begin_transaction_nc()
+                .await
+                .find_by_prefix(&PendingOOBNoteKeyPrefix)
+                .await
+                .collect::<Vec<_>>()
+                .await;
+            for (key, _note) in pending_notes {
+                stale.push(format!(
+                    "User {u}: note {:?} of {} is stuck pending in out-of-band spend {}",
+                    key.nonce,
+                    key.amount,
+                    key.operation_id.fmt_short()
+                ));
+            }
+        }
+        Ok(stale)
+    }
+}
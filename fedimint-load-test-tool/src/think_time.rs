@@ -0,0 +1,106 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use rand::Rng;
+
+use crate::seed::with_rng;
+
+/// Distribution of the pause a simulated user takes between two successive
+/// operations.
+///
+/// Without any pause every user issues its next request as soon as the
+/// previous one finished, so the offered load just follows the federation's
+/// latency. Drawing the pauses from a random distribution instead models
+/// independent users arriving at some average rate, which is closer to real
+/// traffic.
+///
+/// Parsed from `fixed:<secs>`, `exponential:<mean secs>` or
+/// `pareto:<min secs>:<shape>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThinkTime {
+    /// Always pause for the same duration
+    Fixed(Duration),
+    /// Memoryless pauses with the given mean, so the operations of each user
+    /// form a Poisson process
+    Exponential { mean: Duration },
+    /// Heavy tailed pauses of at least `min`, a lower `shape` makes long
+    /// pauses more likely. The mean is only finite for `shape > 1`.
+    Pareto { min: Duration, shape: f64 },
+}
+
+impl ThinkTime {
+    /// Draws the duration of the next pause
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match *self {
+            ThinkTime::Fixed(duration) => duration,
+            ThinkTime::Exponential { mean } => {
+                // Inverse transform sampling, `gen` returns values in [0, 1)
+                let uniform: f64 = rng.gen();
+                mean.mul_f64(-(1.0 - uniform).ln())
+            }
+            ThinkTime::Pareto { min, shape } => {
+                let uniform: f64 = rng.gen();
+                let factor = (1.0 - uniform).powf(-1.0 / shape);
+                Duration::try_from_secs_f64(min.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+            }
+        }
+    }
+
+    /// Sleeps for a freshly drawn pause
+    pub async fn sleep(&self) {
+        let duration = with_rng(|rng| self.sample(rng));
+        if !duration.is_zero() {
+            fedimint_core::task::sleep(duration).await;
+        }
+    }
+}
+
+impl FromStr for ThinkTime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let parse_secs = |secs: &str| -> anyhow::Result<Duration> {
+            let secs = secs
+                .parse::<f64>()
+                .with_context(|| format!("Invalid number of seconds: {secs}"))?;
+            Duration::try_from_secs_f64(secs)
+                .with_context(|| format!("Invalid number of seconds: {secs}"))
+        };
+
+        match s.split(':').collect::<Vec<_>>().as_slice() {
+            ["fixed", secs] => Ok(ThinkTime::Fixed(parse_secs(secs)?)),
+            ["exponential", mean] => Ok(ThinkTime::Exponential {
+                mean: parse_secs(mean)?,
+            }),
+            ["pareto", min, shape] => {
+                let shape = shape
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid pareto shape: {shape}"))?;
+                if !(shape > 0.0 && shape.is_finite()) {
+                    bail!("Pareto shape must be positive, got {shape}");
+                }
+                Ok(ThinkTime::Pareto {
+                    min: parse_secs(min)?,
+                    shape,
+                })
+            }
+            _ => bail!(
+                "Invalid think time {s}, expected fixed:<secs>, exponential:<mean secs> or pareto:<min secs>:<shape>"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ThinkTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThinkTime::Fixed(duration) => write!(f, "fixed:{}", duration.as_secs_f64()),
+            ThinkTime::Exponential { mean } => write!(f, "exponential:{}", mean.as_secs_f64()),
+            ThinkTime::Pareto { min, shape } => {
+                write!(f, "pareto:{}:{shape}", min.as_secs_f64())
+            }
+        }
+    }
+}
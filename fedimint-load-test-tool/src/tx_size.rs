@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::{apply, async_trait_maybe_send, Amount, TieredMulti};
+use fedimint_mint_client::config::FeeConsensus;
+use fedimint_mint_client::{MintClientModule, NotesSelector, OOBNotes, ReissueExternalNotesState};
+use futures::StreamExt;
+use tracing::info;
+
+use crate::common::{reissue_notes, remint_denomination};
+use crate::metrics_channel::MetricSender;
+use crate::think_time::ThinkTime;
+use crate::MetricEvent;
+
+/// Submits transactions with `size` outputs and with `size` inputs for every
+/// size class in `sizes`, `iterations` times, and records how long the
+/// federation takes to accept each of them.
+///
+/// All notes moved are of `denomination`, so the number of notes in a
+/// transaction doesn't depend on how the client splits amounts.
+#[allow(clippy::too_many_arguments)]
+pub async fn do_tx_size_user_task(
+    prefix: String,
+    client: ClientHandleArc,
+    oob_notes: Vec<OOBNotes>,
+    sizes: Vec<u16>,
+    iterations: u16,
+    denomination: Amount,
+    think_time: ThinkTime,
+    event_sender: MetricSender,
+) -> anyhow::Result<()> {
+    for oob_notes in oob_notes {
+        reissue_notes(&client, oob_notes, &event_sender).await?;
+    }
+    for i in 0..iterations {
+        for &size in &sizes {
+            // Mints the notes the inputs-heavy transaction spends right after
+            let accepted = remint_denomination(&client, denomination, size).await?;
+            send_metric(
+                &event_sender,
+                format!("tx_{size}_outputs_accepted"),
+                accepted,
+            )
+            .await?;
+
+            let accepted = reissue_denomination(&client, denomination, size).await?;
+            send_metric(
+                &event_sender,
+                format!("tx_{size}_inputs_accepted"),
+                accepted,
+            )
+            .await?;
+            info!("{prefix} Submitted transactions with {size} outputs and inputs");
+        }
+        if i + 1 < iterations {
+            think_time.sleep().await;
+        }
+    }
+    Ok(())
+}
+
+/// Reissues `quantity` notes of `denomination` in a single transaction, returns
+/// how long the federation took to accept it
+async fn reissue_denomination(
+    client: &ClientHandleArc,
+    denomination: Amount,
+    quantity: u16,
+) -> anyhow::Result<Duration> {
+    let oob_notes = spend_denomination(client, denomination, quantity).await?;
+
+    let mint = client.get_first_module::<MintClientModule>()?;
+    let m = fedimint_core::time::now();
+    let operation_id = mint.reissue_external_notes(oob_notes, ()).await?;
+    let mut updates = mint
+        .subscribe_reissue_external_notes(operation_id)
+        .await?
+        .into_stream();
+    let mut accepted = None;
+    while let Some(update) = updates.next().await {
+        match update {
+            ReissueExternalNotesState::Issuing => accepted = Some(m.elapsed()?),
+            ReissueExternalNotesState::Failed(e) => bail!("Reissue failed: {e}"),
+            _ => {}
+        }
+    }
+    match accepted {
+        Some(accepted) => Ok(accepted),
+        None => Ok(m.elapsed()?),
+    }
+}
+
+/// Spends exactly `quantity` notes of `denomination` out of band
+pub(crate) async fn spend_denomination(
+    client: &ClientHandleArc,
+    denomination: Amount,
+    quantity: u16,
+) -> anyhow::Result<OOBNotes> {
+    let mint = client.get_first_module::<MintClientModule>()?;
+    let (_, oob_notes) = mint
+        .spend_notes_with_selector(
+            &SelectNotesOfDenomination {
+                denomination,
+                quantity: quantity.into(),
+            },
+            denomination * u64::from(quantity),
+            Duration::from_secs(600),
+            false,
+            (),
+        )
+        .await?;
+    Ok(oob_notes)
+}
+
+/// Selects exactly `quantity` notes of `denomination`, ignoring all others
+struct SelectNotesOfDenomination {
+    denomination: Amount,
+    quantity: usize,
+}
+
+#[apply(async_trait_maybe_send!)]
+impl<Note: Send> NotesSelector<Note> for SelectNotesOfDenomination {
+    async fn select_notes(
+        &self,
+        #[cfg(not(target_family = "wasm"))] stream: impl futures::Stream<Item = (Amount, Note)> + Send,
+        #[cfg(target_family = "wasm")] stream: impl futures::Stream<Item = (Amount, Note)>,
+        _requested_amount: Amount,
+        _fee_consensus: FeeConsensus,
+    ) -> anyhow::Result<TieredMulti<Note>> {
+        let selected = stream
+            .filter(|(amount, _)| std::future::ready(*amount == self.denomination))
+            .take(self.quantity)
+            .collect::<Vec<_>>()
+            .await;
+        if selected.len() < self.quantity {
+            bail!(
+                "Only {} notes of denomination {} available, {} required",
+                selected.len(),
+                self.denomination,
+                self.quantity
+            );
+        }
+        Ok(selected.into_iter().collect())
+    }
+}
+
+async fn send_metric(
+    event_sender: &MetricSender,
+    name: String,
+    duration: Duration,
+) -> anyhow::Result<()> {
+    event_sender.send(MetricEvent { name, duration }).await
+}
@@ -1,40 +1,30 @@
 #![deny(clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex, Once};
 
 use axum::http::StatusCode;
 use axum::routing::get;
 use axum::Router;
+use fedimint_core::metrics::{set_metrics_backend, Counter, IMetrics};
 use fedimint_core::task::{TaskGroup, TaskShutdownToken};
 use prometheus::Registry;
 pub use prometheus::{
-    self, histogram_opts, opts, register_histogram_with_registry,
-    register_int_counter_vec_with_registry, Encoder, Gauge, GaugeVec, Histogram, HistogramVec,
-    IntCounter, IntCounterVec, TextEncoder,
+    self, histogram_opts, opts, register_histogram_vec_with_registry,
+    register_histogram_with_registry, register_int_counter_vec_with_registry,
+    register_int_gauge_vec_with_registry, Encoder, Gauge, GaugeVec, Histogram, HistogramVec,
+    IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
 };
 use tokio::net::TcpListener;
-use tracing::error;
+use tracing::{error, info, warn};
 
 pub static REGISTRY: LazyLock<Registry> =
     LazyLock::new(|| Registry::new_custom(Some("fm".into()), None).unwrap());
 
-pub static AMOUNTS_BUCKETS_SATS: LazyLock<Vec<f64>> = LazyLock::new(|| {
-    vec![
-        0.0,
-        0.1,
-        1.0,
-        10.0,
-        100.0,
-        1000.0,
-        10000.0,
-        100_000.0,
-        1_000_000.0,
-        10_000_000.0,
-        100_000_000.0,
-    ]
-});
+pub static AMOUNTS_BUCKETS_SATS: LazyLock<Vec<f64>> =
+    LazyLock::new(|| fedimint_core::metrics::AMOUNTS_BUCKETS_SATS.to_vec());
 
 async fn get_metrics() -> (StatusCode, String) {
     let metric_families = REGISTRY.gather();
@@ -50,10 +40,126 @@ async fn get_metrics() -> (StatusCode, String) {
     }
 }
 
+/// Backend of the [`fedimint_core::metrics`] facade registering its metrics
+/// in [`REGISTRY`] on first use
+#[derive(Debug, Default)]
+pub struct PrometheusMetrics {
+    counters: Mutex<HashMap<&'static str, IntCounterVec>>,
+    gauges: Mutex<HashMap<&'static str, IntGaugeVec>>,
+    histograms: Mutex<HashMap<&'static str, HistogramVec>>,
+}
+
+impl PrometheusMetrics {
+    fn with_gauge(
+        &self,
+        gauge: &fedimint_core::metrics::Gauge,
+        label_values: &[&str],
+        f: impl FnOnce(&IntGauge),
+    ) {
+        let mut gauges = self.gauges.lock().expect("Locking can't fail");
+        if !gauges.contains_key(gauge.name) {
+            let Ok(gauge_vec) = register_int_gauge_vec_with_registry!(
+                opts!(gauge.name, gauge.help),
+                gauge.label_names,
+                REGISTRY
+            ) else {
+                warn!(name = gauge.name, "Failed to register gauge");
+                return;
+            };
+            gauges.insert(gauge.name, gauge_vec);
+        }
+        match gauges[gauge.name].get_metric_with_label_values(label_values) {
+            Ok(gauge) => f(&gauge),
+            Err(e) => warn!(name = gauge.name, "Invalid gauge labels: {e}"),
+        }
+    }
+
+    fn with_histogram(
+        &self,
+        histogram: &fedimint_core::metrics::Histogram,
+        label_values: &[&str],
+        f: impl FnOnce(&Histogram),
+    ) {
+        let mut histograms = self.histograms.lock().expect("Locking can't fail");
+        if !histograms.contains_key(histogram.name) {
+            let Ok(histogram_vec) = register_histogram_vec_with_registry!(
+                histogram_opts!(histogram.name, histogram.help, histogram.buckets.to_vec()),
+                histogram.label_names,
+                REGISTRY
+            ) else {
+                warn!(name = histogram.name, "Failed to register histogram");
+                return;
+            };
+            histograms.insert(histogram.name, histogram_vec);
+        }
+        match histograms[histogram.name].get_metric_with_label_values(label_values) {
+            Ok(histogram) => f(&histogram),
+            Err(e) => warn!(name = histogram.name, "Invalid histogram labels: {e}"),
+        }
+    }
+}
+
+impl IMetrics for PrometheusMetrics {
+    fn inc_counter(&self, counter: &Counter, label_values: &[&str], by: u64) {
+        let mut counters = self.counters.lock().expect("Locking can't fail");
+        if !counters.contains_key(counter.name) {
+            let Ok(counter_vec) = register_int_counter_vec_with_registry!(
+                opts!(counter.name, counter.help),
+                counter.label_names,
+                REGISTRY
+            ) else {
+                warn!(name = counter.name, "Failed to register counter");
+                return;
+            };
+            counters.insert(counter.name, counter_vec);
+        }
+        match counters[counter.name].get_metric_with_label_values(label_values) {
+            Ok(counter) => counter.inc_by(by),
+            Err(e) => warn!(name = counter.name, "Invalid counter labels: {e}"),
+        }
+    }
+
+    fn set_gauge(&self, gauge: &fedimint_core::metrics::Gauge, label_values: &[&str], value: i64) {
+        self.with_gauge(gauge, label_values, |gauge| gauge.set(value));
+    }
+
+    fn add_gauge(&self, gauge: &fedimint_core::metrics::Gauge, label_values: &[&str], delta: i64) {
+        self.with_gauge(gauge, label_values, |gauge| gauge.add(delta));
+    }
+
+    fn observe_histogram(
+        &self,
+        histogram: &fedimint_core::metrics::Histogram,
+        label_values: &[&str],
+        value: f64,
+    ) {
+        self.with_histogram(histogram, label_values, |histogram| {
+            histogram.observe(value);
+        });
+    }
+
+    fn init_histogram(&self, histogram: &fedimint_core::metrics::Histogram, label_values: &[&str]) {
+        self.with_histogram(histogram, label_values, |_| {});
+    }
+}
+
+/// Records the metrics of the [`fedimint_core::metrics`] facade in
+/// [`REGISTRY`] from now on, can be called repeatedly
+pub fn install_prometheus_backend() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| match set_metrics_backend(PrometheusMetrics::default()) {
+        Ok(()) => info!("Exporting facade metrics via prometheus"),
+        Err(e) => warn!("Facade metrics will not be exported via prometheus: {e}"),
+    });
+}
+
+/// Serves the metrics of [`REGISTRY`] on `bind_address`, recording the
+/// metrics of the [`fedimint_core::metrics`] facade in it as well
 pub async fn run_api_server(
     bind_address: SocketAddr,
     task_group: TaskGroup,
 ) -> anyhow::Result<TaskShutdownToken> {
+    install_prometheus_backend();
     let app = Router::new().route("/metrics", get(get_metrics));
     let listener = TcpListener::bind(bind_address).await?;
     let serve = axum::serve(listener, app.into_make_service());
@@ -73,3 +179,89 @@ pub async fn run_api_server(
 
     Ok(shutdown_receiver)
 }
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use fedimint_core::metrics::{Counter, Gauge, Histogram, IMetrics};
+    use prometheus::proto::MetricFamily;
+
+    use super::{PrometheusMetrics, REGISTRY};
+
+    fn gather(name: &str) -> MetricFamily {
+        REGISTRY
+            .gather()
+            .into_iter()
+            .find(|family| family.get_name() == name)
+            .unwrap_or_else(|| panic!("{name} is not registered"))
+    }
+
+    fn label_values(family: &MetricFamily) -> Vec<Vec<String>> {
+        family
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .map(|label| label.get_value().to_owned())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn registers_facade_metrics_on_first_use() {
+        static COUNTER: Counter = Counter::new("test_backend_total", "Test counter", &["peer"]);
+        static GAUGE: Gauge = Gauge::new("test_backend_gauge", "Test gauge", &[]);
+        static HISTOGRAM: Histogram =
+            Histogram::new("test_backend_seconds", "Test histogram", &["method"])
+                .with_buckets(&[1.0, 10.0]);
+
+        let metrics = PrometheusMetrics::default();
+        metrics.inc_counter(&COUNTER, &["0"], 2);
+        metrics.inc_counter(&COUNTER, &["0"], 1);
+        metrics.inc_counter(&COUNTER, &["1"], 0);
+        metrics.set_gauge(&GAUGE, &[], 7);
+        metrics.add_gauge(&GAUGE, &[], -2);
+        metrics.observe_histogram(&HISTOGRAM, &["status"], 5.0);
+        metrics.init_histogram(&HISTOGRAM, &["version"]);
+
+        let counter = gather("fm_test_backend_total");
+        assert_eq!(label_values(&counter), vec![vec!["0"], vec!["1"]]);
+        assert_eq!(counter.get_metric()[0].get_counter().get_value(), 3.0);
+        assert_eq!(counter.get_metric()[1].get_counter().get_value(), 0.0);
+
+        let gauge = gather("fm_test_backend_gauge");
+        assert_eq!(gauge.get_metric()[0].get_gauge().get_value(), 5.0);
+
+        let histogram = gather("fm_test_backend_seconds");
+        assert_eq!(
+            label_values(&histogram),
+            vec![vec!["status"], vec!["version"]]
+        );
+        let status = histogram.get_metric()[0].get_histogram();
+        assert_eq!(status.get_sample_count(), 1);
+        assert_eq!(status.get_bucket()[0].get_cumulative_count(), 0);
+        assert_eq!(status.get_bucket()[1].get_cumulative_count(), 1);
+        assert_eq!(
+            histogram.get_metric()[1].get_histogram().get_sample_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn ignores_records_with_wrong_labels() {
+        static COUNTER: Counter =
+            Counter::new("test_backend_labels_total", "Test counter", &["peer"]);
+
+        let metrics = PrometheusMetrics::default();
+        metrics.inc_counter(&COUNTER, &[], 1);
+        metrics.inc_counter(&COUNTER, &["0", "1"], 1);
+        metrics.inc_counter(&COUNTER, &["0"], 1);
+
+        let counter = gather("fm_test_backend_labels_total");
+        assert_eq!(label_values(&counter), vec![vec!["0"]]);
+        assert_eq!(counter.get_metric()[0].get_counter().get_value(), 1.0);
+    }
+}
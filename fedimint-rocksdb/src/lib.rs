@@ -206,6 +206,10 @@ async fn raw_insert_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<Option<
         })
     }
 
+    async fn raw_put_bytes(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        fedimint_core::runtime::block_in_place(|| Ok(self.0.put(key, value)?))
+    }
+
     async fn raw_get_bytes(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         fedimint_core::runtime::block_in_place(|| Ok(self.0.snapshot().get(key)?))
     }
@@ -327,6 +331,12 @@ async fn set_tx_savepoint(&mut self) -> Result<()> {
 
         Ok(())
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        // RocksDB does not expose popping a savepoint of a transaction, released
+        // savepoints are freed together with the transaction.
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -432,6 +442,10 @@ async fn rollback_tx_to_savepoint(&mut self) -> Result<()> {
     async fn set_tx_savepoint(&mut self) -> Result<()> {
         panic!("Cannot set a savepoint in a read only transaction");
     }
+
+    async fn release_tx_savepoint(&mut self) -> Result<()> {
+        panic!("Cannot release a savepoint in a read only transaction");
+    }
 }
 
 #[async_trait]
@@ -43,6 +43,7 @@
 use crate::config::{gen_cert_and_key, ConfigGenParams, ServerConfig};
 use crate::envs::FM_PEER_ID_SORT_BY_URL_ENV;
 use crate::net::api::{check_auth, ApiResult, HasApiContext};
+use crate::net::tor::TorControlSettings;
 
 /// Serves the config gen API endpoints
 #[derive(Clone)]
@@ -588,10 +589,15 @@ pub struct ConfigGenSettings {
     pub p2p_bind: SocketAddr,
     /// Bind address for our API connection
     pub api_bind: SocketAddr,
+    /// Additional bind addresses for our API connection, e.g. an IPv6 address
+    /// or a local port a Tor onion service forwards to
+    pub api_bind_extra: Vec<SocketAddr>,
     /// URL for our P2P connection
     pub p2p_url: SafeUrl,
     /// URL for our API connection
     pub api_url: SafeUrl,
+    /// Tor daemon to expose our API as an onion service with, if any
+    pub tor_control: Option<TorControlSettings>,
     /// The default params for the modules
     pub default_params: ConfigGenParamsRequest,
     /// How many API connections we will accept
@@ -600,6 +606,19 @@ pub struct ConfigGenSettings {
     pub registry: ServerModuleInitRegistry,
 }
 
+impl ConfigGenSettings {
+    /// All addresses the API should listen on, deduplicated
+    pub fn api_binds(&self) -> Vec<SocketAddr> {
+        let mut binds = vec![self.api_bind];
+        for bind in &self.api_bind_extra {
+            if !binds.contains(bind) {
+                binds.push(*bind);
+            }
+        }
+        binds
+    }
+}
+
 /// State held by the API after receiving a `ConfigGenConnectionsRequest`
 #[derive(Debug, Clone)]
 pub struct ConfigGenState {
@@ -778,6 +797,7 @@ fn reset(&mut self) {
 impl HasApiContext<ConfigGenApi> for ConfigGenApi {
     async fn context(
         &self,
+        _method: &str,
         request: &ApiRequestErased,
         id: Option<ModuleInstanceId>,
     ) -> (&ConfigGenApi, ApiEndpointContext<'_>) {
@@ -1002,8 +1022,10 @@ fn new(port: u16, name_suffix: u16, data_dir: &Path) -> TestConfigApi {
                 download_token_limit: None,
                 p2p_bind,
                 api_bind,
+                api_bind_extra: vec![],
                 p2p_url,
                 api_url: api_url.clone(),
+                tor_control: None,
                 default_params,
                 max_connections: DEFAULT_MAX_CLIENT_CONNECTIONS,
                 registry: ServerModuleInitRegistry::from(vec![DynServerModuleInit::from(
@@ -8,7 +8,7 @@
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use crate::config::ServerConfig;
+use crate::config::{ServerConfig, ServerConfigConsensus};
 
 /// Client configuration file
 pub const CLIENT_CONFIG: &str = "client";
@@ -39,6 +39,12 @@
 
 pub const ENCRYPTED_EXT: &str = "encrypt";
 
+/// Reads only the consensus cfg file, which unlike the private cfg file can
+/// be read without the password
+pub fn read_server_consensus_config(path: &Path) -> anyhow::Result<ServerConfigConsensus> {
+    plaintext_json_read(&path.join(CONSENSUS_CONFIG))
+}
+
 /// Reads the server from the local, private, and consensus cfg files
 pub fn read_server_config(password: &str, path: &Path) -> anyhow::Result<ServerConfig> {
     let salt = fs::read_to_string(path.join(SALT_FILE))?;
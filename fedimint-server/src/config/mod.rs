@@ -37,9 +37,10 @@
 use crate::fedimint_core::encoding::Encodable;
 use crate::fedimint_core::NumPeersExt;
 use crate::multiplexed::PeerConnectionMultiplexer;
-use crate::net::connect::{dns_sanitize, Connector, TlsConfig};
+use crate::net::connect::{
+    dns_sanitize, tor_socks5_proxy_from_env, Connector, TlsConfig, TransportConnector,
+};
 use crate::net::peers::{NetworkConfig, ReconnectPeerConnections};
-use crate::TlsTcpConnector;
 
 pub mod api;
 pub mod distributedgen;
@@ -203,7 +204,7 @@ impl ServerConfig {
     pub fn supported_api_versions() -> SupportedCoreApiVersions {
         SupportedCoreApiVersions {
             core_consensus: CORE_CONSENSUS_VERSION,
-            api: MultiApiVersion::try_from_iter([ApiVersion { major: 0, minor: 4 }])
+            api: MultiApiVersion::try_from_iter([ApiVersion { major: 0, minor: 7 }])
                 .expect("not version conflicts"),
         }
     }
@@ -442,7 +443,7 @@ pub async fn distributed_gen(
             params.tls_config(),
             task_group,
         )
-        .await;
+        .await?;
         let connections = PeerConnectionMultiplexer::new(server_conn).into_dyn();
 
         let peers = &params.peer_ids();
@@ -694,18 +695,24 @@ pub async fn connect<T>(
     network: NetworkConfig,
     certs: TlsConfig,
     task_group: &TaskGroup,
-) -> PeerConnections<T>
+) -> anyhow::Result<PeerConnections<T>>
 where
     T: std::fmt::Debug + Clone + Serialize + DeserializeOwned + Unpin + Send + Sync + 'static,
 {
-    let connector = TlsTcpConnector::new(certs, network.identity).into_dyn();
+    let connector = TransportConnector::new(
+        certs,
+        network.identity,
+        &network.peers[&network.identity],
+        tor_socks5_proxy_from_env()?,
+    )
+    .into_dyn();
 
     let connection_status_channels = Arc::new(RwLock::new(BTreeMap::new()));
     let connections =
         ReconnectPeerConnections::new(network, connector, task_group, connection_status_channels)
             .await;
 
-    connections.into_dyn()
+    Ok(connections.into_dyn())
 }
 
 pub fn gen_cert_and_key(
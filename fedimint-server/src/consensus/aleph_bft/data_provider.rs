@@ -1,14 +1,17 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::time::Instant;
 
 use bitcoin::hashes::Hash;
 use fedimint_core::config::ALEPH_BFT_UNIT_BYTE_LIMIT;
+use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::encoding::Encodable;
 use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::session_outcome::SchnorrSignature;
 use fedimint_core::TransactionId;
 use tokio::sync::watch;
 
+use crate::consensus::quota::ModuleByteQuotas;
+use crate::metrics::CONSENSUS_ITEMS_OVER_QUOTA_TOTAL;
 use crate::LOG_CONSENSUS;
 
 #[derive(
@@ -35,6 +38,9 @@ pub struct DataProvider {
     signature_receiver: watch::Receiver<Option<SchnorrSignature>>,
     submitted_transactions: BTreeSet<TransactionId>,
     leftover_item: Option<ConsensusItem>,
+    quotas: ModuleByteQuotas,
+    /// Bytes of the items each module proposed in this session so far
+    module_bytes: BTreeMap<ModuleInstanceId, usize>,
     // Since it's possible that `fedimintd` after restart will receive citems it
     // sent before restart, we use cheap citem's chsum, as a simple method
     // to self-synchronize. See <https://github.com/fedimint/fedimint/pull/5432#issuecomment-2176860609>
@@ -47,15 +53,33 @@ pub fn new(
         mempool_item_receiver: async_channel::Receiver<ConsensusItem>,
         signature_receiver: watch::Receiver<Option<SchnorrSignature>>,
         timestamp_sender: async_channel::Sender<(Instant, u64)>,
+        quotas: ModuleByteQuotas,
     ) -> Self {
         Self {
             mempool_item_receiver,
             signature_receiver,
             submitted_transactions: BTreeSet::new(),
             leftover_item: None,
+            quotas,
+            module_bytes: BTreeMap::new(),
             timestamp_sender,
         }
     }
+
+    /// Accounts for an item of `n_bytes` proposed by the module, returns false
+    /// if the item would exceed the module's quota for this session
+    fn consume_quota(&mut self, module_id: ModuleInstanceId, n_bytes: usize) -> bool {
+        let used = self.module_bytes.entry(module_id).or_default();
+
+        if !self.quotas.admits(module_id, *used, n_bytes) {
+            CONSENSUS_ITEMS_OVER_QUOTA_TOTAL.inc(&[&module_id.to_string()]);
+            return false;
+        }
+
+        *used += n_bytes;
+
+        true
+    }
 }
 
 #[async_trait::async_trait]
@@ -92,6 +116,15 @@ async fn get_data(&mut self) -> Option<UnitData> {
 
             let n_bytes_item = item.consensus_encode_to_vec().len();
 
+            // the module will propose the item again, so it is only deferred to
+            // a session in which the module has not used up its quota yet
+            if let ConsensusItem::Module(module_item) = &item {
+                if !self.consume_quota(module_item.module_instance_id(), n_bytes_item) {
+                    tracing::debug!(target: LOG_CONSENSUS, ?item, "Module consensus item exceeds the module's quota");
+                    continue;
+                }
+            }
+
             if n_bytes + n_bytes_item <= ALEPH_BFT_UNIT_BYTE_LIMIT {
                 n_bytes += n_bytes_item;
                 items.push(item);
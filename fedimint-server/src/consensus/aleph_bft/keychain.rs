@@ -32,24 +32,30 @@ pub fn new(cfg: &ServerConfig) -> Self {
         }
     }
 
-    // Tagging messages with the hash of the public key set ensures that peers with
-    // an incorrect public key set cannot create signatures that are accepted by
-    // their peers.
     fn tagged_message(&self, message: &[u8]) -> Message {
-        let mut engine = sha256::HashEngine::default();
+        tagged_message(&self.message_tag, message)
+    }
+}
 
-        engine
-            .write_all(self.message_tag.as_ref())
-            .expect("Writing to a hash engine can not fail");
+/// The message the guardians sign for `message`, with `message_tag` being the
+/// consensus hash of their broadcast public keys.
+// Tagging messages with the hash of the public key set ensures that peers with
+// an incorrect public key set cannot create signatures that are accepted by
+// their peers.
+pub fn tagged_message(message_tag: &sha256::Hash, message: &[u8]) -> Message {
+    let mut engine = sha256::HashEngine::default();
 
-        engine
-            .write_all(message)
-            .expect("Writing to a hash engine can not fail");
+    engine
+        .write_all(message_tag.as_ref())
+        .expect("Writing to a hash engine can not fail");
 
-        let hash = sha256::Hash::from_engine(engine);
+    engine
+        .write_all(message)
+        .expect("Writing to a hash engine can not fail");
 
-        Message::from_digest(*hash.as_ref())
-    }
+    let hash = sha256::Hash::from_engine(engine);
+
+    Message::from_digest(*hash.as_ref())
 }
 
 impl aleph_bft::Index for Keychain {
@@ -3,13 +3,15 @@
 use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bitcoin::hashes::sha256;
 use fedimint_aead::{encrypt, get_encryption_key, random_salt};
 use fedimint_api_client::api::{
-    FederationStatus, GuardianConfigBackup, PeerConnectionStatus, PeerStatus, StatusResponse,
+    FederationStatus, GuardianConfigBackup, PeerConnectionStatus, PeerConnectivityStatus,
+    PeerStatus, StatusResponse,
 };
 use fedimint_core::admin_client::ServerStatus;
 use fedimint_core::backup::{ClientBackupKey, ClientBackupSnapshot};
@@ -20,22 +22,25 @@
     Committable, Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped,
 };
 use fedimint_core::endpoint_constants::{
-    API_ANNOUNCEMENTS_ENDPOINT, AUDIT_ENDPOINT, AUTH_ENDPOINT, AWAIT_OUTPUT_OUTCOME_ENDPOINT,
-    AWAIT_SESSION_OUTCOME_ENDPOINT, AWAIT_SIGNED_SESSION_OUTCOME_ENDPOINT,
-    AWAIT_TRANSACTION_ENDPOINT, BACKUP_ENDPOINT, CLIENT_CONFIG_ENDPOINT,
-    CLIENT_CONFIG_JSON_ENDPOINT, FEDERATION_ID_ENDPOINT, FEDIMINTD_VERSION_ENDPOINT,
-    GUARDIAN_CONFIG_BACKUP_ENDPOINT, INVITE_CODE_ENDPOINT, RECOVER_ENDPOINT,
+    ADD_ADMIN_KEY_ENDPOINT, ADMIN_KEYS_ENDPOINT, API_ANNOUNCEMENTS_ENDPOINT, AUDIT_ENDPOINT,
+    AUTH_ENDPOINT, AWAIT_OUTPUT_OUTCOME_ENDPOINT, AWAIT_SESSION_OUTCOME_ENDPOINT,
+    AWAIT_SIGNED_SESSION_OUTCOME_ENDPOINT, AWAIT_TRANSACTION_ENDPOINT, BACKUP_ENDPOINT,
+    CLIENT_CONFIG_ENDPOINT, CLIENT_CONFIG_JSON_ENDPOINT, FEDERATION_ID_ENDPOINT,
+    FEDIMINTD_VERSION_ENDPOINT, FEE_REVENUE_ENDPOINT, GUARDIAN_CONFIG_BACKUP_ENDPOINT,
+    INVITE_CODE_ENDPOINT, PEER_CONNECTIVITY_ENDPOINT, RECOVER_ENDPOINT, REMOVE_ADMIN_KEY_ENDPOINT,
     SERVER_CONFIG_CONSENSUS_HASH_ENDPOINT, SESSION_COUNT_ENDPOINT, SESSION_STATUS_ENDPOINT,
-    SHUTDOWN_ENDPOINT, SIGN_API_ANNOUNCEMENT_ENDPOINT, STATUS_ENDPOINT,
-    SUBMIT_API_ANNOUNCEMENT_ENDPOINT, SUBMIT_TRANSACTION_ENDPOINT, VERSION_ENDPOINT,
+    SHUTDOWN_ENDPOINT, SIGN_API_ANNOUNCEMENT_ENDPOINT, STATE_SNAPSHOT_CHUNK_ENDPOINT,
+    STATE_SNAPSHOT_INFO_ENDPOINT, STATUS_ENDPOINT, SUBMIT_API_ANNOUNCEMENT_ENDPOINT,
+    SUBMIT_TRANSACTION_ENDPOINT, VERSION_ENDPOINT,
 };
-use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::epoch::{ConsensusItem, FeeRevenue};
 use fedimint_core::module::audit::{Audit, AuditSummary};
 use fedimint_core::module::registry::ServerModuleRegistry;
 use fedimint_core::module::{
     api_endpoint, ApiEndpoint, ApiEndpointContext, ApiError, ApiRequestErased, ApiVersion,
     SerdeModuleEncoding, SupportedApiVersionsSummary,
 };
+use fedimint_core::net::admin_auth::AddAdminKeyRequest;
 use fedimint_core::net::api_announcement::{
     ApiAnnouncement, SignedApiAnnouncement, SignedApiAnnouncementSubmission,
 };
@@ -56,13 +61,20 @@
     CONSENSUS_CONFIG, ENCRYPTED_EXT, JSON_EXT, LOCAL_CONFIG, PRIVATE_CONFIG, SALT_FILE,
 };
 use crate::config::ServerConfig;
-use crate::consensus::db::{AcceptedItemPrefix, AcceptedTransactionKey, SignedSessionOutcomeKey};
+use crate::consensus::db::{
+    AcceptedItemPrefix, AcceptedTransactionKey, FeeRevenueKey, SignedSessionOutcomeKey,
+};
 use crate::consensus::engine::get_finished_session_count_static;
+use crate::consensus::snapshot::{
+    read_latest_snapshot_info, read_snapshot_chunk, StateSnapshotInfo, STATE_SNAPSHOTS_DIR,
+};
 use crate::consensus::transaction::process_transaction_with_dbtx;
 use crate::fedimint_core::encoding::Encodable;
 use crate::metrics::{BACKUP_WRITE_SIZE_BYTES, STORED_BACKUPS_COUNT};
+use crate::net::api::admin_auth::{AdminKey, AdminKeyPrefix, AdminRequestVerifier};
 use crate::net::api::announcement::{ApiAnnouncementKey, ApiAnnouncementPrefix};
 use crate::net::api::{check_auth, ApiResult, GuardianAuthToken, HasApiContext};
+use crate::net::peers::PeerConnectivity;
 
 #[derive(Clone)]
 pub struct ConsensusApi {
@@ -80,10 +92,16 @@ pub struct ConsensusApi {
     pub submission_sender: async_channel::Sender<ConsensusItem>,
     pub shutdown_receiver: watch::Receiver<Option<u64>>,
     pub shutdown_sender: watch::Sender<Option<u64>>,
-    pub connection_status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+    pub connection_status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
     pub last_ci_by_peer: Arc<RwLock<BTreeMap<PeerId, u64>>>,
     pub supported_api_versions: SupportedApiVersionsSummary,
     pub code_version_str: String,
+    /// Replay protection for requests signed with an admin key
+    pub admin_request_verifier: Arc<AdminRequestVerifier>,
+    /// Reject the guardian password once admin keys are authorized
+    pub disable_password_auth_with_admin_keys: bool,
+    /// Data directory holding the state snapshots we serve
+    pub data_dir: PathBuf,
 }
 
 impl ConsensusApi {
@@ -218,7 +236,8 @@ pub async fn get_federation_status(&self) -> ApiResult<FederationStatus> {
 
         let status_by_peer = peers_connection_status
             .into_iter()
-            .map(|(peer, connection_status)| {
+            .map(|(peer, connectivity)| {
+                let connection_status = connectivity.status;
                 let last_contribution = last_ci_by_peer.get(&peer).copied();
                 let flagged = last_contribution.unwrap_or(0) + 1 < session_count;
 
@@ -257,6 +276,138 @@ pub async fn get_federation_status(&self) -> ApiResult<FederationStatus> {
         })
     }
 
+    pub async fn fee_revenue(&self) -> FeeRevenue {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .get_value(&FeeRevenueKey)
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn get_peer_connectivity(
+        &self,
+        _auth: &GuardianAuthToken,
+    ) -> BTreeMap<PeerId, PeerConnectivityStatus> {
+        let peers_connectivity = self.connection_status_channels.read().await.clone();
+        let last_ci_by_peer = self.last_ci_by_peer.read().await.clone();
+        let session_count = self.session_count().await;
+
+        peers_connectivity
+            .into_iter()
+            .map(|(peer, connectivity)| {
+                let last_contribution = last_ci_by_peer.get(&peer).copied();
+                let session_lag = match last_contribution {
+                    Some(session) => session_count.saturating_sub(session + 1),
+                    None => session_count,
+                };
+
+                let status = PeerConnectivityStatus {
+                    connection_status: connectivity.status,
+                    last_contact: connectivity.last_contact.map(|last_contact| {
+                        last_contact
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs()
+                    }),
+                    last_contribution,
+                    session_lag,
+                    connection_errors: connectivity.connection_errors,
+                    last_error: connectivity.last_error,
+                };
+
+                (peer, status)
+            })
+            .collect()
+    }
+
+    /// Whether the request to `method` was signed for it by an admin key the
+    /// guardian authorized
+    async fn has_admin_signature(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_, Committable>,
+        method: &str,
+        request: &ApiRequestErased,
+    ) -> bool {
+        let Some(signature) = &request.admin_signature else {
+            return false;
+        };
+
+        dbtx.get_value(&AdminKey(signature.public_key))
+            .await
+            .is_some()
+            && self
+                .admin_request_verifier
+                .verify(method, signature, &request.params)
+    }
+
+    /// Whether the guardian password is still accepted, which is no longer the
+    /// case once admin keys are authorized if the guardian opted into it
+    async fn is_password_auth_enabled(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_, Committable>,
+    ) -> bool {
+        !self.disable_password_auth_with_admin_keys
+            || dbtx
+                .find_by_prefix(&AdminKeyPrefix)
+                .await
+                .next()
+                .await
+                .is_none()
+    }
+
+    /// Unlike [`check_auth`] only accepts the guardian password, used for
+    /// endpoints that admin keys must not be able to call
+    fn check_password_auth(
+        &self,
+        context: &mut ApiEndpointContext,
+    ) -> ApiResult<GuardianAuthToken> {
+        let auth = check_auth(context)?;
+        if context.request_auth() == Some(self.cfg.private.api_auth.clone()) {
+            Ok(auth)
+        } else {
+            Err(ApiError::unauthorized())
+        }
+    }
+
+    async fn add_admin_key(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        request: AddAdminKeyRequest,
+        _auth: &GuardianAuthToken,
+    ) {
+        info!(target: LOG_NET_API, label = %request.label, "Authorizing admin key");
+        dbtx.insert_entry(&AdminKey(request.public_key), &request.label)
+            .await;
+    }
+
+    async fn remove_admin_key(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        public_key: PublicKey,
+        _auth: &GuardianAuthToken,
+    ) -> ApiResult<()> {
+        match dbtx.remove_entry(&AdminKey(public_key)).await {
+            Some(label) => {
+                info!(target: LOG_NET_API, %label, "Revoked admin key");
+                Ok(())
+            }
+            None => Err(ApiError::not_found("Unknown admin key".to_string())),
+        }
+    }
+
+    async fn admin_keys(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        _auth: &GuardianAuthToken,
+    ) -> BTreeMap<PublicKey, String> {
+        dbtx.find_by_prefix(&AdminKeyPrefix)
+            .await
+            .map(|(AdminKey(public_key), label)| (public_key, label))
+            .collect()
+            .await
+    }
+
     fn shutdown(&self, index: Option<u64>) {
         self.shutdown_sender.send_replace(index);
     }
@@ -375,9 +526,9 @@ async fn handle_backup_request<'s, 'dbtx, 'a>(
             )
             .await
             .is_some();
-        BACKUP_WRITE_SIZE_BYTES.observe(request.payload.len() as f64);
+        BACKUP_WRITE_SIZE_BYTES.observe(&[], request.payload.len() as f64);
         if !overwritten {
-            dbtx.on_commit(|| STORED_BACKUPS_COUNT.inc());
+            dbtx.on_commit(|| STORED_BACKUPS_COUNT.inc(&[]));
         }
 
         Ok(())
@@ -409,6 +560,31 @@ fn fedimintd_version(&self) -> String {
         self.code_version_str.clone()
     }
 
+    /// Returns the info of our latest state snapshot, see
+    /// [`crate::consensus::snapshot`]
+    async fn state_snapshot_info(&self) -> ApiResult<Option<StateSnapshotInfo>> {
+        read_latest_snapshot_info(&self.data_dir.join(STATE_SNAPSHOTS_DIR))
+            .await
+            .map_err(|e| ApiError::server_error(e.to_string()))
+    }
+
+    /// Returns a hex encoded chunk of the state snapshot taken after
+    /// `session_count` sessions
+    async fn state_snapshot_chunk(
+        &self,
+        session_count: u64,
+        chunk_index: u64,
+    ) -> ApiResult<String> {
+        read_snapshot_chunk(
+            &self.data_dir.join(STATE_SNAPSHOTS_DIR),
+            session_count,
+            chunk_index,
+        )
+        .await
+        .map(hex::encode)
+        .map_err(|e| ApiError::not_found(e.to_string()))
+    }
+
     /// Add an API URL announcement from a peer to our database to be returned
     /// by [`ConsensusApi::api_announcements`].
     async fn submit_api_announcement(
@@ -487,24 +663,26 @@ async fn sign_api_announcement(&self, new_url: SafeUrl) -> SignedApiAnnouncement
 impl HasApiContext<ConsensusApi> for ConsensusApi {
     async fn context(
         &self,
+        method: &str,
         request: &ApiRequestErased,
         id: Option<ModuleInstanceId>,
     ) -> (&ConsensusApi, ApiEndpointContext<'_>) {
         let mut db = self.db.clone();
         let mut dbtx = self.db.begin_transaction().await;
+        // Dropping the password here also keeps it from passing
+        // `check_password_auth`
+        let password = if self.is_password_auth_enabled(&mut dbtx).await {
+            request.auth.clone()
+        } else {
+            None
+        };
+        let has_auth = password == Some(self.cfg.private.api_auth.clone())
+            || self.has_admin_signature(&mut dbtx, method, request).await;
         if let Some(id) = id {
             db = self.db.with_prefix_module_id(id).0;
             dbtx = dbtx.with_prefix_module_id(id).0;
         }
-        (
-            self,
-            ApiEndpointContext::new(
-                db,
-                dbtx,
-                request.auth == Some(self.cfg.private.api_auth.clone()),
-                request.auth.clone(),
-            ),
-        )
+        (self, ApiEndpointContext::new(db, dbtx, has_auth, password))
     }
 }
 
@@ -512,10 +690,11 @@ async fn context(
 impl HasApiContext<DynServerModule> for ConsensusApi {
     async fn context(
         &self,
+        method: &str,
         request: &ApiRequestErased,
         id: Option<ModuleInstanceId>,
     ) -> (&DynServerModule, ApiEndpointContext<'_>) {
-        let (_, context): (&ConsensusApi, _) = self.context(request, id).await;
+        let (_, context): (&ConsensusApi, _) = self.context(method, request, id).await;
         (
             self.modules.get_expect(id.expect("required module id")),
             context,
@@ -661,7 +840,7 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
             GUARDIAN_CONFIG_BACKUP_ENDPOINT,
             ApiVersion::new(0, 2),
             async |fedimint: &ConsensusApi, context, _v: ()| -> GuardianConfigBackup {
-                let auth = check_auth(context)?;
+                let auth = fedimint.check_password_auth(context)?;
                 let password = context.request_auth().expect("Auth was checked before").0;
                 Ok(fedimint.get_guardian_config_backup(&password, &auth))
             }
@@ -714,6 +893,46 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok(fedimint.sign_api_announcement(new_url).await)
             }
         },
+        api_endpoint! {
+            PEER_CONNECTIVITY_ENDPOINT,
+            ApiVersion::new(0, 5),
+            async |fedimint: &ConsensusApi, context, _v: ()| -> BTreeMap<PeerId, PeerConnectivityStatus> {
+                let auth = check_auth(context)?;
+                Ok(fedimint.get_peer_connectivity(&auth).await)
+            }
+        },
+        api_endpoint! {
+            FEE_REVENUE_ENDPOINT,
+            ApiVersion::new(0, 6),
+            async |fedimint: &ConsensusApi, _context, _v: ()| -> FeeRevenue {
+                Ok(fedimint.fee_revenue().await)
+            }
+        },
+        api_endpoint! {
+            ADD_ADMIN_KEY_ENDPOINT,
+            ApiVersion::new(0, 5),
+            async |fedimint: &ConsensusApi, context, request: AddAdminKeyRequest| -> () {
+                let auth = fedimint.check_password_auth(context)?;
+                fedimint.add_admin_key(&mut context.dbtx().into_nc(), request, &auth).await;
+                Ok(())
+            }
+        },
+        api_endpoint! {
+            REMOVE_ADMIN_KEY_ENDPOINT,
+            ApiVersion::new(0, 5),
+            async |fedimint: &ConsensusApi, context, public_key: PublicKey| -> () {
+                let auth = fedimint.check_password_auth(context)?;
+                fedimint.remove_admin_key(&mut context.dbtx().into_nc(), public_key, &auth).await
+            }
+        },
+        api_endpoint! {
+            ADMIN_KEYS_ENDPOINT,
+            ApiVersion::new(0, 5),
+            async |fedimint: &ConsensusApi, context, _v: ()| -> BTreeMap<PublicKey, String> {
+                let auth = check_auth(context)?;
+                Ok(fedimint.admin_keys(&mut context.dbtx().into_nc(), &auth).await)
+            }
+        },
         api_endpoint! {
             FEDIMINTD_VERSION_ENDPOINT,
             ApiVersion::new(0, 4),
@@ -721,5 +940,20 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok(fedimint.fedimintd_version())
             }
         },
+        api_endpoint! {
+            STATE_SNAPSHOT_INFO_ENDPOINT,
+            ApiVersion::new(0, 7),
+            async |fedimint: &ConsensusApi, _context, _v: ()| -> Option<StateSnapshotInfo> {
+                fedimint.state_snapshot_info().await
+            }
+        },
+        api_endpoint! {
+            STATE_SNAPSHOT_CHUNK_ENDPOINT,
+            ApiVersion::new(0, 7),
+            async |fedimint: &ConsensusApi, _context, request: (u64, u64)| -> String {
+                let (session_count, chunk_index) = request;
+                fedimint.state_snapshot_chunk(session_count, chunk_index).await
+            }
+        },
     ]
 }
@@ -0,0 +1,328 @@
+//! Export of the federation's consensus history into an archive that can be
+//! analyzed and verified offline, without access to the guardians' API.
+//!
+//! # Format
+//!
+//! An archive is a directory containing a [`MANIFEST_FILE`] and one file per
+//! session, named by [`session_file_name`]. The manifest is the JSON encoding
+//! of an [`ArchiveManifest`]. Every session file holds the consensus encoding
+//! of a [`SessionArchive`] followed by the 32 byte SHA256 hash of that
+//! encoding as a checksum.
+//!
+//! A session is valid if its checksum matches and its outcome is signed by a
+//! threshold of the broadcast public keys listed in the manifest. Like in
+//! consensus, the guardians sign the session header (see
+//! [`fedimint_core::session_outcome::SessionOutcome::header`]) tagged with the
+//! consensus hash of their public key set. The manifest's keys are only as
+//! trustworthy as the archive itself, so auditors should compare them to the
+//! `broadcast_public_keys` of the federation's client config.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use anyhow::{ensure, Context};
+use bitcoin::hashes::{sha256, Hash};
+use fedimint_core::config::FederationId;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_core::secp256k1::{schnorr, PublicKey, SECP256K1};
+use fedimint_core::session_outcome::SignedSessionOutcome;
+use fedimint_core::{NumPeersExt, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::aleph_bft::keychain::tagged_message;
+use crate::consensus::db::SignedSessionOutcomeKey;
+
+/// Version of the archive format described in the [module docs](self)
+pub const ARCHIVE_VERSION: u16 = 1;
+
+/// Name of the manifest file in an archive directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const CHECKSUM_LEN: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u16,
+    pub federation_id: FederationId,
+    /// Keys the session outcomes are signed with
+    pub broadcast_public_keys: BTreeMap<PeerId, PublicKey>,
+    pub first_session: u64,
+    /// Last exported session, inclusive
+    pub last_session: u64,
+}
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct SessionArchive {
+    pub session_index: u64,
+    pub signed_session_outcome: SignedSessionOutcome,
+}
+
+/// Summary of a successfully verified archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveVerification {
+    pub federation_id: FederationId,
+    pub first_session: u64,
+    pub last_session: u64,
+    /// Number of accepted items across all sessions
+    pub items: u64,
+}
+
+pub fn session_file_name(session_index: u64) -> String {
+    format!("session-{session_index:012}.fmsa")
+}
+
+/// Writes the signed outcomes of `sessions` from `db` into an archive in
+/// `out_dir`. Fails if one of the sessions isn't complete yet.
+pub async fn export_sessions(
+    db: &Database,
+    federation_id: FederationId,
+    broadcast_public_keys: BTreeMap<PeerId, PublicKey>,
+    sessions: RangeInclusive<u64>,
+    out_dir: &Path,
+) -> anyhow::Result<()> {
+    ensure!(!sessions.is_empty(), "No sessions to export");
+    fs::create_dir_all(out_dir)?;
+
+    let mut dbtx = db.begin_transaction_nc().await;
+    for session_index in sessions.clone() {
+        let signed_session_outcome = dbtx
+            .get_value(&SignedSessionOutcomeKey(session_index))
+            .await
+            .with_context(|| format!("Session {session_index} is not complete"))?;
+
+        let mut bytes = SessionArchive {
+            session_index,
+            signed_session_outcome,
+        }
+        .consensus_encode_to_vec();
+        let checksum = sha256::Hash::hash(&bytes);
+        bytes.extend_from_slice(checksum.as_byte_array());
+
+        fs::write(out_dir.join(session_file_name(session_index)), bytes)?;
+    }
+
+    let manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        federation_id,
+        broadcast_public_keys,
+        first_session: *sessions.start(),
+        last_session: *sessions.end(),
+    };
+    fs::write(
+        out_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(())
+}
+
+/// Reads a session file, checking its checksum. Module specific items are
+/// decoded as unknown, which preserves their encoding.
+pub fn read_session_file(path: &Path) -> anyhow::Result<SessionArchive> {
+    let bytes = fs::read(path)?;
+    ensure!(bytes.len() >= CHECKSUM_LEN, "File is too short");
+
+    let (encoded, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    ensure!(
+        sha256::Hash::hash(encoded).as_byte_array() == checksum,
+        "Checksum mismatch"
+    );
+
+    Ok(SessionArchive::consensus_decode_from_finite_reader(
+        &mut Cursor::new(encoded),
+        &ModuleDecoderRegistry::default().with_fallback(),
+    )?)
+}
+
+/// Verifies the checksums and signatures of every session listed in the
+/// manifest of the archive in `dir`
+pub fn verify_archive(dir: &Path) -> anyhow::Result<ArchiveVerification> {
+    let manifest: ArchiveManifest =
+        serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE))?)?;
+    ensure!(
+        manifest.version == ARCHIVE_VERSION,
+        "Unsupported archive version {}",
+        manifest.version
+    );
+
+    let mut items = 0;
+    for session_index in manifest.first_session..=manifest.last_session {
+        let path = dir.join(session_file_name(session_index));
+        let archive = read_session_file(&path)
+            .with_context(|| format!("Invalid session file {}", path.display()))?;
+        ensure!(
+            archive.session_index == session_index,
+            "File {} contains session {}",
+            path.display(),
+            archive.session_index
+        );
+        verify_session_signatures(
+            &manifest.broadcast_public_keys,
+            session_index,
+            &archive.signed_session_outcome,
+        )
+        .with_context(|| format!("Invalid signatures for session {session_index}"))?;
+
+        items += archive.signed_session_outcome.session_outcome.items.len() as u64;
+    }
+
+    Ok(ArchiveVerification {
+        federation_id: manifest.federation_id,
+        first_session: manifest.first_session,
+        last_session: manifest.last_session,
+        items,
+    })
+}
+
+/// Checks that a threshold of `broadcast_public_keys` signed the outcome of
+/// session `session_index`, the same way the guardians' keychain does
+pub fn verify_session_signatures(
+    broadcast_public_keys: &BTreeMap<PeerId, PublicKey>,
+    session_index: u64,
+    signed_session_outcome: &SignedSessionOutcome,
+) -> anyhow::Result<()> {
+    let threshold = broadcast_public_keys.to_num_peers().threshold();
+    ensure!(
+        threshold <= signed_session_outcome.signatures.len(),
+        "Only {} of {threshold} required signatures",
+        signed_session_outcome.signatures.len()
+    );
+
+    let message = tagged_message(
+        &broadcast_public_keys.consensus_hash(),
+        &signed_session_outcome.session_outcome.header(session_index),
+    );
+
+    for (peer_id, signature) in &signed_session_outcome.signatures {
+        let public_key = broadcast_public_keys
+            .get(peer_id)
+            .with_context(|| format!("Signature of unknown peer {peer_id}"))?;
+        let signature = schnorr::Signature::from_slice(&signature.0)?;
+        SECP256K1
+            .verify_schnorr(&signature, &message, &public_key.x_only_public_key().0)
+            .with_context(|| format!("Invalid signature of peer {peer_id}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::module::registry::ModuleRegistry;
+    use fedimint_core::secp256k1::Keypair;
+    use fedimint_core::session_outcome::{SchnorrSignature, SessionOutcome};
+    use fedimint_testing_core::test_dir;
+
+    use super::*;
+
+    fn keypairs() -> BTreeMap<PeerId, Keypair> {
+        (0..4)
+            .map(|peer| {
+                let keypair = Keypair::new(SECP256K1, &mut rand::thread_rng());
+                (PeerId::from(peer), keypair)
+            })
+            .collect()
+    }
+
+    fn public_keys(keypairs: &BTreeMap<PeerId, Keypair>) -> BTreeMap<PeerId, PublicKey> {
+        keypairs
+            .iter()
+            .map(|(peer, keypair)| (*peer, keypair.public_key()))
+            .collect()
+    }
+
+    /// Signs the outcome of session `session_index` the way the guardians do
+    fn sign_session(
+        keypairs: &BTreeMap<PeerId, Keypair>,
+        session_index: u64,
+    ) -> SignedSessionOutcome {
+        let session_outcome = SessionOutcome { items: vec![] };
+        let message = tagged_message(
+            &public_keys(keypairs).consensus_hash(),
+            &session_outcome.header(session_index),
+        );
+        let signatures = keypairs
+            .iter()
+            .map(|(peer, keypair)| {
+                let signature = keypair.sign_schnorr(message);
+                (*peer, SchnorrSignature(*signature.as_ref()))
+            })
+            .collect();
+
+        SignedSessionOutcome {
+            session_outcome,
+            signatures,
+        }
+    }
+
+    #[test]
+    fn verifies_session_signatures() {
+        let keypairs = keypairs();
+        let public_keys = public_keys(&keypairs);
+        let mut signed = sign_session(&keypairs, 3);
+
+        verify_session_signatures(&public_keys, 3, &signed).expect("Signed by all guardians");
+        assert!(verify_session_signatures(&public_keys, 4, &signed).is_err());
+
+        // A threshold of signatures is enough
+        signed.signatures.remove(&PeerId::from(0));
+        verify_session_signatures(&public_keys, 3, &signed).expect("Signed by a threshold");
+        signed.signatures.remove(&PeerId::from(1));
+        assert!(verify_session_signatures(&public_keys, 3, &signed).is_err());
+
+        // Signatures over another key set are rejected
+        let other_public_keys = public_keys
+            .iter()
+            .map(|(peer, public_key)| (*peer, public_key.negate(SECP256K1)))
+            .collect();
+        assert!(
+            verify_session_signatures(&other_public_keys, 3, &sign_session(&keypairs, 3)).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn exported_archive_verifies() {
+        let keypairs = keypairs();
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction().await;
+        for session_index in 0..3 {
+            dbtx.insert_new_entry(
+                &SignedSessionOutcomeKey(session_index),
+                &sign_session(&keypairs, session_index),
+            )
+            .await;
+        }
+        dbtx.commit_tx().await;
+
+        let (dir, _guard) = test_dir("archive-export");
+        let federation_id = FederationId::dummy();
+        export_sessions(&db, federation_id, public_keys(&keypairs), 1..=2, &dir)
+            .await
+            .expect("Sessions are complete");
+        assert!(
+            export_sessions(&db, federation_id, public_keys(&keypairs), 2..=3, &dir)
+                .await
+                .is_err()
+        );
+
+        let verification = verify_archive(&dir).expect("Archive is valid");
+        assert_eq!(verification.federation_id, federation_id);
+        assert_eq!(
+            (verification.first_session, verification.last_session),
+            (1, 2)
+        );
+
+        // Corrupting a session file breaks its checksum
+        let path = dir.join(session_file_name(2));
+        let mut bytes = fs::read(&path).expect("Session file exists");
+        bytes[0] ^= 1;
+        fs::write(&path, bytes).expect("Can write session file");
+        assert!(verify_archive(&dir).is_err());
+    }
+}
@@ -7,11 +7,13 @@
     MODULE_GLOBAL_PREFIX,
 };
 use fedimint_core::encoding::{Decodable, Encodable};
-use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::epoch::{ConsensusItem, FeeRevenue};
 use fedimint_core::module::ModuleCommon;
 use fedimint_core::session_outcome::{AcceptedItem, SignedSessionOutcome};
 use fedimint_core::util::BoxStream;
-use fedimint_core::{apply, async_trait_maybe_send, impl_db_lookup, impl_db_record, TransactionId};
+use fedimint_core::{
+    apply, async_trait_maybe_send, impl_db_lookup, impl_db_record, Amount, PeerId, TransactionId,
+};
 use futures::StreamExt;
 use serde::Serialize;
 use strum_macros::EnumIter;
@@ -25,6 +27,10 @@ pub enum DbKeyPrefix {
     AlephUnits = 0x05,
     // TODO: do we want to split the server DB into consensus/non-consensus?
     ApiAnnouncements = 0x06,
+    AdminKeys = 0x07,
+    SessionFees = 0x08,
+    FeeRevenue = 0x09,
+    OutputOutcomeRebuild = 0x0a,
     Module = MODULE_GLOBAL_PREFIX,
 }
 
@@ -96,6 +102,35 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 );
 impl_db_lookup!(key = AlephUnitsKey, query_prefix = AlephUnitsPrefix);
 
+/// Transaction fees collected in a session
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct SessionFeesKey(pub u64);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct SessionFeesPrefix;
+
+impl_db_record!(
+    key = SessionFeesKey,
+    value = Amount,
+    db_prefix = DbKeyPrefix::SessionFees,
+    notify_on_modify = false,
+);
+impl_db_lookup!(key = SessionFeesKey, query_prefix = SessionFeesPrefix);
+
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct FeeRevenueKey;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct FeeRevenuePrefix;
+
+impl_db_record!(
+    key = FeeRevenueKey,
+    value = FeeRevenue,
+    db_prefix = DbKeyPrefix::FeeRevenue,
+    notify_on_modify = false,
+);
+impl_db_lookup!(key = FeeRevenueKey, query_prefix = FeeRevenuePrefix);
+
 pub fn get_global_database_migrations() -> BTreeMap<DatabaseVersion, CoreMigrationFn> {
     BTreeMap::new()
 }
@@ -247,8 +282,10 @@ mod fedimint_migration_tests {
     use super::{
         get_global_database_migrations, AcceptedItem, AcceptedItemKey, AcceptedItemPrefix,
         AcceptedTransactionKey, AcceptedTransactionKeyPrefix, AlephUnitsKey, AlephUnitsPrefix,
-        DbKeyPrefix, SignedSessionOutcomeKey, SignedSessionOutcomePrefix,
+        DbKeyPrefix, FeeRevenuePrefix, SessionFeesPrefix, SignedSessionOutcomeKey,
+        SignedSessionOutcomePrefix,
     };
+    use crate::net::api::admin_auth::{AdminKey, AdminKeyPrefix};
     use crate::net::api::announcement::{ApiAnnouncementKey, ApiAnnouncementPrefix};
 
     /// Create a database with version 0 data. The database produced is not
@@ -334,6 +371,9 @@ async fn create_server_db_with_v0_data(db: Database) {
         )
         .await;
 
+        dbtx.insert_new_entry(&AdminKey(key_pair.public_key()), &"operator".to_owned())
+            .await;
+
         dbtx.commit_tx().await;
     }
 
@@ -428,6 +468,52 @@ async fn test_server_db_migrations() -> anyhow::Result<()> {
 
                             assert_eq!(announcements.len(), 1);
                         }
+                        // Fee accounting was added after the last snapshot, so it may be
+                        // missing from it
+                        DbKeyPrefix::SessionFees => {
+                            let session_fees = dbtx
+                                .find_by_prefix(&SessionFeesPrefix)
+                                .await
+                                .collect::<Vec<_>>()
+                                .await;
+                            info!(target: LOG_DB, num_session_fees = session_fees.len(), "Validated SessionFees");
+                        }
+                        DbKeyPrefix::FeeRevenue => {
+                            let fee_revenue = dbtx
+                                .find_by_prefix(&FeeRevenuePrefix)
+                                .await
+                                .collect::<Vec<_>>()
+                                .await;
+                            info!(target: LOG_DB, num_fee_revenue = fee_revenue.len(), "Validated FeeRevenue");
+                        }
+                        DbKeyPrefix::FeeDistributionVote => {
+                            let votes = dbtx
+                                .find_by_prefix(&FeeDistributionVotePrefix)
+                                .await
+                                .collect::<Vec<_>>()
+                                .await;
+                            info!(target: LOG_DB, num_votes = votes.len(), "Validated FeeDistributionVotes");
+                        }
+                        DbKeyPrefix::GuardianFeeBalance => {
+                            let balances = dbtx
+                                .find_by_prefix(&GuardianFeeBalancePrefix)
+                                .await
+                                .collect::<Vec<_>>()
+                                .await;
+                            info!(target: LOG_DB, num_balances = balances.len(), "Validated GuardianFeeBalances");
+                        }
+                        // Only present while rebuilding after a state snapshot sync
+                        DbKeyPrefix::OutputOutcomeRebuild => {}
+                        DbKeyPrefix::AdminKeys => {
+                            // Admin keys were added after the last snapshot, so they may be
+                            // missing from it
+                            let admin_keys = dbtx
+                                .find_by_prefix(&AdminKeyPrefix)
+                                .await
+                                .collect::<Vec<_>>()
+                                .await;
+                            info!(target: LOG_DB, num_admin_keys = admin_keys.len(), "Validated AdminKeys");
+                        }
                     }
                 }
                 Ok(())
@@ -8,10 +8,13 @@
 use aleph_bft::Keychain as KeychainTrait;
 use anyhow::{anyhow, bail};
 use async_channel::Receiver;
-use fedimint_api_client::api::{DynGlobalApi, FederationApiExt, PeerConnectionStatus};
+use fedimint_api_client::api::{DynGlobalApi, FederationApiExt};
 use fedimint_api_client::query::FilterMap;
 use fedimint_core::core::{DynOutput, MODULE_INSTANCE_ID_GLOBAL};
-use fedimint_core::db::{Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::db::{
+    Committable, Database, DatabaseTransaction, IDatabaseTransactionOps,
+    IDatabaseTransactionOpsCoreTyped,
+};
 use fedimint_core::encoding::Decodable;
 use fedimint_core::endpoint_constants::AWAIT_SIGNED_SESSION_OUTCOME_ENDPOINT;
 use fedimint_core::epoch::ConsensusItem;
@@ -25,10 +28,10 @@
 };
 use fedimint_core::task::{sleep, TaskGroup, TaskHandle};
 use fedimint_core::timing::TimeReporter;
-use fedimint_core::{timing, NumPeers, NumPeersExt, PeerId};
+use fedimint_core::{timing, Amount, NumPeers, NumPeersExt, PeerId};
 use futures::StreamExt;
 use rand::Rng;
-use tokio::sync::{watch, RwLock};
+use tokio::sync::{oneshot, watch, Mutex, RwLock};
 use tracing::{debug, info, instrument, warn, Level};
 
 use crate::config::ServerConfig;
@@ -40,19 +43,24 @@
 use crate::consensus::aleph_bft::spawner::Spawner;
 use crate::consensus::aleph_bft::{to_node_index, Message};
 use crate::consensus::db::{
-    AcceptedItemKey, AcceptedItemPrefix, AcceptedTransactionKey, AlephUnitsPrefix,
-    SignedSessionOutcomeKey, SignedSessionOutcomePrefix,
+    AcceptedItemKey, AcceptedItemPrefix, AcceptedTransactionKey, AlephUnitsPrefix, FeeRevenueKey,
+    SessionFeesKey, SignedSessionOutcomeKey, SignedSessionOutcomePrefix,
 };
 use crate::consensus::debug::{DebugConsensusItem, DebugConsensusItemCompact};
+use crate::consensus::quota::ModuleByteQuotas;
+use crate::consensus::snapshot::{
+    is_snapshot_session, write_state_snapshot, ConsensusKeyPrefixes, STATE_SNAPSHOTS_DIR,
+};
 use crate::consensus::transaction::process_transaction_with_dbtx;
 use crate::fedimint_core::encoding::Encodable;
 use crate::metrics::{
+    CONSENSUS_BATCH_COMMIT_DURATION_SECONDS, CONSENSUS_BATCH_COMMIT_ITEMS,
     CONSENSUS_ITEMS_PROCESSED_TOTAL, CONSENSUS_ITEM_PROCESSING_DURATION_SECONDS,
     CONSENSUS_ITEM_PROCESSING_MODULE_AUDIT_DURATION_SECONDS, CONSENSUS_ORDERING_LATENCY_SECONDS,
-    CONSENSUS_PEER_CONTRIBUTION_SESSION_IDX, CONSENSUS_SESSION_COUNT,
+    CONSENSUS_PEER_CONTRIBUTION_SESSION_IDX, CONSENSUS_SESSION_COUNT, CONSENSUS_SESSION_ITEM_BYTES,
 };
-use crate::net::connect::{Connector, TlsTcpConnector};
-use crate::net::peers::ReconnectPeerConnections;
+use crate::net::connect::{tor_socks5_proxy_from_env, Connector, TransportConnector};
+use crate::net::peers::{PeerConnectivity, ReconnectPeerConnections};
 use crate::LOG_CONSENSUS;
 
 // The name of the directory where the database checkpoints are stored.
@@ -71,10 +79,15 @@ pub struct ConsensusEngine {
     pub self_id_str: String,
     /// Just a string version of peer ids for performance
     pub peer_id_str: Vec<String>,
-    pub connection_status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+    pub connection_status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
     pub task_group: TaskGroup,
     pub data_dir: PathBuf,
     pub checkpoint_retention: u64,
+    pub module_byte_quotas: ModuleByteQuotas,
+    /// `None` if one of our modules doesn't support state snapshots
+    pub consensus_key_prefixes: Option<ConsensusKeyPrefixes>,
+    /// Held by the background task writing a state snapshot
+    pub state_snapshot_lock: Arc<Mutex<()>>,
     pub p2p_bind_addr: SocketAddr,
 }
 
@@ -106,7 +119,7 @@ pub async fn run_single_guardian(&self, task_handle: TaskHandle) -> anyhow::Resu
         while !task_handle.is_shutting_down() {
             let session_index = self.get_finished_session_count().await;
 
-            CONSENSUS_SESSION_COUNT.set(session_index as i64);
+            CONSENSUS_SESSION_COUNT.set(&[], session_index as i64);
 
             let mut item_index = self.pending_accepted_items().await.len() as u64;
 
@@ -169,9 +182,16 @@ pub async fn run_consensus(
         self.confirm_server_config_consensus_hash().await?;
 
         // Build P2P connections for the atomic broadcast
+        let network_config = self.cfg.network_config(p2p_bind_addr);
+        let connector = TransportConnector::new(
+            self.cfg.tls_config(),
+            self.identity(),
+            &network_config.peers[&self.identity()],
+            tor_socks5_proxy_from_env()?,
+        );
         let connections = ReconnectPeerConnections::new(
-            self.cfg.network_config(p2p_bind_addr),
-            TlsTcpConnector::new(self.cfg.tls_config(), self.identity()).into_dyn(),
+            network_config,
+            connector.into_dyn(),
             &self.task_group,
             Arc::clone(&self.connection_status_channels),
         )
@@ -182,7 +202,7 @@ pub async fn run_consensus(
         while !task_handle.is_shutting_down() {
             let session_index = self.get_finished_session_count().await;
 
-            CONSENSUS_SESSION_COUNT.set(session_index as i64);
+            CONSENSUS_SESSION_COUNT.set(&[], session_index as i64);
 
             info!(target: LOG_CONSENSUS, session_index, "Starting consensus session");
 
@@ -301,6 +321,7 @@ pub async fn run_session(
                         self.submission_receiver.clone(),
                         signature_receiver,
                         timestamp_sender,
+                        self.module_byte_quotas.clone(),
                     ),
                     FinalizationHandler::new(unit_data_sender),
                     BackupWriter::new(self.db.clone()).await,
@@ -335,6 +356,8 @@ pub async fn run_session(
 
         self.checkpoint_database(session_index);
 
+        self.snapshot_state(session_index + 1).await;
+
         Ok(())
     }
 
@@ -372,7 +395,7 @@ pub async fn complete_signed_session_outcome(
                                  match timestamp_receiver.try_recv() {
                                     Ok((timestamp, chsum)) => {
                                         if get_citem_bytes_chsum(&bytes) == chsum {
-                                            CONSENSUS_ORDERING_LATENCY_SECONDS.observe(timestamp.elapsed().as_secs_f64());
+                                            CONSENSUS_ORDERING_LATENCY_SECONDS.observe_duration(&[], timestamp.elapsed());
                                             break;
                                         }
                                         warn!(target: LOG_CONSENSUS, "Not reporting ordering latency on possibly out of sync item");
@@ -386,17 +409,12 @@ pub async fn complete_signed_session_outcome(
                         }
 
                         if let Ok(items) = Vec::<ConsensusItem>::consensus_decode(&mut bytes.as_slice(), &self.decoders()){
-                            for item in items {
-                                if self.process_consensus_item(
-                                    session_index,
-                                    item_index,
-                                    item.clone(),
-                                    ordered_unit.creator
-                                ).await
-                                .is_ok() {
-                                    item_index += 1;
-                                }
-                            }
+                            item_index += self.process_consensus_batch(
+                                session_index,
+                                item_index,
+                                items,
+                                ordered_unit.creator
+                            ).await;
                         }
                     }
                 },
@@ -511,6 +529,8 @@ pub async fn complete_session(
         session_index: u64,
         signed_session_outcome: SignedSessionOutcome,
     ) {
+        self.record_session_item_bytes(&signed_session_outcome.session_outcome);
+
         let mut dbtx = self.db.begin_transaction().await;
 
         dbtx.remove_by_prefix(&AlephUnitsPrefix).await;
@@ -533,11 +553,80 @@ pub async fn complete_session(
             .expect("This is the only place where we write to this key");
     }
 
+    /// Records how many bytes of the session's items each module and the
+    /// transactions contributed
+    fn record_session_item_bytes(&self, session_outcome: &SessionOutcome) {
+        let mut bytes = BTreeMap::<&str, usize>::new();
+
+        for accepted_item in &session_outcome.items {
+            let source = match &accepted_item.item {
+                ConsensusItem::Transaction(..) => "transaction",
+                ConsensusItem::Module(module_item) => self
+                    .modules
+                    .get_with_kind(module_item.module_instance_id())
+                    .map_or("unknown", |(kind, _)| kind.as_str()),
+                ConsensusItem::Default { .. } => "unknown",
+            };
+
+            *bytes.entry(source).or_default() += accepted_item.item.consensus_encode_to_vec().len();
+        }
+
+        for (source, bytes) in bytes {
+            CONSENSUS_SESSION_ITEM_BYTES.observe(&[source], bytes as f64);
+        }
+    }
+
     /// Returns the full path where the database checkpoints are stored.
     fn db_checkpoints_dir(&self) -> PathBuf {
         self.data_dir.join(DB_CHECKPOINTS_DIR)
     }
 
+    /// Writes a snapshot of our state for recovering peers in the background
+    /// if `session_count` sessions complete a snapshot interval, see
+    /// [`crate::consensus::snapshot`]. Only one snapshot is written at a time,
+    /// returns once its read transaction is open so it sees the state right
+    /// after the session.
+    async fn snapshot_state(&self, session_count: u64) {
+        let Some(prefixes) = self.consensus_key_prefixes.clone() else {
+            return;
+        };
+
+        if !is_snapshot_session(session_count) {
+            return;
+        }
+
+        let Ok(lock) = self.state_snapshot_lock.clone().try_lock_owned() else {
+            warn!(target: LOG_CONSENSUS, session_count, "Previous state snapshot is still being written, skipping");
+            return;
+        };
+
+        let db = self.db.clone();
+        let snapshots_dir = self.data_dir.join(STATE_SNAPSHOTS_DIR);
+        let (read_sender, read_receiver) = oneshot::channel();
+
+        self.task_group
+            .spawn_cancellable("state-snapshot", async move {
+                let _lock = lock;
+
+                let mut dbtx = db.begin_transaction_nc().await;
+
+                read_sender.send(()).ok();
+
+                let _timing /* logs on drop */ = timing::TimeReporter::new("state-snapshot").level(Level::INFO);
+
+                match write_state_snapshot(&mut dbtx, &prefixes, &snapshots_dir).await {
+                    Ok(info) => {
+                        info!(target: LOG_CONSENSUS, session_count, size = info.size, "Created state snapshot");
+                    }
+                    Err(e) => {
+                        warn!(target: LOG_CONSENSUS, session_count, ?e, "Could not create state snapshot");
+                    }
+                }
+            });
+
+        read_receiver.await.ok();
+    }
+
     /// Creates the directory within the data directory for storing the database
     /// checkpoints or deletes checkpoints before `current_session` -
     /// `checkpoint_retention`.
@@ -623,7 +712,7 @@ fn delete_old_database_checkpoint(
         Ok(())
     }
 
-    #[instrument(target = "fm::consensus", skip(self, item), level = "info")]
+    /// Processes a single consensus item in its own database transaction
     pub async fn process_consensus_item(
         &self,
         session_index: u64,
@@ -631,11 +720,92 @@ pub async fn process_consensus_item(
         item: ConsensusItem,
         peer: PeerId,
     ) -> anyhow::Result<()> {
+        let mut dbtx = self.db.begin_transaction().await;
+
+        dbtx.ignore_uncommitted();
+
+        if self
+            .accept_consensus_item(&mut dbtx, session_index, item_index, item, peer)
+            .await?
+        {
+            self.commit_accepted_items(dbtx, peer, 1).await;
+        }
+
+        Ok(())
+    }
+
+    /// Processes the consensus items of an ordered batch, starting at
+    /// `item_index`, and persists all of them with a single database commit.
+    /// Items that are rejected are rolled back to a savepoint without
+    /// affecting the rest of the batch. Returns the number of accepted items.
+    pub async fn process_consensus_batch(
+        &self,
+        session_index: u64,
+        item_index: u64,
+        items: Vec<ConsensusItem>,
+        peer: PeerId,
+    ) -> u64 {
+        let mut dbtx = self.db.begin_transaction().await;
+
+        dbtx.ignore_uncommitted();
+
+        let mut accepted = 0;
+        let mut newly_accepted = 0;
+
+        for item in items {
+            let on_commit_hooks = dbtx.on_commit_hooks_len();
+
+            dbtx.set_tx_savepoint()
+                .await
+                .expect("Setting a savepoint failed");
+
+            match self
+                .accept_consensus_item(&mut dbtx, session_index, item_index + accepted, item, peer)
+                .await
+            {
+                Ok(is_new) => {
+                    dbtx.release_tx_savepoint()
+                        .await
+                        .expect("Releasing a savepoint failed");
+
+                    accepted += 1;
+
+                    if is_new {
+                        newly_accepted += 1;
+                    }
+                }
+                Err(err) => {
+                    debug!(target: LOG_CONSENSUS, %peer, %err, "Rejected consensus item");
+
+                    dbtx.rollback_tx_to_savepoint()
+                        .await
+                        .expect("Rolling back to a savepoint failed");
+                    dbtx.truncate_on_commit_hooks(on_commit_hooks);
+                }
+            }
+        }
+
+        if newly_accepted > 0 {
+            self.commit_accepted_items(dbtx, peer, newly_accepted).await;
+        }
+
+        accepted
+    }
+
+    /// Processes `item` and records it as accepted in `dbtx`. Returns `false`
+    /// if the item was accepted before and is only being replayed.
+    #[instrument(target = "fm::consensus", skip(self, dbtx, item), level = "info")]
+    async fn accept_consensus_item(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_, Committable>,
+        session_index: u64,
+        item_index: u64,
+        item: ConsensusItem,
+        peer: PeerId,
+    ) -> anyhow::Result<bool> {
         let peer_id_str = &self.peer_id_str[peer.to_usize()];
         let _timing /* logs on drop */ = timing::TimeReporter::new("process_consensus_item").level(Level::TRACE);
-        let timing_prom = CONSENSUS_ITEM_PROCESSING_DURATION_SECONDS
-            .with_label_values(&[peer_id_str])
-            .start_timer();
+        let timing_prom = CONSENSUS_ITEM_PROCESSING_DURATION_SECONDS.start_timer(&[peer_id_str]);
 
         debug!(%peer, item = ?DebugConsensusItem(&item), "Processing consensus item");
 
@@ -645,12 +815,7 @@ pub async fn process_consensus_item(
             .insert(peer, session_index);
 
         CONSENSUS_PEER_CONTRIBUTION_SESSION_IDX
-            .with_label_values(&[&self.self_id_str, peer_id_str])
-            .set(session_index as i64);
-
-        let mut dbtx = self.db.begin_transaction().await;
-
-        dbtx.ignore_uncommitted();
+            .set(&[&self.self_id_str, peer_id_str], session_index as i64);
 
         // When we recover from a mid-session crash aleph bft will replay the units that
         // were already processed before the crash. We therefore skip all consensus
@@ -660,14 +825,19 @@ pub async fn process_consensus_item(
             .await
         {
             if existing_item.item == item && existing_item.peer == peer {
-                return Ok(());
+                return Ok(false);
             }
 
             bail!("Item was discarded previously: existing: {existing_item:?} {}, current: {item:?}, {peer}", existing_item.peer);
         }
 
-        self.process_consensus_item_with_db_transaction(&mut dbtx.to_ref_nc(), item.clone(), peer)
-            .await?;
+        self.process_consensus_item_with_db_transaction(
+            &mut dbtx.to_ref_nc(),
+            session_index,
+            item.clone(),
+            peer,
+        )
+        .await?;
 
         // After this point we have to commit the database transaction since the
         // item has been fully processed without errors
@@ -676,6 +846,19 @@ pub async fn process_consensus_item(
         dbtx.insert_entry(&AcceptedItemKey(item_index), &AcceptedItem { item, peer })
             .await;
 
+        timing_prom.observe_duration();
+
+        Ok(true)
+    }
+
+    /// Audits the modules and commits `dbtx` containing `items` newly accepted
+    /// items from `peer`
+    async fn commit_accepted_items(
+        &self,
+        mut dbtx: DatabaseTransaction<'_, Committable>,
+        peer: PeerId,
+        items: u64,
+    ) {
         let mut audit = Audit::default();
 
         for (module_instance_id, kind, module) in self.modules.iter_modules() {
@@ -683,8 +866,7 @@ pub async fn process_consensus_item(
                 TimeReporter::new(format!("audit module {module_instance_id}")).level(Level::TRACE);
 
             let timing_prom = CONSENSUS_ITEM_PROCESSING_MODULE_AUDIT_DURATION_SECONDS
-                .with_label_values(&[&MODULE_INSTANCE_ID_GLOBAL.to_string(), kind.as_str()])
-                .start_timer();
+                .start_timer(&[&MODULE_INSTANCE_ID_GLOBAL.to_string(), kind.as_str()]);
 
             module
                 .audit(
@@ -708,21 +890,23 @@ pub async fn process_consensus_item(
             "Balance sheet of the fed has gone negative, this should never happen! {audit}"
         );
 
+        let timing_prom = CONSENSUS_BATCH_COMMIT_DURATION_SECONDS.start_timer(&[]);
+
         dbtx.commit_tx_result()
             .await
             .expect("Committing consensus epoch failed");
 
-        CONSENSUS_ITEMS_PROCESSED_TOTAL
-            .with_label_values(&[&self.peer_id_str[peer.to_usize()]])
-            .inc();
         timing_prom.observe_duration();
 
-        Ok(())
+        CONSENSUS_BATCH_COMMIT_ITEMS.observe(&[], items as f64);
+
+        CONSENSUS_ITEMS_PROCESSED_TOTAL.inc_by(&[&self.peer_id_str[peer.to_usize()]], items);
     }
 
     async fn process_consensus_item_with_db_transaction(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
+        session_index: u64,
         consensus_item: ConsensusItem,
         peer_id: PeerId,
     ) -> anyhow::Result<()> {
@@ -758,7 +942,7 @@ async fn process_consensus_item_with_db_transaction(
                     .map(DynOutput::module_instance_id)
                     .collect::<Vec<_>>();
 
-                process_transaction_with_dbtx(
+                let fee = process_transaction_with_dbtx(
                     self.modules.clone(),
                     dbtx,
                     &transaction,
@@ -771,6 +955,8 @@ async fn process_consensus_item_with_db_transaction(
                 dbtx.insert_entry(&AcceptedTransactionKey(txid), &modules_ids)
                     .await;
 
+                record_fee_revenue(dbtx, session_index, fee).await;
+
                 Ok(())
             }
             ConsensusItem::Default { variant, .. } => {
@@ -842,6 +1028,25 @@ async fn get_finished_session_count(&self) -> u64 {
     }
 }
 
+/// Adds the fee revenue of an accepted transaction to the totals of the
+/// session and the federation
+async fn record_fee_revenue(dbtx: &mut DatabaseTransaction<'_>, session_index: u64, fee: Amount) {
+    if fee == Amount::ZERO {
+        return;
+    }
+
+    let session_fees = dbtx
+        .get_value(&SessionFeesKey(session_index))
+        .await
+        .unwrap_or(Amount::ZERO);
+    dbtx.insert_entry(&SessionFeesKey(session_index), &(session_fees + fee))
+        .await;
+
+    let mut revenue = dbtx.get_value(&FeeRevenueKey).await.unwrap_or_default();
+    revenue.collected += fee;
+    dbtx.insert_entry(&FeeRevenueKey, &revenue).await;
+}
+
 pub async fn get_finished_session_count_static(dbtx: &mut DatabaseTransaction<'_>) -> u64 {
     dbtx.find_by_prefix_sorted_descending(&SignedSessionOutcomePrefix)
         .await
@@ -849,3 +1054,356 @@ pub async fn get_finished_session_count_static(dbtx: &mut DatabaseTransaction<'_
         .await
         .map_or(0, |entry| (entry.0 .0) + 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::panic::AssertUnwindSafe;
+    use std::sync::Arc;
+
+    use bitcoin::key::Keypair;
+    use bitcoin::secp256k1::{self, Message};
+    use fedimint_api_client::api::DynGlobalApi;
+    use fedimint_core::config::{ServerModuleConfigGenParamsRegistry, ServerModuleInitRegistry};
+    use fedimint_core::core::{DynInput, DynOutput};
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{
+        Database, IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped, IRawDatabaseExt,
+    };
+    use fedimint_core::epoch::ConsensusItem;
+    use fedimint_core::module::registry::ModuleRegistry;
+    use fedimint_core::module::{ApiAuth, IServerModuleInit};
+    use fedimint_core::task::TaskGroup;
+    use fedimint_core::transaction::{Transaction, TransactionSignature};
+    use fedimint_core::{Amount, NumPeers, PeerId};
+    use fedimint_dummy_common::config::DummyGenParams;
+    use fedimint_dummy_common::{broken_fed_key_pair, fed_key_pair, DummyInput, DummyOutput};
+    use fedimint_dummy_server::DummyInit;
+    use futures::{FutureExt, StreamExt};
+    use rand::rngs::OsRng;
+    use tokio::sync::{watch, RwLock};
+
+    use super::ConsensusEngine;
+    use crate::config::{
+        gen_cert_and_key, ConfigGenParams, ConfigGenParamsConsensus, ConfigGenParamsLocal,
+        PeerServerParams, ServerConfig,
+    };
+    use crate::consensus::db::{
+        AcceptedItemKey, AcceptedItemPrefix, FeeRevenueKey, SessionFeesKey,
+    };
+    use crate::consensus::quota::ModuleByteQuotas;
+    use crate::fedimint_core::module::ServerModuleInit;
+
+    fn server_config() -> ServerConfig {
+        let (cert, private_key) = gen_cert_and_key("peer-0").expect("Failed to generate cert");
+
+        let mut modules = ServerModuleConfigGenParamsRegistry::default();
+        modules.attach_config_gen_params_by_id(0, DummyInit::kind(), DummyGenParams::default());
+
+        let params = ConfigGenParams {
+            local: ConfigGenParamsLocal {
+                our_id: PeerId::from(0),
+                our_private_key: private_key,
+                api_auth: ApiAuth("pass".to_string()),
+                p2p_bind: "127.0.0.1:10000".parse().expect("Valid address"),
+                api_bind: "127.0.0.1:10001".parse().expect("Valid address"),
+                max_connections: 10,
+            },
+            consensus: ConfigGenParamsConsensus {
+                peers: BTreeMap::from([(
+                    PeerId::from(0),
+                    PeerServerParams {
+                        cert,
+                        p2p_url: "fedimint://127.0.0.1:10000".parse().expect("Valid url"),
+                        api_url: "ws://127.0.0.1:10001".parse().expect("Valid url"),
+                        name: "peer-0".to_string(),
+                        status: None,
+                    },
+                )]),
+                meta: BTreeMap::new(),
+                modules,
+            },
+        };
+
+        ServerConfig::trusted_dealer_gen(
+            &HashMap::from([(PeerId::from(0), params)]),
+            &ServerModuleInitRegistry::from(vec![DummyInit.into()]),
+            "dummyversionhash",
+        )
+        .remove(&PeerId::from(0))
+        .expect("Config for our peer")
+    }
+
+    async fn consensus_engine(cfg: &ServerConfig, db: &Database) -> ConsensusEngine {
+        let task_group = TaskGroup::new();
+
+        let module = IServerModuleInit::init(
+            &DummyInit,
+            NumPeers::from(1),
+            cfg.get_module_config(0).expect("Dummy module config"),
+            db.with_prefix_module_id(0).0,
+            &task_group,
+            PeerId::from(0),
+        )
+        .await
+        .expect("Failed to initialize the dummy module");
+
+        let (_, submission_receiver) = async_channel::bounded(1);
+        let (_, shutdown_receiver) = watch::channel(None);
+
+        ConsensusEngine {
+            modules: ModuleRegistry::from(BTreeMap::from([(0, (DummyInit::kind(), module))])),
+            db: db.clone(),
+            federation_api: DynGlobalApi::from_endpoints(
+                cfg.consensus
+                    .api_endpoints
+                    .iter()
+                    .map(|(peer, endpoint)| (*peer, endpoint.url.clone())),
+                &None,
+                &fedimint_api_client::api::net::Connector::default(),
+            ),
+            cfg: cfg.clone(),
+            submission_receiver,
+            shutdown_receiver,
+            last_ci_by_peer: Arc::new(RwLock::new(BTreeMap::new())),
+            self_id_str: "0".to_string(),
+            peer_id_str: vec!["0".to_string()],
+            connection_status_channels: Arc::new(RwLock::new(BTreeMap::new())),
+            task_group,
+            data_dir: "unused".into(),
+            checkpoint_retention: 1,
+            module_byte_quotas: ModuleByteQuotas::default(),
+            consensus_key_prefixes: None,
+            state_snapshot_lock: Arc::default(),
+            p2p_bind_addr: "127.0.0.1:10000".parse().expect("Valid address"),
+        }
+    }
+
+    /// Creates a transaction spending `inputs` from the accounts of the key
+    /// pairs into `outputs`, signed by all key pairs
+    fn transaction(inputs: &[(Keypair, u64)], outputs: &[(Keypair, u64)]) -> ConsensusItem {
+        let mut transaction = Transaction {
+            inputs: inputs
+                .iter()
+                .map(|(key_pair, msats)| {
+                    DynInput::from_typed(
+                        0,
+                        DummyInput {
+                            amount: Amount::from_msats(*msats),
+                            account: key_pair.public_key(),
+                        },
+                    )
+                })
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|(key_pair, msats)| {
+                    DynOutput::from_typed(
+                        0,
+                        DummyOutput {
+                            amount: Amount::from_msats(*msats),
+                            account: key_pair.public_key(),
+                        },
+                    )
+                })
+                .collect(),
+            nonce: [0; 8],
+            signatures: TransactionSignature::NaiveMultisig(vec![]),
+        };
+
+        let message =
+            Message::from_digest_slice(&transaction.tx_hash()[..]).expect("txid has right length");
+
+        transaction.signatures = TransactionSignature::NaiveMultisig(
+            inputs
+                .iter()
+                .map(|(key_pair, _)| key_pair.sign_schnorr(message))
+                .collect(),
+        );
+
+        ConsensusItem::Transaction(transaction)
+    }
+
+    fn random_key_pair() -> Keypair {
+        Keypair::new(secp256k1::SECP256K1, &mut OsRng)
+    }
+
+    /// A batch of which the second and third item get rejected, the third one
+    /// only after its first input has already been processed
+    fn batch_with_rejected_items() -> Vec<ConsensusItem> {
+        let alice = random_key_pair();
+        let bob = random_key_pair();
+
+        let print_money = transaction(&[(fed_key_pair(), 1000)], &[(alice, 1000)]);
+
+        vec![
+            print_money.clone(),
+            print_money,
+            transaction(
+                &[(fed_key_pair(), 500), (alice, 1_000_000)],
+                &[(bob, 1_000_500)],
+            ),
+            transaction(&[(alice, 400)], &[(bob, 400)]),
+        ]
+    }
+
+    async fn dump_db(db: &Database) -> Vec<(Vec<u8>, Vec<u8>)> {
+        db.begin_transaction_nc()
+            .await
+            .raw_find_by_prefix(&[])
+            .await
+            .expect("DB operation failed")
+            .collect()
+            .await
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn batch_matches_processing_items_individually() {
+        let cfg = server_config();
+        let items = batch_with_rejected_items();
+
+        let batch_db = MemDatabase::new().into_database();
+        let batch_engine = consensus_engine(&cfg, &batch_db).await;
+
+        let accepted = batch_engine
+            .process_consensus_batch(0, 0, items.clone(), PeerId::from(0))
+            .await;
+
+        let single_db = MemDatabase::new().into_database();
+        let single_engine = consensus_engine(&cfg, &single_db).await;
+
+        let mut item_index = 0;
+
+        for item in items.clone() {
+            if single_engine
+                .process_consensus_item(0, item_index, item, PeerId::from(0))
+                .await
+                .is_ok()
+            {
+                item_index += 1;
+            }
+        }
+
+        assert_eq!(accepted, 2);
+        assert_eq!(accepted, item_index);
+        assert_eq!(dump_db(&batch_db).await, dump_db(&single_db).await);
+
+        let accepted_items = batch_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&AcceptedItemPrefix)
+            .await
+            .map(|(key, value)| (key.0, value.item))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            accepted_items,
+            vec![(0, items[0].clone()), (1, items[3].clone())]
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn replaying_accepted_batch_is_idempotent() {
+        let cfg = server_config();
+        let items = batch_with_rejected_items();
+
+        let db = MemDatabase::new().into_database();
+
+        let accepted = consensus_engine(&cfg, &db)
+            .await
+            .process_consensus_batch(0, 0, items.clone(), PeerId::from(0))
+            .await;
+
+        let state = dump_db(&db).await;
+
+        // After a crash the engine is restarted and the batch gets replayed
+        let replayed = consensus_engine(&cfg, &db)
+            .await
+            .process_consensus_batch(0, 0, items, PeerId::from(0))
+            .await;
+
+        assert_eq!(replayed, accepted);
+        assert_eq!(dump_db(&db).await, state);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn failed_audit_rolls_back_entire_batch() {
+        let cfg = server_config();
+        let alice = random_key_pair();
+
+        let items = vec![
+            transaction(&[(fed_key_pair(), 1000)], &[(alice, 1000)]),
+            // The broken printer does not add the funds it prints to its assets
+            transaction(&[(broken_fed_key_pair(), 1000)], &[(alice, 1000)]),
+        ];
+
+        let db = MemDatabase::new().into_database();
+        let engine = consensus_engine(&cfg, &db).await;
+
+        let result = AssertUnwindSafe(engine.process_consensus_batch(0, 0, items, PeerId::from(0)))
+            .catch_unwind()
+            .await;
+
+        assert!(result.is_err(), "The audit should have failed");
+        assert!(dump_db(&db).await.is_empty());
+        assert!(db
+            .begin_transaction_nc()
+            .await
+            .get_value(&AcceptedItemKey(0))
+            .await
+            .is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn fees_of_accepted_transactions_are_recorded_per_session() {
+        let cfg = server_config();
+        let alice = random_key_pair();
+
+        let db = MemDatabase::new().into_database();
+        let engine = consensus_engine(&cfg, &db).await;
+
+        // Overfunded transactions pay the excess as fees
+        let first_session = vec![
+            transaction(&[(fed_key_pair(), 1000)], &[(alice, 600)]),
+            transaction(&[(alice, 600)], &[(alice, 500)]),
+            // Rejected since Alice only has 500 msat left
+            transaction(&[(alice, 600)], &[(alice, 100)]),
+        ];
+
+        assert_eq!(
+            engine
+                .process_consensus_batch(0, 0, first_session, PeerId::from(0))
+                .await,
+            2
+        );
+
+        engine
+            .process_consensus_item(
+                1,
+                2,
+                transaction(&[(alice, 500)], &[(alice, 450)]),
+                PeerId::from(0),
+            )
+            .await
+            .expect("Transaction is valid");
+
+        let mut dbtx = db.begin_transaction_nc().await;
+
+        assert_eq!(
+            dbtx.get_value(&SessionFeesKey(0)).await,
+            Some(Amount::from_msats(500))
+        );
+        assert_eq!(
+            dbtx.get_value(&SessionFeesKey(1)).await,
+            Some(Amount::from_msats(50))
+        );
+        assert_eq!(
+            dbtx.get_value(&FeeRevenueKey)
+                .await
+                .expect("Fees were collected")
+                .collected,
+            Amount::from_msats(550)
+        );
+    }
+}
@@ -1,8 +1,11 @@
 pub mod aleph_bft;
 pub mod api;
+pub mod archive;
 pub mod db;
 pub mod debug;
 pub mod engine;
+pub mod quota;
+pub mod snapshot;
 pub mod transaction;
 
 use std::collections::BTreeMap;
@@ -20,24 +23,31 @@
 use fedimint_core::config::ServerModuleInitRegistry;
 use fedimint_core::core::{ModuleInstanceId, ModuleKind};
 use fedimint_core::db::{apply_migrations, apply_migrations_server, Database};
-use fedimint_core::envs::is_running_in_test_env;
+use fedimint_core::envs::{is_env_var_set, is_running_in_test_env};
 use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::module::registry::ModuleRegistry;
 use fedimint_core::server::DynServerModule;
 use fedimint_core::task::TaskGroup;
 use fedimint_core::NumPeers;
 use fedimint_logging::{LOG_CONSENSUS, LOG_CORE};
-use jsonrpsee::server::ServerHandle;
 use tokio::sync::{watch, RwLock};
 use tracing::{info, warn};
 
 use crate::config::{ServerConfig, ServerConfigLocal};
 use crate::consensus::api::ConsensusApi;
 use crate::consensus::engine::ConsensusEngine;
-use crate::envs::{FM_DB_CHECKPOINT_RETENTION_DEFAULT, FM_DB_CHECKPOINT_RETENTION_ENV};
+use crate::consensus::quota::ModuleByteQuotas;
+use crate::consensus::snapshot::{
+    rebuild_output_outcomes, sync_from_state_snapshot, ConsensusKeyPrefixes, STATE_SNAPSHOTS_DIR,
+};
+use crate::envs::{
+    FM_DB_CHECKPOINT_RETENTION_DEFAULT, FM_DB_CHECKPOINT_RETENTION_ENV,
+    FM_DISABLE_PASSWORD_AUTH_WITH_ADMIN_KEYS_ENV,
+};
 use crate::net;
+use crate::net::api::admin_auth::AdminRequestVerifier;
 use crate::net::api::announcement::get_api_urls;
-use crate::net::api::{ApiSecrets, RpcHandlerCtx};
+use crate::net::api::{ApiSecrets, ApiServerHandles, RpcHandlerCtx};
 
 /// How many txs can be stored in memory before blocking the API
 const TRANSACTION_BUFFER: usize = 1000;
@@ -45,7 +55,7 @@
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
     p2p_bind_addr: SocketAddr,
-    api_bind_addr: SocketAddr,
+    api_bind_addrs: Vec<SocketAddr>,
     cfg: ServerConfig,
     db: Database,
     module_init_registry: ServerModuleInitRegistry,
@@ -63,6 +73,33 @@ pub async fn run(
     )
     .await?;
 
+    let consensus_key_prefixes = ConsensusKeyPrefixes::from_config(&cfg, &module_init_registry)?;
+
+    let api_urls = get_api_urls(&db, &cfg.consensus).await;
+
+    // FIXME: (@leonardo) How should this be handled ?
+    // Using the `Connector::default()` for now!
+    let federation_api = DynGlobalApi::from_endpoints(
+        api_urls,
+        &force_api_secrets.get_active(),
+        &Connector::default(),
+    );
+
+    // Modules may load parts of their state into memory on initialization, so we
+    // have to sync our state before
+    if let Some(prefixes) = &consensus_key_prefixes {
+        if cfg.consensus.api_endpoints.len() > 1 {
+            sync_from_state_snapshot(
+                &db,
+                &federation_api,
+                &cfg,
+                prefixes,
+                &data_dir.join(STATE_SNAPSHOTS_DIR),
+            )
+            .await;
+        }
+    }
+
     let mut modules = BTreeMap::new();
 
     for (module_id, module_cfg) in &cfg.consensus.modules {
@@ -97,6 +134,12 @@ pub async fn run(
 
     let module_registry = ModuleRegistry::from(modules);
 
+    // Our outcomes of the outputs accepted before a state snapshot we synced from
+    // are not part of it
+    rebuild_output_outcomes(&db, &module_registry).await?;
+
+    let module_byte_quotas = ModuleByteQuotas::from_env(&module_registry)?;
+
     let client_cfg = cfg.consensus.to_client_config(&module_init_registry)?;
 
     let (submission_sender, submission_receiver) = async_channel::bounded(TRANSACTION_BUFFER);
@@ -120,6 +163,14 @@ pub async fn run(
         connection_status_channels: connection_status_channels.clone(),
         force_api_secret: force_api_secrets.get_active(),
         code_version_str,
+        admin_request_verifier: Arc::new(AdminRequestVerifier::new(
+            cfg.calculate_federation_id(),
+            cfg.local.identity,
+        )),
+        disable_password_auth_with_admin_keys: is_env_var_set(
+            FM_DISABLE_PASSWORD_AUTH_WITH_ADMIN_KEYS_ENV,
+        ),
+        data_dir: data_dir.clone(),
     };
 
     info!(target: LOG_CONSENSUS, "Starting Consensus Api");
@@ -128,7 +179,7 @@ pub async fn run(
         &cfg.local,
         consensus_api,
         force_api_secrets.clone(),
-        api_bind_addr,
+        &api_bind_addrs,
     )
     .await;
 
@@ -153,17 +204,9 @@ pub async fn run(
 
     info!(target: LOG_CONSENSUS, "Starting Consensus Engine");
 
-    let api_urls = get_api_urls(&db, &cfg.consensus).await;
-
-    // FIXME: (@leonardo) How should this be handled ?
-    // Using the `Connector::default()` for now!
     ConsensusEngine {
         db,
-        federation_api: DynGlobalApi::from_endpoints(
-            api_urls,
-            &force_api_secrets.get_active(),
-            &Connector::default(),
-        ),
+        federation_api,
         self_id_str: cfg.local.identity.to_string(),
         peer_id_str: (0..cfg.consensus.api_endpoints.len())
             .map(|x| x.to_string())
@@ -177,6 +220,9 @@ pub async fn run(
         task_group: task_group.clone(),
         data_dir,
         checkpoint_retention,
+        module_byte_quotas,
+        consensus_key_prefixes,
+        state_snapshot_lock: Arc::default(),
         p2p_bind_addr,
     }
     .run()
@@ -195,8 +241,8 @@ async fn start_consensus_api(
     cfg: &ServerConfigLocal,
     api: ConsensusApi,
     force_api_secrets: ApiSecrets,
-    api_bind: SocketAddr,
-) -> ServerHandle {
+    api_binds: &[SocketAddr],
+) -> ApiServerHandles {
     let mut rpc_module = RpcHandlerCtx::new_module(api.clone());
 
     net::api::attach_endpoints(&mut rpc_module, api::server_endpoints(), None);
@@ -207,7 +253,7 @@ async fn start_consensus_api(
 
     net::api::spawn(
         "consensus",
-        api_bind,
+        api_binds,
         rpc_module,
         cfg.max_connections,
         force_api_secrets,
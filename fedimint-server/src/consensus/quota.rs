@@ -0,0 +1,150 @@
+//! Per session quotas on the consensus items proposed by each module
+//!
+//! A session can only order a bounded amount of data, so a module proposing a
+//! burst of large items, e.g. the wallet catching up on block headers, could
+//! fill our units and delay the items of all other modules. A quota caps the
+//! encoded bytes of the items a module may propose per session. Items over
+//! the quota are not proposed, modules propose their items again every few
+//! seconds anyway, so they are only deferred to a later session. The first
+//! item of a module in a session is always proposed, so an item larger than
+//! the quota is still ordered eventually.
+//!
+//! Quotas are a local setting since they only limit what we propose, items of
+//! our peers are processed regardless. Guardians may thus configure them
+//! differently without affecting consensus.
+
+use std::collections::BTreeMap;
+use std::env;
+
+use anyhow::{bail, Context};
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::module::registry::ModuleRegistry;
+
+use crate::envs::FM_CONSENSUS_MODULE_BYTE_QUOTAS_ENV;
+
+/// Maximum number of encoded bytes of consensus items each module may propose
+/// per session, modules without a quota are unlimited
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleByteQuotas(BTreeMap<ModuleInstanceId, usize>);
+
+impl ModuleByteQuotas {
+    /// Reads the quotas from [`FM_CONSENSUS_MODULE_BYTE_QUOTAS_ENV`], see
+    /// [`Self::parse`]
+    pub fn from_env<M>(modules: &ModuleRegistry<M>) -> anyhow::Result<Self> {
+        match env::var(FM_CONSENSUS_MODULE_BYTE_QUOTAS_ENV) {
+            Ok(quotas) => Self::parse(&quotas, modules)
+                .with_context(|| format!("{FM_CONSENSUS_MODULE_BYTE_QUOTAS_ENV} is invalid")),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Parses comma separated `<module>=<bytes>` pairs, where a module is
+    /// either given by its instance id or its kind, applying the quota to all
+    /// instances of that kind
+    pub fn parse<M>(quotas: &str, modules: &ModuleRegistry<M>) -> anyhow::Result<Self> {
+        let mut parsed = BTreeMap::new();
+
+        for quota in quotas.split(',').map(str::trim).filter(|q| !q.is_empty()) {
+            let (module, bytes) = quota
+                .split_once('=')
+                .with_context(|| format!("Quota {quota} is not of the form <module>=<bytes>"))?;
+            let bytes = bytes
+                .trim()
+                .parse::<usize>()
+                .with_context(|| format!("Quota {quota} has an invalid number of bytes"))?;
+            let module = module.trim();
+
+            let module_ids = modules
+                .iter_modules_id_kind()
+                .filter(|(module_id, kind)| {
+                    kind.as_str() == module || module_id.to_string() == module
+                })
+                .map(|(module_id, _)| module_id)
+                .collect::<Vec<_>>();
+
+            if module_ids.is_empty() {
+                bail!("Quota {quota} refers to an unknown module");
+            }
+
+            for module_id in module_ids {
+                if parsed.insert(module_id, bytes).is_some() {
+                    bail!("Multiple quotas for module {module_id}");
+                }
+            }
+        }
+
+        Ok(Self(parsed))
+    }
+
+    pub fn get(&self, module_id: ModuleInstanceId) -> Option<usize> {
+        self.0.get(&module_id).copied()
+    }
+
+    /// Returns whether the module may propose an item of `n_bytes` after
+    /// proposing items of `used` bytes in this session
+    pub fn admits(&self, module_id: ModuleInstanceId, used: usize, n_bytes: usize) -> bool {
+        used == 0
+            || self
+                .get(module_id)
+                .map_or(true, |quota| used + n_bytes <= quota)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fedimint_core::core::ModuleKind;
+    use fedimint_core::module::registry::ModuleRegistry;
+
+    use super::ModuleByteQuotas;
+
+    fn modules() -> ModuleRegistry<()> {
+        ModuleRegistry::from(BTreeMap::from([
+            (0, (ModuleKind::from_static_str("mint"), ())),
+            (1, (ModuleKind::from_static_str("wallet"), ())),
+            (2, (ModuleKind::from_static_str("ln"), ())),
+            (3, (ModuleKind::from_static_str("ln"), ())),
+        ]))
+    }
+
+    #[test]
+    fn parse_quotas() {
+        let quotas = ModuleByteQuotas::parse("wallet=1000, ln = 500,0=2000", &modules())
+            .expect("valid quotas");
+
+        assert_eq!(quotas.get(0), Some(2000));
+        assert_eq!(quotas.get(1), Some(1000));
+        assert_eq!(quotas.get(2), Some(500));
+        assert_eq!(quotas.get(3), Some(500));
+
+        assert_eq!(
+            ModuleByteQuotas::parse("", &modules()).expect("valid quotas"),
+            ModuleByteQuotas::default()
+        );
+    }
+
+    #[test]
+    fn admit_items_within_quota() {
+        let quotas = ModuleByteQuotas::parse("wallet=1000", &modules()).expect("valid quotas");
+
+        assert!(quotas.admits(1, 0, 400));
+        assert!(quotas.admits(1, 400, 600));
+        assert!(!quotas.admits(1, 400, 601));
+        assert!(quotas.admits(0, 1_000_000, 1_000_000));
+
+        // an item over the quota is admitted as the first one of the session
+        assert!(quotas.admits(1, 0, 1001));
+        assert!(!quotas.admits(1, 1, 1001));
+    }
+
+    #[test]
+    fn reject_invalid_quotas() {
+        for quotas in ["wallet", "wallet=lots", "unknown=100", "4=100", "ln=1,2=1"] {
+            assert!(
+                ModuleByteQuotas::parse(quotas, &modules()).is_err(),
+                "{quotas} should be invalid"
+            );
+        }
+    }
+}
@@ -0,0 +1,761 @@
+//! Snapshot-based state sync for guardians that fell far behind
+//!
+//! A guardian that was offline for a long time, or restored its database from
+//! an old backup, normally catches up by downloading and processing the signed
+//! outcome of every session it missed, which can take days for an old
+//! federation.
+//!
+//! Instead, every [`STATE_SNAPSHOT_INTERVAL`] sessions each guardian writes a
+//! snapshot of its database to [`STATE_SNAPSHOTS_DIR`] in its data directory
+//! and serves it via the API in chunks of
+//! [`STATE_SNAPSHOT_CHUNK_SIZE`] bytes. On startup, a guardian that is at
+//! least [`SNAPSHOT_SYNC_MIN_SESSIONS_BEHIND`] sessions behind the latest
+//! snapshot a threshold of its peers agree on downloads it from any peer,
+//! verifies it against the agreed [`StateSnapshotInfo`] and replaces its
+//! state with it. The sessions completed since the snapshot are then
+//! processed as usual.
+//!
+//! Only the keys under [`ConsensusKeyPrefixes`] are part of a snapshot, data
+//! only relevant to a single guardian, like its own proposals, stays as it is.
+//! The snapshot is applied before the modules are initialized, since they may
+//! load parts of their state into memory. Output outcomes holding a guardian's
+//! own signature shares are not part of the snapshot either, so after the
+//! modules are initialized [`rebuild_output_outcomes`] recreates ours for every
+//! output accepted before the snapshot.
+
+use std::fs;
+use std::io::{BufRead, BufReader, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use fedimint_api_client::api::{DynGlobalApi, FederationApiExt};
+use fedimint_core::config::ServerModuleInitRegistry;
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::db::{
+    Database, DatabaseTransaction, IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped,
+    MODULE_GLOBAL_PREFIX,
+};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::endpoint_constants::{
+    STATE_SNAPSHOT_CHUNK_ENDPOINT, STATE_SNAPSHOT_INFO_ENDPOINT,
+};
+use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::fmt_utils::OptStacktrace;
+use fedimint_core::module::registry::{ModuleDecoderRegistry, ServerModuleRegistry};
+use fedimint_core::module::ApiRequestErased;
+use fedimint_core::task::sleep;
+use fedimint_core::{OutPoint, PeerId};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::config::ServerConfig;
+use crate::consensus::db::{
+    AcceptedItemPrefix, AcceptedTransactionKey, AlephUnitsPrefix, DbKeyPrefix,
+    OutputOutcomeRebuildKey, SignedSessionOutcomeKey,
+};
+use crate::consensus::engine::get_finished_session_count_static;
+use crate::LOG_CONSENSUS;
+
+/// Number of sessions between two state snapshots
+pub const STATE_SNAPSHOT_INTERVAL: u64 = 1000;
+
+/// How many sessions a guardian has to be behind the latest snapshot to sync
+/// from it instead of processing the missed sessions
+pub const SNAPSHOT_SYNC_MIN_SESSIONS_BEHIND: u64 = 100;
+
+/// Size of the chunks snapshots are downloaded in, keeping API responses well
+/// below the maximum decode size
+pub const STATE_SNAPSHOT_CHUNK_SIZE: u64 = 4_000_000;
+
+/// Maximum number of bytes per second a snapshot is written with, so writing
+/// one in the background doesn't starve consensus of disk bandwidth
+const STATE_SNAPSHOT_WRITE_RATE: u64 = 32_000_000;
+
+/// Name of the directory in the data directory snapshots are stored in
+pub const STATE_SNAPSHOTS_DIR: &str = "state_snapshots";
+
+/// Name of the file describing the latest snapshot in [`STATE_SNAPSHOTS_DIR`]
+const LATEST_SNAPSHOT_FILE: &str = "latest.json";
+
+/// Name of the file in [`STATE_SNAPSHOTS_DIR`] a snapshot is downloaded to
+const DOWNLOADED_SNAPSHOT_FILE: &str = "download.snapshot";
+
+/// Number of decoded entries buffered while applying a snapshot
+const APPLY_ENTRIES_BUFFER: usize = 1024;
+
+/// Identifies a snapshot, guardians agreeing on it have the same state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshotInfo {
+    /// Number of sessions completed when the snapshot was taken
+    pub session_count: u64,
+    /// Length of the snapshot file
+    pub size: u64,
+    /// SHA256 hash of the snapshot file
+    pub hash: sha256::Hash,
+}
+
+impl StateSnapshotInfo {
+    pub fn num_chunks(&self) -> u64 {
+        self.size.div_ceil(STATE_SNAPSHOT_CHUNK_SIZE)
+    }
+}
+
+/// Key prefixes of the database holding consensus state, i.e. data that is the
+/// same for all guardians after processing the same sessions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsensusKeyPrefixes(Vec<Vec<u8>>);
+
+impl ConsensusKeyPrefixes {
+    /// Prefixes of the core and of the given module instances with the
+    /// prefixes of their consensus state within their database
+    pub fn new(modules: impl IntoIterator<Item = (ModuleInstanceId, Vec<u8>)>) -> Self {
+        // The units and accepted items of the session in progress are excluded, between
+        // sessions they are empty anyway
+        let mut prefixes = [
+            fedimint_core::db::DbKeyPrefix::DatabaseVersion as u8,
+            DbKeyPrefix::AcceptedTransaction as u8,
+            DbKeyPrefix::SignedSessionOutcome as u8,
+            DbKeyPrefix::SessionFees as u8,
+            DbKeyPrefix::FeeRevenue as u8,
+        ]
+        .into_iter()
+        .map(|prefix| vec![prefix])
+        .collect::<Vec<_>>();
+
+        for (module_id, module_prefixes) in modules {
+            for module_prefix in module_prefixes {
+                let mut prefix = vec![MODULE_GLOBAL_PREFIX];
+                prefix.append(&mut module_id.consensus_encode_to_vec());
+                prefix.push(module_prefix);
+                prefixes.push(prefix);
+            }
+        }
+
+        // Every guardian has to write the entries in the same order to agree on
+        // the snapshot's hash
+        prefixes.sort();
+
+        Self(prefixes)
+    }
+
+    /// Prefixes of the core and the modules configured in `cfg`, or `None` if
+    /// one of the modules doesn't declare its consensus state, in which case
+    /// we can neither take nor apply snapshots
+    pub fn from_config(
+        cfg: &ServerConfig,
+        module_inits: &ServerModuleInitRegistry,
+    ) -> anyhow::Result<Option<Self>> {
+        let mut modules = vec![];
+
+        for (module_id, module_cfg) in &cfg.consensus.modules {
+            let Some(module_init) = module_inits.get(&module_cfg.kind) else {
+                bail!("Detected configuration for unsupported module id: {module_id}");
+            };
+
+            let Some(module_prefixes) = module_init.consensus_db_prefixes() else {
+                info!(
+                    target: LOG_CONSENSUS,
+                    %module_id,
+                    kind = %module_cfg.kind,
+                    "Module doesn't declare its consensus state, state snapshots are disabled"
+                );
+                return Ok(None);
+            };
+
+            modules.push((*module_id, module_prefixes));
+        }
+
+        Ok(Some(Self::new(modules)))
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        self.0.iter().any(|prefix| key.starts_with(prefix))
+    }
+}
+
+/// Writes a snapshot file while hashing it.
+///
+/// A snapshot file consists of the consensus encoding of the number of
+/// completed sessions followed by the raw database entries of the consensus
+/// state, each encoded as a `(Vec<u8>, Vec<u8>)` key value pair, until the end
+/// of the file. Since there is no length prefix the entries can be streamed
+/// from the database instead of being held in memory, which matters as they
+/// include the entire session history.
+struct SnapshotWriter {
+    file: BufWriter<tokio::fs::File>,
+    engine: sha256::HashEngine,
+    size: u64,
+}
+
+impl SnapshotWriter {
+    async fn create(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(tokio::fs::File::create(path).await?),
+            engine: sha256::HashEngine::default(),
+            size: 0,
+        })
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.engine.input(bytes);
+        self.size += bytes.len() as u64;
+        self.file.write_all(bytes).await?;
+        Ok(())
+    }
+
+    async fn finish(mut self, session_count: u64) -> anyhow::Result<StateSnapshotInfo> {
+        self.file.flush().await?;
+        self.file.get_ref().sync_all().await?;
+
+        Ok(StateSnapshotInfo {
+            session_count,
+            size: self.size,
+            hash: sha256::Hash::from_engine(self.engine),
+        })
+    }
+}
+
+/// Writes a snapshot of the state under `prefixes` as seen by `dbtx` to
+/// `snapshots_dir`, replacing the previous one. The transaction has to be
+/// started between sessions, writing the snapshot may take a while and is
+/// throttled to [`STATE_SNAPSHOT_WRITE_RATE`].
+pub async fn write_state_snapshot(
+    dbtx: &mut DatabaseTransaction<'_>,
+    prefixes: &ConsensusKeyPrefixes,
+    snapshots_dir: &Path,
+) -> anyhow::Result<StateSnapshotInfo> {
+    tokio::fs::create_dir_all(snapshots_dir).await?;
+
+    let previous = read_latest_snapshot_info(snapshots_dir).await?;
+
+    let session_count = get_finished_session_count_static(dbtx).await;
+
+    let path = snapshot_path(snapshots_dir, session_count);
+    let tmp_path = path.with_extension("tmp");
+
+    let mut writer = SnapshotWriter::create(&tmp_path).await?;
+
+    writer
+        .write(&session_count.consensus_encode_to_vec())
+        .await?;
+
+    let start = Instant::now();
+    let mut throttled_size = 0;
+
+    for prefix in &prefixes.0 {
+        let mut entries = dbtx.raw_find_by_prefix(prefix).await?;
+
+        while let Some(entry) = entries.next().await {
+            writer.write(&entry.consensus_encode_to_vec()).await?;
+
+            if writer.size - throttled_size >= STATE_SNAPSHOT_CHUNK_SIZE {
+                throttled_size = writer.size;
+
+                let target =
+                    Duration::from_secs_f64(writer.size as f64 / STATE_SNAPSHOT_WRITE_RATE as f64);
+
+                if let Some(ahead) = target.checked_sub(start.elapsed()) {
+                    sleep(ahead).await;
+                }
+            }
+        }
+    }
+
+    let info = writer.finish(session_count).await?;
+
+    tokio::fs::rename(tmp_path, path).await?;
+
+    // Only serve the snapshot once it was written completely
+    let latest_path = snapshots_dir.join(LATEST_SNAPSHOT_FILE);
+    let tmp_path = latest_path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec(&info)?).await?;
+    tokio::fs::rename(tmp_path, latest_path).await?;
+
+    if let Some(previous) = previous.filter(|p| p.session_count != session_count) {
+        tokio::fs::remove_file(snapshot_path(snapshots_dir, previous.session_count)).await?;
+    }
+
+    Ok(info)
+}
+
+/// Replaces the state of `db` under `prefixes` with the snapshot file at
+/// `path`, leaving all other keys untouched, and marks our output outcomes to
+/// be rebuilt by [`rebuild_output_outcomes`]. The file has to be verified
+/// against the agreed [`StateSnapshotInfo`] before.
+pub async fn apply_state_snapshot(
+    db: &Database,
+    prefixes: &ConsensusKeyPrefixes,
+    path: &Path,
+    session_count: u64,
+) -> anyhow::Result<()> {
+    // The file is decoded on a blocking thread and the entries are streamed to us,
+    // so the snapshot is never held in memory as a whole
+    let (entry_sender, mut entry_receiver) = mpsc::channel(APPLY_ENTRIES_BUFFER);
+    let path = path.to_owned();
+    let decoder = tokio::task::spawn_blocking(move || {
+        decode_snapshot_entries(&path, session_count, &entry_sender)
+    });
+
+    let mut dbtx = db.begin_transaction().await;
+
+    for prefix in &prefixes.0 {
+        dbtx.raw_remove_by_prefix(prefix).await?;
+    }
+
+    // The units and accepted items of a session we were in the middle of belong to
+    // our previous state
+    dbtx.remove_by_prefix(&AlephUnitsPrefix).await;
+    dbtx.remove_by_prefix(&AcceptedItemPrefix).await;
+
+    while let Some((key, value)) = entry_receiver.recv().await {
+        ensure!(
+            prefixes.contains(&key),
+            "Snapshot contains keys that are not consensus state"
+        );
+
+        dbtx.raw_insert_bytes(&key, &value).await?;
+    }
+
+    decoder.await??;
+
+    dbtx.insert_entry(&OutputOutcomeRebuildKey, &0).await;
+
+    dbtx.commit_tx_result().await
+}
+
+/// Recreates our outcomes of the outputs accepted before the state snapshot
+/// we synced from, see [`ServerModule::rebuild_output_outcome`]. Does nothing
+/// if we didn't sync from a snapshot and resumes where it left off if it was
+/// interrupted.
+///
+/// Has to be called after the modules are initialized and before consensus is
+/// started.
+///
+/// [`ServerModule::rebuild_output_outcome`]: fedimint_core::module::ServerModule::rebuild_output_outcome
+pub async fn rebuild_output_outcomes(
+    db: &Database,
+    modules: &ServerModuleRegistry,
+) -> anyhow::Result<()> {
+    let mut dbtx = db.begin_transaction_nc().await;
+
+    let Some(mut session_index) = dbtx.get_value(&OutputOutcomeRebuildKey).await else {
+        return Ok(());
+    };
+
+    let session_count = get_finished_session_count_static(&mut dbtx).await;
+
+    drop(dbtx);
+
+    info!(
+        target: LOG_CONSENSUS,
+        session_index,
+        session_count,
+        "Rebuilding output outcomes after syncing from state snapshot"
+    );
+
+    // One transaction per session keeps them small and lets us resume
+    while session_index < session_count {
+        let mut dbtx = db.begin_transaction().await;
+
+        let signed_session_outcome = dbtx
+            .get_value(&SignedSessionOutcomeKey(session_index))
+            .await
+            .with_context(|| format!("Missing outcome of session {session_index}"))?;
+
+        for accepted_item in signed_session_outcome.session_outcome.items {
+            let ConsensusItem::Transaction(transaction) = accepted_item.item else {
+                continue;
+            };
+
+            let txid = transaction.tx_hash();
+
+            // Rejected transactions are part of the session outcome as well
+            if dbtx
+                .get_value(&AcceptedTransactionKey(txid))
+                .await
+                .is_none()
+            {
+                continue;
+            }
+
+            for (output, out_idx) in transaction.outputs.iter().zip(0u64..) {
+                modules
+                    .get_expect(output.module_instance_id())
+                    .rebuild_output_outcome(
+                        &mut dbtx
+                            .to_ref_with_prefix_module_id(output.module_instance_id())
+                            .0,
+                        output,
+                        OutPoint { txid, out_idx },
+                    )
+                    .await?;
+            }
+        }
+
+        session_index += 1;
+
+        dbtx.insert_entry(&OutputOutcomeRebuildKey, &session_index)
+            .await;
+
+        dbtx.commit_tx_result().await?;
+    }
+
+    let mut dbtx = db.begin_transaction().await;
+
+    dbtx.remove_entry(&OutputOutcomeRebuildKey).await;
+
+    dbtx.commit_tx_result().await?;
+
+    info!(target: LOG_CONSENSUS, session_count, "Rebuilt output outcomes");
+
+    Ok(())
+}
+
+/// Decodes the entries of the snapshot file at `path` and sends them to
+/// `entry_sender`
+fn decode_snapshot_entries(
+    path: &Path,
+    session_count: u64,
+    entry_sender: &mpsc::Sender<(Vec<u8>, Vec<u8>)>,
+) -> anyhow::Result<()> {
+    let decoders = ModuleDecoderRegistry::default();
+    let mut reader = BufReader::new(fs::File::open(path)?);
+
+    ensure!(
+        u64::consensus_decode(&mut reader, &decoders)? == session_count,
+        "Snapshot was taken after a different session"
+    );
+
+    while !reader.fill_buf()?.is_empty() {
+        let entry = <(Vec<u8>, Vec<u8>)>::consensus_decode(&mut reader, &decoders)?;
+
+        if entry_sender.blocking_send(entry).is_err() {
+            bail!("Stopped applying the snapshot");
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces our state with the latest snapshot a threshold of our peers
+/// agree on if we are at least [`SNAPSHOT_SYNC_MIN_SESSIONS_BEHIND`] sessions
+/// behind it. Otherwise, or if no peer serves the snapshot, we process the
+/// missed sessions one by one instead.
+///
+/// Has to be called before the modules are initialized.
+pub async fn sync_from_state_snapshot(
+    db: &Database,
+    federation_api: &DynGlobalApi,
+    cfg: &ServerConfig,
+    prefixes: &ConsensusKeyPrefixes,
+    snapshots_dir: &Path,
+) {
+    let session_count =
+        get_finished_session_count_static(&mut db.begin_transaction_nc().await).await;
+
+    let info = match federation_api
+        .request_current_consensus::<Option<StateSnapshotInfo>>(
+            STATE_SNAPSHOT_INFO_ENDPOINT.to_owned(),
+            ApiRequestErased::default(),
+        )
+        .await
+    {
+        Ok(Some(info)) => info,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(target: LOG_CONSENSUS, "Could not agree on a state snapshot with peers: {}", OptStacktrace(e));
+            return;
+        }
+    };
+
+    if info.session_count < session_count + SNAPSHOT_SYNC_MIN_SESSIONS_BEHIND {
+        return;
+    }
+
+    info!(
+        target: LOG_CONSENSUS,
+        session_count,
+        snapshot_session_count = info.session_count,
+        "Syncing from state snapshot"
+    );
+
+    let path = snapshots_dir.join(DOWNLOADED_SNAPSHOT_FILE);
+
+    for peer in cfg
+        .consensus
+        .api_endpoints
+        .keys()
+        .filter(|p| **p != cfg.local.identity)
+    {
+        if let Err(e) = download_state_snapshot(federation_api, *peer, &info, snapshots_dir).await {
+            warn!(target: LOG_CONSENSUS, %peer, "Could not download state snapshot: {e:#}");
+            continue;
+        }
+
+        match apply_state_snapshot(db, prefixes, &path, info.session_count).await {
+            Ok(()) => {
+                info!(target: LOG_CONSENSUS, session_count = info.session_count, "Synced from state snapshot");
+            }
+            Err(e) => {
+                warn!(target: LOG_CONSENSUS, ?e, "Could not apply state snapshot");
+            }
+        }
+
+        tokio::fs::remove_file(&path).await.ok();
+
+        return;
+    }
+
+    tokio::fs::remove_file(&path).await.ok();
+
+    warn!(target: LOG_CONSENSUS, "No peer served the state snapshot, processing missed sessions instead");
+}
+
+/// Downloads the snapshot described by `info` from `peer` to
+/// [`DOWNLOADED_SNAPSHOT_FILE`] in `snapshots_dir` and checks it against
+/// `info`
+async fn download_state_snapshot(
+    federation_api: &DynGlobalApi,
+    peer: PeerId,
+    info: &StateSnapshotInfo,
+    snapshots_dir: &Path,
+) -> anyhow::Result<()> {
+    tokio::fs::create_dir_all(snapshots_dir).await?;
+
+    let mut writer = SnapshotWriter::create(&snapshots_dir.join(DOWNLOADED_SNAPSHOT_FILE)).await?;
+
+    for chunk_index in 0..info.num_chunks() {
+        let chunk: String = federation_api
+            .request_single_peer_federation(
+                STATE_SNAPSHOT_CHUNK_ENDPOINT.to_owned(),
+                ApiRequestErased::new((info.session_count, chunk_index)),
+                peer,
+            )
+            .await?;
+
+        writer.write(&hex::decode(chunk)?).await?;
+
+        ensure!(writer.size <= info.size, "Snapshot is larger than agreed");
+    }
+
+    ensure!(
+        writer.finish(info.session_count).await? == *info,
+        "Snapshot doesn't match the one agreed upon by our peers"
+    );
+
+    Ok(())
+}
+
+/// Returns true if a snapshot should be taken after `session_count` sessions
+pub fn is_snapshot_session(session_count: u64) -> bool {
+    session_count != 0 && session_count % STATE_SNAPSHOT_INTERVAL == 0
+}
+
+/// Path of the snapshot taken after `session_count` sessions in
+/// `snapshots_dir`
+pub fn snapshot_path(snapshots_dir: &Path, session_count: u64) -> PathBuf {
+    snapshots_dir.join(format!("{session_count}.snapshot"))
+}
+
+/// Returns the info of the latest snapshot in `snapshots_dir`, if any
+pub async fn read_latest_snapshot_info(
+    snapshots_dir: &Path,
+) -> anyhow::Result<Option<StateSnapshotInfo>> {
+    match tokio::fs::read(snapshots_dir.join(LATEST_SNAPSHOT_FILE)).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads chunk `chunk_index` of the snapshot taken after `session_count`
+/// sessions from `snapshots_dir`
+pub async fn read_snapshot_chunk(
+    snapshots_dir: &Path,
+    session_count: u64,
+    chunk_index: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let mut file = tokio::fs::File::open(snapshot_path(snapshots_dir, session_count))
+        .await
+        .with_context(|| format!("No snapshot after {session_count} sessions"))?;
+
+    let start = chunk_index.saturating_mul(STATE_SNAPSHOT_CHUNK_SIZE);
+    ensure!(
+        start < file.metadata().await?.len(),
+        "Chunk index out of range"
+    );
+
+    file.seek(SeekFrom::Start(start)).await?;
+
+    let mut chunk = vec![];
+    file.take(STATE_SNAPSHOT_CHUNK_SIZE)
+        .read_to_end(&mut chunk)
+        .await?;
+
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{
+        Database, IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped,
+        MODULE_GLOBAL_PREFIX,
+    };
+    use fedimint_core::encoding::Encodable;
+    use fedimint_core::module::registry::ModuleDecoderRegistry;
+    use fedimint_testing_core::test_dir;
+    use futures::StreamExt;
+
+    use super::{
+        apply_state_snapshot, read_snapshot_chunk, snapshot_path, write_state_snapshot,
+        ConsensusKeyPrefixes, SnapshotWriter,
+    };
+    use crate::consensus::db::{DbKeyPrefix, OutputOutcomeRebuildKey};
+
+    fn new_db() -> Database {
+        Database::new(MemDatabase::new(), ModuleDecoderRegistry::default())
+    }
+
+    async fn consensus_entries(
+        db: &Database,
+        prefixes: &ConsensusKeyPrefixes,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut dbtx = db.begin_transaction_nc().await;
+        let mut entries = vec![];
+
+        for prefix in &prefixes.0 {
+            entries.extend(
+                dbtx.raw_find_by_prefix(prefix)
+                    .await
+                    .unwrap()
+                    .collect::<Vec<_>>()
+                    .await,
+            );
+        }
+
+        entries
+    }
+
+    #[tokio::test]
+    async fn apply_snapshot_keeps_local_keys() {
+        let (dir, _guard) = test_dir("apply_snapshot_keeps_local_keys");
+
+        // Module 1 keeps its consensus state under prefix 0x10
+        let prefixes = ConsensusKeyPrefixes::new([(1, vec![0x10])]);
+        let module_key = |prefix: u8| {
+            let mut key = vec![MODULE_GLOBAL_PREFIX];
+            key.append(&mut 1u16.consensus_encode_to_vec());
+            key.extend([prefix, 1]);
+            key
+        };
+
+        let source = new_db();
+        let mut dbtx = source.begin_transaction().await;
+        dbtx.raw_insert_bytes(&[DbKeyPrefix::AcceptedTransaction as u8, 1], &[1])
+            .await
+            .unwrap();
+        dbtx.raw_insert_bytes(&module_key(0x10), &[2])
+            .await
+            .unwrap();
+        dbtx.raw_insert_bytes(&[DbKeyPrefix::AdminKeys as u8, 1], &[4])
+            .await
+            .unwrap();
+        dbtx.raw_insert_bytes(&module_key(0x11), &[5])
+            .await
+            .unwrap();
+        dbtx.commit_tx().await;
+
+        let info = write_state_snapshot(&mut source.begin_transaction_nc().await, &prefixes, &dir)
+            .await
+            .unwrap();
+        assert_eq!(info.session_count, 0);
+        assert_eq!(info.num_chunks(), 1);
+
+        let chunk = read_snapshot_chunk(&dir, 0, 0).await.unwrap();
+        assert_eq!(chunk.len() as u64, info.size);
+        assert!(read_snapshot_chunk(&dir, 0, 1).await.is_err());
+
+        let target = new_db();
+        let mut dbtx = target.begin_transaction().await;
+        dbtx.raw_insert_bytes(&[DbKeyPrefix::AcceptedTransaction as u8, 2], &[6])
+            .await
+            .unwrap();
+        dbtx.raw_insert_bytes(&[DbKeyPrefix::AdminKeys as u8, 2], &[7])
+            .await
+            .unwrap();
+        dbtx.raw_insert_bytes(&[DbKeyPrefix::AlephUnits as u8, 2], &[8])
+            .await
+            .unwrap();
+        dbtx.raw_insert_bytes(&[DbKeyPrefix::AcceptedItem as u8, 2], &[9])
+            .await
+            .unwrap();
+        dbtx.commit_tx().await;
+
+        apply_state_snapshot(&target, &prefixes, &snapshot_path(&dir, 0), 0)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            consensus_entries(&target, &prefixes).await,
+            vec![
+                (vec![DbKeyPrefix::AcceptedTransaction as u8, 1], vec![1]),
+                (module_key(0x10), vec![2]),
+            ]
+        );
+
+        // Our local data survives the sync, the state of the unfinished session
+        // is discarded
+        let mut dbtx = target.begin_transaction_nc().await;
+        assert_eq!(
+            dbtx.raw_get_bytes(&[DbKeyPrefix::AdminKeys as u8, 2])
+                .await
+                .unwrap(),
+            Some(vec![7])
+        );
+        assert_eq!(
+            dbtx.raw_get_bytes(&[DbKeyPrefix::AlephUnits as u8, 2])
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            dbtx.raw_get_bytes(&[DbKeyPrefix::AcceptedItem as u8, 2])
+                .await
+                .unwrap(),
+            None
+        );
+
+        // Our output outcomes are rebuilt from the first session on
+        assert_eq!(dbtx.get_value(&OutputOutcomeRebuildKey).await, Some(0));
+    }
+
+    #[tokio::test]
+    async fn reject_snapshot_with_local_keys() {
+        let (dir, _guard) = test_dir("reject_snapshot_with_local_keys");
+        let path = dir.join("local.snapshot");
+
+        let mut writer = SnapshotWriter::create(&path).await.unwrap();
+        writer.write(&1u64.consensus_encode_to_vec()).await.unwrap();
+        writer
+            .write(&(vec![DbKeyPrefix::AdminKeys as u8, 1], vec![1u8]).consensus_encode_to_vec())
+            .await
+            .unwrap();
+        writer.finish(1).await.unwrap();
+
+        let prefixes = ConsensusKeyPrefixes::new([]);
+        let db = new_db();
+        assert!(apply_state_snapshot(&db, &prefixes, &path, 1)
+            .await
+            .is_err());
+        // The snapshot was taken after a different session
+        assert!(apply_state_snapshot(&db, &prefixes, &path, 2)
+            .await
+            .is_err());
+    }
+}
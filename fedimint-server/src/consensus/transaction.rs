@@ -7,18 +7,20 @@
 
 use crate::metrics::{CONSENSUS_TX_PROCESSED_INPUTS, CONSENSUS_TX_PROCESSED_OUTPUTS};
 
+/// Processes the transaction and returns the fee revenue it generates, which
+/// includes the amount the transaction is overfunded by
 pub async fn process_transaction_with_dbtx(
     modules: ServerModuleRegistry,
     dbtx: &mut DatabaseTransaction<'_>,
     transaction: &Transaction,
     version: CoreConsensusVersion,
-) -> Result<(), TransactionError> {
+) -> Result<Amount, TransactionError> {
     let in_count = transaction.inputs.len();
     let out_count = transaction.outputs.len();
 
     dbtx.on_commit(move || {
-        CONSENSUS_TX_PROCESSED_INPUTS.observe(in_count as f64);
-        CONSENSUS_TX_PROCESSED_OUTPUTS.observe(out_count as f64);
+        CONSENSUS_TX_PROCESSED_INPUTS.observe(&[], in_count as f64);
+        CONSENSUS_TX_PROCESSED_OUTPUTS.observe(&[], out_count as f64);
     });
 
     // We can not return the error here as errors are not returned in a specified
@@ -75,9 +77,7 @@ pub async fn process_transaction_with_dbtx(
         funding_verifier.add_output(amount)?;
     }
 
-    funding_verifier.verify_funding(version)?;
-
-    Ok(())
+    funding_verifier.verify_funding(version)
 }
 
 pub struct FundingVerifier {
@@ -121,18 +121,19 @@ pub fn add_output(
         Ok(())
     }
 
-    pub fn verify_funding(self, version: CoreConsensusVersion) -> Result<(), TransactionError> {
+    /// Returns the fee revenue of the transaction if it is funded
+    pub fn verify_funding(self, version: CoreConsensusVersion) -> Result<Amount, TransactionError> {
         let outputs_and_fees = self
             .output_amount
             .checked_add(self.fee_amount)
             .ok_or(TRANSACTION_OVERFLOW_ERROR)?;
 
         if self.input_amount == outputs_and_fees {
-            return Ok(());
+            return Ok(self.fee_amount);
         }
 
         if self.input_amount > outputs_and_fees && version >= CoreConsensusVersion::new(2, 1) {
-            return Ok(());
+            return Ok(self.input_amount.saturating_sub(self.output_amount));
         }
 
         Err(TransactionError::UnbalancedTransaction {
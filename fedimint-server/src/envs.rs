@@ -9,3 +9,19 @@
 // Default number of checkpoints from the current session should be retained on
 // disk.
 pub const FM_DB_CHECKPOINT_RETENTION_DEFAULT: u64 = 1;
+
+/// Environment variable limiting how many bytes of consensus items each module
+/// may propose per session, as comma separated `<module kind or id>=<bytes>`
+/// pairs, see [`crate::consensus::quota`]
+pub const FM_CONSENSUS_MODULE_BYTE_QUOTAS_ENV: &str = "FM_CONSENSUS_MODULE_BYTE_QUOTAS";
+
+/// If set, the guardian password is no longer accepted for API requests once at
+/// least one admin key is authorized, so admin endpoints can only be called
+/// with signed requests. Changing the admin keys then requires restarting
+/// without it.
+pub const FM_DISABLE_PASSWORD_AUTH_WITH_ADMIN_KEYS_ENV: &str =
+    "FM_DISABLE_PASSWORD_AUTH_WITH_ADMIN_KEYS";
+
+/// Environment variable for the address of a Tor SOCKS5 proxy, e.g.
+/// `127.0.0.1:9050`, through which we connect to peers with an onion p2p URL
+pub const FM_P2P_TOR_SOCKS5_PROXY_ENV: &str = "FM_P2P_TOR_SOCKS5_PROXY";
@@ -37,7 +37,7 @@
 use crate::metrics::initialize_gauge_metrics;
 use crate::net::api::announcement::start_api_announcement_service;
 use crate::net::api::RpcHandlerCtx;
-use crate::net::connect::TlsTcpConnector;
+use crate::net::tor::start_api_onion_service;
 
 pub mod envs;
 pub mod metrics;
@@ -57,12 +57,35 @@
 pub async fn run(
     data_dir: PathBuf,
     force_api_secrets: ApiSecrets,
-    settings: ConfigGenSettings,
+    mut settings: ConfigGenSettings,
     db: Database,
     code_version_str: String,
     module_init_registry: &ServerModuleInitRegistry,
     task_group: TaskGroup,
 ) -> anyhow::Result<()> {
+    // The onion service is removed by Tor once this is dropped at the end of the
+    // function, so it is reachable exactly as long as our API is running
+    let api_onion_service = match &settings.tor_control {
+        Some(tor_control) => {
+            Some(start_api_onion_service(tor_control, settings.api_bind, &data_dir).await?)
+        }
+        None => None,
+    };
+
+    let announced_api_url = api_onion_service
+        .as_ref()
+        .filter(|_| {
+            settings
+                .tor_control
+                .as_ref()
+                .is_some_and(|tor| tor.announce)
+        })
+        .map(|service| service.url.clone());
+
+    if let Some(url) = &announced_api_url {
+        settings.api_url = url.clone();
+    }
+
     let cfg = match get_config(&data_dir)? {
         Some(cfg) => cfg,
         None => {
@@ -89,11 +112,18 @@ pub async fn run(
 
     initialize_gauge_metrics(&db).await;
 
-    start_api_announcement_service(&db, &task_group, &cfg, force_api_secrets.get_active()).await;
+    start_api_announcement_service(
+        &db,
+        &task_group,
+        &cfg,
+        force_api_secrets.get_active(),
+        announced_api_url,
+    )
+    .await;
 
     consensus::run(
         settings.p2p_bind,
-        settings.api_bind,
+        settings.api_binds(),
         cfg,
         db,
         module_init_registry.clone(),
@@ -164,7 +194,7 @@ pub async fn run_config_gen(
 
     let api_handler = net::api::spawn(
         "config-gen",
-        settings.api_bind,
+        &settings.api_binds(),
         rpc_module,
         10,
         force_api_secrets.clone(),
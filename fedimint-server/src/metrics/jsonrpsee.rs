@@ -9,7 +9,7 @@
 use std::task;
 use std::task::Poll;
 
-use fedimint_metrics::prometheus::HistogramTimer;
+use fedimint_core::metrics::HistogramTimer;
 use futures::Future;
 use jsonrpsee::server::middleware::rpc::RpcServiceT;
 use jsonrpsee::types::Request;
@@ -44,23 +44,21 @@ fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output>
             if let Some(timer) = projected.timer.take() {
                 timer.observe_duration();
 
-                JSONRPC_API_REQUEST_RESPONSE_CODE
-                    .with_label_values(&[
-                        &projected.method,
-                        &if let Some(code) = res.as_error_code() {
-                            Cow::Owned(code.to_string())
-                        } else {
-                            Cow::Borrowed("0")
-                        },
-                        if res.is_subscription() {
-                            "subscription"
-                        } else if res.is_batch() {
-                            "batch"
-                        } else {
-                            "default"
-                        },
-                    ])
-                    .inc();
+                JSONRPC_API_REQUEST_RESPONSE_CODE.inc(&[
+                    &projected.method,
+                    &if let Some(code) = res.as_error_code() {
+                        Cow::Owned(code.to_string())
+                    } else {
+                        Cow::Borrowed("0")
+                    },
+                    if res.is_subscription() {
+                        "subscription"
+                    } else if res.is_batch() {
+                        "batch"
+                    } else {
+                        "default"
+                    },
+                ]);
             }
         }
         res
@@ -89,9 +87,7 @@ impl<'a, S> RpcServiceT<'a> for MetricsService<S>
     type Future = ResponseFuture<S::Future>;
 
     fn call(&self, req: Request<'a>) -> Self::Future {
-        let timer = JSONRPC_API_REQUEST_DURATION_SECONDS
-            .with_label_values(&[req.method_name()])
-            .start_timer();
+        let timer = JSONRPC_API_REQUEST_DURATION_SECONDS.start_timer(&[req.method_name()]);
 
         ResponseFuture {
             method: req.method.to_string(),
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::net::admin_auth::AdminRequestSignature;
+use fedimint_core::secp256k1::{PublicKey, SECP256K1};
+use fedimint_core::time::duration_since_epoch;
+use fedimint_core::{impl_db_lookup, impl_db_record, PeerId};
+use fedimint_logging::LOG_NET_AUTH;
+use jsonrpsee::core::JsonValue;
+use tracing::debug;
+
+use crate::consensus::db::DbKeyPrefix;
+
+/// How far the timestamp of a signed admin request may deviate from our clock.
+/// Nonces only need to be remembered for this long, older requests are
+/// rejected on their timestamp alone.
+const ADMIN_REQUEST_MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// Admin key the guardian authorized to call admin endpoints, mapped to a
+/// label describing who holds it
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct AdminKey(pub PublicKey);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct AdminKeyPrefix;
+
+impl_db_record!(
+    key = AdminKey,
+    value = String,
+    db_prefix = DbKeyPrefix::AdminKeys,
+    notify_on_modify = false,
+);
+impl_db_lookup!(key = AdminKey, query_prefix = AdminKeyPrefix);
+
+/// Verifies signed admin requests and keeps track of the ones already seen to
+/// reject replays
+#[derive(Debug)]
+pub struct AdminRequestVerifier {
+    /// Federation requests have to be signed for
+    federation_id: FederationId,
+    /// Guardian requests have to be signed for, i.e. us
+    our_peer_id: PeerId,
+    /// `(timestamp, public_key, nonce)` of all accepted requests that are still
+    /// within the clock skew window
+    seen: Mutex<BTreeSet<(u64, PublicKey, u64)>>,
+}
+
+impl AdminRequestVerifier {
+    pub fn new(federation_id: FederationId, our_peer_id: PeerId) -> Self {
+        Self {
+            federation_id,
+            our_peer_id,
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Returns true if the request to `method` was signed for it by the key it
+    /// claims and wasn't seen before. Whether the key is authorized has to be
+    /// checked separately.
+    pub fn verify(
+        &self,
+        method: &str,
+        signature: &AdminRequestSignature,
+        params: &JsonValue,
+    ) -> bool {
+        self.verify_at(method, signature, params, duration_since_epoch())
+    }
+
+    fn verify_at(
+        &self,
+        method: &str,
+        signature: &AdminRequestSignature,
+        params: &JsonValue,
+        now: Duration,
+    ) -> bool {
+        if signature.method != method {
+            debug!(
+                target: LOG_NET_AUTH,
+                %method,
+                signed_method = %signature.method,
+                "Rejecting admin request signed for a different method"
+            );
+            return false;
+        }
+
+        if signature.federation_id != self.federation_id || signature.peer_id != self.our_peer_id {
+            debug!(
+                target: LOG_NET_AUTH,
+                method = %signature.method,
+                peer_id = %signature.peer_id,
+                "Rejecting admin request signed for a different guardian"
+            );
+            return false;
+        }
+
+        let timestamp = Duration::from_secs(signature.timestamp);
+        let skew = timestamp.max(now) - timestamp.min(now);
+        if skew > ADMIN_REQUEST_MAX_CLOCK_SKEW {
+            debug!(
+                target: LOG_NET_AUTH,
+                method = %signature.method,
+                timestamp = signature.timestamp,
+                "Rejecting admin request with stale timestamp"
+            );
+            return false;
+        }
+
+        if !signature.verify(SECP256K1, params) {
+            debug!(target: LOG_NET_AUTH, method = %signature.method, "Rejecting admin request with invalid signature");
+            return false;
+        }
+
+        let mut seen = self.seen.lock().expect("poisoned");
+        let oldest_valid = now.saturating_sub(ADMIN_REQUEST_MAX_CLOCK_SKEW).as_secs();
+        seen.retain(|(timestamp, ..)| oldest_valid <= *timestamp);
+
+        if !seen.insert((signature.timestamp, signature.public_key, signature.nonce)) {
+            debug!(target: LOG_NET_AUTH, method = %signature.method, "Rejecting replayed admin request");
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bitcoin::hashes::{sha256, Hash};
+    use fedimint_core::config::FederationId;
+    use fedimint_core::net::admin_auth::AdminRequestSignature;
+    use fedimint_core::secp256k1::{Keypair, SECP256K1};
+    use fedimint_core::time::duration_since_epoch;
+    use fedimint_core::PeerId;
+    use serde_json::json;
+
+    use super::{AdminRequestVerifier, ADMIN_REQUEST_MAX_CLOCK_SKEW};
+
+    fn federation_id() -> FederationId {
+        FederationId(sha256::Hash::hash(b"federation"))
+    }
+
+    fn verifier() -> AdminRequestVerifier {
+        AdminRequestVerifier::new(federation_id(), PeerId::from(0))
+    }
+
+    fn sign(key: &Keypair, method: &str) -> AdminRequestSignature {
+        AdminRequestSignature::sign(
+            SECP256K1,
+            key,
+            federation_id(),
+            PeerId::from(0),
+            method,
+            &json!(null),
+        )
+    }
+
+    #[test]
+    fn admin_requests_cannot_be_replayed() {
+        let verifier = verifier();
+        let key = Keypair::new(SECP256K1, &mut rand::thread_rng());
+        let params = json!(null);
+
+        let signature = sign(&key, "audit");
+        assert!(verifier.verify("audit", &signature, &params));
+        assert!(!verifier.verify("audit", &signature, &params));
+
+        let signature = sign(&key, "audit");
+        assert!(verifier.verify("audit", &signature, &params));
+    }
+
+    #[test]
+    fn admin_requests_cannot_be_replayed_on_other_methods() {
+        let verifier = verifier();
+        let key = Keypair::new(SECP256K1, &mut rand::thread_rng());
+        let params = json!(null);
+
+        let signature = sign(&key, "audit");
+        assert!(!verifier.verify("shutdown", &signature, &params));
+        assert!(verifier.verify("audit", &signature, &params));
+    }
+
+    #[test]
+    fn admin_requests_expire() {
+        let verifier = verifier();
+        let key = Keypair::new(SECP256K1, &mut rand::thread_rng());
+        let params = json!(null);
+        let signature = sign(&key, "audit");
+
+        let late = duration_since_epoch() + ADMIN_REQUEST_MAX_CLOCK_SKEW + Duration::from_secs(2);
+        assert!(!verifier.verify_at("audit", &signature, &params, late));
+        assert!(verifier.verify("audit", &signature, &params));
+    }
+
+    #[test]
+    fn admin_requests_cannot_be_replayed_against_other_guardians() {
+        let key = Keypair::new(SECP256K1, &mut rand::thread_rng());
+        let params = json!(null);
+        let signature = sign(&key, "audit");
+
+        // Another guardian of the same federation that authorized the same key
+        let other_peer = AdminRequestVerifier::new(federation_id(), PeerId::from(1));
+        assert!(!other_peer.verify("audit", &signature, &params));
+
+        // The same guardian index in a different federation
+        let other_federation = AdminRequestVerifier::new(
+            FederationId(sha256::Hash::hash(b"other federation")),
+            PeerId::from(0),
+        );
+        assert!(!other_federation.verify("audit", &signature, &params));
+
+        assert!(verifier().verify("audit", &signature, &params));
+    }
+}
@@ -39,6 +39,7 @@ pub async fn start_api_announcement_service(
     tg: &TaskGroup,
     cfg: &ServerConfig,
     api_secret: Option<String>,
+    announced_api_url: Option<SafeUrl>,
 ) {
     const INITIAL_DEALY_SECONDS: u64 = 5;
     const FAILURE_RETRY_SECONDS: u64 = 60;
@@ -46,6 +47,10 @@ pub async fn start_api_announcement_service(
 
     insert_signed_api_announcement_if_not_present(db, cfg).await;
 
+    if let Some(api_url) = announced_api_url {
+        update_signed_api_announcement(db, cfg, api_url).await;
+    }
+
     let db = db.clone();
     // FIXME: (@leonardo) how should we handle the connector here ?
     let api_client = DynGlobalApi::from_endpoints(
@@ -118,6 +123,32 @@ async fn insert_signed_api_announcement_if_not_present(db: &Database, cfg: &Serv
     dbtx.commit_tx().await;
 }
 
+/// Replaces our API announcement with one for `api_url` unless we already
+/// announce that URL, e.g. when our API became reachable via a new onion
+/// service after the federation was set up.
+async fn update_signed_api_announcement(db: &Database, cfg: &ServerConfig, api_url: SafeUrl) {
+    let mut dbtx = db.begin_transaction().await;
+    let nonce = match dbtx
+        .get_value(&ApiAnnouncementKey(cfg.local.identity))
+        .await
+    {
+        Some(announcement) if announcement.api_announcement.api_url == api_url => return,
+        Some(announcement) => announcement.api_announcement.nonce + 1,
+        None => 0,
+    };
+
+    let ctx = secp256k1::Secp256k1::new();
+    let signed_announcement = ApiAnnouncement::new(api_url, nonce)
+        .sign(&ctx, &cfg.private.broadcast_secret_key.keypair(&ctx));
+
+    dbtx.insert_entry(
+        &ApiAnnouncementKey(cfg.local.identity),
+        &signed_announcement,
+    )
+    .await;
+    dbtx.commit_tx().await;
+}
+
 /// Returns a list of all peers and their respective API URLs taking into
 /// account announcements overwriting the URLs contained in the original
 /// configuration.
@@ -0,0 +1,132 @@
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use fedimint_core::net::api_compression::{
+    CompressedResponse, ACCEPT_ENCODING_HEADER, MIN_COMPRESSED_RESPONSE_LEN, ZSTD_ENCODING,
+};
+use fedimint_logging::LOG_NET_API;
+use hyper::http::Extensions;
+use hyper::Request;
+use serde_json::Value;
+use tower::Service;
+use tracing::warn;
+
+/// Favors speed over ratio, the hex encoded blobs in responses compress well
+/// even at low levels
+const ZSTD_LEVEL: i32 = 3;
+
+/// Marks connections whose client accepts zstd compressed responses, see
+/// [`fedimint_core::net::api_compression`]
+#[derive(Debug, Clone, Copy)]
+struct AcceptsZstd;
+
+/// Records in the extensions of every request, which are passed on to the API
+/// handlers, whether the client accepts compressed responses
+#[derive(Clone, Debug, Default)]
+pub struct CompressionNegotiationLayer;
+
+impl<S> tower::Layer<S> for CompressionNegotiationLayer {
+    type Service = CompressionNegotiationService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        CompressionNegotiationService { inner: service }
+    }
+}
+
+#[derive(Clone)]
+pub struct CompressionNegotiationService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for CompressionNegotiationService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let accepts_zstd = req
+            .headers()
+            .get(ACCEPT_ENCODING_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|encoding| encoding.trim() == ZSTD_ENCODING)
+            });
+        if accepts_zstd {
+            req.extensions_mut().insert(AcceptsZstd);
+        }
+        self.inner.call(req)
+    }
+}
+
+/// Replaces `response` with a [`CompressedResponse`] if the client accepts it
+/// and compressing is worth it
+pub fn compress_response(response: Value, extensions: &Extensions) -> Value {
+    if extensions.get::<AcceptsZstd>().is_none() {
+        return response;
+    }
+
+    let serialized = serde_json::to_vec(&response).expect("JSON values can be serialized");
+    if serialized.len() < MIN_COMPRESSED_RESPONSE_LEN {
+        return response;
+    }
+
+    let compressed = match zstd::bulk::compress(&serialized, ZSTD_LEVEL) {
+        Ok(compressed) => compressed,
+        Err(err) => {
+            warn!(target: LOG_NET_API, %err, "Failed to compress API response");
+            return response;
+        }
+    };
+    // Base64 adds a third, so poorly compressible responses are sent as they are
+    if serialized.len() <= compressed.len() * 4 / 3 {
+        return response;
+    }
+
+    serde_json::to_value(CompressedResponse {
+        zstd_base64: STANDARD.encode(compressed),
+    })
+    .expect("Can't fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine as _;
+    use fedimint_core::net::api_compression::CompressedResponse;
+    use hyper::http::Extensions;
+    use serde_json::{json, Value};
+
+    use super::{compress_response, AcceptsZstd};
+
+    #[test]
+    fn compresses_large_responses_if_accepted() {
+        let small = json!({ "session": "00ff" });
+        let large = json!({ "session": "00ff".repeat(10_000) });
+
+        let mut accepts_zstd = Extensions::new();
+        accepts_zstd.insert(AcceptsZstd);
+
+        assert_eq!(compress_response(small.clone(), &accepts_zstd), small);
+        assert_eq!(compress_response(large.clone(), &Extensions::new()), large);
+
+        let compressed: CompressedResponse =
+            serde_json::from_value(compress_response(large.clone(), &accepts_zstd)).unwrap();
+        let decompressed =
+            zstd::stream::decode_all(STANDARD.decode(compressed.zstd_base64).unwrap().as_slice())
+                .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&decompressed).unwrap(),
+            large
+        );
+    }
+}
@@ -1,4 +1,6 @@
+pub mod admin_auth;
 pub mod announcement;
+mod compression;
 mod http_auth;
 
 use std::fmt::{self, Debug, Formatter};
@@ -15,12 +17,15 @@
 use fedimint_core::module::{ApiEndpoint, ApiEndpointContext, ApiError, ApiRequestErased};
 use fedimint_logging::LOG_NET_API;
 use futures::FutureExt;
-use jsonrpsee::server::{PingConfig, RpcServiceBuilder, ServerBuilder, ServerHandle};
+use jsonrpsee::server::{
+    AlreadyStoppedError, PingConfig, RpcServiceBuilder, ServerBuilder, ServerHandle,
+};
 use jsonrpsee::types::ErrorObject;
-use jsonrpsee::RpcModule;
+use jsonrpsee::{Methods, RpcModule};
 use tracing::{error, info};
 
 use crate::metrics;
+use crate::net::api::compression::{compress_response, CompressionNegotiationLayer};
 use crate::net::api::http_auth::HttpAuthLayer;
 
 #[derive(Clone, Encodable, Decodable, Default)]
@@ -103,11 +108,13 @@ fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 /// Has the context necessary for serving API endpoints
 ///
 /// Returns the specific `State` the endpoint requires and the
-/// `ApiEndpointContext` which all endpoints can access.
+/// `ApiEndpointContext` which all endpoints can access. `method` is the path
+/// of the called endpoint.
 #[async_trait]
 pub trait HasApiContext<State> {
     async fn context(
         &self,
+        method: &str,
         request: &ApiRequestErased,
         id: Option<ModuleInstanceId>,
     ) -> (&State, ApiEndpointContext<'_>);
@@ -131,29 +138,63 @@ pub fn check_auth(context: &mut ApiEndpointContext) -> ApiResult<GuardianAuthTok
     }
 }
 
+/// Handles of the API servers started by [`spawn`], one per bind address
+pub struct ApiServerHandles(Vec<ServerHandle>);
+
+impl ApiServerHandles {
+    /// Tell all servers to stop, fails if any of them was already stopped
+    pub fn stop(&self) -> Result<(), AlreadyStoppedError> {
+        // Stop every server before reporting an error, so none is left running
+        self.0
+            .iter()
+            .map(ServerHandle::stop)
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Wait for all servers to be stopped
+    pub async fn stopped(self) {
+        futures::future::join_all(self.0.into_iter().map(ServerHandle::stopped)).await;
+    }
+}
+
+/// Start serving `module` on every address in `api_bind_addrs`, e.g. both an
+/// IPv4 and an IPv6 address or a local port an onion service forwards to.
 pub async fn spawn<T>(
     name: &'static str,
-    api_bind_addr: SocketAddr,
+    api_bind_addrs: &[SocketAddr],
     module: RpcModule<RpcHandlerCtx<T>>,
     max_connections: u32,
     force_api_secrets: ApiSecrets,
-) -> ServerHandle {
-    info!(target: LOG_NET_API, "Starting api on ws://{api_bind_addr}");
-
-    let builder =
-        tower::ServiceBuilder::new().layer(HttpAuthLayer::new(force_api_secrets.get_all()));
-
-    ServerBuilder::new()
-        .max_connections(max_connections)
-        .enable_ws_ping(PingConfig::new().ping_interval(Duration::from_secs(10)))
-        .set_rpc_middleware(RpcServiceBuilder::new().layer(metrics::jsonrpsee::MetricsLayer))
-        .set_http_middleware(builder)
-        .build(&api_bind_addr.to_string())
-        .await
-        .context(format!("Bind address: {api_bind_addr}"))
-        .context(format!("API name: {name}"))
-        .expect("Could not build API server")
-        .start(module)
+) -> ApiServerHandles
+where
+    T: Send + Sync + 'static,
+{
+    assert!(!api_bind_addrs.is_empty(), "No API bind address given");
+
+    let methods: Methods = module.into();
+    let mut handles = Vec::with_capacity(api_bind_addrs.len());
+    for api_bind_addr in api_bind_addrs {
+        info!(target: LOG_NET_API, "Starting api on ws://{api_bind_addr}");
+
+        let builder = tower::ServiceBuilder::new()
+            .layer(HttpAuthLayer::new(force_api_secrets.get_all()))
+            .layer(CompressionNegotiationLayer);
+
+        let handle = ServerBuilder::new()
+            .max_connections(max_connections)
+            .enable_ws_ping(PingConfig::new().ping_interval(Duration::from_secs(10)))
+            .set_rpc_middleware(RpcServiceBuilder::new().layer(metrics::jsonrpsee::MetricsLayer))
+            .set_http_middleware(builder)
+            .build(&api_bind_addr.to_string())
+            .await
+            .context(format!("Bind address: {api_bind_addr}"))
+            .context(format!("API name: {name}"))
+            .expect("Could not build API server")
+            .start(methods.clone());
+        handles.push(handle);
+    }
+
+    ApiServerHandles(handles)
 }
 
 pub fn attach_endpoints<State, T>(
@@ -183,7 +224,7 @@ pub fn attach_endpoints<State, T>(
         let handler: &'static _ = Box::leak(endpoint.handler);
 
         rpc_module
-            .register_async_method(path, move |params, rpc_state, _extensions| async move {
+            .register_async_method(path, move |params, rpc_state, extensions| async move {
                 let params = params.one::<serde_json::Value>()?;
                 let rpc_context = &rpc_state.rpc_context;
 
@@ -192,9 +233,11 @@ pub fn attach_endpoints<State, T>(
                 // are only reading and the few that do write anything are atomic. Lastly, this
                 // is only the last line of defense
                 AssertUnwindSafe(tokio::time::timeout(API_ENDPOINT_TIMEOUT, async {
-                    let request = serde_json::from_value(params)
+                    let request: ApiRequestErased = serde_json::from_value(params)
                         .map_err(|e| ApiError::bad_request(e.to_string()))?;
-                    let (state, context) = rpc_context.context(&request, module_instance_id).await;
+                    let (state, context) = rpc_context
+                        .context(path, &request, module_instance_id)
+                        .await;
 
                     (handler)(state, context, request).await
                 }))
@@ -214,6 +257,7 @@ pub fn attach_endpoints<State, T>(
                     ErrorObject::owned(-32000, "Request timeout", None::<()>)
                 })?
                 .map_err(|e| ErrorObject::owned(e.code, e.message, None::<()>))
+                .map(|response| compress_response(response, &extensions))
             })
             .expect("Failed to register async method");
     }
@@ -17,8 +17,15 @@
 use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
 use tokio_rustls::rustls::RootCertStore;
 use tokio_rustls::{rustls, TlsAcceptor, TlsConnector, TlsStream};
+use tokio_socks::tcp::Socks5Stream;
 
+use crate::envs::FM_P2P_TOR_SOCKS5_PROXY_ENV;
 use crate::net::framed::{AnyFramedTransport, BidiFramed, FramedTransport};
+use crate::net::quic::QuicConnector;
+
+/// URL scheme of peers accepting connections over QUIC, the `fedimint` scheme
+/// stands for TLS over TCP
+pub const QUIC_SCHEME: &str = "fedimint+quic";
 
 /// Shared [`Connector`] trait object
 pub type SharedAnyConnector<M> = Arc<dyn Connector<M> + Send + Sync + Unpin + 'static>;
@@ -55,6 +62,83 @@ fn into_dyn(self) -> AnyConnector<M>
     }
 }
 
+/// Transport over which a peer accepts connections, following the scheme of
+/// its p2p URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerTransport {
+    Tcp,
+    Quic,
+}
+
+impl PeerTransport {
+    pub fn of(url: &SafeUrl) -> PeerTransport {
+        if url.scheme() == QUIC_SCHEME {
+            PeerTransport::Quic
+        } else {
+            PeerTransport::Tcp
+        }
+    }
+}
+
+/// Connector picking the transport to every peer by the scheme of its p2p URL,
+/// so a federation can mix peers reachable over TCP, Tor and QUIC
+///
+/// Incoming connections are accepted over the transport of our own p2p URL.
+#[derive(Debug)]
+pub struct TransportConnector {
+    tcp: TlsTcpConnector,
+    quic: QuicConnector,
+    listen_transport: PeerTransport,
+}
+
+impl TransportConnector {
+    pub fn new(
+        cfg: TlsConfig,
+        our_id: PeerId,
+        our_url: &SafeUrl,
+        tor_socks5_proxy: Option<SocketAddr>,
+    ) -> TransportConnector {
+        TransportConnector {
+            tcp: TlsTcpConnector::new(cfg.clone(), our_id).with_tor_socks5_proxy(tor_socks5_proxy),
+            quic: QuicConnector::new(cfg, our_id),
+            listen_transport: PeerTransport::of(our_url),
+        }
+    }
+}
+
+#[async_trait]
+impl<M> Connector<M> for TransportConnector
+where
+    M: Debug + serde::Serialize + serde::de::DeserializeOwned + Send + Unpin + 'static,
+{
+    async fn connect_framed(&self, destination: SafeUrl, peer: PeerId) -> ConnectResult<M> {
+        match PeerTransport::of(&destination) {
+            PeerTransport::Tcp => self.tcp.connect_framed(destination, peer).await,
+            PeerTransport::Quic => self.quic.connect_framed(destination, peer).await,
+        }
+    }
+
+    async fn listen(&self, bind_addr: SocketAddr) -> Result<ConnectionListener<M>, anyhow::Error> {
+        match self.listen_transport {
+            PeerTransport::Tcp => self.tcp.listen(bind_addr).await,
+            PeerTransport::Quic => self.quic.listen(bind_addr).await,
+        }
+    }
+}
+
+/// Reads the Tor SOCKS5 proxy for connections to onion peers from
+/// [`FM_P2P_TOR_SOCKS5_PROXY_ENV`]
+pub fn tor_socks5_proxy_from_env() -> anyhow::Result<Option<SocketAddr>> {
+    match std::env::var(FM_P2P_TOR_SOCKS5_PROXY_ENV) {
+        Ok(proxy) if !proxy.is_empty() => {
+            Ok(Some(proxy.parse().map_err(|e| {
+                format_err!("Invalid {FM_P2P_TOR_SOCKS5_PROXY_ENV} {proxy}: {e}")
+            })?))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// TCP connector with encryption and authentication
 #[derive(Debug)]
 pub struct TlsTcpConnector {
@@ -65,6 +149,8 @@ pub struct TlsTcpConnector {
     /// understands
     cert_store: RootCertStore,
     peer_names: BTreeMap<PeerId, String>,
+    /// SOCKS5 proxy of a Tor daemon used to reach peers with an onion address
+    tor_socks5_proxy: Option<SocketAddr>,
 }
 
 #[derive(Debug, Clone)]
@@ -94,12 +180,43 @@ pub fn new(cfg: TlsConfig, our_id: PeerId) -> TlsTcpConnector {
             peer_certs: Arc::new(PeerCertStore::new(cfg.peer_certs)),
             cert_store,
             peer_names: cfg.peer_names,
+            tor_socks5_proxy: None,
+        }
+    }
+
+    /// Connects to peers with an `.onion` host through the given Tor SOCKS5
+    /// proxy, all other peers are still connected to directly
+    pub fn with_tor_socks5_proxy(mut self, proxy: Option<SocketAddr>) -> TlsTcpConnector {
+        self.tor_socks5_proxy = proxy;
+        self
+    }
+
+    async fn dial(&self, destination: &SafeUrl) -> anyhow::Result<TcpStream> {
+        let host = destination
+            .host_str()
+            .ok_or_else(|| format_err!("Missing host in {destination}"))?;
+        if !host.ends_with(".onion") {
+            return Ok(TcpStream::connect(parse_host_port(destination)?).await?);
         }
+
+        let proxy = self.tor_socks5_proxy.ok_or_else(|| {
+            format_err!(
+                "Connecting to the onion service {host} requires {FM_P2P_TOR_SOCKS5_PROXY_ENV}"
+            )
+        })?;
+        let port = destination
+            .port_or_known_default()
+            .ok_or_else(|| format_err!("Missing port in {destination}"))?;
+        Ok(Socks5Stream::connect(proxy, (host, port))
+            .await?
+            .into_inner())
     }
 }
 
 impl PeerCertStore {
-    fn new(certs: impl IntoIterator<Item = (PeerId, rustls::Certificate)>) -> PeerCertStore {
+    pub(crate) fn new(
+        certs: impl IntoIterator<Item = (PeerId, rustls::Certificate)>,
+    ) -> PeerCertStore {
         PeerCertStore {
             peer_certificates: certs.into_iter().collect(),
         }
@@ -111,7 +228,7 @@ fn get_peer_by_cert(&self, cert: &rustls::Certificate) -> Option<PeerId> {
             .find_map(|(peer, peer_cert)| if peer_cert == cert { Some(*peer) } else { None })
     }
 
-    fn authenticate_peer(
+    pub(crate) fn authenticate_peer(
         &self,
         received: Option<&[rustls::Certificate]>,
     ) -> Result<PeerId, anyhow::Error> {
@@ -175,10 +292,7 @@ async fn connect_framed(&self, destination: SafeUrl, peer: PeerId) -> ConnectRes
 
         let connector = TlsConnector::from(Arc::new(cfg));
         let tls_conn = connector
-            .connect(
-                fake_domain,
-                TcpStream::connect(parse_host_port(&destination)?).await?,
-            )
+            .connect(fake_domain, self.dial(&destination).await?)
             .await?;
 
         let (_, tls_session) = tls_conn.get_ref();
@@ -839,9 +953,8 @@ mod tests {
     use futures::{SinkExt, StreamExt};
 
     use crate::config::gen_cert_and_key;
-    use crate::net::connect::{ConnectionListener, Connector, TlsConfig};
+    use crate::net::connect::{ConnectionListener, Connector, TlsConfig, TlsTcpConnector};
     use crate::net::framed::AnyFramedTransport;
-    use crate::TlsTcpConnector;
 
     fn gen_connector_config(count: usize) -> Vec<TlsConfig> {
         let peer_keys = (0..count)
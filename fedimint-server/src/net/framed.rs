@@ -87,6 +87,15 @@ pub fn new<S>(stream: S) -> BidiFramed<T, WriteHalf<S>, ReadHalf<S>>
         }
     }
 
+    /// Builds a new `BidiFramed` codec around the separate sending and receiving
+    /// halves of a connection, e.g. the two directions of a QUIC stream
+    pub fn new_from_halves(write: WH, read: RH) -> BidiFramed<T, WH, RH> {
+        BidiFramed {
+            sink: FramedSink::new(write, BincodeCodec::new()),
+            stream: FramedStream::new(read, BincodeCodec::new()),
+        }
+    }
+
     /// Splits the codec in its sending and receiving parts
     ///
     /// This can be useful in cases where potentially simultaneous read and
@@ -2,3 +2,5 @@
 pub mod connect;
 pub mod framed;
 pub mod peers;
+pub mod quic;
+pub mod tor;
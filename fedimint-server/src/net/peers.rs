@@ -9,12 +9,13 @@
 use std::fmt::Debug;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
 use fedimint_api_client::api::PeerConnectionStatus;
 use fedimint_core::net::peers::{IPeerConnections, Recipient};
 use fedimint_core::task::{Cancellable, Cancelled, TaskGroup};
+use fedimint_core::time::now;
 use fedimint_core::util::backoff_util::{api_networking_backoff, FibonacciBackoff};
 use fedimint_core::util::SafeUrl;
 use fedimint_core::PeerId;
@@ -74,6 +75,17 @@ pub enum PeerMessage<M> {
     Ping,
 }
 
+/// Our view of the connection to a peer
+#[derive(Debug, Clone, Default)]
+pub struct PeerConnectivity {
+    pub status: PeerConnectionStatus,
+    /// When we last received any message from the peer, including pings
+    pub last_contact: Option<SystemTime>,
+    /// Number of failed connection attempts and dropped connections
+    pub connection_errors: u64,
+    pub last_error: Option<String>,
+}
+
 struct PeerConnectionStateMachine<M> {
     common: CommonPeerConnectionState<M>,
     state: PeerConnectionState<M>,
@@ -89,7 +101,7 @@ struct CommonPeerConnectionState<M> {
     peer_address: SafeUrl,
     connect: SharedAnyConnector<PeerMessage<M>>,
     incoming_connections: Receiver<AnyFramedTransport<PeerMessage<M>>>,
-    status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+    status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
 }
 enum PeerConnectionState<M> {
     Disconnected(FibonacciBackoff),
@@ -109,7 +121,7 @@ pub(crate) async fn new(
         cfg: NetworkConfig,
         connector: PeerConnector<T>,
         task_group: &TaskGroup,
-        status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+        status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
     ) -> Self {
         let connector: SharedAnyConnector<PeerMessage<T>> = connector.into();
         let mut connection_senders = HashMap::new();
@@ -135,7 +147,7 @@ pub(crate) async fn new(
             status_channels
                 .write()
                 .await
-                .insert(*peer, PeerConnectionStatus::Disconnected);
+                .insert(*peer, PeerConnectivity::default());
         }
 
         let mut listener = connector
@@ -238,10 +250,10 @@ async fn state_transition(mut self) -> Option<Self> {
 
                 if let PeerConnectionState::Connected(..) = state {
                     self.common
-                        .status_channels
-                        .write()
-                        .await
-                        .insert(self.common.peer_id, PeerConnectionStatus::Connected);
+                        .update_connectivity(|connectivity| {
+                            connectivity.status = PeerConnectionStatus::Connected;
+                        })
+                        .await;
                 }
 
                 Some(PeerConnectionStateMachine {
@@ -254,10 +266,10 @@ async fn state_transition(mut self) -> Option<Self> {
 
                 if let PeerConnectionState::Disconnected(..) = state {
                     self.common
-                        .status_channels
-                        .write()
-                        .await
-                        .insert(self.common.peer_id, PeerConnectionStatus::Disconnected);
+                        .update_connectivity(|connectivity| {
+                            connectivity.status = PeerConnectionStatus::Disconnected;
+                        })
+                        .await;
                 };
 
                 Some(PeerConnectionStateMachine {
@@ -273,6 +285,24 @@ impl<M> CommonPeerConnectionState<M>
 where
     M: Debug + Clone,
 {
+    async fn update_connectivity(&self, update: impl FnOnce(&mut PeerConnectivity)) {
+        update(
+            self.status_channels
+                .write()
+                .await
+                .entry(self.peer_id)
+                .or_default(),
+        );
+    }
+
+    async fn record_connection_error(&self, error: &anyhow::Error) {
+        self.update_connectivity(|connectivity| {
+            connectivity.connection_errors += 1;
+            connectivity.last_error = Some(error.to_string());
+        })
+        .await;
+    }
+
     async fn state_transition_connected(
         &mut self,
         mut connection: AnyFramedTransport<PeerMessage<M>>,
@@ -287,8 +317,13 @@ async fn state_transition_connected(
             Some(message_res) = connection.next() => {
                 match message_res {
                     Ok(peer_message) => {
+                        self.update_connectivity(|connectivity| {
+                            connectivity.last_contact = Some(now());
+                        })
+                        .await;
+
                         if let PeerMessage::Message(msg) = peer_message {
-                            PEER_MESSAGES_COUNT.with_label_values(&[&self.our_id_str, &self.peer_id_str, "incoming"]).inc();
+                            PEER_MESSAGES_COUNT.inc(&[&self.our_id_str, &self.peer_id_str, "incoming"]);
 
                             if self.incoming.send(msg).await.is_err(){
                                 return None;
@@ -297,7 +332,7 @@ async fn state_transition_connected(
 
                         PeerConnectionState::Connected(connection)
                     },
-                    Err(e) => self.disconnect(e),
+                    Err(e) => self.disconnect(e).await,
                 }
             },
             () = sleep(Duration::from_secs(10)) => {
@@ -315,16 +350,16 @@ async fn connect(
 
         match connection.send(PeerMessage::Ping).await {
             Ok(()) => PeerConnectionState::Connected(connection),
-            Err(e) => self.disconnect(e),
+            Err(e) => self.disconnect(e).await,
         }
     }
 
-    fn disconnect(&self, error: anyhow::Error) -> PeerConnectionState<M> {
+    async fn disconnect(&self, error: anyhow::Error) -> PeerConnectionState<M> {
         info!(target: LOG_NET_PEER, "Disconnected from peer {}: {}", self.peer_id, error);
 
-        PEER_DISCONNECT_COUNT
-            .with_label_values(&[&self.our_id_str, &self.peer_id_str])
-            .inc();
+        self.record_connection_error(&error).await;
+
+        PEER_DISCONNECT_COUNT.inc(&[&self.our_id_str, &self.peer_id_str]);
 
         PeerConnectionState::Disconnected(api_networking_backoff())
     }
@@ -334,17 +369,15 @@ async fn send_message_connected(
         mut connection: AnyFramedTransport<PeerMessage<M>>,
         peer_message: PeerMessage<M>,
     ) -> PeerConnectionState<M> {
-        PEER_MESSAGES_COUNT
-            .with_label_values(&[&self.our_id_str, &self.peer_id_str, "outgoing"])
-            .inc();
+        PEER_MESSAGES_COUNT.inc(&[&self.our_id_str, &self.peer_id_str, "outgoing"]);
 
         if let Err(e) = connection.send(peer_message).await {
-            return self.disconnect(e);
+            return self.disconnect(e).await;
         }
 
         match connection.flush().await {
             Ok(()) => PeerConnectionState::Connected(connection),
-            Err(e) => self.disconnect(e),
+            Err(e) => self.disconnect(e).await,
         }
     }
 
@@ -354,7 +387,7 @@ async fn state_transition_disconnected(
     ) -> Option<PeerConnectionState<M>> {
         Some(tokio::select! {
             maybe_connection = self.incoming_connections.recv() => {
-                PEER_CONNECT_COUNT.with_label_values(&[&self.our_id_str, &self.peer_id_str, "incoming"]).inc();
+                PEER_CONNECT_COUNT.inc(&[&self.our_id_str, &self.peer_id_str, "incoming"]);
 
                 self.connect(maybe_connection?).await
             },
@@ -362,13 +395,14 @@ async fn state_transition_disconnected(
                 // to prevent "reconnection ping-pongs", only the side with lower PeerId is responsible for reconnecting
                 match self.try_reconnect().await {
                     Ok(connection) => {
-                        PEER_CONNECT_COUNT
-                            .with_label_values(&[&self.our_id_str, &self.peer_id_str, "outgoing"])
-                            .inc();
+                        PEER_CONNECT_COUNT.inc(&[&self.our_id_str, &self.peer_id_str, "outgoing"]);
 
                         self.connect(connection).await
                     }
-                    Err(..) => PeerConnectionState::Disconnected(backoff),
+                    Err(e) => {
+                        self.record_connection_error(&e).await;
+                        PeerConnectionState::Disconnected(backoff)
+                    }
                 }
             },
         })
@@ -418,7 +452,7 @@ fn new(
         peer_address: SafeUrl,
         connect: SharedAnyConnector<PeerMessage<M>>,
         incoming_connections: Receiver<AnyFramedTransport<PeerMessage<M>>>,
-        status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+        status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
         task_group: &TaskGroup,
     ) -> PeerConnection<M> {
         let (outgoing_sender, outgoing_receiver) = async_channel::bounded(1024);
@@ -472,7 +506,7 @@ async fn run_io_thread(
         peer_address: SafeUrl,
         connect: SharedAnyConnector<PeerMessage<M>>,
         incoming_connections: Receiver<AnyFramedTransport<PeerMessage<M>>>,
-        status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+        status_channels: Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
     ) {
         info!(target: LOG_NET_PEER, "Starting peer connection state machine {}", peer_id);
 
@@ -506,7 +540,6 @@ mod tests {
     use std::sync::Arc;
 
     use anyhow::{ensure, Context as _};
-    use fedimint_api_client::api::PeerConnectionStatus;
     use fedimint_core::task::TaskGroup;
     use fedimint_core::util::{backoff_util, retry};
     use fedimint_core::PeerId;
@@ -514,7 +547,7 @@ mod tests {
 
     use crate::net::connect::mock::{MockNetwork, StreamReliability};
     use crate::net::connect::Connector;
-    use crate::net::peers::{NetworkConfig, ReconnectPeerConnections};
+    use crate::net::peers::{NetworkConfig, PeerConnectivity, ReconnectPeerConnections};
 
     #[test_log::test(tokio::test)]
     async fn test_connect() {
@@ -523,7 +556,7 @@ async fn test_connect() {
         {
             async fn wait_for_connection(
                 name: &str,
-                status_channels: &Arc<RwLock<BTreeMap<PeerId, PeerConnectionStatus>>>,
+                status_channels: &Arc<RwLock<BTreeMap<PeerId, PeerConnectivity>>>,
             ) {
                 retry(
                     format!("wait for client {name}"),
@@ -0,0 +1,302 @@
+//! Peer connections over QUIC, see [`QuicConnector`]
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, format_err};
+use async_trait::async_trait;
+use fedimint_core::util::SafeUrl;
+use fedimint_core::PeerId;
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::rustls::crypto::CryptoProvider;
+use quinn::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use quinn::rustls::server::WebPkiClientVerifier;
+use quinn::rustls::RootCertStore;
+use quinn::{rustls, Connection, Endpoint, IdleTimeout, Incoming, RecvStream, SendStream};
+use tokio_rustls::rustls as tcp_rustls;
+
+use crate::net::connect::{
+    dns_sanitize, parse_host_port, ConnectResult, ConnectionListener, Connector, PeerCertStore,
+    TlsConfig,
+};
+use crate::net::framed::BidiFramed;
+
+/// Protocol negotiated during the TLS handshake
+const ALPN: &[u8] = b"fedimint-p2p";
+
+/// Sent by the connecting peer when opening the stream, QUIC only announces a
+/// stream to the other side once data has been sent on it
+const STREAM_HELLO: u8 = 0;
+
+/// Keeps connections and NAT bindings alive while no messages are exchanged
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Connections that didn't receive any packets for this long are closed
+const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// QUIC connector authenticating peers like
+/// [`TlsTcpConnector`](crate::net::connect::TlsTcpConnector)
+///
+/// QUIC recovers from packet loss faster than TCP and identifies connections
+/// by connection ids instead of addresses, so connections migrate to a new
+/// address of a peer instead of having to time out and reconnect. Every
+/// connection carries a single bidirectional stream of messages.
+#[derive(Debug)]
+pub struct QuicConnector {
+    our_certificate: CertificateDer<'static>,
+    our_private_key: PrivatePkcs8KeyDer<'static>,
+    peer_certs: Arc<PeerCertStore>,
+    root_store: Arc<RootCertStore>,
+    peer_names: BTreeMap<PeerId, String>,
+}
+
+impl QuicConnector {
+    pub fn new(cfg: TlsConfig, our_id: PeerId) -> QuicConnector {
+        let mut root_store = RootCertStore::empty();
+        for cert in cfg.peer_certs.values() {
+            root_store
+                .add(CertificateDer::from(cert.0.clone()))
+                .expect("Could not add peer certificate");
+        }
+
+        QuicConnector {
+            our_certificate: CertificateDer::from(
+                cfg.peer_certs.get(&our_id).expect("exists").0.clone(),
+            ),
+            our_private_key: PrivatePkcs8KeyDer::from(cfg.our_private_key.0),
+            peer_certs: Arc::new(PeerCertStore::new(cfg.peer_certs)),
+            root_store: Arc::new(root_store),
+            peer_names: cfg.peer_names,
+        }
+    }
+
+    fn client_config(&self) -> anyhow::Result<quinn::ClientConfig> {
+        let mut tls = rustls::ClientConfig::builder_with_provider(crypto_provider())
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .with_root_certificates(self.root_store.clone())
+            .with_client_auth_cert(vec![self.our_certificate.clone()], self.private_key())?;
+        tls.alpn_protocols = vec![ALPN.to_vec()];
+
+        let mut config = quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(tls)?));
+        config.transport_config(transport_config());
+        Ok(config)
+    }
+
+    fn server_config(&self) -> anyhow::Result<quinn::ServerConfig> {
+        let verifier =
+            WebPkiClientVerifier::builder_with_provider(self.root_store.clone(), crypto_provider())
+                .build()?;
+        let mut tls = rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(vec![self.our_certificate.clone()], self.private_key())?;
+        tls.alpn_protocols = vec![ALPN.to_vec()];
+
+        let mut config =
+            quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls)?));
+        config.transport_config(transport_config()).migration(true);
+        Ok(config)
+    }
+
+    fn private_key(&self) -> PrivateKeyDer<'static> {
+        PrivateKeyDer::Pkcs8(self.our_private_key.clone_key())
+    }
+}
+
+#[async_trait]
+impl<M> Connector<M> for QuicConnector
+where
+    M: Debug + serde::Serialize + serde::de::DeserializeOwned + Send + Unpin + 'static,
+{
+    async fn connect_framed(&self, destination: SafeUrl, peer: PeerId) -> ConnectResult<M> {
+        let addr = tokio::net::lookup_host(parse_host_port(&destination)?)
+            .await?
+            .next()
+            .ok_or_else(|| format_err!("Could not resolve {destination}"))?;
+        let bind_addr = if addr.is_ipv4() {
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+        } else {
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+        };
+
+        // The endpoint keeps running as long as the connection is open
+        let mut endpoint = Endpoint::client(bind_addr)?;
+        endpoint.set_default_client_config(self.client_config()?);
+        let connection = endpoint
+            .connect(addr, &dns_sanitize(&self.peer_names[&peer]))?
+            .await?;
+
+        let auth_peer = authenticate_peer(&self.peer_certs, &connection)?;
+        if auth_peer != peer {
+            return Err(anyhow::anyhow!("Connected to unexpected peer"));
+        }
+
+        let (mut send, recv) = connection.open_bi().await?;
+        send.write_all(&[STREAM_HELLO]).await?;
+
+        let framed =
+            BidiFramed::<_, SendStream, RecvStream>::new_from_halves(send, recv).into_dyn();
+        Ok((peer, framed))
+    }
+
+    async fn listen(&self, bind_addr: SocketAddr) -> Result<ConnectionListener<M>, anyhow::Error> {
+        let endpoint = Endpoint::server(self.server_config()?, bind_addr)?;
+        let peer_certs = self.peer_certs.clone();
+
+        let stream = futures::stream::unfold(endpoint, move |endpoint| {
+            let peer_certs = peer_certs.clone();
+
+            Box::pin(async move {
+                let incoming = endpoint.accept().await?;
+                let res = accept_connection(&peer_certs, incoming).await;
+                Some((res, endpoint))
+            })
+        });
+        Ok(Box::pin(stream))
+    }
+}
+
+async fn accept_connection<M>(peer_certs: &PeerCertStore, incoming: Incoming) -> ConnectResult<M>
+where
+    M: Debug + serde::Serialize + serde::de::DeserializeOwned + Send + Unpin + 'static,
+{
+    let connection = incoming.await?;
+    let auth_peer = authenticate_peer(peer_certs, &connection)?;
+
+    let (send, mut recv) = connection.accept_bi().await?;
+    let mut hello = [0; 1];
+    recv.read_exact(&mut hello).await?;
+    if hello[0] != STREAM_HELLO {
+        bail!("Unexpected stream hello {}", hello[0]);
+    }
+
+    let framed = BidiFramed::<_, SendStream, RecvStream>::new_from_halves(send, recv).into_dyn();
+    Ok((auth_peer, framed))
+}
+
+fn authenticate_peer(
+    peer_certs: &PeerCertStore,
+    connection: &Connection,
+) -> anyhow::Result<PeerId> {
+    let cert_chain = connection
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .map(|certs| {
+            certs
+                .iter()
+                .map(|cert| tcp_rustls::Certificate(cert.to_vec()))
+                .collect::<Vec<_>>()
+        });
+
+    peer_certs.authenticate_peer(cert_chain.as_deref())
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn transport_config() -> Arc<quinn::TransportConfig> {
+    let mut config = quinn::TransportConfig::default();
+    config
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        .max_idle_timeout(Some(
+            IdleTimeout::try_from(MAX_IDLE_TIMEOUT).expect("Timeout is in range"),
+        ));
+    Arc::new(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use fedimint_core::runtime::spawn;
+    use fedimint_core::util::SafeUrl;
+    use fedimint_core::PeerId;
+    use futures::{SinkExt, StreamExt};
+
+    use super::QuicConnector;
+    use crate::config::gen_cert_and_key;
+    use crate::net::connect::{ConnectionListener, Connector, TlsConfig};
+    use crate::net::framed::AnyFramedTransport;
+
+    fn gen_connector_config(count: usize) -> Vec<TlsConfig> {
+        let peer_keys = (0..count)
+            .map(|id| gen_cert_and_key(&format!("peer-{id}")).unwrap())
+            .collect::<Vec<_>>();
+
+        peer_keys
+            .iter()
+            .map(|(_cert, key)| TlsConfig {
+                our_private_key: key.clone(),
+                peer_certs: peer_keys
+                    .iter()
+                    .enumerate()
+                    .map(|(peer, (cert, _))| (PeerId::from(peer as u16), cert.clone()))
+                    .collect(),
+                peer_names: (0..count)
+                    .map(|peer| (PeerId::from(peer as u16), format!("peer-{peer}")))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn connect_success() {
+        let bind_addr: SocketAddr = "127.0.0.1:7002".parse().unwrap();
+        let url: SafeUrl = "fedimint+quic://127.0.0.1:7002".parse().unwrap();
+        let connectors = gen_connector_config(4)
+            .into_iter()
+            .enumerate()
+            .map(|(id, cfg)| QuicConnector::new(cfg, PeerId::from(id as u16)))
+            .collect::<Vec<_>>();
+
+        let mut server: ConnectionListener<u64> = connectors[0].listen(bind_addr).await.unwrap();
+
+        let server_task = spawn("server next await", async move {
+            let (peer, mut conn) = server.next().await.unwrap().unwrap();
+            assert_eq!(peer.to_usize(), 2);
+            let received = conn.next().await.unwrap().unwrap();
+            assert_eq!(received, 42);
+            conn.send(21).await.unwrap();
+            assert!(conn.next().await.map_or(true, |res| res.is_err()));
+        });
+
+        let (peer_of_a, mut client_a): (_, AnyFramedTransport<u64>) = connectors[2]
+            .connect_framed(url.clone(), PeerId::from(0))
+            .await
+            .unwrap();
+        assert_eq!(peer_of_a.to_usize(), 0);
+        client_a.send(42).await.unwrap();
+        let received = client_a.next().await.unwrap().unwrap();
+        assert_eq!(received, 21);
+        drop(client_a);
+
+        server_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_reject_unexpected_peer() {
+        let bind_addr: SocketAddr = "127.0.0.1:7003".parse().unwrap();
+        let url: SafeUrl = "fedimint+quic://127.0.0.1:7003".parse().unwrap();
+        let cfg = gen_connector_config(4);
+
+        let mut server: ConnectionListener<u64> =
+            QuicConnector::new(cfg[1].clone(), PeerId::from(1))
+                .listen(bind_addr)
+                .await
+                .unwrap();
+        spawn("server next await", async move {
+            let _ = server.next().await;
+        });
+
+        // The server is peer 1, not the peer 0 we expect
+        let res: anyhow::Result<(_, AnyFramedTransport<u64>)> =
+            QuicConnector::new(cfg[2].clone(), PeerId::from(2))
+                .connect_framed(url, PeerId::from(0))
+                .await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,294 @@
+//! Exposes the guardian API as a Tor onion service through the control port
+//! of an externally running Tor daemon
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use anyhow::{bail, ensure, format_err, Context};
+use fedimint_core::util::{write_new, SafeUrl};
+use fedimint_logging::LOG_NET_API;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tracing::info;
+
+/// File in the data dir the private key of our API onion service is stored in,
+/// so the onion address stays the same across restarts
+pub const API_ONION_KEY_FILE: &str = "api_onion_service.key";
+
+/// Virtual port the onion service exposes the API on, so the onion URL doesn't
+/// need to contain a port
+const ONION_SERVICE_PORT: u16 = 80;
+
+/// How to reach the control port of the Tor daemon we register our API onion
+/// service with
+#[derive(Debug, Clone)]
+pub struct TorControlSettings {
+    /// Address of the Tor control port, e.g. `127.0.0.1:9051`
+    pub control_addr: SocketAddr,
+    /// Password for `HashedControlPassword` authentication, if not set we use
+    /// cookie or no authentication, whatever Tor offers
+    pub control_password: Option<String>,
+    /// Whether to use the onion URL as our API URL during config gen and to
+    /// announce it to peers and clients of an existing federation
+    pub announce: bool,
+}
+
+/// An onion service forwarding to our API, exists as long as this is not
+/// dropped
+#[derive(Debug)]
+pub struct ApiOnionService {
+    /// URL clients can reach our API at via Tor
+    pub url: SafeUrl,
+    /// Tor removes the service once the control connection that created it
+    /// is closed
+    _control: TorControl,
+}
+
+/// Registers an onion service forwarding to `api_bind` with the Tor daemon at
+/// the control port configured in `settings`, using the key stored in
+/// `data_dir` or a new one if there is none yet.
+pub async fn start_api_onion_service(
+    settings: &TorControlSettings,
+    api_bind: SocketAddr,
+    data_dir: &Path,
+) -> anyhow::Result<ApiOnionService> {
+    let mut control = TorControl::connect(settings.control_addr).await?;
+    control
+        .authenticate(settings.control_password.as_deref())
+        .await?;
+
+    let key_path = data_dir.join(API_ONION_KEY_FILE);
+    let key = match std::fs::read_to_string(&key_path) {
+        Ok(key) => Some(key.trim().to_owned()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context("Failed to read API onion service key"),
+    };
+
+    let target = onion_service_target(api_bind);
+    let reply = control
+        .command(&format!(
+            "ADD_ONION {key} {flags}Port={ONION_SERVICE_PORT},{target}",
+            key = key.as_deref().unwrap_or("NEW:ED25519-V3"),
+            flags = if key.is_some() {
+                "Flags=DiscardPK "
+            } else {
+                ""
+            },
+        ))
+        .await?;
+    let (service_id, new_key) = parse_add_onion_reply(&reply)?;
+
+    if key.is_none() {
+        let new_key = new_key.context("Tor did not return the key of the new onion service")?;
+        write_new(&key_path, new_key).context("Failed to store API onion service key")?;
+    }
+
+    let url: SafeUrl = format!("ws://{service_id}.onion/").parse()?;
+    info!(target: LOG_NET_API, %url, %target, "Started API onion service");
+
+    Ok(ApiOnionService {
+        url,
+        _control: control,
+    })
+}
+
+/// Tor can't forward to an unspecified address, so we use the loopback address
+/// instead, which any unspecified bind also listens on
+fn onion_service_target(api_bind: SocketAddr) -> SocketAddr {
+    match api_bind.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), api_bind.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), api_bind.port())
+        }
+        _ => api_bind,
+    }
+}
+
+/// Minimal client for the Tor control protocol, see
+/// <https://spec.torproject.org/control-spec>
+#[derive(Debug)]
+struct TorControl {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl TorControl {
+    async fn connect(control_addr: SocketAddr) -> anyhow::Result<Self> {
+        let (reader, writer) = TcpStream::connect(control_addr)
+            .await
+            .with_context(|| format!("Failed to connect to Tor control port {control_addr}"))?
+            .into_split();
+
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer,
+        })
+    }
+
+    async fn authenticate(&mut self, password: Option<&str>) -> anyhow::Result<()> {
+        let protocol_info = self.command("PROTOCOLINFO 1").await?;
+        let (methods, cookie_file) = parse_protocol_info(&protocol_info)?;
+
+        let auth = match password {
+            Some(password) => format!("AUTHENTICATE \"{}\"", quote(password)),
+            None if methods.iter().any(|method| method == "NULL") => "AUTHENTICATE".to_owned(),
+            None if methods.iter().any(|method| method == "COOKIE") => {
+                let cookie_file = cookie_file
+                    .context("Tor offers cookie authentication but no cookie file")?;
+                let cookie = std::fs::read(&cookie_file)
+                    .with_context(|| format!("Failed to read Tor auth cookie {cookie_file}"))?;
+                format!("AUTHENTICATE {}", hex::encode(cookie))
+            }
+            None => bail!(
+                "No supported Tor control authentication method in {methods:?}, set a control password"
+            ),
+        };
+
+        self.command(&auth).await?;
+        Ok(())
+    }
+
+    /// Sends `command` and returns the lines of the reply with their status
+    /// code stripped, fails unless Tor replies with `250`
+    async fn command(&mut self, command: &str) -> anyhow::Result<Vec<String>> {
+        self.writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await?;
+
+        let mut reply = vec![];
+        loop {
+            let mut line = String::new();
+            ensure!(
+                self.reader.read_line(&mut line).await? != 0,
+                "Tor closed the control connection"
+            );
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            ensure!(line.get(..3) == Some("250"), "Tor rejected command: {line}");
+
+            reply.push(line.get(4..).unwrap_or_default().to_owned());
+            // A space after the status code marks the last line of the reply
+            if line.get(3..4).unwrap_or(" ") == " " {
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+/// Escapes `s` for use in a quoted string of the control protocol
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Extracts the authentication methods and cookie file path from a
+/// `PROTOCOLINFO` reply
+fn parse_protocol_info(reply: &[String]) -> anyhow::Result<(Vec<String>, Option<String>)> {
+    let auth = reply
+        .iter()
+        .find_map(|line| line.strip_prefix("AUTH "))
+        .context("Tor did not list its authentication methods")?;
+
+    let mut methods = vec![];
+    let mut cookie_file = None;
+    for field in auth.split(' ') {
+        if let Some(value) = field.strip_prefix("METHODS=") {
+            methods = value.split(',').map(ToOwned::to_owned).collect();
+        }
+    }
+    if let Some((_, path)) = auth.split_once("COOKIEFILE=\"") {
+        let path = path
+            .strip_suffix('"')
+            .ok_or_else(|| format_err!("Malformed Tor cookie file path"))?;
+        cookie_file = Some(path.replace("\\\"", "\"").replace("\\\\", "\\"));
+    }
+
+    Ok((methods, cookie_file))
+}
+
+/// Extracts the service id and, if a new key was generated, the key of the
+/// onion service from an `ADD_ONION` reply
+fn parse_add_onion_reply(reply: &[String]) -> anyhow::Result<(String, Option<String>)> {
+    let service_id = reply
+        .iter()
+        .find_map(|line| line.strip_prefix("ServiceID="))
+        .context("Tor did not return the onion service id")?;
+    let key = reply
+        .iter()
+        .find_map(|line| line.strip_prefix("PrivateKey="));
+
+    Ok((service_id.to_owned(), key.map(ToOwned::to_owned)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parses_protocol_info() {
+        let reply = lines(&[
+            "PROTOCOLINFO 1",
+            "AUTH METHODS=COOKIE,SAFECOOKIE COOKIEFILE=\"/var/lib/tor/control_auth_cookie\"",
+            "VERSION Tor=\"0.4.8.12\"",
+            "OK",
+        ]);
+
+        let (methods, cookie_file) = parse_protocol_info(&reply).expect("valid reply");
+        assert_eq!(methods, vec!["COOKIE", "SAFECOOKIE"]);
+        assert_eq!(
+            cookie_file.as_deref(),
+            Some("/var/lib/tor/control_auth_cookie")
+        );
+
+        let reply = lines(&["PROTOCOLINFO 1", "AUTH METHODS=NULL", "OK"]);
+        assert_eq!(
+            parse_protocol_info(&reply).expect("valid reply"),
+            (vec!["NULL".to_owned()], None)
+        );
+    }
+
+    #[test]
+    fn parses_add_onion_reply() {
+        let reply = lines(&[
+            "ServiceID=abcdefghijklmnopqrstuvwxyz234567abcdefghijklmnopqrstuvwx",
+            "PrivateKey=ED25519-V3:c2VjcmV0",
+            "OK",
+        ]);
+        assert_eq!(
+            parse_add_onion_reply(&reply).expect("valid reply"),
+            (
+                "abcdefghijklmnopqrstuvwxyz234567abcdefghijklmnopqrstuvwx".to_owned(),
+                Some("ED25519-V3:c2VjcmV0".to_owned())
+            )
+        );
+
+        let reply = lines(&["ServiceID=abc", "OK"]);
+        assert_eq!(
+            parse_add_onion_reply(&reply).expect("valid reply"),
+            ("abc".to_owned(), None)
+        );
+        assert!(parse_add_onion_reply(&lines(&["OK"])).is_err());
+    }
+
+    #[test]
+    fn onion_service_targets_loopback_for_unspecified_binds() {
+        assert_eq!(
+            onion_service_target("0.0.0.0:8174".parse().unwrap()),
+            "127.0.0.1:8174".parse().unwrap()
+        );
+        assert_eq!(
+            onion_service_target("[::]:8174".parse().unwrap()),
+            "[::1]:8174".parse().unwrap()
+        );
+        assert_eq!(
+            onion_service_target("10.0.0.1:8174".parse().unwrap()),
+            "10.0.0.1:8174".parse().unwrap()
+        );
+    }
+}
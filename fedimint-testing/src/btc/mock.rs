@@ -349,6 +349,16 @@ async fn get_fee_rate(&self, _confirmation_target: u16) -> Result<Option<Feerate
         Ok(Some(Feerate { sats_per_kvb: 2000 }))
     }
 
+    async fn get_mempool_tx_fee_rate(&self, txid: &bitcoin::Txid) -> Result<Option<Feerate>> {
+        // Pending transactions pay exactly the fee rate we estimate
+        let inner = self.inner.read().unwrap();
+        Ok(inner
+            .pending
+            .iter()
+            .any(|tx| tx.compute_txid() == *txid)
+            .then_some(Feerate { sats_per_kvb: 2000 }))
+    }
+
     async fn submit_transaction(&self, transaction: bitcoin::Transaction) {
         let mut inner = self.inner.write().unwrap();
         inner.pending.push(transaction);
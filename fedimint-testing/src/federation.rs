@@ -5,6 +5,7 @@
 use fedimint_api_client::api::net::Connector;
 use fedimint_api_client::api::{DynGlobalApi, FederationApiExt};
 use fedimint_client::module::init::ClientModuleInitRegistry;
+use fedimint_client::network_sim::NetworkConditions;
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
 use fedimint_client::{AdminCreds, Client, ClientHandleArc};
 use fedimint_core::admin_client::{ConfigGenParamsConsensus, PeerServerParams};
@@ -35,6 +36,7 @@
 #[derive(Clone)]
 pub struct FederationTest {
     configs: BTreeMap<PeerId, ServerConfig>,
+    server_dbs: BTreeMap<PeerId, Database>,
     server_init: ServerModuleInitRegistry,
     client_init: ClientModuleInitRegistry,
     primary_module_kind: ModuleKind,
@@ -88,11 +90,42 @@ pub async fn new_admin_client(&self, peer_id: PeerId, auth: ApiAuth) -> ClientHa
             .await
     }
 
+    /// Create a client connected to this fed through a simulated network with
+    /// the given `conditions`
+    pub async fn new_client_with_network_conditions(
+        &self,
+        conditions: NetworkConditions,
+    ) -> ClientHandleArc {
+        let client_config = self.configs[&PeerId::from(0)]
+            .consensus
+            .to_client_config(&self.server_init)
+            .unwrap();
+
+        self.build_client(
+            client_config,
+            MemDatabase::new().into(),
+            None,
+            Some(conditions),
+        )
+        .await
+    }
+
     pub async fn new_client_with(
         &self,
         client_config: ClientConfig,
         db: Database,
         admin_creds: Option<AdminCreds>,
+    ) -> ClientHandleArc {
+        self.build_client(client_config, db, admin_creds, None)
+            .await
+    }
+
+    async fn build_client(
+        &self,
+        client_config: ClientConfig,
+        db: Database,
+        admin_creds: Option<AdminCreds>,
+        network_conditions: Option<NetworkConditions>,
     ) -> ClientHandleArc {
         info!(target: LOG_TEST, "Setting new client with config");
         let mut client_builder = Client::builder(db).await.expect("Failed to build client");
@@ -101,6 +134,9 @@ pub async fn new_client_with(
         if let Some(admin_creds) = admin_creds {
             client_builder.set_admin_creds(admin_creds);
         }
+        if let Some(network_conditions) = network_conditions {
+            client_builder.with_network_conditions(network_conditions);
+        }
         let client_secret = Client::load_or_generate_client_secret(client_builder.db_no_decoders())
             .await
             .unwrap();
@@ -130,6 +166,21 @@ pub fn id(&self) -> FederationId {
             .calculate_federation_id()
     }
 
+    /// Return the config of guardian `peer_id`
+    pub fn server_config(&self, peer_id: PeerId) -> &ServerConfig {
+        &self.configs[&peer_id]
+    }
+
+    /// Return the databases of the guardians that are online
+    pub fn server_dbs(&self) -> &BTreeMap<PeerId, Database> {
+        &self.server_dbs
+    }
+
+    /// Return the server module inits the federation was started with
+    pub fn server_init(&self) -> &ServerModuleInitRegistry {
+        &self.server_init
+    }
+
     /// Connects a gateway to this `FederationTest`
     pub async fn connect_gateway(&self, gw: &Gateway) {
         gw.handle_connect_federation(ConnectFedPayload {
@@ -216,6 +267,7 @@ pub async fn build(self) -> FederationTest {
             ServerConfig::trusted_dealer_gen(&params, &self.server_init, &self.version_hash);
 
         let task_group = TaskGroup::new();
+        let mut server_dbs = BTreeMap::new();
         for (peer_id, config) in configs.clone() {
             let p2p_bind_addr = params.get(&peer_id).expect("Must exist").local.p2p_bind;
             let api_bind_addr = params.get(&peer_id).expect("Must exist").local.api_bind;
@@ -226,6 +278,7 @@ pub async fn build(self) -> FederationTest {
             let instances = config.consensus.iter_module_instances();
             let decoders = self.server_init.available_decoders(instances).unwrap();
             let db = Database::new(MemDatabase::new(), decoders);
+            server_dbs.insert(peer_id, db.clone());
             let module_init_registry = self.server_init.clone();
             let subgroup = task_group.make_subgroup();
             let checkpoint_dir = tempfile::Builder::new().tempdir().unwrap().into_path();
@@ -234,7 +287,7 @@ pub async fn build(self) -> FederationTest {
             task_group.spawn("fedimintd", move |_| async move {
                 consensus::run(
                     p2p_bind_addr,
-                    api_bind_addr,
+                    vec![api_bind_addr],
                     config.clone(),
                     db.clone(),
                     module_init_registry,
@@ -276,6 +329,7 @@ pub async fn build(self) -> FederationTest {
 
         FederationTest {
             configs,
+            server_dbs,
             server_init: self.server_init,
             client_init: self.client_init,
             primary_module_kind: self.primary_module_kind,
@@ -9,7 +9,7 @@
 use bitcoin::secp256k1::{self, PublicKey, SecretKey};
 use fedimint_core::task::TaskGroup;
 use fedimint_core::util::BoxStream;
-use fedimint_core::Amount;
+use fedimint_core::{Amount, Msats, Sats};
 use fedimint_ln_common::contracts::Preimage;
 use fedimint_ln_common::route_hints::RouteHint;
 use fedimint_ln_common::PrunedInvoice;
@@ -291,9 +291,9 @@ async fn list_active_channels(&self) -> Result<ListActiveChannelsResponse, Light
 
     async fn get_balances(&self) -> Result<GetBalancesResponse, LightningRpcError> {
         Ok(GetBalancesResponse {
-            onchain_balance_sats: 0,
-            lightning_balance_msats: 0,
-            inbound_lightning_liquidity_msats: 0,
+            onchain_balance_sats: Sats::ZERO,
+            lightning_balance_msats: Msats::ZERO,
+            inbound_lightning_liquidity_msats: Msats::ZERO,
         })
     }
 }
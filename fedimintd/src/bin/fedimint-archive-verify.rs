@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use fedimint_logging::TracingSetup;
+use fedimint_server::consensus::archive::verify_archive;
+
+/// Verifies the checksums and guardian signatures of an archive written by
+/// `fedimintd export-epochs`, without connecting to the federation
+#[derive(Parser)]
+#[command(version)]
+struct Opts {
+    /// Directory containing the archive
+    archive_dir: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    TracingSetup::default().init()?;
+    let opts = Opts::parse();
+
+    let verification = verify_archive(&opts.archive_dir)?;
+    println!("{}", serde_json::to_string_pretty(&verification)?);
+
+    Ok(())
+}
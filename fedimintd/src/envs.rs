@@ -25,12 +25,29 @@
 // Env variable to TODO
 pub const FM_API_URL_ENV: &str = "FM_API_URL";
 
+// Comma separated list of additional addresses to bind the API to (e.g. IPv6
+// or a local port a Tor onion service forwards to)
+pub const FM_BIND_API_EXTRA_ENV: &str = "FM_BIND_API_EXTRA";
+
+// Address of the control port of a Tor daemon to expose the API as an onion
+// service with
+pub const FM_TOR_CONTROL_ENV: &str = "FM_TOR_CONTROL";
+
+// Password for the Tor control port
+pub const FM_TOR_CONTROL_PASSWORD_ENV: &str = "FM_TOR_CONTROL_PASSWORD";
+
+// Env variable to use the API onion service URL as our API URL
+pub const FM_ANNOUNCE_ONION_API_ENV: &str = "FM_ANNOUNCE_ONION_API";
+
 // Env variable to TODO
 pub const FM_BITCOIN_NETWORK_ENV: &str = "FM_BITCOIN_NETWORK";
 
 // Env variable to TODO
 pub const FM_FINALITY_DELAY_ENV: &str = "FM_FINALITY_DELAY";
 
+// Env variable to require more confirmations for larger peg-ins
+pub const FM_PEG_IN_CONFIRMATION_TIERS_ENV: &str = "FM_PEG_IN_CONFIRMATION_TIERS";
+
 // Env variable to TODO
 pub const FM_BIND_METRICS_API_ENV: &str = "FM_BIND_METRICS_API";
 
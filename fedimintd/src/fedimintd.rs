@@ -1,22 +1,26 @@
+mod dkg;
 mod metrics;
 
 use std::collections::BTreeMap;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use anyhow::{bail, format_err, Context};
 use clap::{Parser, Subcommand};
 use fedimint_core::admin_client::ConfigGenParamsRequest;
 use fedimint_core::config::{
-    EmptyGenParams, ModuleInitParams, ServerModuleConfigGenParamsRegistry, ServerModuleInitRegistry,
+    EmptyGenParams, FederationId, ModuleInitParams, ServerModuleConfigGenParamsRegistry,
+    ServerModuleInitRegistry,
 };
 use fedimint_core::core::ModuleKind;
 use fedimint_core::db::{get_current_database_version, Database};
+use fedimint_core::encoding::Encodable;
 use fedimint_core::envs::{
     is_env_var_set, BitcoinRpcConfig, FM_ENABLE_MODULE_LNV2_ENV, FM_USE_UNKNOWN_MODULE_ENV,
 };
-use fedimint_core::module::registry::ModuleRegistry;
+use fedimint_core::module::registry::{ModuleDecoderRegistry, ModuleRegistry};
 use fedimint_core::module::{ServerApiVersionsSummary, ServerDbVersionsSummary, ServerModuleInit};
 use fedimint_core::task::TaskGroup;
 use fedimint_core::timing;
@@ -29,14 +33,17 @@
 use fedimint_meta_server::{MetaGenParams, MetaInit};
 use fedimint_mint_server::common::config::{MintGenParams, MintGenParamsConsensus};
 use fedimint_mint_server::MintInit;
+use fedimint_rocksdb::RocksDbReadOnly;
 use fedimint_server::config::api::ConfigGenSettings;
-use fedimint_server::config::io::{DB_FILE, PLAINTEXT_PASSWORD};
+use fedimint_server::config::io::{read_server_consensus_config, DB_FILE, PLAINTEXT_PASSWORD};
 use fedimint_server::config::ServerConfig;
+use fedimint_server::consensus::archive::export_sessions;
 use fedimint_server::net::api::ApiSecrets;
+use fedimint_server::net::tor::TorControlSettings;
 use fedimint_unknown_common::config::UnknownGenParams;
 use fedimint_unknown_server::UnknownInit;
 use fedimint_wallet_server::common::config::{
-    WalletGenParams, WalletGenParamsConsensus, WalletGenParamsLocal,
+    PegInConfirmationTier, WalletGenParams, WalletGenParamsConsensus, WalletGenParamsLocal,
 };
 use fedimint_wallet_server::WalletInit;
 use futures::FutureExt;
@@ -44,10 +51,11 @@
 
 use crate::default_esplora_server;
 use crate::envs::{
-    FM_API_URL_ENV, FM_BIND_API_ENV, FM_BIND_METRICS_API_ENV, FM_BIND_P2P_ENV,
-    FM_BITCOIN_NETWORK_ENV, FM_DATA_DIR_ENV, FM_DISABLE_META_MODULE_ENV, FM_EXTRA_DKG_META_ENV,
-    FM_FINALITY_DELAY_ENV, FM_FORCE_API_SECRETS_ENV, FM_P2P_URL_ENV, FM_PASSWORD_ENV,
-    FM_TOKIO_CONSOLE_BIND_ENV,
+    FM_ANNOUNCE_ONION_API_ENV, FM_API_URL_ENV, FM_BIND_API_ENV, FM_BIND_API_EXTRA_ENV,
+    FM_BIND_METRICS_API_ENV, FM_BIND_P2P_ENV, FM_BITCOIN_NETWORK_ENV, FM_DATA_DIR_ENV,
+    FM_DISABLE_META_MODULE_ENV, FM_EXTRA_DKG_META_ENV, FM_FINALITY_DELAY_ENV,
+    FM_FORCE_API_SECRETS_ENV, FM_P2P_URL_ENV, FM_PASSWORD_ENV, FM_PEG_IN_CONFIRMATION_TIERS_ENV,
+    FM_TOKIO_CONSOLE_BIND_ENV, FM_TOR_CONTROL_ENV, FM_TOR_CONTROL_PASSWORD_ENV,
 };
 use crate::fedimintd::metrics::APP_START_TS;
 
@@ -75,21 +83,45 @@ struct ServerOpts {
     /// Address we bind to for federation communication
     #[arg(long, env = FM_BIND_P2P_ENV, default_value = "127.0.0.1:8173")]
     bind_p2p: SocketAddr,
-    /// Our external address for communicating with our peers
+    /// Our external address for communicating with our peers, peers connect
+    /// to us over QUIC instead of TCP if the scheme is `fedimint+quic`
     #[arg(long, env = FM_P2P_URL_ENV, default_value = "fedimint://127.0.0.1:8173")]
     p2p_url: SafeUrl,
     /// Address we bind to for exposing the API
     #[arg(long, env = FM_BIND_API_ENV, default_value = "127.0.0.1:8174")]
     bind_api: SocketAddr,
+    /// Additional addresses we bind to for exposing the API, e.g. `[::]:8174`
+    /// to listen on IPv6 or a local port an external onion service forwards
+    /// to
+    #[arg(long, env = FM_BIND_API_EXTRA_ENV, value_delimiter = ',')]
+    bind_api_extra: Vec<SocketAddr>,
     /// Our API address for clients to connect to us
     #[arg(long, env = FM_API_URL_ENV, default_value = "ws://127.0.0.1:8174")]
     api_url: SafeUrl,
+    /// Control port of a Tor daemon, e.g. `127.0.0.1:9051`, to expose our API
+    /// as an onion service with. The key of the service is kept in the data
+    /// dir, so its address stays the same across restarts.
+    #[arg(long, env = FM_TOR_CONTROL_ENV)]
+    tor_control: Option<SocketAddr>,
+    /// Password for the Tor control port, cookie authentication is used if
+    /// not set
+    #[arg(long, env = FM_TOR_CONTROL_PASSWORD_ENV, requires = "tor_control")]
+    tor_control_password: Option<String>,
+    /// Use the URL of our onion service instead of `--api-url` during config
+    /// gen and announce it to the peers and clients of an existing federation
+    #[arg(long, env = FM_ANNOUNCE_ONION_API_ENV, requires = "tor_control")]
+    announce_onion_api: bool,
     /// The bitcoin network that fedimint will be running on
     #[arg(long, env = FM_BITCOIN_NETWORK_ENV, default_value = "regtest")]
     network: bitcoin::network::Network,
     /// The number of blocks the federation stays behind the blockchain tip
     #[arg(long, env = FM_FINALITY_DELAY_ENV, default_value = "10")]
     finality_delay: u32,
+    /// Comma separated list of `<min amount in sats>=<confirmations>` tiers
+    /// requiring peg-ins of at least that amount to have more confirmations
+    /// than the finality delay, e.g. `1000000=20,10000000=60`
+    #[arg(long, env = FM_PEG_IN_CONFIRMATION_TIERS_ENV, value_delimiter = ',')]
+    peg_in_confirmation_tiers: Vec<PegInConfirmationTier>,
 
     #[arg(long, env = FM_BIND_METRICS_API_ENV)]
     bind_metrics_api: Option<SocketAddr>,
@@ -125,6 +157,28 @@ enum ServerSubcommand {
     /// Development-related commands
     #[clap(subcommand)]
     Dev(DevSubcommand),
+    /// Export the signed outcomes of completed sessions into an archive that
+    /// can be checked offline with `fedimint-archive-verify`
+    ExportEpochs {
+        /// First session to export
+        #[arg(long)]
+        from: u64,
+        /// Last session to export, inclusive
+        #[arg(long)]
+        to: u64,
+        /// Directory to write the archive to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Set up a new federation by running distributed key generation across
+    /// the peers described in a TOML file, instead of using the admin UI of
+    /// every peer. All peers must be running and awaiting their password.
+    Dkg {
+        /// TOML file with the peers' API endpoints and passwords, the meta
+        /// and the modules of the federation
+        #[arg(long)]
+        peers: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -196,7 +250,7 @@ fn parse_map(s: &str) -> anyhow::Result<BTreeMap<String, String>> {
 ///         // use `.with_default_modules()` to avoid having
 ///         // to import these manually
 ///         .with_module_kind(WalletInit)
-///         .with_module_kind(MintInit)
+///         .with_module_kind(MintInit::default())
 ///         .with_module_kind(LightningInit)
 ///         .run()
 ///         .await
@@ -241,10 +295,6 @@ pub fn new(
 
         let fedimint_version = env!("CARGO_PKG_VERSION");
 
-        APP_START_TS
-            .with_label_values(&[fedimint_version, code_version_hash])
-            .set(fedimint_core::time::duration_since_epoch().as_secs() as i64);
-
         let opts: ServerOpts = ServerOpts::parse();
 
         TracingSetup::default()
@@ -255,6 +305,12 @@ pub fn new(
 
         info!("Starting fedimintd (version: {fedimint_version} version_hash: {code_version_hash})");
 
+        fedimint_metrics::install_prometheus_backend();
+        APP_START_TS.set(
+            &[fedimint_version, code_version_hash],
+            fedimint_core::time::duration_since_epoch().as_secs() as i64,
+        );
+
         let bitcoind_rpc = BitcoinRpcConfig::get_defaults_from_env_vars()?;
 
         Ok(Self {
@@ -300,12 +356,35 @@ pub fn with_module_instance<P>(mut self, kind: ModuleKind, params: P) -> Self
         self
     }
 
+    /// Attach additional module instance with parameters under an instance id
+    /// derived from its kind, consensus parameters and `discriminator`
+    ///
+    /// Unlike [`Self::with_module_instance`] the id does not change when other
+    /// modules are added, removed or reordered, so clients keep finding their
+    /// data for this module after the federation regenerates its config.
+    /// Instances of the same kind with the same parameters need different
+    /// discriminators.
+    pub fn with_stable_module_instance<P>(
+        mut self,
+        kind: ModuleKind,
+        discriminator: u64,
+        params: P,
+    ) -> anyhow::Result<Self>
+    where
+        P: ModuleInitParams,
+    {
+        self.server_gen_params
+            .attach_config_gen_params_stable(kind, discriminator, params)?;
+        Ok(self)
+    }
+
     /// Attach default server modules to Fedimintd instance
     pub fn with_default_modules(self) -> anyhow::Result<Self> {
         let network = self.opts.network;
 
         let bitcoind_rpc = self.bitcoind_rpc.clone();
         let finality_delay = self.opts.finality_delay;
+        let peg_in_confirmation_tiers = self.opts.peg_in_confirmation_tiers.clone();
         let s = self
             .with_module_kind(LightningInit)
             .with_module_instance(
@@ -317,7 +396,7 @@ pub fn with_default_modules(self) -> anyhow::Result<Self> {
                     consensus: LightningGenParamsConsensus { network },
                 },
             )
-            .with_module_kind(MintInit)
+            .with_module_kind(MintInit::default())
             .with_module_instance(
                 MintInit::kind(),
                 MintGenParams {
@@ -345,6 +424,7 @@ pub fn with_default_modules(self) -> anyhow::Result<Self> {
                         client_default_bitcoin_rpc: default_esplora_server(network),
                         fee_consensus:
                             fedimint_wallet_server::common::config::FeeConsensus::default(),
+                        peg_in_confirmation_tiers,
                     },
                 },
             );
@@ -404,6 +484,22 @@ pub async fn run(self) -> ! {
                     println!("{db_versions}");
                     std::process::exit(0);
                 }
+                ServerSubcommand::ExportEpochs { from, to, out } => {
+                    match export_epochs(self.opts.data_dir.as_ref(), *from..=*to, out).await {
+                        Ok(()) => std::process::exit(0),
+                        Err(e) => {
+                            error!("Failed to export epochs: {e:#}");
+                            std::process::exit(-1);
+                        }
+                    }
+                }
+                ServerSubcommand::Dkg { peers } => match dkg::run_dkg_from_file(peers).await {
+                    Ok(()) => std::process::exit(0),
+                    Err(e) => {
+                        error!("Failed to run DKG: {e:#}");
+                        std::process::exit(-1);
+                    }
+                },
             }
         }
 
@@ -530,6 +626,33 @@ fn check_release_notes_ack() -> anyhow::Result<()> {
     bail!("Must acknowledge release notes. See details above.")
 }
 
+async fn export_epochs(
+    data_dir: Option<&PathBuf>,
+    sessions: RangeInclusive<u64>,
+    out: &Path,
+) -> anyhow::Result<()> {
+    let data_dir = data_dir.context("data-dir option is not present")?;
+    let consensus = read_server_consensus_config(data_dir)?;
+
+    // Only the consensus items are exported, which decode without the modules
+    let db = Database::new(
+        RocksDbReadOnly::open_read_only(data_dir.join(DB_FILE))?,
+        ModuleDecoderRegistry::default().with_fallback(),
+    );
+
+    export_sessions(
+        &db,
+        FederationId(consensus.api_endpoints.consensus_hash()),
+        consensus.broadcast_public_keys,
+        sessions,
+        out,
+    )
+    .await?;
+
+    info!(out = %out.display(), "Exported epochs");
+    Ok(())
+}
+
 async fn run(
     opts: ServerOpts,
     task_group: &TaskGroup,
@@ -562,8 +685,14 @@ async fn run(
         download_token_limit: None,
         p2p_bind: opts.bind_p2p,
         api_bind: opts.bind_api,
+        api_bind_extra: opts.bind_api_extra,
         p2p_url: opts.p2p_url,
         api_url: opts.api_url,
+        tor_control: opts.tor_control.map(|control_addr| TorControlSettings {
+            control_addr,
+            control_password: opts.tor_control_password,
+            announce: opts.announce_onion_api,
+        }),
         default_params,
         max_connections: fedimint_server::config::max_connections(),
         registry: module_inits.clone(),
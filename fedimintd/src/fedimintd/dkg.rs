@@ -0,0 +1,256 @@
+//! Non-interactive distributed key generation across the peers of a new
+//! federation, see [`run_dkg_from_file`]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context};
+use fedimint_api_client::api::DynGlobalApi;
+use fedimint_core::admin_client::{
+    ConfigGenConnectionsRequest, ConfigGenParamsRequest, ServerStatus,
+};
+use fedimint_core::core::ModuleKind;
+use fedimint_core::module::ApiAuth;
+use fedimint_core::util::backoff_util::custom_backoff;
+use fedimint_core::util::{retry, SafeUrl};
+use fedimint_core::NumPeers;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+/// How long we wait for every peer to reach the next setup stage
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Declarative description of a federation to set up, read from a TOML file
+///
+/// ```toml
+/// # Optional, key-value pairs passed to clients
+/// meta = { federation_name = "Example Federation" }
+/// # Optional, the kinds of the modules to enable, all default modules of the
+/// # leader if omitted
+/// modules = ["ln", "mint", "wallet", "meta"]
+/// # Optional, checked against the threshold following from the number of peers
+/// threshold = 3
+/// # Optional, needed if the peers enforce API secrets
+/// api_secret = "secret"
+///
+/// # The first peer is the leader
+/// [[peers]]
+/// name = "alice"
+/// api_url = "wss://alice.example.com"
+/// password = "alice-password"
+/// ```
+///
+/// The endpoints the peers bind to and announce to each other are those every
+/// `fedimintd` was started with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DkgFile {
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+    pub modules: Option<BTreeSet<ModuleKind>>,
+    pub threshold: Option<usize>,
+    pub api_secret: Option<String>,
+    pub peers: Vec<DkgPeer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DkgPeer {
+    /// Guardian name, peers are assigned their ids by sorting the names
+    pub name: String,
+    /// API endpoint of the peer's `fedimintd`, which must be awaiting a
+    /// password
+    pub api_url: SafeUrl,
+    /// The password the peer encrypts its config files with
+    pub password: String,
+}
+
+impl DkgFile {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let dkg_file: DkgFile =
+            toml::from_str(&file).with_context(|| format!("Failed to parse {}", path.display()))?;
+        dkg_file.validate()?;
+        Ok(dkg_file)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(!self.peers.is_empty(), "At least one peer is required");
+
+        let names = self
+            .peers
+            .iter()
+            .map(|peer| &peer.name)
+            .collect::<BTreeSet<_>>();
+        ensure!(names.len() == self.peers.len(), "Peer names must be unique");
+
+        let urls = self
+            .peers
+            .iter()
+            .map(|peer| &peer.api_url)
+            .collect::<BTreeSet<_>>();
+        ensure!(
+            urls.len() == self.peers.len(),
+            "Peer API URLs must be unique"
+        );
+
+        if let Some(threshold) = self.threshold {
+            let actual = NumPeers::from(self.peers.len()).threshold();
+            ensure!(
+                threshold == actual,
+                "A federation of {} peers has a threshold of {actual}, not {threshold}",
+                self.peers.len()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Sets up a new federation by running the config generation steps of the
+/// admin UI against every peer listed in the [`DkgFile`] at `path`, so a
+/// federation can be deployed from a script.
+///
+/// Every peer must be running `fedimintd` and awaiting its password. Returns
+/// once consensus runs on all peers.
+pub async fn run_dkg_from_file(path: &Path) -> anyhow::Result<()> {
+    let dkg_file = DkgFile::read(path)?;
+
+    let peers = dkg_file
+        .peers
+        .iter()
+        .map(|peer| {
+            let api = DynGlobalApi::from_pre_peer_id_admin_endpoint(
+                peer.api_url.clone(),
+                &dkg_file.api_secret,
+            );
+            (peer, ApiAuth(peer.password.clone()), api)
+        })
+        .collect::<Vec<_>>();
+    let (leader, _, leader_api) = &peers[0];
+
+    for (peer, auth, api) in &peers {
+        wait_server_status(peer, api, ServerStatus::AwaitingPassword).await?;
+        api.set_password(auth.clone())
+            .await
+            .with_context(|| format!("Failed to set the password of {}", peer.name))?;
+    }
+    info!(leader = %leader.name, "Set the passwords of all peers");
+
+    for (peer, auth, api) in &peers {
+        let leader_api_url = (peer.name != leader.name).then(|| leader.api_url.clone());
+        api.set_config_gen_connections(
+            ConfigGenConnectionsRequest {
+                our_name: peer.name.clone(),
+                leader_api_url,
+            },
+            auth.clone(),
+        )
+        .await
+        .with_context(|| format!("Failed to connect {} to the leader", peer.name))?;
+
+        // Every peer sets its own local params, only the leader's consensus params
+        // are used
+        let defaults = api.get_default_config_gen_params(auth.clone()).await?;
+        let modules = defaults
+            .modules
+            .into_iter_modules()
+            .filter(|(_, kind, _)| {
+                dkg_file
+                    .modules
+                    .as_ref()
+                    .map_or(true, |modules| modules.contains(kind))
+            })
+            .collect();
+        api.set_config_gen_params(
+            ConfigGenParamsRequest {
+                meta: dkg_file.meta.clone(),
+                modules,
+            },
+            auth.clone(),
+        )
+        .await
+        .with_context(|| format!("Failed to set the config gen params of {}", peer.name))?;
+    }
+
+    let params = leader_api.consensus_config_gen_params().await?;
+    let found_names = params
+        .consensus
+        .peers
+        .values()
+        .map(|peer| &peer.name)
+        .collect::<BTreeSet<_>>();
+    let expected_names = peers
+        .iter()
+        .map(|(peer, _, _)| &peer.name)
+        .collect::<BTreeSet<_>>();
+    ensure!(
+        found_names == expected_names,
+        "The leader knows the peers {found_names:?}, expected {expected_names:?}"
+    );
+    if let Some(modules) = &dkg_file.modules {
+        let found_modules = params
+            .consensus
+            .modules
+            .iter_modules()
+            .map(|(_, kind, _)| kind.clone())
+            .collect::<BTreeSet<_>>();
+        ensure!(
+            &found_modules == modules,
+            "The leader doesn't support all requested modules, found {found_modules:?}"
+        );
+    }
+
+    info!("Running DKG");
+    futures::future::try_join_all(peers.iter().map(|(peer, auth, api)| async move {
+        api.run_dkg(auth.clone())
+            .await
+            .with_context(|| format!("DKG failed on {}", peer.name))
+    }))
+    .await?;
+
+    let mut hashes = BTreeSet::new();
+    for (peer, auth, api) in &peers {
+        wait_server_status(peer, api, ServerStatus::VerifyingConfigs).await?;
+        hashes.insert(api.get_verify_config_hash(auth.clone()).await?);
+    }
+    if hashes.len() != 1 {
+        bail!("Peers generated different configs, not starting consensus");
+    }
+
+    for (peer, auth, api) in &peers {
+        // The connection might drop while the peer switches to consensus, so we only
+        // rely on its status
+        if let Err(e) = api.start_consensus(auth.clone()).await {
+            debug!(peer = %peer.name, "Error calling start_consensus: {e:?}, trying to continue...");
+        }
+        wait_server_status(peer, api, ServerStatus::ConsensusRunning).await?;
+    }
+    info!("Consensus running on all peers");
+
+    Ok(())
+}
+
+async fn wait_server_status(
+    peer: &DkgPeer,
+    api: &DynGlobalApi,
+    expected: ServerStatus,
+) -> anyhow::Result<()> {
+    let max_retries = (STATUS_TIMEOUT.as_secs() / 5) as usize;
+    retry(
+        format!("Waiting for {} to reach {expected:?}", peer.name),
+        custom_backoff(
+            Duration::from_millis(200),
+            Duration::from_secs(5),
+            Some(max_retries),
+        ),
+        || async {
+            let status = api.status().await?.server;
+            ensure!(status == expected, "{} is {status:?}", peer.name);
+            Ok(())
+        },
+    )
+    .await
+}
@@ -2,7 +2,7 @@
 use fedimint_core::config::FederationId;
 use fedimint_core::Amount;
 use ln_gateway::rpc::rpc_client::GatewayRpcClient;
-use ln_gateway::rpc::{ConfigPayload, SetFeesPayload};
+use ln_gateway::rpc::{ConfigPayload, SetFeesPayload, SetPaymentLimitsPayload};
 
 use crate::print_response;
 
@@ -35,6 +35,24 @@ pub enum ConfigCommands {
         #[clap(long)]
         tx_ppm: Option<u64>,
     },
+    /// Set the gateway's payment size limits, an amount of zero removes the
+    /// limit
+    SetLimits {
+        #[clap(long)]
+        federation_id: Option<FederationId>,
+
+        /// Minimum amount of incoming HTLCs
+        #[clap(long)]
+        htlc_min: Option<Amount>,
+
+        /// Maximum amount of incoming HTLCs
+        #[clap(long)]
+        htlc_max: Option<Amount>,
+
+        /// Maximum amount of outgoing payments
+        #[clap(long)]
+        payment_max: Option<Amount>,
+    },
 }
 
 impl ConfigCommands {
@@ -80,6 +98,21 @@ pub async fn handle(
                     })
                     .await?;
             }
+            Self::SetLimits {
+                federation_id,
+                htlc_min,
+                htlc_max,
+                payment_max,
+            } => {
+                create_client()
+                    .set_payment_limits(SetPaymentLimitsPayload {
+                        federation_id,
+                        htlc_minimum: htlc_min,
+                        htlc_maximum: htlc_max,
+                        payment_maximum: payment_max,
+                    })
+                    .await?;
+            }
         }
 
         Ok(())
@@ -0,0 +1,116 @@
+use clap::Subcommand;
+use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
+use fedimint_core::hex::FromHex;
+use fedimint_core::util::confirm;
+use fedimint_ln_common::contracts::Preimage;
+use ln_gateway::rpc::rpc_client::GatewayRpcClient;
+use ln_gateway::rpc::{ContractResolution, ListContractsPayload, ResolveContractPayload};
+
+use crate::print_response;
+
+#[derive(Subcommand)]
+pub enum ContractCommands {
+    /// List the LNv1 and LNv2 contracts that haven't reached a terminal
+    /// resolution yet
+    List {
+        #[clap(long)]
+        federation_id: Option<FederationId>,
+
+        /// Only list contracts that have been pending for over an hour
+        #[clap(long)]
+        stuck: bool,
+    },
+    /// Claim the outgoing contract of a stuck payment with the preimage of
+    /// the paid invoice
+    ForceClaim {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        #[clap(long)]
+        operation_id: OperationId,
+
+        /// Hex encoded preimage of the paid invoice
+        #[clap(long, value_parser = parse_preimage)]
+        preimage: Preimage,
+
+        /// Claim without asking for confirmation
+        #[clap(long)]
+        yes: bool,
+    },
+    /// Refund the outgoing contract of a stuck payment to the user
+    ForceRefund {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        #[clap(long)]
+        operation_id: OperationId,
+
+        /// Refund without asking for confirmation
+        #[clap(long)]
+        yes: bool,
+    },
+}
+
+impl ContractCommands {
+    pub async fn handle(
+        self,
+        create_client: impl Fn() -> GatewayRpcClient + Send + Sync,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::List {
+                federation_id,
+                stuck,
+            } => {
+                let response = create_client()
+                    .list_contracts(ListContractsPayload {
+                        federation_id,
+                        stuck,
+                    })
+                    .await?;
+
+                print_response(response);
+            }
+            Self::ForceClaim {
+                federation_id,
+                operation_id,
+                preimage,
+                yes,
+            } => {
+                if yes || confirm("Claim the outgoing contract for the gateway?")? {
+                    create_client()
+                        .resolve_contract(ResolveContractPayload {
+                            federation_id,
+                            operation_id,
+                            resolution: ContractResolution::Claim { preimage },
+                        })
+                        .await?;
+                }
+            }
+            Self::ForceRefund {
+                federation_id,
+                operation_id,
+                yes,
+            } => {
+                eprintln!(
+                    "Make sure the lightning payment can't succeed anymore, otherwise the gateway loses the funds."
+                );
+                if yes || confirm("Refund the outgoing contract to the user?")? {
+                    create_client()
+                        .resolve_contract(ResolveContractPayload {
+                            federation_id,
+                            operation_id,
+                            resolution: ContractResolution::Refund,
+                        })
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_preimage(s: &str) -> anyhow::Result<Preimage> {
+    Ok(Preimage(<[u8; 32]>::from_hex(s)?))
+}
@@ -1,9 +1,9 @@
 use clap::Subcommand;
 use fedimint_core::config::FederationId;
-use fedimint_core::fedimint_build_code_version_env;
+use fedimint_core::{fedimint_build_code_version_env, Amount};
 use fedimint_eventlog::{EventKind, EventLogId};
 use ln_gateway::rpc::rpc_client::GatewayRpcClient;
-use ln_gateway::rpc::{ConnectFedPayload, LeaveFedPayload, PaymentLogPayload};
+use ln_gateway::rpc::{ConnectFedPayload, LeaveFedPayload, PaymentLogPayload, ProbePayload};
 
 use crate::print_response;
 
@@ -50,6 +50,13 @@ pub enum GeneralCommands {
         #[clap(long)]
         event_kinds: Vec<EventKind>,
     },
+    /// Pay ecash over the gateway's lightning node and back into ecash to check
+    /// that payments through the gateway work, exits with an error if they
+    /// don't
+    Probe {
+        federation_id: FederationId,
+        amount: Amount,
+    },
 }
 
 impl GeneralCommands {
@@ -124,6 +131,22 @@ pub async fn handle(
                     .await?;
                 print_response(payment_log);
             }
+            Self::Probe {
+                federation_id,
+                amount,
+            } => {
+                let response = create_client()
+                    .probe(ProbePayload {
+                        federation_id,
+                        amount,
+                    })
+                    .await?;
+                let success = response.success;
+                print_response(response);
+                if !success {
+                    anyhow::bail!("Liquidity probe failed");
+                }
+            }
         }
 
         Ok(())
@@ -1,4 +1,5 @@
 use clap::Subcommand;
+use fedimint_core::{Amount, Msats, Sats};
 use lightning_invoice::Bolt11Invoice;
 use ln_gateway::rpc::rpc_client::GatewayRpcClient;
 use ln_gateway::rpc::{CloseChannelsWithPeerPayload, OpenChannelPayload};
@@ -12,7 +13,9 @@
 pub enum LightningCommands {
     /// Create an invoice to receive lightning funds to the gateway.
     CreateInvoice {
-        amount_msats: u64,
+        /// Amount of the invoice, e.g. `21sat` or `0.001btc`, defaults to
+        /// millisatoshis without a denomination
+        amount_msats: Msats,
 
         #[clap(long)]
         expiry_secs: Option<u32>,
@@ -22,6 +25,15 @@ pub enum LightningCommands {
     },
     /// Pay a lightning invoice as the gateway (i.e. no e-cash exchange).
     PayInvoice { invoice: Bolt11Invoice },
+    /// Pay a BOLT12 offer as the gateway. The offer's invoice is paid over
+    /// the blinded paths chosen by its issuer.
+    PayOffer {
+        offer: String,
+
+        /// Amount to pay, required if the offer doesn't specify one
+        #[clap(long)]
+        amount: Option<Amount>,
+    },
     /// Open a channel with another lightning node.
     OpenChannel {
         /// The public key of the node to open a channel with
@@ -31,13 +43,15 @@ pub enum LightningCommands {
         #[clap(long)]
         host: String,
 
-        /// The amount to fund the channel with
+        /// The amount to fund the channel with, defaults to satoshis without
+        /// a denomination
         #[clap(long)]
-        channel_size_sats: u64,
+        channel_size_sats: Sats,
 
-        /// The amount to push to the other side of the channel
+        /// The amount to push to the other side of the channel, defaults to
+        /// satoshis without a denomination
         #[clap(long)]
-        push_amount_sats: Option<u64>,
+        push_amount_sats: Option<Sats>,
     },
     /// Close all channels with a peer, claiming the funds to the lightning
     /// node's on-chain wallet.
@@ -77,6 +91,12 @@ pub async fn handle(
                     .await?;
                 println!("{response}");
             }
+            Self::PayOffer { offer, amount } => {
+                let response = create_client()
+                    .pay_offer(ln_gateway::rpc::PayOfferForOperatorPayload { offer, amount })
+                    .await?;
+                println!("{response}");
+            }
             Self::OpenChannel {
                 pubkey,
                 host,
@@ -88,7 +108,7 @@ pub async fn handle(
                         pubkey,
                         host,
                         channel_size_sats,
-                        push_amount_sats: push_amount_sats.unwrap_or(0),
+                        push_amount_sats: push_amount_sats.unwrap_or(Sats::ZERO),
                     })
                     .await?;
                 println!("{funding_txid}");
@@ -1,6 +1,7 @@
 #![deny(clippy::pedantic, clippy::nursery)]
 
 mod config_commands;
+mod contract_commands;
 mod ecash_commands;
 mod general_commands;
 mod lightning_commands;
@@ -8,8 +9,10 @@
 
 use clap::{CommandFactory, Parser, Subcommand};
 use config_commands::ConfigCommands;
+use contract_commands::ContractCommands;
 use ecash_commands::EcashCommands;
 use fedimint_core::util::SafeUrl;
+use fedimint_core::AMOUNT_FORMAT_HELP;
 use fedimint_logging::TracingSetup;
 use general_commands::GeneralCommands;
 use lightning_commands::LightningCommands;
@@ -19,7 +22,7 @@
 use serde::Serialize;
 
 #[derive(Parser)]
-#[command(version)]
+#[command(version, after_help = AMOUNT_FORMAT_HELP)]
 struct Cli {
     /// The address of the gateway webserver
     #[clap(short, long, default_value = "http://127.0.0.1:8175")]
@@ -43,9 +46,11 @@ enum Commands {
     Onchain(OnchainCommands),
     #[command(subcommand)]
     Cfg(ConfigCommands),
-    Completion {
-        shell: clap_complete::Shell,
-    },
+    /// List and manually resolve contracts that got stuck
+    #[command(subcommand)]
+    Contracts(ContractCommands),
+    /// Print a shell completion script, e.g. for bash, zsh or fish
+    Completion { shell: clap_complete::Shell },
 }
 
 #[tokio::main]
@@ -62,6 +67,7 @@ async fn main() -> anyhow::Result<()> {
         Commands::Ecash(ecash_command) => ecash_command.handle(create_client).await?,
         Commands::Onchain(onchain_command) => onchain_command.handle(create_client).await?,
         Commands::Cfg(config_commands) => config_commands.handle(create_client).await?,
+        Commands::Contracts(contract_commands) => contract_commands.handle(create_client).await?,
         Commands::Completion { shell } => {
             clap_complete::generate(
                 shell,
@@ -1,8 +1,10 @@
 use bitcoin::address::NetworkUnchecked;
 use clap::Subcommand;
+use fedimint_core::config::FederationId;
 use fedimint_core::BitcoinAmountOrAll;
 use ln_gateway::rpc::rpc_client::GatewayRpcClient;
-use ln_gateway::rpc::SendOnchainPayload;
+use ln_gateway::rpc::{DepositAddressPayload, SendOnchainPayload, WithdrawPayload};
+use serde_json::json;
 
 use crate::print_response;
 
@@ -29,6 +31,34 @@ pub enum OnchainCommands {
         #[clap(long)]
         fee_rate_sats_per_vbyte: u64,
     },
+    /// Get the balance of the lightning node's on-chain wallet.
+    Balance,
+    /// Peg-in funds from the lightning node's on-chain wallet to a federation
+    /// the gateway is connected to, the e-cash is claimed once the deposit
+    /// confirmed.
+    Deposit {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// The amount to peg-in, can be "all" to use all on-chain funds.
+        #[clap(long)]
+        amount: BitcoinAmountOrAll,
+
+        /// The fee rate to use in satoshis per vbyte.
+        #[clap(long)]
+        fee_rate_sats_per_vbyte: u64,
+    },
+    /// Peg-out e-cash from a federation the gateway is connected to into the
+    /// lightning node's on-chain wallet.
+    Withdraw {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// The amount to peg-out, can be "all" to withdraw all e-cash of the
+        /// federation.
+        #[clap(long)]
+        amount: BitcoinAmountOrAll,
+    },
 }
 
 impl OnchainCommands {
@@ -58,6 +88,46 @@ pub async fn handle(
                     .await?;
                 print_response(response);
             }
+            Self::Balance => {
+                let response = create_client().get_balances().await?;
+                print_response(json!({
+                    "onchain_balance_sats": response.onchain_balance_sats,
+                }));
+            }
+            Self::Deposit {
+                federation_id,
+                amount,
+                fee_rate_sats_per_vbyte,
+            } => {
+                let address = create_client()
+                    .get_deposit_address(DepositAddressPayload { federation_id })
+                    .await?;
+                let txid = create_client()
+                    .send_onchain(SendOnchainPayload {
+                        address: address.clone(),
+                        amount,
+                        fee_rate_sats_per_vbyte,
+                    })
+                    .await?;
+                print_response(json!({
+                    "address": address.assume_checked(),
+                    "txid": txid,
+                }));
+            }
+            Self::Withdraw {
+                federation_id,
+                amount,
+            } => {
+                let address = create_client().get_ln_onchain_address().await?;
+                let response = create_client()
+                    .withdraw(WithdrawPayload {
+                        federation_id,
+                        amount,
+                        address,
+                    })
+                    .await?;
+                print_response(response);
+            }
         }
 
         Ok(())
@@ -19,7 +19,7 @@
 use fedimint_core::envs::{is_env_var_set, FM_DEVIMINT_DISABLE_MODULE_LNV2_ENV};
 use fedimint_core::util::backoff_util::aggressive_backoff_long;
 use fedimint_core::util::retry;
-use fedimint_core::{Amount, BitcoinAmountOrAll};
+use fedimint_core::{Amount, BitcoinAmountOrAll, Msats, Sats};
 use fedimint_testing::gateway::LightningNodeType;
 use itertools::Itertools;
 use ln_gateway::rpc::{GatewayBalances, GatewayFedConfig, GatewayInfo};
@@ -584,9 +584,9 @@ async fn liquidity_test() -> anyhow::Result<()> {
                 aggressive_backoff_long(),
                 || async {
                     let curr_lightning_balance = balances.lightning_balance_msats;
-                    ensure!(curr_lightning_balance == 0, "Close channels did not sweep all lightning funds");
+                    ensure!(curr_lightning_balance == Msats::ZERO, "Close channels did not sweep all lightning funds");
                     let inbound_lightning_balance = balances.inbound_lightning_liquidity_msats;
-                    ensure!(inbound_lightning_balance == 0, "Close channels did not sweep all lightning funds");
+                    ensure!(inbound_lightning_balance == Msats::ZERO, "Close channels did not sweep all lightning funds");
                     Ok(())
                 }
             ).await?;
@@ -600,7 +600,7 @@ async fn liquidity_test() -> anyhow::Result<()> {
                 aggressive_backoff_long(),
                 || async {
                     let curr_balance = gw.get_balances().await?.onchain_balance_sats;
-                    ensure!(curr_balance == 0, "Gateway onchain balance did not match previous balance minus withdraw amount");
+                    ensure!(curr_balance == Sats::ZERO, "Gateway onchain balance did not match previous balance minus withdraw amount");
                     Ok(())
                 }
             ).await?;
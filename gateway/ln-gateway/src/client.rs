@@ -8,12 +8,13 @@
 use fedimint_client::derivable_secret::{ChildId, DerivableSecret};
 use fedimint_client::module::init::ClientModuleInitRegistry;
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
-use fedimint_client::{Client, ClientBuilder};
+use fedimint_client::{Client, ClientBuilder, ClientHandle};
 use fedimint_core::config::FederationId;
 use fedimint_core::core::ModuleKind;
 use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
 use fedimint_core::encoding::Encodable;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_ln_client::LightningClientInit;
 
 use crate::db::FederationConfig;
 use crate::error::AdminGatewayError;
@@ -182,6 +183,48 @@ pub async fn build(
         .map_err(AdminGatewayError::ClientCreationError)
     }
 
+    /// Builds the client liquidity probes of the federation are paid from and
+    /// to, see [`Gateway::handle_probe_msg`]. It is a regular user client with
+    /// its own database and secret, so funds of an interrupted probe are
+    /// returned to the gateway by the next probe.
+    pub async fn build_probe(
+        &self,
+        config: FederationConfig,
+        mnemonic: &Mnemonic,
+    ) -> AdminResult<ClientHandle> {
+        let invite_code = config.invite_code;
+        let federation_id = invite_code.federation_id();
+        let db_path = self.work_dir.join(format!("probe-{federation_id}.db"));
+        let rocksdb = fedimint_rocksdb::RocksDb::open(db_path)
+            .map_err(AdminGatewayError::ClientCreationError)?;
+        let db = Database::new(rocksdb, ModuleDecoderRegistry::default());
+
+        let mut registry = self.registry.clone();
+        registry.attach(LightningClientInit::default());
+
+        let mut client_builder = Client::builder(db)
+            .await
+            .map_err(AdminGatewayError::ClientCreationError)?;
+        client_builder.with_module_inits(registry);
+        client_builder.with_primary_module_kind(self.primary_module_kind.clone());
+        client_builder.with_connector(config.connector);
+
+        let root_secret = Self::derive_probe_secret(mnemonic, &federation_id);
+        if Client::is_initialized(client_builder.db_no_decoders()).await {
+            client_builder.open(root_secret).await
+        } else {
+            let client_config = config
+                .connector
+                .download_from_invite_code(&invite_code)
+                .await
+                .map_err(AdminGatewayError::ClientCreationError)?;
+            client_builder
+                .join(root_secret, client_config, invite_code.api_secret())
+                .await
+        }
+        .map_err(AdminGatewayError::ClientCreationError)
+    }
+
     /// Verifies that the saved `ClientConfig` contains the expected
     /// federation's config.
     async fn verify_client_config(db: &Database, federation_id: FederationId) -> AdminResult<()> {
@@ -209,6 +252,17 @@ fn derive_federation_secret(
         federation_wallet_root_secret.child_key(ChildId(0))
     }
 
+    /// Derives the secret of the probe client, the second wallet of the
+    /// federation according to the same policy as
+    /// [`Self::derive_federation_secret`].
+    fn derive_probe_secret(mnemonic: &Mnemonic, federation_id: &FederationId) -> DerivableSecret {
+        let global_root_secret = Bip39RootSecretStrategy::<12>::to_root_secret(mnemonic);
+        let multi_federation_root_secret = global_root_secret.child_key(ChildId(0));
+        let federation_root_secret = multi_federation_root_secret.federation_key(federation_id);
+        let federation_wallet_root_secret = federation_root_secret.child_key(ChildId(1));
+        federation_wallet_root_secret.child_key(ChildId(0))
+    }
+
     /// Returns a vector of "legacy" federations which did not derive their
     /// client secret's from the gateway's mnemonic.
     pub fn legacy_federations(&self, all_federations: BTreeSet<FederationId>) -> Vec<FederationId> {
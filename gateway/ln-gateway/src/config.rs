@@ -5,10 +5,12 @@
 
 use bitcoin::Network;
 use clap::Parser;
+use fedimint_core::config::FederationId;
 use fedimint_core::util::SafeUrl;
 
 use super::envs;
 use super::lightning::LightningMode;
+use super::registration_policy::RegistrationPolicy;
 use super::rpc::V1_API_ENDPOINT;
 
 /// Command line parameters for starting the gateway. `mode`, `data_dir`,
@@ -50,6 +52,30 @@ pub struct GatewayOpts {
     /// The Lightning module to use: LNv1, LNv2, or both
     #[arg(long = "lightning-module-mode", env = envs::FM_GATEWAY_LIGHTNING_MODULE_MODE_ENV, default_value_t = LightningModuleMode::All)]
     lightning_module_mode: LightningModuleMode,
+
+    /// Federations the gateway is allowed to connect to, all federations are
+    /// allowed if empty
+    #[arg(
+        long = "federation-allowlist",
+        env = envs::FM_GATEWAY_FEDERATION_ALLOWLIST_ENV,
+        value_delimiter = ','
+    )]
+    federation_allowlist: Vec<FederationId>,
+
+    /// Federations the gateway refuses to connect to
+    #[arg(
+        long = "federation-denylist",
+        env = envs::FM_GATEWAY_FEDERATION_DENYLIST_ENV,
+        value_delimiter = ','
+    )]
+    federation_denylist: Vec<FederationId>,
+
+    /// Maximum number of attempts to connect to a new federation per hour
+    #[arg(
+        long = "max-registrations-per-hour",
+        env = envs::FM_GATEWAY_MAX_REGISTRATIONS_PER_HOUR_ENV
+    )]
+    max_registrations_per_hour: Option<u32>,
 }
 
 impl GatewayOpts {
@@ -72,6 +98,12 @@ pub fn to_gateway_parameters(&self) -> anyhow::Result<GatewayParameters> {
             network: self.network,
             num_route_hints: self.num_route_hints,
             lightning_module_mode: self.lightning_module_mode,
+            registration_policy: RegistrationPolicy::new(
+                (!self.federation_allowlist.is_empty())
+                    .then(|| self.federation_allowlist.iter().copied().collect()),
+                self.federation_denylist.iter().copied().collect(),
+                self.max_registrations_per_hour,
+            ),
         })
     }
 }
@@ -90,6 +122,7 @@ pub struct GatewayParameters {
     pub network: Network,
     pub num_route_hints: u32,
     pub lightning_module_mode: LightningModuleMode,
+    pub registration_policy: RegistrationPolicy,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
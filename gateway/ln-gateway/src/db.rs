@@ -1,16 +1,20 @@
 use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
 
 use bitcoin::hashes::{sha256, Hash};
 use fedimint_api_client::api::net::Connector;
 use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
 use fedimint_core::db::{
-    CoreMigrationFn, DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCoreTyped,
-    MigrationContext,
+    CoreMigrationFn, DatabaseTransaction, DatabaseValue, DatabaseVersion,
+    IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped, MigrationContext,
 };
 use fedimint_core::encoding::btc::NetworkLegacyEncodingWrapper;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::invite_code::InviteCode;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::{impl_db_lookup, impl_db_record, push_db_pair_items, secp256k1, Amount};
+use fedimint_ln_common::contracts::{ContractId, Preimage};
 use fedimint_ln_common::serde_routing_fees;
 use fedimint_lnv2_common::contracts::{IncomingContract, PaymentImage};
 use fedimint_lnv2_common::gateway_api::PaymentFee;
@@ -23,6 +27,10 @@
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+use crate::error::PaymentLimitError;
+use crate::lightning::failover::LightningBackend;
+use crate::rpc::ContractResolution;
+
 pub trait GatewayDbtxNcExt {
     async fn save_federation_config(&mut self, config: &FederationConfig);
     async fn load_federation_configs_v0(&mut self) -> BTreeMap<FederationId, FederationConfigV0>;
@@ -72,6 +80,52 @@ async fn load_registered_incoming_contract(
         payment_image: PaymentImage,
     ) -> Option<RegisteredIncomingContract>;
 
+    /// Adds an intercepted HTLC to the retry queue, or updates it if it is
+    /// already queued.
+    async fn save_pending_htlc(&mut self, key: PendingHtlcKey, htlc: &PendingHtlc);
+
+    async fn remove_pending_htlc(&mut self, key: &PendingHtlcKey);
+
+    async fn load_pending_htlcs(&mut self) -> BTreeMap<PendingHtlcKey, PendingHtlc>;
+
+    /// Pins the outgoing payment with the given payment hash to a lightning
+    /// node, so retries are dispatched to the node the payment may already be
+    /// in-flight on.
+    async fn save_outgoing_payment_backend(
+        &mut self,
+        payment_hash: sha256::Hash,
+        backend: LightningBackend,
+    );
+
+    async fn load_outgoing_payment_backend(
+        &mut self,
+        payment_hash: sha256::Hash,
+    ) -> Option<LightningBackend>;
+
+    /// Returns the recorded outcome of the outgoing LNv1 payment with the given
+    /// payment hash, if the gateway was asked to pay it before.
+    async fn load_outgoing_payment_outcome(
+        &mut self,
+        payment_hash: sha256::Hash,
+    ) -> Option<OutgoingPaymentOutcome>;
+
+    async fn save_outgoing_payment_outcome(
+        &mut self,
+        payment_hash: sha256::Hash,
+        outcome: &OutgoingPaymentOutcome,
+    );
+
+    async fn remove_outgoing_payment_outcome(&mut self, payment_hash: sha256::Hash);
+
+    /// Records the resolution the operator forced for the outgoing contract of
+    /// the payment with the given operation id. Returns false without
+    /// changing the record if a resolution was already forced.
+    async fn save_forced_contract_resolution(
+        &mut self,
+        operation_id: OperationId,
+        resolution: &ContractResolution,
+    ) -> bool;
+
     /// Reads and serializes structures from the gateway's database for the
     /// purpose for serializing to JSON for inspection.
     async fn dump_database(
@@ -178,6 +232,74 @@ async fn load_registered_incoming_contract(
             .await
     }
 
+    async fn save_pending_htlc(&mut self, key: PendingHtlcKey, htlc: &PendingHtlc) {
+        self.insert_entry(&key, htlc).await;
+    }
+
+    async fn remove_pending_htlc(&mut self, key: &PendingHtlcKey) {
+        self.remove_entry(key).await;
+    }
+
+    async fn load_pending_htlcs(&mut self) -> BTreeMap<PendingHtlcKey, PendingHtlc> {
+        self.find_by_prefix(&PendingHtlcKeyPrefix)
+            .await
+            .collect::<BTreeMap<_, _>>()
+            .await
+    }
+
+    async fn save_outgoing_payment_backend(
+        &mut self,
+        payment_hash: sha256::Hash,
+        backend: LightningBackend,
+    ) {
+        self.insert_entry(&OutgoingPaymentBackendKey(payment_hash), &backend)
+            .await;
+    }
+
+    async fn load_outgoing_payment_backend(
+        &mut self,
+        payment_hash: sha256::Hash,
+    ) -> Option<LightningBackend> {
+        self.get_value(&OutgoingPaymentBackendKey(payment_hash))
+            .await
+    }
+
+    async fn load_outgoing_payment_outcome(
+        &mut self,
+        payment_hash: sha256::Hash,
+    ) -> Option<OutgoingPaymentOutcome> {
+        self.get_value(&OutgoingPaymentOutcomeKey(payment_hash))
+            .await
+    }
+
+    async fn save_outgoing_payment_outcome(
+        &mut self,
+        payment_hash: sha256::Hash,
+        outcome: &OutgoingPaymentOutcome,
+    ) {
+        self.insert_entry(&OutgoingPaymentOutcomeKey(payment_hash), outcome)
+            .await;
+    }
+
+    async fn remove_outgoing_payment_outcome(&mut self, payment_hash: sha256::Hash) {
+        self.remove_entry(&OutgoingPaymentOutcomeKey(payment_hash))
+            .await;
+    }
+
+    async fn save_forced_contract_resolution(
+        &mut self,
+        operation_id: OperationId,
+        resolution: &ContractResolution,
+    ) -> bool {
+        let key = ForcedContractResolutionKey(operation_id);
+        if self.get_value(&key).await.is_some() {
+            return false;
+        }
+
+        self.insert_new_entry(&key, resolution).await;
+        true
+    }
+
     async fn dump_database(
         &mut self,
         prefix_names: Vec<String>,
@@ -206,6 +328,46 @@ async fn dump_database(
                             .insert("Gateway Public Key".to_string(), Box::new(public_key));
                     }
                 }
+                DbKeyPrefix::PendingHtlc => {
+                    push_db_pair_items!(
+                        self,
+                        PendingHtlcKeyPrefix,
+                        PendingHtlcKey,
+                        PendingHtlc,
+                        gateway_items,
+                        "Pending HTLCs"
+                    );
+                }
+                DbKeyPrefix::OutgoingPaymentBackend => {
+                    push_db_pair_items!(
+                        self,
+                        OutgoingPaymentBackendKeyPrefix,
+                        OutgoingPaymentBackendKey,
+                        LightningBackend,
+                        gateway_items,
+                        "Outgoing Payment Backends"
+                    );
+                }
+                DbKeyPrefix::OutgoingPaymentOutcome => {
+                    push_db_pair_items!(
+                        self,
+                        OutgoingPaymentOutcomeKeyPrefix,
+                        OutgoingPaymentOutcomeKey,
+                        OutgoingPaymentOutcome,
+                        gateway_items,
+                        "Outgoing Payment Outcomes"
+                    );
+                }
+                DbKeyPrefix::ForcedContractResolution => {
+                    push_db_pair_items!(
+                        self,
+                        ForcedContractResolutionKeyPrefix,
+                        ForcedContractResolutionKey,
+                        ContractResolution,
+                        gateway_items,
+                        "Forced Contract Resolutions"
+                    );
+                }
                 _ => {}
             }
         }
@@ -222,6 +384,10 @@ enum DbKeyPrefix {
     GatewayConfiguration = 0x07,
     PreimageAuthentication = 0x08,
     RegisteredIncomingContract = 0x09,
+    PendingHtlc = 0x0a,
+    OutgoingPaymentBackend = 0x0b,
+    OutgoingPaymentOutcome = 0x0c,
+    ForcedContractResolution = 0x0d,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -271,6 +437,18 @@ pub struct FederationConfigV1 {
     pub connector: Connector,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct FederationConfigV2 {
+    pub invite_code: InviteCode,
+    // Unique integer identifier per-federation that is assigned when the gateways joins a
+    // federation.
+    #[serde(alias = "mint_channel_id")]
+    pub federation_index: u64,
+    pub lightning_fee: PaymentFee,
+    pub transaction_fee: PaymentFee,
+    pub connector: Connector,
+}
+
 #[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
 struct FederationIdKey {
     id: FederationId,
@@ -286,6 +464,55 @@ pub struct FederationConfig {
     pub lightning_fee: PaymentFee,
     pub transaction_fee: PaymentFee,
     pub connector: Connector,
+    #[serde(default)]
+    pub payment_limits: PaymentLimits,
+}
+
+/// Operator configured bounds on the size of payments the gateway routes for a
+/// federation. Protects the operator from being spammed with dust HTLCs as
+/// well as from taking on too much exposure in a single payment.
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize,
+)]
+pub struct PaymentLimits {
+    /// Incoming HTLCs below this amount are rejected
+    pub htlc_minimum: Option<Amount>,
+    /// Incoming HTLCs above this amount are rejected
+    pub htlc_maximum: Option<Amount>,
+    /// Outgoing payments above this amount are rejected
+    pub payment_maximum: Option<Amount>,
+}
+
+impl PaymentLimits {
+    /// Checks that an incoming HTLC of `amount` is within the configured
+    /// minimum and maximum.
+    pub fn check_incoming_htlc(&self, amount: Amount) -> Result<(), PaymentLimitError> {
+        if let Some(minimum) = self.htlc_minimum {
+            if amount < minimum {
+                return Err(PaymentLimitError::HtlcBelowMinimum { amount, minimum });
+            }
+        }
+
+        if let Some(maximum) = self.htlc_maximum {
+            if amount > maximum {
+                return Err(PaymentLimitError::HtlcAboveMaximum { amount, maximum });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that an outgoing payment of `amount` does not exceed the
+    /// configured maximum.
+    pub fn check_outgoing_payment(&self, amount: Amount) -> Result<(), PaymentLimitError> {
+        if let Some(maximum) = self.payment_maximum {
+            if amount > maximum {
+                return Err(PaymentLimitError::PaymentAboveMaximum { amount, maximum });
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl_db_record!(
@@ -404,6 +631,7 @@ pub fn get_gatewayd_database_migrations() -> BTreeMap<DatabaseVersion, CoreMigra
     migrations.insert(DatabaseVersion(1), |ctx| migrate_to_v2(ctx).boxed());
     migrations.insert(DatabaseVersion(2), |ctx| migrate_to_v3(ctx).boxed());
     migrations.insert(DatabaseVersion(3), |ctx| migrate_to_v4(ctx).boxed());
+    migrations.insert(DatabaseVersion(4), |ctx| migrate_to_v5(ctx).boxed());
     migrations
 }
 
@@ -500,6 +728,7 @@ async fn migrate_to_v4(mut ctx: MigrationContext<'_>) -> Result<(), anyhow::Erro
                 lightning_fee: old_federation_config.fees.into(),
                 transaction_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
                 connector: Connector::default(),
+                payment_limits: PaymentLimits::default(),
             };
             let new_key = FederationIdKey { id: fed_id.id };
             dbtx.insert_new_entry(&new_key, &new_fed_config).await;
@@ -508,6 +737,39 @@ async fn migrate_to_v4(mut ctx: MigrationContext<'_>) -> Result<(), anyhow::Erro
     Ok(())
 }
 
+/// Adds [`PaymentLimits`] to the federation configs that were written before
+/// they existed. Configs that [`migrate_to_v4`] wrote with a gateway already
+/// knowing about them are in the current format and are left alone.
+async fn migrate_to_v5(mut ctx: MigrationContext<'_>) -> Result<(), anyhow::Error> {
+    let mut dbtx = ctx.dbtx();
+    let decoders = ModuleDecoderRegistry::default();
+
+    let configs = dbtx
+        .raw_find_by_prefix(&[DbKeyPrefix::FederationConfig as u8])
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+    for (key, value) in configs {
+        // An old config is shorter, so it never decodes as the current one
+        if FederationConfig::from_bytes(&value, &decoders).is_ok() {
+            continue;
+        }
+
+        let old_federation_config = FederationConfigV2::from_bytes(&value, &decoders)?;
+        let new_fed_config = FederationConfig {
+            invite_code: old_federation_config.invite_code,
+            federation_index: old_federation_config.federation_index,
+            lightning_fee: old_federation_config.lightning_fee,
+            transaction_fee: old_federation_config.transaction_fee,
+            connector: old_federation_config.connector,
+            payment_limits: PaymentLimits::default(),
+        };
+        dbtx.raw_insert_bytes(&key, &new_fed_config.to_bytes())
+            .await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Encodable, Decodable)]
 struct RegisteredIncomingContractKey(pub PaymentImage);
 
@@ -525,6 +787,179 @@ pub struct RegisteredIncomingContract {
     db_prefix = DbKeyPrefix::RegisteredIncomingContract,
 );
 
+/// Identifies an intercepted HTLC on the gateway's lightning node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encodable, Decodable)]
+pub struct PendingHtlcKey {
+    pub incoming_chan_id: u64,
+    pub htlc_id: u64,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+struct PendingHtlcKeyPrefix;
+
+/// An intercepted LNv1 HTLC that is held while the federation it is destined
+/// for is unreachable, so contract creation can be retried later instead of
+/// failing the payment immediately.
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct PendingHtlc {
+    pub payment_hash: sha256::Hash,
+    pub amount_msat: u64,
+    /// Absolute block height at which the HTLC expires
+    pub expiry: u32,
+    /// Short channel id identifying the federation the HTLC is destined for
+    pub short_channel_id: u64,
+    /// Number of failed attempts to create the incoming contract
+    pub attempts: u32,
+}
+
+impl PendingHtlc {
+    /// Whether the HTLC is within `safety_margin` blocks of its expiry at
+    /// `block_height` and has to be failed back instead of being held longer
+    pub fn is_about_to_expire(&self, block_height: u32, safety_margin: u32) -> bool {
+        self.expiry <= block_height.saturating_add(safety_margin)
+    }
+}
+
+impl_db_record!(
+    key = PendingHtlcKey,
+    value = PendingHtlc,
+    db_prefix = DbKeyPrefix::PendingHtlc,
+);
+impl_db_lookup!(key = PendingHtlcKey, query_prefix = PendingHtlcKeyPrefix);
+
+/// Lightning node an outgoing payment was dispatched to, keyed by the payment
+/// hash. Only written if the gateway is configured with a standby node.
+#[derive(Debug, Encodable, Decodable)]
+pub struct OutgoingPaymentBackendKey(pub sha256::Hash);
+
+#[derive(Debug, Encodable, Decodable)]
+struct OutgoingPaymentBackendKeyPrefix;
+
+impl_db_record!(
+    key = OutgoingPaymentBackendKey,
+    value = LightningBackend,
+    db_prefix = DbKeyPrefix::OutgoingPaymentBackend,
+);
+impl_db_lookup!(
+    key = OutgoingPaymentBackendKey,
+    query_prefix = OutgoingPaymentBackendKeyPrefix
+);
+
+/// Outcome of an outgoing LNv1 payment, keyed by the payment hash. Makes the
+/// pay endpoint idempotent: a retried request returns the recorded outcome
+/// instead of paying the invoice a second time.
+#[derive(Debug, Encodable, Decodable)]
+pub struct OutgoingPaymentOutcomeKey(pub sha256::Hash);
+
+#[derive(Debug, Encodable, Decodable)]
+struct OutgoingPaymentOutcomeKeyPrefix;
+
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum OutgoingPaymentOutcome {
+    /// The payment was started for the given contract and has no outcome yet
+    Pending {
+        contract_id: ContractId,
+        started_at: SystemTime,
+    },
+    Succeeded {
+        contract_id: ContractId,
+        preimage: Preimage,
+    },
+    Failed {
+        contract_id: ContractId,
+        error_message: String,
+    },
+}
+
+/// How long a `Pending` outcome without a pay operation blocks the invoice
+/// from being paid with another contract. Such a record is left behind if the
+/// gateway stopped between recording the payment and starting it.
+pub const PENDING_OUTGOING_PAYMENT_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+/// What to do with a request to pay an invoice given its recorded outcome
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OutgoingPaymentAction {
+    /// Record the payment as pending and pay the invoice
+    Pay,
+    /// The invoice was already paid, return the preimage
+    Paid(Preimage),
+    /// The invoice must not be paid for this request
+    Reject(String),
+}
+
+impl OutgoingPaymentOutcome {
+    /// Decides how to handle a request to pay the invoice for `contract_id`.
+    /// `pending_operation_exists` tells whether the client has a pay operation
+    /// for the contract of a `Pending` outcome.
+    pub fn next_action(
+        &self,
+        contract_id: ContractId,
+        pending_operation_exists: bool,
+        now: SystemTime,
+    ) -> OutgoingPaymentAction {
+        match self {
+            Self::Succeeded { preimage, .. } => OutgoingPaymentAction::Paid(preimage.clone()),
+            Self::Failed {
+                contract_id: failed_contract_id,
+                error_message,
+            } if *failed_contract_id == contract_id => {
+                OutgoingPaymentAction::Reject(error_message.clone())
+            }
+            Self::Failed { .. } => OutgoingPaymentAction::Pay,
+            // A retry for the same contract resumes the existing pay operation
+            Self::Pending {
+                contract_id: pending_contract_id,
+                ..
+            } if *pending_contract_id == contract_id => OutgoingPaymentAction::Pay,
+            Self::Pending {
+                contract_id: pending_contract_id,
+                started_at,
+            } => {
+                let expired = now
+                    .duration_since(*started_at)
+                    .is_ok_and(|age| PENDING_OUTGOING_PAYMENT_EXPIRY <= age);
+                if pending_operation_exists || !expired {
+                    OutgoingPaymentAction::Reject(format!(
+                        "Invoice is already being paid with contract id {pending_contract_id}"
+                    ))
+                } else {
+                    OutgoingPaymentAction::Pay
+                }
+            }
+        }
+    }
+}
+
+impl_db_record!(
+    key = OutgoingPaymentOutcomeKey,
+    value = OutgoingPaymentOutcome,
+    db_prefix = DbKeyPrefix::OutgoingPaymentOutcome,
+);
+impl_db_lookup!(
+    key = OutgoingPaymentOutcomeKey,
+    query_prefix = OutgoingPaymentOutcomeKeyPrefix
+);
+
+/// Resolution the operator forced for the outgoing contract of a stuck
+/// payment, keyed by the payment's operation id. The payment's state machine
+/// waits for it, and the record is kept to audit the intervention.
+#[derive(Debug, Encodable, Decodable)]
+pub struct ForcedContractResolutionKey(pub OperationId);
+
+#[derive(Debug, Encodable, Decodable)]
+struct ForcedContractResolutionKeyPrefix;
+
+impl_db_record!(
+    key = ForcedContractResolutionKey,
+    value = ContractResolution,
+    db_prefix = DbKeyPrefix::ForcedContractResolution,
+    notify_on_modify = true,
+);
+impl_db_lookup!(
+    key = ForcedContractResolutionKey,
+    query_prefix = ForcedContractResolutionKeyPrefix
+);
+
 #[cfg(test)]
 mod fedimint_migration_tests {
     use std::str::FromStr;
@@ -585,6 +1020,26 @@ async fn create_gatewayd_db_data(db: Database) {
         dbtx.insert_new_entry(&preimage_auth, &verification_hash)
             .await;
 
+        let payment_hash = sha256::Hash::from_slice(&BYTE_32).expect("Hash should not fail");
+        let outgoing_payment_outcome = OutgoingPaymentOutcome::Succeeded {
+            contract_id: ContractId::from_raw_hash(payment_hash),
+            preimage: Preimage(BYTE_32),
+        };
+        dbtx.insert_new_entry(
+            &OutgoingPaymentOutcomeKey(payment_hash),
+            &outgoing_payment_outcome,
+        )
+        .await;
+
+        let forced_contract_resolution = ContractResolution::Claim {
+            preimage: Preimage(BYTE_32),
+        };
+        dbtx.insert_new_entry(
+            &ForcedContractResolutionKey(OperationId(BYTE_32)),
+            &forced_contract_resolution,
+        )
+        .await;
+
         dbtx.commit_tx().await;
     }
 
@@ -635,6 +1090,18 @@ async fn test_server_db_migrations() -> anyhow::Result<()> {
                             ensure!(num_auths > 0, "validate_migrations was not able to read any PreimageAuthentication");
                             info!("Validated PreimageAuthentication");
                         }
+                        // Snapshots taken before these records existed don't contain any, so
+                        // only check that the ones present decode
+                        DbKeyPrefix::OutgoingPaymentOutcome => {
+                            let outcomes = dbtx.find_by_prefix(&OutgoingPaymentOutcomeKeyPrefix).await.collect::<Vec<_>>().await;
+                            let num_outcomes = outcomes.len();
+                            info!(num_outcomes, "Validated OutgoingPaymentOutcome");
+                        }
+                        DbKeyPrefix::ForcedContractResolution => {
+                            let resolutions = dbtx.find_by_prefix(&ForcedContractResolutionKeyPrefix).await.collect::<Vec<_>>().await;
+                            let num_resolutions = resolutions.len();
+                            info!(num_resolutions, "Validated ForcedContractResolution");
+                        }
                         _ => {}
                     }
                 }
@@ -647,3 +1114,320 @@ async fn test_server_db_migrations() -> anyhow::Result<()> {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use std::str::FromStr;
+
+    use bitcoin::hashes::{sha256, Hash};
+    use fedimint_api_client::api::net::Connector;
+    use fedimint_core::config::FederationId;
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{
+        apply_migrations, Database, DatabaseKeyPrefix, DatabaseVersion, DatabaseVersionKey,
+        IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped, MODULE_GLOBAL_PREFIX,
+    };
+    use fedimint_core::encoding::Encodable;
+    use fedimint_core::invite_code::InviteCode;
+    use fedimint_core::module::registry::ModuleDecoderRegistry;
+    use fedimint_core::util::SafeUrl;
+    use fedimint_core::Amount;
+    use fedimint_ln_common::contracts::{ContractId, Preimage};
+    use fedimint_lnv2_common::gateway_api::PaymentFee;
+
+    use super::{
+        get_gatewayd_database_migrations, FederationConfig, FederationConfigV2, FederationIdKey,
+        ForcedContractResolutionKey, GatewayDbtxNcExt, OutgoingPaymentAction,
+        OutgoingPaymentOutcome, PaymentLimits, PendingHtlc, PendingHtlcKey,
+        PENDING_OUTGOING_PAYMENT_EXPIRY,
+    };
+    use crate::error::PaymentLimitError;
+    use crate::rpc::ContractResolution;
+
+    #[test]
+    fn payment_limits_bound_htlcs_and_payments() {
+        let limits = PaymentLimits {
+            htlc_minimum: Some(Amount::from_sats(10)),
+            htlc_maximum: Some(Amount::from_sats(1_000)),
+            payment_maximum: Some(Amount::from_sats(500)),
+        };
+
+        assert_eq!(limits.check_incoming_htlc(Amount::from_sats(10)), Ok(()));
+        assert_eq!(limits.check_incoming_htlc(Amount::from_sats(1_000)), Ok(()));
+        assert_eq!(
+            limits.check_incoming_htlc(Amount::from_sats(9)),
+            Err(PaymentLimitError::HtlcBelowMinimum {
+                amount: Amount::from_sats(9),
+                minimum: Amount::from_sats(10),
+            })
+        );
+        assert_eq!(
+            limits.check_incoming_htlc(Amount::from_sats(1_001)),
+            Err(PaymentLimitError::HtlcAboveMaximum {
+                amount: Amount::from_sats(1_001),
+                maximum: Amount::from_sats(1_000),
+            })
+        );
+
+        assert_eq!(
+            limits.check_outgoing_payment(Amount::from_sats(500)),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check_outgoing_payment(Amount::from_sats(501)),
+            Err(PaymentLimitError::PaymentAboveMaximum {
+                amount: Amount::from_sats(501),
+                maximum: Amount::from_sats(500),
+            })
+        );
+    }
+
+    #[test]
+    fn default_payment_limits_accept_everything() {
+        let limits = PaymentLimits::default();
+
+        assert_eq!(limits.check_incoming_htlc(Amount::ZERO), Ok(()));
+        assert_eq!(
+            limits.check_incoming_htlc(Amount::from_sats(u64::MAX / 1000)),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check_outgoing_payment(Amount::from_sats(u64::MAX / 1000)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn outgoing_payment_outcome_deduplicates_payments() {
+        let contract_id = ContractId::from_raw_hash(sha256::Hash::hash(b"contract"));
+        let other_contract_id = ContractId::from_raw_hash(sha256::Hash::hash(b"other"));
+        let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let fresh = started_at + Duration::from_secs(1);
+        let expired = started_at + PENDING_OUTGOING_PAYMENT_EXPIRY;
+
+        let succeeded = OutgoingPaymentOutcome::Succeeded {
+            contract_id: other_contract_id,
+            preimage: Preimage([1; 32]),
+        };
+        assert_eq!(
+            succeeded.next_action(contract_id, false, fresh),
+            OutgoingPaymentAction::Paid(Preimage([1; 32]))
+        );
+
+        let failed = OutgoingPaymentOutcome::Failed {
+            contract_id,
+            error_message: "no route".to_owned(),
+        };
+        assert_eq!(
+            failed.next_action(contract_id, false, fresh),
+            OutgoingPaymentAction::Reject("no route".to_owned())
+        );
+        assert_eq!(
+            failed.next_action(other_contract_id, false, fresh),
+            OutgoingPaymentAction::Pay
+        );
+
+        let pending = OutgoingPaymentOutcome::Pending {
+            contract_id: other_contract_id,
+            started_at,
+        };
+        assert_eq!(
+            pending.next_action(other_contract_id, true, expired),
+            OutgoingPaymentAction::Pay
+        );
+        assert!(matches!(
+            pending.next_action(contract_id, true, expired),
+            OutgoingPaymentAction::Reject(_)
+        ));
+        assert!(matches!(
+            pending.next_action(contract_id, false, fresh),
+            OutgoingPaymentAction::Reject(_)
+        ));
+        assert_eq!(
+            pending.next_action(contract_id, false, expired),
+            OutgoingPaymentAction::Pay
+        );
+    }
+
+    #[test]
+    fn pending_htlcs_expire_within_safety_margin() {
+        let pending_htlc = PendingHtlc {
+            payment_hash: sha256::Hash::hash(b"htlc"),
+            amount_msat: 1_000,
+            expiry: 1_000,
+            short_channel_id: 1,
+            attempts: 1,
+        };
+
+        assert!(!pending_htlc.is_about_to_expire(981, 18));
+        assert!(pending_htlc.is_about_to_expire(982, 18));
+        assert!(pending_htlc.is_about_to_expire(1_000, 18));
+        assert!(pending_htlc.is_about_to_expire(u32::MAX, 18));
+    }
+
+    #[tokio::test]
+    async fn pending_htlcs_are_kept_until_removed() {
+        let db = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+        let key = PendingHtlcKey {
+            incoming_chan_id: 1,
+            htlc_id: 2,
+        };
+        let other_key = PendingHtlcKey {
+            incoming_chan_id: 1,
+            htlc_id: 3,
+        };
+        let mut pending_htlc = PendingHtlc {
+            payment_hash: sha256::Hash::hash(b"htlc"),
+            amount_msat: 1_000,
+            expiry: 1_000,
+            short_channel_id: 1,
+            attempts: 1,
+        };
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.save_pending_htlc(key, &pending_htlc).await;
+        dbtx.save_pending_htlc(other_key, &pending_htlc).await;
+        dbtx.commit_tx().await;
+
+        // A failed retry updates the queued HTLC instead of adding another one
+        pending_htlc.attempts += 1;
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.save_pending_htlc(key, &pending_htlc).await;
+        dbtx.commit_tx().await;
+
+        let pending_htlcs = db.begin_transaction_nc().await.load_pending_htlcs().await;
+        assert_eq!(pending_htlcs.len(), 2);
+        assert_eq!(pending_htlcs.get(&key), Some(&pending_htlc));
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.remove_pending_htlc(&key).await;
+        dbtx.commit_tx().await;
+
+        let pending_htlcs = db.begin_transaction_nc().await.load_pending_htlcs().await;
+        assert_eq!(pending_htlcs.keys().collect::<Vec<_>>(), vec![&other_key]);
+    }
+
+    #[tokio::test]
+    async fn forced_contract_resolution_is_only_recorded_once() {
+        let db = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+        let operation_id = OperationId([1; 32]);
+        let claim = ContractResolution::Claim {
+            preimage: Preimage([2; 32]),
+        };
+
+        let waiting = tokio::spawn({
+            let db = db.clone();
+            async move {
+                db.wait_key_exists(&ForcedContractResolutionKey(operation_id))
+                    .await
+            }
+        });
+
+        let mut dbtx = db.begin_transaction().await;
+        assert!(
+            dbtx.save_forced_contract_resolution(operation_id, &claim)
+                .await
+        );
+        dbtx.commit_tx().await;
+
+        assert_eq!(waiting.await.unwrap(), claim);
+
+        let mut dbtx = db.begin_transaction().await;
+        assert!(
+            !dbtx
+                .save_forced_contract_resolution(operation_id, &ContractResolution::Refund)
+                .await
+        );
+        assert_eq!(
+            dbtx.get_value(&ForcedContractResolutionKey(operation_id))
+                .await,
+            Some(claim)
+        );
+    }
+
+    #[tokio::test]
+    async fn migrate_to_v5_only_converts_configs_without_payment_limits() {
+        let db = Database::new(MemDatabase::new(), ModuleDecoderRegistry::default());
+        let invite_code = |federation_id| {
+            InviteCode::new(
+                SafeUrl::from_str("http://myexamplefed.com").expect("SafeUrl parsing can't fail"),
+                0.into(),
+                federation_id,
+                None,
+            )
+        };
+        let old_federation_id = FederationId::dummy();
+        let new_federation_id = FederationId(sha256::Hash::hash(b"federation"));
+
+        // Written by a gateway that didn't know about payment limits yet
+        let old_config = FederationConfigV2 {
+            invite_code: invite_code(old_federation_id),
+            federation_index: 1,
+            lightning_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
+            transaction_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
+            connector: Connector::default(),
+        };
+        // Written by `migrate_to_v4` of a gateway that already does
+        let new_config = FederationConfig {
+            invite_code: invite_code(new_federation_id),
+            federation_index: 2,
+            lightning_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
+            transaction_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
+            connector: Connector::default(),
+            payment_limits: PaymentLimits {
+                htlc_minimum: None,
+                htlc_maximum: None,
+                payment_maximum: Some(Amount::from_sats(1_000)),
+            },
+        };
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.insert_new_entry(
+            &DatabaseVersionKey(MODULE_GLOBAL_PREFIX.into()),
+            &DatabaseVersion(4),
+        )
+        .await;
+        dbtx.raw_insert_bytes(
+            &DatabaseKeyPrefix::to_bytes(&FederationIdKey {
+                id: old_federation_id,
+            }),
+            &old_config.consensus_encode_to_vec(),
+        )
+        .await
+        .unwrap();
+        dbtx.insert_new_entry(
+            &FederationIdKey {
+                id: new_federation_id,
+            },
+            &new_config,
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        apply_migrations(
+            &db,
+            "gatewayd".to_string(),
+            get_gatewayd_database_migrations(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let configs = db
+            .begin_transaction_nc()
+            .await
+            .load_federation_configs()
+            .await;
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[&new_federation_id], new_config);
+        assert_eq!(configs[&old_federation_id].federation_index, 1);
+        assert_eq!(
+            configs[&old_federation_id].payment_limits,
+            PaymentLimits::default()
+        );
+    }
+}
@@ -32,6 +32,19 @@
 /// Necessary for LND configuration.
 pub const FM_LND_MACAROON_ENV: &str = "FM_LND_MACAROON";
 
+/// Environment variable that specifies the URL to connect to a standby LND
+/// node that takes over outgoing payments while the primary one is
+/// unreachable. Optional.
+pub const FM_LND_STANDBY_RPC_ADDR_ENV: &str = "FM_LND_STANDBY_RPC_ADDR";
+
+/// Environment variable that specifies the location of the standby LND's TLS
+/// certificate. Required if a standby LND node is configured.
+pub const FM_LND_STANDBY_TLS_CERT_ENV: &str = "FM_LND_STANDBY_TLS_CERT";
+
+/// Environment variable that specifies the location of the standby LND's
+/// macaroon. Required if a standby LND node is configured.
+pub const FM_LND_STANDBY_MACAROON_ENV: &str = "FM_LND_STANDBY_MACAROON";
+
 /// Environment variable that specifies the URL of an Esplora server.
 /// Necessary for LDK configuration if using esplora as the backend.
 pub const FM_LDK_ESPLORA_SERVER_URL: &str = "FM_LDK_ESPLORA_SERVER_URL";
@@ -61,6 +74,20 @@
 /// LNv1 and LNv2 invoices.
 pub const FM_GATEWAY_LIGHTNING_MODULE_MODE_ENV: &str = "FM_GATEWAY_LIGHTNING_MODULE_MODE";
 
+/// Environment variable that specifies a comma separated list of federation ids
+/// the gateway is allowed to connect to. If not set, all federations are
+/// allowed unless they are on the denylist.
+pub const FM_GATEWAY_FEDERATION_ALLOWLIST_ENV: &str = "FM_GATEWAY_FEDERATION_ALLOWLIST";
+
+/// Environment variable that specifies a comma separated list of federation ids
+/// the gateway refuses to connect to, even if they are on the allowlist.
+pub const FM_GATEWAY_FEDERATION_DENYLIST_ENV: &str = "FM_GATEWAY_FEDERATION_DENYLIST";
+
+/// Environment variable that specifies the maximum number of attempts to
+/// connect the gateway to a new federation per hour. If not set, attempts are
+/// not rate limited.
+pub const FM_GATEWAY_MAX_REGISTRATIONS_PER_HOUR_ENV: &str = "FM_GATEWAY_MAX_REGISTRATIONS_PER_HOUR";
+
 /// Environment variable that instructs the gateway to run in "debug mode",
 /// which allows errors to return to clients without redacting private
 /// information.
@@ -4,6 +4,7 @@
 use fedimint_core::config::{FederationId, FederationIdPrefix};
 use fedimint_core::envs::is_env_var_set;
 use fedimint_core::fmt_utils::OptStacktrace;
+use fedimint_core::Amount;
 use reqwest::StatusCode;
 use thiserror::Error;
 use tracing::error;
@@ -27,6 +28,8 @@ pub enum PublicGatewayError {
     FederationNotConnected(#[from] FederationNotConnected),
     #[error("Failed to receive ecash: {failure_reason}")]
     ReceiveEcashError { failure_reason: String },
+    #[error("{}", .0)]
+    PaymentLimit(#[from] PaymentLimitError),
 }
 
 impl IntoResponse for PublicGatewayError {
@@ -39,6 +42,7 @@ fn into_response(self) -> Response {
             PublicGatewayError::FederationNotConnected(e) => {
                 (e.to_string(), StatusCode::BAD_REQUEST)
             }
+            PublicGatewayError::PaymentLimit(e) => (e.to_string(), StatusCode::BAD_REQUEST),
             PublicGatewayError::ReceiveEcashError { .. } => (
                 "Failed to receive ecash".to_string(),
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -90,6 +94,12 @@ pub enum AdminGatewayError {
     Lightning(#[from] LightningRpcError),
     #[error("Error registering federation {federation_id}")]
     RegistrationError { federation_id: FederationId },
+    #[error("Federation {federation_id} is not allowed by the gateway's registration policy")]
+    FederationNotAllowed { federation_id: FederationId },
+    #[error(
+        "Too many federation registration attempts, at most {max_registrations_per_hour} per hour are allowed"
+    )]
+    RegistrationRateLimited { max_registrations_per_hour: u32 },
     #[error("Error withdrawing funds onchain: {failure_reason}")]
     WithdrawError { failure_reason: String },
 }
@@ -112,6 +122,8 @@ fn into_response(self) -> Response {
 pub enum LNv1Error {
     #[error("Incoming payment error: {}", OptStacktrace(.0))]
     IncomingPayment(String),
+    #[error("Federation unreachable: {}", OptStacktrace(.0))]
+    FederationUnreachable(String),
     #[error(
         "Outgoing Contract Error Reason: {message} Stack: {}",
         OptStacktrace(error)
@@ -134,6 +146,18 @@ pub enum LNv2Error {
     OutgoingPayment(#[from] anyhow::Error),
 }
 
+/// Public error that indicates a payment was rejected because its amount is
+/// outside of the [`crate::db::PaymentLimits`] configured by the operator.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum PaymentLimitError {
+    #[error("HTLC amount {amount} is below the minimum of {minimum}")]
+    HtlcBelowMinimum { amount: Amount, minimum: Amount },
+    #[error("HTLC amount {amount} is above the maximum of {maximum}")]
+    HtlcAboveMaximum { amount: Amount, maximum: Amount },
+    #[error("Payment amount {amount} is above the maximum of {maximum}")]
+    PaymentAboveMaximum { amount: Amount, maximum: Amount },
+}
+
 /// Public error that indicates the requested federation is not connected to
 /// this gateway.
 #[derive(Debug, Error)]
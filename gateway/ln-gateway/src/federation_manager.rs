@@ -12,7 +12,7 @@
 use crate::db::GatewayDbtxNcExt;
 use crate::error::{AdminGatewayError, FederationNotConnected};
 use crate::gateway_module_v2::GatewayClientModuleV2;
-use crate::rpc::FederationInfo;
+use crate::rpc::{FederationInfo, PendingContract};
 use crate::state_machine::GatewayClientModule;
 use crate::AdminResult;
 
@@ -122,6 +122,27 @@ pub async fn wait_for_incoming_payments(&self) -> AdminResult<()> {
         Ok(())
     }
 
+    /// Returns the pending LNv1 and LNv2 contracts of every connected
+    /// federation
+    pub async fn pending_contracts(
+        &self,
+    ) -> AdminResult<BTreeMap<FederationId, Vec<PendingContract>>> {
+        let mut pending_contracts = BTreeMap::new();
+        for (federation_id, client) in &self.clients {
+            let lnv1 = client.value().get_first_module::<GatewayClientModule>()?;
+            let mut contracts = lnv1.list_pending_contracts().await;
+
+            if let Ok(lnv2) = client.value().get_first_module::<GatewayClientModuleV2>() {
+                contracts.extend(lnv2.list_pending_contracts().await);
+                contracts.sort_by_key(|contract| std::cmp::Reverse(contract.pending_secs));
+            }
+
+            pending_contracts.insert(*federation_id, contracts);
+        }
+
+        Ok(pending_contracts)
+    }
+
     async fn unannounce_from_federation(
         &self,
         federation_id: FederationId,
@@ -8,12 +8,12 @@
 use fedimint_core::task::sleep;
 use fedimint_ln_common::contracts::Preimage;
 use fedimint_lnv2_common::contracts::PaymentImage;
-use tracing::warn;
+use tracing::{instrument, warn};
 
 use super::events::CompleteLightningPaymentSucceeded;
 use super::FinalReceiveState;
 use crate::gateway_module_v2::GatewayClientContextV2;
-use crate::lightning::{InterceptPaymentResponse, PaymentAction};
+use crate::lightning::{InterceptPaymentResponse, PaymentAction, PaymentTraceId};
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// State machine that completes the incoming payment by contacting the
@@ -134,6 +134,7 @@ fn transition_receive(
         old_state.update(CompleteSMState::Completing(final_receive_state))
     }
 
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(&payment_hash)))]
     async fn await_completion(
         context: GatewayClientContextV2,
         payment_hash: bitcoin::hashes::sha256::Hash,
@@ -9,7 +9,7 @@
 use std::sync::Arc;
 
 use anyhow::{anyhow, ensure};
-use bitcoin::hashes::sha256;
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1::Message;
 use events::{IncomingPaymentStarted, OutgoingPaymentStarted};
 use fedimint_api_client::api::DynModuleApi;
@@ -46,12 +46,14 @@
 use tpe::{AggregatePublicKey, PublicKeyShare};
 use tracing::{info, warn};
 
+use crate::db::GatewayDbtxNcExt;
 use crate::gateway_module_v2::api::GatewayFederationApi;
 use crate::gateway_module_v2::complete_sm::{
     CompleteSMCommon, CompleteSMState, CompleteStateMachine,
 };
 use crate::gateway_module_v2::receive_sm::ReceiveSMCommon;
 use crate::gateway_module_v2::send_sm::SendSMCommon;
+use crate::rpc::{ContractDirection, ContractResolution, PendingContract};
 use crate::{Gateway, EXPIRATION_DELTA_MINIMUM_V2};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -600,4 +602,80 @@ pub async fn await_completion(&self, operation_id: OperationId) {
             }
         }
     }
+
+    /// Returns the contracts whose operations still have active state
+    /// machines, i.e. that haven't reached a terminal resolution yet
+    pub async fn list_pending_contracts(&self) -> Vec<PendingContract> {
+        let states = self
+            .client_ctx
+            .get_own_active_states()
+            .await
+            .into_iter()
+            .map(|(state, meta)| {
+                let direction = match state {
+                    GatewayClientStateMachinesV2::Send(_) => ContractDirection::Outgoing,
+                    GatewayClientStateMachinesV2::Receive(_)
+                    | GatewayClientStateMachinesV2::Complete(_) => ContractDirection::Incoming,
+                };
+                (
+                    state.operation_id(),
+                    direction,
+                    state.to_string(),
+                    meta.created_at,
+                )
+            });
+
+        PendingContract::from_active_states(&fedimint_lnv2_common::KIND, states, now())
+    }
+
+    /// Resolves the outgoing contract of a stuck payment by either claiming it
+    /// with a preimage the operator obtained out of band or by refunding it to
+    /// the user. The resolution is recorded in the gateway's database, where
+    /// the payment's state machine picks it up instead of continuing the
+    /// payment. The pending lightning payment attempt is not aborted, so it is
+    /// up to the operator to make sure that it can't succeed anymore before
+    /// refunding.
+    pub async fn gateway_force_resolve_outgoing_contract(
+        &self,
+        operation_id: OperationId,
+        resolution: ContractResolution,
+    ) -> anyhow::Result<()> {
+        let send = self
+            .client_ctx
+            .get_own_active_states()
+            .await
+            .into_iter()
+            .find_map(|(state, _)| match state {
+                GatewayClientStateMachinesV2::Send(send)
+                    if send.common.operation_id == operation_id =>
+                {
+                    Some(send)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("No pending outgoing contract for operation {operation_id}"))?;
+
+        ensure!(
+            send.state == SendSMState::Sending,
+            "The outgoing contract is already being resolved"
+        );
+
+        if let ContractResolution::Claim { preimage } = &resolution {
+            ensure!(
+                PaymentImage::Hash(sha256::Hash::hash(&preimage.0))
+                    == send.common.contract.payment_image,
+                "Preimage does not match the payment image of the outgoing contract"
+            );
+        }
+
+        let mut dbtx = self.gateway.gateway_db.begin_transaction().await;
+        ensure!(
+            dbtx.save_forced_contract_resolution(operation_id, &resolution)
+                .await,
+            "The outgoing contract is already being resolved"
+        );
+        dbtx.commit_tx_result().await?;
+
+        Ok(())
+    }
 }
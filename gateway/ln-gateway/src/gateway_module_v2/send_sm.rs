@@ -11,10 +11,14 @@
 use fedimint_lnv2_common::contracts::{OutgoingContract, PaymentImage};
 use fedimint_lnv2_common::{LightningInput, LightningInputV0, LightningInvoice, OutgoingWitness};
 use serde::{Deserialize, Serialize};
+use tracing::{info, Instrument};
 
 use super::events::{OutgoingPaymentFailed, OutgoingPaymentSucceeded};
 use super::FinalReceiveState;
+use crate::db::ForcedContractResolutionKey;
 use crate::gateway_module_v2::{GatewayClientContextV2, GatewayClientModuleV2};
+use crate::lightning::PaymentTraceId;
+use crate::rpc::ContractResolution;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
 pub struct SendStateMachine {
@@ -91,6 +95,7 @@ pub enum Cancelled {
     Refunded,
     Failure,
     LightningRpcError(String),
+    OperatorRefunded,
 }
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
@@ -102,6 +107,8 @@ pub enum Cancelled {
 ///
 ///     Sending -- payment is successful --> Claiming
 ///     Sending -- payment fails --> Cancelled
+///     Sending -- operator forced claim --> Claiming
+///     Sending -- operator forced refund --> Cancelled
 /// ```
 impl State for SendStateMachine {
     type ModuleContext = GatewayClientContextV2;
@@ -113,27 +120,43 @@ fn transitions(
     ) -> Vec<StateTransition<Self>> {
         let gc = global_context.clone();
         let gateway_context = context.clone();
+        let forced_gc = global_context.clone();
+        let forced_gateway_context = context.clone();
 
         match &self.state {
             SendSMState::Sending => {
-                vec![StateTransition::new(
-                    Self::send_payment(
-                        context.clone(),
-                        self.common.max_delay,
-                        self.common.min_contract_amount,
-                        self.common.invoice.clone(),
-                        self.common.contract.clone(),
+                vec![
+                    StateTransition::new(
+                        Self::send_payment(
+                            context.clone(),
+                            self.common.max_delay,
+                            self.common.min_contract_amount,
+                            self.common.invoice.clone(),
+                            self.common.contract.clone(),
+                        ),
+                        move |dbtx, result, old_state| {
+                            Box::pin(Self::transition_send_payment(
+                                dbtx,
+                                old_state,
+                                gc.clone(),
+                                result,
+                                gateway_context.clone(),
+                            ))
+                        },
+                    ),
+                    StateTransition::new(
+                        Self::await_forced_resolution(context.clone(), self.common.operation_id),
+                        move |dbtx, result, old_state| {
+                            Box::pin(Self::transition_send_payment(
+                                dbtx,
+                                old_state,
+                                forced_gc.clone(),
+                                result,
+                                forced_gateway_context.clone(),
+                            ))
+                        },
                     ),
-                    move |dbtx, result, old_state| {
-                        Box::pin(Self::transition_send_payment(
-                            dbtx,
-                            old_state,
-                            gc.clone(),
-                            result,
-                            gateway_context.clone(),
-                        ))
-                    },
-                )]
+                ]
             }
             SendSMState::Claiming(..) | SendSMState::Cancelled(..) => {
                 vec![]
@@ -147,6 +170,32 @@ fn operation_id(&self) -> OperationId {
 }
 
 impl SendStateMachine {
+    /// Waits until the operator forces a resolution of the outgoing contract,
+    /// see [`GatewayClientModuleV2::gateway_force_resolve_outgoing_contract`]
+    async fn await_forced_resolution(
+        context: GatewayClientContextV2,
+        operation_id: OperationId,
+    ) -> Result<PaymentResponse, Cancelled> {
+        let resolution = context
+            .gateway
+            .gateway_db
+            .wait_key_exists(&ForcedContractResolutionKey(operation_id))
+            .await;
+
+        info!(
+            ?resolution,
+            "Operator forced resolution of outgoing contract"
+        );
+
+        match resolution {
+            ContractResolution::Claim { preimage } => Ok(PaymentResponse {
+                preimage: preimage.0,
+                target_federation: None,
+            }),
+            ContractResolution::Refund => Err(Cancelled::OperatorRefunded),
+        }
+    }
+
     async fn send_payment(
         context: GatewayClientContextV2,
         max_delay: u64,
@@ -213,9 +262,11 @@ async fn send_payment(
             };
         }
 
+        let trace_id = PaymentTraceId::new(invoice.payment_hash());
         let preimage = lightning_context
             .lnrpc
             .pay(invoice, max_delay, max_fee)
+            .instrument(trace_id.span())
             .await
             .map(|response| response.preimage.0)
             .map_err(|e| Cancelled::LightningRpcError(e.to_string()))?;
@@ -21,6 +21,8 @@
 mod federation_manager;
 pub mod gateway_module_v2;
 pub mod lightning;
+mod metrics;
+pub mod registration_policy;
 pub mod rpc;
 pub mod state_machine;
 mod types;
@@ -28,13 +30,14 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fmt::Display;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context};
-use bitcoin::hashes::sha256;
+use anyhow::{anyhow, bail, Context};
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::{Address, Network, Txid};
 use clap::Parser;
 use client::GatewayClientBuilder;
@@ -48,10 +51,10 @@
 use fedimint_bip39::{Bip39RootSecretStrategy, Language, Mnemonic};
 use fedimint_client::module::init::ClientModuleInitRegistry;
 use fedimint_client::secret::RootSecretStrategy;
-use fedimint_client::{Client, ClientHandleArc};
+use fedimint_client::{Client, ClientHandle, ClientHandleArc};
 use fedimint_core::config::FederationId;
 use fedimint_core::core::{
-    ModuleInstanceId, ModuleKind, LEGACY_HARDCODED_INSTANCE_ID_MINT,
+    ModuleInstanceId, ModuleKind, OperationId, LEGACY_HARDCODED_INSTANCE_ID_MINT,
     LEGACY_HARDCODED_INSTANCE_ID_WALLET,
 };
 use fedimint_core::db::{apply_migrations_server, Database, DatabaseTransaction};
@@ -60,20 +63,22 @@
 use fedimint_core::secp256k1::schnorr::Signature;
 use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::task::{sleep, TaskGroup, TaskHandle, TaskShutdownToken};
-use fedimint_core::time::duration_since_epoch;
+use fedimint_core::time::{duration_since_epoch, now};
 use fedimint_core::util::{SafeUrl, Spanned};
 use fedimint_core::{
-    fedimint_build_code_version_env, get_network_for_address, Amount, BitcoinAmountOrAll,
+    fedimint_build_code_version_env, get_network_for_address, Amount, BitcoinAmountOrAll, Sats,
 };
 use fedimint_eventlog::{DBTransactionEventLogExt, EventLogId};
-use fedimint_ln_common::config::LightningClientConfig;
-use fedimint_ln_common::contracts::Preimage;
-use fedimint_ln_common::LightningCommonInit;
+use fedimint_ln_client::incoming::IncomingSmError;
+use fedimint_ln_client::{LightningClientModule, LnPayState, LnReceiveState, PayType};
+use fedimint_ln_common::config::{FeeToAmount, LightningClientConfig};
+use fedimint_ln_common::contracts::{ContractId, Preimage};
+use fedimint_ln_common::{LightningCommonInit, PrunedInvoice};
 use fedimint_lnv2_common::contracts::{IncomingContract, PaymentImage};
 use fedimint_lnv2_common::gateway_api::{
     CreateBolt11InvoicePayload, PaymentFee, RoutingInfo, SendPaymentPayload,
 };
-use fedimint_lnv2_common::Bolt11InvoiceDescription;
+use fedimint_lnv2_common::{Bolt11InvoiceDescription, LightningInvoice};
 use fedimint_mint_client::{
     MintClientInit, MintClientModule, MintCommonInit, SelectNotesWithAtleastAmount,
     SelectNotesWithExactAmount,
@@ -90,22 +95,32 @@
 use lightning_invoice::Bolt11Invoice;
 use rand::thread_rng;
 use rpc::{
-    CloseChannelsWithPeerPayload, CreateInvoiceForOperatorPayload, FederationInfo,
-    GatewayFedConfig, GatewayInfo, LeaveFedPayload, MnemonicResponse, OpenChannelPayload,
-    PayInvoiceForOperatorPayload, PaymentLogPayload, PaymentLogResponse, ReceiveEcashPayload,
-    ReceiveEcashResponse, SendOnchainPayload, SetFeesPayload, SpendEcashPayload,
-    SpendEcashResponse, WithdrawResponse, V1_API_ENDPOINT,
+    CloseChannelsWithPeerPayload, ContractResolution, CreateInvoiceForOperatorPayload,
+    FederationInfo, GatewayFedConfig, GatewayInfo, LeaveFedPayload, ListContractsPayload,
+    ListContractsResponse, MnemonicResponse, OpenChannelPayload, PayInvoiceForOperatorPayload,
+    PayOfferForOperatorPayload, PaymentLogPayload, PaymentLogResponse, ProbeLeg, ProbePayload,
+    ProbeResponse, ReceiveEcashPayload, ReceiveEcashResponse, ResolveContractPayload,
+    SendOnchainPayload, SetFeesPayload, SetPaymentLimitsPayload, SpendEcashPayload,
+    SpendEcashResponse, WithdrawResponse, STUCK_CONTRACT_AGE, V1_API_ENDPOINT,
 };
 use state_machine::{GatewayClientModule, GatewayExtPayStates};
 use tokio::sync::RwLock;
-use tracing::{debug, error, info, info_span, warn};
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
 use crate::config::LightningModuleMode;
-use crate::db::{get_gatewayd_database_migrations, FederationConfig};
+use crate::db::{
+    get_gatewayd_database_migrations, FederationConfig, OutgoingPaymentAction,
+    OutgoingPaymentOutcome, PaymentLimits, PendingHtlc, PendingHtlcKey,
+};
 use crate::envs::FM_GATEWAY_MNEMONIC_ENV;
 use crate::error::{AdminGatewayError, LNv1Error, LNv2Error, PublicGatewayError};
 use crate::gateway_module_v2::GatewayClientModuleV2;
-use crate::lightning::{GatewayLightningBuilder, LightningContext, LightningMode, RouteHtlcStream};
+use crate::lightning::{
+    check_offer_amount, GatewayLightningBuilder, LightningContext, LightningMode, PaymentTraceId,
+    RouteHtlcStream,
+};
+use crate::metrics::HTLC_RETRY_QUEUE_DEPTH;
+use crate::registration_policy::RegistrationPolicy;
 use crate::rpc::rpc_server::run_webserver;
 use crate::rpc::{
     BackupPayload, ConnectFedPayload, DepositAddressPayload, FederationBalanceInfo,
@@ -126,6 +141,66 @@
 /// LNv2 CLTV Delta in blocks
 const EXPIRATION_DELTA_MINIMUM_V2: u64 = 144;
 
+/// Number of blocks before an intercepted HTLC expires at which the gateway
+/// stops waiting for an unreachable federation and fails the HTLC back, so
+/// the upstream channel is never at risk of being force closed.
+const PENDING_HTLC_CLTV_SAFETY_MARGIN: u32 = 18;
+
+/// How long to wait between attempts to hand a held HTLC to its federation
+const PENDING_HTLC_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum CLTV delta of payments of the gateway's own funds, the LDK default
+const OPERATOR_PAYMENT_MAX_DELAY: u64 = 1008;
+
+/// Fee limit of payments of the gateway's own funds, the LDK defaults
+fn operator_payment_max_fee(amount_msats: u64) -> Amount {
+    const BASE_FEE: u64 = 50;
+    const FEE_DENOMINATOR: u64 = 100;
+
+    Amount::from_msats(BASE_FEE + amount_msats.saturating_div(FEE_DENOMINATOR))
+}
+
+/// How long each leg of a liquidity probe may take before it is reported as
+/// failed
+const PROBE_LEG_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// After how long ecash moved between a federation client and its probe
+/// client is reclaimed if it wasn't redeemed
+const PROBE_ECASH_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Marks the invoice of the lightning node a liquidity probe pays as such
+/// until it is dropped, see [`Gateway::is_probe_payment`]
+struct ProbePayment {
+    probe_payment_hashes: Arc<std::sync::Mutex<BTreeSet<sha256::Hash>>>,
+    payment_hash: sha256::Hash,
+}
+
+impl ProbePayment {
+    fn new(
+        probe_payment_hashes: Arc<std::sync::Mutex<BTreeSet<sha256::Hash>>>,
+        payment_hash: sha256::Hash,
+    ) -> Self {
+        probe_payment_hashes
+            .lock()
+            .expect("poisoned")
+            .insert(payment_hash);
+
+        Self {
+            probe_payment_hashes,
+            payment_hash,
+        }
+    }
+}
+
+impl Drop for ProbePayment {
+    fn drop(&mut self) {
+        self.probe_payment_hashes
+            .lock()
+            .expect("poisoned")
+            .remove(&self.payment_hash);
+    }
+}
+
 pub type Result<T> = std::result::Result<T, PublicGatewayError>;
 pub type AdminResult<T> = std::result::Result<T, AdminGatewayError>;
 
@@ -226,6 +301,18 @@ pub struct Gateway {
 
     /// The Bitcoin network that the Lightning network is configured to.
     network: Network,
+
+    /// Restricts which federations the gateway serves and how often new
+    /// federations can be connected.
+    registration_policy: Arc<RegistrationPolicy>,
+
+    /// Held HTLCs a retry task is currently running for, so HTLCs the
+    /// lightning node re-delivers after a reconnect are not handled twice.
+    held_htlcs: Arc<std::sync::Mutex<BTreeSet<PendingHtlcKey>>>,
+
+    /// Payment hashes of the invoices of running liquidity probes, the only
+    /// payments the lightning node routes back to itself.
+    probe_payment_hashes: Arc<std::sync::Mutex<BTreeSet<sha256::Hash>>>,
 }
 
 impl std::fmt::Debug for Gateway {
@@ -270,6 +357,7 @@ pub async fn new_with_custom_registry(
                 network,
                 num_route_hints,
                 lightning_module_mode,
+                registration_policy: RegistrationPolicy::default(),
             },
             gateway_db,
             client_builder,
@@ -367,6 +455,9 @@ async fn new(
             bcrypt_password_hash: Arc::new(gateway_parameters.bcrypt_password_hash),
             num_route_hints,
             network,
+            registration_policy: Arc::new(gateway_parameters.registration_policy),
+            held_htlcs: Arc::default(),
+            probe_payment_hashes: Arc::default(),
         })
     }
 
@@ -522,6 +613,8 @@ async fn route_lightning_payments<'a>(
             .await;
         info!("Gateway is running");
 
+        self.resume_held_lightning_payments().await;
+
         // Runs until the connection to the lightning node breaks or we receive the
         // shutdown signal.
         if handle
@@ -582,6 +675,7 @@ async fn is_shutting_down_safely(&self) {
     /// incoming payment to a federation, spawns a state machine and hands the
     /// payment off to it. Otherwise, forwards the payment to the next hop like
     /// a normal lightning node.
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(&payment_request.payment_hash)))]
     async fn handle_lightning_payment(
         &self,
         payment_request: InterceptPaymentRequest,
@@ -592,6 +686,11 @@ async fn handle_lightning_payment(
             PrettyInterceptPaymentRequest(&payment_request)
         );
 
+        if self.is_held_lightning_payment(&payment_request) {
+            debug!("Lightning payment is already held and retried, ignoring re-delivery");
+            return;
+        }
+
         if self
             .try_handle_lightning_payment_lnv2(&payment_request, lightning_context)
             .await
@@ -600,12 +699,23 @@ async fn handle_lightning_payment(
             return;
         }
 
-        if self
+        match self
             .try_handle_lightning_payment_ln_legacy(&payment_request)
             .await
-            .is_ok()
         {
-            return;
+            Ok(()) => return,
+            Err(PublicGatewayError::PaymentLimit(error)) => {
+                warn!("Rejecting intercepted lightning payment: {error}");
+                Self::cancel_lightning_payment(&payment_request, lightning_context).await;
+                return;
+            }
+            Err(PublicGatewayError::LNv1(LNv1Error::FederationUnreachable(error))) => {
+                warn!("Holding intercepted lightning payment until the federation is reachable: {error}");
+                self.hold_lightning_payment(payment_request, lightning_context)
+                    .await;
+                return;
+            }
+            Err(_) => {}
         }
 
         Self::forward_lightning_payment(payment_request, lightning_context).await;
@@ -630,6 +740,16 @@ async fn try_handle_lightning_payment_lnv2(
             )
             .await?;
 
+        if let Err(error) = self
+            .payment_limits(client.federation_id())
+            .await
+            .check_incoming_htlc(Amount::from_msats(htlc_request.amount_msat))
+        {
+            warn!("Rejecting intercepted lightning payment: {error}");
+            Self::cancel_lightning_payment(htlc_request, lightning_context).await;
+            return Ok(());
+        }
+
         if let Err(error) = client
             .get_first_module::<GatewayClientModuleV2>()
             .expect("Must have client module")
@@ -644,16 +764,7 @@ async fn try_handle_lightning_payment_lnv2(
         {
             error!("Error relaying incoming lightning payment: {error:?}");
 
-            let outcome = InterceptPaymentResponse {
-                action: PaymentAction::Cancel,
-                payment_hash: htlc_request.payment_hash,
-                incoming_chan_id: htlc_request.incoming_chan_id,
-                htlc_id: htlc_request.htlc_id,
-            };
-
-            if let Err(error) = lightning_context.lnrpc.complete_htlc(outcome).await {
-                error!("Error sending HTLC response to lightning node: {error:?}");
-            }
+            Self::cancel_lightning_payment(htlc_request, lightning_context).await;
         }
 
         Ok(())
@@ -684,6 +795,10 @@ async fn try_handle_lightning_payment_ln_legacy(
         client
             .borrow()
             .with(|client| async {
+                self.payment_limits(client.federation_id())
+                    .await
+                    .check_incoming_htlc(Amount::from_msats(htlc_request.amount_msat))?;
+
                 let htlc = htlc_request.clone().try_into();
                 if let Ok(htlc) = htlc {
                     match client
@@ -693,6 +808,16 @@ async fn try_handle_lightning_payment_ln_legacy(
                         .await
                     {
                         Ok(_) => Ok(()),
+                        Err(e)
+                            if matches!(
+                                e.downcast_ref::<IncomingSmError>(),
+                                Some(IncomingSmError::TimeoutFetchingOffer { .. })
+                            ) =>
+                        {
+                            Err(PublicGatewayError::LNv1(LNv1Error::FederationUnreachable(
+                                format!("Error intercepting lightning payment {e:?}"),
+                            )))
+                        }
                         Err(e) => Err(PublicGatewayError::LNv1(LNv1Error::IncomingPayment(
                             format!("Error intercepting lightning payment {e:?}"),
                         ))),
@@ -706,6 +831,201 @@ async fn try_handle_lightning_payment_ln_legacy(
             .await
     }
 
+    /// Puts an intercepted LNv1 payment whose federation is currently
+    /// unreachable into the persistent retry queue and spawns a task that
+    /// keeps trying to create the incoming contract. The HTLC is failed back
+    /// once it gets within `PENDING_HTLC_CLTV_SAFETY_MARGIN` blocks of its
+    /// expiry.
+    async fn hold_lightning_payment(
+        &self,
+        payment_request: InterceptPaymentRequest,
+        lightning_context: &LightningContext,
+    ) {
+        let Some(short_channel_id) = payment_request.short_channel_id else {
+            Self::cancel_lightning_payment(&payment_request, lightning_context).await;
+            return;
+        };
+
+        let key = PendingHtlcKey {
+            incoming_chan_id: payment_request.incoming_chan_id,
+            htlc_id: payment_request.htlc_id,
+        };
+        let pending_htlc = PendingHtlc {
+            payment_hash: payment_request.payment_hash,
+            amount_msat: payment_request.amount_msat,
+            expiry: payment_request.expiry,
+            short_channel_id,
+            attempts: 1,
+        };
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.save_pending_htlc(key, &pending_htlc).await;
+        dbtx.commit_tx().await;
+        self.update_htlc_retry_queue_depth().await;
+
+        self.spawn_held_lightning_payment_retry(key, pending_htlc);
+    }
+
+    /// Spawns retry tasks for the HTLCs in the retry queue that are not
+    /// retried yet, most notably the ones held before the gateway restarted.
+    /// Called whenever the connection to the lightning node is established,
+    /// before any re-delivered HTLC is handled.
+    async fn resume_held_lightning_payments(&self) {
+        let pending_htlcs = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_pending_htlcs()
+            .await;
+
+        for (key, pending_htlc) in pending_htlcs {
+            if self.held_htlcs.lock().expect("poisoned").contains(&key) {
+                continue;
+            }
+
+            info!(
+                payment_hash = %pending_htlc.payment_hash,
+                attempts = pending_htlc.attempts,
+                "Resuming held HTLC"
+            );
+            self.spawn_held_lightning_payment_retry(key, pending_htlc);
+        }
+
+        self.update_htlc_retry_queue_depth().await;
+    }
+
+    fn is_held_lightning_payment(&self, payment_request: &InterceptPaymentRequest) -> bool {
+        self.held_htlcs
+            .lock()
+            .expect("poisoned")
+            .contains(&PendingHtlcKey {
+                incoming_chan_id: payment_request.incoming_chan_id,
+                htlc_id: payment_request.htlc_id,
+            })
+    }
+
+    /// Spawns the retry task for a held HTLC unless one is already running
+    fn spawn_held_lightning_payment_retry(&self, key: PendingHtlcKey, pending_htlc: PendingHtlc) {
+        // Claim the HTLC before spawning, so a re-delivery arriving before the task
+        // first runs is already ignored
+        if !self.held_htlcs.lock().expect("poisoned").insert(key) {
+            return;
+        }
+
+        let gateway = self.clone();
+        self.task_group.spawn_cancellable(
+            format!("retry held htlc {}", pending_htlc.payment_hash),
+            async move {
+                gateway
+                    .retry_held_lightning_payment(key, pending_htlc)
+                    .await;
+                gateway.held_htlcs.lock().expect("poisoned").remove(&key);
+            },
+        );
+    }
+
+    /// Periodically retries handing a held HTLC to its federation until it
+    /// succeeds, fails for a reason other than the federation being
+    /// unreachable, or the HTLC gets too close to its expiry.
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(&pending_htlc.payment_hash)))]
+    async fn retry_held_lightning_payment(
+        &self,
+        key: PendingHtlcKey,
+        mut pending_htlc: PendingHtlc,
+    ) {
+        let payment_request = InterceptPaymentRequest {
+            payment_hash: pending_htlc.payment_hash,
+            amount_msat: pending_htlc.amount_msat,
+            expiry: pending_htlc.expiry,
+            incoming_chan_id: key.incoming_chan_id,
+            short_channel_id: Some(pending_htlc.short_channel_id),
+            htlc_id: key.htlc_id,
+        };
+
+        loop {
+            sleep(PENDING_HTLC_RETRY_INTERVAL).await;
+
+            // The HTLC stays held on the lightning node while we are disconnected from
+            // it, so we wait for the connection to come back and always use the
+            // current one
+            let Ok(lightning_context) = self.get_lightning_context().await else {
+                continue;
+            };
+
+            let block_height = match lightning_context.lnrpc.parsed_node_info().await {
+                Ok((_, _, _, block_height, _)) => block_height,
+                Err(e) => {
+                    warn!(?e, "Failed to fetch block height for held HTLC");
+                    continue;
+                }
+            };
+
+            if pending_htlc.is_about_to_expire(block_height, PENDING_HTLC_CLTV_SAFETY_MARGIN) {
+                warn!(
+                    payment_hash = %pending_htlc.payment_hash,
+                    expiry = pending_htlc.expiry,
+                    block_height,
+                    "Held HTLC is about to expire, cancelling it"
+                );
+                Self::cancel_lightning_payment(&payment_request, &lightning_context).await;
+                break;
+            }
+
+            match self
+                .try_handle_lightning_payment_ln_legacy(&payment_request)
+                .await
+            {
+                Ok(()) => {
+                    info!(
+                        payment_hash = %pending_htlc.payment_hash,
+                        attempts = pending_htlc.attempts,
+                        "Handed held HTLC to the federation"
+                    );
+                    break;
+                }
+                Err(PublicGatewayError::LNv1(LNv1Error::FederationUnreachable(error))) => {
+                    pending_htlc.attempts += 1;
+                    debug!(
+                        payment_hash = %pending_htlc.payment_hash,
+                        attempts = pending_htlc.attempts,
+                        "Federation still unreachable for held HTLC: {error}"
+                    );
+                    let mut dbtx = self.gateway_db.begin_transaction().await;
+                    dbtx.save_pending_htlc(key, &pending_htlc).await;
+                    dbtx.commit_tx().await;
+                }
+                Err(error) => {
+                    warn!(
+                        payment_hash = %pending_htlc.payment_hash,
+                        "Failed to handle held HTLC, cancelling it: {error}"
+                    );
+                    Self::cancel_lightning_payment(&payment_request, &lightning_context).await;
+                    break;
+                }
+            }
+        }
+
+        self.remove_held_lightning_payment(&key).await;
+    }
+
+    async fn remove_held_lightning_payment(&self, key: &PendingHtlcKey) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.remove_pending_htlc(key).await;
+        dbtx.commit_tx().await;
+        self.update_htlc_retry_queue_depth().await;
+    }
+
+    async fn update_htlc_retry_queue_depth(&self) {
+        let depth = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_pending_htlcs()
+            .await
+            .len();
+        HTLC_RETRY_QUEUE_DEPTH.set(depth as i64);
+    }
+
     /// Forwards a lightning payment to the next hop like a normal lightning
     /// node. Only necessary for LNv1, since LNv2 uses hold invoices instead
     /// of HTLC interception for routing incoming payments.
@@ -725,6 +1045,35 @@ async fn forward_lightning_payment(
         }
     }
 
+    /// Fails an intercepted lightning payment back to the sender.
+    async fn cancel_lightning_payment(
+        htlc_request: &InterceptPaymentRequest,
+        lightning_context: &LightningContext,
+    ) {
+        let outcome = InterceptPaymentResponse {
+            action: PaymentAction::Cancel,
+            payment_hash: htlc_request.payment_hash,
+            incoming_chan_id: htlc_request.incoming_chan_id,
+            htlc_id: htlc_request.htlc_id,
+        };
+
+        if let Err(error) = lightning_context.lnrpc.complete_htlc(outcome).await {
+            error!("Error sending lightning payment response to lightning node: {error:?}");
+        }
+    }
+
+    /// Returns the [`PaymentLimits`] configured for the federation, or no
+    /// limits if the federation is unknown.
+    async fn payment_limits(&self, federation_id: FederationId) -> PaymentLimits {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_federation_config(federation_id)
+            .await
+            .map(|config| config.payment_limits)
+            .unwrap_or_default()
+    }
+
     /// Helper function for atomically changing the Gateway's internal state.
     async fn set_gateway_state(&self, state: GatewayState) {
         let mut lock = self.state.write().await;
@@ -864,7 +1213,7 @@ pub async fn handle_withdraw_msg(
             // the amount we are withdrawing
             BitcoinAmountOrAll::All => {
                 let balance =
-                    bitcoin::Amount::from_sat(client.value().get_balance().await.msats / 1000);
+                    bitcoin::Amount::from_sat(client.value().get_balance().await.sats_round_down());
                 let fees = wallet_module.get_withdraw_fees(&address, balance).await?;
                 let withdraw_amount = balance.checked_sub(fees.amount());
                 if withdraw_amount.is_none() {
@@ -924,7 +1273,7 @@ async fn handle_create_invoice_for_operator_msg(
                 .create_invoice(CreateInvoiceRequest {
                     payment_hash: None, /* Empty payment hash indicates an invoice payable
                                          * directly to the gateway. */
-                    amount_msat: payload.amount_msats,
+                    amount_msat: payload.amount_msats.0,
                     expiry_secs: payload.expiry_secs.unwrap_or(3600),
                     description: payload.description.map(InvoiceDescription::Direct),
                 })
@@ -940,37 +1289,70 @@ async fn handle_create_invoice_for_operator_msg(
 
     /// Requests the gateway to pay an outgoing LN invoice using its own funds.
     /// Returns the payment hash's preimage on success.
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(payload.invoice.payment_hash())))]
     async fn handle_pay_invoice_for_operator_msg(
         &self,
         payload: PayInvoiceForOperatorPayload,
     ) -> AdminResult<Preimage> {
-        // Those are the ldk defaults
-        const BASE_FEE: u64 = 50;
-        const FEE_DENOMINATOR: u64 = 100;
-        const MAX_DELAY: u64 = 1008;
-
         let GatewayState::Running { lightning_context } = self.get_state().await else {
             return Err(AdminGatewayError::Lightning(
                 LightningRpcError::FailedToConnect,
             ));
         };
 
-        let max_fee = BASE_FEE
-            + payload
+        let max_fee = operator_payment_max_fee(
+            payload
                 .invoice
                 .amount_milli_satoshis()
-                .context("Invoice is missing amount")?
-                .saturating_div(FEE_DENOMINATOR);
+                .context("Invoice is missing amount")?,
+        );
+
+        let res = lightning_context
+            .lnrpc
+            .pay(payload.invoice, OPERATOR_PAYMENT_MAX_DELAY, max_fee)
+            .await?;
+        Ok(res.preimage)
+    }
+
+    /// Requests the gateway to pay a BOLT12 offer using its own funds. The
+    /// invoice for the offer is paid over the blinded paths chosen by its
+    /// issuer. Returns the payment hash's preimage on success.
+    async fn handle_pay_offer_for_operator_msg(
+        &self,
+        payload: PayOfferForOperatorPayload,
+    ) -> AdminResult<Preimage> {
+        let GatewayState::Running { lightning_context } = self.get_state().await else {
+            return Err(AdminGatewayError::Lightning(
+                LightningRpcError::FailedToConnect,
+            ));
+        };
+
+        if !lightning_context.lnrpc.supports_offers() {
+            return Err(AdminGatewayError::Lightning(
+                LightningRpcError::FailedPayment {
+                    failure_reason: "The lightning node can't pay offers".to_string(),
+                },
+            ));
+        }
+
+        let offer = ::lightning::offers::offer::Offer::from_str(&payload.offer)
+            .map_err(|e| anyhow!("Invalid offer: {e:?}"))?;
+        check_offer_amount(&offer, payload.amount)?;
 
         let res = lightning_context
             .lnrpc
-            .pay(payload.invoice, MAX_DELAY, Amount::from_msats(max_fee))
+            .pay_offer(offer, payload.amount)
             .await?;
         Ok(res.preimage)
     }
 
     /// Requests the gateway to pay an outgoing LN invoice on behalf of a
     /// Fedimint client. Returns the payment hash's preimage on success.
+    ///
+    /// Requests are idempotent on the payment hash: a retried request returns
+    /// the recorded outcome instead of paying the invoice a second time. Only
+    /// once a payment failed can the invoice be paid with another contract.
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(&payload.payment_data.payment_hash())))]
     async fn handle_pay_invoice_msg(
         &self,
         payload: fedimint_ln_client::pay::PayInvoicePayload,
@@ -982,18 +1364,62 @@ async fn handle_pay_invoice_msg(
         };
 
         debug!("Handling pay invoice message: {payload:?}");
+
+        if let Some(amount) = payload.payment_data.amount() {
+            self.payment_limits(payload.federation_id)
+                .await
+                .check_outgoing_payment(amount)?;
+        }
+
         let client = self.select_client(payload.federation_id).await?;
         let contract_id = payload.contract_id;
+        let payment_hash = payload.payment_data.payment_hash();
         let gateway_module = &client
             .value()
             .get_first_module::<GatewayClientModule>()
             .map_err(LNv1Error::OutgoingPayment)
             .map_err(PublicGatewayError::LNv1)?;
-        let operation_id = gateway_module
-            .gateway_pay_bolt11_invoice(payload)
+
+        if let Some(preimage) = self
+            .begin_outgoing_payment(gateway_module, payment_hash, contract_id)
+            .await?
+        {
+            info!(%payment_hash, "Invoice was already paid, returning the recorded preimage for contract id {contract_id}");
+            return Ok(preimage);
+        }
+
+        if let Err(error) = gateway_module.gateway_pay_bolt11_invoice(payload).await {
+            // Only if no pay operation was started can the invoice safely be paid again,
+            // otherwise the record stays pending until the operation has an outcome
+            if !gateway_module.pay_operation_exists(contract_id).await {
+                let mut dbtx = self.gateway_db.begin_transaction().await;
+                if let Some(OutgoingPaymentOutcome::Pending {
+                    contract_id: pending_contract_id,
+                    ..
+                }) = dbtx.load_outgoing_payment_outcome(payment_hash).await
+                {
+                    if pending_contract_id == contract_id {
+                        dbtx.remove_outgoing_payment_outcome(payment_hash).await;
+                    }
+                }
+                dbtx.commit_tx().await;
+            }
+            return Err(PublicGatewayError::LNv1(LNv1Error::OutgoingPayment(error)));
+        }
+
+        self.await_outgoing_payment(gateway_module, payment_hash, contract_id)
             .await
-            .map_err(LNv1Error::OutgoingPayment)
-            .map_err(PublicGatewayError::LNv1)?;
+    }
+
+    /// Waits for the outcome of the pay operation for `contract_id` and records
+    /// it for the invoice with the given payment hash
+    async fn await_outgoing_payment(
+        &self,
+        gateway_module: &GatewayClientModule,
+        payment_hash: sha256::Hash,
+        contract_id: ContractId,
+    ) -> Result<Preimage> {
+        let operation_id = OperationId(contract_id.to_byte_array());
         let mut updates = gateway_module
             .gateway_subscribe_ln_pay(operation_id)
             .await
@@ -1004,21 +1430,51 @@ async fn handle_pay_invoice_msg(
             match update {
                 GatewayExtPayStates::Success { preimage, .. } => {
                     debug!("Successfully paid invoice: {contract_id}");
+                    self.save_outgoing_payment_outcome(
+                        payment_hash,
+                        OutgoingPaymentOutcome::Succeeded {
+                            contract_id,
+                            preimage: preimage.clone(),
+                        },
+                    )
+                    .await;
                     return Ok(preimage);
                 }
                 GatewayExtPayStates::Fail {
                     error,
                     error_message,
                 } => {
+                    let error_message = format!(
+                        "{error_message} while paying invoice with contract id {contract_id}"
+                    );
+                    self.save_outgoing_payment_outcome(
+                        payment_hash,
+                        OutgoingPaymentOutcome::Failed {
+                            contract_id,
+                            error_message: error_message.clone(),
+                        },
+                    )
+                    .await;
                     return Err(PublicGatewayError::LNv1(LNv1Error::OutgoingContract {
                         error: Box::new(error),
-                        message: format!(
-                            "{error_message} while paying invoice with contract id {contract_id}"
-                        ),
+                        message: error_message,
                     }));
                 }
                 GatewayExtPayStates::Canceled { error } => {
-                    return Err(PublicGatewayError::LNv1(LNv1Error::OutgoingContract { error: Box::new(error.clone()), message: format!("Cancelled with {error} while paying invoice with contract id {contract_id}") }));
+                    let error_message =
+                        format!("Cancelled with {error} while paying invoice with contract id {contract_id}");
+                    self.save_outgoing_payment_outcome(
+                        payment_hash,
+                        OutgoingPaymentOutcome::Failed {
+                            contract_id,
+                            error_message: error_message.clone(),
+                        },
+                    )
+                    .await;
+                    return Err(PublicGatewayError::LNv1(LNv1Error::OutgoingContract {
+                        error: Box::new(error),
+                        message: error_message,
+                    }));
                 }
                 GatewayExtPayStates::Created => {
                     debug!("Got initial state Created while paying invoice: {contract_id}");
@@ -1034,6 +1490,95 @@ async fn handle_pay_invoice_msg(
         )))
     }
 
+    /// Records that the invoice with the given payment hash is being paid for
+    /// `contract_id`. Returns the preimage if the invoice was already paid and
+    /// fails if it is in-flight for another contract or the payment for this
+    /// contract already failed.
+    ///
+    /// A payment for another contract that was interrupted by a restart is
+    /// awaited first, so its outcome gets recorded. A pending record without a
+    /// pay operation only blocks the invoice until it expires.
+    async fn begin_outgoing_payment(
+        &self,
+        gateway_module: &GatewayClientModule,
+        payment_hash: sha256::Hash,
+        contract_id: ContractId,
+    ) -> Result<Option<Preimage>> {
+        let recorded = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_outgoing_payment_outcome(payment_hash)
+            .await;
+        if let Some(OutgoingPaymentOutcome::Pending {
+            contract_id: pending_contract_id,
+            ..
+        }) = recorded
+        {
+            if pending_contract_id != contract_id
+                && gateway_module
+                    .pay_operation_exists(pending_contract_id)
+                    .await
+            {
+                if let Ok(preimage) = self
+                    .await_outgoing_payment(gateway_module, payment_hash, pending_contract_id)
+                    .await
+                {
+                    return Ok(Some(preimage));
+                }
+            }
+        }
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if let Some(recorded) = dbtx.load_outgoing_payment_outcome(payment_hash).await {
+            let pending_operation_exists = match &recorded {
+                OutgoingPaymentOutcome::Pending {
+                    contract_id: pending_contract_id,
+                    ..
+                } => {
+                    gateway_module
+                        .pay_operation_exists(*pending_contract_id)
+                        .await
+                }
+                _ => false,
+            };
+            match recorded.next_action(contract_id, pending_operation_exists, now()) {
+                OutgoingPaymentAction::Pay => {}
+                OutgoingPaymentAction::Paid(preimage) => return Ok(Some(preimage)),
+                OutgoingPaymentAction::Reject(error_message) => {
+                    return Err(PublicGatewayError::LNv1(LNv1Error::OutgoingPayment(
+                        anyhow!(error_message),
+                    )));
+                }
+            }
+        }
+
+        dbtx.save_outgoing_payment_outcome(
+            payment_hash,
+            &OutgoingPaymentOutcome::Pending {
+                contract_id,
+                started_at: now(),
+            },
+        )
+        .await;
+        // Fails if a concurrent request for the same payment hash committed first
+        dbtx.commit_tx_result()
+            .await
+            .map_err(LNv1Error::OutgoingPayment)?;
+        Ok(None)
+    }
+
+    async fn save_outgoing_payment_outcome(
+        &self,
+        payment_hash: sha256::Hash,
+        outcome: OutgoingPaymentOutcome,
+    ) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.save_outgoing_payment_outcome(payment_hash, &outcome)
+            .await;
+        dbtx.commit_tx().await;
+    }
+
     /// Handles a connection request to join a new federation. The gateway will
     /// download the federation's client configuration, construct a new
     /// client, registers, the gateway with the federation, and persists the
@@ -1068,6 +1613,7 @@ pub async fn handle_connect_federation(
         let connector = Connector::default();
 
         let federation_id = invite_code.federation_id();
+        self.registration_policy.check_registration(federation_id)?;
 
         let mut federation_manager = self.federation_manager.write().await;
 
@@ -1088,6 +1634,7 @@ pub async fn handle_connect_federation(
             lightning_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
             transaction_fee: PaymentFee::TRANSACTION_FEE_DEFAULT,
             connector,
+            payment_limits: PaymentLimits::default(),
         };
 
         let mnemonic = Self::load_or_generate_mnemonic(&self.gateway_db).await?;
@@ -1298,6 +1845,61 @@ pub async fn handle_set_fees_msg(
         Ok(())
     }
 
+    /// Handles a request to change the payment limits for all federations or a
+    /// federation specified by the `FederationId`.
+    pub async fn handle_set_payment_limits_msg(
+        &self,
+        SetPaymentLimitsPayload {
+            federation_id,
+            htlc_minimum,
+            htlc_maximum,
+            payment_maximum,
+        }: SetPaymentLimitsPayload,
+    ) -> AdminResult<()> {
+        // An amount of zero removes the limit
+        fn limit(amount: Amount) -> Option<Amount> {
+            (amount != Amount::ZERO).then_some(amount)
+        }
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        let mut fed_configs = dbtx.load_federation_configs().await;
+        if let Some(fed_id) = federation_id {
+            fed_configs.retain(|id, _| *id == fed_id);
+        }
+
+        for config in fed_configs.values_mut() {
+            let mut payment_limits = config.payment_limits;
+            if let Some(htlc_minimum) = htlc_minimum {
+                payment_limits.htlc_minimum = limit(htlc_minimum);
+            }
+
+            if let Some(htlc_maximum) = htlc_maximum {
+                payment_limits.htlc_maximum = limit(htlc_maximum);
+            }
+
+            if let Some(payment_maximum) = payment_maximum {
+                payment_limits.payment_maximum = limit(payment_maximum);
+            }
+
+            if let (Some(minimum), Some(maximum)) =
+                (payment_limits.htlc_minimum, payment_limits.htlc_maximum)
+            {
+                if maximum < minimum {
+                    return Err(AdminGatewayError::GatewayConfigurationError(format!(
+                        "HTLC maximum {maximum} is below the HTLC minimum {minimum}"
+                    )));
+                }
+            }
+
+            config.payment_limits = payment_limits;
+            dbtx.save_federation_config(config).await;
+        }
+
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
     /// Generates an onchain address to fund the gateway's lightning node.
     pub async fn handle_get_ln_onchain_address_msg(&self) -> AdminResult<Address> {
         let context = self.get_lightning_context().await?;
@@ -1489,6 +2091,285 @@ pub async fn handle_receive_ecash_msg(
         Ok(ReceiveEcashResponse { amount })
     }
 
+    /// Runs a liquidity probe of a federation: ecash of the gateway is paid over
+    /// its lightning node and back into ecash, measuring the latency and fees of
+    /// real payments in both directions. Meant to be run periodically as a
+    /// synthetic check by monitoring systems.
+    ///
+    /// A dedicated probe client of the federation first pays an invoice of the
+    /// gateway's lightning node through the gateway, then the node pays an
+    /// invoice of the probe client through the gateway, so both legs are
+    /// circular payments of the node to itself. Failed legs are reported in
+    /// the response instead of failing the request. Afterwards the probe
+    /// client's balance is returned to the gateway.
+    pub async fn handle_probe_msg(&self, payload: ProbePayload) -> AdminResult<ProbeResponse> {
+        let ProbePayload {
+            federation_id,
+            amount,
+        } = payload;
+        let lightning_context = self.get_lightning_context().await?;
+        if !lightning_context.lnrpc.supports_self_payments() {
+            return Err(AdminGatewayError::Unexpected(anyhow!(
+                "Probes pay the gateway's lightning node over circular routes, which it doesn't support"
+            )));
+        }
+        let client = self.select_client(federation_id).await?.into_value();
+        let federation_config = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .load_federation_config(federation_id)
+            .await
+            .ok_or(FederationNotConnected {
+                federation_id_prefix: federation_id.to_prefix(),
+            })?;
+        let mnemonic = Self::load_or_generate_mnemonic(&self.gateway_db).await?;
+        let probe_client = self
+            .client_builder
+            .build_probe(federation_config.clone(), &mnemonic)
+            .await?;
+
+        let response = self
+            .run_probe(
+                &client,
+                &probe_client,
+                &federation_config,
+                &lightning_context,
+                amount,
+            )
+            .await;
+        let sweep = Self::sweep_probe_client(&client, &probe_client).await;
+        probe_client.shutdown().await;
+
+        let response = response?;
+        sweep?;
+        info!(
+            %federation_id,
+            success = response.success,
+            duration_ms = response.total_duration_ms,
+            "Liquidity probe finished"
+        );
+        Ok(response)
+    }
+
+    async fn run_probe(
+        &self,
+        client: &ClientHandleArc,
+        probe_client: &ClientHandle,
+        federation_config: &FederationConfig,
+        lightning_context: &LightningContext,
+        amount: Amount,
+    ) -> AdminResult<ProbeResponse> {
+        let start = Instant::now();
+        let probe_ln = probe_client.get_first_module::<LightningClientModule>()?;
+        probe_ln.update_gateway_cache().await?;
+        let gateway = probe_ln
+            .select_gateway(&self.gateway_id)
+            .await
+            .context("The gateway is not registered with the federation")?;
+
+        // The probe client may still hold funds of an earlier probe
+        let required = federation_config.lightning_fee.add_to(amount.msats);
+        let missing = required.saturating_sub(probe_client.get_balance().await);
+        if missing > Amount::ZERO {
+            let (_, notes) = client
+                .get_first_module::<MintClientModule>()?
+                .spend_notes_with_selector(
+                    &SelectNotesWithAtleastAmount,
+                    missing,
+                    PROBE_ECASH_TIMEOUT,
+                    false,
+                    (),
+                )
+                .await?;
+            let probe_mint = probe_client.get_first_module::<MintClientModule>()?;
+            let operation_id = probe_mint.reissue_external_notes(notes, ()).await?;
+            Self::await_reissue(&probe_mint, operation_id).await?;
+        }
+
+        let ecash_to_ln = Self::run_probe_leg("ecash to LN", async {
+            let invoice = self
+                .handle_create_invoice_for_operator_msg(CreateInvoiceForOperatorPayload {
+                    amount_msats: amount.into(),
+                    expiry_secs: Some(PROBE_LEG_TIMEOUT.as_secs() as u32),
+                    description: Some("Gateway liquidity probe".to_string()),
+                })
+                .await?;
+            // Lets the gateway pay the invoice of its own lightning node
+            let _probe_payment =
+                ProbePayment::new(self.probe_payment_hashes.clone(), *invoice.payment_hash());
+            let payment = probe_ln
+                .pay_bolt11_invoice(Some(gateway.clone()), invoice, ())
+                .await?;
+            let PayType::Lightning(operation_id) = payment.payment_type else {
+                bail!("The invoice of the lightning node was paid internally");
+            };
+            let mut updates = probe_ln.subscribe_ln_pay(operation_id).await?.into_stream();
+            while let Some(update) = updates.next().await {
+                match update {
+                    LnPayState::Success { .. } => return Ok(payment.fee),
+                    LnPayState::Refunded { gateway_error } => {
+                        bail!("The gateway failed to pay the invoice: {gateway_error}")
+                    }
+                    LnPayState::WaitingForRefund { error_reason } => {
+                        bail!("The payment failed, waiting for a refund: {error_reason}")
+                    }
+                    LnPayState::Canceled => {
+                        bail!("The funding transaction was rejected")
+                    }
+                    LnPayState::UnexpectedError { error_message } => {
+                        bail!("Unexpected error: {error_message}")
+                    }
+                    LnPayState::Created
+                    | LnPayState::Funded { .. }
+                    | LnPayState::AwaitingChange => {}
+                }
+            }
+            bail!("Ran out of payment state updates")
+        })
+        .await;
+
+        let ln_to_ecash = if ecash_to_ln.success {
+            Some(
+                Self::run_probe_leg("LN to ecash", async {
+                    let description =
+                        lightning_invoice::Description::new("Gateway liquidity probe".to_string())?;
+                    let (operation_id, invoice, _) = probe_ln
+                        .create_bolt11_invoice(
+                            amount,
+                            lightning_invoice::Bolt11InvoiceDescription::Direct(&description),
+                            Some(PROBE_LEG_TIMEOUT.as_secs()),
+                            (),
+                            Some(gateway.clone()),
+                        )
+                        .await?;
+                    let max_fee = operator_payment_max_fee(amount.msats);
+                    lightning_context
+                        .lnrpc
+                        .pay_private_to_self(
+                            PrunedInvoice::try_from(invoice)?,
+                            OPERATOR_PAYMENT_MAX_DELAY,
+                            max_fee,
+                        )
+                        .await?;
+                    let mut updates = probe_ln
+                        .subscribe_ln_receive(operation_id)
+                        .await?
+                        .into_stream();
+                    while let Some(update) = updates.next().await {
+                        match update {
+                            // The fee is charged in the invoice's route hint
+                            LnReceiveState::Claimed => return Ok(gateway.fees.to_amount(&amount)),
+                            LnReceiveState::Canceled { reason } => {
+                                bail!("The payment was canceled: {reason}")
+                            }
+                            LnReceiveState::Created
+                            | LnReceiveState::WaitingForPayment { .. }
+                            | LnReceiveState::Funded
+                            | LnReceiveState::AwaitingFunds => {}
+                        }
+                    }
+                    bail!("Ran out of receive state updates")
+                })
+                .await,
+            )
+        } else {
+            None
+        };
+
+        Ok(ProbeResponse {
+            success: ln_to_ecash.as_ref().is_some_and(|leg| leg.success),
+            amount,
+            ecash_to_ln,
+            ln_to_ecash,
+            total_duration_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Runs a leg of a liquidity probe resolving to the fee it cost
+    async fn run_probe_leg(
+        name: &str,
+        leg: impl Future<Output = anyhow::Result<Amount>>,
+    ) -> ProbeLeg {
+        let start = Instant::now();
+        let result = fedimint_core::runtime::timeout(PROBE_LEG_TIMEOUT, leg)
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Timed out after {PROBE_LEG_TIMEOUT:?}")));
+        let duration_ms = start.elapsed().as_millis() as u64;
+        match result {
+            Ok(fee) => ProbeLeg {
+                success: true,
+                duration_ms,
+                fee: Some(fee),
+                error: None,
+            },
+            Err(e) => {
+                warn!("Liquidity probe leg {name} failed: {e:?}");
+                ProbeLeg {
+                    success: false,
+                    duration_ms,
+                    fee: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Whether `payment_hash` belongs to the invoice of the lightning node
+    /// a running liquidity probe pays, see [`Self::handle_probe_msg`]
+    pub fn is_probe_payment(&self, payment_hash: &sha256::Hash) -> bool {
+        self.probe_payment_hashes
+            .lock()
+            .expect("poisoned")
+            .contains(payment_hash)
+    }
+
+    /// Returns the spendable balance of the probe client to the gateway's
+    /// client of the federation
+    async fn sweep_probe_client(
+        client: &ClientHandleArc,
+        probe_client: &ClientHandle,
+    ) -> anyhow::Result<()> {
+        let balance = probe_client.get_balance().await;
+        if balance == Amount::ZERO {
+            return Ok(());
+        }
+
+        let (_, notes) = probe_client
+            .get_first_module::<MintClientModule>()?
+            .spend_notes_with_selector(
+                &SelectNotesWithExactAmount,
+                balance,
+                PROBE_ECASH_TIMEOUT,
+                false,
+                (),
+            )
+            .await?;
+        let mint = client.get_first_module::<MintClientModule>()?;
+        let operation_id = mint.reissue_external_notes(notes, ()).await?;
+        Self::await_reissue(&mint, operation_id).await
+    }
+
+    async fn await_reissue(
+        mint: &MintClientModule,
+        operation_id: OperationId,
+    ) -> anyhow::Result<()> {
+        let mut updates = mint
+            .subscribe_reissue_external_notes(operation_id)
+            .await?
+            .into_stream();
+        while let Some(update) = updates.next().await {
+            match update {
+                fedimint_mint_client::ReissueExternalNotesState::Done => return Ok(()),
+                fedimint_mint_client::ReissueExternalNotesState::Failed(e) => {
+                    bail!("Failed to reissue ecash: {e}")
+                }
+                _ => {}
+            }
+        }
+        bail!("Ran out of reissue state updates")
+    }
+
     /// Instructs the gateway to shutdown, but only after all incoming payments
     /// have been handlded.
     pub async fn handle_shutdown_msg(&self, task_group: TaskGroup) -> AdminResult<()> {
@@ -1573,6 +2454,96 @@ pub async fn handle_payment_log_msg(
         Ok(PaymentLogResponse(payment_log))
     }
 
+    /// Lists the LNv1 contracts that haven't reached a terminal resolution yet.
+    pub async fn handle_list_contracts_msg(
+        &self,
+        ListContractsPayload {
+            federation_id,
+            stuck,
+        }: ListContractsPayload,
+    ) -> AdminResult<ListContractsResponse> {
+        let mut federations = self
+            .federation_manager
+            .read()
+            .await
+            .pending_contracts()
+            .await?;
+
+        if let Some(federation_id) = federation_id {
+            federations.retain(|id, _| *id == federation_id);
+        }
+
+        if stuck {
+            for contracts in federations.values_mut() {
+                contracts.retain(|contract| contract.pending_secs >= STUCK_CONTRACT_AGE.as_secs());
+            }
+        }
+
+        Ok(ListContractsResponse { federations })
+    }
+
+    /// Manually resolves the outgoing contract of a stuck LNv1 or LNv2 payment.
+    /// This is an escape hatch for operators, every resolution is logged and
+    /// kept in the gateway's database so it can be audited later.
+    pub async fn handle_resolve_contract_msg(
+        &self,
+        ResolveContractPayload {
+            federation_id,
+            operation_id,
+            resolution,
+        }: ResolveContractPayload,
+    ) -> AdminResult<()> {
+        let client = self.select_client(federation_id).await?;
+        let action = match resolution {
+            ContractResolution::Claim { .. } => "claim",
+            ContractResolution::Refund => "refund",
+        };
+
+        warn!(
+            %federation_id,
+            operation_id = %operation_id.fmt_short(),
+            action,
+            "Operator is force resolving outgoing contract"
+        );
+        let is_lnv2 = client
+            .value()
+            .operation_log()
+            .get_operation(operation_id)
+            .await
+            .is_some_and(|entry| {
+                entry.operation_module_kind() == fedimint_lnv2_common::KIND.as_str()
+            });
+        let result = if is_lnv2 {
+            client
+                .value()
+                .get_first_module::<GatewayClientModuleV2>()?
+                .gateway_force_resolve_outgoing_contract(operation_id, resolution)
+                .await
+        } else {
+            client
+                .value()
+                .get_first_module::<GatewayClientModule>()?
+                .gateway_force_resolve_outgoing_contract(operation_id, resolution)
+                .await
+        };
+        result.inspect_err(|e| {
+            warn!(
+                %federation_id,
+                operation_id = %operation_id.fmt_short(),
+                action,
+                "Failed to force resolve outgoing contract: {e}"
+            );
+        })?;
+        info!(
+            %federation_id,
+            operation_id = %operation_id.fmt_short(),
+            action,
+            "Submitted forced resolution of outgoing contract"
+        );
+
+        Ok(())
+    }
+
     /// Registers the gateway with each specified federation.
     async fn register_federations(
         &self,
@@ -1686,6 +2657,11 @@ async fn load_clients(&self) -> AdminResult<()> {
         let mnemonic = Self::load_or_generate_mnemonic(&self.gateway_db).await?;
 
         for (federation_id, config) in configs {
+            if !self.registration_policy.is_allowed(federation_id) {
+                warn!("Not loading client for federation {federation_id}, it is not allowed by the registration policy");
+                continue;
+            }
+
             let federation_index = config.federation_index;
             if let Ok(client) = Box::pin(Spanned::try_new(
                 info_span!("client", federation_id  = %federation_id.clone()),
@@ -1890,12 +2866,24 @@ async fn send_payment_v2(
         &self,
         payload: SendPaymentPayload,
     ) -> Result<std::result::Result<[u8; 32], Signature>> {
+        let LightningInvoice::Bolt11(invoice) = &payload.invoice;
+        let trace_id = PaymentTraceId::new(invoice.payment_hash());
+        // Without an amount in the invoice we check the contract's amount, which
+        // includes our fee
+        let amount = invoice
+            .amount_milli_satoshis()
+            .map_or(payload.contract.amount, Amount::from_msats);
+        self.payment_limits(payload.federation_id)
+            .await
+            .check_outgoing_payment(amount)?;
+
         self.select_client(payload.federation_id)
             .await?
             .value()
             .get_first_module::<GatewayClientModuleV2>()
             .expect("Must have client module")
             .send_payment(payload)
+            .instrument(trace_id.span())
             .await
             .map_err(LNv2Error::OutgoingPayment)
             .map_err(PublicGatewayError::LNv2)
@@ -1942,6 +2930,10 @@ async fn create_bolt11_invoice_v2(
             )));
         }
 
+        self.payment_limits(payload.federation_id)
+            .await
+            .check_incoming_htlc(payload.amount)?;
+
         if payload.contract.commitment.expiration <= duration_since_epoch().as_secs() {
             return Err(PublicGatewayError::LNv2(LNv2Error::IncomingPayment(
                 "The contract has already expired".to_string(),
@@ -2077,3 +3069,43 @@ fn is_running_lnv1(&self) -> bool {
             || self.lightning_module_mode == LightningModuleMode::All
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::anyhow;
+    use bitcoin::hashes::{sha256, Hash};
+    use fedimint_core::Amount;
+
+    use super::{Gateway, ProbePayment};
+
+    #[test]
+    fn probe_payments_are_marked_until_dropped() {
+        let probe_payment_hashes = Arc::new(Mutex::new(BTreeSet::new()));
+        let payment_hash = sha256::Hash::hash(b"probe");
+
+        let probe_payment = ProbePayment::new(probe_payment_hashes.clone(), payment_hash);
+        assert!(probe_payment_hashes
+            .lock()
+            .expect("poisoned")
+            .contains(&payment_hash));
+
+        drop(probe_payment);
+        assert!(probe_payment_hashes.lock().expect("poisoned").is_empty());
+    }
+
+    #[tokio::test]
+    async fn probe_legs_report_their_outcome() {
+        let leg = Gateway::run_probe_leg("test", async { Ok(Amount::from_msats(42)) }).await;
+        assert!(leg.success);
+        assert_eq!(leg.fee, Some(Amount::from_msats(42)));
+        assert_eq!(leg.error, None);
+
+        let leg = Gateway::run_probe_leg("test", async { Err(anyhow!("No route")) }).await;
+        assert!(!leg.success);
+        assert_eq!(leg.fee, None);
+        assert_eq!(leg.error.as_deref(), Some("No route"));
+    }
+}
@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bitcoin::hashes::sha256;
+use fedimint_core::db::Database;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::task::TaskGroup;
+use fedimint_core::Amount;
+use fedimint_ln_common::PrunedInvoice;
+use futures::StreamExt;
+use lightning::offers::offer::Offer;
+use lightning_invoice::Bolt11Invoice;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    CloseChannelsWithPeerResponse, CreateInvoiceRequest, CreateInvoiceResponse,
+    GetBalancesResponse, GetLnOnchainAddressResponse, GetNodeInfoResponse, GetRouteHintsResponse,
+    ILnRpcClient, InterceptPaymentResponse, LightningRpcError, ListActiveChannelsResponse,
+    OpenChannelResponse, PayInvoiceResponse, RouteHtlcStream, SendOnchainResponse,
+};
+use crate::db::GatewayDbtxNcExt;
+use crate::rpc::{CloseChannelsWithPeerPayload, SendOnchainPayload};
+use crate::OpenChannelPayload;
+
+/// How long the result of checking whether the primary node is reachable is
+/// reused, so we don't query the node before every payment and invoice
+const PRIMARY_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Intercepted HTLCs that were not completed after this long are forgotten.
+/// Lightning nodes fail HTLCs back before they expire, and HTLCs expire at most
+/// 2016 blocks after they were offered, so such an HTLC doesn't exist anymore.
+const INTERCEPTED_HTLC_MAX_AGE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// One of the lightning nodes of a [`FailoverLnRpcClient`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub enum LightningBackend {
+    Primary,
+    Standby,
+}
+
+#[derive(Debug)]
+enum LightningNode {
+    /// Not intercepting HTLCs yet
+    Idle(Box<dyn ILnRpcClient>),
+    /// Intercepting HTLCs
+    Routing(Arc<dyn ILnRpcClient>),
+    /// Failed to start intercepting HTLCs, the gateway reconnects to it the
+    /// next time the HTLC stream is re-established
+    Unavailable,
+}
+
+impl LightningNode {
+    fn client(&self) -> Option<&dyn ILnRpcClient> {
+        match self {
+            LightningNode::Idle(client) => Some(client.as_ref()),
+            LightningNode::Routing(client) => Some(client.as_ref()),
+            LightningNode::Unavailable => None,
+        }
+    }
+}
+
+/// Node each HTLC in the current HTLC stream was intercepted by, keyed by
+/// `(incoming_chan_id, htlc_id)`
+#[derive(Debug, Default)]
+struct InterceptedHtlcs(Mutex<HashMap<(u64, u64), (LightningBackend, Instant)>>);
+
+impl InterceptedHtlcs {
+    /// Records the node an HTLC was intercepted by and forgets the HTLCs that
+    /// were never completed and have expired by now
+    fn insert(&self, htlc: (u64, u64), backend: LightningBackend, now: Instant) {
+        let mut htlcs = self.0.lock().expect("poisoned");
+        htlcs.retain(|_, (_, intercepted_at)| {
+            now.saturating_duration_since(*intercepted_at) < INTERCEPTED_HTLC_MAX_AGE
+        });
+        htlcs.insert(htlc, (backend, now));
+    }
+
+    fn remove(&self, htlc: (u64, u64)) -> Option<LightningBackend> {
+        self.0
+            .lock()
+            .expect("poisoned")
+            .remove(&htlc)
+            .map(|(backend, _)| backend)
+    }
+}
+
+/// Result of the last check whether the primary node is reachable
+#[derive(Debug, Default)]
+struct PrimaryProbe(Mutex<Option<(Instant, bool)>>);
+
+impl PrimaryProbe {
+    /// Returns whether the primary node was reachable, unless that was checked
+    /// too long ago
+    fn get(&self, now: Instant) -> Option<bool> {
+        let probe = *self.0.lock().expect("poisoned");
+        probe
+            .filter(|(checked_at, _)| {
+                now.saturating_duration_since(*checked_at) < PRIMARY_PROBE_INTERVAL
+            })
+            .map(|(_, reachable)| reachable)
+    }
+
+    fn set(&self, now: Instant, reachable: bool) {
+        *self.0.lock().expect("poisoned") = Some((now, reachable));
+    }
+}
+
+/// Lightning client backed by a primary and a standby node, e.g. two LND
+/// instances with their own channels.
+///
+/// Both nodes intercept HTLCs for the gateway and every intercepted HTLC is
+/// completed on the node it arrived at, so incoming payments keep working
+/// while either node is down. Outgoing payments, invoices and route hints are
+/// served by the primary node and fail over to the standby node while the
+/// primary one is unreachable. Managing channels and on-chain funds always
+/// targets the primary node, since it operates on a specific node's funds.
+///
+/// A payment is only handed to the standby node if the primary node could not
+/// be reached before dispatching it. The chosen node is persisted per payment
+/// hash, so the idempotent retries of the payment state machines always reach
+/// the node the payment may already be in-flight on and a payment is never
+/// attempted on both nodes.
+#[derive(Debug)]
+pub struct FailoverLnRpcClient {
+    primary: LightningNode,
+    standby: LightningNode,
+    gateway_db: Database,
+    intercepted_htlcs: Arc<InterceptedHtlcs>,
+    primary_probe: PrimaryProbe,
+}
+
+impl FailoverLnRpcClient {
+    pub fn new(
+        primary: Box<dyn ILnRpcClient>,
+        standby: Box<dyn ILnRpcClient>,
+        gateway_db: Database,
+    ) -> Self {
+        Self {
+            primary: LightningNode::Idle(primary),
+            standby: LightningNode::Idle(standby),
+            gateway_db,
+            intercepted_htlcs: Arc::default(),
+            primary_probe: PrimaryProbe::default(),
+        }
+    }
+
+    fn node(&self, backend: LightningBackend) -> Result<&dyn ILnRpcClient, LightningRpcError> {
+        match backend {
+            LightningBackend::Primary => self.primary.client(),
+            LightningBackend::Standby => self.standby.client(),
+        }
+        .ok_or(LightningRpcError::FailedToConnect)
+    }
+
+    /// Returns the primary node if it is reachable, otherwise the standby node
+    async fn available_backend(&self) -> Result<LightningBackend, LightningRpcError> {
+        if let Ok(primary) = self.node(LightningBackend::Primary) {
+            let now = Instant::now();
+            let reachable = match self.primary_probe.get(now) {
+                Some(reachable) => reachable,
+                None => {
+                    let reachable = match primary.info().await {
+                        Ok(_) => true,
+                        Err(e) => {
+                            warn!(
+                                ?e,
+                                "Primary lightning node is unreachable, using standby node"
+                            );
+                            false
+                        }
+                    };
+                    self.primary_probe.set(now, reachable);
+                    reachable
+                }
+            };
+
+            if reachable {
+                return Ok(LightningBackend::Primary);
+            }
+        }
+
+        self.node(LightningBackend::Standby)?;
+        Ok(LightningBackend::Standby)
+    }
+
+    /// Returns the node the payment with the given hash was dispatched to, or
+    /// selects and pins one if it is a new payment
+    async fn payment_backend(
+        &self,
+        payment_hash: sha256::Hash,
+    ) -> Result<LightningBackend, LightningRpcError> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if let Some(backend) = dbtx.load_outgoing_payment_backend(payment_hash).await {
+            return Ok(backend);
+        }
+
+        let backend = self.available_backend().await?;
+        dbtx.save_outgoing_payment_backend(payment_hash, backend)
+            .await;
+        dbtx.commit_tx_result()
+            .await
+            .map_err(|e| LightningRpcError::FailedPayment {
+                failure_reason: format!("Failed to persist payment backend: {e:?}"),
+            })?;
+
+        if backend == LightningBackend::Standby {
+            info!(%payment_hash, "Dispatching payment to standby lightning node");
+        }
+
+        Ok(backend)
+    }
+
+    /// Starts intercepting HTLCs on the node, returning an empty stream if
+    /// the node cannot be reached
+    async fn route_node_htlcs<'a>(
+        node: LightningNode,
+        backend: LightningBackend,
+        task_group: &TaskGroup,
+        intercepted_htlcs: Arc<InterceptedHtlcs>,
+    ) -> (RouteHtlcStream<'a>, LightningNode) {
+        let client = match node {
+            LightningNode::Idle(client) => client,
+            node => return (futures::stream::empty().boxed(), node),
+        };
+
+        match client.route_htlcs(task_group).await {
+            Ok((stream, client)) => {
+                let stream = stream
+                    .map(move |htlc| {
+                        intercepted_htlcs.insert(
+                            (htlc.incoming_chan_id, htlc.htlc_id),
+                            backend,
+                            Instant::now(),
+                        );
+                        htlc
+                    })
+                    .boxed();
+                (stream, LightningNode::Routing(client))
+            }
+            Err(e) => {
+                warn!(?e, ?backend, "Failed to intercept HTLCs on lightning node");
+                (futures::stream::empty().boxed(), LightningNode::Unavailable)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ILnRpcClient for FailoverLnRpcClient {
+    async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
+        if let Ok(primary) = self.node(LightningBackend::Primary) {
+            match primary.info().await {
+                Ok(info) => return Ok(info),
+                Err(e) => warn!(
+                    ?e,
+                    "Primary lightning node is unreachable, using standby node"
+                ),
+            }
+        }
+
+        self.node(LightningBackend::Standby)?.info().await
+    }
+
+    async fn routehints(
+        &self,
+        num_route_hints: usize,
+    ) -> Result<GetRouteHintsResponse, LightningRpcError> {
+        let backend = self.available_backend().await?;
+        self.node(backend)?.routehints(num_route_hints).await
+    }
+
+    async fn pay(
+        &self,
+        invoice: Bolt11Invoice,
+        max_delay: u64,
+        max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let backend = self.payment_backend(*invoice.payment_hash()).await?;
+        self.node(backend)?.pay(invoice, max_delay, max_fee).await
+    }
+
+    async fn pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let backend = self.payment_backend(invoice.payment_hash).await?;
+        self.node(backend)?
+            .pay_private(invoice, max_delay, max_fee)
+            .await
+    }
+
+    fn supports_private_payments(&self) -> bool {
+        [&self.primary, &self.standby]
+            .into_iter()
+            .filter_map(LightningNode::client)
+            .all(|client| client.supports_private_payments())
+    }
+
+    async fn pay_private_to_self(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let backend = self.payment_backend(invoice.payment_hash).await?;
+        self.node(backend)?
+            .pay_private_to_self(invoice, max_delay, max_fee)
+            .await
+    }
+
+    fn supports_self_payments(&self) -> bool {
+        [&self.primary, &self.standby]
+            .into_iter()
+            .filter_map(LightningNode::client)
+            .all(|client| client.supports_self_payments())
+    }
+
+    async fn pay_offer(
+        &self,
+        offer: Offer,
+        amount: Option<Amount>,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let backend = self.available_backend().await?;
+        self.node(backend)?.pay_offer(offer, amount).await
+    }
+
+    fn supports_offers(&self) -> bool {
+        [&self.primary, &self.standby]
+            .into_iter()
+            .filter_map(LightningNode::client)
+            .all(|client| client.supports_offers())
+    }
+
+    async fn route_htlcs<'a>(
+        self: Box<Self>,
+        task_group: &TaskGroup,
+    ) -> Result<(RouteHtlcStream<'a>, Arc<dyn ILnRpcClient>), LightningRpcError> {
+        let Self {
+            primary,
+            standby,
+            gateway_db,
+            ..
+        } = *self;
+        let intercepted_htlcs = Arc::<InterceptedHtlcs>::default();
+
+        let (primary_stream, primary) = Self::route_node_htlcs(
+            primary,
+            LightningBackend::Primary,
+            task_group,
+            intercepted_htlcs.clone(),
+        )
+        .await;
+        let (standby_stream, standby) = Self::route_node_htlcs(
+            standby,
+            LightningBackend::Standby,
+            task_group,
+            intercepted_htlcs.clone(),
+        )
+        .await;
+
+        if primary.client().is_none() && standby.client().is_none() {
+            return Err(LightningRpcError::FailedToRouteHtlcs {
+                failure_reason: "Neither the primary nor the standby node is reachable".to_string(),
+            });
+        }
+
+        let stream = futures::stream::select(primary_stream, standby_stream).boxed();
+        let client = Arc::new(Self {
+            primary,
+            standby,
+            gateway_db,
+            intercepted_htlcs,
+            primary_probe: PrimaryProbe::default(),
+        });
+
+        Ok((stream, client))
+    }
+
+    async fn complete_htlc(&self, htlc: InterceptPaymentResponse) -> Result<(), LightningRpcError> {
+        let backend = self
+            .intercepted_htlcs
+            .remove((htlc.incoming_chan_id, htlc.htlc_id));
+
+        match backend {
+            Some(backend) => self.node(backend)?.complete_htlc(htlc).await,
+            // Not intercepted through our stream, e.g. a held HTLC that is retried
+            // after a restart, so try both nodes
+            None => match self.node(LightningBackend::Primary) {
+                Ok(primary) => match primary.complete_htlc(htlc.clone()).await {
+                    Ok(()) => Ok(()),
+                    Err(e) => match self.node(LightningBackend::Standby) {
+                        Ok(standby) => standby.complete_htlc(htlc).await,
+                        Err(_) => Err(e),
+                    },
+                },
+                Err(_) => {
+                    self.node(LightningBackend::Standby)?
+                        .complete_htlc(htlc)
+                        .await
+                }
+            },
+        }
+    }
+
+    async fn create_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        let backend = self.available_backend().await?;
+        self.node(backend)?
+            .create_invoice(create_invoice_request)
+            .await
+    }
+
+    async fn get_ln_onchain_address(
+        &self,
+    ) -> Result<GetLnOnchainAddressResponse, LightningRpcError> {
+        self.node(LightningBackend::Primary)?
+            .get_ln_onchain_address()
+            .await
+    }
+
+    async fn send_onchain(
+        &self,
+        payload: SendOnchainPayload,
+    ) -> Result<SendOnchainResponse, LightningRpcError> {
+        self.node(LightningBackend::Primary)?
+            .send_onchain(payload)
+            .await
+    }
+
+    async fn open_channel(
+        &self,
+        payload: OpenChannelPayload,
+    ) -> Result<OpenChannelResponse, LightningRpcError> {
+        self.node(LightningBackend::Primary)?
+            .open_channel(payload)
+            .await
+    }
+
+    async fn close_channels_with_peer(
+        &self,
+        payload: CloseChannelsWithPeerPayload,
+    ) -> Result<CloseChannelsWithPeerResponse, LightningRpcError> {
+        self.node(LightningBackend::Primary)?
+            .close_channels_with_peer(payload)
+            .await
+    }
+
+    async fn list_active_channels(&self) -> Result<ListActiveChannelsResponse, LightningRpcError> {
+        self.node(LightningBackend::Primary)?
+            .list_active_channels()
+            .await
+    }
+
+    async fn get_balances(&self) -> Result<GetBalancesResponse, LightningRpcError> {
+        self.node(LightningBackend::Primary)?.get_balances().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        InterceptedHtlcs, LightningBackend, PrimaryProbe, INTERCEPTED_HTLC_MAX_AGE,
+        PRIMARY_PROBE_INTERVAL,
+    };
+
+    #[test]
+    fn intercepted_htlcs_are_completed_on_their_node() {
+        let htlcs = InterceptedHtlcs::default();
+        let now = Instant::now();
+        htlcs.insert((1, 0), LightningBackend::Primary, now);
+        htlcs.insert((2, 0), LightningBackend::Standby, now);
+
+        assert_eq!(htlcs.remove((2, 0)), Some(LightningBackend::Standby));
+        assert_eq!(htlcs.remove((2, 0)), None);
+        assert_eq!(htlcs.remove((1, 0)), Some(LightningBackend::Primary));
+    }
+
+    #[test]
+    fn expired_intercepted_htlcs_are_pruned() {
+        let htlcs = InterceptedHtlcs::default();
+        let start = Instant::now();
+        htlcs.insert((1, 0), LightningBackend::Primary, start);
+        htlcs.insert(
+            (2, 0),
+            LightningBackend::Standby,
+            start + INTERCEPTED_HTLC_MAX_AGE - Duration::from_secs(1),
+        );
+        htlcs.insert(
+            (3, 0),
+            LightningBackend::Primary,
+            start + INTERCEPTED_HTLC_MAX_AGE,
+        );
+
+        assert_eq!(htlcs.remove((1, 0)), None);
+        assert_eq!(htlcs.remove((2, 0)), Some(LightningBackend::Standby));
+        assert_eq!(htlcs.remove((3, 0)), Some(LightningBackend::Primary));
+    }
+
+    #[test]
+    fn primary_probe_is_reused_within_interval() {
+        let probe = PrimaryProbe::default();
+        let start = Instant::now();
+        assert_eq!(probe.get(start), None);
+
+        probe.set(start, false);
+        assert_eq!(probe.get(start), Some(false));
+        assert_eq!(
+            probe.get(start + PRIMARY_PROBE_INTERVAL - Duration::from_millis(1)),
+            Some(false)
+        );
+        assert_eq!(probe.get(start + PRIMARY_PROBE_INTERVAL), None);
+
+        probe.set(start + PRIMARY_PROBE_INTERVAL, true);
+        assert_eq!(probe.get(start + PRIMARY_PROBE_INTERVAL), Some(true));
+    }
+}
@@ -11,7 +11,7 @@
 use fedimint_core::envs::{is_env_var_set, BitcoinRpcConfig};
 use fedimint_core::task::{block_in_place, TaskGroup, TaskHandle};
 use fedimint_core::util::SafeUrl;
-use fedimint_core::{Amount, BitcoinAmountOrAll};
+use fedimint_core::{Amount, BitcoinAmountOrAll, Msats, Sats};
 use fedimint_ln_common::contracts::Preimage;
 use ldk_node::lightning::ln::msgs::SocketAddress;
 use ldk_node::lightning::ln::PaymentHash;
@@ -19,6 +19,7 @@
 use ldk_node::payment::{PaymentKind, PaymentStatus, SendingParameters};
 use lightning::ln::channelmanager::PaymentId;
 use lightning::ln::PaymentPreimage;
+use lightning::offers::offer::Offer;
 use lightning::util::scid_utils::scid_from_parts;
 use lightning_invoice::Bolt11Invoice;
 use tokio::sync::mpsc::Sender;
@@ -244,6 +245,46 @@ async fn outpoint_to_scid(&self, funding_txo: OutPoint) -> anyhow::Result<u64> {
         scid_from_parts(block_height, u64::from(tx_index), u64::from(output_index))
             .map_err(|e| anyhow::anyhow!("Failed to convert to short channel ID: {e:?}"))
     }
+
+    /// Waits for an outgoing payment initiated by the node to complete,
+    /// returning its preimage
+    async fn await_payment(
+        &self,
+        payment_id: PaymentId,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        // TODO: Find a way to avoid looping/polling to know when a payment is
+        // completed. `ldk-node` provides `PaymentSuccessful` and `PaymentFailed`
+        // events, but interacting with the node event queue here isn't
+        // straightforward.
+        loop {
+            if let Some(payment_details) = self.node.payment(&payment_id) {
+                match payment_details.status {
+                    PaymentStatus::Pending => {}
+                    PaymentStatus::Succeeded => {
+                        if let PaymentKind::Bolt11 {
+                            preimage: Some(preimage),
+                            ..
+                        }
+                        | PaymentKind::Bolt12Offer {
+                            preimage: Some(preimage),
+                            ..
+                        } = payment_details.kind
+                        {
+                            return Ok(PayInvoiceResponse {
+                                preimage: Preimage(preimage.0),
+                            });
+                        }
+                    }
+                    PaymentStatus::Failed => {
+                        return Err(LightningRpcError::FailedPayment {
+                            failure_reason: "LDK payment failed".to_string(),
+                        });
+                    }
+                }
+            }
+            fedimint_core::runtime::sleep(Duration::from_millis(100)).await;
+        }
+    }
 }
 
 impl Drop for GatewayLdkClient {
@@ -359,34 +400,31 @@ async fn pay(
             );
         }
 
-        // TODO: Find a way to avoid looping/polling to know when a payment is
-        // completed. `ldk-node` provides `PaymentSuccessful` and `PaymentFailed`
-        // events, but interacting with the node event queue here isn't
-        // straightforward.
-        loop {
-            if let Some(payment_details) = self.node.payment(&payment_id) {
-                match payment_details.status {
-                    PaymentStatus::Pending => {}
-                    PaymentStatus::Succeeded => {
-                        if let PaymentKind::Bolt11 {
-                            preimage: Some(preimage),
-                            ..
-                        } = payment_details.kind
-                        {
-                            return Ok(PayInvoiceResponse {
-                                preimage: Preimage(preimage.0),
-                            });
-                        }
-                    }
-                    PaymentStatus::Failed => {
-                        return Err(LightningRpcError::FailedPayment {
-                            failure_reason: "LDK payment failed".to_string(),
-                        });
-                    }
-                }
+        self.await_payment(payment_id).await
+    }
+
+    async fn pay_offer(
+        &self,
+        offer: Offer,
+        amount: Option<Amount>,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let payment_id = match amount {
+            Some(amount) => {
+                self.node
+                    .bolt12_payment()
+                    .send_using_amount(&offer, amount.msats, None, None)
             }
-            fedimint_core::runtime::sleep(Duration::from_millis(100)).await;
+            None => self.node.bolt12_payment().send(&offer, None, None),
         }
+        .map_err(|e| LightningRpcError::FailedPayment {
+            failure_reason: format!("LDK offer payment failed to initialize: {e:?}"),
+        })?;
+
+        self.await_payment(payment_id).await
+    }
+
+    fn supports_offers(&self) -> bool {
+        true
     }
 
     async fn route_htlcs<'a>(
@@ -536,10 +574,10 @@ async fn open_channel(
             push_amount_sats,
         }: OpenChannelPayload,
     ) -> Result<OpenChannelResponse, LightningRpcError> {
-        let push_amount_msats_or = if push_amount_sats == 0 {
+        let push_amount_msats_or = if push_amount_sats == Sats::ZERO {
             None
         } else {
-            Some(push_amount_sats * 1000)
+            Some(push_amount_sats.to_msats().msats)
         };
 
         let user_channel_id = self
@@ -551,7 +589,7 @@ async fn open_channel(
                         failure_reason: e.to_string(),
                     }
                 })?,
-                channel_size_sats,
+                channel_size_sats.0,
                 push_amount_msats_or,
                 None,
             )
@@ -620,9 +658,11 @@ async fn list_active_channels(&self) -> Result<ListActiveChannelsResponse, Light
         {
             channels.push(ChannelInfo {
                 remote_pubkey: channel_details.counterparty_node_id,
-                channel_size_sats: channel_details.channel_value_sats,
-                outbound_liquidity_sats: channel_details.outbound_capacity_msat / 1000,
-                inbound_liquidity_sats: channel_details.inbound_capacity_msat / 1000,
+                channel_size_sats: Sats(channel_details.channel_value_sats),
+                outbound_liquidity_sats: Msats(channel_details.outbound_capacity_msat)
+                    .to_sats_round_down(),
+                inbound_liquidity_sats: Msats(channel_details.inbound_capacity_msat)
+                    .to_sats_round_down(),
                 short_channel_id: match channel_details.funding_txo {
                     Some(funding_txo) => self.outpoint_to_scid(funding_txo).await.unwrap_or(0),
                     None => 0,
@@ -642,14 +682,14 @@ async fn get_balances(&self) -> Result<GetBalancesResponse, LightningRpcError> {
             .filter(|chan| chan.is_usable)
             .collect::<Vec<_>>();
         // map and get the total inbound_capacity_msat in the channels
-        let total_inbound_liquidity_balance_msat: u64 = channel_lists
+        let total_inbound_liquidity_balance_msat: Msats = channel_lists
             .iter()
-            .map(|channel| channel.inbound_capacity_msat)
+            .map(|channel| Msats(channel.inbound_capacity_msat))
             .sum();
 
         Ok(GetBalancesResponse {
-            onchain_balance_sats: balances.total_onchain_balance_sats,
-            lightning_balance_msats: balances.total_lightning_balance_sats * 1000,
+            onchain_balance_sats: Sats(balances.total_onchain_balance_sats),
+            lightning_balance_msats: Sats(balances.total_lightning_balance_sats).into(),
             inbound_lightning_liquidity_msats: total_inbound_liquidity_balance_msat,
         })
     }
@@ -9,7 +9,7 @@
 use bitcoin::hashes::{sha256, Hash};
 use fedimint_core::db::Database;
 use fedimint_core::task::{sleep, TaskGroup};
-use fedimint_core::{secp256k1, Amount, BitcoinAmountOrAll};
+use fedimint_core::{secp256k1, Amount, BitcoinAmountOrAll, Msats, Sats};
 use fedimint_ln_common::contracts::Preimage;
 use fedimint_ln_common::route_hints::{RouteHint, RouteHintHop};
 use fedimint_ln_common::PrunedInvoice;
@@ -43,8 +43,8 @@
 use tracing::{debug, error, info, trace, warn};
 
 use super::{
-    ChannelInfo, ILnRpcClient, LightningRpcError, ListActiveChannelsResponse, RouteHtlcStream,
-    MAX_LIGHTNING_RETRIES,
+    ChannelInfo, ILnRpcClient, LightningRpcError, ListActiveChannelsResponse, PaymentTraceId,
+    RouteHtlcStream, MAX_LIGHTNING_RETRIES,
 };
 use crate::db::GatewayDbtxNcExt;
 use crate::lightning::{
@@ -59,6 +59,10 @@
 
 const LND_PAYMENT_TIMEOUT_SECONDS: i32 = 180;
 
+/// gRPC metadata carrying the [`PaymentTraceId`] of the payment a call to LND
+/// concerns, visible to RPC middleware and proxies in front of LND
+const TRACE_ID_METADATA_KEY: &str = "fedimint-trace-id";
+
 #[derive(Clone)]
 pub struct GatewayLndClient {
     /// LND client
@@ -506,10 +510,13 @@ async fn lookup_payment(
         loop {
             let payments = client
                 .router()
-                .track_payment_v2(TrackPaymentRequest {
-                    payment_hash: payment_hash.clone(),
-                    no_inflight_updates: true,
-                })
+                .track_payment_v2(traced_request(
+                    TrackPaymentRequest {
+                        payment_hash: payment_hash.clone(),
+                        no_inflight_updates: true,
+                    },
+                    &payment_hash,
+                ))
                 .await;
 
             match payments {
@@ -581,9 +588,12 @@ async fn settle_hold_invoice(
 
         client
             .invoices()
-            .settle_invoice(SettleInvoiceMsg {
-                preimage: preimage.0.to_vec(),
-            })
+            .settle_invoice(traced_request(
+                SettleInvoiceMsg {
+                    preimage: preimage.0.to_vec(),
+                },
+                &payment_hash,
+            ))
             .await
             .map_err(|e| {
                 error!(
@@ -623,9 +633,12 @@ async fn cancel_hold_invoice(&self, payment_hash: Vec<u8>) -> Result<(), Lightni
 
         client
             .invoices()
-            .cancel_invoice(CancelInvoiceMsg {
-                payment_hash: payment_hash.clone(),
-            })
+            .cancel_invoice(traced_request(
+                CancelInvoiceMsg {
+                    payment_hash: payment_hash.clone(),
+                },
+                &payment_hash,
+            ))
             .await
             .map_err(|e| {
                 error!(
@@ -640,129 +653,15 @@ async fn cancel_hold_invoice(&self, payment_hash: Vec<u8>) -> Result<(), Lightni
 
         Ok(())
     }
-}
 
-impl fmt::Debug for GatewayLndClient {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "LndClient")
-    }
-}
-
-#[async_trait]
-impl ILnRpcClient for GatewayLndClient {
-    async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
-        let info = client
-            .lightning()
-            .get_info(GetInfoRequest {})
-            .await
-            .map_err(|status| LightningRpcError::FailedToGetNodeInfo {
-                failure_reason: format!("Failed to get node info {status:?}"),
-            })?
-            .into_inner();
-
-        let pub_key: PublicKey =
-            info.identity_pubkey
-                .parse()
-                .map_err(|e| LightningRpcError::FailedToGetNodeInfo {
-                    failure_reason: format!("Failed to parse public key {e:?}"),
-                })?;
-
-        let network = match info
-            .chains
-            .first()
-            .ok_or_else(|| LightningRpcError::FailedToGetNodeInfo {
-                failure_reason: "Failed to parse node network".to_string(),
-            })?
-            .network
-            .as_str()
-        {
-            // LND uses "mainnet", but rust-bitcoin uses "bitcoin".
-            // TODO: create a fedimint `Network` type that understands "mainnet"
-            "mainnet" => "bitcoin",
-            other => other,
-        }
-        .to_string();
-
-        return Ok(GetNodeInfoResponse {
-            pub_key,
-            alias: info.alias,
-            network,
-            block_height: info.block_height,
-            synced_to_chain: info.synced_to_chain,
-        });
-    }
-
-    async fn routehints(
-        &self,
-        num_route_hints: usize,
-    ) -> Result<GetRouteHintsResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
-        let mut channels = client
-            .lightning()
-            .list_channels(ListChannelsRequest {
-                active_only: true,
-                inactive_only: false,
-                public_only: false,
-                private_only: false,
-                peer: vec![],
-            })
-            .await
-            .map_err(|status| LightningRpcError::FailedToGetRouteHints {
-                failure_reason: format!("Failed to list channels {status:?}"),
-            })?
-            .into_inner()
-            .channels;
-
-        // Take the channels with the largest incoming capacity
-        channels.sort_by(|a, b| b.remote_balance.cmp(&a.remote_balance));
-        channels.truncate(num_route_hints);
-
-        let mut route_hints: Vec<RouteHint> = vec![];
-        for chan in &channels {
-            let info = client
-                .lightning()
-                .get_chan_info(ChanInfoRequest {
-                    chan_id: chan.chan_id,
-                })
-                .await
-                .map_err(|status| LightningRpcError::FailedToGetRouteHints {
-                    failure_reason: format!("Failed to get channel info {status:?}"),
-                })?
-                .into_inner();
-
-            let Some(policy) = info.node1_policy.clone() else {
-                continue;
-            };
-            let src_node_id =
-                PublicKey::from_str(&chan.remote_pubkey).expect("Failed to parse pubkey");
-            let short_channel_id = chan.chan_id;
-            let base_msat = policy.fee_base_msat as u32;
-            let proportional_millionths = policy.fee_rate_milli_msat as u32;
-            let cltv_expiry_delta = policy.time_lock_delta;
-            let htlc_maximum_msat = Some(policy.max_htlc_msat);
-            let htlc_minimum_msat = Some(policy.min_htlc as u64);
-
-            let route_hint_hop = RouteHintHop {
-                src_node_id,
-                short_channel_id,
-                base_msat,
-                proportional_millionths,
-                cltv_expiry_delta: cltv_expiry_delta as u16,
-                htlc_minimum_msat,
-                htlc_maximum_msat,
-            };
-            route_hints.push(RouteHint(vec![route_hint_hop]));
-        }
-
-        Ok(GetRouteHintsResponse { route_hints })
-    }
-
-    async fn pay_private(
+    /// Pays `invoice`, over circular routes back to our own node only if
+    /// `allow_self_payment` is set
+    async fn pay_private_inner(
         &self,
         invoice: PrunedInvoice,
         max_delay: u64,
         max_fee: Amount,
+        allow_self_payment: bool,
     ) -> Result<PayInvoiceResponse, LightningRpcError> {
         let payment_hash = invoice.payment_hash.to_byte_array().to_vec();
         info!(
@@ -832,20 +731,24 @@ async fn pay_private(
             );
             let payments = client
                 .router()
-                .send_payment_v2(SendPaymentRequest {
-                    amt_msat,
-                    dest: invoice.destination.serialize().to_vec(),
-                    dest_features,
-                    payment_hash: invoice.payment_hash.to_byte_array().to_vec(),
-                    payment_addr: invoice.payment_secret.to_vec(),
-                    route_hints: route_hints_to_lnd(&invoice.route_hints),
-                    final_cltv_delta,
-                    cltv_limit,
-                    no_inflight_updates: false,
-                    timeout_seconds: LND_PAYMENT_TIMEOUT_SECONDS,
-                    fee_limit_msat,
-                    ..Default::default()
-                })
+                .send_payment_v2(traced_request(
+                    SendPaymentRequest {
+                        amt_msat,
+                        dest: invoice.destination.serialize().to_vec(),
+                        dest_features,
+                        payment_hash: invoice.payment_hash.to_byte_array().to_vec(),
+                        payment_addr: invoice.payment_secret.to_vec(),
+                        route_hints: route_hints_to_lnd(&invoice.route_hints),
+                        final_cltv_delta,
+                        cltv_limit,
+                        no_inflight_updates: false,
+                        timeout_seconds: LND_PAYMENT_TIMEOUT_SECONDS,
+                        fee_limit_msat,
+                        allow_self_payment,
+                        ..Default::default()
+                    },
+                    &payment_hash,
+                ))
                 .await
                 .map_err(|status| {
                     error!(
@@ -923,6 +826,143 @@ async fn pay_private(
             preimage: Preimage(preimage.try_into().expect("Failed to create preimage")),
         })
     }
+}
+
+impl fmt::Debug for GatewayLndClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LndClient")
+    }
+}
+
+#[async_trait]
+impl ILnRpcClient for GatewayLndClient {
+    async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
+        let mut client = self.connect().await?;
+        let info = client
+            .lightning()
+            .get_info(GetInfoRequest {})
+            .await
+            .map_err(|status| LightningRpcError::FailedToGetNodeInfo {
+                failure_reason: format!("Failed to get node info {status:?}"),
+            })?
+            .into_inner();
+
+        let pub_key: PublicKey =
+            info.identity_pubkey
+                .parse()
+                .map_err(|e| LightningRpcError::FailedToGetNodeInfo {
+                    failure_reason: format!("Failed to parse public key {e:?}"),
+                })?;
+
+        let network = match info
+            .chains
+            .first()
+            .ok_or_else(|| LightningRpcError::FailedToGetNodeInfo {
+                failure_reason: "Failed to parse node network".to_string(),
+            })?
+            .network
+            .as_str()
+        {
+            // LND uses "mainnet", but rust-bitcoin uses "bitcoin".
+            // TODO: create a fedimint `Network` type that understands "mainnet"
+            "mainnet" => "bitcoin",
+            other => other,
+        }
+        .to_string();
+
+        return Ok(GetNodeInfoResponse {
+            pub_key,
+            alias: info.alias,
+            network,
+            block_height: info.block_height,
+            synced_to_chain: info.synced_to_chain,
+        });
+    }
+
+    async fn routehints(
+        &self,
+        num_route_hints: usize,
+    ) -> Result<GetRouteHintsResponse, LightningRpcError> {
+        let mut client = self.connect().await?;
+        let mut channels = client
+            .lightning()
+            .list_channels(ListChannelsRequest {
+                active_only: true,
+                inactive_only: false,
+                public_only: false,
+                private_only: false,
+                peer: vec![],
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToGetRouteHints {
+                failure_reason: format!("Failed to list channels {status:?}"),
+            })?
+            .into_inner()
+            .channels;
+
+        // Take the channels with the largest incoming capacity
+        channels.sort_by(|a, b| b.remote_balance.cmp(&a.remote_balance));
+        channels.truncate(num_route_hints);
+
+        let mut route_hints: Vec<RouteHint> = vec![];
+        for chan in &channels {
+            let info = client
+                .lightning()
+                .get_chan_info(ChanInfoRequest {
+                    chan_id: chan.chan_id,
+                })
+                .await
+                .map_err(|status| LightningRpcError::FailedToGetRouteHints {
+                    failure_reason: format!("Failed to get channel info {status:?}"),
+                })?
+                .into_inner();
+
+            let Some(policy) = info.node1_policy.clone() else {
+                continue;
+            };
+            let src_node_id =
+                PublicKey::from_str(&chan.remote_pubkey).expect("Failed to parse pubkey");
+            let short_channel_id = chan.chan_id;
+            let base_msat = policy.fee_base_msat as u32;
+            let proportional_millionths = policy.fee_rate_milli_msat as u32;
+            let cltv_expiry_delta = policy.time_lock_delta;
+            let htlc_maximum_msat = Some(policy.max_htlc_msat);
+            let htlc_minimum_msat = Some(policy.min_htlc as u64);
+
+            let route_hint_hop = RouteHintHop {
+                src_node_id,
+                short_channel_id,
+                base_msat,
+                proportional_millionths,
+                cltv_expiry_delta: cltv_expiry_delta as u16,
+                htlc_minimum_msat,
+                htlc_maximum_msat,
+            };
+            route_hints.push(RouteHint(vec![route_hint_hop]));
+        }
+
+        Ok(GetRouteHintsResponse { route_hints })
+    }
+
+    async fn pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        self.pay_private_inner(invoice, max_delay, max_fee, false)
+            .await
+    }
+
+    async fn pay_private_to_self(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        self.pay_private_inner(invoice, max_delay, max_fee, true)
+            .await
+    }
 
     /// Returns true if the lightning backend supports payments without full
     /// invoices
@@ -930,6 +970,10 @@ fn supports_private_payments(&self) -> bool {
         true
     }
 
+    fn supports_self_payments(&self) -> bool {
+        true
+    }
+
     async fn route_htlcs<'a>(
         self: Box<Self>,
         task_group: &TaskGroup,
@@ -1203,8 +1247,8 @@ async fn open_channel(
             .lightning()
             .open_channel_sync(OpenChannelRequest {
                 node_pubkey: pubkey.serialize().to_vec(),
-                local_funding_amount: channel_size_sats.try_into().expect("u64 -> i64"),
-                push_sat: push_amount_sats.try_into().expect("u64 -> i64"),
+                local_funding_amount: channel_size_sats.0.try_into().expect("u64 -> i64"),
+                push_sat: push_amount_sats.0.try_into().expect("u64 -> i64"),
                 ..Default::default()
             })
             .await
@@ -1327,9 +1371,9 @@ async fn list_active_channels(&self) -> Result<ListActiveChannelsResponse, Light
                         ChannelInfo {
                             remote_pubkey: PublicKey::from_str(&channel.remote_pubkey)
                                 .expect("Lightning node returned invalid remote channel pubkey"),
-                            channel_size_sats,
-                            outbound_liquidity_sats,
-                            inbound_liquidity_sats,
+                            channel_size_sats: Sats(channel_size_sats),
+                            outbound_liquidity_sats: Sats(outbound_liquidity_sats),
+                            inbound_liquidity_sats: Sats(inbound_liquidity_sats),
                             short_channel_id: channel.chan_id,
                         }
                     })
@@ -1382,15 +1426,32 @@ async fn get_balances(&self) -> Result<GetBalancesResponse, LightningRpcError> {
             total_inbound.msat - unsettled_inbound.msat - pending_inbound.msat;
 
         Ok(GetBalancesResponse {
-            onchain_balance_sats: (wallet_balance_response.total_balance
-                + wallet_balance_response.reserved_balance_anchor_chan)
-                as u64,
-            lightning_balance_msats,
-            inbound_lightning_liquidity_msats,
+            onchain_balance_sats: Sats(
+                (wallet_balance_response.total_balance
+                    + wallet_balance_response.reserved_balance_anchor_chan) as u64,
+            ),
+            lightning_balance_msats: Msats(lightning_balance_msats),
+            inbound_lightning_liquidity_msats: Msats(inbound_lightning_liquidity_msats),
         })
     }
 }
 
+/// Wraps `message` of a call concerning the payment with `payment_hash` in a
+/// request carrying its [`PaymentTraceId`]
+fn traced_request<T>(message: T, payment_hash: &[u8]) -> tonic_lnd::tonic::Request<T> {
+    let mut request = tonic_lnd::tonic::Request::new(message);
+    if let Ok(payment_hash) = sha256::Hash::from_slice(payment_hash) {
+        request.metadata_mut().insert(
+            TRACE_ID_METADATA_KEY,
+            PaymentTraceId::new(&payment_hash)
+                .to_string()
+                .parse()
+                .expect("Hex is valid metadata"),
+        );
+    }
+    request
+}
+
 fn route_hints_to_lnd(
     route_hints: &[fedimint_ln_common::route_hints::RouteHint],
 ) -> Vec<tonic_lnd::lnrpc::RouteHint> {
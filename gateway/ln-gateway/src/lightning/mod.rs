@@ -1,7 +1,8 @@
+pub mod failover;
 pub mod ldk;
 pub mod lnd;
 
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -16,20 +17,23 @@
 use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::task::TaskGroup;
 use fedimint_core::util::{backoff_util, retry, SafeUrl};
-use fedimint_core::{secp256k1, Amount};
+use fedimint_core::{secp256k1, Amount, Msats, Sats};
 use fedimint_ln_common::route_hints::RouteHint;
 use fedimint_ln_common::PrunedInvoice;
 use futures::stream::BoxStream;
 use ldk::GatewayLdkChainSourceConfig;
+use lightning::offers::offer::{Amount as OfferAmount, Offer};
 use lightning_invoice::Bolt11Invoice;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, info_span, warn};
 
+use self::failover::FailoverLnRpcClient;
 use self::lnd::GatewayLndClient;
 use crate::envs::{
     FM_GATEWAY_SKIP_WAIT_FOR_SYNC_ENV, FM_LDK_BITCOIND_RPC_URL, FM_LDK_ESPLORA_SERVER_URL,
-    FM_LDK_NETWORK, FM_LND_MACAROON_ENV, FM_LND_RPC_ADDR_ENV, FM_LND_TLS_CERT_ENV, FM_PORT_LDK,
+    FM_LDK_NETWORK, FM_LND_MACAROON_ENV, FM_LND_RPC_ADDR_ENV, FM_LND_STANDBY_MACAROON_ENV,
+    FM_LND_STANDBY_RPC_ADDR_ENV, FM_LND_STANDBY_TLS_CERT_ENV, FM_LND_TLS_CERT_ENV, FM_PORT_LDK,
 };
 use crate::rpc::{CloseChannelsWithPeerPayload, SendOnchainPayload};
 use crate::{OpenChannelPayload, Preimage};
@@ -161,6 +165,59 @@ fn supports_private_payments(&self) -> bool {
         false
     }
 
+    /// Like [`ILnRpcClient::pay_private`], but also pays over circular routes
+    /// back to our own node, which lightning nodes refuse by default. Only
+    /// used by liquidity probes, see [`crate::Gateway::handle_probe_msg`]. If
+    /// this is implemented, [`ILnRpcClient::supports_self_payments`] must
+    /// return true.
+    async fn pay_private_to_self(
+        &self,
+        _invoice: PrunedInvoice,
+        _max_delay: u64,
+        _max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedPayment {
+            failure_reason: "Payments to self not supported".to_string(),
+        })
+    }
+
+    /// Returns true if the lightning backend can pay itself over circular
+    /// routes. LDK refuses to route payments to its own node, so only LND
+    /// supports this. If this returns true,
+    /// [`ILnRpcClient::pay_private_to_self`] must be implemented.
+    fn supports_self_payments(&self) -> bool {
+        false
+    }
+
+    /// Attempts to pay a BOLT12 offer using the lightning node, waiting for the
+    /// payment to complete and returning the preimage. The node requests an
+    /// invoice from the offer's issuer and pays it over the blinded paths it
+    /// contains, so neither the offer nor the invoice reveal the recipient's
+    /// node. `amount` is required if the offer doesn't specify one.
+    ///
+    /// Unlike [`ILnRpcClient::pay`] this is not idempotent, since every call
+    /// requests a new invoice from the issuer. If this is implemented,
+    /// [`ILnRpcClient::supports_offers`] must return true.
+    async fn pay_offer(
+        &self,
+        _offer: Offer,
+        _amount: Option<Amount>,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedPayment {
+            failure_reason: "Paying offers not supported".to_string(),
+        })
+    }
+
+    /// Returns true if the lightning backend can pay BOLT12 offers. If this
+    /// returns true, [`ILnRpcClient::pay_offer`] must be implemented.
+    ///
+    /// Offers can only be paid by the operator: the invoice of an offer is
+    /// only requested while paying it, but the contracts of Fedimint clients
+    /// need its payment hash before they are funded.
+    fn supports_offers(&self) -> bool {
+        false
+    }
+
     /// Consumes the current client and returns a stream of intercepted HTLCs
     /// and a new client. `complete_htlc` must be called for all successfully
     /// intercepted HTLCs sent to the returned stream.
@@ -294,9 +351,9 @@ pub async fn wait_for_chain_sync(&self) -> std::result::Result<(), LightningRpcE
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChannelInfo {
     pub remote_pubkey: secp256k1::PublicKey,
-    pub channel_size_sats: u64,
-    pub outbound_liquidity_sats: u64,
-    pub inbound_liquidity_sats: u64,
+    pub channel_size_sats: Sats,
+    pub outbound_liquidity_sats: Sats,
+    pub inbound_liquidity_sats: Sats,
     pub short_channel_id: u64,
 }
 
@@ -315,6 +372,26 @@ pub enum LightningMode {
         /// LND macaroon file path
         #[arg(long = "lnd-macaroon", env = FM_LND_MACAROON_ENV)]
         lnd_macaroon: String,
+
+        /// RPC address of a standby LND node that takes over outgoing payments
+        /// while the primary one is unreachable
+        #[arg(
+            long = "lnd-standby-rpc-host",
+            env = FM_LND_STANDBY_RPC_ADDR_ENV,
+            requires_all = ["lnd_standby_tls_cert", "lnd_standby_macaroon"]
+        )]
+        #[serde(default)]
+        lnd_standby_rpc_addr: Option<String>,
+
+        /// Standby LND TLS cert file path
+        #[arg(long = "lnd-standby-tls-cert", env = FM_LND_STANDBY_TLS_CERT_ENV)]
+        #[serde(default)]
+        lnd_standby_tls_cert: Option<String>,
+
+        /// Standby LND macaroon file path
+        #[arg(long = "lnd-standby-macaroon", env = FM_LND_STANDBY_MACAROON_ENV)]
+        #[serde(default)]
+        lnd_standby_macaroon: Option<String>,
     },
     #[clap(name = "ldk")]
     Ldk {
@@ -361,13 +438,41 @@ async fn build(&self, runtime: Arc<tokio::runtime::Runtime>) -> Box<dyn ILnRpcCl
                 lnd_rpc_addr,
                 lnd_tls_cert,
                 lnd_macaroon,
-            } => Box::new(GatewayLndClient::new(
-                lnd_rpc_addr,
-                lnd_tls_cert,
-                lnd_macaroon,
-                None,
-                self.gateway_db.clone(),
-            )),
+                lnd_standby_rpc_addr,
+                lnd_standby_tls_cert,
+                lnd_standby_macaroon,
+            } => {
+                let primary = Box::new(GatewayLndClient::new(
+                    lnd_rpc_addr,
+                    lnd_tls_cert,
+                    lnd_macaroon,
+                    None,
+                    self.gateway_db.clone(),
+                ));
+
+                match (
+                    lnd_standby_rpc_addr,
+                    lnd_standby_tls_cert,
+                    lnd_standby_macaroon,
+                ) {
+                    (Some(rpc_addr), Some(tls_cert), Some(macaroon)) => {
+                        let standby = Box::new(GatewayLndClient::new(
+                            rpc_addr,
+                            tls_cert,
+                            macaroon,
+                            None,
+                            self.gateway_db.clone(),
+                        ));
+                        Box::new(FailoverLnRpcClient::new(
+                            primary,
+                            standby,
+                            self.gateway_db.clone(),
+                        ))
+                    }
+                    (None, None, None) => primary,
+                    _ => panic!("Standby LND node requires an RPC address, TLS cert and macaroon"),
+                }
+            }
             LightningMode::Ldk {
                 esplora_server_url,
                 bitcoind_rpc_url,
@@ -420,6 +525,36 @@ pub struct GetNodeInfoResponse {
     pub synced_to_chain: bool,
 }
 
+/// Short id of a payment through the gateway, attached to the gateway's logs
+/// and to the calls to the lightning node concerning the payment.
+///
+/// It's the first 8 bytes of the payment hash, so the intercepting, paying
+/// and completing side of a payment derive the same id without passing it
+/// along, and it can be searched for in the logs of the lightning node, which
+/// show full payment hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaymentTraceId([u8; 8]);
+
+impl PaymentTraceId {
+    pub fn new(payment_hash: &crate::sha256::Hash) -> Self {
+        let mut id = [0; 8];
+        id.copy_from_slice(&payment_hash.as_ref()[..8]);
+        PaymentTraceId(id)
+    }
+
+    /// Span to run the handling of the payment in, so every log line carries
+    /// the trace id
+    pub fn span(self) -> tracing::Span {
+        info_span!("payment", trace_id = %self)
+    }
+}
+
+impl Display for PaymentTraceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InterceptPaymentRequest {
     pub payment_hash: crate::sha256::Hash,
@@ -474,6 +609,29 @@ pub struct CreateInvoiceResponse {
     pub invoice: String,
 }
 
+/// Checks that `amount` can be used to pay `offer`: it is required if the
+/// offer doesn't specify an amount in bitcoin and must not be lower than the
+/// amount it does specify.
+pub fn check_offer_amount(offer: &Offer, amount: Option<Amount>) -> Result<(), LightningRpcError> {
+    let failure_reason = match (offer.amount(), amount) {
+        (Some(OfferAmount::Bitcoin { amount_msats }), Some(amount))
+            if amount.msats < amount_msats =>
+        {
+            format!(
+                "Amount {amount} is below the offer's amount of {}",
+                Amount::from_msats(amount_msats)
+            )
+        }
+        (Some(OfferAmount::Bitcoin { .. }), _) | (_, Some(_)) => return Ok(()),
+        (Some(OfferAmount::Currency { .. }), None) => {
+            "Offer is denominated in a fiat currency, an amount is required".to_string()
+        }
+        (None, None) => "Offer doesn't specify an amount, an amount is required".to_string(),
+    };
+
+    Err(LightningRpcError::FailedPayment { failure_reason })
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GetLnOnchainAddressResponse {
     pub address: String,
@@ -501,7 +659,48 @@ pub struct ListActiveChannelsResponse {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GetBalancesResponse {
-    pub onchain_balance_sats: u64,
-    pub lightning_balance_msats: u64,
-    pub inbound_lightning_liquidity_msats: u64,
+    pub onchain_balance_sats: Sats,
+    pub lightning_balance_msats: Msats,
+    pub inbound_lightning_liquidity_msats: Msats,
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::secp256k1::{Secp256k1, SecretKey};
+    use lightning::offers::offer::OfferBuilder;
+
+    use super::*;
+
+    fn offer(amount_msats: Option<u64>) -> Offer {
+        let signing_pubkey = SecretKey::from_slice(&[1; 32])
+            .expect("Valid secret key")
+            .public_key(&Secp256k1::new());
+        let builder = OfferBuilder::new(signing_pubkey);
+        match amount_msats {
+            Some(amount_msats) => builder.amount_msats(amount_msats).build(),
+            None => builder.build(),
+        }
+        .expect("Valid offer")
+    }
+
+    #[test]
+    fn offer_amount_is_checked() {
+        let offer_with_amount = offer(Some(1_000));
+        assert!(check_offer_amount(&offer_with_amount, None).is_ok());
+        assert!(check_offer_amount(&offer_with_amount, Some(Amount::from_msats(1_000))).is_ok());
+        assert!(check_offer_amount(&offer_with_amount, Some(Amount::from_msats(2_000))).is_ok());
+        assert!(check_offer_amount(&offer_with_amount, Some(Amount::from_msats(999))).is_err());
+
+        let offer_without_amount = offer(None);
+        assert!(check_offer_amount(&offer_without_amount, None).is_err());
+        assert!(check_offer_amount(&offer_without_amount, Some(Amount::from_msats(1))).is_ok());
+
+        // Offers are exchanged in their bech32 encoding
+        assert_eq!(
+            Offer::from_str(&offer_with_amount.to_string())
+                .expect("Valid offer")
+                .amount(),
+            offer_with_amount.amount()
+        );
+    }
 }
@@ -0,0 +1,17 @@
+use std::sync::LazyLock;
+
+use fedimint_metrics::prometheus::{register_int_gauge_with_registry, IntGauge};
+use fedimint_metrics::{opts, REGISTRY};
+
+/// Number of intercepted HTLCs currently held while waiting for their
+/// federation to become reachable again
+pub(crate) static HTLC_RETRY_QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge_with_registry!(
+        opts!(
+            "gateway_htlc_retry_queue_depth",
+            "Number of intercepted HTLCs waiting for their federation to become reachable"
+        ),
+        REGISTRY
+    )
+    .unwrap()
+});
@@ -0,0 +1,145 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use fedimint_core::config::FederationId;
+
+use crate::error::AdminGatewayError;
+
+/// Window over which federation registration attempts are rate limited
+const REGISTRATION_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Restricts which federations the gateway serves and how often new
+/// federations can be registered.
+///
+/// Without a policy any federation whose invite code reaches the admin API
+/// gets a client on the gateway. Operators can limit the gateway to an
+/// allowlist of federations, exclude federations with a denylist and cap the
+/// number of registration attempts per hour, so a leaked password can't be
+/// used to rapidly attach the gateway to arbitrary federations.
+#[derive(Debug, Default)]
+pub struct RegistrationPolicy {
+    /// If set, only these federations are served
+    allowlist: Option<BTreeSet<FederationId>>,
+    /// Federations that are never served, takes precedence over the allowlist
+    denylist: BTreeSet<FederationId>,
+    /// Maximum number of registration attempts per hour, unlimited if `None`
+    max_registrations_per_hour: Option<u32>,
+    /// Times of the registration attempts within the last hour
+    attempts: Mutex<VecDeque<Instant>>,
+}
+
+impl RegistrationPolicy {
+    pub fn new(
+        allowlist: Option<BTreeSet<FederationId>>,
+        denylist: BTreeSet<FederationId>,
+        max_registrations_per_hour: Option<u32>,
+    ) -> Self {
+        Self {
+            allowlist,
+            denylist,
+            max_registrations_per_hour,
+            attempts: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns true if the gateway is allowed to serve the federation
+    pub fn is_allowed(&self, federation_id: FederationId) -> bool {
+        !self.denylist.contains(&federation_id)
+            && self
+                .allowlist
+                .as_ref()
+                .map_or(true, |allowlist| allowlist.contains(&federation_id))
+    }
+
+    /// Records a registration attempt for `federation_id` and checks it
+    /// against the policy. Rejected attempts count towards the rate limit as
+    /// well, so the allowlist can't be probed quickly.
+    pub fn check_registration(&self, federation_id: FederationId) -> Result<(), AdminGatewayError> {
+        self.record_attempt(Instant::now())?;
+
+        if !self.is_allowed(federation_id) {
+            return Err(AdminGatewayError::FederationNotAllowed { federation_id });
+        }
+
+        Ok(())
+    }
+
+    fn record_attempt(&self, now: Instant) -> Result<(), AdminGatewayError> {
+        let Some(max_registrations_per_hour) = self.max_registrations_per_hour else {
+            return Ok(());
+        };
+
+        let mut attempts = self.attempts.lock().expect("poisoned");
+        while attempts
+            .front()
+            .is_some_and(|attempt| now.duration_since(*attempt) >= REGISTRATION_RATE_LIMIT_WINDOW)
+        {
+            attempts.pop_front();
+        }
+
+        if attempts.len() >= max_registrations_per_hour as usize {
+            return Err(AdminGatewayError::RegistrationRateLimited {
+                max_registrations_per_hour,
+            });
+        }
+
+        attempts.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::time::{Duration, Instant};
+
+    use bitcoin::hashes::{sha256, Hash};
+    use fedimint_core::config::FederationId;
+
+    use super::{RegistrationPolicy, REGISTRATION_RATE_LIMIT_WINDOW};
+
+    fn federation_id(byte: u8) -> FederationId {
+        FederationId(sha256::Hash::from_byte_array([byte; 32]))
+    }
+
+    #[test]
+    fn denylist_takes_precedence_over_allowlist() {
+        let allowed = federation_id(0);
+        let denied = federation_id(1);
+        let other = federation_id(2);
+
+        let policy = RegistrationPolicy::new(
+            Some(BTreeSet::from([allowed, denied])),
+            BTreeSet::from([denied]),
+            None,
+        );
+        assert!(policy.is_allowed(allowed));
+        assert!(!policy.is_allowed(denied));
+        assert!(!policy.is_allowed(other));
+
+        let policy = RegistrationPolicy::new(None, BTreeSet::from([denied]), None);
+        assert!(policy.is_allowed(other));
+        assert!(!policy.is_allowed(denied));
+    }
+
+    #[test]
+    fn rate_limits_registration_attempts() {
+        let policy = RegistrationPolicy::new(None, BTreeSet::new(), Some(2));
+        let start = Instant::now();
+
+        assert!(policy.record_attempt(start).is_ok());
+        assert!(policy
+            .record_attempt(start + Duration::from_secs(1))
+            .is_ok());
+        assert!(policy
+            .record_attempt(start + Duration::from_secs(2))
+            .is_err());
+        assert!(policy
+            .record_attempt(start + REGISTRATION_RATE_LIMIT_WINDOW)
+            .is_ok());
+        assert!(policy
+            .record_attempt(start + REGISTRATION_RATE_LIMIT_WINDOW)
+            .is_err());
+    }
+}
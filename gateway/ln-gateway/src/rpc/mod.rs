@@ -2,13 +2,16 @@
 pub mod rpc_server;
 
 use std::collections::BTreeMap;
+use std::time::SystemTime;
 
 use bitcoin::address::NetworkUnchecked;
 use bitcoin::{Address, Network};
 use fedimint_core::config::{FederationId, JsonClientConfig};
 use fedimint_core::core::{ModuleInstanceId, ModuleKind, OperationId};
-use fedimint_core::{secp256k1, Amount, BitcoinAmountOrAll};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::{secp256k1, Amount, BitcoinAmountOrAll, Msats, Sats};
 use fedimint_eventlog::{EventKind, EventLogId};
+use fedimint_ln_common::contracts::Preimage;
 use fedimint_mint_client::OOBNotes;
 use fedimint_wallet_client::PegOutFees;
 use lightning_invoice::Bolt11Invoice;
@@ -30,14 +33,19 @@
 pub const GATEWAY_INFO_POST_ENDPOINT: &str = "/info";
 pub const GET_LN_ONCHAIN_ADDRESS_ENDPOINT: &str = "/get_ln_onchain_address";
 pub const LEAVE_FED_ENDPOINT: &str = "/leave_fed";
+pub const LIST_CONTRACTS_ENDPOINT: &str = "/list_contracts";
 pub const LIST_ACTIVE_CHANNELS_ENDPOINT: &str = "/list_active_channels";
 pub const MNEMONIC_ENDPOINT: &str = "/mnemonic";
 pub const OPEN_CHANNEL_ENDPOINT: &str = "/open_channel";
 pub const CLOSE_CHANNELS_WITH_PEER_ENDPOINT: &str = "/close_channels_with_peer";
 pub const PAY_INVOICE_FOR_OPERATOR_ENDPOINT: &str = "/pay_invoice_for_operator";
+pub const PAY_OFFER_FOR_OPERATOR_ENDPOINT: &str = "/pay_offer_for_operator";
 pub const PAYMENT_LOG_ENDPOINT: &str = "/payment_log";
+pub const PROBE_ENDPOINT: &str = "/probe";
 pub const RECEIVE_ECASH_ENDPOINT: &str = "/receive_ecash";
+pub const RESOLVE_CONTRACT_ENDPOINT: &str = "/resolve_contract";
 pub const SET_FEES_ENDPOINT: &str = "/set_fees";
+pub const SET_PAYMENT_LIMITS_ENDPOINT: &str = "/set_payment_limits";
 pub const STOP_ENDPOINT: &str = "/stop";
 pub const SEND_ONCHAIN_ENDPOINT: &str = "/send_onchain";
 pub const SPEND_ECASH_ENDPOINT: &str = "/spend_ecash";
@@ -144,9 +152,20 @@ pub struct SetFeesPayload {
     pub transaction_parts_per_million: Option<u64>,
 }
 
+/// Updates the payment limits of all federations or the federation specified
+/// by `federation_id`. Limits that are `None` are left unchanged, an amount of
+/// zero removes the limit.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetPaymentLimitsPayload {
+    pub federation_id: Option<FederationId>,
+    pub htlc_minimum: Option<Amount>,
+    pub htlc_maximum: Option<Amount>,
+    pub payment_maximum: Option<Amount>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CreateInvoiceForOperatorPayload {
-    pub amount_msats: u64,
+    pub amount_msats: Msats,
     pub expiry_secs: Option<u32>,
     pub description: Option<String>,
 }
@@ -156,12 +175,20 @@ pub struct PayInvoiceForOperatorPayload {
     pub invoice: Bolt11Invoice,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayOfferForOperatorPayload {
+    /// BOLT12 offer, encoded as a bech32 `lno` string
+    pub offer: String,
+    /// Amount to pay, required if the offer doesn't specify one
+    pub amount: Option<Amount>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct OpenChannelPayload {
     pub pubkey: secp256k1::PublicKey,
     pub host: String,
-    pub channel_size_sats: u64,
-    pub push_amount_sats: u64,
+    pub channel_size_sats: Sats,
+    pub push_amount_sats: Sats,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -207,6 +234,38 @@ pub struct SpendEcashResponse {
     pub notes: OOBNotes,
 }
 
+/// Runs a liquidity probe: a circular payment of `amount` from the gateway's
+/// ecash over its lightning node back to ecash
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProbePayload {
+    pub federation_id: FederationId,
+    pub amount: Amount,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProbeResponse {
+    /// If both legs of the probe succeeded
+    pub success: bool,
+    pub amount: Amount,
+    /// A client of the federation pays an invoice of the lightning node through
+    /// the gateway
+    pub ecash_to_ln: ProbeLeg,
+    /// The lightning node pays an invoice of the client through the gateway,
+    /// only attempted if the first leg succeeded
+    pub ln_to_ecash: Option<ProbeLeg>,
+    pub total_duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProbeLeg {
+    pub success: bool,
+    pub duration_ms: u64,
+    /// The fees the client paid on top of the amount, excluding the routing
+    /// fees paid by the lightning node
+    pub fee: Option<Amount>,
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ReceiveEcashPayload {
     pub notes: OOBNotes,
@@ -221,10 +280,10 @@ pub struct ReceiveEcashResponse {
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct GatewayBalances {
-    pub onchain_balance_sats: u64,
-    pub lightning_balance_msats: u64,
+    pub onchain_balance_sats: Sats,
+    pub lightning_balance_msats: Msats,
     pub ecash_balances: Vec<FederationBalanceInfo>,
-    pub inbound_lightning_liquidity_msats: u64,
+    pub inbound_lightning_liquidity_msats: Msats,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -258,3 +317,154 @@ pub struct PaymentLogPayload {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PaymentLogResponse(pub Vec<GatewayTransactionEvent>);
+
+/// Age after which a pending contract is considered stuck
+pub const STUCK_CONTRACT_AGE: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Lists the LNv1 and LNv2 contracts of all federations or the federation
+/// specified by `federation_id` that haven't reached a terminal resolution
+/// yet. If `stuck` is set only contracts that have been pending for longer
+/// than [`STUCK_CONTRACT_AGE`] are returned.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListContractsPayload {
+    pub federation_id: Option<FederationId>,
+    pub stuck: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListContractsResponse {
+    pub federations: BTreeMap<FederationId, Vec<PendingContract>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractDirection {
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PendingContract {
+    pub operation_id: OperationId,
+    /// Kind of the lightning module the contract belongs to
+    pub module_kind: ModuleKind,
+    pub direction: ContractDirection,
+    /// The states of the operation's active state machines
+    pub states: Vec<String>,
+    /// Seconds since the oldest active state machine was created
+    pub pending_secs: u64,
+}
+
+impl PendingContract {
+    /// Groups the active state machines of a lightning module by operation,
+    /// given as operation id, direction, state and creation time, ordered by
+    /// how long the operations have been pending, the longest first
+    pub fn from_active_states(
+        module_kind: &ModuleKind,
+        states: impl IntoIterator<Item = (OperationId, ContractDirection, String, SystemTime)>,
+        now: SystemTime,
+    ) -> Vec<Self> {
+        let mut contracts = BTreeMap::<OperationId, PendingContract>::new();
+
+        for (operation_id, direction, state, created_at) in states {
+            let pending_secs = now.duration_since(created_at).unwrap_or_default().as_secs();
+
+            let contract = contracts
+                .entry(operation_id)
+                .or_insert_with(|| PendingContract {
+                    operation_id,
+                    module_kind: module_kind.clone(),
+                    direction,
+                    states: vec![],
+                    pending_secs,
+                });
+            contract.states.push(state);
+            contract.pending_secs = contract.pending_secs.max(pending_secs);
+        }
+
+        let mut contracts = contracts.into_values().collect::<Vec<_>>();
+        contracts.sort_by_key(|contract| std::cmp::Reverse(contract.pending_secs));
+        contracts
+    }
+}
+
+/// Manually resolves the outgoing contract of a stuck LNv1 or LNv2 payment
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResolveContractPayload {
+    pub federation_id: FederationId,
+    pub operation_id: OperationId,
+    pub resolution: ContractResolution,
+}
+
+/// Resolution of a stuck outgoing contract forced by the operator. It is
+/// stored in the gateway's database, where the payment's state machine picks
+/// it up instead of continuing the payment.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Encodable, Decodable)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractResolution {
+    /// Claims the contract for the gateway with the preimage of the paid
+    /// invoice
+    Claim { preimage: Preimage },
+    /// Refunds the contract to the user, the gateway loses the funds if the
+    /// lightning payment still succeeds
+    Refund,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn pending_contracts_are_grouped_by_operation() {
+        let now = fedimint_core::time::now();
+        let kind = ModuleKind::from_static_str("ln");
+        let (first, second) = (OperationId([1; 32]), OperationId([2; 32]));
+
+        let contracts = PendingContract::from_active_states(
+            &kind,
+            [
+                (
+                    first,
+                    ContractDirection::Incoming,
+                    "Receive".to_owned(),
+                    now - Duration::from_secs(10),
+                ),
+                (
+                    second,
+                    ContractDirection::Outgoing,
+                    "PayInvoice".to_owned(),
+                    now - Duration::from_secs(20),
+                ),
+                (
+                    first,
+                    ContractDirection::Incoming,
+                    "Complete".to_owned(),
+                    now - Duration::from_secs(30),
+                ),
+            ],
+            now,
+        );
+
+        assert_eq!(
+            contracts,
+            vec![
+                PendingContract {
+                    operation_id: first,
+                    module_kind: kind.clone(),
+                    direction: ContractDirection::Incoming,
+                    states: vec!["Receive".to_owned(), "Complete".to_owned()],
+                    pending_secs: 30,
+                },
+                PendingContract {
+                    operation_id: second,
+                    module_kind: kind,
+                    direction: ContractDirection::Outgoing,
+                    states: vec!["PayInvoice".to_owned()],
+                    pending_secs: 20,
+                },
+            ]
+        );
+    }
+}
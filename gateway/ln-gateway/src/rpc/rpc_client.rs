@@ -10,16 +10,19 @@
 use super::{
     BackupPayload, CloseChannelsWithPeerPayload, ConfigPayload, ConnectFedPayload,
     CreateInvoiceForOperatorPayload, DepositAddressPayload, FederationInfo, GatewayBalances,
-    GatewayFedConfig, GatewayInfo, LeaveFedPayload, MnemonicResponse, OpenChannelPayload,
-    PayInvoiceForOperatorPayload, PaymentLogPayload, PaymentLogResponse, ReceiveEcashPayload,
-    ReceiveEcashResponse, SendOnchainPayload, SetFeesPayload, SpendEcashPayload,
-    SpendEcashResponse, WithdrawPayload, WithdrawResponse, ADDRESS_ENDPOINT, BACKUP_ENDPOINT,
-    CLOSE_CHANNELS_WITH_PEER_ENDPOINT, CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT,
-    CREATE_BOLT11_INVOICE_FOR_OPERATOR_ENDPOINT, GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT,
-    GET_BALANCES_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT,
-    LIST_ACTIVE_CHANNELS_ENDPOINT, MNEMONIC_ENDPOINT, OPEN_CHANNEL_ENDPOINT, PAYMENT_LOG_ENDPOINT,
-    PAY_INVOICE_FOR_OPERATOR_ENDPOINT, RECEIVE_ECASH_ENDPOINT, SEND_ONCHAIN_ENDPOINT,
-    SET_FEES_ENDPOINT, SPEND_ECASH_ENDPOINT, STOP_ENDPOINT, WITHDRAW_ENDPOINT,
+    GatewayFedConfig, GatewayInfo, LeaveFedPayload, ListContractsPayload, ListContractsResponse,
+    MnemonicResponse, OpenChannelPayload, PayInvoiceForOperatorPayload, PayOfferForOperatorPayload,
+    PaymentLogPayload, PaymentLogResponse, ProbePayload, ProbeResponse, ReceiveEcashPayload,
+    ReceiveEcashResponse, ResolveContractPayload, SendOnchainPayload, SetFeesPayload,
+    SetPaymentLimitsPayload, SpendEcashPayload, SpendEcashResponse, WithdrawPayload,
+    WithdrawResponse, ADDRESS_ENDPOINT, BACKUP_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT,
+    CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT, CREATE_BOLT11_INVOICE_FOR_OPERATOR_ENDPOINT,
+    GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT, GET_BALANCES_ENDPOINT,
+    GET_LN_ONCHAIN_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
+    LIST_CONTRACTS_ENDPOINT, MNEMONIC_ENDPOINT, OPEN_CHANNEL_ENDPOINT, PAYMENT_LOG_ENDPOINT,
+    PAY_INVOICE_FOR_OPERATOR_ENDPOINT, PAY_OFFER_FOR_OPERATOR_ENDPOINT, PROBE_ENDPOINT,
+    RECEIVE_ECASH_ENDPOINT, RESOLVE_CONTRACT_ENDPOINT, SEND_ONCHAIN_ENDPOINT, SET_FEES_ENDPOINT,
+    SET_PAYMENT_LIMITS_ENDPOINT, SPEND_ECASH_ENDPOINT, STOP_ENDPOINT, WITHDRAW_ENDPOINT,
 };
 use crate::lightning::{ChannelInfo, CloseChannelsWithPeerResponse};
 
@@ -128,6 +131,17 @@ pub async fn set_fees(&self, payload: SetFeesPayload) -> GatewayRpcResult<()> {
         self.call_post(url, payload).await
     }
 
+    pub async fn set_payment_limits(
+        &self,
+        payload: SetPaymentLimitsPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_PAYMENT_LIMITS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
     pub async fn create_invoice_for_self(
         &self,
         payload: CreateInvoiceForOperatorPayload,
@@ -150,6 +164,14 @@ pub async fn pay_invoice(
         self.call_post(url, payload).await
     }
 
+    pub async fn pay_offer(&self, payload: PayOfferForOperatorPayload) -> GatewayRpcResult<String> {
+        let url = self
+            .base_url
+            .join(PAY_OFFER_FOR_OPERATOR_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
     pub async fn get_ln_onchain_address(&self) -> GatewayRpcResult<Address<NetworkUnchecked>> {
         let url = self
             .base_url
@@ -247,6 +269,33 @@ pub async fn payment_log(
         self.call_post(url, payload).await
     }
 
+    pub async fn probe(&self, payload: ProbePayload) -> GatewayRpcResult<ProbeResponse> {
+        let url = self
+            .base_url
+            .join(PROBE_ENDPOINT)
+            .expect("Invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_contracts(
+        &self,
+        payload: ListContractsPayload,
+    ) -> GatewayRpcResult<ListContractsResponse> {
+        let url = self
+            .base_url
+            .join(LIST_CONTRACTS_ENDPOINT)
+            .expect("Invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn resolve_contract(&self, payload: ResolveContractPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(RESOLVE_CONTRACT_ENDPOINT)
+            .expect("Invalid base url");
+        self.call_post(url, payload).await
+    }
+
     async fn call<P: Serialize, T: DeserializeOwned>(
         &self,
         method: Method,
@@ -24,14 +24,18 @@
 use super::{
     BackupPayload, CloseChannelsWithPeerPayload, ConnectFedPayload,
     CreateInvoiceForOperatorPayload, DepositAddressPayload, InfoPayload, LeaveFedPayload,
-    OpenChannelPayload, PayInvoiceForOperatorPayload, PaymentLogPayload, ReceiveEcashPayload,
-    SendOnchainPayload, SetFeesPayload, SpendEcashPayload, WithdrawPayload, ADDRESS_ENDPOINT,
-    BACKUP_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT, CONFIGURATION_ENDPOINT,
-    CONNECT_FED_ENDPOINT, CREATE_BOLT11_INVOICE_FOR_OPERATOR_ENDPOINT, GATEWAY_INFO_ENDPOINT,
-    GATEWAY_INFO_POST_ENDPOINT, GET_BALANCES_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT,
-    LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT, MNEMONIC_ENDPOINT, OPEN_CHANNEL_ENDPOINT,
-    PAYMENT_LOG_ENDPOINT, PAY_INVOICE_FOR_OPERATOR_ENDPOINT, RECEIVE_ECASH_ENDPOINT,
-    SEND_ONCHAIN_ENDPOINT, SET_FEES_ENDPOINT, SPEND_ECASH_ENDPOINT, STOP_ENDPOINT, V1_API_ENDPOINT,
+    ListContractsPayload, OpenChannelPayload, PayInvoiceForOperatorPayload,
+    PayOfferForOperatorPayload, PaymentLogPayload, ProbePayload, ReceiveEcashPayload,
+    ResolveContractPayload, SendOnchainPayload, SetFeesPayload, SetPaymentLimitsPayload,
+    SpendEcashPayload, WithdrawPayload, ADDRESS_ENDPOINT, BACKUP_ENDPOINT,
+    CLOSE_CHANNELS_WITH_PEER_ENDPOINT, CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT,
+    CREATE_BOLT11_INVOICE_FOR_OPERATOR_ENDPOINT, GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT,
+    GET_BALANCES_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT,
+    LIST_ACTIVE_CHANNELS_ENDPOINT, LIST_CONTRACTS_ENDPOINT, MNEMONIC_ENDPOINT,
+    OPEN_CHANNEL_ENDPOINT, PAYMENT_LOG_ENDPOINT, PAY_INVOICE_FOR_OPERATOR_ENDPOINT,
+    PAY_OFFER_FOR_OPERATOR_ENDPOINT, PROBE_ENDPOINT, RECEIVE_ECASH_ENDPOINT,
+    RESOLVE_CONTRACT_ENDPOINT, SEND_ONCHAIN_ENDPOINT, SET_FEES_ENDPOINT,
+    SET_PAYMENT_LIMITS_ENDPOINT, SPEND_ECASH_ENDPOINT, STOP_ENDPOINT, V1_API_ENDPOINT,
     WITHDRAW_ENDPOINT,
 };
 use crate::error::{AdminGatewayError, PublicGatewayError};
@@ -153,6 +157,7 @@ fn v1_routes(gateway: Arc<Gateway>, task_group: TaskGroup) -> Router {
             PAY_INVOICE_FOR_OPERATOR_ENDPOINT,
             post(pay_invoice_operator),
         )
+        .route(PAY_OFFER_FOR_OPERATOR_ENDPOINT, post(pay_offer_operator))
         .route(GET_LN_ONCHAIN_ADDRESS_ENDPOINT, get(get_ln_onchain_address))
         .route(OPEN_CHANNEL_ENDPOINT, post(open_channel))
         .route(
@@ -166,7 +171,11 @@ fn v1_routes(gateway: Arc<Gateway>, task_group: TaskGroup) -> Router {
         .route(MNEMONIC_ENDPOINT, get(mnemonic))
         .route(STOP_ENDPOINT, get(stop))
         .route(PAYMENT_LOG_ENDPOINT, post(payment_log))
+        .route(PROBE_ENDPOINT, post(probe))
+        .route(LIST_CONTRACTS_ENDPOINT, post(list_contracts))
+        .route(RESOLVE_CONTRACT_ENDPOINT, post(resolve_contract))
         .route(SET_FEES_ENDPOINT, post(set_fees))
+        .route(SET_PAYMENT_LIMITS_ENDPOINT, post(set_payment_limits))
         .route(CONFIGURATION_ENDPOINT, post(configuration))
         // FIXME: deprecated >= 0.3.0
         .route(GATEWAY_INFO_POST_ENDPOINT, post(handle_post_info))
@@ -254,6 +263,15 @@ async fn pay_invoice_operator(
     Ok(Json(json!(preimage.0.encode_hex::<String>())))
 }
 
+#[instrument(skip_all, err, fields(?payload))]
+async fn pay_offer_operator(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<PayOfferForOperatorPayload>,
+) -> Result<impl IntoResponse, AdminGatewayError> {
+    let preimage = gateway.handle_pay_offer_for_operator_msg(payload).await?;
+    Ok(Json(json!(preimage.0.encode_hex::<String>())))
+}
+
 #[instrument(skip_all, err, fields(?payload))]
 async fn pay_invoice(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -302,6 +320,15 @@ async fn set_fees(
     Ok(Json(json!(())))
 }
 
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_payment_limits(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetPaymentLimitsPayload>,
+) -> Result<impl IntoResponse, AdminGatewayError> {
+    gateway.handle_set_payment_limits_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
 #[instrument(skip_all, err)]
 async fn get_ln_onchain_address(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -395,6 +422,14 @@ async fn spend_ecash(
     Ok(Json(json!(gateway.handle_spend_ecash_msg(payload).await?)))
 }
 
+#[instrument(skip_all, err, fields(?payload))]
+async fn probe(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ProbePayload>,
+) -> Result<impl IntoResponse, AdminGatewayError> {
+    Ok(Json(json!(gateway.handle_probe_msg(payload).await?)))
+}
+
 #[instrument(skip_all, err)]
 async fn receive_ecash(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -430,3 +465,21 @@ async fn payment_log(
     let payment_log = gateway.handle_payment_log_msg(payload).await?;
     Ok(Json(json!(payment_log)))
 }
+
+#[instrument(skip_all, err)]
+async fn list_contracts(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ListContractsPayload>,
+) -> Result<impl IntoResponse, AdminGatewayError> {
+    let contracts = gateway.handle_list_contracts_msg(payload).await?;
+    Ok(Json(json!(contracts)))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn resolve_contract(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ResolveContractPayload>,
+) -> Result<impl IntoResponse, AdminGatewayError> {
+    gateway.handle_resolve_contract_msg(payload).await?;
+    Ok(Json(json!(())))
+}
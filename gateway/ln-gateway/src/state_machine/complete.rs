@@ -11,13 +11,13 @@
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, instrument, warn};
 
 use super::events::{
     CompleteLightningPaymentSucceeded, IncomingPaymentFailed, IncomingPaymentSucceeded,
 };
 use super::{GatewayClientContext, GatewayClientStateMachines};
-use crate::lightning::{InterceptPaymentResponse, PaymentAction};
+use crate::lightning::{InterceptPaymentResponse, PaymentAction, PaymentTraceId};
 
 #[derive(Error, Debug, Serialize, Deserialize, Encodable, Decodable, Clone, Eq, PartialEq)]
 enum CompleteHtlcError {
@@ -241,6 +241,7 @@ fn transitions(
         )]
     }
 
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(&common.payment_hash)))]
     async fn await_complete_htlc(
         context: GatewayClientContext,
         common: GatewayCompleteCommon,
@@ -7,7 +7,7 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::ensure;
+use anyhow::{anyhow, bail, ensure};
 use async_stream::stream;
 use bitcoin::hashes::{sha256, Hash};
 use bitcoin::key::Secp256k1;
@@ -40,7 +40,7 @@
     RealGatewayConnection,
 };
 use fedimint_ln_common::config::LightningClientConfig;
-use fedimint_ln_common::contracts::{ContractId, Preimage};
+use fedimint_ln_common::contracts::{ContractId, FundedContract, Preimage};
 use fedimint_ln_common::route_hints::RouteHint;
 use fedimint_ln_common::{
     create_gateway_remove_message, LightningCommonInit, LightningGateway,
@@ -58,7 +58,9 @@
     GatewayPayCommon, GatewayPayInvoice, GatewayPayStateMachine, GatewayPayStates,
     OutgoingPaymentError,
 };
+use crate::db::GatewayDbtxNcExt;
 use crate::lightning::{InterceptPaymentRequest, LightningContext};
+use crate::rpc::{ContractDirection, ContractResolution, PendingContract};
 use crate::state_machine::complete::{
     GatewayCompleteCommon, GatewayCompleteStates, WaitForPreimageState,
 };
@@ -601,6 +603,13 @@ pub async fn gateway_pay_bolt11_invoice(
         let payload = pay_invoice_payload.clone();
         let lightning_context = self.gateway.get_lightning_context().await?;
 
+        // Zero-amount invoices can only be paid as pruned invoices, which carry the
+        // amount chosen by the payer
+        ensure!(
+            pay_invoice_payload.payment_data.amount().is_some(),
+            "Invoice has no amount and no amount was supplied"
+        );
+
         if matches!(
             pay_invoice_payload.payment_data,
             PaymentData::PrunedInvoice { .. }
@@ -668,6 +677,13 @@ pub async fn gateway_pay_bolt11_invoice(
             })
     }
 
+    /// Whether a pay operation was started for `contract_id`
+    pub async fn pay_operation_exists(&self, contract_id: ContractId) -> bool {
+        self.client_ctx
+            .operation_exists(OperationId(contract_id.to_byte_array()))
+            .await
+    }
+
     pub async fn gateway_subscribe_ln_pay(
         &self,
         operation_id: OperationId,
@@ -736,6 +752,110 @@ pub async fn gateway_subscribe_ln_pay(
             }
         }))
     }
+
+    /// Returns the contracts whose operations still have active state
+    /// machines, i.e. that haven't reached a terminal resolution yet
+    pub async fn list_pending_contracts(&self) -> Vec<PendingContract> {
+        let states = self
+            .client_ctx
+            .get_own_active_states()
+            .await
+            .into_iter()
+            .map(|(state, meta)| {
+                let direction = match state {
+                    GatewayClientStateMachines::Pay(_) => ContractDirection::Outgoing,
+                    GatewayClientStateMachines::Receive(_)
+                    | GatewayClientStateMachines::Complete(_) => ContractDirection::Incoming,
+                };
+                (
+                    state.operation_id(),
+                    direction,
+                    state.to_string(),
+                    meta.created_at,
+                )
+            });
+
+        PendingContract::from_active_states(&KIND, states, fedimint_core::time::now())
+    }
+
+    /// Resolves the outgoing contract of a stuck payment by either claiming it
+    /// with a preimage the operator obtained out of band or by refunding it to
+    /// the user. The resolution is recorded in the gateway's database, where
+    /// the payment's state machine picks it up instead of continuing the
+    /// payment. The pending lightning payment attempt is not aborted, so it is
+    /// up to the operator to make sure that it can't succeed anymore before
+    /// refunding.
+    pub async fn gateway_force_resolve_outgoing_contract(
+        &self,
+        operation_id: OperationId,
+        resolution: ContractResolution,
+    ) -> anyhow::Result<()> {
+        let pay_states = self
+            .client_ctx
+            .get_own_active_states()
+            .await
+            .into_iter()
+            .filter_map(|(state, _)| match state {
+                GatewayClientStateMachines::Pay(pay) if pay.common.operation_id == operation_id => {
+                    Some(pay.state)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        ensure!(
+            !pay_states.is_empty(),
+            "No pending outgoing contract for operation {}",
+            operation_id.fmt_short()
+        );
+        ensure!(
+            pay_states.iter().all(|state| matches!(
+                state,
+                GatewayPayStates::PayInvoice(_) | GatewayPayStates::WaitForSwapPreimage(_)
+            )),
+            "The outgoing contract is already being resolved"
+        );
+
+        let contract_id = ContractId::from_byte_array(operation_id.0);
+        let account = self
+            .module_api
+            .fetch_contract(contract_id)
+            .await?
+            .ok_or_else(|| anyhow!("Outgoing contract {contract_id} does not exist"))?;
+        let FundedContract::Outgoing(contract) = account.contract else {
+            bail!("Contract {contract_id} is not an outgoing contract");
+        };
+
+        ensure!(
+            contract.gateway_key == self.redeem_key.public_key(),
+            "Outgoing contract {contract_id} does not belong to this gateway"
+        );
+        ensure!(
+            !contract.cancelled,
+            "Outgoing contract {contract_id} was already canceled"
+        );
+        ensure!(
+            account.amount != Amount::ZERO,
+            "Outgoing contract {contract_id} was already claimed"
+        );
+
+        if let ContractResolution::Claim { preimage } = &resolution {
+            ensure!(
+                sha256::Hash::hash(&preimage.0) == contract.hash,
+                "Preimage does not match the payment hash of contract {contract_id}"
+            );
+        }
+
+        let mut dbtx = self.gateway.gateway_db.begin_transaction().await;
+        ensure!(
+            dbtx.save_forced_contract_resolution(operation_id, &resolution)
+                .await,
+            "The outgoing contract is already being resolved"
+        );
+        dbtx.commit_tx_result().await?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
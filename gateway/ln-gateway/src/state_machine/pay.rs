@@ -16,17 +16,18 @@
 use fedimint_ln_common::config::FeeToAmount;
 use fedimint_ln_common::contracts::outgoing::OutgoingContractAccount;
 use fedimint_ln_common::contracts::{ContractId, FundedContract, IdentifiableContract, Preimage};
-use fedimint_ln_common::{LightningInput, LightningOutput};
+use fedimint_ln_common::{LightningInput, LightningOutput, PrunedInvoice};
 use futures::future;
 use lightning_invoice::RoutingFees;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio_stream::StreamExt;
-use tracing::{debug, error, info, warn, Instrument};
+use tracing::{debug, error, info, instrument, warn, Instrument};
 
 use super::{GatewayClientContext, GatewayExtReceiveStates};
-use crate::db::GatewayDbtxNcExt;
-use crate::lightning::{LightningRpcError, PayInvoiceResponse};
+use crate::db::{ForcedContractResolutionKey, GatewayDbtxNcExt};
+use crate::lightning::{LightningRpcError, PayInvoiceResponse, PaymentTraceId};
+use crate::rpc::ContractResolution;
 use crate::state_machine::events::{OutgoingPaymentFailed, OutgoingPaymentSucceeded};
 use crate::state_machine::GatewayClientModule;
 use crate::GatewayState;
@@ -48,6 +49,10 @@
 ///    PayInvoice -- pay invoice via direct swap successful --> WaitForSwapPreimage
 ///    WaitForSwapPreimage -- received preimage --> ClaimOutgoingContract
 ///    WaitForSwapPreimage -- wait for preimge failed --> Canceled
+///    PayInvoice -- operator forced claim --> ClaimOutgoingContract
+///    PayInvoice -- operator forced refund --> CancelContract
+///    WaitForSwapPreimage -- operator forced claim --> ClaimOutgoingContract
+///    WaitForSwapPreimage -- operator forced refund --> CancelContract
 ///    ClaimOutgoingContract -- claim tx submission --> Preimage
 ///    CancelContract -- cancel tx submission successful --> Canceled
 ///    CancelContract -- cancel tx submission unsuccessful --> Failed
@@ -97,6 +102,55 @@ pub struct GatewayPayStateMachine {
     pub state: GatewayPayStates,
 }
 
+impl GatewayPayStateMachine {
+    /// Waits until the operator forces a resolution of the outgoing contract
+    /// of this payment, see
+    /// [`GatewayClientModule::gateway_force_resolve_outgoing_contract`]
+    async fn await_forced_resolution(
+        context: GatewayClientContext,
+        operation_id: OperationId,
+    ) -> ContractResolution {
+        context
+            .gateway
+            .gateway_db
+            .wait_key_exists(&ForcedContractResolutionKey(operation_id))
+            .await
+    }
+
+    /// Claims `contract` with the preimage the operator obtained out of band
+    /// or refunds it to the user, depending on the forced `resolution`
+    fn forced_resolution(
+        common: GatewayPayCommon,
+        contract: OutgoingContractAccount,
+        resolution: ContractResolution,
+    ) -> Self {
+        info!(
+            ?contract,
+            ?resolution,
+            "Operator forced resolution of outgoing contract"
+        );
+        let state = match resolution {
+            ContractResolution::Claim { preimage } => {
+                let claim = GatewayPayClaimOutgoingContract { contract, preimage };
+                GatewayPayStates::ClaimOutgoingContract(Box::new(claim))
+            }
+            ContractResolution::Refund => {
+                let error = OutgoingPaymentError {
+                    error_type: OutgoingPaymentErrorType::OperatorRefunded,
+                    contract_id: contract.contract.contract_id(),
+                    contract: Some(contract.clone()),
+                };
+                GatewayPayStates::CancelContract(Box::new(GatewayPayCancelContract {
+                    contract,
+                    error,
+                }))
+            }
+        };
+
+        Self { common, state }
+    }
+}
+
 impl fmt::Display for GatewayPayStateMachine {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -185,6 +239,8 @@ pub enum OutgoingPaymentErrorType {
     InvalidFederationConfiguration,
     #[error("Invalid invoice preimage")]
     InvalidInvoicePreimage,
+    #[error("The contract was refunded by the gateway operator")]
+    OperatorRefunded,
 }
 
 #[derive(
@@ -215,15 +271,68 @@ fn transitions(
         common: &GatewayPayCommon,
     ) -> Vec<StateTransition<GatewayPayStateMachine>> {
         let payload = self.pay_invoice_payload.clone();
-        vec![StateTransition::new(
-            Self::fetch_parameters_and_pay(
-                global_context,
-                payload,
-                context.clone(),
-                common.clone(),
+        let forced_common = common.clone();
+        vec![
+            StateTransition::new(
+                Self::fetch_parameters_and_pay(
+                    global_context.clone(),
+                    payload.clone(),
+                    context.clone(),
+                    common.clone(),
+                ),
+                |_dbtx, result, _old_state| Box::pin(futures::future::ready(result)),
             ),
-            |_dbtx, result, _old_state| Box::pin(futures::future::ready(result)),
-        )]
+            StateTransition::new(
+                Self::await_forced_resolution(
+                    global_context,
+                    context.clone(),
+                    common.clone(),
+                    payload,
+                ),
+                move |_dbtx, (contract, resolution), _old_state| {
+                    Box::pin(futures::future::ready(
+                        GatewayPayStateMachine::forced_resolution(
+                            forced_common.clone(),
+                            contract,
+                            resolution,
+                        ),
+                    ))
+                },
+            ),
+        ]
+    }
+
+    /// Waits for a forced resolution of the payment's outgoing contract and
+    /// the contract itself, which the operator could only resolve once it
+    /// was funded
+    async fn await_forced_resolution(
+        global_context: DynGlobalClientContext,
+        context: GatewayClientContext,
+        common: GatewayPayCommon,
+        payload: PayInvoicePayload,
+    ) -> (OutgoingContractAccount, ContractResolution) {
+        let resolution =
+            GatewayPayStateMachine::await_forced_resolution(context, common.operation_id).await;
+        let account = global_context
+            .module_api()
+            .await_contract(payload.contract_id)
+            .await;
+
+        let FundedContract::Outgoing(contract) = account.contract else {
+            warn!(
+                contract_id = %payload.contract_id,
+                "Ignoring forced resolution of a contract that is not outgoing"
+            );
+            return std::future::pending().await;
+        };
+
+        (
+            OutgoingContractAccount {
+                amount: account.amount,
+                contract,
+            },
+            resolution,
+        )
     }
 
     async fn fetch_parameters_and_pay(
@@ -400,6 +509,7 @@ async fn await_get_payment_parameters(
         })
     }
 
+    #[instrument(skip_all, fields(trace_id = %PaymentTraceId::new(&buy_preimage.payment_data.payment_hash())))]
     async fn buy_preimage_over_lightning(
         context: GatewayClientContext,
         buy_preimage: PaymentParameters,
@@ -424,7 +534,30 @@ async fn buy_preimage_over_lightning(
             );
         };
 
+        let is_probe_payment = context
+            .gateway
+            .is_probe_payment(&buy_preimage.payment_data.payment_hash());
         let payment_result = match buy_preimage.payment_data {
+            // Liquidity probes pay an invoice of our own lightning node
+            PaymentData::Invoice(invoice) if is_probe_payment => {
+                match PrunedInvoice::try_from(invoice) {
+                    Ok(invoice) => {
+                        lightning_context
+                            .lnrpc
+                            .pay_private_to_self(invoice, max_delay, max_fee)
+                            .await
+                    }
+                    Err(e) => Err(LightningRpcError::FailedPayment {
+                        failure_reason: e.to_string(),
+                    }),
+                }
+            }
+            PaymentData::PrunedInvoice(invoice) if is_probe_payment => {
+                lightning_context
+                    .lnrpc
+                    .pay_private_to_self(invoice, max_delay, max_fee)
+                    .await
+            }
             PaymentData::Invoice(invoice) => {
                 lightning_context
                     .lnrpc
@@ -756,16 +889,40 @@ fn transitions(
         let federation_id = self.federation_id;
         let operation_id = self.operation_id;
         let contract = self.contract.clone();
-        vec![StateTransition::new(
-            Self::await_preimage(context, federation_id, operation_id, contract.clone()),
-            move |_dbtx, result, _old_state| {
-                let common = common.clone();
-                let contract = contract.clone();
-                Box::pin(async {
-                    Self::transition_claim_outgoing_contract(common, result, contract)
-                })
-            },
-        )]
+        let forced_common = common.clone();
+        let forced_contract = contract.clone();
+        vec![
+            StateTransition::new(
+                Self::await_preimage(
+                    context.clone(),
+                    federation_id,
+                    operation_id,
+                    contract.clone(),
+                ),
+                move |_dbtx, result, _old_state| {
+                    let common = common.clone();
+                    let contract = contract.clone();
+                    Box::pin(async {
+                        Self::transition_claim_outgoing_contract(common, result, contract)
+                    })
+                },
+            ),
+            StateTransition::new(
+                GatewayPayStateMachine::await_forced_resolution(
+                    context,
+                    forced_common.operation_id,
+                ),
+                move |_dbtx, resolution, _old_state| {
+                    Box::pin(futures::future::ready(
+                        GatewayPayStateMachine::forced_resolution(
+                            forced_common.clone(),
+                            forced_contract.clone(),
+                            resolution,
+                        ),
+                    ))
+                },
+            ),
+        ]
     }
 
     async fn await_preimage(
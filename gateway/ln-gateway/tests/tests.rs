@@ -55,7 +55,7 @@
     OutgoingPaymentStarted, OutgoingPaymentSucceeded,
 };
 use ln_gateway::gateway_module_v2::{FinalReceiveState, GatewayClientModuleV2};
-use ln_gateway::rpc::{PaymentLogPayload, SetFeesPayload};
+use ln_gateway::rpc::{PaymentLogPayload, ProbePayload, SetFeesPayload};
 use ln_gateway::state_machine::pay::{
     OutgoingContractError, OutgoingPaymentError, OutgoingPaymentErrorType,
 };
@@ -351,6 +351,23 @@ async fn test_gateway_enforces_fees() -> anyhow::Result<()> {
     .await
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_refuses_probe_if_node_cannot_pay_itself() -> anyhow::Result<()> {
+    single_federation_test(|gateway, _, fed, _, _| async move {
+        // The fake lightning node doesn't support circular payments to itself
+        assert!(gateway
+            .handle_probe_msg(ProbePayload {
+                federation_id: fed.id(),
+                amount: sats(10),
+            })
+            .await
+            .is_err());
+
+        Ok(())
+    })
+    .await
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_gateway_cannot_claim_invalid_preimage() -> anyhow::Result<()> {
     single_federation_test(
@@ -184,6 +184,10 @@ fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn>
         migrations.insert(DatabaseVersion(0), |ctx| migrate_to_v1(ctx).boxed());
         migrations
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        Some(vec![DbKeyPrefix::Funds as u8, DbKeyPrefix::Outcome as u8])
+    }
 }
 
 /// Dummy module
@@ -1,5 +1,8 @@
+use std::time::Duration;
+
 use anyhow::bail;
 use fedimint_client::module::OutPointRange;
+use fedimint_client::network_sim::NetworkConditions;
 use fedimint_client::transaction::{
     ClientInput, ClientInputBundle, ClientOutput, ClientOutputBundle, TransactionBuilder,
 };
@@ -39,6 +42,36 @@ async fn can_print_and_send_money() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn can_print_and_send_money_over_unreliable_network() -> anyhow::Result<()> {
+    let fed = fixtures().new_default_fed().await;
+    let client1 = fed
+        .new_client_with_network_conditions(NetworkConditions {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(50),
+            request_loss: 0.1,
+            response_loss: 0.1,
+            seed: Some(0),
+            ..NetworkConditions::default()
+        })
+        .await;
+    let client2 = fed.new_client().await;
+
+    let client1_dummy_module = client1.get_first_module::<DummyClientModule>()?;
+    let client2_dummy_module = client2.get_first_module::<DummyClientModule>()?;
+    let (_, outpoint) = client1_dummy_module.print_money(sats(1000)).await?;
+    client1_dummy_module.receive_money(outpoint).await?;
+    assert_eq!(client1.get_balance().await, sats(1000));
+
+    let outpoint = client1_dummy_module
+        .send_money(client2_dummy_module.account(), sats(250))
+        .await?;
+    client2_dummy_module.receive_money(outpoint).await?;
+    assert_eq!(client1.get_balance().await, sats(750));
+    assert_eq!(client2.get_balance().await, sats(250));
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn client_ignores_unknown_module() {
     let fed = fixtures().new_default_fed().await;
@@ -158,6 +158,10 @@ fn validate_config(
     fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn> {
         BTreeMap::new()
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        Some(vec![DbKeyPrefix::Example as u8])
+    }
 }
 
 /// Empty module
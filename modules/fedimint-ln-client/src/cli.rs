@@ -9,7 +9,7 @@
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
-use crate::OutgoingLightningPayment;
+use crate::{OutgoingLightningPayment, PaymentFeeLimit};
 
 #[derive(Parser, Serialize)]
 enum Opts {
@@ -29,7 +29,7 @@ enum Opts {
     Pay {
         /// Lightning invoice or lnurl
         payment_info: String,
-        /// Amount to pay, used for lnurl
+        /// Amount to pay, used for lnurl and zero-amount invoices
         #[clap(long)]
         amount: Option<Amount>,
         /// Invoice comment/description, used on lnurl
@@ -42,7 +42,17 @@ enum Opts {
         gateway_id: Option<PublicKey>,
         #[clap(long, default_value = "false")]
         force_internal: bool,
+        /// Abort if the gateway and routing fees exceed this amount
+        #[clap(long)]
+        max_fee: Option<Amount>,
+        /// Abort if the gateway and routing fees exceed this many parts per
+        /// million of the amount
+        #[clap(long)]
+        max_fee_ppm: Option<u64>,
     },
+    /// List the gateways that kept funds locked until an outgoing contract
+    /// timed out
+    GatewayMisbehavior,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,6 +101,8 @@ pub(crate) async fn handle_cli_command(
             lnurl_comment,
             gateway_id,
             force_internal,
+            max_fee,
+            max_fee_ppm,
         } => {
             let bolt11 = crate::get_invoice(&payment_info, amount, lnurl_comment).await?;
             info!("Paying invoice: {bolt11}");
@@ -100,7 +112,18 @@ pub(crate) async fn handle_cli_command(
                 payment_type,
                 contract_id,
                 fee,
-            } = module.pay_bolt11_invoice(ln_gateway, bolt11, ()).await?;
+            } = module
+                .pay_bolt11_invoice_with_fee_limit(
+                    ln_gateway,
+                    bolt11,
+                    amount,
+                    PaymentFeeLimit {
+                        max_fee,
+                        max_fee_ppm,
+                    },
+                    (),
+                )
+                .await?;
             let operation_id = payment_type.operation_id();
             info!(
                 "Gateway fee: {fee}, payment operation id: {}",
@@ -126,5 +149,8 @@ pub(crate) async fn handle_cli_command(
                     .context("expected a response")?
             }
         }
+        Opts::GatewayMisbehavior => {
+            serde_json::to_value(module.list_gateway_misbehavior().await).expect("Can't fail")
+        }
     })
 }
@@ -1,14 +1,16 @@
 use std::io::Cursor;
+use std::time::SystemTime;
 
 use bitcoin::hashes::sha256;
 use fedimint_core::core::OperationId;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::secp256k1::{Keypair, PublicKey};
-use fedimint_core::{impl_db_lookup, impl_db_record, OutPoint, TransactionId};
+use fedimint_core::{impl_db_lookup, impl_db_record, Amount, OutPoint, TransactionId};
+use fedimint_ln_common::contracts::ContractId;
 use fedimint_ln_common::{LightningGateway, LightningGatewayRegistration};
 use lightning_invoice::Bolt11Invoice;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
 use crate::pay::lightningpay::LightningPayStates;
@@ -30,6 +32,8 @@ pub enum DbKeyPrefix {
     PaymentResult = 0x29,
     MetaOverridesDeprecated = 0x30,
     LightningGateway = 0x45,
+    GatewayMisbehavior = 0x46,
+    ActiveGatewayId = 0x47,
     /// Prefixes between 0xb0..=0xcf shall all be considered allocated for
     /// historical and future external use
     ExternalReservedStart = 0xb0,
@@ -99,6 +103,60 @@ pub struct PaymentResult {
     query_prefix = LightningGatewayKeyPrefix
 );
 
+/// Gateway selected by the user to route payments through, see
+/// [`crate::LightningClientModule::set_active_gateway`]
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ActiveGatewayIdKey;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ActiveGatewayIdKeyPrefix;
+
+impl_db_record!(
+    key = ActiveGatewayIdKey,
+    value = PublicKey,
+    db_prefix = DbKeyPrefix::ActiveGatewayId,
+);
+impl_db_lookup!(
+    key = ActiveGatewayIdKey,
+    query_prefix = ActiveGatewayIdKeyPrefix
+);
+
+/// Evidence that the gateway `gateway_id` locked the funds of the outgoing
+/// contract `contract_id` without ever delivering the preimage or cancelling
+/// the contract, so the client had to wait for the timelock to refund itself.
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct GatewayMisbehaviorKey {
+    pub gateway_id: PublicKey,
+    pub contract_id: ContractId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct GatewayMisbehaviorKeyPrefix;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct GatewayMisbehaviorGatewayPrefix(pub PublicKey);
+
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct GatewayMisbehavior {
+    pub operation_id: OperationId,
+    /// Funds the gateway kept locked until the timelock expired
+    pub amount: Amount,
+    pub timelock: u32,
+    pub funded_at: SystemTime,
+    pub refunded_at: SystemTime,
+}
+
+impl_db_record!(
+    key = GatewayMisbehaviorKey,
+    value = GatewayMisbehavior,
+    db_prefix = DbKeyPrefix::GatewayMisbehavior,
+);
+impl_db_lookup!(
+    key = GatewayMisbehaviorKey,
+    query_prefix = GatewayMisbehaviorKeyPrefix,
+    query_prefix = GatewayMisbehaviorGatewayPrefix
+);
+
 /// Migrates `SubmittedOfferV0` to `SubmittedOffer` and `ConfirmedInvoiceV0` to
 /// `ConfirmedInvoice`
 pub(crate) fn get_v1_migrated_state(
@@ -270,16 +328,25 @@ pub struct LightningPayRefundV0 {
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
+    use std::time::SystemTime;
 
     use fedimint_client::db::migrate_state;
     use fedimint_core::core::{IntoDynInstance, OperationId};
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
     use fedimint_core::encoding::Encodable;
+    use fedimint_core::module::registry::ModuleRegistry;
     use fedimint_core::secp256k1::{Keypair, SECP256K1};
-    use fedimint_core::{BitcoinHash, TransactionId};
+    use fedimint_core::{Amount, BitcoinHash, TransactionId};
+    use fedimint_ln_common::contracts::ContractId;
+    use futures::StreamExt;
     use lightning_invoice::Bolt11Invoice;
     use rand::thread_rng;
 
-    use crate::db::{get_v1_migrated_state, get_v2_migrated_state};
+    use crate::db::{
+        get_v1_migrated_state, get_v2_migrated_state, GatewayMisbehavior,
+        GatewayMisbehaviorGatewayPrefix, GatewayMisbehaviorKey, GatewayMisbehaviorKeyPrefix,
+    };
     use crate::receive::{
         LightningReceiveConfirmedInvoice, LightningReceiveStateMachine, LightningReceiveStates,
         LightningReceiveSubmittedOffer,
@@ -484,4 +551,52 @@ async fn test_sm_migration_to_v3_submitted() {
             (new_state.consensus_encode_to_vec(), operation_id)
         );
     }
+
+    #[tokio::test]
+    async fn gateway_misbehavior_is_looked_up_per_gateway() {
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let gateways = [
+            Keypair::new(SECP256K1, &mut thread_rng()).public_key(),
+            Keypair::new(SECP256K1, &mut thread_rng()).public_key(),
+        ];
+
+        let mut dbtx = db.begin_transaction().await;
+        for (gateway_id, incidents) in gateways.iter().zip([1u8, 2]) {
+            for incident in 0..incidents {
+                dbtx.insert_new_entry(
+                    &GatewayMisbehaviorKey {
+                        gateway_id: *gateway_id,
+                        contract_id: ContractId::from_byte_array([incident; 32]),
+                    },
+                    &GatewayMisbehavior {
+                        operation_id: OperationId::new_random(),
+                        amount: Amount::from_sats(1000),
+                        timelock: 500,
+                        funded_at: SystemTime::UNIX_EPOCH,
+                        refunded_at: SystemTime::UNIX_EPOCH,
+                    },
+                )
+                .await;
+            }
+        }
+        dbtx.commit_tx().await;
+
+        let mut dbtx = db.begin_transaction_nc().await;
+        for (gateway_id, incidents) in gateways.iter().zip([1, 2]) {
+            assert_eq!(
+                dbtx.find_by_prefix(&GatewayMisbehaviorGatewayPrefix(*gateway_id))
+                    .await
+                    .count()
+                    .await,
+                incidents
+            );
+        }
+        assert_eq!(
+            dbtx.find_by_prefix(&GatewayMisbehaviorKeyPrefix)
+                .await
+                .count()
+                .await,
+            3
+        );
+    }
 }
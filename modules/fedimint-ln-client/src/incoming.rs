@@ -16,7 +16,7 @@
 use fedimint_client::DynGlobalClientContext;
 use fedimint_core::core::OperationId;
 use fedimint_core::encoding::{Decodable, Encodable};
-use fedimint_core::runtime::sleep;
+use fedimint_core::util::backoff_util::custom_backoff;
 use fedimint_core::{Amount, OutPoint, TransactionId};
 use fedimint_ln_common::contracts::incoming::IncomingContractAccount;
 use fedimint_ln_common::contracts::{ContractId, Preimage};
@@ -24,7 +24,7 @@
 use lightning_invoice::Bolt11Invoice;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info};
 
 use crate::api::LnFederationApi;
 use crate::{set_payment_result, LightningClientContext, PayType};
@@ -236,31 +236,29 @@ async fn await_preimage_decryption(
         global_context: DynGlobalClientContext,
         contract_id: ContractId,
     ) -> Result<Preimage, IncomingSmError> {
-        loop {
-            debug!("Awaiting preimage decryption for contract {contract_id:?}");
-            match global_context
-                .module_api()
-                .wait_preimage_decrypted(contract_id)
-                .await
-            {
-                Ok((incoming_contract_account, preimage)) => {
-                    if let Some(preimage) = preimage {
-                        debug!("Preimage decrypted for contract {contract_id:?}");
-                        return Ok(preimage);
-                    }
-
-                    info!("Invalid preimage for contract {contract_id:?}");
-                    return Err(IncomingSmError::InvalidPreimage {
-                        contract: Box::new(incoming_contract_account),
-                    });
-                }
-                Err(error) => {
-                    warn!("Incoming contract {contract_id:?} error waiting for preimage decryption: {error:?}, will keep retrying...");
-                }
-            }
-
-            sleep(Duration::from_secs(1)).await;
+        debug!("Awaiting preimage decryption for contract {contract_id:?}");
+        let (incoming_contract_account, preimage) = global_context
+            .retry(
+                "await-preimage-decryption",
+                custom_backoff(Duration::from_secs(1), Duration::from_secs(1), None),
+                || async {
+                    Ok(global_context
+                        .module_api()
+                        .wait_preimage_decrypted(contract_id)
+                        .await?)
+                },
+            )
+            .await;
+
+        if let Some(preimage) = preimage {
+            debug!("Preimage decrypted for contract {contract_id:?}");
+            return Ok(preimage);
         }
+
+        info!("Invalid preimage for contract {contract_id:?}");
+        Err(IncomingSmError::InvalidPreimage {
+            contract: Box::new(incoming_contract_account),
+        })
     }
 
     async fn transition_incoming_contract_funded(
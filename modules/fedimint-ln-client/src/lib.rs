@@ -26,11 +26,14 @@
 use bitcoin::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
 use bitcoin::Network;
 use db::{
-    DbKeyPrefix, LightningGatewayKey, LightningGatewayKeyPrefix, PaymentResult, PaymentResultKey,
+    ActiveGatewayIdKey, ActiveGatewayIdKeyPrefix, DbKeyPrefix, GatewayMisbehavior,
+    GatewayMisbehaviorGatewayPrefix, GatewayMisbehaviorKey, GatewayMisbehaviorKeyPrefix,
+    LightningGatewayKey, LightningGatewayKeyPrefix, PaymentResult, PaymentResultKey,
 };
 use fedimint_api_client::api::DynModuleApi;
 use fedimint_client::db::{migrate_state, ClientMigrationFn};
 use fedimint_client::derivable_secret::ChildId;
+use fedimint_client::event_bus::ClientEvent;
 use fedimint_client::module::init::{ClientModuleInit, ClientModuleInitArgs};
 use fedimint_client::module::recovery::NoModuleBackup;
 use fedimint_client::module::{ClientContext, ClientModule, IClientModule, OutPointRange};
@@ -52,7 +55,7 @@
 use fedimint_core::secp256k1::{
     All, Keypair, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification,
 };
-use fedimint_core::task::{timeout, MaybeSend, MaybeSync};
+use fedimint_core::task::{timeout, MaybeSend, MaybeSync, TaskGroup};
 use fedimint_core::util::update_merge::UpdateMerge;
 use fedimint_core::util::{backoff_util, retry, BoxStream};
 use fedimint_core::{
@@ -67,6 +70,7 @@
     Contract, ContractId, DecryptedPreimage, EncryptedPreimage, IdentifiableContract, Preimage,
     PreimageKey,
 };
+use fedimint_ln_common::federation_endpoint_constants::AWAIT_ACCOUNT_ENDPOINT;
 use fedimint_ln_common::gateway_endpoint_constants::{
     GET_GATEWAY_ID_ENDPOINT, PAY_INVOICE_ENDPOINT,
 };
@@ -83,12 +87,12 @@
 };
 use pay::PayInvoicePayload;
 use rand::rngs::OsRng;
-use rand::seq::IteratorRandom as _;
+use rand::seq::{IteratorRandom as _, SliceRandom as _};
 use rand::{CryptoRng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use strum::IntoEnumIterator;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::db::PaymentResultPrefix;
 use crate::incoming::{
@@ -108,6 +112,15 @@
 /// client can get refund
 const OUTGOING_LN_CONTRACT_TIMELOCK: u64 = 500;
 
+/// Number of recorded misbehaviors after which a gateway is no longer selected
+/// automatically. Gateways with fewer are only selected if no gateway without
+/// any is available.
+const GATEWAY_BLACKLIST_THRESHOLD: usize = 3;
+
+/// Number of gateways [`LightningClientModule::transfer_to_federation`] tries
+/// before giving up
+const FEDERATION_TRANSFER_MAX_ATTEMPTS: usize = 3;
+
 // 24 hours. Many wallets default to 1 hour, but it's a bad user experience if
 // invoices expire too quickly
 const DEFAULT_INVOICE_EXPIRY_TIME: Duration = Duration::from_secs(60 * 60 * 24);
@@ -320,6 +333,26 @@ async fn dump_database(
                         "Lightning Gateways"
                     );
                 }
+                DbKeyPrefix::GatewayMisbehavior => {
+                    push_db_pair_items!(
+                        dbtx,
+                        GatewayMisbehaviorKeyPrefix,
+                        GatewayMisbehaviorKey,
+                        GatewayMisbehavior,
+                        ln_client_items,
+                        "Gateway Misbehavior"
+                    );
+                }
+                DbKeyPrefix::ActiveGatewayId => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ActiveGatewayIdKeyPrefix,
+                        ActiveGatewayIdKey,
+                        secp256k1::PublicKey,
+                        ln_client_items,
+                        "Active Gateway Id"
+                    );
+                }
                 DbKeyPrefix::ExternalReservedStart
                 | DbKeyPrefix::CoreInternalReservedStart
                 | DbKeyPrefix::CoreInternalReservedEnd => {}
@@ -396,6 +429,7 @@ pub struct LightningClientModule {
     client_ctx: ClientContext<Self>,
     update_gateway_cache_merge: UpdateMerge,
     gateway_conn: Arc<dyn GatewayConnection + Send + Sync>,
+    task_group: TaskGroup,
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -414,6 +448,19 @@ fn context(&self) -> Self::ModuleStateMachineContext {
         }
     }
 
+    async fn start(&self) {
+        self.task_group
+            .spawn_cancellable("gateway registry refresh", {
+                let client_ctx = self.client_ctx.clone();
+                async move {
+                    client_ctx
+                        .self_ref()
+                        .update_gateway_cache_continuously(|gateways| async { gateways })
+                        .await;
+                }
+            });
+    }
+
     fn input_fee(
         &self,
         _amount: Amount,
@@ -471,7 +518,13 @@ async fn handle_rpc(
                 "pay_bolt11_invoice" => {
                     let req: PayBolt11InvoiceRequest = serde_json::from_value(payload)?;
                     let outgoing_payment = self
-                        .pay_bolt11_invoice(req.maybe_gateway, req.invoice, req.extra_meta)
+                        .pay_bolt11_invoice_with_fee_limit(
+                            req.maybe_gateway,
+                            req.invoice,
+                            req.amount,
+                            req.fee_limit,
+                            req.extra_meta,
+                        )
                         .await?;
                     yield serde_json::to_value(outgoing_payment)?;
                 }
@@ -533,6 +586,10 @@ async fn handle_rpc(
                     self.update_gateway_cache().await?;
                     yield serde_json::Value::Null;
                 }
+                "list_gateway_misbehavior" => {
+                    let reports = self.list_gateway_misbehavior().await;
+                    yield serde_json::to_value(reports)?;
+                }
                 _ => {
                     Err(anyhow::format_err!("Unknown method: {}", method))?;
                     unreachable!()
@@ -555,6 +612,11 @@ struct CreateBolt11InvoiceRequest {
 struct PayBolt11InvoiceRequest {
     maybe_gateway: Option<LightningGateway>,
     invoice: Bolt11Invoice,
+    /// Amount to pay, required if the invoice doesn't specify one
+    #[serde(default)]
+    amount: Option<Amount>,
+    #[serde(default)]
+    fee_limit: PaymentFeeLimit,
     extra_meta: Option<serde_json::Value>,
 }
 
@@ -591,6 +653,31 @@ struct SubscribeLnClaimRequest {
     operation_id: OperationId,
 }
 
+/// Misbehavior recorded against a gateway, see
+/// [`LightningClientModule::list_gateway_misbehavior`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayMisbehaviorReport {
+    pub gateway_id: secp256k1::PublicKey,
+    /// Whether the gateway is excluded from automatic gateway selection
+    pub blacklisted: bool,
+    pub incidents: BTreeMap<ContractId, GatewayMisbehavior>,
+}
+
+/// Result of a [`LightningClientModule::transfer_to_federation`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederationTransfer {
+    pub target_federation_id: FederationId,
+    /// Amount received by the target federation's client
+    pub amount: Amount,
+    /// Gateway fee paid on top of `amount`
+    pub fee: Amount,
+    pub gateway_id: secp256k1::PublicKey,
+    /// Number of gateways tried, including the successful one
+    pub attempts: usize,
+    pub pay_operation_id: OperationId,
+    pub receive_operation_id: OperationId,
+}
+
 #[derive(Deserialize)]
 struct GetGatewayRequest {
     gateway_id: Option<secp256k1::PublicKey>,
@@ -605,6 +692,74 @@ pub enum PayBolt11InvoiceError {
     NoLnGatewayAvailable,
     #[error("Funded contract already exists: {}", .contract_id)]
     FundedContractAlreadyExists { contract_id: ContractId },
+    #[error("Invoice has no amount and none was supplied")]
+    MissingAmount,
+    #[error("Cannot pay a zero-amount invoice with an amount of zero")]
+    ZeroAmount,
+    #[error("Supplied amount {amount} does not match the invoice amount {invoice_amount}")]
+    AmountMismatch {
+        invoice_amount: Amount,
+        amount: Amount,
+    },
+    #[error("Gateway does not support paying zero-amount invoices")]
+    GatewayDoesNotSupportZeroAmountInvoices,
+    #[error("Payment fee {fee} exceeds the fee limit {fee_limit}")]
+    FeeLimitExceeded { fee: Amount, fee_limit: Amount },
+}
+
+/// Determines the amount to pay for `invoice`, which is the caller supplied
+/// `amount` for zero-amount invoices and the invoice amount otherwise
+fn invoice_amount_to_pay(
+    invoice: &Bolt11Invoice,
+    amount: Option<Amount>,
+) -> Result<Amount, PayBolt11InvoiceError> {
+    match (invoice.amount_milli_satoshis(), amount) {
+        (Some(invoice_msats), Some(amount)) if invoice_msats != amount.msats => {
+            Err(PayBolt11InvoiceError::AmountMismatch {
+                invoice_amount: Amount::from_msats(invoice_msats),
+                amount,
+            })
+        }
+        (Some(invoice_msats), _) => Ok(Amount::from_msats(invoice_msats)),
+        (None, Some(amount)) if amount == Amount::ZERO => Err(PayBolt11InvoiceError::ZeroAmount),
+        (None, Some(amount)) => Ok(amount),
+        (None, None) => Err(PayBolt11InvoiceError::MissingAmount),
+    }
+}
+
+/// Upper bound for the fees of an outgoing payment, covering both the
+/// gateway's fee and the routing fees it pays. If both limits are set the
+/// lower one applies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentFeeLimit {
+    /// Maximum fee in absolute terms
+    pub max_fee: Option<Amount>,
+    /// Maximum fee in parts per million of the paid amount
+    pub max_fee_ppm: Option<u64>,
+}
+
+impl PaymentFeeLimit {
+    /// Returns the maximum fee for paying `amount`, or `None` if unbounded
+    pub fn fee_limit(&self, amount: Amount) -> Option<Amount> {
+        let ppm_limit = self
+            .max_fee_ppm
+            .map(|ppm| Amount::from_msats(amount.msats.saturating_mul(ppm) / 1_000_000));
+
+        match (self.max_fee, ppm_limit) {
+            (Some(max_fee), Some(ppm_limit)) => Some(max_fee.min(ppm_limit)),
+            (max_fee, ppm_limit) => max_fee.or(ppm_limit),
+        }
+    }
+
+    /// Checks the `fee` for paying `amount` against the limit
+    pub fn check(&self, amount: Amount, fee: Amount) -> Result<(), PayBolt11InvoiceError> {
+        match self.fee_limit(amount) {
+            Some(fee_limit) if fee_limit < fee => {
+                Err(PayBolt11InvoiceError::FeeLimitExceeded { fee, fee_limit })
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl LightningClientModule {
@@ -629,6 +784,7 @@ fn new(
             client_ctx: args.context(),
             update_gateway_cache_merge: UpdateMerge::default(),
             gateway_conn,
+            task_group: args.task_group().clone(),
         }
     }
 
@@ -676,6 +832,7 @@ async fn create_outgoing_output<'a, 'b>(
         &'a self,
         operation_id: OperationId,
         invoice: Bolt11Invoice,
+        invoice_amount: Amount,
         gateway: LightningGateway,
         fed_id: FederationId,
         mut rng: impl RngCore + CryptoRng + 'a,
@@ -712,12 +869,6 @@ async fn create_outgoing_output<'a, 'b>(
             consensus_count + min_final_cltv + OUTGOING_LN_CONTRACT_TIMELOCK - 1;
 
         // Compute amount to lock in the outgoing contract
-        let invoice_amount = Amount::from_msats(
-            invoice
-                .amount_milli_satoshis()
-                .context("MissingInvoiceAmount")?,
-        );
-
         let gateway_fee = gateway.fees.to_amount(&invoice_amount);
         let contract_amount = invoice_amount + gateway_fee;
 
@@ -788,19 +939,13 @@ async fn create_incoming_output(
         &self,
         operation_id: OperationId,
         invoice: Bolt11Invoice,
+        invoice_amount: Amount,
     ) -> anyhow::Result<(
         ClientOutput<LightningOutputV0>,
         ClientOutputSM<LightningClientStateMachines>,
         ContractId,
     )> {
         let payment_hash = *invoice.payment_hash();
-        let invoice_amount = Amount {
-            msats: invoice
-                .amount_milli_satoshis()
-                .ok_or(IncomingSmError::AmountError {
-                    invoice: invoice.clone(),
-                })?,
-        };
 
         let (incoming_output, amount, contract_id) = create_incoming_contract_output(
             &self.module_api,
@@ -1018,6 +1163,13 @@ pub async fn update_gateway_cache(&self) -> anyhow::Result<()> {
                 let gateways = self.module_api.fetch_gateways().await?;
                 let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
 
+                let mut previous_gateways = dbtx
+                    .find_by_prefix(&LightningGatewayKeyPrefix)
+                    .await
+                    .map(|(key, gw)| (key.0, gw.info))
+                    .collect::<BTreeMap<_, _>>()
+                    .await;
+
                 // Remove all previous gateway entries
                 dbtx.remove_by_prefix(&LightningGatewayKeyPrefix).await;
 
@@ -1027,6 +1179,21 @@ pub async fn update_gateway_cache(&self) -> anyhow::Result<()> {
                         &gw.clone().anchor(),
                     )
                     .await;
+
+                    if previous_gateways.remove(&gw.info.gateway_id).as_ref() != Some(&gw.info) {
+                        self.client_ctx.publish_client_event(
+                            &mut dbtx,
+                            ClientEvent::GatewayChanged {
+                                gateway_id: gw.info.gateway_id,
+                            },
+                        );
+                    }
+                }
+
+                // Whatever is left was not announced anymore
+                let active_gateway_id = dbtx.get_value(&ActiveGatewayIdKey).await;
+                for gateway_id in previous_gateways.into_keys() {
+                    self.publish_gateway_removed(&mut dbtx, gateway_id, active_gateway_id);
                 }
 
                 dbtx.commit_tx().await;
@@ -1036,10 +1203,88 @@ pub async fn update_gateway_cache(&self) -> anyhow::Result<()> {
             .await
     }
 
+    /// Removes cached registrations that expired, so they are dropped even
+    /// while the federation is unreachable
+    async fn remove_expired_gateways(&self) {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+        let expired = dbtx
+            .find_by_prefix(&LightningGatewayKeyPrefix)
+            .await
+            .filter_map(|(key, gw)| async move { gw.is_expired().then_some(key.0) })
+            .collect::<Vec<_>>()
+            .await;
+
+        if expired.is_empty() {
+            return;
+        }
+
+        let active_gateway_id = dbtx.get_value(&ActiveGatewayIdKey).await;
+        for gateway_id in expired {
+            debug!(target: LOG_CLIENT_MODULE_LN, %gateway_id, "Removing expired gateway");
+            dbtx.remove_entry(&LightningGatewayKey(gateway_id)).await;
+            self.publish_gateway_removed(&mut dbtx, gateway_id, active_gateway_id);
+        }
+
+        dbtx.commit_tx().await;
+    }
+
+    fn publish_gateway_removed<Cap: Send>(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_, Cap>,
+        gateway_id: secp256k1::PublicKey,
+        active_gateway_id: Option<secp256k1::PublicKey>,
+    ) {
+        self.client_ctx
+            .publish_client_event(dbtx, ClientEvent::GatewayChanged { gateway_id });
+
+        if active_gateway_id == Some(gateway_id) {
+            warn!(target: LOG_CLIENT_MODULE_LN, %gateway_id, "Active gateway is no longer available");
+            self.client_ctx
+                .publish_client_event(dbtx, ClientEvent::ActiveGatewayUnavailable { gateway_id });
+        }
+    }
+
+    /// Selects the gateway payments should be routed through unless the caller
+    /// picks one, see [`Self::get_gateway`]. The gateway has to be in the
+    /// gateway cache, if it expires or disappears from the federation later
+    /// [`ClientEvent::ActiveGatewayUnavailable`] is published.
+    pub async fn set_active_gateway(
+        &self,
+        gateway_id: &secp256k1::PublicKey,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            self.select_gateway(gateway_id).await.is_some(),
+            "Gateway {gateway_id} is not registered with the federation"
+        );
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+        dbtx.insert_entry(&ActiveGatewayIdKey, gateway_id).await;
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
+    /// Returns the gateway selected with [`Self::set_active_gateway`] if it is
+    /// still registered with the federation
+    pub async fn get_active_gateway(&self) -> Option<LightningGateway> {
+        let gateway_id = self
+            .client_ctx
+            .module_db()
+            .begin_transaction_nc()
+            .await
+            .get_value(&ActiveGatewayIdKey)
+            .await?;
+
+        self.select_gateway(&gateway_id).await
+    }
+
     /// Continuously update the gateway cache whenever a gateway expires.
     ///
     /// The gateways returned by `gateway_filters` are checked for expiry.
-    /// Client integrators are expected to call this function in a spawned task.
+    /// Expired gateways are removed from the cache even while the federation
+    /// is unreachable. The module runs this for all gateways in the
+    /// background while the client is running, so integrators don't need to
+    /// spawn it themselves anymore.
     pub async fn update_gateway_cache_continuously<Fut>(
         &self,
         gateways_filter: impl Fn(Vec<LightningGatewayAnnouncement>) -> Fut,
@@ -1071,13 +1316,59 @@ pub async fn update_gateway_cache_continuously<Fut>(
             let _ = retry(
                 "update_gateway_cache",
                 backoff_util::background_backoff(),
-                || self.update_gateway_cache(),
+                || async {
+                    self.remove_expired_gateways().await;
+                    self.update_gateway_cache().await
+                },
             )
             .await;
             first_time = false;
         }
     }
 
+    /// Returns the evidence recorded against gateways that kept the funds of
+    /// an outgoing payment locked until the contract timed out.
+    pub async fn list_gateway_misbehavior(&self) -> Vec<GatewayMisbehaviorReport> {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction_nc().await;
+        let misbehavior = dbtx
+            .find_by_prefix(&GatewayMisbehaviorKeyPrefix)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut reports = BTreeMap::<_, BTreeMap<_, _>>::new();
+        for (key, misbehavior) in misbehavior {
+            reports
+                .entry(key.gateway_id)
+                .or_default()
+                .insert(key.contract_id, misbehavior);
+        }
+
+        reports
+            .into_iter()
+            .map(|(gateway_id, incidents)| GatewayMisbehaviorReport {
+                gateway_id,
+                blacklisted: GATEWAY_BLACKLIST_THRESHOLD <= incidents.len(),
+                incidents,
+            })
+            .collect()
+    }
+
+    async fn gateway_misbehavior_counts(&self) -> BTreeMap<secp256k1::PublicKey, usize> {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction_nc().await;
+        let mut counts = BTreeMap::new();
+        for gateway in self.list_gateways().await {
+            let gateway_id = gateway.info.gateway_id;
+            let count = dbtx
+                .find_by_prefix(&GatewayMisbehaviorGatewayPrefix(gateway_id))
+                .await
+                .count()
+                .await;
+            counts.insert(gateway_id, count);
+        }
+        counts
+    }
+
     /// Returns all gateways that are currently in the gateway cache.
     pub async fn list_gateways(&self) -> Vec<LightningGatewayAnnouncement> {
         let mut dbtx = self.client_ctx.module_db().begin_transaction_nc().await;
@@ -1102,6 +1393,49 @@ pub async fn pay_bolt11_invoice<M: Serialize + MaybeSend + MaybeSync>(
         invoice: Bolt11Invoice,
         extra_meta: M,
     ) -> anyhow::Result<OutgoingLightningPayment> {
+        self.pay_bolt11_invoice_with_amount(maybe_gateway, invoice, None, extra_meta)
+            .await
+    }
+
+    /// Like [`LightningClientModule::pay_bolt11_invoice`], but lets the caller
+    /// supply the `amount` to pay, which is required for zero-amount invoices.
+    /// If the invoice specifies an amount, `amount` has to be `None` or match
+    /// it.
+    ///
+    /// Paying a zero-amount invoice over lightning requires a gateway that
+    /// supports private payments, since only those accept an amount separate
+    /// from the invoice.
+    pub async fn pay_bolt11_invoice_with_amount<M: Serialize + MaybeSend + MaybeSync>(
+        &self,
+        maybe_gateway: Option<LightningGateway>,
+        invoice: Bolt11Invoice,
+        amount: Option<Amount>,
+        extra_meta: M,
+    ) -> anyhow::Result<OutgoingLightningPayment> {
+        self.pay_bolt11_invoice_with_fee_limit(
+            maybe_gateway,
+            invoice,
+            amount,
+            PaymentFeeLimit::default(),
+            extra_meta,
+        )
+        .await
+    }
+
+    /// Like [`LightningClientModule::pay_bolt11_invoice_with_amount`], but
+    /// aborts the payment before the contract is funded with
+    /// [`PayBolt11InvoiceError::FeeLimitExceeded`] if the fees quoted by the
+    /// gateway exceed `fee_limit`.
+    pub async fn pay_bolt11_invoice_with_fee_limit<M: Serialize + MaybeSend + MaybeSync>(
+        &self,
+        maybe_gateway: Option<LightningGateway>,
+        invoice: Bolt11Invoice,
+        amount: Option<Amount>,
+        fee_limit: PaymentFeeLimit,
+        extra_meta: M,
+    ) -> anyhow::Result<OutgoingLightningPayment> {
+        let amount = invoice_amount_to_pay(&invoice, amount)?;
+
         let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
         let maybe_gateway_id = maybe_gateway.as_ref().map(|g| g.gateway_id);
         let prev_payment_result = self
@@ -1157,7 +1491,7 @@ pub async fn pay_bolt11_invoice<M: Serialize + MaybeSend + MaybeSync>(
 
         let (pay_type, client_output, client_output_sm, contract_id) = if is_internal_payment {
             let (output, output_sm, contract_id) = self
-                .create_incoming_output(operation_id, invoice.clone())
+                .create_incoming_output(operation_id, invoice.clone(), amount)
                 .await?;
             (
                 PayType::Internal(operation_id),
@@ -1167,10 +1501,14 @@ pub async fn pay_bolt11_invoice<M: Serialize + MaybeSend + MaybeSync>(
             )
         } else {
             let gateway = maybe_gateway.context(PayBolt11InvoiceError::NoLnGatewayAvailable)?;
+            if invoice.amount_milli_satoshis().is_none() && !gateway.supports_private_payments {
+                bail!(PayBolt11InvoiceError::GatewayDoesNotSupportZeroAmountInvoices);
+            }
             let (output, output_sm, contract_id) = self
                 .create_outgoing_output(
                     operation_id,
                     invoice.clone(),
+                    amount,
                     gateway,
                     self.client_ctx
                         .get_config()
@@ -1202,17 +1540,15 @@ pub async fn pay_bolt11_invoice<M: Serialize + MaybeSend + MaybeSync>(
                 let fee_msat = contract
                     .amount
                     .msats
-                    .checked_sub(
-                        invoice
-                            .amount_milli_satoshis()
-                            .ok_or(anyhow!("MissingInvoiceAmount"))?,
-                    )
+                    .checked_sub(amount.msats)
                     .expect("Contract amount should be greater or equal than invoice amount");
                 Amount::from_msats(fee_msat)
             }
             _ => unreachable!("User client will only create contract outputs on spend"),
         };
 
+        fee_limit.check(amount, fee)?;
+
         let output = self.client_ctx.make_client_outputs(ClientOutputBundle::new(
             vec![ClientOutput {
                 output: LightningOutput::V0(client_output.output),
@@ -1541,15 +1877,38 @@ pub async fn create_bolt11_invoice<M: Serialize + Send + Sync>(
     ) -> anyhow::Result<(OperationId, Bolt11Invoice, [u8; 32])> {
         let receiving_key =
             ReceivingKey::Personal(Keypair::new(&self.secp, &mut rand::rngs::OsRng));
-        self.create_bolt11_invoice_internal(
-            amount,
-            description,
-            expiry_time,
-            receiving_key,
-            extra_meta,
-            gateway,
-        )
-        .await
+        let (operation_id, invoice, preimage) = self
+            .create_bolt11_invoice_internal(
+                amount,
+                description,
+                expiry_time,
+                receiving_key,
+                extra_meta,
+                gateway,
+            )
+            .await?;
+
+        // The incoming contract gets funded once the invoice was paid, so a
+        // wallet sleeping in the background can be woken to claim it. Talking
+        // to the relay must not delay handing out the invoice.
+        let contract_id = ContractId::from_raw_hash(*invoice.payment_hash());
+        self.task_group.spawn_cancellable("register invoice push watch", {
+            let client_ctx = self.client_ctx.clone();
+            async move {
+                if let Err(e) = client_ctx
+                    .add_push_watch(
+                        operation_id.fmt_full().to_string(),
+                        AWAIT_ACCOUNT_ENDPOINT,
+                        contract_id,
+                    )
+                    .await
+                {
+                    warn!(target: LOG_CLIENT_MODULE_LN, err = %e, "Failed to register push watch for invoice");
+                }
+            }
+        });
+
+        Ok((operation_id, invoice, preimage))
     }
 
     /// Receive over LN with a new invoice for another user, tweaking their key
@@ -1764,9 +2123,11 @@ pub async fn subscribe_ln_receive(
         }))
     }
 
-    /// Returns a gateway to be used for a lightning operation. If
-    /// `force_internal` is true and no `gateway_id` is specified, no
-    /// gateway will be selected.
+    /// Returns a gateway to be used for a lightning operation. If no
+    /// `gateway_id` is specified, the gateway selected with
+    /// [`Self::set_active_gateway`] is used if it is still registered, a random
+    /// one otherwise. If `force_internal` is true and no `gateway_id` is
+    /// specified, no gateway will be selected.
     pub async fn get_gateway(
         &self,
         gateway_id: Option<secp256k1::PublicKey>,
@@ -1786,15 +2147,26 @@ pub async fn get_gateway(
             None if !force_internal => {
                 // Refresh the gateway cache to find a random gateway to select from.
                 self.update_gateway_cache().await?;
+                if let Some(gw) = self.get_active_gateway().await {
+                    let gw_id = gw.gateway_id;
+                    info!(%gw_id, "Using active gateway");
+                    return Ok(Some(gw));
+                }
                 let gateways = self.list_gateways().await;
-                let gw = gateways.into_iter().choose(&mut OsRng).map(|gw| gw.info);
+                let misbehavior = self.gateway_misbehavior_counts().await;
+                let gw = least_misbehaving_gateways(
+                    gateways.into_iter().map(|gw| gw.info).collect(),
+                    &misbehavior,
+                )
+                .into_iter()
+                .choose(&mut OsRng);
                 if let Some(gw) = gw {
                     let gw_id = gw.gateway_id;
                     info!(%gw_id, "Using random gateway");
                     Ok(Some(gw))
                 } else {
                     Err(anyhow!(
-                        "No gateways exist in gateway cache and `force_internal` is false"
+                        "No gateways that aren't blacklisted exist in gateway cache and `force_internal` is false"
                     ))
                 }
             }
@@ -1892,6 +2264,152 @@ pub async fn wait_for_ln_payment(
         };
         bail!("Lightning Payment failed")
     }
+
+    /// Moves `amount` to the federation of `target` by paying an invoice
+    /// created by its lightning module. The target receives exactly `amount`,
+    /// the gateway fee is paid by this client on top.
+    ///
+    /// Gateways connected to both federations are preferred, since they can
+    /// swap the funds without routing over the lightning network. If a gateway
+    /// fails to pay, the payment is retried with the next one once it was
+    /// refunded, up to [`FEDERATION_TRANSFER_MAX_ATTEMPTS`] gateways.
+    pub async fn transfer_to_federation(
+        &self,
+        target: &ClientHandleArc,
+        amount: Amount,
+    ) -> anyhow::Result<FederationTransfer> {
+        let federation_id = self
+            .client_ctx
+            .get_config()
+            .await
+            .global
+            .calculate_federation_id();
+        let target_federation_id = target.federation_id();
+        ensure!(
+            target_federation_id != federation_id,
+            "Target client belongs to the same federation"
+        );
+        let target_ln = target.get_first_module::<LightningClientModule>()?;
+
+        self.update_gateway_cache().await?;
+        target_ln.update_gateway_cache().await?;
+
+        let target_gateways = target_ln
+            .list_gateways()
+            .await
+            .into_iter()
+            .map(|gw| (gw.info.gateway_id, gw.info))
+            .collect::<BTreeMap<_, _>>();
+        let gateways = federation_transfer_gateways(
+            self.list_gateways()
+                .await
+                .into_iter()
+                .map(|gw| gw.info)
+                .collect(),
+            &target_gateways,
+            &self.gateway_misbehavior_counts().await,
+        );
+
+        let first_gateway = gateways
+            .first()
+            .context(PayBolt11InvoiceError::NoLnGatewayAvailable)?;
+        // Receiving through the same gateway lets it settle the payment internally
+        let receive_gateway = match target_gateways.get(&first_gateway.gateway_id) {
+            Some(gateway) => Some(gateway.clone()),
+            None => target_ln.get_gateway(None, false).await?,
+        };
+
+        let (receive_operation_id, invoice, _) = target_ln
+            .create_bolt11_invoice(
+                amount,
+                lightning_invoice::Bolt11InvoiceDescription::Direct(
+                    &lightning_invoice::Description::new(format!(
+                        "Transfer from federation {federation_id}"
+                    ))?,
+                ),
+                None,
+                json!({ "transfer_from": federation_id }),
+                receive_gateway,
+            )
+            .await?;
+
+        let mut attempts = 0;
+        let (gateway_id, payment) = loop {
+            let Some(gateway) = gateways.get(attempts) else {
+                bail!("Transfer failed after trying {attempts} gateways");
+            };
+            attempts += 1;
+
+            let payment = self
+                .pay_bolt11_invoice(
+                    Some(gateway.clone()),
+                    invoice.clone(),
+                    json!({ "transfer_to": target_federation_id }),
+                )
+                .await?;
+            match self.await_transfer_payment(&payment.payment_type).await {
+                Ok(()) => break (gateway.gateway_id, payment),
+                Err(e) => {
+                    warn!(
+                        target: LOG_CLIENT_MODULE_LN,
+                        gateway_id = %gateway.gateway_id,
+                        err = %e,
+                        "Gateway failed to pay transfer invoice"
+                    );
+                }
+            }
+        };
+
+        let mut updates = target_ln
+            .subscribe_ln_receive(receive_operation_id)
+            .await?
+            .into_stream();
+        while let Some(update) = updates.next().await {
+            match update {
+                LnReceiveState::Claimed => {
+                    return Ok(FederationTransfer {
+                        target_federation_id,
+                        amount,
+                        fee: payment.fee,
+                        gateway_id,
+                        attempts,
+                        pay_operation_id: payment.payment_type.operation_id(),
+                        receive_operation_id,
+                    });
+                }
+                LnReceiveState::Canceled { reason } => {
+                    bail!("Target client failed to claim the transfer: {reason}")
+                }
+                _ => {}
+            }
+        }
+        bail!("Target client failed to claim the transfer")
+    }
+
+    /// Waits until a payment of [`Self::transfer_to_federation`] either
+    /// succeeded or was refunded, so it can be retried
+    async fn await_transfer_payment(&self, payment_type: &PayType) -> anyhow::Result<()> {
+        let PayType::Lightning(operation_id) = payment_type else {
+            bail!("Transfer invoice was unexpectedly paid internally");
+        };
+
+        let mut updates = self.subscribe_ln_pay(*operation_id).await?.into_stream();
+        while let Some(update) = updates.next().await {
+            match update {
+                LnPayState::Success { .. } => return Ok(()),
+                LnPayState::Refunded { gateway_error } => bail!("Refunded: {gateway_error}"),
+                LnPayState::Canceled => bail!("Funding transaction was rejected"),
+                LnPayState::UnexpectedError { error_message } => {
+                    bail!("UnexpectedError: {error_message}")
+                }
+                LnPayState::Created
+                | LnPayState::Funded { .. }
+                | LnPayState::AwaitingChange
+                | LnPayState::WaitingForRefund { .. } => {}
+            }
+        }
+        bail!("Lightning Payment failed")
+    }
 }
 
 // TODO: move to appropriate module (cli?)
@@ -2088,8 +2606,8 @@ pub async fn get_invoice(
                 (Some(_), Some(_)) => {
                     bail!("Amount specified in both invoice and command line")
                 }
-                (None, _) => {
-                    bail!("We don't support invoices without an amount")
+                (None, None) => {
+                    bail!("Invoice has no amount, an amount must be specified")
                 }
                 _ => {}
             };
@@ -2268,3 +2786,258 @@ pub async fn ln_operation(
 
     Ok(operation)
 }
+
+/// Returns the gateways with the fewest recorded misbehaviors, never including
+/// blacklisted ones
+fn least_misbehaving_gateways(
+    gateways: Vec<LightningGateway>,
+    misbehavior: &BTreeMap<secp256k1::PublicKey, usize>,
+) -> Vec<LightningGateway> {
+    let misbehavior_count =
+        |gw: &LightningGateway| misbehavior.get(&gw.gateway_id).copied().unwrap_or_default();
+    let fewest_misbehaviors = gateways
+        .iter()
+        .map(misbehavior_count)
+        .filter(|count| *count < GATEWAY_BLACKLIST_THRESHOLD)
+        .min();
+    gateways
+        .into_iter()
+        .filter(|gw| Some(misbehavior_count(gw)) == fewest_misbehaviors)
+        .collect()
+}
+
+/// Returns the gateways [`LightningClientModule::transfer_to_federation`]
+/// tries, in order: gateways also connected to the target federation first,
+/// then the ones with fewer recorded misbehaviors, never including blacklisted
+/// ones. Gateways of the same rank are tried in random order.
+fn federation_transfer_gateways(
+    mut gateways: Vec<LightningGateway>,
+    target_gateways: &BTreeMap<secp256k1::PublicKey, LightningGateway>,
+    misbehavior: &BTreeMap<secp256k1::PublicKey, usize>,
+) -> Vec<LightningGateway> {
+    let misbehavior_count =
+        |gw: &LightningGateway| misbehavior.get(&gw.gateway_id).copied().unwrap_or_default();
+    gateways.retain(|gw| misbehavior_count(gw) < GATEWAY_BLACKLIST_THRESHOLD);
+    gateways.shuffle(&mut OsRng);
+    // The sort is stable, so gateways of the same rank stay shuffled
+    gateways.sort_by_key(|gw| {
+        (
+            !target_gateways.contains_key(&gw.gateway_id),
+            misbehavior_count(gw),
+        )
+    });
+    gateways.truncate(FEDERATION_TRANSFER_MAX_ATTEMPTS);
+    gateways
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use fedimint_core::secp256k1::{Secp256k1, SecretKey};
+    use fedimint_ln_common::PrunedInvoice;
+
+    use super::*;
+
+    fn invoice(amount_msats: Option<u64>) -> Bolt11Invoice {
+        let ctx = Secp256k1::new();
+        let secret_key = SecretKey::new(&mut rand::thread_rng());
+        let mut builder = InvoiceBuilder::new(Currency::Regtest)
+            .description(String::new())
+            .payment_hash(sha256::Hash::hash(&[0; 32]))
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(0)
+            .payment_secret(PaymentSecret([0; 32]));
+        if let Some(amount_msats) = amount_msats {
+            builder = builder.amount_milli_satoshis(amount_msats);
+        }
+
+        builder
+            .build_signed(|m| ctx.sign_ecdsa_recoverable(m, &secret_key))
+            .expect("Invoice is valid")
+    }
+
+    #[test]
+    fn invoice_amount_is_paid_if_present() {
+        let invoice = invoice(Some(1000));
+
+        assert_eq!(
+            invoice_amount_to_pay(&invoice, None).unwrap(),
+            Amount::from_msats(1000)
+        );
+        assert_eq!(
+            invoice_amount_to_pay(&invoice, Some(Amount::from_msats(1000))).unwrap(),
+            Amount::from_msats(1000)
+        );
+        assert!(matches!(
+            invoice_amount_to_pay(&invoice, Some(Amount::from_msats(2000))),
+            Err(PayBolt11InvoiceError::AmountMismatch { invoice_amount, amount })
+                if invoice_amount == Amount::from_msats(1000) && amount == Amount::from_msats(2000)
+        ));
+    }
+
+    #[test]
+    fn zero_amount_invoice_requires_non_zero_amount() {
+        let invoice = invoice(None);
+
+        assert_eq!(
+            invoice_amount_to_pay(&invoice, Some(Amount::from_msats(1500))).unwrap(),
+            Amount::from_msats(1500)
+        );
+        assert!(matches!(
+            invoice_amount_to_pay(&invoice, None),
+            Err(PayBolt11InvoiceError::MissingAmount)
+        ));
+        assert!(matches!(
+            invoice_amount_to_pay(&invoice, Some(Amount::ZERO)),
+            Err(PayBolt11InvoiceError::ZeroAmount)
+        ));
+    }
+
+    #[test]
+    fn pruned_zero_amount_invoice_carries_supplied_amount() {
+        let invoice = invoice(None);
+        let pruned = PrunedInvoice::new(&invoice, Amount::from_msats(1500));
+
+        assert_eq!(pruned.amount, Amount::from_msats(1500));
+        assert_eq!(pruned.payment_hash, *invoice.payment_hash());
+        assert!(PrunedInvoice::try_from(invoice).is_err());
+    }
+
+    #[test]
+    fn fee_limit_applies_the_lower_limit() {
+        let amount = Amount::from_sats(100_000);
+        let unbounded = PaymentFeeLimit::default();
+        let absolute = PaymentFeeLimit {
+            max_fee: Some(Amount::from_sats(500)),
+            max_fee_ppm: None,
+        };
+        let both = PaymentFeeLimit {
+            max_fee: Some(Amount::from_sats(500)),
+            max_fee_ppm: Some(1_000),
+        };
+
+        assert_eq!(unbounded.fee_limit(amount), None);
+        assert!(unbounded.check(amount, Amount::from_sats(50_000)).is_ok());
+
+        assert!(absolute.check(amount, Amount::from_sats(500)).is_ok());
+        assert!(matches!(
+            absolute.check(amount, Amount::from_sats(501)),
+            Err(PayBolt11InvoiceError::FeeLimitExceeded { fee, fee_limit })
+                if fee == Amount::from_sats(501) && fee_limit == Amount::from_sats(500)
+        ));
+
+        // 1000 ppm of 100k sats is 100 sats, below the absolute limit
+        assert_eq!(both.fee_limit(amount), Some(Amount::from_sats(100)));
+        assert!(matches!(
+            both.check(amount, Amount::from_sats(101)),
+            Err(PayBolt11InvoiceError::FeeLimitExceeded { fee_limit, .. })
+                if fee_limit == Amount::from_sats(100)
+        ));
+    }
+
+    fn gateway(id: u8) -> LightningGateway {
+        let public_key = SecretKey::from_slice(&[id; 32])
+            .expect("Valid secret key")
+            .public_key(&Secp256k1::new());
+        LightningGateway {
+            federation_index: id.into(),
+            gateway_redeem_key: public_key,
+            node_pub_key: public_key,
+            lightning_alias: format!("gateway {id}"),
+            api: "http://127.0.0.1:8175/v1".parse().expect("Valid URL"),
+            route_hints: vec![],
+            fees: RoutingFees {
+                base_msat: 0,
+                proportional_millionths: 0,
+            },
+            gateway_id: public_key,
+            supports_private_payments: false,
+        }
+    }
+
+    #[test]
+    fn gateway_selection_avoids_misbehaving_gateways() {
+        let gateways = vec![gateway(1), gateway(2), gateway(3)];
+        let ids = |gateways: Vec<LightningGateway>| {
+            gateways
+                .into_iter()
+                .map(|gw| gw.federation_index)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(least_misbehaving_gateways(
+                gateways.clone(),
+                &BTreeMap::new()
+            )),
+            vec![1, 2, 3]
+        );
+
+        let misbehavior = BTreeMap::from([(gateway(1).gateway_id, 1), (gateway(2).gateway_id, 2)]);
+        assert_eq!(
+            ids(least_misbehaving_gateways(gateways.clone(), &misbehavior)),
+            vec![3]
+        );
+
+        // Gateways with misbehaviors are still used if there is nothing better
+        let misbehavior = BTreeMap::from([
+            (gateway(1).gateway_id, 1),
+            (gateway(2).gateway_id, 1),
+            (gateway(3).gateway_id, GATEWAY_BLACKLIST_THRESHOLD),
+        ]);
+        assert_eq!(
+            ids(least_misbehaving_gateways(gateways.clone(), &misbehavior)),
+            vec![1, 2]
+        );
+
+        let misbehavior = gateways
+            .iter()
+            .map(|gw| (gw.gateway_id, GATEWAY_BLACKLIST_THRESHOLD))
+            .collect();
+        assert!(least_misbehaving_gateways(gateways, &misbehavior).is_empty());
+    }
+
+    #[test]
+    fn federation_transfers_prefer_gateways_connected_to_the_target() {
+        let gateways = (1..=5).map(gateway).collect::<Vec<_>>();
+        let ids = |gateways: Vec<LightningGateway>| {
+            gateways
+                .into_iter()
+                .map(|gw| gw.federation_index)
+                .collect::<Vec<_>>()
+        };
+        let target_gateways = [gateway(4), gateway(5)]
+            .into_iter()
+            .map(|gw| (gw.gateway_id, gw))
+            .collect();
+        let misbehavior = BTreeMap::from([
+            (gateway(1).gateway_id, GATEWAY_BLACKLIST_THRESHOLD),
+            (gateway(2).gateway_id, 1),
+            (gateway(4).gateway_id, 1),
+        ]);
+
+        // Connected gateways come first even if they misbehaved, the number of
+        // attempts is limited and blacklisted gateways are never tried
+        assert_eq!(
+            ids(federation_transfer_gateways(
+                gateways.clone(),
+                &target_gateways,
+                &misbehavior
+            )),
+            vec![5, 4, 3]
+        );
+
+        // Without connected gateways any of the equally ranked ones is tried
+        let candidates = ids(federation_transfer_gateways(
+            gateways,
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+        ));
+        assert_eq!(candidates.len(), FEDERATION_TRANSFER_MAX_ATTEMPTS);
+        assert_eq!(
+            candidates.iter().collect::<BTreeSet<_>>().len(),
+            FEDERATION_TRANSFER_MAX_ATTEMPTS
+        );
+    }
+}
@@ -1,14 +1,17 @@
 use std::time::{Duration, SystemTime};
 
 use bitcoin::hashes::sha256;
+use fedimint_client::event_bus::ClientEvent;
 use fedimint_client::sm::{ClientSMDatabaseTransaction, State, StateTransition};
 use fedimint_client::transaction::{ClientInput, ClientInputBundle};
 use fedimint_client::DynGlobalClientContext;
 use fedimint_core::config::FederationId;
 use fedimint_core::core::OperationId;
+use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::task::sleep;
 use fedimint_core::time::duration_since_epoch;
+use fedimint_core::util::backoff_util::background_backoff;
 use fedimint_core::{secp256k1, Amount, OutPoint, TransactionId};
 use fedimint_ln_common::contracts::outgoing::OutgoingContractData;
 use fedimint_ln_common::contracts::{ContractId, FundedContract, IdentifiableContract};
@@ -23,6 +26,7 @@
 
 pub use self::lightningpay::LightningPayStates;
 use crate::api::LnFederationApi;
+use crate::db::{GatewayMisbehavior, GatewayMisbehaviorKey};
 use crate::{set_payment_result, LightningClientContext, PayType};
 
 const RETRY_DELAY: Duration = Duration::from_secs(1);
@@ -259,8 +263,11 @@ fn transitions(
         let timelock = self.timelock;
         let payment_hash = *common.invoice.payment_hash();
         let success_common = common.clone();
+        let success_global_context = global_context.clone();
         let timeout_common = common.clone();
         let timeout_global_context = global_context.clone();
+        let gateway_id = self.gateway.gateway_id;
+        let funding_time = self.funding_time;
         vec![
             StateTransition::new(
                 Self::gateway_pay_invoice(gateway, payload, context, self.funding_time),
@@ -272,6 +279,7 @@ fn transitions(
                         dbtx,
                         payment_hash,
                         success_common.clone(),
+                        success_global_context.clone(),
                     ))
                 },
             ),
@@ -290,13 +298,26 @@ fn transitions(
             StateTransition::new(
                 await_contract_timeout(timeout_global_context.clone(), timelock),
                 move |dbtx, (), old_state| {
-                    Box::pin(try_refund_outgoing_contract(
-                        old_state,
-                        timeout_common.clone(),
-                        dbtx,
-                        timeout_global_context.clone(),
-                        format!("Outgoing contract timed out, BlockHeight: {timelock}"),
-                    ))
+                    let common = timeout_common.clone();
+                    let global_context = timeout_global_context.clone();
+                    Box::pin(async move {
+                        record_gateway_misbehavior(
+                            dbtx,
+                            gateway_id,
+                            &common,
+                            timelock,
+                            funding_time,
+                        )
+                        .await;
+                        try_refund_outgoing_contract(
+                            old_state,
+                            common,
+                            dbtx,
+                            global_context,
+                            format!("Outgoing contract timed out, BlockHeight: {timelock}"),
+                        )
+                        .await
+                    })
                 },
             ),
         ]
@@ -377,6 +398,7 @@ async fn transition_outgoing_contract_execution(
         dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
         payment_hash: sha256::Hash,
         common: LightningPayCommon,
+        global_context: DynGlobalClientContext,
     ) -> LightningPayStateMachine {
         match result {
             Ok(preimage) => {
@@ -388,15 +410,30 @@ async fn transition_outgoing_contract_execution(
                     common.gateway_fee,
                 )
                 .await;
+                global_context.publish_client_event(
+                    dbtx,
+                    ClientEvent::PaymentSucceeded {
+                        operation_id: old_state.common.operation_id,
+                    },
+                );
                 LightningPayStateMachine {
                     common: old_state.common,
                     state: LightningPayStates::Success(preimage),
                 }
             }
-            Err(e) => LightningPayStateMachine {
-                common: old_state.common,
-                state: LightningPayStates::Failure(e.to_string()),
-            },
+            Err(e) => {
+                global_context.publish_client_event(
+                    dbtx,
+                    ClientEvent::PaymentFailed {
+                        operation_id: old_state.common.operation_id,
+                        error: e.to_string(),
+                    },
+                );
+                LightningPayStateMachine {
+                    common: old_state.common,
+                    state: LightningPayStates::Failure(e.to_string()),
+                }
+            }
         }
     }
 }
@@ -451,22 +488,21 @@ fn transitions(
 
 /// Waits for a contract with `contract_id` to be cancelled by the gateway.
 async fn await_contract_cancelled(contract_id: ContractId, global_context: DynGlobalClientContext) {
-    loop {
-        // If we fail to get the contract from the federation, we need to keep retrying
-        // until we successfully do.
-        match global_context
-            .module_api()
-            .wait_outgoing_contract_cancelled(contract_id)
-            .await
-        {
-            Ok(_) => return,
-            Err(error) => {
-                error!("Error waiting for outgoing contract to be cancelled: {error:?}");
-            }
-        }
-
-        sleep(RETRY_DELAY).await;
-    }
+    // If we fail to get the contract from the federation, we need to keep retrying
+    // until we successfully do.
+    global_context
+        .retry(
+            "await-outgoing-contract-cancelled",
+            background_backoff(),
+            || async {
+                global_context
+                    .module_api()
+                    .wait_outgoing_contract_cancelled(contract_id)
+                    .await?;
+                Ok(())
+            },
+        )
+        .await;
 }
 
 /// Waits until a specific block height at which the contract will be able to be
@@ -478,6 +514,40 @@ async fn await_contract_timeout(global_context: DynGlobalClientContext, timelock
         .await;
 }
 
+/// Records evidence against a gateway that kept the funds of an outgoing
+/// contract locked until it timed out, without paying the invoice or cancelling
+/// the contract. Such gateways are avoided when selecting a gateway, see
+/// [`crate::LightningClientModule::get_gateway`].
+async fn record_gateway_misbehavior(
+    dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+    gateway_id: secp256k1::PublicKey,
+    common: &LightningPayCommon,
+    timelock: u32,
+    funded_at: SystemTime,
+) {
+    let contract_id = common.contract.contract_account.contract.contract_id();
+    warn!(
+        %gateway_id,
+        %contract_id,
+        "Gateway neither paid nor cancelled the outgoing contract before it timed out"
+    );
+    dbtx.module_tx()
+        .insert_entry(
+            &GatewayMisbehaviorKey {
+                gateway_id,
+                contract_id,
+            },
+            &GatewayMisbehavior {
+                operation_id: common.operation_id,
+                amount: common.contract.contract_account.amount,
+                timelock,
+                funded_at,
+                refunded_at: fedimint_core::time::now(),
+            },
+        )
+        .await;
+}
+
 /// Claims a refund for an expired or cancelled outgoing contract
 ///
 /// This can be necessary when the Lightning gateway cannot route the
@@ -512,6 +582,14 @@ async fn try_refund_outgoing_contract(
         .await
         .expect("Cannot claim input, additional funding needed");
 
+    global_context.publish_client_event(
+        dbtx,
+        ClientEvent::PaymentFailed {
+            operation_id: old_state.common.operation_id,
+            error: error_reason.clone(),
+        },
+    );
+
     LightningPayStateMachine {
         common: old_state.common,
         state: LightningPayStates::Refund(LightningPayRefund {
@@ -549,13 +627,23 @@ fn new(common: LightningPayCommon) -> Self {
     }
 
     fn new_pruned(common: LightningPayCommon) -> Self {
+        // For zero-amount invoices the amount to pay is whatever was locked in the
+        // contract on top of the gateway fee
+        let amount = common.invoice.amount_milli_satoshis().map_or_else(
+            || {
+                common
+                    .contract
+                    .contract_account
+                    .amount
+                    .saturating_sub(common.gateway_fee)
+            },
+            Amount::from_msats,
+        );
         Self {
             contract_id: common.contract.contract_account.contract.contract_id(),
             federation_id: common.federation_id,
             preimage_auth: common.preimage_auth,
-            payment_data: PaymentData::PrunedInvoice(
-                common.invoice.try_into().expect("Invoice has amount"),
-            ),
+            payment_data: PaymentData::PrunedInvoice(PrunedInvoice::new(&common.invoice, amount)),
         }
     }
 }
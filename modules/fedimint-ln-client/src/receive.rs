@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use fedimint_api_client::api::DynModuleApi;
+use fedimint_client::event_bus::ClientEvent;
 use fedimint_client::module::OutPointRange;
 use fedimint_client::sm::{ClientSMDatabaseTransaction, DynState, State, StateTransition};
 use fedimint_client::transaction::{ClientInput, ClientInputBundle};
@@ -346,13 +347,21 @@ fn transitions(
         global_context: &DynGlobalClientContext,
     ) -> Vec<StateTransition<LightningReceiveStateMachine>> {
         let out_points = self.out_points.clone();
+        let success_global_context = global_context.clone();
         vec![StateTransition::new(
             Self::await_claim_success(global_context.clone(), self.txid),
-            move |_dbtx, result, old_state| {
+            move |dbtx, result, old_state| {
                 let out_points = out_points.clone();
-                Box::pin(
-                    async move { Self::transition_claim_success(&result, &old_state, out_points) },
-                )
+                let global_context = success_global_context.clone();
+                Box::pin(async move {
+                    Self::transition_claim_success(
+                        dbtx,
+                        &global_context,
+                        &result,
+                        &old_state,
+                        out_points,
+                    )
+                })
             },
         )]
     }
@@ -367,6 +376,8 @@ async fn await_claim_success(
     }
 
     fn transition_claim_success(
+        dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+        global_context: &DynGlobalClientContext,
         result: &Result<(), String>,
         old_state: &LightningReceiveStateMachine,
         out_points: Vec<OutPoint>,
@@ -374,6 +385,12 @@ fn transition_claim_success(
         match result {
             Ok(()) => {
                 // Claim successful
+                global_context.publish_client_event(
+                    dbtx,
+                    ClientEvent::PaymentReceived {
+                        operation_id: old_state.operation_id,
+                    },
+                );
                 LightningReceiveStateMachine {
                     operation_id: old_state.operation_id,
                     state: LightningReceiveStates::Success(out_points),
@@ -210,7 +210,7 @@ fn supported_api_versions(&self) -> SupportedModuleApiVersions {
 
     async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<DynServerModule> {
         // Eagerly initialize metrics that trigger infrequently
-        LN_CANCEL_OUTGOING_CONTRACTS.get();
+        LN_CANCEL_OUTGOING_CONTRACTS.init(&[]);
 
         Ok(Lightning::new(args.cfg().to_typed()?, args.our_peer_id())?.into())
     }
@@ -303,6 +303,19 @@ fn get_client_config(
             network: config.network,
         })
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        // Our own decryption shares and the gateways registered with us are local
+        Some(vec![
+            DbKeyPrefix::Contract as u8,
+            DbKeyPrefix::Offer as u8,
+            DbKeyPrefix::AgreedDecryptionShare as u8,
+            DbKeyPrefix::ContractUpdate as u8,
+            DbKeyPrefix::BlockCountVote as u8,
+            DbKeyPrefix::EncryptedPreimageIndex as u8,
+            DbKeyPrefix::LightningAuditItem as u8,
+        ])
+    }
 }
 /// The lightning module implements an account system. It does not have the
 /// privacy guarantees of the e-cash mint module but instead allows for smart
@@ -722,7 +735,7 @@ async fn process_output<'a, 'b>(
                     .await;
 
                 dbtx.on_commit(|| {
-                    LN_INCOMING_OFFER.inc();
+                    LN_INCOMING_OFFER.inc(&[]);
                 });
 
                 Ok(TransactionItemAmount::ZERO)
@@ -779,7 +792,7 @@ async fn process_output<'a, 'b>(
                 .await;
 
                 dbtx.on_commit(|| {
-                    LN_CANCEL_OUTGOING_CONTRACTS.inc();
+                    LN_CANCEL_OUTGOING_CONTRACTS.inc(&[]);
                 });
 
                 Ok(TransactionItemAmount::ZERO)
@@ -1218,12 +1231,13 @@ async fn remove_gateway(
 }
 
 fn record_funded_contract_metric(updated_contract_account: &ContractAccount) {
-    LN_FUNDED_CONTRACT_SATS
-        .with_label_values(&[match updated_contract_account.contract {
+    LN_FUNDED_CONTRACT_SATS.observe(
+        &[match updated_contract_account.contract {
             FundedContract::Incoming(_) => "incoming",
             FundedContract::Outgoing(_) => "outgoing",
-        }])
-        .observe(updated_contract_account.amount.sats_f64());
+        }],
+        updated_contract_account.amount.sats_f64(),
+    );
 }
 
 #[cfg(test)]
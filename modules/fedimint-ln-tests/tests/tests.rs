@@ -2,6 +2,7 @@
 use std::sync::Arc;
 
 use assert_matches::assert_matches;
+use fedimint_client::event_bus::ClientEvent;
 use fedimint_client::Client;
 use fedimint_core::util::NextOrPending;
 use fedimint_core::{sats, secp256k1, Amount};
@@ -598,6 +599,56 @@ async fn rejects_wrong_network_invoice() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn uses_active_gateway_until_it_is_unregistered() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_default_fed().await;
+    let client = fed.new_client().await;
+    let _gw1 = gateway(&fixtures, &fed).await;
+    let gw2 = gateway(&fixtures, &fed).await;
+    let ln_module = client.get_first_module::<LightningClientModule>()?;
+    ln_module.update_gateway_cache().await?;
+
+    let unknown_gateway_id = Keypair::new(secp256k1::SECP256K1, &mut OsRng).public_key();
+    assert!(ln_module
+        .set_active_gateway(&unknown_gateway_id)
+        .await
+        .is_err());
+
+    ln_module.set_active_gateway(&gw2.gateway_id()).await?;
+    for _ in 0..10 {
+        let gateway = ln_module
+            .get_gateway(None, false)
+            .await?
+            .expect("Gateways are registered");
+        assert_eq!(gateway.gateway_id, gw2.gateway_id());
+    }
+
+    let mut events = client.subscribe_client_events();
+    gw2.unannounce_from_all_federations().await;
+    ln_module.update_gateway_cache().await?;
+    loop {
+        if events.recv().await?.event
+            == (ClientEvent::ActiveGatewayUnavailable {
+                gateway_id: gw2.gateway_id(),
+            })
+        {
+            break;
+        }
+    }
+    assert!(ln_module.get_active_gateway().await.is_none());
+    assert_ne!(
+        ln_module
+            .get_gateway(None, false)
+            .await?
+            .expect("A gateway is still registered")
+            .gateway_id,
+        gw2.gateway_id()
+    );
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod fedimint_migration_tests {
     use std::str::FromStr;
@@ -1280,6 +1331,9 @@ async fn test_client_db_migrations() -> anyhow::Result<()> {
                             );
                             info!("Validated LightningGateways");
                         }
+                        // Added after the snapshot was taken
+                        fedimint_ln_client::db::DbKeyPrefix::GatewayMisbehavior
+                        | fedimint_ln_client::db::DbKeyPrefix::ActiveGatewayId => {}
                         fedimint_ln_client::db::DbKeyPrefix::CoreInternalReservedStart
                         | fedimint_ln_client::db::DbKeyPrefix::ExternalReservedStart
                         | fedimint_ln_client::db::DbKeyPrefix::CoreInternalReservedEnd => {}
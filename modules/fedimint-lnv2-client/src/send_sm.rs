@@ -8,7 +8,7 @@
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::util::backoff_util::api_networking_backoff;
 use fedimint_core::util::SafeUrl;
-use fedimint_core::{secp256k1, util, OutPoint, TransactionId};
+use fedimint_core::{secp256k1, OutPoint, TransactionId};
 use fedimint_lnv2_common::contracts::OutgoingContract;
 use fedimint_lnv2_common::{LightningInput, LightningInputV0, OutgoingWitness};
 use futures::future::pending;
@@ -92,6 +92,7 @@ fn transitions(
                 vec![
                     StateTransition::new(
                         Self::gateway_send_payment(
+                            global_context.clone(),
                             self.common.gateway_api.clone(),
                             context.federation_id,
                             self.common.contract.clone(),
@@ -150,8 +151,9 @@ fn transition_funding(
         })
     }
 
-    #[instrument(skip(refund_keypair, context))]
+    #[instrument(skip(global_context, refund_keypair, context))]
     async fn gateway_send_payment(
+        global_context: DynGlobalClientContext,
         gateway_api: SafeUrl,
         federation_id: FederationId,
         contract: OutgoingContract,
@@ -159,29 +161,29 @@ async fn gateway_send_payment(
         refund_keypair: Keypair,
         context: LightningClientContext,
     ) -> Result<[u8; 32], Signature> {
-        util::retry("gateway-send-payment", api_networking_backoff(), || async {
-            let payment_result = context
-                .gateway_conn
-                .send_payment(
-                    gateway_api.clone(),
-                    federation_id,
-                    contract.clone(),
-                    invoice.clone(),
-                    refund_keypair.sign_schnorr(secp256k1::Message::from_digest(
-                        *invoice.consensus_hash::<sha256::Hash>().as_ref(),
-                    )),
-                )
-                .await?;
+        global_context
+            .retry("gateway-send-payment", api_networking_backoff(), || async {
+                let payment_result = context
+                    .gateway_conn
+                    .send_payment(
+                        gateway_api.clone(),
+                        federation_id,
+                        contract.clone(),
+                        invoice.clone(),
+                        refund_keypair.sign_schnorr(secp256k1::Message::from_digest(
+                            *invoice.consensus_hash::<sha256::Hash>().as_ref(),
+                        )),
+                    )
+                    .await?;
 
-            ensure!(
-                contract.verify_gateway_response(&payment_result),
-                "Invalid gateway response: {payment_result:?}"
-            );
+                ensure!(
+                    contract.verify_gateway_response(&payment_result),
+                    "Invalid gateway response: {payment_result:?}"
+                );
 
-            Ok(payment_result)
-        })
-        .await
-        .expect("Number of retries has no limit")
+                Ok(payment_result)
+            })
+            .await
     }
 
     async fn transition_gateway_send_payment(
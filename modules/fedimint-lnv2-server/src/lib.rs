@@ -285,6 +285,19 @@ fn get_client_config(
             network: config.network,
         })
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        // The gateways are added by each guardian's operator and the output outcomes
+        // hold our own decryption key shares, which are recreated by
+        // `rebuild_output_outcome` instead
+        Some(vec![
+            DbKeyPrefix::BlockCountVote as u8,
+            DbKeyPrefix::UnixTimeVote as u8,
+            DbKeyPrefix::IncomingContract as u8,
+            DbKeyPrefix::OutgoingContract as u8,
+            DbKeyPrefix::Preimage as u8,
+        ])
+    }
 }
 
 fn dealer_keygen(
@@ -526,6 +539,28 @@ async fn output_status(
         dbtx.get_value(&OutputOutcomeKey(out_point)).await
     }
 
+    async fn rebuild_output_outcome(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        output: &LightningOutput,
+        out_point: OutPoint,
+    ) -> anyhow::Result<()> {
+        let outcome = match output.ensure_v0_ref()? {
+            LightningOutputV0::Outgoing(..) => LightningOutputOutcomeV0::Outgoing,
+            LightningOutputV0::Incoming(contract) => LightningOutputOutcomeV0::Incoming(
+                contract.create_decryption_key_share(&self.cfg.private.sk),
+            ),
+        };
+
+        dbtx.insert_entry(
+            &OutputOutcomeKey(out_point),
+            &LightningOutputOutcome::V0(outcome),
+        )
+        .await;
+
+        Ok(())
+    }
+
     async fn audit(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
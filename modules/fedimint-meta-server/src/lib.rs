@@ -200,6 +200,14 @@ fn validate_config(
     fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn> {
         BTreeMap::new()
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        // How our operator wants us to vote is local
+        Some(vec![
+            DbKeyPrefix::Consensus as u8,
+            DbKeyPrefix::Submissions as u8,
+        ])
+    }
 }
 
 /// Meta module
@@ -2,7 +2,10 @@
 use fedimint_core::module::ApiRequestErased;
 use fedimint_core::task::{MaybeSend, MaybeSync};
 use fedimint_core::{apply, async_trait_maybe_send};
-use fedimint_mint_common::endpoint_constants::{BLIND_NONCE_USED_ENDPOINT, NOTE_SPENT_ENDPOINT};
+use fedimint_mint_common::config::FeeConsensus;
+use fedimint_mint_common::endpoint_constants::{
+    BLIND_NONCE_USED_ENDPOINT, FEE_CONSENSUS_ENDPOINT, NOTE_SPENT_ENDPOINT,
+};
 use fedimint_mint_common::{BlindNonce, Nonce};
 
 #[apply(async_trait_maybe_send!)]
@@ -12,6 +15,10 @@ pub trait MintFederationApi {
 
     /// Check if an e-cash note was already spent.
     async fn check_note_spent(&self, nonce: Nonce) -> FederationResult<bool>;
+
+    /// Fee schedule the guardians currently charge for e-cash inputs and
+    /// outputs.
+    async fn fee_consensus(&self) -> FederationResult<FeeConsensus>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -34,4 +41,12 @@ async fn check_note_spent(&self, nonce: Nonce) -> FederationResult<bool> {
         )
         .await
     }
+
+    async fn fee_consensus(&self) -> FederationResult<FeeConsensus> {
+        self.request_current_consensus(
+            FEE_CONSENSUS_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
 }
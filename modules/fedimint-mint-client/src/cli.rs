@@ -1,3 +1,4 @@
+use std::time::Duration;
 use std::{ffi, iter};
 
 use anyhow::bail;
@@ -5,12 +6,43 @@
 use futures::StreamExt;
 use serde::Serialize;
 
-use crate::{MintClientModule, OOBNotes, ReissueExternalNotesState};
+use fedimint_core::Amount;
+
+use crate::payment_request::{EcashPaymentRequest, SealedOOBNotes};
+use crate::{MintClientModule, OOBNotes, ReissueExternalNotesState, SelectNotesWithAtleastAmount};
 
 #[derive(Parser, Serialize)]
 enum Opts {
     /// Reissue out of band notes
     Reissue { notes: OOBNotes },
+    /// Create a reusable payment request others can pay while we are offline
+    CreatePaymentRequest {
+        #[clap(long)]
+        amount: Option<Amount>,
+        #[clap(long)]
+        memo: Option<String>,
+        /// Number of seconds after which the request expires
+        #[clap(long)]
+        expiry_secs: Option<u64>,
+    },
+    /// Pay a payment request, returns the sealed notes for the recipient
+    PayPaymentRequest {
+        request: EcashPaymentRequest,
+        #[clap(long)]
+        amount: Option<Amount>,
+        /// Number of seconds after which the spend is canceled if the
+        /// recipient hasn't claimed the notes
+        #[clap(long, default_value_t = 60 * 60 * 24 * 7)]
+        timeout: u64,
+    },
+    /// Claim notes sent to one of our payment requests
+    ClaimSealedNotes { notes: SealedOOBNotes },
+    /// Show the fee schedule quoted by the federation after checking it
+    /// against the client config
+    FeeSchedule,
+    /// Score how identifiable spending an amount with the notes currently
+    /// held would be, without spending anything
+    SpendPrivacy { amount: Amount },
 }
 
 pub(crate) async fn handle_cli_command(
@@ -39,5 +71,67 @@ pub(crate) async fn handle_cli_command(
 
             Ok(serde_json::to_value(amount).expect("JSON serialization failed"))
         }
+        Opts::CreatePaymentRequest {
+            amount,
+            memo,
+            expiry_secs,
+        } => {
+            let expiry =
+                expiry_secs.map(|secs| fedimint_core::time::now() + Duration::from_secs(secs));
+
+            let request = mint.create_payment_request(amount, memo, expiry);
+
+            Ok(serde_json::to_value(request).expect("JSON serialization failed"))
+        }
+        Opts::PayPaymentRequest {
+            request,
+            amount,
+            timeout,
+        } => {
+            let (operation_id, sealed_notes) = mint
+                .pay_payment_request(
+                    &SelectNotesWithAtleastAmount,
+                    &request,
+                    amount,
+                    Duration::from_secs(timeout),
+                    false,
+                    (),
+                )
+                .await?;
+
+            Ok(serde_json::json!({
+                "operation_id": operation_id,
+                "notes": sealed_notes,
+            }))
+        }
+        Opts::ClaimSealedNotes { notes } => {
+            let operation_id = mint.claim_sealed_notes(&notes, ()).await?;
+
+            let mut updates = mint
+                .subscribe_reissue_external_notes(operation_id)
+                .await
+                .unwrap()
+                .into_stream();
+
+            while let Some(update) = updates.next().await {
+                if let ReissueExternalNotesState::Failed(e) = update {
+                    bail!("Reissue failed: {e}");
+                }
+            }
+
+            Ok(serde_json::to_value(operation_id).expect("JSON serialization failed"))
+        }
+        Opts::FeeSchedule => {
+            let fee_consensus = mint.fetch_verified_fee_consensus().await?;
+
+            Ok(serde_json::to_value(fee_consensus).expect("JSON serialization failed"))
+        }
+        Opts::SpendPrivacy { amount } => {
+            let report = mint
+                .spend_privacy_report(&SelectNotesWithAtleastAmount, amount)
+                .await?;
+
+            Ok(serde_json::to_value(report).expect("JSON serialization failed"))
+        }
     }
 }
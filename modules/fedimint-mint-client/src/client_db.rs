@@ -1,5 +1,6 @@
 use std::io::Cursor;
 
+use anyhow::anyhow;
 use fedimint_client::module::init::recovery::RecoveryFromHistoryCommon;
 use fedimint_client::module::{IdxRange, OutPointRange};
 use fedimint_core::core::OperationId;
@@ -26,6 +27,7 @@ pub enum DbKeyPrefix {
     RecoveryState = 0x2c,
     RecoveryFinalized = 0x2d,
     ReusedNoteIndices = 0x2e,
+    PendingOOBNote = 0x2f,
     /// Prefixes between 0xb0..=0xcf shall all be considered allocated for
     /// historical and future external use
     ExternalReservedStart = 0xb0,
@@ -55,7 +57,87 @@ pub struct NoteKey {
     value = SpendableNoteUndecoded,
     db_prefix = DbKeyPrefix::Note,
 );
-impl_db_lookup!(key = NoteKey, query_prefix = NoteKeyPrefix);
+impl_db_lookup!(
+    key = NoteKey,
+    query_prefix = NoteKeyPrefix,
+    query_prefix = NoteKeyAmountPrefix
+);
+
+/// Notes of a single denomination, ordered by their nonce
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct NoteKeyAmountPrefix(pub Amount);
+
+/// Note handed out by the out-of-band spend `operation_id` that is refunded
+/// unless the recipient reissues it in time.
+///
+/// Notes are moved between the [`NoteKey`] table and this one, so every note
+/// is stored exactly once and can never be counted as spendable and pending at
+/// the same time, while [`NoteKey`] only ever holds spendable notes.
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct PendingOOBNoteKey {
+    pub operation_id: OperationId,
+    pub amount: Amount,
+    pub nonce: Nonce,
+}
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct PendingOOBNoteKeyPrefix;
+
+impl_db_record!(
+    key = PendingOOBNoteKey,
+    value = SpendableNoteUndecoded,
+    db_prefix = DbKeyPrefix::PendingOOBNote,
+);
+impl_db_lookup!(
+    key = PendingOOBNoteKey,
+    query_prefix = PendingOOBNoteKeyPrefix
+);
+
+/// Moves a spendable note to the pending notes of the out-of-band spend
+/// `operation_id`, so it is no longer spendable.
+pub(crate) async fn mark_note_pending_oob(
+    dbtx: &mut DatabaseTransaction<'_>,
+    amount: Amount,
+    nonce: Nonce,
+    operation_id: OperationId,
+) -> anyhow::Result<()> {
+    let note = dbtx
+        .remove_entry(&NoteKey { amount, nonce })
+        .await
+        .ok_or_else(|| anyhow!("Note {nonce:?} is not spendable"))?;
+
+    dbtx.insert_new_entry(
+        &PendingOOBNoteKey {
+            operation_id,
+            amount,
+            nonce,
+        },
+        &note,
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Removes a note handed out by the out-of-band spend `operation_id` from the
+/// wallet, returning it so it can be refunded.
+///
+/// Fails without modifying the database if the note is not pending in this
+/// spend, e.g. because it was already taken.
+pub(crate) async fn take_pending_oob_note(
+    dbtx: &mut DatabaseTransaction<'_>,
+    amount: Amount,
+    nonce: Nonce,
+    operation_id: OperationId,
+) -> anyhow::Result<SpendableNoteUndecoded> {
+    dbtx.remove_entry(&PendingOOBNoteKey {
+        operation_id,
+        amount,
+        nonce,
+    })
+    .await
+    .ok_or_else(|| anyhow!("Note {nonce:?} is not pending in {operation_id:?}"))
+}
 
 #[derive(Debug, Clone, Encodable, Decodable, Serialize)]
 pub struct NextECashNoteIndexKey(pub Amount);
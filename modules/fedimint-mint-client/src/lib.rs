@@ -19,6 +19,10 @@
 mod oob;
 /// State machines for mint outputs
 pub mod output;
+/// Reusable e-cash payment requests and notes sealed to their recipient
+pub mod payment_request;
+/// Scoring how identifiable the denominations of a spend are
+pub mod privacy;
 
 pub mod event;
 
@@ -26,13 +30,13 @@
 pub mod api;
 
 use std::cmp::{min, Ordering};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::io::Read;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, bail, ensure, Context as _};
 use async_stream::{stream, try_stream};
@@ -40,11 +44,13 @@
 use base64::Engine as _;
 use bitcoin_hashes::{sha256, sha256t, Hash, HashEngine as BitcoinHashEngine};
 use client_db::{
-    migrate_state_to_v2, migrate_to_v1, DbKeyPrefix, NoteKeyPrefix, RecoveryFinalizedKey,
-    ReusedNoteIndices,
+    mark_note_pending_oob, migrate_state_to_v2, migrate_to_v1, DbKeyPrefix, NoteKeyPrefix,
+    PendingOOBNoteKey, PendingOOBNoteKeyPrefix, RecoveryFinalizedKey, ReusedNoteIndices,
 };
 use event::{NoteSpent, OOBNotesReissued, OOBNotesSpent};
+use fedimint_api_client::api::DynModuleApi;
 use fedimint_client::db::{migrate_state, ClientMigrationFn};
+use fedimint_client::event_bus::ClientEvent;
 use fedimint_client::module::init::{
     ClientModuleInit, ClientModuleInitArgs, ClientModuleRecoverArgs,
 };
@@ -65,11 +71,12 @@
 };
 use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
 use fedimint_core::invite_code::{InviteCode, InviteCodeV2};
+use fedimint_core::metrics::Counter;
 use fedimint_core::module::registry::{ModuleDecoderRegistry, ModuleRegistry};
 use fedimint_core::module::{
     ApiVersion, CommonModuleInit, ModuleCommon, ModuleInit, MultiApiVersion,
 };
-use fedimint_core::secp256k1::{All, Keypair, Secp256k1};
+use fedimint_core::secp256k1::{rand, All, Keypair, Secp256k1, SECP256K1};
 use fedimint_core::util::{BoxFuture, BoxStream, NextOrPending, SafeUrl};
 use fedimint_core::{
     apply, async_trait_maybe_send, push_db_pair_items, Amount, OutPoint, PeerId, Tiered,
@@ -84,18 +91,21 @@
 use hex::ToHex;
 use input::MintInputStateCreatedBundle;
 use itertools::Itertools as _;
-use oob::MintOOBStatesCreatedMulti;
+use oob::MintOOBStatesCreatedPending;
 use output::MintOutputStatesCreatedMulti;
+use payment_request::{EcashPaymentRequest, SealedOOBNotes};
+use privacy::SpendPrivacyReport;
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use tbs::{AggregatePublicKey, Signature};
 use thiserror::Error;
 use tracing::{debug, warn};
 
+use crate::api::MintFederationApi;
 use crate::backup::EcashBackup;
 use crate::client_db::{
     CancelledOOBSpendKey, CancelledOOBSpendKeyPrefix, NextECashNoteIndexKey,
-    NextECashNoteIndexKeyPrefix, NoteKey,
+    NextECashNoteIndexKeyPrefix, NoteKey, NoteKeyAmountPrefix,
 };
 use crate::input::{MintInputCommon, MintInputStateMachine, MintInputStates};
 use crate::oob::{MintOOBStateMachine, MintOOBStates};
@@ -104,6 +114,17 @@
 };
 
 const MINT_E_CASH_TYPE_CHILD_ID: ChildId = ChildId(0);
+const MINT_PAYMENT_REQUEST_CHILD_ID: ChildId = ChildId(1);
+
+/// Number of notes per denomination whose signature is verified by
+/// [`MintClientModule::check_integrity`]
+const NOTE_INTEGRITY_SAMPLE_SIZE: usize = 8;
+
+static REISSUED_NOTES: Counter = Counter::new(
+    "client_mint_reissued_notes_total",
+    "Notes submitted for reissuance by the mint client",
+    &[],
+);
 
 /// An encapsulation of [`FederationId`] and e-cash notes in the form of
 /// [`TieredMulti<SpendableNote>`] for the purpose of spending e-cash
@@ -454,13 +475,27 @@ pub enum ReissueExternalNotesState {
     /// The operation has been created and is waiting to be accepted by the
     /// federation.
     Created,
-    /// We are waiting for blind signatures to arrive but can already assume the
-    /// transaction to be successful.
+    /// The transaction spending the `notes` being reissued has been submitted
+    /// to the federation.
+    InputsSubmitted { notes: usize },
+    /// The transaction has been accepted, we are waiting for blind signatures
+    /// to arrive but can already assume the reissue to be successful.
     Issuing,
+    /// We are collecting a threshold of blind signature shares from the
+    /// guardians for each of the `notes` new notes.
+    CollectingSignatures { notes: usize },
+    /// The signatures of `stored` out of `total` new notes have been combined
+    /// and the unblinded notes stored in our wallet.
+    NotesStored { stored: usize, total: usize },
     /// The operation has been completed successfully.
     Done,
     /// Some error happened and the operation failed.
     Failed(String),
+    /// The federation changed the mint's config while we were waiting for
+    /// blind signatures. They may not arrive until the change is applied with
+    /// [`fedimint_client::Client::apply_config_change`] and the client is
+    /// restarted.
+    ConfigChanged,
 }
 
 /// The high-level state of a raw e-cash spend operation started with
@@ -488,6 +523,22 @@ pub enum SpendOOBState {
     Refunded,
 }
 
+/// Outcome of [`MintClientModule::import_notes`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportNotesSummary {
+    /// Reissue operation claiming the imported notes, `None` if no note was
+    /// left to import
+    pub operation_id: Option<OperationId>,
+    /// Total amount of the notes being reissued
+    pub imported: Amount,
+    /// Notes the wallet already held or that were passed multiple times
+    pub skipped_duplicates: usize,
+    /// Notes with an unknown amount tier or an invalid signature
+    pub skipped_invalid: usize,
+    /// Notes the federation reported as already spent
+    pub skipped_spent: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintOperationMeta {
     pub variant: MintOperationMetaVariant,
@@ -511,6 +562,9 @@ pub enum MintOperationMetaVariant {
         // Introduced in 0.3.0:
         #[serde(default)]
         out_point_indices: Vec<u64>,
+        // Introduced in 0.6.0:
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        input_notes: Option<usize>,
     },
     SpendOOB {
         requested_amount: Amount,
@@ -567,6 +621,16 @@ async fn dump_database(
                         "CancelledOOBSpendKey"
                     );
                 }
+                DbKeyPrefix::PendingOOBNote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PendingOOBNoteKeyPrefix,
+                        PendingOOBNoteKey,
+                        SpendableNoteUndecoded,
+                        mint_client_items,
+                        "PendingOOBNote"
+                    );
+                }
                 DbKeyPrefix::RecoveryFinalized => {
                     if let Some(val) = dbtx.get_value(&RecoveryFinalizedKey).await {
                         mint_client_items.insert("RecoveryFinalized".to_string(), Box::new(val));
@@ -601,6 +665,7 @@ async fn init(&self, args: &ClientModuleInitArgs<Self>) -> anyhow::Result<Self::
             secp: Secp256k1::new(),
             notifier: args.notifier().clone(),
             client_ctx: args.context(),
+            module_api: args.module_api().clone(),
         })
     }
 
@@ -659,6 +724,7 @@ pub struct MintClientModule {
     secp: Secp256k1<All>,
     notifier: ModuleNotifier<MintClientStateMachines>,
     pub client_ctx: ClientContext<Self>,
+    module_api: DynModuleApi,
 }
 
 // TODO: wrap in Arc
@@ -838,7 +904,10 @@ async fn subscribe_balance_changes(&self) -> BoxStream<'static, ()> {
                             ..
                         })
                         | MintClientStateMachines::OOB(MintOOBStateMachine {
-                            state: MintOOBStates::Created(_),
+                            state:
+                                MintOOBStates::Created(_)
+                                | MintOOBStates::CreatedMulti(_)
+                                | MintOOBStates::CreatedPending(_),
                             ..
                         }) => Some(()),
                         _ => None,
@@ -858,6 +927,55 @@ async fn leave(&self, dbtx: &mut DatabaseTransaction<'_>) -> anyhow::Result<()>
         }
         Ok(())
     }
+
+    async fn check_integrity(&self, dbtx: &mut DatabaseTransaction<'_>) -> Vec<String> {
+        let mut issues = vec![];
+
+        for (amount, pk) in self.cfg.tbs_pks.iter() {
+            // Notes are ordered by their nonce within a denomination, so starting at a
+            // random nonce and wrapping around to the lowest one samples random notes
+            let start = Nonce(Keypair::new(SECP256K1, &mut rand::thread_rng()).public_key());
+            let mut sample = dbtx
+                .find_by_range(
+                    NoteKey {
+                        amount,
+                        nonce: start,
+                    }..NoteKey {
+                        amount: amount + Amount::from_msats(1),
+                        nonce: start,
+                    },
+                )
+                .await
+                .take_while(|(key, _)| std::future::ready(key.amount == amount))
+                .take(NOTE_INTEGRITY_SAMPLE_SIZE)
+                .collect::<Vec<_>>()
+                .await;
+            let remaining = NOTE_INTEGRITY_SAMPLE_SIZE - sample.len();
+            sample.extend(
+                dbtx.find_by_prefix(&NoteKeyAmountPrefix(amount))
+                    .await
+                    .take_while(|(key, _)| std::future::ready(key.nonce < start))
+                    .take(remaining)
+                    .collect::<Vec<_>>()
+                    .await,
+            );
+
+            for (key, note) in sample {
+                let valid = note
+                    .decode()
+                    .is_ok_and(|note| note.nonce() == key.nonce && note.note().verify(*pk));
+                if !valid {
+                    issues.push(format!(
+                        "Note {:?} of denomination {} has an invalid signature",
+                        key.nonce, key.amount
+                    ));
+                }
+            }
+        }
+
+        issues
+    }
+
     async fn handle_rpc(
         &self,
         method: String,
@@ -987,6 +1105,18 @@ pub enum ReissueExternalNotesError {
     AlreadyReissued,
 }
 
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum PayPaymentRequestError {
+    #[error("Federation ID does not match")]
+    WrongFederationId,
+    #[error("Payment request expired")]
+    Expired,
+    #[error("Payment request doesn't specify an amount and none was given")]
+    AmountMissing,
+    #[error("Payment request is for {requested}, but {amount} was given")]
+    AmountMismatch { requested: Amount, amount: Amount },
+}
+
 impl MintClientModule {
     async fn create_sufficient_input(
         &self,
@@ -1074,6 +1204,18 @@ pub async fn create_output(
             &self.cfg.fee_consensus,
         );
 
+        self.create_output_with_denominations(dbtx, operation_id, &denominations)
+            .await
+    }
+
+    /// Creates a mint output issuing exactly the e-cash notes in
+    /// `denominations`
+    async fn create_output_with_denominations(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        operation_id: OperationId,
+        denominations: &TieredCounts,
+    ) -> ClientOutputBundle<MintOutput, MintClientStateMachines> {
         let mut outputs = Vec::new();
         let mut issuance_requests = Vec::new();
 
@@ -1148,7 +1290,8 @@ pub async fn get_wallet_summary(&self, dbtx: &mut DatabaseTransaction<'_>) -> Ti
 
     /// Wait for the e-cash notes to be retrieved. If this is not possible
     /// because another terminal state was reached an error describing the
-    /// failure is returned.
+    /// failure is returned. An error is also returned if awaiting got aborted
+    /// using [`fedimint_client::Client::cancel_operation_awaits`].
     pub async fn await_output_finalized(
         &self,
         operation_id: OperationId,
@@ -1185,7 +1328,9 @@ pub async fn await_output_finalized(
             });
         pin_mut!(stream);
 
-        stream.next_or_pending().await
+        self.client_ctx
+            .await_cancellable(operation_id, stream.next_or_pending())
+            .await?
     }
 
     /// Provisional implementation of note consolidation
@@ -1314,15 +1459,29 @@ async fn spend_notes_oob(
 
         let operation_id = spendable_notes_to_operation_id(&selected_notes);
 
+        // The notes are moved to the pending notes of this spend until they
+        // are refunded, so they are neither spent again nor counted in the
+        // balance
         for (amount, note) in selected_notes.iter_items() {
             debug!(target: LOG_CLIENT_MODULE_MINT, %amount, %note, "Spending note as oob");
-            MintClientModule::delete_spendable_note(&self.client_ctx, dbtx, amount, note).await;
+            mark_note_pending_oob(dbtx, amount, note.nonce(), operation_id).await?;
+            self.client_ctx
+                .log_event(
+                    dbtx,
+                    NoteSpent {
+                        nonce: note.nonce(),
+                    },
+                )
+                .await;
         }
 
         let state_machines = vec![MintClientStateMachines::OOB(MintOOBStateMachine {
             operation_id,
-            state: MintOOBStates::CreatedMulti(MintOOBStatesCreatedMulti {
-                spendable_notes: selected_notes.clone().into_iter_items().collect(),
+            state: MintOOBStates::CreatedPending(MintOOBStatesCreatedPending {
+                notes: selected_notes
+                    .iter_items()
+                    .map(|(amount, note)| (amount, note.nonce()))
+                    .collect(),
                 timeout: fedimint_core::time::now() + try_cancel_after,
             }),
         })];
@@ -1353,7 +1512,9 @@ pub async fn await_spend_oob_refund(&self, operation_id: OperationId) -> SpendOO
                             user_triggered: true,
                             transaction_ids: vec![refund.refund_txid],
                         }),
-                        MintOOBStates::Created(_) | MintOOBStates::CreatedMulti(_) => None,
+                        MintOOBStates::Created(_)
+                        | MintOOBStates::CreatedMulti(_)
+                        | MintOOBStates::CreatedPending(_) => None,
                     }
                 }),
         )
@@ -1457,27 +1618,14 @@ pub async fn new_ecash_note(
         NoteIssuanceRequest::new(&self.secp, &secret)
     }
 
-    /// Try to reissue e-cash notes received from a third party to receive them
-    /// in our wallet. The progress and outcome can be observed using
-    /// [`MintClientModule::subscribe_reissue_external_notes`].
-    /// Can return error of type [`ReissueExternalNotesError`]
-    pub async fn reissue_external_notes<M: Serialize + Send>(
+    /// Submits a transaction spending `notes` into fresh notes of our wallet.
+    /// The operation id is derived from the notes, so reissuing the same
+    /// notes twice fails with [`ReissueExternalNotesError::AlreadyReissued`].
+    async fn submit_reissue<M: Serialize + Send>(
         &self,
-        oob_notes: OOBNotes,
+        notes: TieredMulti<SpendableNote>,
         extra_meta: M,
     ) -> anyhow::Result<OperationId> {
-        let notes = oob_notes.notes().clone();
-        let federation_id_prefix = oob_notes.federation_id_prefix();
-
-        ensure!(
-            notes.total_amount() > Amount::ZERO,
-            "Reissuing zero-amount e-cash isn't supported"
-        );
-
-        if federation_id_prefix != self.federation_id.to_prefix() {
-            bail!(ReissueExternalNotesError::WrongFederationId);
-        }
-
         let operation_id = OperationId(
             notes
                 .consensus_hash::<sha256t::Hash<OOBReissueTag>>()
@@ -1485,6 +1633,7 @@ pub async fn reissue_external_notes<M: Serialize + Send>(
         );
 
         let amount = notes.total_amount();
+        let input_notes = notes.count_items();
         let mint_inputs = self.create_input_from_notes(notes)?;
 
         let tx = TransactionBuilder::new().with_inputs(
@@ -1502,6 +1651,7 @@ pub async fn reissue_external_notes<M: Serialize + Send>(
                     .into_iter()
                     .map(|out_point| out_point.out_idx)
                     .collect(),
+                input_notes: Some(input_notes),
             },
             amount,
             extra_meta: extra_meta.clone(),
@@ -1516,10 +1666,129 @@ pub async fn reissue_external_notes<M: Serialize + Send>(
             )
             .await
             .context(ReissueExternalNotesError::AlreadyReissued)?;
+        REISSUED_NOTES.inc_by(&[], input_notes as u64);
+
+        Ok(operation_id)
+    }
+
+    /// Imports e-cash notes obtained outside of this client, e.g. from a
+    /// backup or another wallet, into the wallet.
+    ///
+    /// Notes the wallet already holds or that appear multiple times are
+    /// skipped, as are notes with an unknown amount tier or an invalid
+    /// signature. Notes the federation reports as already spent are skipped
+    /// too, so they don't make the reissue of the other notes fail. The
+    /// remaining notes are reissued, so they only count towards the balance
+    /// once the federation accepted them. A note spent elsewhere after the
+    /// check still fails the whole reissue. The reissue can be observed using
+    /// [`MintClientModule::subscribe_reissue_external_notes`].
+    pub async fn import_notes<M: Serialize + Send>(
+        &self,
+        notes: TieredMulti<SpendableNote>,
+        extra_meta: M,
+    ) -> anyhow::Result<ImportNotesSummary> {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction_nc().await;
+        let mut seen = BTreeSet::new();
+        let mut candidates = vec![];
+        let mut skipped_duplicates = 0;
+        let mut skipped_invalid = 0;
+
+        for (amount, note) in notes.into_iter_items() {
+            if !seen.insert(note.nonce())
+                || dbtx
+                    .get_value(&NoteKey {
+                        amount,
+                        nonce: note.nonce(),
+                    })
+                    .await
+                    .is_some()
+            {
+                skipped_duplicates += 1;
+                continue;
+            }
+
+            if !self
+                .cfg
+                .tbs_pks
+                .get(amount)
+                .is_some_and(|pk| note.note().verify(*pk))
+            {
+                warn!(target: LOG_CLIENT_MODULE_MINT, %amount, nonce = ?note.nonce(), "Skipping invalid imported note");
+                skipped_invalid += 1;
+                continue;
+            }
+
+            candidates.push((amount, note));
+        }
+
+        let spent = futures::future::try_join_all(
+            candidates
+                .iter()
+                .map(|(_, note)| self.module_api.check_note_spent(note.nonce())),
+        )
+        .await
+        .context("Failed to check whether the imported notes are spent")?;
+
+        let mut new_notes = TieredMulti::default();
+        let mut skipped_spent = 0;
+        for ((amount, note), spent) in candidates.into_iter().zip(spent) {
+            if spent {
+                skipped_spent += 1;
+            } else {
+                new_notes.push(amount, note);
+            }
+        }
+
+        let imported = new_notes.total_amount();
+        let operation_id = if imported == Amount::ZERO {
+            None
+        } else {
+            Some(self.submit_reissue(new_notes, extra_meta).await?)
+        };
+
+        Ok(ImportNotesSummary {
+            operation_id,
+            imported,
+            skipped_duplicates,
+            skipped_invalid,
+            skipped_spent,
+        })
+    }
+
+    /// Try to reissue e-cash notes received from a third party to receive them
+    /// in our wallet. The progress and outcome can be observed using
+    /// [`MintClientModule::subscribe_reissue_external_notes`].
+    /// Can return error of type [`ReissueExternalNotesError`]
+    pub async fn reissue_external_notes<M: Serialize + Send>(
+        &self,
+        oob_notes: OOBNotes,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        let notes = oob_notes.notes().clone();
+        let federation_id_prefix = oob_notes.federation_id_prefix();
+
+        ensure!(
+            notes.total_amount() > Amount::ZERO,
+            "Reissuing zero-amount e-cash isn't supported"
+        );
+
+        if federation_id_prefix != self.federation_id.to_prefix() {
+            bail!(ReissueExternalNotesError::WrongFederationId);
+        }
+
+        let amount = notes.total_amount();
+        let operation_id = self.submit_reissue(notes, extra_meta).await?;
         let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
         self.client_ctx
             .log_event(&mut dbtx, OOBNotesReissued { amount })
             .await;
+        self.client_ctx.publish_client_event(
+            &mut dbtx,
+            ClientEvent::NoteReceived {
+                operation_id,
+                amount,
+            },
+        );
         dbtx.commit_tx().await;
 
         Ok(operation_id)
@@ -1532,11 +1801,12 @@ pub async fn subscribe_reissue_external_notes(
         operation_id: OperationId,
     ) -> anyhow::Result<UpdateStreamOrOutcome<ReissueExternalNotesState>> {
         let operation = self.mint_operation(operation_id).await?;
-        let (txid, out_points) = match operation.meta::<MintOperationMeta>().variant {
+        let (txid, out_points, input_notes) = match operation.meta::<MintOperationMeta>().variant {
             MintOperationMetaVariant::Reissuance {
                 legacy_out_point,
                 txid,
                 out_point_indices,
+                input_notes,
             } => {
                 // Either txid or legacy_out_point will be present, so we should always
                 // have a source for the txid
@@ -1550,7 +1820,7 @@ pub async fn subscribe_reissue_external_notes(
                     .chain(legacy_out_point)
                     .collect::<Vec<_>>();
 
-                (txid, out_points)
+                (txid, out_points, input_notes)
             }
             MintOperationMetaVariant::SpendOOB { .. } => bail!("Operation is not a reissuance"),
         };
@@ -1561,6 +1831,11 @@ pub async fn subscribe_reissue_external_notes(
             stream! {
                 yield ReissueExternalNotesState::Created;
 
+                // Not recorded by operations created before 0.6.0
+                if let Some(notes) = input_notes {
+                    yield ReissueExternalNotesState::InputsSubmitted { notes };
+                }
+
                 match client_ctx
                     .transaction_updates(operation_id)
                     .await
@@ -1576,11 +1851,35 @@ pub async fn subscribe_reissue_external_notes(
                     }
                 }
 
-                for out_point in out_points {
-                    if let Err(e) = client_ctx.self_ref().await_output_finalized(operation_id, out_point).await {
+                // Every output holds a single note, except for the legacy outputs
+                // of operations created before 0.3.0
+                let total = out_points.len();
+                yield ReissueExternalNotesState::CollectingSignatures { notes: total };
+
+                let mut config_change_reported = false;
+                for (stored, out_point) in (1..).zip(out_points) {
+                    let output_finalized = client_ctx.self_ref().await_output_finalized(operation_id, out_point);
+                    pin_mut!(output_finalized);
+
+                    let result = loop {
+                        if config_change_reported {
+                            break output_finalized.as_mut().await;
+                        }
+
+                        tokio::select! {
+                            result = output_finalized.as_mut() => break result,
+                            _ = client_ctx.await_config_change() => {}
+                        }
+
+                        config_change_reported = true;
+                        yield ReissueExternalNotesState::ConfigChanged;
+                    };
+
+                    if let Err(e) = result {
                         yield ReissueExternalNotesState::Failed(e.to_string());
                         return;
                     }
+                    yield ReissueExternalNotesState::NotesStored { stored, total };
                 }
                 yield ReissueExternalNotesState::Done;
             }}
@@ -1717,6 +2016,259 @@ pub async fn spend_notes_with_selector<M: Serialize + Send>(
             })
     }
 
+    /// Scores how identifiable spending `requested_amount` with
+    /// `notes_selector` would be given the notes currently held, without
+    /// spending anything. See [`privacy`] for how spends are scored.
+    pub async fn spend_privacy_report(
+        &self,
+        notes_selector: &impl NotesSelector,
+        requested_amount: Amount,
+    ) -> anyhow::Result<SpendPrivacyReport> {
+        let mut dbtx = self.client_ctx.module_db().begin_transaction_nc().await;
+        let notes = Self::select_notes(
+            &mut dbtx,
+            notes_selector,
+            requested_amount,
+            FeeConsensus::zero(),
+        )
+        .await?;
+
+        Ok(self.score_spend(requested_amount, &notes))
+    }
+
+    fn score_spend(
+        &self,
+        requested_amount: Amount,
+        notes: &TieredMulti<SpendableNote>,
+    ) -> SpendPrivacyReport {
+        let canonical_notes = represent_amount(
+            requested_amount,
+            &TieredCounts::default(),
+            &self.cfg.tbs_pks,
+            0,
+            &FeeConsensus::zero(),
+        );
+
+        SpendPrivacyReport::new(requested_amount, notes.summary(), canonical_notes)
+    }
+
+    /// Like [`MintClientModule::spend_notes_with_selector`], but checks how
+    /// identifiable the spend is first. Identifiable spends are logged and, if
+    /// `reblind` is set, the wallet first reissues `requested_amount` into the
+    /// canonical notes for it, waiting for them to be issued before spending.
+    /// Returns the report of the notes that were actually spent.
+    pub async fn spend_notes_with_privacy_check<M: Serialize + Send>(
+        &self,
+        notes_selector: &impl NotesSelector,
+        requested_amount: Amount,
+        try_cancel_after: Duration,
+        include_invite: bool,
+        reblind: bool,
+        extra_meta: M,
+    ) -> anyhow::Result<(OperationId, OOBNotes, SpendPrivacyReport)> {
+        let report = self
+            .spend_privacy_report(notes_selector, requested_amount)
+            .await?;
+
+        if report.is_identifiable() {
+            warn!(
+                target: LOG_CLIENT_MODULE_MINT,
+                score = report.score,
+                warnings = ?report.warnings,
+                %reblind,
+                "Spend is identifiable by its denominations"
+            );
+
+            if reblind {
+                self.reblind_for_spend(requested_amount).await?;
+            }
+        }
+
+        let (operation_id, oob_notes) = self
+            .spend_notes_with_selector(
+                notes_selector,
+                requested_amount,
+                try_cancel_after,
+                include_invite,
+                extra_meta,
+            )
+            .await?;
+
+        let report = self.score_spend(requested_amount, oob_notes.notes());
+
+        Ok((operation_id, oob_notes, report))
+    }
+
+    /// Reissues e-cash worth `amount` into the canonical notes representing
+    /// it, funded from the wallet, and waits until they were issued
+    pub async fn reblind_for_spend(&self, amount: Amount) -> anyhow::Result<OperationId> {
+        let operation_id = OperationId::new_random();
+        let denominations = represent_amount(
+            amount,
+            &TieredCounts::default(),
+            &self.cfg.tbs_pks,
+            0,
+            &FeeConsensus::zero(),
+        );
+
+        let mut dbtx = self.client_ctx.module_db().begin_transaction().await;
+        let output = self
+            .create_output_with_denominations(&mut dbtx.to_ref_nc(), operation_id, &denominations)
+            .await;
+        dbtx.commit_tx_result().await?;
+
+        let tx =
+            TransactionBuilder::new().with_outputs(self.client_ctx.make_client_outputs(output));
+
+        let operation_meta_gen = |change_range: OutPointRange| MintOperationMeta {
+            variant: MintOperationMetaVariant::Reissuance {
+                legacy_out_point: None,
+                txid: Some(change_range.txid()),
+                out_point_indices: (0..change_range.start_idx() + change_range.count() as u64)
+                    .collect(),
+                input_notes: None,
+            },
+            amount,
+            extra_meta: serde_json::Value::Null,
+        };
+
+        let change_range = self
+            .client_ctx
+            .finalize_and_submit_transaction(
+                operation_id,
+                MintCommonInit::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        for out_idx in 0..change_range.start_idx() + change_range.count() as u64 {
+            self.await_output_finalized(
+                operation_id,
+                OutPoint {
+                    txid: change_range.txid(),
+                    out_idx,
+                },
+            )
+            .await?;
+        }
+
+        Ok(operation_id)
+    }
+
+    /// Key pair used to receive e-cash sent in response to the
+    /// [`EcashPaymentRequest`] with the given `key_index`. It is derived from
+    /// the module secret, so it survives recovery, but differs between
+    /// requests, so they can't be linked.
+    fn payment_request_keypair(&self, key_index: u64) -> Keypair {
+        self.secret
+            .child_key(MINT_PAYMENT_REQUEST_CHILD_ID)
+            .child_key(ChildId(key_index))
+            .to_secp_key(&self.secp)
+    }
+
+    /// Create a reusable [`EcashPaymentRequest`] that senders can pay with
+    /// [`MintClientModule::pay_payment_request`] without us having to be
+    /// online at the same time.
+    ///
+    /// If `amount` is `None` the sender chooses how much to send. After
+    /// `expiry` senders will refuse to pay the request.
+    pub fn create_payment_request(
+        &self,
+        amount: Option<Amount>,
+        memo: Option<String>,
+        expiry: Option<SystemTime>,
+    ) -> EcashPaymentRequest {
+        let key_index = rand::random();
+
+        EcashPaymentRequest {
+            federation_id_prefix: self.federation_id.to_prefix(),
+            key_index,
+            recipient_pk: self.payment_request_keypair(key_index).public_key(),
+            amount,
+            memo,
+            expiry: expiry.map(|expiry| {
+                expiry
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            }),
+        }
+    }
+
+    /// Pay an [`EcashPaymentRequest`] by spending notes out-of-band like
+    /// [`MintClientModule::spend_notes_with_selector`] and sealing them to the
+    /// recipient, so nobody relaying them can claim them.
+    ///
+    /// `amount` has to be supplied if the request doesn't specify one and has
+    /// to match it otherwise. The returned [`SealedOOBNotes`] have to be
+    /// delivered to the recipient, the spend can be observed and canceled
+    /// like any other out-of-band spend using the returned [`OperationId`].
+    /// Until the recipient reissues the notes with
+    /// [`MintClientModule::claim_sealed_notes`] we can still reclaim them, so
+    /// the payment is only final once the recipient has claimed it.
+    pub async fn pay_payment_request<M: Serialize + Send>(
+        &self,
+        notes_selector: &impl NotesSelector,
+        request: &EcashPaymentRequest,
+        amount: Option<Amount>,
+        try_cancel_after: Duration,
+        include_invite: bool,
+        extra_meta: M,
+    ) -> anyhow::Result<(OperationId, SealedOOBNotes)> {
+        if request.federation_id_prefix != self.federation_id.to_prefix() {
+            bail!(PayPaymentRequestError::WrongFederationId);
+        }
+
+        if request.is_expired() {
+            bail!(PayPaymentRequestError::Expired);
+        }
+
+        let amount = match (request.amount, amount) {
+            (Some(requested), Some(amount)) if requested != amount => {
+                bail!(PayPaymentRequestError::AmountMismatch { requested, amount })
+            }
+            (Some(amount), _) | (None, Some(amount)) => amount,
+            (None, None) => bail!(PayPaymentRequestError::AmountMissing),
+        };
+
+        let (operation_id, oob_notes) = self
+            .spend_notes_with_selector(
+                notes_selector,
+                amount,
+                try_cancel_after,
+                include_invite,
+                extra_meta,
+            )
+            .await?;
+
+        let sealed_notes = SealedOOBNotes::seal(&oob_notes, request)?;
+
+        Ok((operation_id, sealed_notes))
+    }
+
+    /// Open [`SealedOOBNotes`] that were sent to one of our payment requests
+    /// and reissue them like [`MintClientModule::reissue_external_notes`].
+    ///
+    /// The sender can still spend the notes until the reissuance is accepted,
+    /// so the payment should only be considered received once the returned
+    /// operation succeeded.
+    pub async fn claim_sealed_notes<M: Serialize + Send>(
+        &self,
+        sealed_notes: &SealedOOBNotes,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        let keypair = self.payment_request_keypair(sealed_notes.key_index);
+
+        if sealed_notes.recipient_pk != keypair.public_key() {
+            bail!("Sealed notes were not sent to one of our payment requests");
+        }
+
+        let oob_notes = sealed_notes.open(&keypair.secret_key())?;
+
+        self.reissue_external_notes(oob_notes, extra_meta).await
+    }
+
     /// Validate the given notes and return the total amount of the notes.
     /// Validation checks that:
     /// - the federation ID is correct
@@ -1888,6 +2440,30 @@ pub async fn advance_note_idx(&self, amount: Amount) -> anyhow::Result<Derivable
             .await?)
     }
 
+    /// Fetches the fee schedule the guardians quote and checks it against the
+    /// one in our client config.
+    ///
+    /// Only the fee schedule in the client config is committed to by its
+    /// consensus hash, which the client verified when joining the federation.
+    /// Comparing the consensus encodings ensures that fees shown to the user
+    /// are never taken from an API response quoting inflated fees, including
+    /// one that only differs in the encoding version.
+    pub async fn fetch_verified_fee_consensus(&self) -> anyhow::Result<FeeConsensus> {
+        let quoted = self
+            .module_api
+            .fee_consensus()
+            .await
+            .context("Failed to fetch fee schedule")?;
+
+        ensure!(
+            quoted.consensus_encode_to_vec() == self.cfg.fee_consensus.consensus_encode_to_vec(),
+            "Fee schedule quoted by the federation ({quoted:?}) does not match the one in the client config ({:?})",
+            self.cfg.fee_consensus
+        );
+
+        Ok(quoted)
+    }
+
     /// Returns secrets for the note indices that were reused by previous
     /// clients with same client secret.
     pub async fn reused_note_secrets(&self) -> Vec<(Amount, NoteIssuanceRequest, BlindNonce)> {
@@ -1964,6 +2540,8 @@ async fn select_notes(
 /// Select notes with total amount of *exactly* `request_amount`. If the amount
 /// cannot be represented with the available denominations an error is returned,
 /// this **does not** mean that the balance is too low.
+///
+/// The notes are chosen by [`TieredMulti::split`].
 pub struct SelectNotesWithExactAmount;
 
 #[apply(async_trait_maybe_send!)]
@@ -1975,17 +2553,31 @@ async fn select_notes(
         requested_amount: Amount,
         fee_consensus: FeeConsensus,
     ) -> anyhow::Result<TieredMulti<Note>> {
-        let notes = select_notes_from_stream(stream, requested_amount, fee_consensus).await?;
+        // Notes that are not worth their fee are skipped, like when selecting at
+        // least an amount
+        let notes = stream
+            .filter(|(amount, _)| std::future::ready(fee_consensus.fee(*amount) < *amount))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<TieredMulti<Note>>();
 
-        if notes.total_amount() != requested_amount {
-            bail!(
-                "Could not select notes with exact amount. Requested amount: {}. Selected amount: {}",
+        let total_amount = notes.total_amount();
+        if total_amount < requested_amount {
+            return Err(InsufficientBalanceError {
                 requested_amount,
-                notes.total_amount()
-            );
+                total_amount,
+            }
+            .into());
         }
 
-        Ok(notes)
+        let Some((selected, _)) = notes.split(requested_amount) else {
+            bail!(
+                "Could not select notes with exact amount. Requested amount: {requested_amount}. Available amount: {total_amount}"
+            );
+        };
+
+        Ok(selected)
     }
 }
 
@@ -2215,6 +2807,12 @@ fn note(&self) -> Note {
         }
     }
 
+    /// Verify the note's signature under the federation's aggregate mint key
+    /// `pk` for the note's denomination
+    pub fn verify(&self, pk: AggregatePublicKey) -> bool {
+        self.note().verify(pk)
+    }
+
     pub fn to_undecoded(&self) -> SpendableNoteUndecoded {
         SpendableNoteUndecoded {
             signature: self
@@ -2436,9 +3034,13 @@ mod tests {
     use std::fmt::Display;
     use std::iter;
     use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
 
     use bitcoin_hashes::Hash;
     use fedimint_core::config::FederationId;
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
     use fedimint_core::encoding::Decodable;
     use fedimint_core::invite_code::{InviteCode, InviteCodeV2};
     use fedimint_core::module::registry::ModuleRegistry;
@@ -2449,13 +3051,15 @@ mod tests {
     use fedimint_mint_common::config::FeeConsensus;
     use itertools::Itertools;
     use secp256k1::rand::rngs::OsRng;
-    use secp256k1::{SecretKey, SECP256K1};
+    use secp256k1::{Keypair, SecretKey, SECP256K1};
     use serde_json::json;
     use tbs::Signature;
 
+    use crate::client_db::{mark_note_pending_oob, take_pending_oob_note, NoteKey};
+    use crate::payment_request::{EcashPaymentRequest, SealedOOBNotes};
     use crate::{
-        represent_amount, select_notes_from_stream, MintOperationMetaVariant, OOBNoteV2, OOBNotes,
-        OOBNotesPart, OOBNotesV2, SpendableNote, SpendableNoteUndecoded,
+        represent_amount, select_notes_from_stream, MintClientModule, MintOperationMetaVariant,
+        OOBNoteV2, OOBNotes, OOBNotesPart, OOBNotesV2, SpendableNote, SpendableNoteUndecoded,
     };
 
     #[test]
@@ -2592,6 +3196,35 @@ async fn select_notes_returns_error_if_amount_is_too_large() {
         assert_eq!(error.total_amount, Amount::from_sats(10));
     }
 
+    #[test_log::test(tokio::test)]
+    async fn select_exact_amount_or_fail() {
+        let f = || {
+            reverse_sorted_note_stream(vec![
+                (Amount::from_sats(1), 2),
+                (Amount::from_sats(5), 2),
+                (Amount::from_sats(20), 1),
+            ])
+        };
+        let select =
+            |amount| SelectNotesWithExactAmount.select_notes(f(), amount, FeeConsensus::zero());
+
+        assert_eq!(
+            select(Amount::from_sats(26)).await.unwrap(),
+            notes(vec![
+                (Amount::from_sats(1), 1),
+                (Amount::from_sats(5), 1),
+                (Amount::from_sats(20), 1)
+            ])
+        );
+        // 3 sats can't be made from the two 1 sat notes
+        assert!(select(Amount::from_sats(23)).await.is_err());
+        assert!(select(Amount::from_sats(33))
+            .await
+            .unwrap_err()
+            .downcast::<InsufficientBalanceError>()
+            .is_ok());
+    }
+
     fn reverse_sorted_note_stream(
         notes: Vec<(Amount, usize)>,
     ) -> impl futures::Stream<Item = (Amount, String)> {
@@ -2731,6 +3364,125 @@ fn oob_notes_v2_encode_base64_roundtrip() {
         assert_eq!(notes, decoded);
     }
 
+    #[test]
+    fn payment_request_seal_open_roundtrip() {
+        let federation_id = FederationId::dummy();
+        let recipient = SecretKey::new(&mut OsRng).keypair(SECP256K1);
+        let other = SecretKey::new(&mut OsRng).keypair(SECP256K1);
+
+        let request = EcashPaymentRequest {
+            federation_id_prefix: federation_id.to_prefix(),
+            key_index: 7,
+            recipient_pk: recipient.public_key(),
+            amount: Some(Amount::from_sats(21)),
+            memo: Some("coffee".to_string()),
+            expiry: Some(1000),
+        };
+        test_roundtrip_serialize_str(request.clone(), |parsed| {
+            assert_eq!(parsed, request);
+        });
+        assert!(request.is_expired_at(UNIX_EPOCH + Duration::from_secs(1000)));
+        assert!(!request.is_expired_at(UNIX_EPOCH + Duration::from_secs(999)));
+
+        let notes = vec![(
+            Amount::from_sats(1),
+            SpendableNote::consensus_decode_hex("a5dd3ebacad1bc48bd8718eed5a8da1d68f91323bef2848ac4fa2e6f8eed710f3178fd4aef047cc234e6b1127086f33cc408b39818781d9521475360de6b205f3328e490a6d99d5e2553a4553207c8bd", &ModuleRegistry::default()).unwrap(),
+        )]
+        .into_iter()
+        .collect::<TieredMulti<_>>();
+        let oob_notes = OOBNotes::new(federation_id.to_prefix(), notes);
+
+        let sealed = SealedOOBNotes::seal(&oob_notes, &request).unwrap();
+        test_roundtrip_serialize_str(sealed.clone(), |parsed| {
+            assert_eq!(parsed, sealed);
+        });
+        assert_eq!(sealed.key_index, request.key_index);
+
+        // Every seal uses a fresh ephemeral key
+        let resealed = SealedOOBNotes::seal(&oob_notes, &request).unwrap();
+        assert_ne!(resealed.ephemeral_pk, sealed.ephemeral_pk);
+
+        assert_eq!(sealed.open(&recipient.secret_key()).unwrap(), oob_notes);
+        assert!(sealed.open(&other.secret_key()).is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn pending_oob_notes_are_not_spendable() {
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction_nc().await;
+        let amount = Amount::from_sats(1);
+        let operation_id = OperationId::new_random();
+        let note = || SpendableNoteUndecoded {
+            signature: [0; 48],
+            spend_key: Keypair::new(SECP256K1, &mut OsRng),
+        };
+
+        let (pending, spendable) = (note(), note());
+        for note in [pending, spendable] {
+            dbtx.insert_new_entry(
+                &NoteKey {
+                    amount,
+                    nonce: note.nonce(),
+                },
+                &note,
+            )
+            .await;
+        }
+
+        mark_note_pending_oob(&mut dbtx, amount, pending.nonce(), operation_id)
+            .await
+            .unwrap();
+        assert!(
+            mark_note_pending_oob(&mut dbtx, amount, pending.nonce(), operation_id)
+                .await
+                .is_err(),
+            "A pending note must not be spent twice"
+        );
+        assert!(
+            mark_note_pending_oob(&mut dbtx, amount, note().nonce(), operation_id)
+                .await
+                .is_err(),
+            "Only notes in the wallet can be pending"
+        );
+
+        let spendable_notes = MintClientModule::get_all_spendable_notes(&mut dbtx).await;
+        assert_eq!(
+            spendable_notes.into_iter_items().collect::<Vec<_>>(),
+            vec![(amount, spendable)]
+        );
+
+        assert!(
+            take_pending_oob_note(
+                &mut dbtx,
+                amount,
+                pending.nonce(),
+                OperationId::new_random()
+            )
+            .await
+            .is_err(),
+            "Only the spend a note is pending in can take it"
+        );
+        assert_eq!(
+            take_pending_oob_note(&mut dbtx, amount, pending.nonce(), operation_id)
+                .await
+                .unwrap(),
+            pending
+        );
+        assert!(
+            take_pending_oob_note(&mut dbtx, amount, pending.nonce(), operation_id)
+                .await
+                .is_err(),
+            "A pending note must not be taken twice"
+        );
+        assert!(dbtx
+            .get_value(&NoteKey {
+                amount,
+                nonce: pending.nonce(),
+            })
+            .await
+            .is_none());
+    }
+
     #[test]
     fn spendable_note_undecoded_sanity() {
         // TODO: add more hex dumps to the loop
@@ -2771,6 +3523,7 @@ fn reissuance_meta_compatibility_02_03() {
                 legacy_out_point: Some(dummy_outpoint),
                 txid: None,
                 out_point_indices: vec![],
+                input_notes: None,
             }
         );
 
@@ -2778,6 +3531,7 @@ fn reissuance_meta_compatibility_02_03() {
             legacy_out_point: None,
             txid: Some(dummy_outpoint.txid),
             out_point_indices: vec![0],
+            input_notes: None,
         })
         .expect("serializing always works");
         assert_eq!(
@@ -8,8 +8,11 @@
 use fedimint_core::core::OperationId;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::{runtime, Amount, TransactionId};
-use fedimint_mint_common::MintInput;
+use fedimint_logging::LOG_CLIENT_MODULE_MINT;
+use fedimint_mint_common::{MintInput, Nonce};
+use tracing::error;
 
+use crate::client_db::take_pending_oob_note;
 use crate::input::{
     MintInputCommon, MintInputStateMachine, MintInputStateRefundedBundle, MintInputStates,
 };
@@ -57,6 +60,11 @@ pub enum MintOOBStates {
     /// Obsoleted, legacy from [`MintOOBStatesV0`], like
     /// [`MintOOBStates::UserRefundMulti`] but for single note only
     UserRefund(MintOOBStatesUserRefund),
+
+    /// Like [`MintOOBStates::CreatedMulti`], but the notes stay in the wallet
+    /// as [`crate::client_db::PendingOOBNoteKey`] instead of being copied
+    /// into the state, so they are stored only once until they are refunded.
+    CreatedPending(MintOOBStatesCreatedPending),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
@@ -84,6 +92,13 @@ pub struct MintOOBStatesCreatedMulti {
     pub(crate) timeout: SystemTime,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
+pub struct MintOOBStatesCreatedPending {
+    /// Notes pending in this spend, they are held in the wallet until refunded
+    pub(crate) notes: Vec<(Amount, Nonce)>,
+    pub(crate) timeout: SystemTime,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
 pub struct MintOOBStatesUserRefundMulti {
     /// The txid we are hoping succeeds refunding all notes in one go
@@ -117,6 +132,9 @@ fn transitions(
             MintOOBStates::CreatedMulti(created) => {
                 created.transitions(self.operation_id, context, global_context)
             }
+            MintOOBStates::CreatedPending(created) => {
+                created.transitions(self.operation_id, context, global_context)
+            }
             MintOOBStates::UserRefund(_)
             | MintOOBStates::TimeoutRefund(_)
             | MintOOBStates::UserRefundMulti(_) => {
@@ -194,6 +212,40 @@ fn transitions(
     }
 }
 
+impl MintOOBStatesCreatedPending {
+    fn transitions(
+        &self,
+        operation_id: OperationId,
+        context: &MintClientContext,
+        global_context: &DynGlobalClientContext,
+    ) -> Vec<StateTransition<MintOOBStateMachine>> {
+        let user_cancel_gc = global_context.clone();
+        let timeout_cancel_gc = global_context.clone();
+        vec![
+            StateTransition::new(
+                context.await_cancel_oob_payment(operation_id),
+                move |dbtx, (), state| {
+                    Box::pin(transition_user_cancel_pending(
+                        state,
+                        dbtx,
+                        user_cancel_gc.clone(),
+                    ))
+                },
+            ),
+            StateTransition::new(
+                await_timeout_cancel(self.timeout),
+                move |dbtx, (), state| {
+                    Box::pin(transition_timeout_cancel_pending(
+                        state,
+                        dbtx,
+                        timeout_cancel_gc.clone(),
+                    ))
+                },
+            ),
+        ]
+    }
+}
+
 async fn transition_user_cancel(
     prev_state: MintOOBStateMachine,
     dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
@@ -244,6 +296,33 @@ async fn transition_user_cancel_multi(
     }
 }
 
+async fn transition_user_cancel_pending(
+    prev_state: MintOOBStateMachine,
+    dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+    global_context: DynGlobalClientContext,
+) -> MintOOBStateMachine {
+    let notes = match prev_state.state {
+        MintOOBStates::CreatedPending(created) => created.notes,
+        _ => panic!("Invalid previous state: {prev_state:?}"),
+    };
+
+    let spendable_notes = take_pending_notes(dbtx, prev_state.operation_id, notes).await;
+    let refund_txid = try_cancel_oob_spend_multi(
+        dbtx,
+        prev_state.operation_id,
+        spendable_notes.clone(),
+        global_context,
+    )
+    .await;
+    MintOOBStateMachine {
+        operation_id: prev_state.operation_id,
+        state: MintOOBStates::UserRefundMulti(MintOOBStatesUserRefundMulti {
+            refund_txid,
+            spendable_notes,
+        }),
+    }
+}
+
 async fn await_timeout_cancel(deadline: SystemTime) {
     if let Ok(time_until_deadline) = deadline.duration_since(fedimint_core::time::now()) {
         runtime::sleep(time_until_deadline).await;
@@ -297,6 +376,53 @@ async fn transition_timeout_cancel_multi(
     }
 }
 
+async fn transition_timeout_cancel_pending(
+    prev_state: MintOOBStateMachine,
+    dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+    global_context: DynGlobalClientContext,
+) -> MintOOBStateMachine {
+    let notes = match prev_state.state {
+        MintOOBStates::CreatedPending(created) => created.notes,
+        _ => panic!("Invalid previous state: {prev_state:?}"),
+    };
+
+    let spendable_notes = take_pending_notes(dbtx, prev_state.operation_id, notes).await;
+    let refund_txid = try_cancel_oob_spend_multi(
+        dbtx,
+        prev_state.operation_id,
+        spendable_notes,
+        global_context,
+    )
+    .await;
+    MintOOBStateMachine {
+        operation_id: prev_state.operation_id,
+        state: MintOOBStates::TimeoutRefund(MintOOBStatesTimeoutRefund { refund_txid }),
+    }
+}
+
+/// Removes the notes pending in the spend from the wallet so they can be
+/// refunded. Notes that are no longer pending in it are skipped, refunding
+/// them could only fail.
+async fn take_pending_notes(
+    dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
+    operation_id: OperationId,
+    notes: Vec<(Amount, Nonce)>,
+) -> Vec<(Amount, SpendableNote)> {
+    let mut spendable_notes = Vec::with_capacity(notes.len());
+    for (amount, nonce) in notes {
+        match take_pending_oob_note(&mut dbtx.module_tx(), amount, nonce, operation_id).await {
+            Ok(note) => {
+                let note = note.decode().expect("Notes in the wallet are valid");
+                spendable_notes.push((amount, note));
+            }
+            Err(err) => {
+                error!(target: LOG_CLIENT_MODULE_MINT, %err, "Can't refund out-of-band spent note");
+            }
+        }
+    }
+    spendable_notes
+}
+
 async fn try_cancel_oob_spend(
     dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
     operation_id: OperationId,
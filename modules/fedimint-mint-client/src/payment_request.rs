@@ -0,0 +1,184 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, ensure};
+use base64::Engine as _;
+use fedimint_core::config::FederationIdPrefix;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use fedimint_core::secp256k1::{ecdh, rand, Keypair, PublicKey, SecretKey, SECP256K1};
+use fedimint_core::Amount;
+use fedimint_derive_secret::DerivableSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::OOBNotes;
+
+/// Human readable prefix of an encoded [`EcashPaymentRequest`]
+const PAYMENT_REQUEST_PREFIX: &str = "fedimintreq";
+
+/// Human readable prefix of encoded [`SealedOOBNotes`]
+const SEALED_NOTES_PREFIX: &str = "fedimintsealed";
+
+/// Salt used when turning the ECDH shared secret into an encryption key
+const SEALED_NOTES_KDF_SALT: &[u8] = b"fedimint-mint-payment-request";
+
+/// A reusable request for e-cash, comparable to a static address.
+///
+/// The recipient publishes it once (e.g. as a QR code) and any number of
+/// senders can pay it with
+/// [`crate::MintClientModule::pay_payment_request`]. The resulting
+/// [`SealedOOBNotes`] are encrypted to `recipient_pk`, so they can be passed
+/// along over untrusted channels and claimed by the recipient whenever they
+/// come online using [`crate::MintClientModule::claim_sealed_notes`].
+///
+/// Sealing only protects the notes in transit: they are ordinary bearer notes
+/// the sender still knows the spend keys of, so the sender can cancel the
+/// payment and reclaim them until the recipient has reissued them.
+///
+/// Every request uses its own `recipient_pk`, derived from the recipient's
+/// secret and the random `key_index`, so two requests of the same recipient
+/// can't be linked to each other.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encodable, Decodable)]
+pub struct EcashPaymentRequest {
+    /// Federation the recipient is willing to receive e-cash from
+    pub federation_id_prefix: FederationIdPrefix,
+    /// Index the recipient derived `recipient_pk` with
+    pub key_index: u64,
+    /// Key the e-cash will be encrypted to, only the recipient can open it
+    pub recipient_pk: PublicKey,
+    /// Requested amount, if `None` the sender chooses the amount
+    pub amount: Option<Amount>,
+    /// Free-form description shown to the sender
+    pub memo: Option<String>,
+    /// Seconds since the unix epoch after which the request shouldn't be paid
+    /// anymore, `None` if it never expires
+    pub expiry: Option<u64>,
+}
+
+impl EcashPaymentRequest {
+    /// Returns `true` if the request expired at time `now`
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        self.expiry.is_some_and(|expiry| {
+            now.duration_since(UNIX_EPOCH).unwrap_or_default() >= Duration::from_secs(expiry)
+        })
+    }
+
+    /// Returns `true` if the request is expired
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(fedimint_core::time::now())
+    }
+}
+
+/// Out-of-band e-cash notes encrypted to the recipient of an
+/// [`EcashPaymentRequest`].
+///
+/// The notes are encrypted with a key derived from the ECDH shared secret of
+/// a fresh `ephemeral_pk` and the request's `recipient_pk`. This hides them
+/// from whoever relays them, not from the sender, see
+/// [`EcashPaymentRequest`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encodable, Decodable)]
+pub struct SealedOOBNotes {
+    pub federation_id_prefix: FederationIdPrefix,
+    pub key_index: u64,
+    pub recipient_pk: PublicKey,
+    pub ephemeral_pk: PublicKey,
+    pub ciphertext: Vec<u8>,
+}
+
+impl SealedOOBNotes {
+    /// Encrypt `notes` to the recipient of `request` using a freshly
+    /// generated ephemeral key
+    pub fn seal(notes: &OOBNotes, request: &EcashPaymentRequest) -> anyhow::Result<Self> {
+        let ephemeral = Keypair::new(SECP256K1, &mut rand::thread_rng());
+        let key = sealing_key(&ecdh::SharedSecret::new(
+            &request.recipient_pk,
+            &ephemeral.secret_key(),
+        ));
+        let ciphertext = fedimint_aead::encrypt(notes.consensus_encode_to_vec(), &key)?;
+
+        Ok(Self {
+            federation_id_prefix: notes.federation_id_prefix(),
+            key_index: request.key_index,
+            recipient_pk: request.recipient_pk,
+            ephemeral_pk: ephemeral.public_key(),
+            ciphertext,
+        })
+    }
+
+    /// Decrypt the notes using the recipient's secret key
+    pub fn open(&self, recipient_sk: &SecretKey) -> anyhow::Result<OOBNotes> {
+        let key = sealing_key(&ecdh::SharedSecret::new(&self.ephemeral_pk, recipient_sk));
+        let mut ciphertext = self.ciphertext.clone();
+        let plaintext = fedimint_aead::decrypt(&mut ciphertext, &key)?;
+
+        let notes =
+            OOBNotes::consensus_decode_vec(plaintext.to_vec(), &ModuleDecoderRegistry::default())?;
+
+        ensure!(
+            notes.federation_id_prefix() == self.federation_id_prefix,
+            "Sealed notes were issued by a different federation than advertised"
+        );
+
+        Ok(notes)
+    }
+}
+
+fn sealing_key(shared_secret: &ecdh::SharedSecret) -> fedimint_aead::LessSafeKey {
+    fedimint_aead::LessSafeKey::new(
+        DerivableSecret::new_root(&shared_secret.secret_bytes(), SEALED_NOTES_KDF_SALT)
+            .to_chacha20_poly1305_key(),
+    )
+}
+
+macro_rules! impl_prefixed_base64_string {
+    ($ty:ty, $prefix:expr) => {
+        impl Display for $ty {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                f.write_str($prefix)?;
+                f.write_str(&crate::BASE64_URL_SAFE.encode(self.consensus_encode_to_vec()))
+            }
+        }
+
+        impl FromStr for $ty {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let s: String = s.chars().filter(|&c| !c.is_whitespace()).collect();
+
+                let Some(encoded) = s.strip_prefix($prefix) else {
+                    bail!("Missing {} prefix", $prefix);
+                };
+
+                let bytes = crate::BASE64_URL_SAFE.decode(encoded)?;
+
+                Ok(Self::consensus_decode_vec(
+                    bytes,
+                    &ModuleDecoderRegistry::default(),
+                )?)
+            }
+        }
+
+        impl Serialize for $ty {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                let s = String::deserialize(deserializer)?;
+                FromStr::from_str(&s).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+impl_prefixed_base64_string!(EcashPaymentRequest, PAYMENT_REQUEST_PREFIX);
+impl_prefixed_base64_string!(SealedOOBNotes, SEALED_NOTES_PREFIX);
@@ -0,0 +1,172 @@
+//! Scoring how identifiable the notes of a spend are
+//!
+//! Blind signatures hide which issuance a note came from, but not its
+//! denomination. A recipient, or a federation seeing the notes reissued, can
+//! tell spends apart by the combination of denominations used. Any wallet with
+//! enough notes spends an amount using its canonical representation (the
+//! fewest notes adding up to the amount), so spends that deviate from it, e.g.
+//! by using many small notes or overpaying with a large one, stand out.
+
+use fedimint_core::{Amount, TieredCounts};
+use serde::{Deserialize, Serialize};
+
+/// Score from which [`SpendPrivacyReport::is_identifiable`] considers a spend
+/// identifiable
+pub const IDENTIFIABLE_SPEND_SCORE: u8 = 30;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpendPrivacyWarning {
+    /// More notes of `denomination` than the canonical representation uses
+    UnusualNoteCount {
+        denomination: Amount,
+        count: usize,
+        expected: usize,
+    },
+    /// Notes of a denomination the canonical representation doesn't use
+    UnexpectedDenomination { denomination: Amount, count: usize },
+    /// The notes are worth more than the spent amount
+    Overpayment { excess: Amount },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendPrivacyReport {
+    /// Amount that is supposed to be spent
+    pub amount: Amount,
+    /// Notes selected for the spend
+    pub notes: TieredCounts,
+    /// Notes every wallet holding enough notes of each denomination would
+    /// spend `amount` with
+    pub canonical_notes: TieredCounts,
+    /// Share of notes deviating from the canonical representation, from 0 if
+    /// the spend looks like any other spend of `amount` to 100 if it shares no
+    /// notes with it
+    pub score: u8,
+    pub warnings: Vec<SpendPrivacyWarning>,
+}
+
+impl SpendPrivacyReport {
+    /// Compares the `notes` selected to spend `amount` to its canonical
+    /// representation `canonical_notes`
+    pub fn new(amount: Amount, notes: TieredCounts, canonical_notes: TieredCounts) -> Self {
+        let mut warnings = vec![];
+        let mut deviating_notes = 0;
+
+        for (denomination, count) in notes.iter().filter(|(_, count)| *count > 0) {
+            let expected = canonical_notes.get(denomination);
+
+            if expected == 0 {
+                warnings.push(SpendPrivacyWarning::UnexpectedDenomination {
+                    denomination,
+                    count,
+                });
+            } else if expected < count {
+                warnings.push(SpendPrivacyWarning::UnusualNoteCount {
+                    denomination,
+                    count,
+                    expected,
+                });
+            }
+
+            deviating_notes += count.abs_diff(expected);
+        }
+
+        for (denomination, expected) in canonical_notes.iter() {
+            if notes.get(denomination) == 0 {
+                deviating_notes += expected;
+            }
+        }
+
+        if amount < notes.total_amount() {
+            warnings.push(SpendPrivacyWarning::Overpayment {
+                excess: notes.total_amount() - amount,
+            });
+        }
+
+        let total_notes = notes.count_items() + canonical_notes.count_items();
+        let score = if total_notes == 0 {
+            0
+        } else {
+            (deviating_notes * 100 / total_notes) as u8
+        };
+
+        Self {
+            amount,
+            notes,
+            canonical_notes,
+            score,
+            warnings,
+        }
+    }
+
+    /// Returns true if the spend deviates enough from the canonical
+    /// representation of its amount to likely be linkable to this wallet
+    pub fn is_identifiable(&self) -> bool {
+        IDENTIFIABLE_SPEND_SCORE <= self.score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::{Amount, TieredCounts};
+
+    use super::{SpendPrivacyReport, SpendPrivacyWarning};
+
+    fn counts(notes: &[(u64, usize)]) -> TieredCounts {
+        let mut counts = TieredCounts::default();
+        for (msats, count) in notes {
+            counts.inc(Amount::from_msats(*msats), *count);
+        }
+        counts
+    }
+
+    #[test]
+    fn canonical_spend_is_not_identifiable() {
+        let canonical = counts(&[(1024, 1), (4, 1)]);
+        let report =
+            SpendPrivacyReport::new(Amount::from_msats(1028), canonical.clone(), canonical);
+
+        assert_eq!(report.score, 0);
+        assert!(report.warnings.is_empty());
+        assert!(!report.is_identifiable());
+    }
+
+    #[test]
+    fn fragmented_spend_is_identifiable() {
+        let report = SpendPrivacyReport::new(
+            Amount::from_msats(1028),
+            counts(&[(512, 2), (2, 2)]),
+            counts(&[(1024, 1), (4, 1)]),
+        );
+
+        assert_eq!(report.score, 100);
+        assert!(report.is_identifiable());
+        assert_eq!(
+            report.warnings,
+            vec![
+                SpendPrivacyWarning::UnexpectedDenomination {
+                    denomination: Amount::from_msats(2),
+                    count: 2,
+                },
+                SpendPrivacyWarning::UnexpectedDenomination {
+                    denomination: Amount::from_msats(512),
+                    count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn overpayment_is_reported() {
+        let report = SpendPrivacyReport::new(
+            Amount::from_msats(1000),
+            counts(&[(1024, 1)]),
+            counts(&[(512, 1), (256, 1), (128, 1), (64, 1), (32, 1), (8, 1)]),
+        );
+
+        assert!(report.is_identifiable());
+        assert!(report.warnings.contains(&SpendPrivacyWarning::Overpayment {
+            excess: Amount::from_msats(24),
+        }));
+    }
+}
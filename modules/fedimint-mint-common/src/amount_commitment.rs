@@ -0,0 +1,62 @@
+//! Reserved encoding space for mint outputs that hide their amount
+//!
+//! Mint outputs reveal the denomination of the note they issue, which lets
+//! anybody following consensus learn the amounts flowing through the
+//! federation. Outputs committing to their amount instead, with a proof that
+//! the committed amount is one of the federation's denominations, would avoid
+//! that. Which commitment and proof system to use is still an open research
+//! question, so this module only reserves the output variant such outputs
+//! will be encoded as and defines the [`AmountCommitmentScheme`] hook a
+//! prototype can plug into the mint server behind its `amount-commitments`
+//! feature. Prototypes thus don't require another wire format break.
+//!
+//! The mint still learns the amount of every output it signs, as it signs with
+//! the key of the output's denomination. It only records the commitments of
+//! such outputs though, so its database and audit never contain their amounts
+//! individually, but only their sum per session.
+//!
+//! Federations without a scheme reject these outputs as an unknown variant,
+//! just like any other variant they don't support.
+
+use std::fmt::Debug;
+
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::BlindNonce;
+
+/// Variant of [`crate::MintOutput`] reserved for
+/// [`MintOutputAmountCommitment`]s
+pub const AMOUNT_COMMITMENT_OUTPUT_VARIANT: u64 = 1;
+
+/// A mint output committing to the amount of the note it issues instead of
+/// revealing it
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct MintOutputAmountCommitment {
+    /// Commitment to the amount of the note, opaque to anything but the
+    /// [`AmountCommitmentScheme`]
+    pub commitment: Vec<u8>,
+    /// Proof that the committed amount is one of the federation's
+    /// denominations
+    pub range_proof: Vec<u8>,
+    pub blind_nonce: BlindNonce,
+}
+
+/// Verification of [`MintOutputAmountCommitment`]s, see the
+/// [module docs](self)
+pub trait AmountCommitmentScheme: Debug + Send + Sync {
+    /// Verifies the commitment and range proof of `output` against the
+    /// federation's `denominations` and returns the amount the output adds
+    /// to the transaction balance and audit
+    fn verify(
+        &self,
+        output: &MintOutputAmountCommitment,
+        denominations: &[Amount],
+    ) -> Result<Amount, String>;
+
+    /// Returns the total amount committed to by `commitments`, which were all
+    /// accepted by [`Self::verify`], for the audit of the federation's
+    /// liabilities
+    fn audit(&self, commitments: &[Vec<u8>]) -> Result<Amount, String>;
+}
@@ -2,7 +2,8 @@
 
 use fedimint_core::config::EmptyGenParams;
 use fedimint_core::core::ModuleKind;
-use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::serde_json;
 use fedimint_core::{plugin_types_trait_impl_config, Amount, PeerId, Tiered};
 use serde::{Deserialize, Serialize};
@@ -109,12 +110,60 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     MintClientConfig
 );
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+/// Takes the place of the base fee in the consensus encoding of a
+/// [`FeeConsensus`] to announce an explicitly versioned encoding. No base fee
+/// can ever be this large, so it is unambiguous with the unversioned layout.
+const FEE_CONSENSUS_VERSION_MARKER: u64 = u64::MAX;
+
+/// Fee schedule of the mint module.
+///
+/// The fee schedule is part of the client config and thereby committed to by
+/// its consensus hash, so its consensus encoding must never change silently.
+/// Version 0 is encoded as the plain `base` and `parts_per_million` fields,
+/// exactly as before the encoding was versioned. Later versions are encoded
+/// as [`FEE_CONSENSUS_VERSION_MARKER`], the version and the length prefixed
+/// version specific fields, so clients that don't know a version reject the
+/// config instead of misinterpreting the fees.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct FeeConsensus {
     base: Amount,
     parts_per_million: u64,
 }
 
+impl Encodable for FeeConsensus {
+    fn consensus_encode<W: std::io::Write>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
+        // Only version 0 exists so far, which uses the unversioned layout
+        let mut len = 0;
+        len += self.base.consensus_encode(writer)?;
+        len += self.parts_per_million.consensus_encode(writer)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for FeeConsensus {
+    fn consensus_decode_from_finite_reader<R: std::io::Read>(
+        reader: &mut R,
+        modules: &ModuleDecoderRegistry,
+    ) -> Result<Self, DecodeError> {
+        let base = u64::consensus_decode_from_finite_reader(reader, modules)?;
+
+        if base == FEE_CONSENSUS_VERSION_MARKER {
+            let version = u16::consensus_decode_from_finite_reader(reader, modules)?;
+            // Consume the fields so the error is about the version, not the framing
+            Vec::<u8>::consensus_decode_from_finite_reader(reader, modules)?;
+
+            return Err(DecodeError::new_custom(anyhow::anyhow!(
+                "Unsupported fee consensus version {version}"
+            )));
+        }
+
+        Ok(Self {
+            base: Amount::from_msats(base),
+            parts_per_million: u64::consensus_decode_from_finite_reader(reader, modules)?,
+        })
+    }
+}
+
 impl FeeConsensus {
     /// The mint module will charge a non-configurable base fee of one hundred
     /// millisatoshis per transaction input and output to account for the costs
@@ -187,3 +236,29 @@ fn test_fee_consensus() {
         Amount::from_bitcoins(100) + Amount::from_msats(100)
     );
 }
+
+#[test]
+fn test_fee_consensus_encoding() {
+    let fee_consensus = FeeConsensus::new(1_000).expect("Relative fee is within range");
+
+    // Version 0 has to stay byte for byte identical to the unversioned encoding
+    // since it is committed to by the consensus hash of existing client configs
+    let encoded = fee_consensus.consensus_encode_to_vec();
+    let mut expected = Amount::from_msats(100).consensus_encode_to_vec();
+    expected.append(&mut 1_000u64.consensus_encode_to_vec());
+    assert_eq!(encoded, expected);
+
+    assert_eq!(
+        FeeConsensus::consensus_decode_vec(encoded, &ModuleDecoderRegistry::default())
+            .expect("Decoding succeeds"),
+        fee_consensus
+    );
+
+    let mut unknown_version = FEE_CONSENSUS_VERSION_MARKER.consensus_encode_to_vec();
+    unknown_version.append(&mut 1u16.consensus_encode_to_vec());
+    unknown_version.append(&mut vec![0u8; 4].consensus_encode_to_vec());
+    assert!(
+        FeeConsensus::consensus_decode_vec(unknown_version, &ModuleDecoderRegistry::default())
+            .is_err()
+    );
+}
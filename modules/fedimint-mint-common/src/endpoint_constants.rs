@@ -3,3 +3,4 @@
 pub const RECOVER_ENDPOINT: &str = "recover";
 pub const NOTE_SPENT_ENDPOINT: &str = "note_spent";
 pub const BLIND_NONCE_USED_ENDPOINT: &str = "blind_nonce_used";
+pub const FEE_CONSENSUS_ENDPOINT: &str = "fee_consensus";
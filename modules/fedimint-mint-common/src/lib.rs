@@ -8,12 +8,14 @@
 use core::fmt;
 use std::hash::Hash;
 
+use amount_commitment::{MintOutputAmountCommitment, AMOUNT_COMMITMENT_OUTPUT_VARIANT};
 use bitcoin_hashes::hex::DisplayHex;
 use bitcoin_hashes::Hash as _;
 pub use common::{BackupRequest, SignedBackupRequest};
 use config::MintClientConfig;
 use fedimint_core::core::{Decoder, ModuleInstanceId, ModuleKind};
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::{CommonModuleInit, ModuleCommon, ModuleConsensusVersion};
 use fedimint_core::{
     extensible_associated_module_type, plugin_types_trait_impl_common, secp256k1, Amount,
@@ -23,6 +25,7 @@
 use thiserror::Error;
 use tracing::error;
 
+pub mod amount_commitment;
 pub mod common;
 pub mod config;
 pub mod endpoint_constants;
@@ -152,6 +155,9 @@ pub fn new_v0(amount: Amount, note: Note) -> MintInput {
     }
 }
 
+/// Spends a single e-cash note. Every note is its own transaction input that
+/// is authorized by the note's spend key and charged its own fee, so inputs
+/// can be signed and rejected individually.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
 pub struct MintInputV0 {
     pub amount: Amount,
@@ -173,6 +179,31 @@ pub fn new_v0(amount: Amount, blind_nonce: BlindNonce) -> MintOutput {
             blind_nonce,
         })
     }
+
+    /// Encodes `output` as the variant reserved for amount commitments, see
+    /// [`amount_commitment`]
+    pub fn new_amount_commitment(output: &MintOutputAmountCommitment) -> MintOutput {
+        MintOutput::Default {
+            variant: AMOUNT_COMMITMENT_OUTPUT_VARIANT,
+            bytes: output.consensus_encode_to_vec(),
+        }
+    }
+
+    /// Decodes the output if it is of the variant reserved for amount
+    /// commitments, see [`amount_commitment`]
+    pub fn maybe_amount_commitment(&self) -> Option<MintOutputAmountCommitment> {
+        match self {
+            MintOutput::Default {
+                variant: AMOUNT_COMMITMENT_OUTPUT_VARIANT,
+                bytes,
+            } => MintOutputAmountCommitment::consensus_decode_vec(
+                bytes.clone(),
+                &ModuleDecoderRegistry::default(),
+            )
+            .ok(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
@@ -269,4 +300,6 @@ pub enum MintOutputError {
     InvalidAmountTier(Amount),
     #[error("The mint output version is not supported by this federation")]
     UnknownOutputVariant(#[from] UnknownMintOutputVariantError),
+    #[error("The amount commitment of the mint output is invalid: {0}")]
+    InvalidAmountCommitment(String),
 }
@@ -14,6 +14,7 @@ pub enum DbKeyPrefix {
     MintAuditItem = 0x14,
     EcashBackup = 0x15,
     BlindNonce = 0x16,
+    CommittedIssuance = 0x17,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -113,3 +114,21 @@ pub struct ECashUserBackupSnapshot {
     #[serde(with = "fedimint_core::hex::serde")]
     pub data: Vec<u8>,
 }
+
+/// Commitments of the outputs issued since the last audit that hide their
+/// amount, see [`fedimint_mint_common::amount_commitment`]
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct CommittedIssuanceKey(pub OutPoint);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct CommittedIssuanceKeyPrefix;
+
+impl_db_record!(
+    key = CommittedIssuanceKey,
+    value = Vec<u8>,
+    db_prefix = DbKeyPrefix::CommittedIssuance,
+);
+impl_db_lookup!(
+    key = CommittedIssuanceKey,
+    query_prefix = CommittedIssuanceKeyPrefix
+);
@@ -6,8 +6,12 @@
 
 pub mod db;
 mod metrics;
+mod nonce_filter;
 
 use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "amount-commitments")]
+use std::sync::Arc;
+use std::sync::RwLock;
 
 use anyhow::bail;
 use fedimint_core::config::{
@@ -16,8 +20,8 @@
 };
 use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{
-    CoreMigrationFn, DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCoreTyped,
-    MigrationContext,
+    CoreMigrationFn, Database, DatabaseTransaction, DatabaseVersion,
+    IDatabaseTransactionOpsCoreTyped, MigrationContext,
 };
 use fedimint_core::module::audit::Audit;
 use fedimint_core::module::{
@@ -33,9 +37,11 @@
 };
 use fedimint_logging::LOG_MODULE_MINT;
 pub use fedimint_mint_common as common;
+#[cfg(feature = "amount-commitments")]
+use fedimint_mint_common::amount_commitment::AmountCommitmentScheme;
 use fedimint_mint_common::config::{
-    MintClientConfig, MintConfig, MintConfigConsensus, MintConfigLocal, MintConfigPrivate,
-    MintGenParams,
+    FeeConsensus, MintClientConfig, MintConfig, MintConfigConsensus, MintConfigLocal,
+    MintConfigPrivate, MintGenParams,
 };
 use fedimint_mint_common::endpoint_constants::{BACKUP_ENDPOINT, RECOVER_ENDPOINT};
 pub use fedimint_mint_common::{BackupRequest, SignedBackupRequest};
@@ -50,8 +56,9 @@
 use itertools::Itertools;
 use metrics::{
     MINT_INOUT_FEES_SATS, MINT_INOUT_SATS, MINT_ISSUED_ECASH_FEES_SATS, MINT_ISSUED_ECASH_SATS,
-    MINT_REDEEMED_ECASH_FEES_SATS, MINT_REDEEMED_ECASH_SATS,
+    MINT_NONCE_FILTER_CHECKS, MINT_REDEEMED_ECASH_FEES_SATS, MINT_REDEEMED_ECASH_SATS,
 };
+use nonce_filter::NonceFilter;
 use rand::rngs::OsRng;
 use strum::IntoEnumIterator;
 use tbs::{
@@ -63,16 +70,26 @@
 use threshold_crypto::{G2Projective, Scalar};
 use tracing::{debug, info, warn};
 
-use crate::common::endpoint_constants::{BLIND_NONCE_USED_ENDPOINT, NOTE_SPENT_ENDPOINT};
+use crate::common::endpoint_constants::{
+    BLIND_NONCE_USED_ENDPOINT, FEE_CONSENSUS_ENDPOINT, NOTE_SPENT_ENDPOINT,
+};
 use crate::common::{BlindNonce, Nonce};
 use crate::db::{
-    BlindNonceKey, BlindNonceKeyPrefix, DbKeyPrefix, ECashUserBackupSnapshot, EcashBackupKey,
-    EcashBackupKeyPrefix, MintAuditItemKey, MintAuditItemKeyPrefix, MintOutputOutcomeKey,
-    MintOutputOutcomePrefix, NonceKey, NonceKeyPrefix,
+    BlindNonceKey, BlindNonceKeyPrefix, CommittedIssuanceKey, CommittedIssuanceKeyPrefix,
+    DbKeyPrefix, ECashUserBackupSnapshot, EcashBackupKey, EcashBackupKeyPrefix, MintAuditItemKey,
+    MintAuditItemKeyPrefix, MintOutputOutcomeKey, MintOutputOutcomePrefix, NonceKey,
+    NonceKeyPrefix,
 };
 
-#[derive(Debug, Clone)]
-pub struct MintInit;
+#[derive(Debug, Clone, Default)]
+pub struct MintInit {
+    /// Scheme to verify outputs hiding their amount with, see
+    /// [`fedimint_mint_common::amount_commitment`]. Accepting such outputs
+    /// changes consensus, so all guardians of a federation have to use the same
+    /// scheme. This is meant for research deployments only.
+    #[cfg(feature = "amount-commitments")]
+    pub amount_commitment_scheme: Option<Arc<dyn AmountCommitmentScheme>>,
+}
 
 impl ModuleInit for MintInit {
     type Common = MintCommonInit;
@@ -130,6 +147,16 @@ async fn dump_database(
                         "Used Blind Nonces"
                     );
                 }
+                DbKeyPrefix::CommittedIssuance => {
+                    push_db_pair_items!(
+                        dbtx,
+                        CommittedIssuanceKeyPrefix,
+                        CommittedIssuanceKey,
+                        Vec<u8>,
+                        mint,
+                        "Committed Issuances"
+                    );
+                }
             }
         }
 
@@ -152,12 +179,16 @@ fn supported_api_versions(&self) -> SupportedModuleApiVersions {
                 MODULE_CONSENSUS_VERSION.major,
                 MODULE_CONSENSUS_VERSION.minor,
             ),
-            &[(0, 1)],
+            &[(0, 2)],
         )
     }
 
     async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<DynServerModule> {
-        Ok(Mint::new(args.cfg().to_typed()?).into())
+        let mint = Mint::new(args.cfg().to_typed()?);
+        #[cfg(feature = "amount-commitments")]
+        let mint = mint.with_amount_commitment_scheme(self.amount_commitment_scheme.clone());
+        mint.load_spent_nonces(args.db()).await;
+        Ok(mint.into())
     }
 
     fn trusted_dealer_gen(
@@ -331,6 +362,18 @@ fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn>
         migrations.insert(DatabaseVersion(0), migrate_db_v0 as CoreMigrationFn);
         migrations
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        // Ecash backups are stored by each guardian as clients upload them and the
+        // output outcomes hold our own signature shares, which are recreated by
+        // `rebuild_output_outcome` instead
+        Some(vec![
+            DbKeyPrefix::NoteNonce as u8,
+            DbKeyPrefix::MintAuditItem as u8,
+            DbKeyPrefix::BlindNonce as u8,
+            DbKeyPrefix::CommittedIssuance as u8,
+        ])
+    }
 }
 
 fn migrate_db_v0(mut migration_context: MigrationContext<'_>) -> BoxFuture<anyhow::Result<()>> {
@@ -414,6 +457,11 @@ pub struct Mint {
     cfg: MintConfig,
     sec_key: Tiered<SecretKeyShare>,
     pub_key: HashMap<Amount, AggregatePublicKey>,
+    /// Pre-filter over the spent nonces, see [`NonceFilter`]. `None` until it
+    /// was loaded from the database, in which case every nonce is looked up.
+    spent_nonces: RwLock<Option<NonceFilter>>,
+    #[cfg(feature = "amount-commitments")]
+    amount_commitment_scheme: Option<Arc<dyn AmountCommitmentScheme>>,
 }
 #[apply(async_trait_maybe_send!)]
 impl ServerModule for Mint {
@@ -460,19 +508,41 @@ async fn process_input<'a, 'b, 'c>(
 
         debug!(target: LOG_MODULE_MINT, nonce=%(input.note.nonce), "Marking note as spent");
 
-        if dbtx
-            .insert_entry(&NonceKey(input.note.nonce), &())
-            .await
-            .is_some()
-        {
-            return Err(MintInputError::SpentCoin);
-        }
+        let never_spent = self
+            .spent_nonces
+            .write()
+            .expect("poisoned")
+            .as_mut()
+            .is_some_and(|filter| filter.insert(&input.note.nonce));
+        MINT_NONCE_FILTER_CHECKS.inc(&[if never_spent {
+            "absent"
+        } else {
+            "maybe_present"
+        }]);
+
+        if never_spent {
+            // The filter has no false negatives, so we can skip reading the database
+            dbtx.put_new_entry(&NonceKey(input.note.nonce), &()).await;
+            dbtx.put_new_entry(
+                &MintAuditItemKey::Redemption(NonceKey(input.note.nonce)),
+                &input.amount,
+            )
+            .await;
+        } else {
+            if dbtx
+                .insert_entry(&NonceKey(input.note.nonce), &())
+                .await
+                .is_some()
+            {
+                return Err(MintInputError::SpentCoin);
+            }
 
-        dbtx.insert_new_entry(
-            &MintAuditItemKey::Redemption(NonceKey(input.note.nonce)),
-            &input.amount,
-        )
-        .await;
+            dbtx.insert_new_entry(
+                &MintAuditItemKey::Redemption(NonceKey(input.note.nonce)),
+                &input.amount,
+            )
+            .await;
+        }
 
         let amount = input.amount;
         let fee = self.cfg.consensus.fee_consensus.fee(amount);
@@ -491,41 +561,39 @@ async fn process_output<'a, 'b>(
         output: &'a MintOutput,
         out_point: OutPoint,
     ) -> Result<TransactionItemAmount, MintOutputError> {
-        let output = output.ensure_v0_ref()?;
+        #[cfg(feature = "amount-commitments")]
+        if let (Some(output), Some(scheme)) = (
+            output.maybe_amount_commitment(),
+            &self.amount_commitment_scheme,
+        ) {
+            let denominations = self.sec_key.tiers().copied().collect::<Vec<_>>();
+            let amount = scheme
+                .verify(&output, &denominations)
+                .map_err(MintOutputError::InvalidAmountCommitment)?;
+
+            let amount = self
+                .issue_note(dbtx, amount, output.blind_nonce, out_point)
+                .await?;
+
+            // The audit only learns the sum of the committed amounts, see `audit`
+            dbtx.insert_new_entry(&CommittedIssuanceKey(out_point), &output.commitment)
+                .await;
+
+            return Ok(amount);
+        }
 
-        let amount_key = self
-            .sec_key
-            .get(output.amount)
-            .ok_or(MintOutputError::InvalidAmountTier(output.amount))?;
+        let output = output.ensure_v0_ref()?;
 
-        dbtx.insert_new_entry(
-            &MintOutputOutcomeKey(out_point),
-            &MintOutputOutcome::new_v0(sign_blinded_msg(output.blind_nonce.0, *amount_key)),
-        )
-        .await;
+        let amount = self
+            .issue_note(dbtx, output.amount, output.blind_nonce, out_point)
+            .await?;
 
         dbtx.insert_new_entry(&MintAuditItemKey::Issuance(out_point), &output.amount)
             .await;
 
-        if dbtx
-            .insert_entry(&BlindNonceKey(output.blind_nonce), &())
-            .await
-            .is_some()
-        {
-            // TODO: make a consensus rule against this
-            warn!(
-                denomination = %output.amount,
-                bnonce = ?output.blind_nonce,
-                "Blind nonce already used, money was burned!"
-            );
-        }
-
-        let amount = output.amount;
-        let fee = self.cfg.consensus.fee_consensus.fee(amount);
+        calculate_mint_issued_ecash_metrics(dbtx, amount.amount, amount.fee);
 
-        calculate_mint_issued_ecash_metrics(dbtx, amount, fee);
-
-        Ok(TransactionItemAmount { amount, fee })
+        Ok(amount)
     }
 
     async fn output_status(
@@ -536,6 +604,42 @@ async fn output_status(
         dbtx.get_value(&MintOutputOutcomeKey(out_point)).await
     }
 
+    async fn rebuild_output_outcome(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        output: &MintOutput,
+        out_point: OutPoint,
+    ) -> anyhow::Result<()> {
+        #[cfg(feature = "amount-commitments")]
+        if let (Some(output), Some(scheme)) = (
+            output.maybe_amount_commitment(),
+            &self.amount_commitment_scheme,
+        ) {
+            let denominations = self.sec_key.tiers().copied().collect::<Vec<_>>();
+            let amount = scheme
+                .verify(&output, &denominations)
+                .map_err(MintOutputError::InvalidAmountCommitment)?;
+
+            dbtx.insert_entry(
+                &MintOutputOutcomeKey(out_point),
+                &self.sign_output(amount, output.blind_nonce)?,
+            )
+            .await;
+
+            return Ok(());
+        }
+
+        let output = output.ensure_v0_ref()?;
+
+        dbtx.insert_entry(
+            &MintOutputOutcomeKey(out_point),
+            &self.sign_output(output.amount, output.blind_nonce)?,
+        )
+        .await;
+
+        Ok(())
+    }
+
     async fn audit(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
@@ -544,6 +648,10 @@ async fn audit(
     ) {
         let mut redemptions = Amount::from_sats(0);
         let mut issuances = Amount::from_sats(0);
+        #[cfg(feature = "amount-commitments")]
+        {
+            issuances += self.audit_committed_issuances(dbtx).await;
+        }
         let remove_audit_keys = dbtx
             .find_by_prefix(&MintAuditItemKeyPrefix)
             .await
@@ -609,10 +717,26 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
             api_endpoint! {
                 NOTE_SPENT_ENDPOINT,
                 ApiVersion::new(0, 1),
-                async |_module: &Mint, context, nonce: Nonce| -> bool {
+                async |module: &Mint, context, nonce: Nonce| -> bool {
+                    if module
+                        .spent_nonces
+                        .read()
+                        .expect("poisoned")
+                        .as_ref()
+                        .is_some_and(|filter| !filter.may_contain(&nonce))
+                    {
+                        return Ok(false);
+                    }
                     Ok(context.dbtx().get_value(&NonceKey(nonce)).await.is_some())
                 }
             },
+            api_endpoint! {
+                FEE_CONSENSUS_ENDPOINT,
+                ApiVersion::new(0, 2),
+                async |module: &Mint, _context, _v: ()| -> FeeConsensus {
+                    Ok(module.cfg.consensus.fee_consensus.clone())
+                }
+            },
             api_endpoint! {
                 BLIND_NONCE_USED_ENDPOINT,
                 ApiVersion::new(0, 1),
@@ -625,6 +749,56 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
 }
 
 impl Mint {
+    /// Signs the blind nonce of an output, the caller records its issuance for
+    /// the audit
+    /// Our signature share for a note of `amount` with `blind_nonce`
+    fn sign_output(
+        &self,
+        amount: Amount,
+        blind_nonce: BlindNonce,
+    ) -> Result<MintOutputOutcome, MintOutputError> {
+        let amount_key = self
+            .sec_key
+            .get(amount)
+            .ok_or(MintOutputError::InvalidAmountTier(amount))?;
+
+        Ok(MintOutputOutcome::new_v0(sign_blinded_msg(
+            blind_nonce.0,
+            *amount_key,
+        )))
+    }
+
+    async fn issue_note(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        amount: Amount,
+        blind_nonce: BlindNonce,
+        out_point: OutPoint,
+    ) -> Result<TransactionItemAmount, MintOutputError> {
+        dbtx.insert_new_entry(
+            &MintOutputOutcomeKey(out_point),
+            &self.sign_output(amount, blind_nonce)?,
+        )
+        .await;
+
+        if dbtx
+            .insert_entry(&BlindNonceKey(blind_nonce), &())
+            .await
+            .is_some()
+        {
+            // TODO: make a consensus rule against this
+            warn!(
+                denomination = %amount,
+                bnonce = ?blind_nonce,
+                "Blind nonce already used, money was burned!"
+            );
+        }
+
+        let fee = self.cfg.consensus.fee_consensus.fee(amount);
+
+        Ok(TransactionItemAmount { amount, fee })
+    }
+
     async fn handle_backup_request(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
@@ -670,14 +844,10 @@ fn calculate_mint_issued_ecash_metrics(
     fee: Amount,
 ) {
     dbtx.on_commit(move || {
-        MINT_INOUT_SATS
-            .with_label_values(&["outgoing"])
-            .observe(amount.sats_f64());
-        MINT_INOUT_FEES_SATS
-            .with_label_values(&["outgoing"])
-            .observe(fee.sats_f64());
-        MINT_ISSUED_ECASH_SATS.observe(amount.sats_f64());
-        MINT_ISSUED_ECASH_FEES_SATS.observe(fee.sats_f64());
+        MINT_INOUT_SATS.observe(&["outgoing"], amount.sats_f64());
+        MINT_INOUT_FEES_SATS.observe(&["outgoing"], fee.sats_f64());
+        MINT_ISSUED_ECASH_SATS.observe(&[], amount.sats_f64());
+        MINT_ISSUED_ECASH_FEES_SATS.observe(&[], fee.sats_f64());
     });
 }
 
@@ -687,14 +857,10 @@ fn calculate_mint_redeemed_ecash_metrics(
     fee: Amount,
 ) {
     dbtx.on_commit(move || {
-        MINT_INOUT_SATS
-            .with_label_values(&["incoming"])
-            .observe(amount.sats_f64());
-        MINT_INOUT_FEES_SATS
-            .with_label_values(&["incoming"])
-            .observe(fee.sats_f64());
-        MINT_REDEEMED_ECASH_SATS.observe(amount.sats_f64());
-        MINT_REDEEMED_ECASH_FEES_SATS.observe(fee.sats_f64());
+        MINT_INOUT_SATS.observe(&["incoming"], amount.sats_f64());
+        MINT_INOUT_FEES_SATS.observe(&["incoming"], fee.sats_f64());
+        MINT_REDEEMED_ECASH_SATS.observe(&[], amount.sats_f64());
+        MINT_REDEEMED_ECASH_FEES_SATS.observe(&[], fee.sats_f64());
     });
 }
 
@@ -763,9 +929,88 @@ pub fn new(cfg: MintConfig) -> Mint {
             cfg: cfg.clone(),
             sec_key: cfg.private.tbs_sks,
             pub_key: aggregate_pub_keys,
+            spent_nonces: RwLock::new(None),
+            #[cfg(feature = "amount-commitments")]
+            amount_commitment_scheme: None,
         }
     }
 
+    /// Verifies outputs hiding their amount with `scheme`, see
+    /// [`MintInit::amount_commitment_scheme`]
+    #[cfg(feature = "amount-commitments")]
+    pub fn with_amount_commitment_scheme(
+        self,
+        scheme: Option<Arc<dyn AmountCommitmentScheme>>,
+    ) -> Mint {
+        Mint {
+            amount_commitment_scheme: scheme,
+            ..self
+        }
+    }
+
+    /// Removes the commitments of the outputs issued since the last audit and
+    /// returns the amount they add up to, so that only the sum of the hidden
+    /// amounts ends up in the audit
+    #[cfg(feature = "amount-commitments")]
+    async fn audit_committed_issuances(&self, dbtx: &mut DatabaseTransaction<'_>) -> Amount {
+        let Some(scheme) = &self.amount_commitment_scheme else {
+            return Amount::ZERO;
+        };
+
+        let (keys, commitments): (Vec<_>, Vec<_>) = dbtx
+            .find_by_prefix(&CommittedIssuanceKeyPrefix)
+            .await
+            .unzip()
+            .await;
+
+        if commitments.is_empty() {
+            return Amount::ZERO;
+        }
+
+        match scheme.audit(&commitments) {
+            Ok(amount) => {
+                for key in keys {
+                    dbtx.remove_entry(&key).await;
+                }
+                amount
+            }
+            Err(err) => {
+                // Keep the commitments to retry with the next audit
+                warn!(target: LOG_MODULE_MINT, %err, "Failed to audit committed issuances");
+                Amount::ZERO
+            }
+        }
+    }
+
+    /// Rebuilds the spent nonce filter from the database, sized for twice the
+    /// number of notes spent so far.
+    ///
+    /// Nonces committed while this runs would be missing from the filter, so it
+    /// must only be called before the module processes any inputs, see
+    /// [`NonceFilter`].
+    async fn load_spent_nonces(&self, db: &Database) {
+        let spent_nonces = db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&NonceKeyPrefix)
+            .await
+            .map(|(NonceKey(nonce), ())| nonce)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut filter = NonceFilter::with_capacity(spent_nonces.len() * 2);
+        for nonce in &spent_nonces {
+            filter.insert(nonce);
+        }
+        info!(
+            target: LOG_MODULE_MINT,
+            num_spent_nonces = spent_nonces.len(),
+            "Loaded spent nonce filter"
+        );
+
+        *self.spent_nonces.write().expect("poisoned") = Some(filter);
+    }
+
     pub fn pub_key(&self) -> HashMap<Amount, AggregatePublicKey> {
         self.pub_key.clone()
     }
@@ -774,16 +1019,27 @@ pub fn pub_key(&self) -> HashMap<Amount, AggregatePublicKey> {
 #[cfg(test)]
 mod test {
     use assert_matches::assert_matches;
+    use fedimint_core::bitcoin::hashes::Hash;
     use fedimint_core::config::{
         ClientModuleConfig, ConfigGenModuleParams, EmptyGenParams, ServerModuleConfig,
     };
     use fedimint_core::db::mem_impl::MemDatabase;
     use fedimint_core::db::Database;
     use fedimint_core::module::registry::ModuleRegistry;
-    use fedimint_core::module::{ModuleConsensusVersion, ServerModuleInit};
-    use fedimint_core::{secp256k1, Amount, PeerId, ServerModule};
+    use fedimint_core::module::{IServerModuleInit, ModuleConsensusVersion, ServerModuleInit};
+    use fedimint_core::task::TaskGroup;
+    use fedimint_core::{
+        secp256k1, Amount, NumPeers, OutPoint, PeerId, ServerModule, TransactionId,
+    };
+    use fedimint_mint_common::amount_commitment::MintOutputAmountCommitment;
     use fedimint_mint_common::config::FeeConsensus;
-    use fedimint_mint_common::{MintInput, Nonce, Note};
+    use fedimint_mint_common::{
+        BlindNonce, MintInput, MintInputError, MintOutput, MintOutputError, Nonce, Note,
+    };
+    use fedimint_server::consensus::snapshot::{
+        apply_state_snapshot, snapshot_path, write_state_snapshot, ConsensusKeyPrefixes,
+    };
+    use fedimint_testing_core::test_dir;
     use tbs::blind_message;
 
     use crate::common::config::MintGenParamsConsensus;
@@ -796,7 +1052,7 @@ mod test {
 
     fn build_configs() -> (Vec<ServerModuleConfig>, ClientModuleConfig) {
         let peers = (0..MINTS).map(PeerId::from).collect::<Vec<_>>();
-        let mint_cfg = MintInit.trusted_dealer_gen(
+        let mint_cfg = MintInit::default().trusted_dealer_gen(
             &peers,
             &ConfigGenModuleParams::from_typed(MintGenParams {
                 local: EmptyGenParams::default(),
@@ -811,7 +1067,7 @@ fn build_configs() -> (Vec<ServerModuleConfig>, ClientModuleConfig) {
             0,
             MintInit::kind(),
             ModuleConsensusVersion::new(0, 0),
-            MintInit
+            MintInit::default()
                 .get_client_config(&mint_cfg[&PeerId::from(0)].consensus)
                 .unwrap(),
         )
@@ -911,4 +1167,319 @@ async fn test_detect_double_spends() {
             Err(_)
         );
     }
+
+    #[test_log::test(tokio::test)]
+    async fn test_detect_double_spends_before_nonce_filter_is_loaded() {
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+        let (_, tiered) = mint
+            .cfg
+            .consensus
+            .peer_tbs_pks
+            .first_key_value()
+            .expect("mint has peers");
+        let highest_denomination = *tiered.max_tier();
+        let (_, note) = issue_note(&mint_server_cfg, highest_denomination);
+        let input = MintInput::new_v0(highest_denomination, note);
+
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction().await;
+        mint.process_input(
+            &mut dbtx.to_ref_with_prefix_module_id(42).0.into_nc(),
+            &input,
+        )
+        .await
+        .expect("Spend of valid e-cash works");
+        dbtx.commit_tx().await;
+
+        // A mint whose filter was never loaded from the database has to look up
+        // every nonce instead of treating it as never spent
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+        let mut dbtx = db.begin_transaction_nc().await;
+        assert_matches!(
+            mint.process_input(
+                &mut dbtx.to_ref_with_prefix_module_id(42).0.into_nc(),
+                &input,
+            )
+            .await,
+            Err(MintInputError::SpentCoin)
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_detect_double_spends_after_snapshot_sync() {
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+        let (_, tiered) = mint
+            .cfg
+            .consensus
+            .peer_tbs_pks
+            .first_key_value()
+            .expect("mint has peers");
+        let highest_denomination = *tiered.max_tier();
+        let (_, note) = issue_note(&mint_server_cfg, highest_denomination);
+        let input = MintInput::new_v0(highest_denomination, note);
+
+        // The note is spent in a session the recovering guardian syncs from a snapshot
+        let prefixes = ConsensusKeyPrefixes::new([(
+            0,
+            ServerModuleInit::consensus_db_prefixes(&MintInit::default())
+                .expect("mint supports snapshots"),
+        )]);
+        let source = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = source.begin_transaction().await;
+        mint.process_input(
+            &mut dbtx.to_ref_with_prefix_module_id(0).0.into_nc(),
+            &input,
+        )
+        .await
+        .expect("Spend of valid e-cash works");
+        dbtx.commit_tx().await;
+        let (dir, _guard) = test_dir("test_detect_double_spends_after_snapshot_sync");
+        let info = write_state_snapshot(&mut source.begin_transaction_nc().await, &prefixes, &dir)
+            .await
+            .unwrap();
+
+        let target = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        apply_state_snapshot(
+            &target,
+            &prefixes,
+            &snapshot_path(&dir, info.session_count),
+            info.session_count,
+        )
+        .await
+        .unwrap();
+
+        let module = IServerModuleInit::init(
+            &MintInit::default(),
+            NumPeers::from(usize::from(MINTS)),
+            mint_server_cfg[0].clone(),
+            target.with_prefix_module_id(0).0,
+            &TaskGroup::new(),
+            PeerId::from(0),
+        )
+        .await
+        .unwrap();
+        let mint = module.as_any().downcast_ref::<Mint>().expect("is a mint");
+
+        let mut dbtx = target.begin_transaction_nc().await;
+        assert_matches!(
+            mint.process_input(
+                &mut dbtx.to_ref_with_prefix_module_id(0).0.into_nc(),
+                &input,
+            )
+            .await,
+            Err(MintInputError::SpentCoin)
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_rebuild_output_outcome_after_snapshot_sync() {
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+        let amount = *mint.sec_key.tiers().next().expect("Mint has denominations");
+        let output = MintOutput::new_v0(
+            amount,
+            BlindNonce(blind_message(
+                Nonce(
+                    secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng())
+                        .public_key(),
+                )
+                .to_message(),
+                tbs::BlindingKey::random(),
+            )),
+        );
+        let out_point = OutPoint {
+            txid: TransactionId::all_zeros(),
+            out_idx: 0,
+        };
+
+        let prefixes = ConsensusKeyPrefixes::new([(
+            0,
+            ServerModuleInit::consensus_db_prefixes(&MintInit::default())
+                .expect("mint supports snapshots"),
+        )]);
+        let source = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = source.begin_transaction().await;
+        mint.process_output(
+            &mut dbtx.to_ref_with_prefix_module_id(0).0.into_nc(),
+            &output,
+            out_point,
+        )
+        .await
+        .expect("Output is valid");
+        dbtx.commit_tx().await;
+        let issued = mint
+            .output_status(
+                &mut source
+                    .begin_transaction_nc()
+                    .await
+                    .to_ref_with_prefix_module_id(0)
+                    .0
+                    .into_nc(),
+                out_point,
+            )
+            .await
+            .expect("Output was issued");
+
+        let (dir, _guard) = test_dir("test_rebuild_output_outcome_after_snapshot_sync");
+        let info = write_state_snapshot(&mut source.begin_transaction_nc().await, &prefixes, &dir)
+            .await
+            .unwrap();
+        let target = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        apply_state_snapshot(
+            &target,
+            &prefixes,
+            &snapshot_path(&dir, info.session_count),
+            info.session_count,
+        )
+        .await
+        .unwrap();
+
+        // Our signature share is not part of the snapshot, but can be recreated
+        let mut dbtx = target.begin_transaction_nc().await;
+        let mut module_dbtx = dbtx.to_ref_with_prefix_module_id(0).0.into_nc();
+        assert_eq!(mint.output_status(&mut module_dbtx, out_point).await, None);
+        mint.rebuild_output_outcome(&mut module_dbtx, &output, out_point)
+            .await
+            .unwrap();
+        assert_eq!(
+            mint.output_status(&mut module_dbtx, out_point).await,
+            Some(issued)
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_reject_amount_commitment_without_scheme() {
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+
+        let committed = MintOutputAmountCommitment {
+            commitment: vec![1; 33],
+            range_proof: vec![2; 64],
+            blind_nonce: BlindNonce(blind_message(
+                Nonce(
+                    secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng())
+                        .public_key(),
+                )
+                .to_message(),
+                tbs::BlindingKey::random(),
+            )),
+        };
+        let output = MintOutput::new_amount_commitment(&committed);
+        assert_eq!(output.maybe_amount_commitment(), Some(committed));
+
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction_nc().await;
+        assert_matches!(
+            mint.process_output(
+                &mut dbtx.to_ref_with_prefix_module_id(42).0.into_nc(),
+                &output,
+                OutPoint {
+                    txid: TransactionId::all_zeros(),
+                    out_idx: 0,
+                },
+            )
+            .await,
+            Err(MintOutputError::UnknownOutputVariant(_))
+        );
+    }
+
+    /// Commits to amounts in the clear, just to test the plumbing
+    #[cfg(feature = "amount-commitments")]
+    #[derive(Debug)]
+    struct PlaintextCommitmentScheme;
+
+    #[cfg(feature = "amount-commitments")]
+    impl fedimint_mint_common::amount_commitment::AmountCommitmentScheme for PlaintextCommitmentScheme {
+        fn verify(
+            &self,
+            output: &MintOutputAmountCommitment,
+            denominations: &[Amount],
+        ) -> Result<Amount, String> {
+            let amount = self.audit(&[output.commitment.clone()])?;
+            if denominations.contains(&amount) {
+                Ok(amount)
+            } else {
+                Err(format!("{amount} is not a denomination"))
+            }
+        }
+
+        fn audit(&self, commitments: &[Vec<u8>]) -> Result<Amount, String> {
+            commitments
+                .iter()
+                .map(|commitment| {
+                    let msats = commitment
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| "Invalid commitment".to_owned())?;
+                    Ok(Amount::from_msats(u64::from_be_bytes(msats)))
+                })
+                .sum()
+        }
+    }
+
+    #[cfg(feature = "amount-commitments")]
+    #[test_log::test(tokio::test)]
+    async fn test_audit_only_sees_sum_of_committed_amounts() {
+        use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
+        use fedimint_core::module::audit::Audit;
+        use futures::StreamExt;
+
+        use crate::db::{CommittedIssuanceKey, CommittedIssuanceKeyPrefix, MintAuditItemKey};
+
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap())
+            .with_amount_commitment_scheme(Some(std::sync::Arc::new(PlaintextCommitmentScheme)));
+        let amount = *mint.sec_key.tiers().next().expect("Mint has denominations");
+
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction_nc().await;
+        let mut module_dbtx = dbtx.to_ref_with_prefix_module_id(42).0.into_nc();
+        for out_idx in 0..2 {
+            let output = MintOutput::new_amount_commitment(&MintOutputAmountCommitment {
+                commitment: amount.msats.to_be_bytes().to_vec(),
+                range_proof: vec![],
+                blind_nonce: BlindNonce(blind_message(
+                    Nonce(
+                        secp256k1::Keypair::new(secp256k1::SECP256K1, &mut rand::thread_rng())
+                            .public_key(),
+                    )
+                    .to_message(),
+                    tbs::BlindingKey::random(),
+                )),
+            });
+            let out_point = OutPoint {
+                txid: TransactionId::all_zeros(),
+                out_idx,
+            };
+
+            let item = mint
+                .process_output(&mut module_dbtx, &output, out_point)
+                .await
+                .expect("Valid commitment is accepted");
+            assert_eq!(item.amount, amount);
+            assert!(module_dbtx
+                .get_value(&MintAuditItemKey::Issuance(out_point))
+                .await
+                .is_none());
+            assert!(module_dbtx
+                .get_value(&CommittedIssuanceKey(out_point))
+                .await
+                .is_some());
+        }
+
+        let mut audit = Audit::default();
+        mint.audit(&mut module_dbtx, &mut audit, 42).await;
+        assert_eq!(
+            audit.net_assets().expect("No overflow").milli_sat,
+            -((amount.msats * 2) as i64)
+        );
+        assert!(module_dbtx
+            .find_by_prefix(&CommittedIssuanceKeyPrefix)
+            .await
+            .collect::<Vec<_>>()
+            .await
+            .is_empty());
+    }
 }
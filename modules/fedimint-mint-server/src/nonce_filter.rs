@@ -0,0 +1,133 @@
+use fedimint_mint_common::Nonce;
+
+/// Target false positive rate of the filter while it holds at most its
+/// capacity of nonces
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Number of bit positions set per nonce, optimal for a 1% false positive rate
+const NUM_HASHES: u64 = 7;
+
+/// Capacity the filter is sized for even if few notes were spent so far, so it
+/// doesn't degrade right after the federation starts
+const MIN_CAPACITY: usize = 1_000_000;
+
+/// Bloom filter over the spent note nonces, used to skip the database lookup
+/// when spending a note that was definitely never spent before.
+///
+/// The filter may only ever return false positives, so it must contain every
+/// nonce that is present in the database. Nonces are added before they are
+/// written to the database and never removed; a nonce whose transaction ends up
+/// being rejected just becomes another false positive.
+///
+/// This relies on the following ordering, any nonce written to the database
+/// outside of it would be missing from the filter and could be spent again:
+/// - the filter is loaded from the database on module init, after a state
+///   snapshot was synced and before any input is processed
+/// - afterwards nonces only reach the database through `process_input`, which
+///   inserts them into the filter first
+///
+/// Until the filter is loaded the mint looks up every nonce in the database.
+///
+/// Once more nonces than the capacity were inserted the false positive rate
+/// slowly rises, which only costs performance. The filter is re-sized from the
+/// database on the next restart.
+#[derive(Debug)]
+pub struct NonceFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+}
+
+impl NonceFilter {
+    /// Creates an empty filter sized for the given number of spent nonces
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(MIN_CAPACITY) as f64;
+        let num_bits =
+            (-capacity * FALSE_POSITIVE_RATE.ln() / std::f64::consts::LN_2.powi(2)).ceil() as u64;
+        let num_words = num_bits.div_ceil(64);
+
+        Self {
+            bits: vec![0; num_words as usize],
+            num_bits: num_words * 64,
+        }
+    }
+
+    /// Adds the nonce to the filter, returns `true` if it was definitely not
+    /// contained before
+    pub fn insert(&mut self, nonce: &Nonce) -> bool {
+        let mut was_absent = false;
+        for bit in self.bit_indices(nonce) {
+            let (word, mask) = Self::word_and_mask(bit);
+            was_absent |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        was_absent
+    }
+
+    /// Returns `false` if the nonce is definitely not contained, `true` if it
+    /// may be
+    pub fn may_contain(&self, nonce: &Nonce) -> bool {
+        self.bit_indices(nonce).all(|bit| {
+            let (word, mask) = Self::word_and_mask(bit);
+            self.bits[word] & mask != 0
+        })
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn word_and_mask(bit: u64) -> (usize, u64) {
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    /// Derives the bit positions of a nonce using double hashing. Nonces are
+    /// public keys chosen by the users, so their x-coordinate already is
+    /// uniformly distributed. Users grinding nonces to collide can only cause
+    /// false positives, which just fall back to the database lookup.
+    fn bit_indices(&self, nonce: &Nonce) -> impl Iterator<Item = u64> + '_ {
+        let bytes = nonce.0.serialize();
+        let h1 = u64::from_le_bytes(bytes[1..9].try_into().expect("33 byte public key"));
+        let h2 = u64::from_le_bytes(bytes[9..17].try_into().expect("33 byte public key")) | 1;
+
+        (0..NUM_HASHES).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::secp256k1::{Keypair, SECP256K1};
+    use fedimint_mint_common::Nonce;
+
+    use super::NonceFilter;
+
+    fn random_nonce() -> Nonce {
+        Nonce(Keypair::new(SECP256K1, &mut rand::thread_rng()).public_key())
+    }
+
+    #[test]
+    fn nonce_filter_has_no_false_negatives() {
+        let mut filter = NonceFilter::with_capacity(0);
+        let nonces = (0..1000).map(|_| random_nonce()).collect::<Vec<_>>();
+
+        for nonce in &nonces {
+            assert!(filter.insert(nonce));
+        }
+        for nonce in &nonces {
+            assert!(filter.may_contain(nonce));
+            assert!(!filter.insert(nonce));
+        }
+    }
+
+    #[test]
+    fn nonce_filter_rejects_unknown_nonces() {
+        let mut filter = NonceFilter::with_capacity(0);
+        for _ in 0..1000 {
+            filter.insert(&random_nonce());
+        }
+
+        let false_positives = (0..1000)
+            .filter(|_| filter.may_contain(&random_nonce()))
+            .count();
+        assert!(false_positives < 10, "{false_positives} false positives");
+    }
+}
@@ -1,14 +1,17 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Cursor;
 use std::time::Duration;
 
 use bls12_381::G1Affine;
 use fedimint_client::backup::{ClientBackup, Metadata};
+use fedimint_client::event_bus::{ClientEvent, ClientEventEnvelope};
 use fedimint_client::transaction::{ClientInput, ClientInputBundle, TransactionBuilder};
 use fedimint_core::config::EmptyGenParams;
 use fedimint_core::core::OperationId;
+use fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
 use fedimint_core::task::sleep_in_test;
 use fedimint_core::util::NextOrPending;
-use fedimint_core::{sats, secp256k1, Amount, TieredMulti};
+use fedimint_core::{sats, secp256k1, Amount, PeerId, TieredMulti};
 use fedimint_dummy_client::{DummyClientInit, DummyClientModule};
 use fedimint_dummy_common::config::DummyGenParams;
 use fedimint_dummy_server::DummyInit;
@@ -21,7 +24,11 @@
 use fedimint_mint_common::config::{FeeConsensus, MintGenParams, MintGenParamsConsensus};
 use fedimint_mint_common::{MintInput, MintInputV0, Nonce};
 use fedimint_mint_server::MintInit;
+use fedimint_server::consensus::db::AcceptedItemPrefix;
+use fedimint_server::consensus::snapshot::{write_state_snapshot, ConsensusKeyPrefixes};
 use fedimint_testing::fixtures::{Fixtures, TIMEOUT};
+use fedimint_testing_core::test_dir;
+use futures::stream::BoxStream;
 use futures::StreamExt;
 use secp256k1::Keypair;
 use serde::{Deserialize, Serialize};
@@ -32,7 +39,7 @@
 fn fixtures() -> Fixtures {
     let fixtures = Fixtures::new_primary(
         MintClientInit,
-        MintInit,
+        MintInit::default(),
         MintGenParams {
             consensus: MintGenParamsConsensus::new(
                 2,
@@ -99,6 +106,30 @@ async fn transaction_with_invalid_signature_is_rejected() -> anyhow::Result<()>
     Ok(())
 }
 
+/// Follows the updates of a reissue from the transaction being accepted until
+/// every new note is stored
+async fn assert_reissue_completes(
+    sub: &mut BoxStream<'static, ReissueExternalNotesState>,
+) -> anyhow::Result<()> {
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Issuing);
+    let update = sub.ok().await?;
+    let ReissueExternalNotesState::CollectingSignatures { notes } = update else {
+        panic!("Expected signature collection, got {update:?}");
+    };
+    assert!(0 < notes);
+    for stored in 1..=notes {
+        assert_eq!(
+            sub.ok().await?,
+            ReissueExternalNotesState::NotesStored {
+                stored,
+                total: notes
+            }
+        );
+    }
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Done);
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
     // Print notes for client1
@@ -115,6 +146,7 @@ async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
     let (op, notes) = client1_mint
         .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(750), TIMEOUT, false, ())
         .await?;
+    let num_notes = notes.notes().count_items();
     let sub1 = &mut client1_mint.subscribe_spend_notes(op).await?.into_stream();
     assert_eq!(sub1.ok().await?, SpendOOBState::Created);
 
@@ -124,10 +156,12 @@ async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
     let mut sub2 = sub2.into_stream();
     info!("### SUB2: WAIT CREATED");
     assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Created);
-    info!("### SUB2: WAIT ISSUING");
-    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Issuing);
+    assert_eq!(
+        sub2.ok().await?,
+        ReissueExternalNotesState::InputsSubmitted { notes: num_notes }
+    );
     info!("### SUB2: WAIT DONE");
-    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Done);
+    assert_reissue_completes(&mut sub2).await?;
     info!("### SUB1: WAIT SUCCESS");
     assert_eq!(sub1.ok().await?, SpendOOBState::Success);
     info!("### REISSUE: DONE");
@@ -137,6 +171,95 @@ async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn reissuing_ecash_publishes_note_received() -> anyhow::Result<()> {
+    let fed = fixtures().new_default_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let client1_dummy_module = client1.get_first_module::<DummyClientModule>()?;
+    let (op, outpoint) = client1_dummy_module.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let client1_mint = client1.get_first_module::<MintClientModule>()?;
+    let client2_mint = client2.get_first_module::<MintClientModule>()?;
+    let (_, notes) = client1_mint
+        .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(750), TIMEOUT, false, ())
+        .await?;
+    let amount = notes.total_amount();
+
+    let mut client1_events = client1.subscribe_client_events();
+    let mut client2_events = client2.subscribe_client_events();
+    let op = client2_mint.reissue_external_notes(notes, ()).await?;
+
+    let event = fedimint_core::runtime::timeout(TIMEOUT, client2_events.recv()).await??;
+    assert_eq!(
+        event,
+        ClientEventEnvelope {
+            module_instance_id: client2_mint.id,
+            event: ClientEvent::NoteReceived {
+                operation_id: op,
+                amount,
+            },
+        }
+    );
+    // Events are only published on the bus of the client they happened in
+    assert!(client1_events.try_recv().is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn imports_notes_skipping_duplicates() -> anyhow::Result<()> {
+    let fed = fixtures().new_default_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let client1_dummy_module = client1.get_first_module::<DummyClientModule>()?;
+    let (op, outpoint) = client1_dummy_module.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let client1_mint = client1.get_first_module::<MintClientModule>()?;
+    let client2_mint = client2.get_first_module::<MintClientModule>()?;
+    let (_, notes) = client1_mint
+        .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(750), TIMEOUT, false, ())
+        .await?;
+    let num_notes = notes.notes().count_items();
+
+    // Every note is passed twice, the copies must be skipped
+    let notes_with_duplicates = notes
+        .notes()
+        .iter_items()
+        .chain(notes.notes().iter_items())
+        .map(|(amount, note)| (amount, *note))
+        .collect::<TieredMulti<_>>();
+    let summary = client2_mint.import_notes(notes_with_duplicates, ()).await?;
+    assert_eq!(summary.imported, notes.total_amount());
+    assert_eq!(summary.skipped_duplicates, num_notes);
+    assert_eq!(summary.skipped_invalid, 0);
+
+    let op = summary.operation_id.expect("Notes to import");
+    let mut sub = client2_mint
+        .subscribe_reissue_external_notes(op)
+        .await?
+        .into_stream();
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Created);
+    assert_eq!(
+        sub.ok().await?,
+        ReissueExternalNotesState::InputsSubmitted { notes: num_notes }
+    );
+    assert_reissue_completes(&mut sub).await?;
+    assert!(client2.get_balance().await >= sats(750).saturating_sub(EXPECTED_MAXIMUM_FEE));
+
+    // The notes were spent by the reissue, so nothing is left to import
+    let client3 = fed.new_client().await;
+    let summary = client3
+        .get_first_module::<MintClientModule>()?
+        .import_notes(notes.notes().clone(), ())
+        .await?;
+    assert_eq!(summary.operation_id, None);
+    assert_eq!(summary.imported, Amount::ZERO);
+    assert_eq!(summary.skipped_spent, num_notes);
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn blind_nonce_index() -> anyhow::Result<()> {
     // Print notes for client1
@@ -268,9 +391,11 @@ async fn sends_ecash_oob_highly_parallel() -> anyhow::Result<()> {
                 let mut sub2 = sub2.into_stream();
                 assert_eq!(sub2.ok().await.unwrap(), ReissueExternalNotesState::Created);
                 info!("Reissuance {num_reissue} created");
-                assert_eq!(sub2.ok().await.unwrap(), ReissueExternalNotesState::Issuing);
-                info!("Reissuance {num_reissue} accepted");
-                assert_eq!(sub2.ok().await.unwrap(), ReissueExternalNotesState::Done);
+                assert!(matches!(
+                    sub2.ok().await.unwrap(),
+                    ReissueExternalNotesState::InputsSubmitted { .. }
+                ));
+                assert_reissue_completes(&mut sub2).await.unwrap();
                 info!("Reissuance {num_reissue} finished");
             },
         ));
@@ -424,10 +549,12 @@ async fn sends_ecash_out_of_band_cancel_partial() -> anyhow::Result<()> {
     let mut sub2 = sub2.into_stream();
     info!("### SUB2: WAIT CREATED");
     assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Created);
-    info!("### SUB2: WAIT ISSUING");
-    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Issuing);
+    assert_eq!(
+        sub2.ok().await?,
+        ReissueExternalNotesState::InputsSubmitted { notes: 1 }
+    );
     info!("### SUB2: WAIT DONE");
-    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Done);
+    assert_reissue_completes(&mut sub2).await?;
     info!("### REISSUE: DONE");
 
     info!("### CANCEL NOTES");
@@ -503,6 +630,86 @@ async fn error_zero_value_oob_receive() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn guardians_agree_on_state_snapshots() -> anyhow::Result<()> {
+    let fed = fixtures().new_default_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let client1_dummy_module = client1.get_first_module::<DummyClientModule>()?;
+    let (op, outpoint) = client1_dummy_module.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    // Issue and spend notes, so the guardians' signature shares end up in their
+    // databases
+    let client1_mint = client1.get_first_module::<MintClientModule>()?;
+    let client2_mint = client2.get_first_module::<MintClientModule>()?;
+    let (_, notes) = client1_mint
+        .spend_notes_with_selector(&SelectNotesWithAtleastAmount, sats(750), TIMEOUT, false, ())
+        .await?;
+    let num_notes = notes.notes().count_items();
+    let op = client2_mint.reissue_external_notes(notes, ()).await?;
+    let mut sub = client2_mint
+        .subscribe_reissue_external_notes(op)
+        .await?
+        .into_stream();
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Created);
+    assert_eq!(
+        sub.ok().await?,
+        ReissueExternalNotesState::InputsSubmitted { notes: num_notes }
+    );
+    assert_reissue_completes(&mut sub).await?;
+
+    let prefixes =
+        ConsensusKeyPrefixes::from_config(fed.server_config(PeerId::from(0)), fed.server_init())?
+            .expect("All modules support state snapshots");
+    let (dir, _guard) = test_dir("guardians_agree_on_state_snapshots");
+
+    // The guardians complete sessions independently, so we retry until we catch
+    // all of them between the same two sessions
+    for _ in 0..120 {
+        let mut infos = BTreeMap::new();
+
+        for (peer, db) in fed.server_dbs() {
+            let mut dbtx = db.begin_transaction_nc().await;
+
+            if dbtx
+                .find_by_prefix(&AcceptedItemPrefix)
+                .await
+                .next()
+                .await
+                .is_some()
+            {
+                break;
+            }
+
+            let info =
+                write_state_snapshot(&mut dbtx, &prefixes, &dir.join(peer.to_string())).await?;
+            infos.insert(*peer, info);
+        }
+
+        let session_counts = infos
+            .values()
+            .map(|info| info.session_count)
+            .collect::<BTreeSet<_>>();
+
+        if infos.len() == fed.server_dbs().len() && session_counts.len() == 1 {
+            let hashes = infos
+                .values()
+                .map(|info| info.hash)
+                .collect::<BTreeSet<_>>();
+            assert_eq!(hashes.len(), 1, "Guardians disagree on snapshot: {infos:?}");
+            return Ok(());
+        }
+
+        sleep_in_test(
+            "waiting for guardians to complete the same session",
+            Duration::from_millis(500),
+        )
+        .await;
+    }
+
+    panic!("Guardians never completed the same session at the same time");
+}
+
 #[cfg(test)]
 mod fedimint_migration_tests {
     use std::collections::BTreeMap;
@@ -722,7 +929,7 @@ async fn snapshot_server_db_migrations() -> anyhow::Result<()> {
     async fn test_server_db_migrations() -> anyhow::Result<()> {
         let _ = TracingSetup::default().init();
 
-        let module = DynServerModuleInit::from(MintInit);
+        let module = DynServerModuleInit::from(MintInit::default());
         validate_migrations_server(module, "mint-server", |db| async move {
             let mut dbtx = db.begin_transaction_nc().await;
 
@@ -784,6 +991,10 @@ async fn test_server_db_migrations() -> anyhow::Result<()> {
                         // Would require an entire re-design of the way we test
                         // here, manually testing instead for now
                     }
+                    DbKeyPrefix::CommittedIssuance => {
+                        // Only written by federations verifying amount
+                        // commitments, which the v0 data doesn't contain
+                    }
                 }
             }
 
@@ -870,7 +1081,8 @@ async fn test_client_db_migrations() -> anyhow::Result<()> {
                             );
                             info!("Validated RecoveryFinalized");
                         }
-                        fedimint_mint_client::client_db::DbKeyPrefix::ReusedNoteIndices => {}
+                        fedimint_mint_client::client_db::DbKeyPrefix::ReusedNoteIndices
+                        | fedimint_mint_client::client_db::DbKeyPrefix::PendingOOBNote => {}
                         fedimint_mint_client::client_db::DbKeyPrefix::ExternalReservedStart
                         | fedimint_mint_client::client_db::DbKeyPrefix::CoreInternalReservedEnd
                         | fedimint_mint_client::client_db::DbKeyPrefix::CoreInternalReservedStart =>
@@ -137,6 +137,10 @@ fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn>
         migrations.insert(DatabaseVersion(0), |_| Box::pin(async { Ok(()) }));
         migrations
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        Some(vec![])
+    }
 }
 
 /// Unknown module
@@ -6,9 +6,12 @@
 use fedimint_core::{apply, async_trait_maybe_send, PeerId};
 use fedimint_wallet_common::endpoint_constants::{
     BITCOIN_KIND_ENDPOINT, BITCOIN_RPC_CONFIG_ENDPOINT, BLOCK_COUNT_ENDPOINT,
-    MODULE_CONSENSUS_VERSION_ENDPOINT, PEG_OUT_FEES_ENDPOINT, WALLET_SUMMARY_ENDPOINT,
+    MODULE_CONSENSUS_VERSION_ENDPOINT, PEG_IN_REORG_ALERTS_ENDPOINT, PEG_OUT_FEES_ENDPOINT,
+    PEG_OUT_SIGNING_SESSIONS_ENDPOINT, WALLET_SUMMARY_ENDPOINT,
+};
+use fedimint_wallet_common::{
+    PegInReorgAlert, PegOutFees, PegOutSigningSessionStatus, WalletSummary,
 };
-use fedimint_wallet_common::{PegOutFees, WalletSummary};
 
 #[apply(async_trait_maybe_send!)]
 pub trait WalletFederationApi {
@@ -27,6 +30,16 @@ async fn fetch_peg_out_fees(
     async fn fetch_bitcoin_rpc_config(&self, auth: ApiAuth) -> FederationResult<BitcoinRpcConfig>;
 
     async fn fetch_wallet_summary(&self) -> FederationResult<WalletSummary>;
+
+    async fn fetch_peg_out_signing_sessions(
+        &self,
+        auth: ApiAuth,
+    ) -> FederationResult<Vec<PegOutSigningSessionStatus>>;
+
+    async fn fetch_peg_in_reorg_alerts(
+        &self,
+        auth: ApiAuth,
+    ) -> FederationResult<Vec<PegInReorgAlert>>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -98,4 +111,28 @@ async fn fetch_wallet_summary(&self) -> FederationResult<WalletSummary> {
         )
         .await
     }
+
+    async fn fetch_peg_out_signing_sessions(
+        &self,
+        auth: ApiAuth,
+    ) -> FederationResult<Vec<PegOutSigningSessionStatus>> {
+        self.request_admin(
+            PEG_OUT_SIGNING_SESSIONS_ENDPOINT,
+            ApiRequestErased::default(),
+            auth,
+        )
+        .await
+    }
+
+    async fn fetch_peg_in_reorg_alerts(
+        &self,
+        auth: ApiAuth,
+    ) -> FederationResult<Vec<PegInReorgAlert>> {
+        self.request_admin(
+            PEG_IN_REORG_ALERTS_ENDPOINT,
+            ApiRequestErased::default(),
+            auth,
+        )
+        .await
+    }
 }
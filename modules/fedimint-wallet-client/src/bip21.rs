@@ -0,0 +1,142 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::{Address, Denomination};
+
+const BIP21_SCHEME: &str = "bitcoin:";
+
+/// A [BIP-21](https://github.com/bitcoin/bips/blob/master/bip-0021.mediawiki)
+/// `bitcoin:` payment URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bip21Uri {
+    pub address: Address<NetworkUnchecked>,
+    /// Amount the recipient requests
+    pub amount: Option<bitcoin::Amount>,
+    /// Label for the recipient, e.g. the name of the merchant
+    pub label: Option<String>,
+    /// Message describing the payment
+    pub message: Option<String>,
+}
+
+impl Bip21Uri {
+    /// Returns true if `s` looks like a BIP-21 URI rather than a plain address
+    pub fn is_uri(s: &str) -> bool {
+        s.get(..BIP21_SCHEME.len())
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case(BIP21_SCHEME))
+    }
+}
+
+impl From<Address<NetworkUnchecked>> for Bip21Uri {
+    fn from(address: Address<NetworkUnchecked>) -> Self {
+        Self {
+            address,
+            amount: None,
+            label: None,
+            message: None,
+        }
+    }
+}
+
+impl FromStr for Bip21Uri {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !Self::is_uri(s) {
+            bail!("Not a bitcoin: URI");
+        }
+
+        let (address, query) = s[BIP21_SCHEME.len()..]
+            .split_once('?')
+            .unwrap_or((&s[BIP21_SCHEME.len()..], ""));
+
+        let mut uri = Bip21Uri {
+            address: address.parse().context("Invalid address in URI")?,
+            amount: None,
+            label: None,
+            message: None,
+        };
+
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode(value)?;
+
+            match key {
+                "amount" => {
+                    let amount = bitcoin::Amount::from_str_in(&value, Denomination::Bitcoin)
+                        .with_context(|| format!("Invalid amount {value} in URI"))?;
+                    if uri.amount.replace(amount).is_some() {
+                        bail!("Multiple amounts in URI");
+                    }
+                }
+                "label" => uri.label = Some(value),
+                "message" => uri.message = Some(value),
+                // Clients must reject URIs with required parameters they don't understand
+                key if key.starts_with("req-") => bail!("Unsupported required parameter {key}"),
+                _ => {}
+            }
+        }
+
+        Ok(uri)
+    }
+}
+
+fn percent_decode(s: &str) -> anyhow::Result<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.bytes();
+
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let hex = [
+                chars.next().context("Incomplete percent encoding")?,
+                chars.next().context("Incomplete percent encoding")?,
+            ];
+            let hex = std::str::from_utf8(&hex).context("Invalid percent encoding")?;
+            bytes.push(u8::from_str_radix(hex, 16).context("Invalid percent encoding")?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).context("Percent encoded value is not UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Bip21Uri;
+
+    const ADDRESS: &str = "bcrt1qsdrpnua6ea3spqxs42ecd6sq8zkvn6jgcl3y3v";
+
+    #[test]
+    fn parse_uri() {
+        let uri = Bip21Uri::from_str(&format!(
+            "BITCOIN:{ADDRESS}?amount=0.0005&label=Luke%20Jr&foo=bar"
+        ))
+        .expect("valid uri");
+
+        assert_eq!(uri.address, ADDRESS.parse().unwrap());
+        assert_eq!(uri.amount, Some(bitcoin::Amount::from_sat(50_000)));
+        assert_eq!(uri.label.as_deref(), Some("Luke Jr"));
+        assert_eq!(uri.message, None);
+
+        let uri = Bip21Uri::from_str(&format!("bitcoin:{ADDRESS}")).expect("valid uri");
+        assert_eq!(uri.amount, None);
+        assert_eq!(uri.label, None);
+    }
+
+    #[test]
+    fn reject_invalid_uri() {
+        for uri in [
+            ADDRESS.to_string(),
+            "bitcoin:notanaddress".to_string(),
+            format!("bitcoin:{ADDRESS}?amount=1,5"),
+            format!("bitcoin:{ADDRESS}?amount=1&amount=2"),
+            format!("bitcoin:{ADDRESS}?req-somethingyoudontunderstand=50"),
+            format!("bitcoin:{ADDRESS}?label=%2"),
+        ] {
+            assert!(Bip21Uri::from_str(&uri).is_err(), "{uri} should be invalid");
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{bail, ensure, Context};
+use base64::Engine as _;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::opcodes::all::OP_RETURN;
+use bitcoin::opcodes::OP_0;
+use bitcoin::secp256k1::{Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::{
+    absolute, transaction, Address, Amount, CompressedPublicKey, OutPoint, Script, ScriptBuf,
+    Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use serde::{Deserialize, Serialize};
+
+const MESSAGE_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// A [BIP-322](https://github.com/bitcoin/bips/blob/master/bip-0322.mediawiki)
+/// signature of `message` proving control over an address, e.g. to show that
+/// the destination of a withdrawal belongs to the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressOwnershipProof {
+    /// The signed message, usually a challenge chosen by the verifier
+    pub message: String,
+    /// Base64 encoded signature in the BIP-322 "simple" format, the witness
+    /// spending the address
+    pub signature: String,
+}
+
+impl AddressOwnershipProof {
+    /// Verifies that the proof was signed by the key controlling `address`.
+    ///
+    /// Only P2WPKH and P2TR key path spends are supported.
+    pub fn verify(&self, address: &Address) -> anyhow::Result<()> {
+        let witness_bytes = base64::engine::general_purpose::STANDARD
+            .decode(&self.signature)
+            .context("Signature is not valid base64")?;
+        let witness: Witness = bitcoin::consensus::deserialize(&witness_bytes)
+            .context("Signature is not a serialized witness")?;
+
+        let script_pubkey = address.script_pubkey();
+        let to_spend = to_spend(&script_pubkey, self.message.as_bytes());
+        let to_sign = to_sign(to_spend.compute_txid(), witness);
+
+        if script_pubkey.is_p2wpkh() {
+            verify_p2wpkh(&script_pubkey, &to_sign)
+        } else if script_pubkey.is_p2tr() {
+            verify_p2tr(&to_spend.output[0], &to_sign)
+        } else {
+            bail!("Ownership proofs are only supported for P2WPKH and P2TR addresses")
+        }
+    }
+}
+
+fn verify_p2wpkh(script_pubkey: &Script, to_sign: &Transaction) -> anyhow::Result<()> {
+    let witness = &to_sign.input[0].witness;
+    ensure!(
+        witness.len() == 2,
+        "P2WPKH signature must have 2 witness elements"
+    );
+    let signature = bitcoin::ecdsa::Signature::from_slice(&witness[0])?;
+    let public_key = CompressedPublicKey::from_slice(&witness[1])?;
+    ensure!(
+        signature.sighash_type == EcdsaSighashType::All,
+        "Signature must use SIGHASH_ALL"
+    );
+    ensure!(
+        ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash()) == *script_pubkey,
+        "Signature is for a different public key"
+    );
+
+    let sighash = SighashCache::new(to_sign).p2wpkh_signature_hash(
+        0,
+        script_pubkey,
+        Amount::ZERO,
+        signature.sighash_type,
+    )?;
+    Secp256k1::verification_only()
+        .verify_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &signature.signature,
+            &public_key.0,
+        )
+        .context("Invalid signature")
+}
+
+fn verify_p2tr(spent: &TxOut, to_sign: &Transaction) -> anyhow::Result<()> {
+    let witness = &to_sign.input[0].witness;
+    ensure!(
+        witness.len() == 1,
+        "P2TR signature must be a key path spend with 1 witness element"
+    );
+    let signature = bitcoin::taproot::Signature::from_slice(&witness[0])?;
+    ensure!(
+        matches!(
+            signature.sighash_type,
+            TapSighashType::Default | TapSighashType::All
+        ),
+        "Signature must use SIGHASH_DEFAULT or SIGHASH_ALL"
+    );
+    let output_key = XOnlyPublicKey::from_slice(&spent.script_pubkey.as_bytes()[2..])?;
+
+    let sighash = SighashCache::new(to_sign).taproot_key_spend_signature_hash(
+        0,
+        &Prevouts::All(&[spent]),
+        signature.sighash_type,
+    )?;
+    Secp256k1::verification_only()
+        .verify_schnorr(
+            &signature.signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &output_key,
+        )
+        .context("Invalid signature")
+}
+
+fn message_hash(message: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(MESSAGE_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+/// The virtual transaction creating an output of `script_pubkey` committing to
+/// the message
+fn to_spend(script_pubkey: &Script, message: &[u8]) -> Transaction {
+    let script_sig = ScriptBuf::builder()
+        .push_opcode(OP_0)
+        .push_slice(message_hash(message).to_byte_array())
+        .into_script();
+
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0xFFFF_FFFF,
+            },
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.to_owned(),
+        }],
+    }
+}
+
+/// The virtual transaction spending the output of [`to_spend`] with the
+/// signature's `witness`
+fn to_sign(to_spend: Txid, witness: Witness) -> Transaction {
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend,
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::from_bytes(vec![OP_RETURN.to_u8()]),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine as _;
+    use bitcoin::address::NetworkUnchecked;
+    use bitcoin::hashes::Hash;
+    use bitcoin::key::{Keypair, TapTweak};
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+    use bitcoin::{Address, CompressedPublicKey, Network, Witness};
+
+    use super::{message_hash, to_sign, to_spend, AddressOwnershipProof};
+
+    // Test vectors from BIP-322
+    const P2WPKH_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const P2WPKH_HELLO_WORLD_SIGNATURE: &str = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+
+    fn address(s: &str) -> Address {
+        s.parse::<Address<NetworkUnchecked>>()
+            .unwrap()
+            .require_network(Network::Bitcoin)
+            .unwrap()
+    }
+
+    #[test]
+    fn message_hashes() {
+        assert_eq!(
+            message_hash(b"").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash(b"Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn verify_p2wpkh() {
+        let proof = AddressOwnershipProof {
+            message: "Hello World".to_owned(),
+            signature: P2WPKH_HELLO_WORLD_SIGNATURE.to_owned(),
+        };
+        proof.verify(&address(P2WPKH_ADDRESS)).unwrap();
+
+        let wrong_message = AddressOwnershipProof {
+            message: "Hello World!".to_owned(),
+            ..proof.clone()
+        };
+        assert!(wrong_message.verify(&address(P2WPKH_ADDRESS)).is_err());
+
+        let secp = Secp256k1::new();
+        let other_key = SecretKey::from_slice(&[1; 32]).unwrap().public_key(&secp);
+        let other_address = Address::p2wpkh(&CompressedPublicKey(other_key), Network::Bitcoin);
+        assert!(proof.verify(&other_address).is_err());
+    }
+
+    #[test]
+    fn verify_p2tr() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1; 32]).unwrap());
+        let (internal_key, _parity) = keypair.x_only_public_key();
+        let address = Address::p2tr(&secp, internal_key, None, Network::Bitcoin);
+        let message = "Withdraw to this address";
+
+        let to_spend = to_spend(&address.script_pubkey(), message.as_bytes());
+        let unsigned = to_sign(to_spend.compute_txid(), Witness::new());
+        let sighash = SighashCache::new(&unsigned)
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&to_spend.output[0]]),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let signature = bitcoin::taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(
+                &Message::from_digest(sighash.to_byte_array()),
+                &keypair.tap_tweak(&secp, None).to_inner(),
+            ),
+            sighash_type: TapSighashType::Default,
+        };
+        let witness = Witness::from_slice(&[signature.to_vec()]);
+
+        let proof = AddressOwnershipProof {
+            message: message.to_owned(),
+            signature: base64::engine::general_purpose::STANDARD
+                .encode(bitcoin::consensus::serialize(&witness)),
+        };
+        proof.verify(&address).unwrap();
+
+        let wrong_message = AddressOwnershipProof {
+            message: "Withdraw to another address".to_owned(),
+            ..proof
+        };
+        assert!(wrong_message.verify(&address).is_err());
+    }
+}
@@ -15,10 +15,19 @@ enum Opts {
         #[arg(long, default_value = "1")]
         num: usize,
     },
+    /// Returns the confirmations the unclaimed deposits of a deposit
+    /// operation need and how many blocks are still missing
+    DepositConfirmations { operation_id: OperationId },
     /// Returns the Bitcoin RPC kind
     GetBitcoinRpcKind { peer_id: u16 },
     /// Returns the Bitcoin RPC kind and URL, if authenticated
     GetBitcoinRpcConfig,
+    /// Returns which guardians signed the peg-outs still collecting
+    /// signatures, if authenticated
+    GetPegOutSigningSessions,
+    /// Returns the claimed peg-ins whose block was reorged out of the chain of
+    /// the guardian's bitcoin backend, if authenticated
+    GetPegInReorgAlerts,
 }
 
 pub(crate) async fn handle_cli_command(
@@ -34,6 +43,10 @@ pub(crate) async fn handle_cli_command(
                 .await?;
             serde_json::Value::Null
         }
+        Opts::DepositConfirmations { operation_id } => {
+            serde_json::to_value(module.get_deposit_confirmations(operation_id).await?)
+                .expect("JSON serialization failed")
+        }
         Opts::GetBitcoinRpcKind { peer_id } => {
             let kind = module
                 .module_api
@@ -51,6 +64,29 @@ pub(crate) async fn handle_cli_command(
             serde_json::to_value(module.module_api.fetch_bitcoin_rpc_config(auth).await?)
                 .expect("JSON serialization failed")
         }
+        Opts::GetPegOutSigningSessions => {
+            let auth = module
+                .admin_auth
+                .clone()
+                .ok_or(anyhow::anyhow!("Admin auth not set"))?;
+
+            serde_json::to_value(
+                module
+                    .module_api
+                    .fetch_peg_out_signing_sessions(auth)
+                    .await?,
+            )
+            .expect("JSON serialization failed")
+        }
+        Opts::GetPegInReorgAlerts => {
+            let auth = module
+                .admin_auth
+                .clone()
+                .ok_or(anyhow::anyhow!("Admin auth not set"))?;
+
+            serde_json::to_value(module.module_api.fetch_peg_in_reorg_alerts(auth).await?)
+                .expect("JSON serialization failed")
+        }
     };
 
     Ok(res)
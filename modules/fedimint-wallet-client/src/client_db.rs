@@ -18,6 +18,8 @@ pub enum DbKeyPrefix {
     ClaimedPegIn = 0x2e,
     RecoveryFinalized = 0x2f,
     RecoveryState = 0x30,
+    PegInBlock = 0x31,
+    ReorgedPegIn = 0x32,
     /// Prefixes between 0xb0..=0xcf shall all be considered allocated for
     /// historical and future external use
     ExternalReservedStart = 0xb0,
@@ -151,6 +153,48 @@ pub struct ClaimedPegInData {
 );
 impl_db_lookup!(key = ClaimedPegInKey, query_prefix = ClaimedPegInPrefix);
 
+/// Block count of the block a deposit that wasn't claimed yet was last seen
+/// confirmed in, to notice it being reorged out
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegInBlockKey {
+    pub peg_in_index: TweakIdx,
+    pub btc_out_point: bitcoin::OutPoint,
+}
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegInBlockPrefix;
+
+impl_db_record!(
+    key = PegInBlockKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::PegInBlock,
+);
+impl_db_lookup!(key = PegInBlockKey, query_prefix = PegInBlockPrefix);
+
+/// A deposit whose block was reorged out before it was claimed
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReorgedPegInKey {
+    pub peg_in_index: TweakIdx,
+    pub btc_out_point: bitcoin::OutPoint,
+}
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReorgedPegInPrefix;
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReorgedPegInData {
+    /// Block count of the block the deposit was confirmed in before the reorg
+    pub reorged_block_count: u64,
+}
+
+impl_db_record!(
+    key = ReorgedPegInKey,
+    value = ReorgedPegInData,
+    db_prefix = DbKeyPrefix::ReorgedPegIn,
+    notify_on_modify = true,
+);
+impl_db_lookup!(key = ReorgedPegInKey, query_prefix = ReorgedPegInPrefix);
+
 #[derive(Debug, Clone, Encodable, Decodable, Serialize)]
 pub struct RecoveryFinalizedKey;
 
@@ -35,3 +35,26 @@ impl Event for DepositConfirmed {
 
     const KIND: EventKind = EventKind::from_static("deposit-confirmed");
 }
+
+/// Event that is emitted when an onchain deposit that wasn't claimed yet is
+/// reorged out of the block it confirmed in.
+#[derive(Serialize, Deserialize)]
+pub struct DepositReorged {
+    /// The bitcoin transaction ID
+    pub txid: Txid,
+
+    /// The out index of the deposit transaction
+    pub out_idx: u32,
+
+    /// Block count of the block the deposit was confirmed in before the reorg
+    pub reorged_block_count: u64,
+
+    /// Block count of the block the deposit is confirmed in now, if any
+    pub tx_block_count: Option<u64>,
+}
+
+impl Event for DepositReorged {
+    const MODULE: Option<ModuleKind> = Some(fedimint_wallet_common::KIND);
+
+    const KIND: EventKind = EventKind::from_static("deposit-reorged");
+}
@@ -11,6 +11,10 @@
 
 mod backup;
 
+/// Parsing of BIP-21 `bitcoin:` URIs to withdraw to
+pub mod bip21;
+/// Verification of BIP-322 proofs of address ownership
+pub mod bip322;
 pub mod client_db;
 /// Legacy, state-machine based peg-ins, replaced by `pegin_monitor`
 /// but retained for time being to ensure existing peg-ins complete.
@@ -23,11 +27,13 @@
 use std::collections::BTreeMap;
 use std::future;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use anyhow::{anyhow, bail, Context as AnyhowContext};
+use anyhow::{anyhow, bail, ensure, Context as AnyhowContext};
 use async_stream::stream;
 use backup::WalletModuleBackup;
+use bip21::Bip21Uri;
+use bip322::AddressOwnershipProof;
 use bitcoin::address::NetworkUnchecked;
 use bitcoin::secp256k1::{All, Secp256k1, SECP256K1};
 use bitcoin::{Address, Network, ScriptBuf};
@@ -60,14 +66,15 @@
 use fedimint_core::util::backoff_util::background_backoff;
 use fedimint_core::util::{backoff_util, retry};
 use fedimint_core::{
-    apply, async_trait_maybe_send, push_db_pair_items, runtime, secp256k1, Amount, OutPoint,
-    TransactionId,
+    apply, async_trait_maybe_send, push_db_pair_items, runtime, secp256k1, Amount, Feerate,
+    OutPoint, TransactionId,
 };
 use fedimint_logging::LOG_CLIENT_MODULE_WALLET;
 use fedimint_wallet_common::config::{FeeConsensus, WalletClientConfig};
 use fedimint_wallet_common::tweakable::Tweakable;
 pub use fedimint_wallet_common::*;
-use futures::{Stream, StreamExt};
+use futures::future::Either;
+use futures::{pin_mut, Stream, StreamExt};
 use rand::{thread_rng, Rng};
 use secp256k1::Keypair;
 use serde::{Deserialize, Serialize};
@@ -78,10 +85,12 @@
 use crate::api::WalletFederationApi;
 use crate::backup::WalletRecovery;
 use crate::client_db::{
-    ClaimedPegInData, ClaimedPegInKey, ClaimedPegInPrefix, NextPegInTweakIndexKey,
-    PegInTweakIndexData, PegInTweakIndexPrefix, RecoveryFinalizedKey,
+    ClaimedPegInData, ClaimedPegInKey, ClaimedPegInPrefix, NextPegInTweakIndexKey, PegInBlockKey,
+    PegInBlockPrefix, PegInTweakIndexData, PegInTweakIndexPrefix, RecoveryFinalizedKey,
+    ReorgedPegInData, ReorgedPegInKey, ReorgedPegInPrefix,
 };
 use crate::deposit::DepositStateMachine;
+use crate::pegin_monitor::{filter_onchain_deposit_outputs, peg_in_blocks_needed};
 use crate::withdraw::{CreatedWithdrawState, WithdrawStateMachine, WithdrawStates};
 
 const WALLET_TWEAK_CHILD_ID: ChildId = ChildId(0);
@@ -107,10 +116,19 @@ pub enum DepositStateV1 {
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum DepositStateV2 {
     WaitingForTransaction,
+    /// Sent again whenever the confirmation progress of the deposit changes
     WaitingForConfirmation {
         #[serde(with = "bitcoin::amount::serde::as_sat")]
         btc_deposited: bitcoin::Amount,
         btc_out_point: bitcoin::OutPoint,
+        /// `None` if the progress couldn't be fetched
+        #[serde(default)]
+        progress: Option<DepositProgress>,
+        /// Block count of the block the deposit was confirmed in before it
+        /// was reorged out, the claim is paused until the deposit has the
+        /// confirmations it needs again
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reorged_block_count: Option<u64>,
     },
     Confirmed {
         #[serde(with = "bitcoin::amount::serde::as_sat")]
@@ -125,6 +143,140 @@ pub enum DepositStateV2 {
     Failed(String),
 }
 
+/// Average time between two blocks that the ETA of deposits is based on
+const EXPECTED_BLOCK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How often [`WalletClientModule::subscribe_deposit`] refreshes the
+/// confirmation progress of a deposit
+const DEPOSIT_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Confirmation targets whose fee rate estimates the fee rate of an
+/// unconfirmed deposit is compared against to estimate when it's mined
+const DEPOSIT_CONFIRMATION_TARGETS: [u16; 6] = [1, 3, 6, 12, 24, 144];
+
+/// Confirmation progress of a deposit, so wallets can show e.g. "2/6
+/// confirmations, ~40 min left" without knowing the federation's rules
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DepositProgress {
+    /// Height of the block the deposit transaction was seen in, `None` while
+    /// it's unconfirmed
+    pub seen_height: Option<u64>,
+    /// Confirmations of the deposit as counted by the federation, which only
+    /// considers blocks that are `finality_delay` deep
+    pub confirmations: u32,
+    /// Confirmations the federation requires for a deposit of this amount
+    pub required_confirmations: u32,
+    /// Expected seconds until the deposit can be claimed, assuming a block
+    /// every ten minutes and, while the deposit transaction is unconfirmed,
+    /// that it's mined within the first confirmation target whose fee rate
+    /// estimate it pays, see [`blocks_until_mined`]
+    pub eta_secs: u64,
+}
+
+impl DepositProgress {
+    /// `blocks_until_mined` is only considered while the deposit transaction
+    /// is unconfirmed, i.e. `seen_height` is `None`
+    fn new(
+        required_confirmations: u32,
+        finality_delay: u32,
+        seen_height: Option<u64>,
+        consensus_block_count: u64,
+        blocks_until_mined: u64,
+    ) -> Self {
+        let remaining_blocks = match seen_height {
+            Some(seen_height) => peg_in_blocks_needed(
+                required_confirmations,
+                finality_delay,
+                seen_height.saturating_add(1),
+                consensus_block_count,
+            ),
+            None => blocks_until_mined.saturating_add(u64::from(required_confirmations)),
+        };
+        let confirmations = match seen_height {
+            Some(_) => required_confirmations
+                .saturating_sub(u32::try_from(remaining_blocks).unwrap_or(u32::MAX)),
+            None => 0,
+        };
+
+        Self {
+            seen_height,
+            confirmations,
+            required_confirmations,
+            eta_secs: remaining_blocks.saturating_mul(EXPECTED_BLOCK_INTERVAL.as_secs()),
+        }
+    }
+
+    async fn fetch(
+        rpc: &DynBitcoindRpc,
+        module_api: &DynModuleApi,
+        cfg: &WalletClientConfig,
+        btc_deposited: bitcoin::Amount,
+        txid: &bitcoin::Txid,
+    ) -> anyhow::Result<Self> {
+        let seen_height = rpc.get_tx_block_height(txid).await?;
+        let consensus_block_count = module_api.fetch_consensus_block_count().await?;
+        let blocks_until_mined = match seen_height {
+            Some(_) => 0,
+            None => fetch_blocks_until_mined(rpc, txid).await,
+        };
+        Ok(Self::new(
+            cfg.required_peg_in_confirmations(btc_deposited),
+            cfg.finality_delay,
+            seen_height,
+            consensus_block_count,
+            blocks_until_mined,
+        ))
+    }
+}
+
+/// Estimates in how many blocks the unconfirmed transaction `txid` is mined
+/// from its fee rate and the current fee rate estimates, assuming the next
+/// block if either is unavailable
+async fn fetch_blocks_until_mined(rpc: &DynBitcoindRpc, txid: &bitcoin::Txid) -> u64 {
+    let fee_rate = match rpc.get_mempool_tx_fee_rate(txid).await {
+        Ok(Some(fee_rate)) => fee_rate,
+        Ok(None) => return 1,
+        Err(error) => {
+            debug!(target: LOG_CLIENT_MODULE_WALLET, %txid, ?error, "Failed to fetch deposit fee rate");
+            return 1;
+        }
+    };
+
+    let mut estimates = vec![];
+    for target in DEPOSIT_CONFIRMATION_TARGETS {
+        if let Ok(Some(estimate)) = rpc.get_fee_rate(target).await {
+            estimates.push((target, estimate));
+        }
+    }
+
+    blocks_until_mined(fee_rate, &estimates)
+}
+
+/// Blocks until a transaction paying `fee_rate` is expected to be mined, i.e.
+/// the first confirmation target in `estimates` whose fee rate estimate it
+/// pays. A transaction paying less than all estimates is assumed to take as
+/// long as the last target, without any estimates as long as the next block.
+fn blocks_until_mined(fee_rate: Feerate, estimates: &[(u16, Feerate)]) -> u64 {
+    estimates
+        .iter()
+        .find(|(_, estimate)| estimate.sats_per_kvb <= fee_rate.sats_per_kvb)
+        .or(estimates.last())
+        .map_or(1, |(target, _)| u64::from(*target))
+}
+
+/// Confirmation progress of a deposit that wasn't claimed yet
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DepositConfirmationStatus {
+    pub btc_out_point: bitcoin::OutPoint,
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub btc_deposited: bitcoin::Amount,
+    /// Confirmations the federation requires for a deposit of this amount
+    pub required_confirmations: u32,
+    /// Blocks that still need to be mined before the deposit can be claimed,
+    /// `None` while the deposit transaction is unconfirmed
+    pub remaining_blocks: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum WithdrawState {
     Created,
@@ -204,6 +356,26 @@ async fn dump_database(
                         wallet_client_items.insert("RecoveryFinalized".to_string(), Box::new(val));
                     }
                 }
+                DbKeyPrefix::PegInBlock => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PegInBlockPrefix,
+                        PegInBlockKey,
+                        u64,
+                        wallet_client_items,
+                        "Peg-In Block"
+                    );
+                }
+                DbKeyPrefix::ReorgedPegIn => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ReorgedPegInPrefix,
+                        ReorgedPegInKey,
+                        ReorgedPegInData,
+                        wallet_client_items,
+                        "Reorged Peg-In"
+                    );
+                }
                 DbKeyPrefix::RecoveryState
                 | DbKeyPrefix::ExternalReservedStart
                 | DbKeyPrefix::CoreInternalReservedStart
@@ -302,6 +474,13 @@ pub enum WalletOperationMetaVariant {
         amount: bitcoin::Amount,
         fee: PegOutFees,
         change: Vec<OutPoint>,
+        /// Label of the BIP-21 URI the withdrawal was made to, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        /// Verified proof that the recipient controls `address`, if one was
+        /// supplied
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ownership_proof: Option<AddressOwnershipProof>,
     },
 
     RbfWithdraw {
@@ -770,6 +949,8 @@ pub async fn subscribe_deposit(
 
         Ok(self.client_ctx.outcome_or_updates(&operation, operation_id, || {
             let stream_rpc = self.rpc.clone();
+            let stream_module_api = self.module_api.clone();
+            let stream_cfg = self.cfg().clone();
             let stream_client_ctx = self.client_ctx.clone();
             let stream_script_pub_key = address.script_pubkey();
 
@@ -804,15 +985,76 @@ pub async fn subscribe_deposit(
                     }
                 ).await.expect("Will never give up");
 
+                let mut progress = DepositProgress::fetch(
+                    &stream_rpc,
+                    &stream_module_api,
+                    &stream_cfg,
+                    btc_deposited,
+                    &btc_out_point.txid,
+                ).await.ok();
+                let mut reorged_block_count = None;
                 yield DepositStateV2::WaitingForConfirmation {
                     btc_deposited,
-                    btc_out_point
+                    btc_out_point,
+                    progress,
+                    reorged_block_count,
                 };
 
-                let claim_data = stream_client_ctx.module_db().wait_key_exists(&ClaimedPegInKey {
+                let claimed_key = ClaimedPegInKey {
+                    peg_in_index: tweak_idx,
+                    btc_out_point,
+                };
+                let reorged_key = ReorgedPegInKey {
                     peg_in_index: tweak_idx,
                     btc_out_point,
-                }).await;
+                };
+
+                loop {
+                    let watch_reorg = reorged_block_count.is_none();
+                    let claimed = stream_client_ctx.module_db().wait_key_exists(&claimed_key);
+                    let reorged = async {
+                        if watch_reorg {
+                            stream_client_ctx.module_db().wait_key_exists(&reorged_key).await
+                        } else {
+                            futures::future::pending().await
+                        }
+                    };
+                    let refresh = fedimint_core::runtime::sleep(DEPOSIT_PROGRESS_INTERVAL);
+                    pin_mut!(claimed, reorged, refresh);
+                    match futures::future::select(futures::future::select(claimed, reorged), refresh).await {
+                        Either::Left((Either::Left(_), _)) => break,
+                        Either::Left((Either::Right((reorged, _)), _)) => {
+                            reorged_block_count = Some(reorged.reorged_block_count);
+                            yield DepositStateV2::WaitingForConfirmation {
+                                btc_deposited,
+                                btc_out_point,
+                                progress,
+                                reorged_block_count,
+                            };
+                            continue;
+                        }
+                        Either::Right(_) => {}
+                    }
+
+                    let new_progress = DepositProgress::fetch(
+                        &stream_rpc,
+                        &stream_module_api,
+                        &stream_cfg,
+                        btc_deposited,
+                        &btc_out_point.txid,
+                    ).await.ok();
+                    if new_progress.is_some() && new_progress != progress {
+                        progress = new_progress;
+                        yield DepositStateV2::WaitingForConfirmation {
+                            btc_deposited,
+                            btc_out_point,
+                            progress,
+                            reorged_block_count,
+                        };
+                    }
+                }
+
+                let claim_data = stream_client_ctx.module_db().wait_key_exists(&claimed_key).await;
 
                 yield DepositStateV2::Confirmed {
                     btc_deposited,
@@ -830,6 +1072,67 @@ pub async fn subscribe_deposit(
         }))
     }
 
+    /// Reports how many confirmations the unclaimed deposits to the address
+    /// of the deposit operation `operation_id` need and how many blocks are
+    /// still missing
+    pub async fn get_deposit_confirmations(
+        &self,
+        operation_id: OperationId,
+    ) -> anyhow::Result<Vec<DepositConfirmationStatus>> {
+        let tweak_idx = self.find_tweak_idx_by_operation_id(operation_id).await?;
+        let (script, _, _, _) = self.data.derive_peg_in_script(tweak_idx);
+        let current_consensus_block_count = self.module_api.fetch_consensus_block_count().await?;
+
+        self.rpc.watch_script_history(&script).await?;
+        let history = self.rpc.get_script_history(&script).await?;
+
+        let mut res = vec![];
+        for (transaction, out_idx) in filter_onchain_deposit_outputs(history.into_iter(), &script) {
+            let txid = transaction.compute_txid();
+            let btc_out_point = bitcoin::OutPoint {
+                txid,
+                vout: out_idx,
+            };
+
+            if self
+                .db
+                .begin_transaction_nc()
+                .await
+                .get_value(&ClaimedPegInKey {
+                    peg_in_index: tweak_idx,
+                    btc_out_point,
+                })
+                .await
+                .is_some()
+            {
+                continue;
+            }
+
+            let btc_deposited = transaction.output[out_idx as usize].value;
+            let remaining_blocks =
+                self.rpc
+                    .get_tx_block_height(&txid)
+                    .await?
+                    .map(|tx_block_height| {
+                        peg_in_blocks_needed(
+                            self.cfg().required_peg_in_confirmations(btc_deposited),
+                            self.cfg().finality_delay,
+                            tx_block_height.saturating_add(1),
+                            current_consensus_block_count,
+                        )
+                    });
+
+            res.push(DepositConfirmationStatus {
+                btc_out_point,
+                btc_deposited,
+                required_confirmations: self.cfg().required_peg_in_confirmations(btc_deposited),
+                remaining_blocks,
+            });
+        }
+
+        Ok(res)
+    }
+
     pub async fn find_tweak_idx_by_operation_id(
         &self,
         operation_id: OperationId,
@@ -1013,6 +1316,88 @@ pub async fn withdraw<M: Serialize + MaybeSend + MaybeSync>(
         amount: bitcoin::Amount,
         fee: PegOutFees,
         extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        self.submit_withdraw(address, amount, fee, None, None, extra_meta)
+            .await
+    }
+
+    /// Like [`Self::withdraw`], but only if `proof` shows that the recipient
+    /// controls `address`. The proof is recorded in the operation's meta, e.g.
+    /// for custodians that have to document the ownership of withdrawal
+    /// addresses.
+    pub async fn withdraw_with_ownership_proof<M: Serialize + MaybeSend + MaybeSync>(
+        &self,
+        address: &bitcoin::Address,
+        amount: bitcoin::Amount,
+        fee: PegOutFees,
+        proof: AddressOwnershipProof,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        proof
+            .verify(address)
+            .context("Invalid ownership proof for the withdrawal address")?;
+
+        self.submit_withdraw(address, amount, fee, None, Some(proof), extra_meta)
+            .await
+    }
+
+    /// Like [`Self::withdraw`], but to the address of a BIP-21 URI. The URI's
+    /// address has to be for the federation's network and if it requests an
+    /// amount it has to match `amount`. The URI's label is recorded in the
+    /// operation's meta.
+    pub async fn withdraw_to_uri<M: Serialize + MaybeSend + MaybeSync>(
+        &self,
+        uri: &Bip21Uri,
+        amount: bitcoin::Amount,
+        fee: PegOutFees,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        self.withdraw_to_uri_with_ownership_proof(uri, amount, fee, None, extra_meta)
+            .await
+    }
+
+    /// Like [`Self::withdraw_to_uri`], but if a `proof` is given only if it
+    /// shows that the recipient controls the URI's address, see
+    /// [`Self::withdraw_with_ownership_proof`]
+    pub async fn withdraw_to_uri_with_ownership_proof<M: Serialize + MaybeSend + MaybeSync>(
+        &self,
+        uri: &Bip21Uri,
+        amount: bitcoin::Amount,
+        fee: PegOutFees,
+        proof: Option<AddressOwnershipProof>,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        let address = uri
+            .address
+            .clone()
+            .require_network(self.get_network())
+            .context("URI address is not for the federation's network")?;
+
+        if let Some(requested) = uri.amount {
+            ensure!(
+                requested == amount,
+                "Amount {amount} differs from the {requested} requested by the URI"
+            );
+        }
+
+        if let Some(proof) = &proof {
+            proof
+                .verify(&address)
+                .context("Invalid ownership proof for the withdrawal address")?;
+        }
+
+        self.submit_withdraw(&address, amount, fee, uri.label.clone(), proof, extra_meta)
+            .await
+    }
+
+    async fn submit_withdraw<M: Serialize + MaybeSend + MaybeSync>(
+        &self,
+        address: &bitcoin::Address,
+        amount: bitcoin::Amount,
+        fee: PegOutFees,
+        label: Option<String>,
+        ownership_proof: Option<AddressOwnershipProof>,
+        extra_meta: M,
     ) -> anyhow::Result<OperationId> {
         {
             let operation_id = OperationId(thread_rng().gen());
@@ -1034,6 +1419,8 @@ pub async fn withdraw<M: Serialize + MaybeSend + MaybeSync>(
                             amount,
                             fee,
                             change: change_range.into_iter().collect(),
+                            label: label.clone(),
+                            ownership_proof: ownership_proof.clone(),
                         },
                         extra_meta: extra_meta.clone(),
                     },
@@ -1366,4 +1753,56 @@ async fn sanity_test_recover_inner() {
             }
         );
     }
+
+    #[test]
+    fn deposit_progress_eta() {
+        let block_secs = EXPECTED_BLOCK_INTERVAL.as_secs();
+
+        // Unconfirmed, mined in 3 blocks and then needs 12 confirmations
+        assert_eq!(
+            DepositProgress::new(12, 10, None, 95, 3),
+            DepositProgress {
+                seen_height: None,
+                confirmations: 0,
+                required_confirmations: 12,
+                eta_secs: 15 * block_secs,
+            }
+        );
+
+        // Confirmed at height 100, the federation waits for block count 103
+        assert_eq!(
+            DepositProgress::new(12, 10, Some(100), 95, 3),
+            DepositProgress {
+                seen_height: Some(100),
+                confirmations: 4,
+                required_confirmations: 12,
+                eta_secs: 8 * block_secs,
+            }
+        );
+
+        // Claimable
+        assert_eq!(
+            DepositProgress::new(12, 10, Some(100), 103, 3),
+            DepositProgress {
+                seen_height: Some(100),
+                confirmations: 12,
+                required_confirmations: 12,
+                eta_secs: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn blocks_until_mined_follows_fee_estimates() {
+        let rate = |sats_per_kvb| Feerate { sats_per_kvb };
+        let estimates = [(1, rate(20_000)), (6, rate(8_000)), (144, rate(2_000))];
+
+        assert_eq!(blocks_until_mined(rate(25_000), &estimates), 1);
+        assert_eq!(blocks_until_mined(rate(8_000), &estimates), 6);
+        assert_eq!(blocks_until_mined(rate(5_000), &estimates), 144);
+        // Below all estimates
+        assert_eq!(blocks_until_mined(rate(1_000), &estimates), 144);
+        // Without estimates
+        assert_eq!(blocks_until_mined(rate(1_000), &[]), 1);
+    }
 }
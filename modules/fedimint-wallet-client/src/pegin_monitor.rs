@@ -4,6 +4,7 @@
 use bitcoin::ScriptBuf;
 use fedimint_api_client::api::DynModuleApi;
 use fedimint_bitcoind::DynBitcoindRpc;
+use fedimint_client::event_bus::ClientEvent;
 use fedimint_client::module::{ClientContext, OutPointRange};
 use fedimint_client::transaction::{ClientInput, ClientInputBundle};
 use fedimint_core::core::OperationId;
@@ -25,10 +26,10 @@
 
 use crate::api::WalletFederationApi as _;
 use crate::client_db::{
-    ClaimedPegInData, ClaimedPegInKey, PegInTweakIndexData, PegInTweakIndexKey,
-    PegInTweakIndexPrefix, TweakIdx,
+    ClaimedPegInData, ClaimedPegInKey, PegInBlockKey, PegInTweakIndexData, PegInTweakIndexKey,
+    PegInTweakIndexPrefix, ReorgedPegInData, ReorgedPegInKey, TweakIdx,
 };
-use crate::events::DepositConfirmed;
+use crate::events::{DepositConfirmed, DepositReorged};
 use crate::{WalletClientModule, WalletClientModuleData};
 
 /// A helper struct meant to combined data from all addresses/records
@@ -357,18 +358,28 @@ async fn check_idx_pegins(
         }
         let finality_delay = u64::from(data.cfg.finality_delay);
 
-        let tx_block_count =
-            if let Some(tx_block_height) = btc_rpc.get_tx_block_height(&txid).await? {
-                tx_block_height.saturating_add(1)
-            } else {
-                outcomes.push(CheckOutcome::Pending {
-                    num_blocks_needed: finality_delay,
-                });
-                debug!(target:LOG_CLIENT_MODULE_WALLET, %txid, %out_idx,"In the mempool");
-                continue;
-            };
+        let tx_block_count = btc_rpc
+            .get_tx_block_height(&txid)
+            .await?
+            .map(|tx_block_height| tx_block_height.saturating_add(1));
+
+        track_deposit_block(client_ctx, tweak_idx, outpoint, tx_block_count).await?;
+
+        let Some(tx_block_count) = tx_block_count else {
+            outcomes.push(CheckOutcome::Pending {
+                num_blocks_needed: finality_delay,
+            });
+            debug!(target:LOG_CLIENT_MODULE_WALLET, %txid, %out_idx,"In the mempool");
+            continue;
+        };
 
-        let num_blocks_needed = tx_block_count.saturating_sub(current_consensus_block_count);
+        let num_blocks_needed = peg_in_blocks_needed(
+            data.cfg
+                .required_peg_in_confirmations(transaction.output[out_idx as usize].value),
+            data.cfg.finality_delay,
+            tx_block_count,
+            current_consensus_block_count,
+        );
 
         if 0 < num_blocks_needed {
             outcomes.push(CheckOutcome::Pending { num_blocks_needed });
@@ -398,6 +409,87 @@ async fn check_idx_pegins(
     Ok(outcomes)
 }
 
+/// Remembers the block a deposit that wasn't claimed yet is confirmed in and
+/// records a reorg if it got unconfirmed or moved to another block since the
+/// last check.
+///
+/// The confirmations the claim needs are always counted from the block the
+/// deposit is in now, so after a reorg the claim is paused until the deposit
+/// is buried deep enough again.
+async fn track_deposit_block(
+    client_ctx: &ClientContext<WalletClientModule>,
+    tweak_idx: TweakIdx,
+    out_point: bitcoin::OutPoint,
+    tx_block_count: Option<u64>,
+) -> anyhow::Result<()> {
+    let block_key = PegInBlockKey {
+        peg_in_index: tweak_idx,
+        btc_out_point: out_point,
+    };
+
+    client_ctx
+        .module_db()
+        .autocommit(
+            |dbtx, _| {
+                Box::pin(async {
+                    let previous_block_count = match tx_block_count {
+                        Some(count) => dbtx.insert_entry(&block_key, &count).await,
+                        None => dbtx.remove_entry(&block_key).await,
+                    };
+                    if let Some(reorged_block_count) =
+                        reorged_block_count(previous_block_count, tx_block_count)
+                    {
+                        warn!(target: LOG_CLIENT_MODULE_WALLET, %out_point, %reorged_block_count, ?tx_block_count, "Deposit was reorged out of the block it confirmed in");
+
+                        dbtx.insert_entry(
+                            &ReorgedPegInKey {
+                                peg_in_index: tweak_idx,
+                                btc_out_point: out_point,
+                            },
+                            &ReorgedPegInData {
+                                reorged_block_count,
+                            },
+                        )
+                        .await;
+                        client_ctx
+                            .log_event(
+                                dbtx,
+                                DepositReorged {
+                                    txid: out_point.txid,
+                                    out_idx: out_point.vout,
+                                    reorged_block_count,
+                                    tx_block_count,
+                                },
+                            )
+                            .await;
+                    }
+
+                    Ok::<_, anyhow::Error>(())
+                })
+            },
+            None,
+        )
+        .await
+        .map_err(|e| match e {
+            AutocommitError::CommitFailed {
+                last_error,
+                attempts,
+            } => last_error.context(format!("Failed to commit after {attempts} attempts")),
+            AutocommitError::ClosureError { error, .. } => error,
+        })?;
+
+    Ok(())
+}
+
+/// Returns the block count of the block a deposit was confirmed in if it got
+/// unconfirmed or moved to another block since
+fn reorged_block_count(
+    previous_block_count: Option<u64>,
+    tx_block_count: Option<u64>,
+) -> Option<u64> {
+    previous_block_count.filter(|previous| Some(*previous) != tx_block_count)
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn claim_peg_in(
     client_ctx: &ClientContext<WalletClientModule>,
@@ -451,6 +543,14 @@ async fn claim_peg_in_inner(
                 },
             )
             .await;
+        client_ctx.publish_client_event(
+            dbtx,
+            ClientEvent::DepositConfirmed {
+                operation_id,
+                txid: btc_transaction.compute_txid(),
+                amount,
+            },
+        );
 
         client_ctx
             .claim_inputs(
@@ -483,6 +583,11 @@ async fn claim_peg_in_inner(
                     )
                     .await;
 
+                    dbtx.remove_entry(&PegInBlockKey {
+                        peg_in_index: tweak_idx,
+                        btc_out_point: out_point,
+                    })
+                    .await;
                     dbtx.insert_entry(
                         &ClaimedPegInKey {
                             peg_in_index: tweak_idx,
@@ -512,6 +617,23 @@ async fn claim_peg_in_inner(
     Ok(())
 }
 
+/// Number of blocks the federation still needs to sync before a deposit
+/// requiring `required_confirmations` confirmed in a block with count
+/// `tx_block_count` can be claimed
+///
+/// The federation only syncs blocks that are `finality_delay` deep, so only
+/// confirmations a larger deposit needs beyond that delay the claim further.
+pub(crate) fn peg_in_blocks_needed(
+    required_confirmations: u32,
+    finality_delay: u32,
+    tx_block_count: u64,
+    current_consensus_block_count: u64,
+) -> u64 {
+    let extra_blocks = required_confirmations.saturating_sub(finality_delay);
+
+    (tx_block_count + u64::from(extra_blocks)).saturating_sub(current_consensus_block_count)
+}
+
 pub(crate) fn filter_onchain_deposit_outputs<'a>(
     tx_iter: impl Iterator<Item = bitcoin::Transaction> + 'a,
     out_script: &'a ScriptBuf,
@@ -530,3 +652,20 @@ pub(crate) fn filter_onchain_deposit_outputs<'a>(
             })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::reorged_block_count;
+
+    #[test]
+    fn detects_deposits_leaving_their_block() {
+        // First seen in the mempool or confirmed
+        assert_eq!(reorged_block_count(None, None), None);
+        assert_eq!(reorged_block_count(None, Some(100)), None);
+        // Still in the same block
+        assert_eq!(reorged_block_count(Some(100), Some(100)), None);
+        // Back in the mempool or confirmed in another block
+        assert_eq!(reorged_block_count(Some(100), None), Some(100));
+        assert_eq!(reorged_block_count(Some(100), Some(101)), Some(100));
+    }
+}
@@ -1,11 +1,14 @@
 use std::collections::BTreeMap;
+use std::str::FromStr;
 
+use anyhow::Context as _;
 use bitcoin::secp256k1::SecretKey;
 use bitcoin::Network;
 use fedimint_core::core::ModuleKind;
 use fedimint_core::encoding::btc::NetworkLegacyEncodingWrapper;
-use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
 use fedimint_core::envs::BitcoinRpcConfig;
+use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::serde_json;
 use fedimint_core::util::SafeUrl;
 use fedimint_core::{plugin_types_trait_impl_config, Feerate, PeerId};
@@ -42,6 +45,7 @@ pub fn regtest(bitcoin_rpc: BitcoinRpcConfig) -> WalletGenParams {
                     .expect("Failed to parse default esplora server"),
                 },
                 fee_consensus: FeeConsensus::default(),
+                peg_in_confirmation_tiers: vec![],
             },
         }
     }
@@ -63,6 +67,9 @@ pub struct WalletGenParamsConsensus {
     ///
     /// Deposit fees in particular are a protection against dust attacks.
     pub fee_consensus: FeeConsensus,
+    /// See [`WalletConfigConsensus::peg_in_confirmation_tiers`].
+    #[serde(default)]
+    pub peg_in_confirmation_tiers: Vec<PegInConfirmationTier>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -108,6 +115,22 @@ pub struct WalletConfigConsensus {
     /// **This is only used by the client, the RPC used by the server is defined
     /// in [`WalletConfigLocal`].**
     pub client_default_bitcoin_rpc: BitcoinRpcConfig,
+    /// Larger peg-ins need more confirmations than `finality_delay`, see
+    /// [`required_peg_in_confirmations`]. Must stay the last field, see
+    /// [`PegInConfirmationTiers`].
+    #[serde(default, skip_serializing_if = "PegInConfirmationTiers::is_empty")]
+    pub peg_in_confirmation_tiers: PegInConfirmationTiers,
+}
+
+impl WalletConfigConsensus {
+    /// Confirmations a peg-in of `amount` needs before it can be claimed
+    pub fn required_peg_in_confirmations(&self, amount: bitcoin::Amount) -> u32 {
+        required_peg_in_confirmations(
+            self.finality_delay,
+            &self.peg_in_confirmation_tiers.0,
+            amount,
+        )
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
@@ -124,6 +147,22 @@ pub struct WalletClientConfig {
     /// become configurable locally and this should merely be a suggested
     /// default by the federation.*
     pub default_bitcoin_rpc: BitcoinRpcConfig,
+    /// Larger peg-ins need more confirmations than `finality_delay`, see
+    /// [`required_peg_in_confirmations`]. Must stay the last field, see
+    /// [`PegInConfirmationTiers`].
+    #[serde(default, skip_serializing_if = "PegInConfirmationTiers::is_empty")]
+    pub peg_in_confirmation_tiers: PegInConfirmationTiers,
+}
+
+impl WalletClientConfig {
+    /// Confirmations a peg-in of `amount` needs before it can be claimed
+    pub fn required_peg_in_confirmations(&self, amount: bitcoin::Amount) -> u32 {
+        required_peg_in_confirmations(
+            self.finality_delay,
+            &self.peg_in_confirmation_tiers.0,
+            amount,
+        )
+    }
 }
 
 impl std::fmt::Display for WalletClientConfig {
@@ -151,6 +190,111 @@ fn default() -> Self {
     }
 }
 
+/// Peg-ins of at least `min_amount` need at least `confirmations`
+/// confirmations before they can be claimed
+///
+/// Parsed from `<min amount in sats>=<confirmations>`, e.g. `1000000=20`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct PegInConfirmationTier {
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub min_amount: bitcoin::Amount,
+    pub confirmations: u32,
+}
+
+impl FromStr for PegInConfirmationTier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min_amount, confirmations) = s
+            .split_once('=')
+            .context("Expected <min amount in sats>=<confirmations>")?;
+
+        Ok(Self {
+            min_amount: bitcoin::Amount::from_sat(
+                min_amount.trim().parse().context("Invalid min amount")?,
+            ),
+            confirmations: confirmations
+                .trim()
+                .parse()
+                .context("Invalid number of confirmations")?,
+        })
+    }
+}
+
+/// Peg-in confirmation tiers as an optional trailing field of the wallet
+/// configs.
+///
+/// The configs are committed to by their consensus hash and clients reject
+/// configs with bytes they don't expect. So without tiers nothing at all is
+/// encoded, keeping configs byte for byte identical to before tiers existed.
+/// Only configs that actually use tiers are encoded with a length prefixed
+/// list of them appended, which older clients can't decode. Decoding consumes
+/// the rest of the reader, so this has to be the last field of a config.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PegInConfirmationTiers(pub Vec<PegInConfirmationTier>);
+
+impl PegInConfirmationTiers {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Encodable for PegInConfirmationTiers {
+    fn consensus_encode<W: std::io::Write>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
+        if self.0.is_empty() {
+            return Ok(0);
+        }
+
+        self.0.consensus_encode(writer)
+    }
+}
+
+impl Decodable for PegInConfirmationTiers {
+    fn consensus_decode_from_finite_reader<R: std::io::Read>(
+        reader: &mut R,
+        modules: &ModuleDecoderRegistry,
+    ) -> Result<Self, DecodeError> {
+        let mut rest = vec![];
+        reader
+            .read_to_end(&mut rest)
+            .map_err(DecodeError::from_err)?;
+
+        if rest.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let mut rest = &rest[..];
+        let tiers =
+            Vec::<PegInConfirmationTier>::consensus_decode_from_finite_reader(&mut rest, modules)?;
+        if !rest.is_empty() {
+            return Err(DecodeError::from_str(
+                "Unexpected bytes after the peg-in confirmation tiers",
+            ));
+        }
+
+        Ok(Self(tiers))
+    }
+}
+
+/// Confirmations a peg-in of `amount` needs: the highest of `finality_delay`
+/// and the confirmations of all tiers `amount` reaches
+///
+/// Since the federation only considers blocks that are `finality_delay` deep,
+/// a tier effectively delays the claim by `confirmations - finality_delay`
+/// blocks.
+pub fn required_peg_in_confirmations(
+    finality_delay: u32,
+    tiers: &[PegInConfirmationTier],
+    amount: bitcoin::Amount,
+) -> u32 {
+    tiers
+        .iter()
+        .filter(|tier| tier.min_amount <= amount)
+        .map(|tier| tier.confirmations)
+        .fold(finality_delay, u32::max)
+}
+
 impl WalletConfig {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -162,6 +306,7 @@ pub fn new(
         bitcoin_rpc: BitcoinRpcConfig,
         client_default_bitcoin_rpc: BitcoinRpcConfig,
         fee_consensus: FeeConsensus,
+        peg_in_confirmation_tiers: Vec<PegInConfirmationTier>,
     ) -> Self {
         let peg_in_descriptor = if pubkeys.len() == 1 {
             PegInDescriptor::Wpkh(
@@ -190,6 +335,7 @@ pub fn new(
                 default_fee: Feerate { sats_per_kvb: 1000 },
                 fee_consensus,
                 client_default_bitcoin_rpc,
+                peg_in_confirmation_tiers: PegInConfirmationTiers(peg_in_confirmation_tiers),
             },
         }
     }
@@ -206,3 +352,58 @@ pub fn new(
     WalletConfigConsensus,
     WalletClientConfig
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_peg_in_confirmations_picks_highest_reached_tier() {
+        let tiers = vec![
+            PegInConfirmationTier::from_str("1000000=20").unwrap(),
+            PegInConfirmationTier::from_str("100000 = 12").unwrap(),
+            // Tiers below the finality delay don't lower it
+            PegInConfirmationTier::from_str("0=3").unwrap(),
+        ];
+
+        let required =
+            |sats| required_peg_in_confirmations(10, &tiers, bitcoin::Amount::from_sat(sats));
+
+        assert_eq!(required(99_999), 10);
+        assert_eq!(required(100_000), 12);
+        assert_eq!(required(999_999), 12);
+        assert_eq!(required(5_000_000), 20);
+        assert_eq!(
+            required_peg_in_confirmations(10, &[], bitcoin::Amount::MAX),
+            10
+        );
+        assert!(PegInConfirmationTier::from_str("20").is_err());
+    }
+
+    #[test]
+    fn peg_in_confirmation_tiers_encoding() {
+        let decode = |bytes: Vec<u8>| {
+            PegInConfirmationTiers::consensus_decode_vec(bytes, &ModuleDecoderRegistry::default())
+        };
+
+        // Configs without tiers have to stay byte for byte identical to before
+        // tiers existed, since they are committed to by their consensus hash
+        let empty = PegInConfirmationTiers::default();
+        assert!(empty.consensus_encode_to_vec().is_empty());
+        assert_eq!(decode(vec![]).expect("Decoding succeeds"), empty);
+
+        let tiers =
+            PegInConfirmationTiers(vec![PegInConfirmationTier::from_str("1000000=20").unwrap()]);
+        let encoded = tiers.consensus_encode_to_vec();
+        assert_eq!(decode(encoded.clone()).expect("Decoding succeeds"), tiers);
+
+        let mut trailing = encoded;
+        trailing.push(0);
+        assert!(decode(trailing).is_err());
+
+        assert_eq!(
+            serde_json::to_value(&tiers).expect("Serializable"),
+            serde_json::json!([{"min_amount": 1_000_000, "confirmations": 20}])
+        );
+    }
+}
@@ -6,3 +6,5 @@
 pub const MODULE_CONSENSUS_VERSION_ENDPOINT: &str = "module_consensus_version";
 pub const ACTIVATE_CONSENSUS_VERSION_VOTING_ENDPOINT: &str = "activate_consensus_version_voting";
 pub const WALLET_SUMMARY_ENDPOINT: &str = "wallet_summary";
+pub const PEG_OUT_SIGNING_SESSIONS_ENDPOINT: &str = "peg_out_signing_sessions";
+pub const PEG_IN_REORG_ALERTS_ENDPOINT: &str = "peg_in_reorg_alerts";
@@ -6,6 +6,7 @@
 #![allow(clippy::needless_lifetimes)]
 #![allow(clippy::return_self_not_must_use)]
 
+use std::collections::BTreeSet;
 use std::hash::Hasher;
 
 use bitcoin::address::NetworkUnchecked;
@@ -16,7 +17,9 @@
 use fedimint_core::encoding::btc::NetworkLegacyEncodingWrapper;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::module::{CommonModuleInit, ModuleCommon, ModuleConsensusVersion};
-use fedimint_core::{extensible_associated_module_type, plugin_types_trait_impl_common, Feerate};
+use fedimint_core::{
+    extensible_associated_module_type, plugin_types_trait_impl_common, Feerate, PeerId,
+};
 use impl_tools::autoimpl;
 use miniscript::Descriptor;
 use serde::{Deserialize, Serialize};
@@ -34,7 +37,7 @@
 pub mod txoproof;
 
 pub const KIND: ModuleKind = ModuleKind::from_static_str("wallet");
-pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(2, 2);
+pub const MODULE_CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion::new(2, 3);
 
 /// Used for estimating a feerate that will confirm within a target number of
 /// blocks.
@@ -62,6 +65,9 @@ pub enum WalletConsensusItem {
     Feerate(Feerate),
     PegOutSignature(PegOutSignatureItem),
     ModuleConsensusVersion(ModuleConsensusVersion),
+    /// Vote that a block synced by the federation is no longer part of the
+    /// chain of the guardian's bitcoin backend
+    ReorgedBlock(BlockHash),
     #[encodable_default]
     Default {
         variant: u64,
@@ -92,6 +98,9 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                     version.major, version.minor
                 )
             }
+            WalletConsensusItem::ReorgedBlock(block_hash) => {
+                write!(f, "Wallet Reorged Block {block_hash}")
+            }
             WalletConsensusItem::Default { variant, .. } => {
                 write!(f, "Unknown Wallet CI variant={variant}")
             }
@@ -113,6 +122,31 @@ pub struct SpendableUTXO {
     pub amount: bitcoin::Amount,
 }
 
+/// Progress of the guardians threshold signing a peg-out transaction
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct PegOutSigningSessionStatus {
+    pub txid: Txid,
+    /// Consensus block count at which the peg-out was created
+    pub started_at_block_count: u32,
+    /// Guardians whose valid signatures were received
+    pub signed_by: BTreeSet<PeerId>,
+    /// Guardians whose signatures are still missing
+    pub missing: BTreeSet<PeerId>,
+}
+
+/// A claimed peg-in whose block is no longer part of the chain of a guardian's
+/// bitcoin backend, so the e-cash issued for it may not be backed anymore
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct PegInReorgAlert {
+    pub outpoint: bitcoin::OutPoint,
+    /// Height of the block the peg-in was confirmed in
+    pub height: u32,
+    /// Hash of the block synced by the federation at that height
+    pub block_hash: BlockHash,
+    /// Hash of the block at that height in the guardian's chain now
+    pub current_block_hash: BlockHash,
+}
+
 /// A transaction output, either unspent or consumed
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
 pub struct TxOutputSummary {
@@ -507,6 +541,10 @@ pub enum WalletInputError {
     WrongOutputScript,
     #[error("Wrong tx out")]
     WrongTxOut,
+    #[error("The peg-in needs {required} confirmations, {remaining} more blocks are missing")]
+    NotEnoughConfirmations { required: u32, remaining: u64 },
+    #[error("The block {0} of the peg-in was reorged out, claiming it is paused")]
+    PegInBlockReorged(BlockHash),
 }
 
 #[derive(Debug, Error, Encodable, Decodable, Hash, Clone, Eq, PartialEq)]
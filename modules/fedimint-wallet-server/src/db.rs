@@ -1,3 +1,6 @@
+use std::collections::BTreeSet;
+
+use anyhow::ensure;
 use bitcoin::secp256k1::ecdsa::Signature;
 use bitcoin::{BlockHash, OutPoint, TxOut, Txid};
 use fedimint_core::db::{IDatabaseTransactionOpsCoreTyped, MigrationContext};
@@ -28,6 +31,12 @@ pub enum DbKeyPrefix {
     ConsensusVersionVote = 0x40,
     UnspentTxOut = 0x41,
     ConsensusVersionVotingActivation = 0x42,
+    PegOutSigningSession = 0x43,
+    UnspentTxOutHeight = 0x44,
+    BlockHashByHeight = 0x45,
+    ClaimedPegInBlock = 0x46,
+    ReorgedBlockVote = 0x47,
+    ReorgedBlock = 0x48,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -242,6 +251,99 @@ pub async fn migrate_to_v1(mut ctx: MigrationContext<'_>) -> Result<(), anyhow::
 );
 impl_db_lookup!(key = UnspentTxOutKey, query_prefix = UnspentTxOutPrefix);
 
+/// Height of the block an [`UnspentTxOutKey`] was confirmed in, needed to
+/// enforce the peg-in confirmation tiers. Outputs confirmed before this was
+/// tracked have no entry.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct UnspentTxOutHeightKey(pub bitcoin::OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct UnspentTxOutHeightPrefix;
+
+impl_db_record!(
+    key = UnspentTxOutHeightKey,
+    value = u32,
+    db_prefix = DbKeyPrefix::UnspentTxOutHeight,
+);
+impl_db_lookup!(
+    key = UnspentTxOutHeightKey,
+    query_prefix = UnspentTxOutHeightPrefix
+);
+
+/// Hash of the block synced at a height, so blocks that are reorged out of
+/// the chain after being synced can be detected. Blocks synced before this
+/// was tracked have no entry.
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct BlockHashByHeightKey(pub u32);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct BlockHashByHeightPrefix;
+
+impl_db_record!(
+    key = BlockHashByHeightKey,
+    value = BlockHash,
+    db_prefix = DbKeyPrefix::BlockHashByHeight,
+);
+impl_db_lookup!(
+    key = BlockHashByHeightKey,
+    query_prefix = BlockHashByHeightPrefix
+);
+
+/// Block a claimed peg-in was confirmed in according to its proof, to alert
+/// if the block is reorged out after the peg-in was claimed
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ClaimedPegInBlockKey(pub OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ClaimedPegInBlockPrefix;
+
+impl_db_record!(
+    key = ClaimedPegInBlockKey,
+    value = BlockHash,
+    db_prefix = DbKeyPrefix::ClaimedPegInBlock,
+);
+impl_db_lookup!(
+    key = ClaimedPegInBlockKey,
+    query_prefix = ClaimedPegInBlockPrefix
+);
+
+/// Vote of a guardian that a block synced by the federation is no longer part
+/// of the chain of its bitcoin backend
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReorgedBlockVoteKey(pub BlockHash, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ReorgedBlockVotePrefix;
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ReorgedBlockVoteBlockPrefix(pub BlockHash);
+
+impl_db_record!(
+    key = ReorgedBlockVoteKey,
+    value = (),
+    db_prefix = DbKeyPrefix::ReorgedBlockVote,
+);
+impl_db_lookup!(
+    key = ReorgedBlockVoteKey,
+    query_prefix = ReorgedBlockVotePrefix,
+    query_prefix = ReorgedBlockVoteBlockPrefix
+);
+
+/// A synced block that more than `max_evil` guardians voted to be reorged
+/// out, peg-ins confirmed in it can't be claimed anymore
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct ReorgedBlockKey(pub BlockHash);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct ReorgedBlockPrefix;
+
+impl_db_record!(
+    key = ReorgedBlockKey,
+    value = (),
+    db_prefix = DbKeyPrefix::ReorgedBlock,
+);
+impl_db_lookup!(key = ReorgedBlockKey, query_prefix = ReorgedBlockPrefix);
+
 #[derive(Clone, Debug, Encodable, Decodable, Serialize)]
 pub struct ConsensusVersionVotingActivationKey;
 
@@ -257,3 +359,60 @@ pub async fn migrate_to_v1(mut ctx: MigrationContext<'_>) -> Result<(), anyhow::
     key = ConsensusVersionVotingActivationKey,
     query_prefix = ConsensusVersionVotingActivationPrefix
 );
+
+/// Tracks the collection of threshold signatures for an
+/// [`UnsignedTransaction`], so peg-outs that don't make progress can be
+/// detected
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize)]
+pub struct PegOutSigningSession {
+    /// Consensus block count at which the peg-out was created
+    pub started_at_block_count: u32,
+    /// Guardians whose valid signatures were received
+    pub signed_by: BTreeSet<PeerId>,
+}
+
+impl PegOutSigningSession {
+    pub fn new(started_at_block_count: u32) -> Self {
+        Self {
+            started_at_block_count,
+            signed_by: BTreeSet::new(),
+        }
+    }
+
+    /// Records the signature of `peer`, fails if it already signed
+    pub fn add_signer(&mut self, peer: PeerId) -> anyhow::Result<()> {
+        ensure!(
+            self.signed_by.insert(peer),
+            "Peer already signed the peg out transaction"
+        );
+        Ok(())
+    }
+
+    /// Guardians out of `peers` whose signatures are still missing
+    pub fn missing_signers<'a>(
+        &self,
+        peers: impl IntoIterator<Item = &'a PeerId>,
+    ) -> BTreeSet<PeerId> {
+        peers
+            .into_iter()
+            .filter(|peer| !self.signed_by.contains(peer))
+            .copied()
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegOutSigningSessionKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PegOutSigningSessionPrefix;
+
+impl_db_record!(
+    key = PegOutSigningSessionKey,
+    value = PegOutSigningSession,
+    db_prefix = DbKeyPrefix::PegOutSigningSession,
+);
+impl_db_lookup!(
+    key = PegOutSigningSessionKey,
+    query_prefix = PegOutSigningSessionPrefix
+);
@@ -15,6 +15,7 @@
 use std::clone::Clone;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::Infallible;
+use std::future::Future;
 use std::iter;
 use std::sync::Arc;
 #[cfg(not(target_family = "wasm"))]
@@ -32,10 +33,10 @@
 use bitcoin::{Address, BlockHash, Network, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid};
 use common::config::WalletConfigConsensus;
 use common::{
-    proprietary_tweak_key, PegOutFees, PegOutSignatureItem, ProcessPegOutSigError, SpendableUTXO,
-    TxOutputSummary, WalletCommonInit, WalletConsensusItem, WalletCreationError, WalletInput,
-    WalletModuleTypes, WalletOutput, WalletOutputOutcome, WalletSummary, DEPRECATED_RBF_ERROR,
-    FEERATE_MULTIPLIER,
+    proprietary_tweak_key, PegInReorgAlert, PegOutFees, PegOutSignatureItem,
+    PegOutSigningSessionStatus, ProcessPegOutSigError, SpendableUTXO, TxOutputSummary,
+    WalletCommonInit, WalletConsensusItem, WalletCreationError, WalletInput, WalletModuleTypes,
+    WalletOutput, WalletOutputOutcome, WalletSummary, DEPRECATED_RBF_ERROR, FEERATE_MULTIPLIER,
 };
 use fedimint_bitcoind::{create_bitcoind, DynBitcoindRpc};
 use fedimint_core::config::{
@@ -77,7 +78,8 @@
 use fedimint_wallet_common::endpoint_constants::{
     ACTIVATE_CONSENSUS_VERSION_VOTING_ENDPOINT, BITCOIN_KIND_ENDPOINT, BITCOIN_RPC_CONFIG_ENDPOINT,
     BLOCK_COUNT_ENDPOINT, BLOCK_COUNT_LOCAL_ENDPOINT, MODULE_CONSENSUS_VERSION_ENDPOINT,
-    PEG_OUT_FEES_ENDPOINT, WALLET_SUMMARY_ENDPOINT,
+    PEG_IN_REORG_ALERTS_ENDPOINT, PEG_OUT_FEES_ENDPOINT, PEG_OUT_SIGNING_SESSIONS_ENDPOINT,
+    WALLET_SUMMARY_ENDPOINT,
 };
 use fedimint_wallet_common::keys::CompressedPublicKey;
 use fedimint_wallet_common::tweakable::Tweakable;
@@ -97,22 +99,40 @@
 use serde::Serialize;
 use strum::IntoEnumIterator;
 use tokio::sync::watch;
-use tracing::{debug, info, instrument, trace, warn};
+use tracing::{debug, error, info, instrument, trace, warn};
 
 use crate::db::{
-    migrate_to_v1, BlockCountVoteKey, BlockCountVotePrefix, BlockHashKey, BlockHashKeyPrefix,
-    ClaimedPegInOutpointKey, ClaimedPegInOutpointPrefixKey, ConsensusVersionVoteKey,
-    ConsensusVersionVotePrefix, ConsensusVersionVotingActivationKey,
+    migrate_to_v1, BlockCountVoteKey, BlockCountVotePrefix, BlockHashByHeightKey,
+    BlockHashByHeightPrefix, BlockHashKey, BlockHashKeyPrefix, ClaimedPegInBlockKey,
+    ClaimedPegInBlockPrefix, ClaimedPegInOutpointKey, ClaimedPegInOutpointPrefixKey,
+    ConsensusVersionVoteKey, ConsensusVersionVotePrefix, ConsensusVersionVotingActivationKey,
     ConsensusVersionVotingActivationPrefix, DbKeyPrefix, FeeRateVoteKey, FeeRateVotePrefix,
-    PegOutBitcoinTransaction, PegOutBitcoinTransactionPrefix, PegOutNonceKey, PegOutTxSignatureCI,
-    PegOutTxSignatureCIPrefix, PendingTransactionKey, PendingTransactionPrefixKey, UTXOKey,
-    UTXOPrefixKey, UnsignedTransactionKey, UnsignedTransactionPrefixKey, UnspentTxOutKey,
-    UnspentTxOutPrefix,
+    PegOutBitcoinTransaction, PegOutBitcoinTransactionPrefix, PegOutNonceKey, PegOutSigningSession,
+    PegOutSigningSessionKey, PegOutSigningSessionPrefix, PegOutTxSignatureCI,
+    PegOutTxSignatureCIPrefix, PendingTransactionKey, PendingTransactionPrefixKey, ReorgedBlockKey,
+    ReorgedBlockPrefix, ReorgedBlockVoteBlockPrefix, ReorgedBlockVoteKey, ReorgedBlockVotePrefix,
+    UTXOKey, UTXOPrefixKey, UnsignedTransactionKey, UnsignedTransactionPrefixKey,
+    UnspentTxOutHeightKey, UnspentTxOutHeightPrefix, UnspentTxOutKey, UnspentTxOutPrefix,
 };
-use crate::metrics::WALLET_BLOCK_COUNT;
+use crate::metrics::{WALLET_BLOCK_COUNT, WALLET_PEGINS_AT_RISK};
 
 mod metrics;
 
+/// Module consensus version from which on the guardians track which of them
+/// signed a peg-out transaction and reject duplicate signatures
+const PEG_OUT_SIGNING_SESSIONS_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(2, 3);
+
+/// Module consensus version from which on the guardians track the blocks they
+/// synced and the blocks of claimed peg-ins, and vote on blocks that were
+/// reorged out to pause claiming peg-ins confirmed in them
+const PEG_IN_REORG_TRACKING_CONSENSUS_VERSION: ModuleConsensusVersion =
+    ModuleConsensusVersion::new(2, 3);
+
+/// Number of blocks below our chain tip in which the synced blocks are
+/// watched for reorgs
+pub const PEG_IN_REORG_WATCH_BLOCKS: u32 = 1008;
+
 #[derive(Debug, Clone)]
 pub struct WalletInit;
 
@@ -248,6 +268,66 @@ async fn dump_database(
                         "Consensus Version Voting Activation Key"
                     );
                 }
+                DbKeyPrefix::PegOutSigningSession => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PegOutSigningSessionPrefix,
+                        PegOutSigningSessionKey,
+                        PegOutSigningSession,
+                        wallet,
+                        "Peg Out Signing Sessions"
+                    );
+                }
+                DbKeyPrefix::UnspentTxOutHeight => {
+                    push_db_pair_items!(
+                        dbtx,
+                        UnspentTxOutHeightPrefix,
+                        UnspentTxOutHeightKey,
+                        u32,
+                        wallet,
+                        "Unspent Tx Out Heights"
+                    );
+                }
+                DbKeyPrefix::BlockHashByHeight => {
+                    push_db_pair_items!(
+                        dbtx,
+                        BlockHashByHeightPrefix,
+                        BlockHashByHeightKey,
+                        BlockHash,
+                        wallet,
+                        "Block Hashes By Height"
+                    );
+                }
+                DbKeyPrefix::ClaimedPegInBlock => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ClaimedPegInBlockPrefix,
+                        ClaimedPegInBlockKey,
+                        BlockHash,
+                        wallet,
+                        "Claimed Peg-in Blocks"
+                    );
+                }
+                DbKeyPrefix::ReorgedBlockVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ReorgedBlockVotePrefix,
+                        ReorgedBlockVoteKey,
+                        (),
+                        wallet,
+                        "Reorged Block Votes"
+                    );
+                }
+                DbKeyPrefix::ReorgedBlock => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ReorgedBlockPrefix,
+                        ReorgedBlockKey,
+                        (),
+                        wallet,
+                        "Reorged Blocks"
+                    );
+                }
             }
         }
 
@@ -270,24 +350,20 @@ fn supported_api_versions(&self) -> SupportedModuleApiVersions {
                 MODULE_CONSENSUS_VERSION.major,
                 MODULE_CONSENSUS_VERSION.minor,
             ),
-            &[(0, 1)],
+            &[(0, 3)],
         )
     }
 
     async fn init(&self, args: &ServerModuleInitArgs<Self>) -> anyhow::Result<DynServerModule> {
         for direction in ["incoming", "outgoing"] {
-            WALLET_INOUT_FEES_SATS
-                .with_label_values(&[direction])
-                .get_sample_count();
-            WALLET_INOUT_SATS
-                .with_label_values(&[direction])
-                .get_sample_count();
+            WALLET_INOUT_FEES_SATS.init(&[direction]);
+            WALLET_INOUT_SATS.init(&[direction]);
         }
         // Eagerly initialize metrics that trigger infrequently
-        WALLET_PEGIN_FEES_SATS.get_sample_count();
-        WALLET_PEGIN_SATS.get_sample_count();
-        WALLET_PEGOUT_SATS.get_sample_count();
-        WALLET_PEGOUT_FEES_SATS.get_sample_count();
+        WALLET_PEGIN_FEES_SATS.init(&[]);
+        WALLET_PEGIN_SATS.init(&[]);
+        WALLET_PEGOUT_SATS.init(&[]);
+        WALLET_PEGOUT_FEES_SATS.init(&[]);
 
         Ok(Wallet::new(
             args.cfg().to_typed()?,
@@ -327,6 +403,7 @@ fn trusted_dealer_gen(
                     params.local.bitcoin_rpc.clone(),
                     params.consensus.client_default_bitcoin_rpc.clone(),
                     params.consensus.fee_consensus,
+                    params.consensus.peg_in_confirmation_tiers.clone(),
                 );
                 (*id, cfg)
             })
@@ -363,6 +440,7 @@ async fn distributed_gen(
             params.local.bitcoin_rpc.clone(),
             params.consensus.client_default_bitcoin_rpc.clone(),
             params.consensus.fee_consensus,
+            params.consensus.peg_in_confirmation_tiers.clone(),
         );
 
         Ok(wallet_cfg.to_erased())
@@ -396,6 +474,7 @@ fn get_client_config(
             fee_consensus: config.fee_consensus,
             finality_delay: config.finality_delay,
             default_bitcoin_rpc: config.client_default_bitcoin_rpc,
+            peg_in_confirmation_tiers: config.peg_in_confirmation_tiers,
         })
     }
 
@@ -405,6 +484,30 @@ fn get_database_migrations(&self) -> BTreeMap<DatabaseVersion, CoreMigrationFn>
         migrations.insert(DatabaseVersion(0), |ctx| migrate_to_v1(ctx).boxed());
         migrations
     }
+
+    fn consensus_db_prefixes(&self) -> Option<Vec<u8>> {
+        // Our own peg-out signatures and whether our operator activated consensus
+        // version voting are local
+        Some(vec![
+            DbKeyPrefix::BlockHash as u8,
+            DbKeyPrefix::Utxo as u8,
+            DbKeyPrefix::BlockCountVote as u8,
+            DbKeyPrefix::FeeRateVote as u8,
+            DbKeyPrefix::UnsignedTransaction as u8,
+            DbKeyPrefix::PendingTransaction as u8,
+            DbKeyPrefix::PegOutBitcoinOutPoint as u8,
+            DbKeyPrefix::PegOutNonce as u8,
+            DbKeyPrefix::ClaimedPegInOutpoint as u8,
+            DbKeyPrefix::ConsensusVersionVote as u8,
+            DbKeyPrefix::UnspentTxOut as u8,
+            DbKeyPrefix::PegOutSigningSession as u8,
+            DbKeyPrefix::UnspentTxOutHeight as u8,
+            DbKeyPrefix::BlockHashByHeight as u8,
+            DbKeyPrefix::ClaimedPegInBlock as u8,
+            DbKeyPrefix::ReorgedBlockVote as u8,
+            DbKeyPrefix::ReorgedBlock as u8,
+        ])
+    }
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -453,7 +556,7 @@ async fn consensus_proposal<'a>(
                     "Proposing block count"
                 );
 
-                WALLET_BLOCK_COUNT.set(i64::from(block_count_vote));
+                WALLET_BLOCK_COUNT.set(&[], i64::from(block_count_vote));
                 items.push(WalletConsensusItem::BlockCount(block_count_vote));
             }
             Err(err) => {
@@ -465,6 +568,24 @@ async fn consensus_proposal<'a>(
 
         items.push(WalletConsensusItem::Feerate(fee_rate_proposal));
 
+        if self.consensus_module_consensus_version(dbtx).await
+            >= PEG_IN_REORG_TRACKING_CONSENSUS_VERSION
+        {
+            let reorged_blocks = self.peg_in_reorgs_rx.borrow().reorged_blocks.clone();
+            for reorged_block in reorged_blocks {
+                if dbtx
+                    .get_value(&ReorgedBlockVoteKey(
+                        reorged_block.block_hash,
+                        self.our_peer_id,
+                    ))
+                    .await
+                    .is_none()
+                {
+                    items.push(WalletConsensusItem::ReorgedBlock(reorged_block.block_hash));
+                }
+            }
+        }
+
         if dbtx
             .get_value(&ConsensusVersionVotingActivationKey)
             .await
@@ -556,12 +677,30 @@ async fn process_consensus_item<'a, 'b>(
                     .await
                     .context("Unsigned transaction does not exist")?;
 
+                let session = if self.consensus_module_consensus_version(dbtx).await
+                    >= PEG_OUT_SIGNING_SESSIONS_CONSENSUS_VERSION
+                {
+                    let mut session = match dbtx.get_value(&PegOutSigningSessionKey(txid)).await {
+                        Some(session) => session,
+                        None => PegOutSigningSession::new(self.consensus_block_count(dbtx).await),
+                    };
+                    session.add_signer(peer)?;
+                    Some(session)
+                } else {
+                    None
+                };
+
                 self.sign_peg_out_psbt(&mut unsigned.psbt, peer, &peg_out_signature)
                     .context("Peg out signature is invalid")?;
 
                 dbtx.insert_entry(&UnsignedTransactionKey(txid), &unsigned)
                     .await;
 
+                if let Some(session) = session {
+                    dbtx.insert_entry(&PegOutSigningSessionKey(txid), &session)
+                        .await;
+                }
+
                 if let Ok(pending_tx) = self.finalize_peg_out_psbt(unsigned) {
                     // We were able to finalize the transaction, so we will delete the
                     // PSBT and instead keep the extracted tx for periodic transmission
@@ -572,6 +711,7 @@ async fn process_consensus_item<'a, 'b>(
 
                     dbtx.remove_entry(&PegOutTxSignatureCI(txid)).await;
                     dbtx.remove_entry(&UnsignedTransactionKey(txid)).await;
+                    dbtx.remove_entry(&PegOutSigningSessionKey(txid)).await;
                 }
             }
             WalletConsensusItem::ModuleConsensusVersion(module_consensus_version) => {
@@ -593,6 +733,51 @@ async fn process_consensus_item<'a, 'b>(
                     "Wallet module does not support new consensus version, please upgrade the module"
                 );
             }
+            WalletConsensusItem::ReorgedBlock(block_hash) => {
+                ensure!(
+                    self.consensus_module_consensus_version(dbtx).await
+                        >= PEG_IN_REORG_TRACKING_CONSENSUS_VERSION,
+                    "Reorged block votes are not supported by the consensus version yet"
+                );
+                ensure!(
+                    self.block_is_known(dbtx, block_hash).await,
+                    "Reorged block was never synced"
+                );
+                ensure!(
+                    dbtx.insert_entry(&ReorgedBlockVoteKey(block_hash, peer), &())
+                        .await
+                        .is_none(),
+                    "Reorged block vote is redundant"
+                );
+
+                let votes = dbtx
+                    .find_by_prefix(&ReorgedBlockVoteBlockPrefix(block_hash))
+                    .await
+                    .count()
+                    .await;
+
+                // Once a guardian that is not faulty saw the block being reorged out,
+                // claiming peg-ins confirmed in it is paused
+                let max_evil = self
+                    .cfg
+                    .consensus
+                    .peer_peg_in_keys
+                    .to_num_peers()
+                    .max_evil();
+                if votes > max_evil
+                    && dbtx
+                        .insert_entry(&ReorgedBlockKey(block_hash), &())
+                        .await
+                        .is_none()
+                {
+                    error!(
+                        target: LOG_MODULE_WALLET,
+                        %block_hash,
+                        votes,
+                        "Synced block was reorged out, paused claiming peg-ins confirmed in it"
+                    );
+                }
+            }
             WalletConsensusItem::Default { variant, .. } => {
                 panic!("Received wallet consensus item with unknown variant {variant}");
             }
@@ -606,7 +791,7 @@ async fn process_input<'a, 'b, 'c>(
         dbtx: &mut DatabaseTransaction<'c>,
         input: &'b WalletInput,
     ) -> Result<InputMeta, WalletInputError> {
-        let (outpoint, value, pub_key) = match input {
+        let (outpoint, value, pub_key, block_hash) = match input {
             WalletInput::V0(input) => {
                 if !self.block_is_known(dbtx, input.proof_block()).await {
                     return Err(WalletInputError::UnknownPegInProofBlock(
@@ -622,6 +807,7 @@ async fn process_input<'a, 'b, 'c>(
                     input.0.outpoint(),
                     input.tx_output().value,
                     *input.tweak_contract_key(),
+                    Some(input.proof_block()),
                 )
             }
             WalletInput::V1(input) => {
@@ -647,7 +833,18 @@ async fn process_input<'a, 'b, 'c>(
                     return Err(WalletInputError::WrongTxOut);
                 }
 
-                (input.outpoint, input_tx_out.value, input.tweak_contract_key)
+                let block_hash = match dbtx.get_value(&UnspentTxOutHeightKey(input.outpoint)).await
+                {
+                    Some(height) => dbtx.get_value(&BlockHashByHeightKey(height)).await,
+                    None => None,
+                };
+
+                (
+                    input.outpoint,
+                    input_tx_out.value,
+                    input.tweak_contract_key,
+                    block_hash,
+                )
             }
             WalletInput::Default { variant, .. } => {
                 return Err(WalletInputError::UnknownInputVariant(
@@ -656,6 +853,15 @@ async fn process_input<'a, 'b, 'c>(
             }
         };
 
+        self.check_peg_in_confirmations(dbtx, outpoint, value)
+            .await?;
+
+        if let Some(block_hash) = block_hash {
+            if dbtx.get_value(&ReorgedBlockKey(block_hash)).await.is_some() {
+                return Err(WalletInputError::PegInBlockReorged(block_hash));
+            }
+        }
+
         if dbtx
             .insert_entry(&ClaimedPegInOutpointKey(outpoint), &())
             .await
@@ -664,6 +870,15 @@ async fn process_input<'a, 'b, 'c>(
             return Err(WalletInputError::PegInAlreadyClaimed);
         }
 
+        if let Some(block_hash) = block_hash {
+            if self.consensus_module_consensus_version(dbtx).await
+                >= PEG_IN_REORG_TRACKING_CONSENSUS_VERSION
+            {
+                dbtx.insert_new_entry(&ClaimedPegInBlockKey(outpoint), &block_hash)
+                    .await;
+            }
+        }
+
         dbtx.insert_new_entry(
             &UTXOKey(outpoint),
             &SpendableUTXO {
@@ -764,6 +979,17 @@ async fn process_output<'a, 'b>(
         dbtx.insert_new_entry(&PegOutTxSignatureCI(txid), &sigs)
             .await;
 
+        if self.consensus_module_consensus_version(dbtx).await
+            >= PEG_OUT_SIGNING_SESSIONS_CONSENSUS_VERSION
+        {
+            let block_count = self.consensus_block_count(dbtx).await;
+            dbtx.insert_new_entry(
+                &PegOutSigningSessionKey(txid),
+                &PegOutSigningSession::new(block_count),
+            )
+            .await;
+        }
+
         dbtx.insert_new_entry(
             &PegOutBitcoinTransaction(out_point),
             &WalletOutputOutcome::new_v0(txid),
@@ -922,6 +1148,22 @@ fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
                     Ok(module.get_wallet_summary(&mut context.dbtx().into_nc()).await)
                 }
             },
+            api_endpoint! {
+                PEG_OUT_SIGNING_SESSIONS_ENDPOINT,
+                ApiVersion::new(0, 2),
+                async |module: &Wallet, context, _params: ()| -> Vec<PegOutSigningSessionStatus> {
+                    check_auth(context)?;
+                    Ok(module.peg_out_signing_sessions(&mut context.dbtx().into_nc()).await)
+                }
+            },
+            api_endpoint! {
+                PEG_IN_REORG_ALERTS_ENDPOINT,
+                ApiVersion::new(0, 3),
+                async |module: &Wallet, context, _params: ()| -> Vec<PegInReorgAlert> {
+                    check_auth(context)?;
+                    Ok(module.peg_in_reorgs_rx.borrow().alerts.clone())
+                }
+            },
         ]
     }
 }
@@ -932,14 +1174,10 @@ fn calculate_pegin_metrics(
     fee: fedimint_core::Amount,
 ) {
     dbtx.on_commit(move || {
-        WALLET_INOUT_SATS
-            .with_label_values(&["incoming"])
-            .observe(amount.sats_f64());
-        WALLET_INOUT_FEES_SATS
-            .with_label_values(&["incoming"])
-            .observe(fee.sats_f64());
-        WALLET_PEGIN_SATS.observe(amount.sats_f64());
-        WALLET_PEGIN_FEES_SATS.observe(fee.sats_f64());
+        WALLET_INOUT_SATS.observe(&["incoming"], amount.sats_f64());
+        WALLET_INOUT_FEES_SATS.observe(&["incoming"], fee.sats_f64());
+        WALLET_PEGIN_SATS.observe(&[], amount.sats_f64());
+        WALLET_PEGIN_FEES_SATS.observe(&[], fee.sats_f64());
     });
 }
 
@@ -949,14 +1187,10 @@ fn calculate_pegout_metrics(
     fee: fedimint_core::Amount,
 ) {
     dbtx.on_commit(move || {
-        WALLET_INOUT_SATS
-            .with_label_values(&["outgoing"])
-            .observe(amount.sats_f64());
-        WALLET_INOUT_FEES_SATS
-            .with_label_values(&["outgoing"])
-            .observe(fee.sats_f64());
-        WALLET_PEGOUT_SATS.observe(amount.sats_f64());
-        WALLET_PEGOUT_FEES_SATS.observe(fee.sats_f64());
+        WALLET_INOUT_SATS.observe(&["outgoing"], amount.sats_f64());
+        WALLET_INOUT_FEES_SATS.observe(&["outgoing"], fee.sats_f64());
+        WALLET_PEGOUT_SATS.observe(&[], amount.sats_f64());
+        WALLET_PEGOUT_FEES_SATS.observe(&[], fee.sats_f64());
     });
 }
 
@@ -970,6 +1204,10 @@ pub struct Wallet {
     block_count_rx: watch::Receiver<Option<u32>>,
     /// Fee rate updated periodically by a background task
     fee_rate_rx: watch::Receiver<Feerate>,
+    /// Synced blocks that were reorged out of the chain of our bitcoin backend
+    /// and the claimed peg-ins confirmed in them, updated periodically by a
+    /// background task
+    peg_in_reorgs_rx: watch::Receiver<PegInReorgs>,
     task_group: TaskGroup,
 }
 
@@ -993,6 +1231,8 @@ pub async fn new_with_bitcoind(
     ) -> Result<Wallet, WalletCreationError> {
         Self::spawn_broadcast_pending_task(task_group, &bitcoind, db);
 
+        let peg_in_reorgs_rx = Self::spawn_peg_in_reorg_watch_task(task_group, &bitcoind, db);
+
         let (block_count_rx, fee_rate_rx) =
             Self::spawn_bitcoin_update_task(&cfg, task_group, &bitcoind)
                 .map_err(|e| WalletCreationError::FeerateSourceError(e.to_string()))?;
@@ -1019,12 +1259,45 @@ pub async fn new_with_bitcoind(
             our_peer_id,
             block_count_rx,
             fee_rate_rx,
+            peg_in_reorgs_rx,
             task_group: task_group.clone(),
         };
 
         Ok(wallet)
     }
 
+    /// Signing progress of all peg-out transactions that didn't collect a
+    /// threshold of signatures yet
+    pub async fn peg_out_signing_sessions(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+    ) -> Vec<PegOutSigningSessionStatus> {
+        let block_count = self.consensus_block_count(dbtx).await;
+
+        let txids = dbtx
+            .find_by_prefix(&UnsignedTransactionPrefixKey)
+            .await
+            .map(|(UnsignedTransactionKey(txid), _)| txid)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut sessions = Vec::with_capacity(txids.len());
+        for txid in txids {
+            let session = dbtx
+                .get_value(&PegOutSigningSessionKey(txid))
+                .await
+                .unwrap_or_else(|| PegOutSigningSession::new(block_count));
+
+            sessions.push(PegOutSigningSessionStatus {
+                txid,
+                started_at_block_count: session.started_at_block_count,
+                missing: session.missing_signers(self.cfg.consensus.peer_peg_in_keys.keys()),
+                signed_by: session.signed_by,
+            });
+        }
+        sessions
+    }
+
     /// Try to attach signatures to a pending peg-out tx.
     fn sign_peg_out_psbt(
         &self,
@@ -1261,6 +1534,8 @@ async fn sync_up_to_consensus_count<'a>(
                     for tx_in in &transaction.input {
                         dbtx.remove_entry(&UnspentTxOutKey(tx_in.previous_output))
                             .await;
+                        dbtx.remove_entry(&UnspentTxOutHeightKey(tx_in.previous_output))
+                            .await;
                     }
 
                     for (vout, tx_out) in transaction.output.iter().enumerate() {
@@ -1276,6 +1551,8 @@ async fn sync_up_to_consensus_count<'a>(
 
                             dbtx.insert_new_entry(&UnspentTxOutKey(outpoint), tx_out)
                                 .await;
+                            dbtx.insert_new_entry(&UnspentTxOutHeightKey(outpoint), &height)
+                                .await;
                         }
                     }
                 }
@@ -1324,6 +1601,12 @@ async fn sync_up_to_consensus_count<'a>(
             }
 
             dbtx.insert_new_entry(&BlockHashKey(block_hash), &()).await;
+            if self.consensus_module_consensus_version(dbtx).await
+                >= PEG_IN_REORG_TRACKING_CONSENSUS_VERSION
+            {
+                dbtx.insert_new_entry(&BlockHashByHeightKey(height), &block_hash)
+                    .await;
+            }
         }
     }
 
@@ -1397,6 +1680,40 @@ async fn remove_rbf_transactions<'a>(
         }
     }
 
+    /// Checks that a peg-in of `value` has the confirmations its
+    /// confirmation tier requires. The federation only syncs blocks that are
+    /// `finality_delay` deep, so only confirmations beyond that need to be
+    /// checked.
+    async fn check_peg_in_confirmations(
+        &self,
+        dbtx: &mut DatabaseTransaction<'_>,
+        outpoint: bitcoin::OutPoint,
+        value: bitcoin::Amount,
+    ) -> Result<(), WalletInputError> {
+        let required = self.cfg.consensus.required_peg_in_confirmations(value);
+        let extra_blocks = required.saturating_sub(self.cfg.consensus.finality_delay);
+        if extra_blocks == 0 {
+            return Ok(());
+        }
+
+        // Heights are only recorded for outputs synced since tiers were introduced,
+        // so outputs synced before can't be checked
+        let Some(height) = dbtx.get_value(&UnspentTxOutHeightKey(outpoint)).await else {
+            return Ok(());
+        };
+
+        let required_block_count = u64::from(height) + 1 + u64::from(extra_blocks);
+        let consensus_block_count = u64::from(self.consensus_block_count(dbtx).await);
+        if consensus_block_count < required_block_count {
+            return Err(WalletInputError::NotEnoughConfirmations {
+                required,
+                remaining: required_block_count - consensus_block_count,
+            });
+        }
+
+        Ok(())
+    }
+
     async fn block_is_known(
         &self,
         dbtx: &mut DatabaseTransaction<'_>,
@@ -1559,6 +1876,79 @@ fn spawn_broadcast_pending_task(
         });
     }
 
+    /// Periodically checks whether the blocks synced by the federation are
+    /// still part of the chain of our bitcoin backend.
+    ///
+    /// The federation only syncs blocks that are `finality_delay` deep, so this
+    /// should never happen. If it does, we vote to pause claiming the peg-ins
+    /// confirmed in the reorged blocks. The e-cash already issued for claimed
+    /// peg-ins may not be backed anymore, which can't be undone automatically
+    /// and needs to be investigated by the guardians.
+    fn spawn_peg_in_reorg_watch_task(
+        task_group: &TaskGroup,
+        bitcoind: &DynBitcoindRpc,
+        db: &Database,
+    ) -> watch::Receiver<PegInReorgs> {
+        let (reorgs_tx, reorgs_rx) = watch::channel(PegInReorgs::default());
+
+        let mut interval = tokio::time::interval(if is_running_in_test_env() {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_secs(60)
+        });
+
+        task_group.spawn_cancellable("wallet module: peg-in reorg watch", {
+            let bitcoind = bitcoind.clone();
+            let db = db.clone();
+            async move {
+                // A reorg always changes the chain tip, so we only check again once it does
+                let mut last_tip = None;
+                loop {
+                    interval.tick().await;
+
+                    let reorgs = match check_peg_in_reorgs(&db, &bitcoind, &mut last_tip).await {
+                        Ok(Some(reorgs)) => reorgs,
+                        Ok(None) => continue,
+                        Err(err) => {
+                            warn!(target: LOG_MODULE_WALLET, %err, "Unable to check synced blocks for reorgs");
+                            continue;
+                        }
+                    };
+
+                    for reorged_block in &reorgs.reorged_blocks {
+                        if !reorgs_tx.borrow().reorged_blocks.contains(reorged_block) {
+                            error!(
+                                target: LOG_MODULE_WALLET,
+                                height = reorged_block.height,
+                                block_hash = %reorged_block.block_hash,
+                                current_block_hash = %reorged_block.current_block_hash,
+                                "Synced block was reorged out, voting to pause claiming peg-ins confirmed in it"
+                            );
+                        }
+                    }
+
+                    for alert in &reorgs.alerts {
+                        if !reorgs_tx.borrow().alerts.contains(alert) {
+                            error!(
+                                target: LOG_MODULE_WALLET,
+                                outpoint = %alert.outpoint,
+                                height = alert.height,
+                                block_hash = %alert.block_hash,
+                                current_block_hash = %alert.current_block_hash,
+                                "Block of a claimed peg-in was reorged out, the issued e-cash may not be backed"
+                            );
+                        }
+                    }
+
+                    WALLET_PEGINS_AT_RISK.set(&[], reorgs.alerts.len() as i64);
+                    reorgs_tx.send_replace(reorgs);
+                }
+            }
+        });
+
+        reorgs_rx
+    }
+
     fn spawn_bitcoin_update_task(
         cfg: &WalletConfig,
         task_group: &TaskGroup,
@@ -1711,6 +2101,119 @@ async fn wait_for_finality_confs_or_shutdown(&self, consensus_block_count: u32)
     }
 }
 
+/// Blocks synced by the federation that were reorged out of the chain of our
+/// bitcoin backend and the claimed peg-ins confirmed in them
+#[derive(Debug, Clone, Default)]
+struct PegInReorgs {
+    reorged_blocks: Vec<ReorgedBlock>,
+    alerts: Vec<PegInReorgAlert>,
+}
+
+/// A block synced by the federation that is no longer part of the chain of
+/// our bitcoin backend
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ReorgedBlock {
+    height: u32,
+    block_hash: BlockHash,
+    current_block_hash: BlockHash,
+}
+
+/// Checks the synced blocks and the claimed peg-ins for reorgs, returns `None`
+/// if the tip of our chain is still `last_tip`
+async fn check_peg_in_reorgs(
+    db: &Database,
+    rpc: &DynBitcoindRpc,
+    last_tip: &mut Option<BlockHash>,
+) -> anyhow::Result<Option<PegInReorgs>> {
+    let block_count = u32::try_from(rpc.get_block_count().await?)?;
+    let Some(tip_height) = block_count.checked_sub(1) else {
+        return Ok(None);
+    };
+    let tip = rpc.get_block_hash(u64::from(tip_height)).await?;
+    if *last_tip == Some(tip) {
+        return Ok(None);
+    }
+
+    let mut dbtx = db.begin_transaction_nc().await;
+    let reorged_blocks = find_reorged_blocks(&mut dbtx, block_count, |height| {
+        rpc.get_block_hash(u64::from(height))
+    })
+    .await?;
+    let alerts = find_reorged_peg_ins(&mut dbtx, &reorged_blocks).await;
+    *last_tip = Some(tip);
+
+    Ok(Some(PegInReorgs {
+        reorged_blocks,
+        alerts,
+    }))
+}
+
+/// Walks down from the highest block synced by the federation within
+/// [`PEG_IN_REORG_WATCH_BLOCKS`] of `block_count` and returns the blocks that
+/// differ from the ones in our chain now, stopping at the first one that still
+/// matches as the blocks below it can't have been reorged out either.
+///
+/// Heights our chain doesn't have yet aren't checked, our bitcoin backend may
+/// just be behind.
+async fn find_reorged_blocks<F, Fut>(
+    dbtx: &mut DatabaseTransaction<'_>,
+    block_count: u32,
+    mut get_block_hash: F,
+) -> anyhow::Result<Vec<ReorgedBlock>>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = anyhow::Result<BlockHash>>,
+{
+    let min_height = block_count.saturating_sub(PEG_IN_REORG_WATCH_BLOCKS);
+
+    let mut reorged_blocks = vec![];
+    for height in (min_height..block_count).rev() {
+        let Some(block_hash) = dbtx.get_value(&BlockHashByHeightKey(height)).await else {
+            continue;
+        };
+
+        let current_block_hash = get_block_hash(height).await?;
+        if current_block_hash == block_hash {
+            break;
+        }
+
+        reorged_blocks.push(ReorgedBlock {
+            height,
+            block_hash,
+            current_block_hash,
+        });
+    }
+
+    Ok(reorged_blocks)
+}
+
+/// Returns the claimed peg-ins confirmed in one of the `reorged_blocks`
+async fn find_reorged_peg_ins(
+    dbtx: &mut DatabaseTransaction<'_>,
+    reorged_blocks: &[ReorgedBlock],
+) -> Vec<PegInReorgAlert> {
+    if reorged_blocks.is_empty() {
+        return vec![];
+    }
+
+    dbtx.find_by_prefix(&ClaimedPegInBlockPrefix)
+        .await
+        .filter_map(|(key, block_hash)| async move {
+            let reorged_block = reorged_blocks
+                .iter()
+                .find(|reorged_block| reorged_block.block_hash == block_hash)?;
+
+            Some(PegInReorgAlert {
+                outpoint: key.0,
+                height: reorged_block.height,
+                block_hash,
+                current_block_hash: reorged_block.current_block_hash,
+            })
+        })
+        .collect()
+        .await
+}
+
 #[instrument(level = "debug", skip_all)]
 pub async fn run_broadcast_pending_tx(db: Database, rpc: DynBitcoindRpc, tg_handle: &TaskHandle) {
     while !tg_handle.is_shutting_down() {
@@ -2176,15 +2679,20 @@ mod tests {
 
     use bitcoin::hashes::Hash;
     use bitcoin::Network::{Bitcoin, Testnet};
-    use bitcoin::{secp256k1, Address, Amount, OutPoint, Txid};
+    use bitcoin::{secp256k1, Address, Amount, BlockHash, OutPoint, Txid};
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
     use fedimint_core::encoding::btc::NetworkLegacyEncodingWrapper;
+    use fedimint_core::module::registry::ModuleRegistry;
     use fedimint_core::Feerate;
     use fedimint_wallet_common::{PegOut, PegOutFees, Rbf, WalletOutputV0};
     use miniscript::descriptor::Wsh;
 
     use crate::common::PegInDescriptor;
+    use crate::db::{BlockHashByHeightKey, ClaimedPegInBlockKey, PegOutSigningSession};
     use crate::{
-        CompressedPublicKey, OsRng, SpendableUTXO, StatelessWallet, UTXOKey, WalletOutputError,
+        find_reorged_blocks, find_reorged_peg_ins, CompressedPublicKey, OsRng, PeerId,
+        PegInReorgAlert, ReorgedBlock, SpendableUTXO, StatelessWallet, UTXOKey, WalletOutputError,
     };
 
     #[test]
@@ -2292,10 +2800,117 @@ fn create_tx_should_validate_amounts() {
         );
     }
 
+    #[test]
+    fn peg_out_signing_session_tracks_signers() {
+        let peers = (0..4).map(PeerId::from).collect::<Vec<_>>();
+        let mut session = PegOutSigningSession::new(100);
+
+        assert_eq!(
+            session.missing_signers(&peers),
+            peers.iter().copied().collect()
+        );
+
+        session.add_signer(peers[1]).expect("first signature");
+        session.add_signer(peers[3]).expect("first signature");
+        assert!(session.add_signer(peers[1]).is_err());
+
+        assert_eq!(
+            session.missing_signers(&peers),
+            [peers[0], peers[2]].into_iter().collect()
+        );
+        assert_eq!(
+            session.signed_by,
+            [peers[1], peers[3]].into_iter().collect()
+        );
+    }
+
     fn rbf(sats_per_kvb: u64, total_weight: u64) -> WalletOutputV0 {
         WalletOutputV0::Rbf(Rbf {
             fees: PegOutFees::new(sats_per_kvb, total_weight),
             txid: Txid::all_zeros(),
         })
     }
+
+    fn block_hash(n: u32) -> BlockHash {
+        BlockHash::from_byte_array([n as u8; 32])
+    }
+
+    #[tokio::test]
+    async fn finds_reorged_blocks_down_to_the_fork() {
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction_nc().await;
+
+        // The federation synced the blocks up to height 15, our chain forked off
+        // after height 12 and doesn't have a block at height 15 yet
+        for height in 10..=15 {
+            dbtx.insert_entry(&BlockHashByHeightKey(height), &block_hash(height))
+                .await;
+        }
+        let reorged_blocks = find_reorged_blocks(&mut dbtx, 15, |height| async move {
+            Ok(if height <= 12 {
+                block_hash(height)
+            } else {
+                block_hash(100 + height)
+            })
+        })
+        .await
+        .expect("bitcoin backend is reachable");
+
+        assert_eq!(
+            reorged_blocks,
+            vec![
+                ReorgedBlock {
+                    height: 14,
+                    block_hash: block_hash(14),
+                    current_block_hash: block_hash(114),
+                },
+                ReorgedBlock {
+                    height: 13,
+                    block_hash: block_hash(13),
+                    current_block_hash: block_hash(113),
+                },
+            ]
+        );
+
+        let claimed_peg_in = |vout| OutPoint {
+            txid: Txid::all_zeros(),
+            vout,
+        };
+        dbtx.insert_entry(&ClaimedPegInBlockKey(claimed_peg_in(0)), &block_hash(13))
+            .await;
+        dbtx.insert_entry(&ClaimedPegInBlockKey(claimed_peg_in(1)), &block_hash(12))
+            .await;
+
+        assert_eq!(
+            find_reorged_peg_ins(&mut dbtx, &reorged_blocks).await,
+            vec![PegInReorgAlert {
+                outpoint: claimed_peg_in(0),
+                height: 13,
+                block_hash: block_hash(13),
+                current_block_hash: block_hash(113),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_first_block_still_in_our_chain() {
+        let db = Database::new(MemDatabase::new(), ModuleRegistry::default());
+        let mut dbtx = db.begin_transaction_nc().await;
+
+        for height in 10..=15 {
+            dbtx.insert_entry(&BlockHashByHeightKey(height), &block_hash(height))
+                .await;
+        }
+
+        let mut fetched_heights = vec![];
+        let reorged_blocks = find_reorged_blocks(&mut dbtx, 20, |height| {
+            fetched_heights.push(height);
+            async move { Ok(block_hash(height)) }
+        })
+        .await
+        .expect("bitcoin backend is reachable");
+
+        assert!(reorged_blocks.is_empty());
+        assert_eq!(fetched_heights, vec![15]);
+    }
 }
@@ -235,15 +235,27 @@ async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
         .await;
 
     info!("Waiting for confirmation");
-    assert!(matches!(
-        deposit_updates.next().await.unwrap(),
-        DepositStateV2::WaitingForConfirmation { btc_out_point, .. } if btc_out_point.txid == tx.compute_txid()
-    ));
+    let progress = match deposit_updates.next().await.unwrap() {
+        DepositStateV2::WaitingForConfirmation {
+            btc_out_point,
+            progress,
+            ..
+        } => {
+            assert_eq!(btc_out_point.txid, tx.compute_txid());
+            progress.expect("Progress of the mined deposit is known")
+        }
+        update => bail!("Unexpected update {update:?}"),
+    };
+    assert!(progress.seen_height.is_some());
+    assert_eq!(u64::from(progress.required_confirmations), finality_delay);
+    assert!(progress.confirmations < progress.required_confirmations);
+    assert!(0 < progress.eta_secs);
 
     bitcoin.mine_blocks(finality_delay).await;
 
     // Afaik technically not necessary, but useful to speed up test (should probably
     // just poll more often in tests?)
+    let mut progress_updates = vec![progress];
     let await_update_while_rechecking = async {
         loop {
             wallet_module
@@ -252,6 +264,11 @@ async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
                 .expect("Operation exists");
             select! {
                 update = deposit_updates.next() => {
+                    // Progress updates while the deposit confirms
+                    if let Some(DepositStateV2::WaitingForConfirmation { progress, .. }) = update {
+                        progress_updates.extend(progress);
+                        continue;
+                    }
                     break update;
                 },
                 _ = sleep_in_test("Waiting for address recheck", Duration::from_millis(100)) => { }
@@ -264,6 +281,12 @@ async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
         await_update_while_rechecking.await.unwrap(),
         DepositStateV2::Confirmed { btc_out_point, .. } if btc_out_point.txid == tx.compute_txid()
     ));
+    // The deposit only gains confirmations and gets closer to being claimable
+    assert!(progress_updates.windows(2).all(|updates| {
+        updates[0].seen_height == updates[1].seen_height
+            && updates[0].confirmations <= updates[1].confirmations
+            && updates[1].eta_secs <= updates[0].eta_secs
+    }));
 
     info!("Waiting for e-cash");
     assert!(matches!(
@@ -985,6 +1008,7 @@ fn build_wallet_server_configs(
                 finality_delay: 10,
                 client_default_bitcoin_rpc: bitcoin_rpc.clone(),
                 fee_consensus: Default::default(),
+                peg_in_confirmation_tiers: vec![],
             },
         })?,
     );
@@ -1435,6 +1459,13 @@ async fn test_server_db_migrations() -> anyhow::Result<()> {
                             );
                             info!("Validated ConsensusVersionVotingActivation");
                         }
+                        // Added after the snapshot was taken
+                        DbKeyPrefix::PegOutSigningSession
+                        | DbKeyPrefix::UnspentTxOutHeight
+                        | DbKeyPrefix::BlockHashByHeight
+                        | DbKeyPrefix::ClaimedPegInBlock
+                        | DbKeyPrefix::ReorgedBlockVote
+                        | DbKeyPrefix::ReorgedBlock => {}
                     }
                 }
                 Ok(())
@@ -1476,6 +1507,8 @@ async fn test_client_db_migrations() -> anyhow::Result<()> {
                         client_db::DbKeyPrefix::ClaimedPegIn => {}
                         client_db::DbKeyPrefix::RecoveryFinalized => {}
                         client_db::DbKeyPrefix::RecoveryState => {}
+                        client_db::DbKeyPrefix::PegInBlock => {}
+                        client_db::DbKeyPrefix::ReorgedPegIn => {}
                         client_db::DbKeyPrefix::ExternalReservedStart
                         | client_db::DbKeyPrefix::CoreInternalReservedStart
                         | client_db::DbKeyPrefix::CoreInternalReservedEnd => {}